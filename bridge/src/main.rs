@@ -1,9 +1,20 @@
 use chrono::Local;
 use clap::Parser;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, RandomState};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::time::{self, Duration};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Notify, Semaphore};
+use tokio::time::{self, Duration, Instant};
+
+mod metrics;
+mod systemd;
+mod template;
 
 // ── Logging ─────────────────────────────────────────────────────────
 
@@ -27,7 +38,7 @@ macro_rules! log_global {
     about = "Lightweight OpenGate agent polling daemon"
 )]
 struct Cli {
-    /// Path to TOML config file
+    /// Path to TOML config file (re-read on SIGHUP)
     #[arg(short, long, env = "OPENGATE_BRIDGE_CONFIG")]
     config: PathBuf,
 
@@ -35,9 +46,19 @@ struct Cli {
     #[arg(long)]
     once: bool,
 
+    /// Validate the config, key files and each agent's API key, then exit (non-zero on
+    /// any problem) without polling or waking anyone
+    #[arg(long, conflicts_with = "once")]
+    check: bool,
+
     /// Only process this agent (by name)
     #[arg(long)]
     agent: Option<String>,
+
+    /// Serve per-agent counters on this address (`/metrics` for Prometheus, `/status`
+    /// as JSON)
+    #[arg(long, env = "OPENGATE_BRIDGE_METRICS_ADDR")]
+    metrics_addr: Option<SocketAddr>,
 }
 
 // ── Config ──────────────────────────────────────────────────────────
@@ -56,6 +77,13 @@ struct ServerConfig {
     heartbeat_interval: u64,
     #[serde(default = "default_poll_interval")]
     poll_interval: u64,
+    /// Longest wait between polls while the server is failing, in seconds
+    #[serde(default = "default_max_backoff")]
+    max_backoff: u64,
+    /// Most wakes running at once across all agents; unlimited when unset
+    max_concurrent_wakes: Option<usize>,
+    /// Registers agents whose `api_key_file` doesn't exist yet, writing the new key there
+    setup_token: Option<String>,
 }
 
 fn default_heartbeat_interval() -> u64 {
@@ -64,19 +92,65 @@ fn default_heartbeat_interval() -> u64 {
 fn default_poll_interval() -> u64 {
     60
 }
+fn default_max_backoff() -> u64 {
+    900
+}
 
 #[derive(Debug, Deserialize, Clone)]
 struct AgentConfig {
     name: String,
     api_key_file: String,
+    /// Sent when the agent is auto-registered
+    skills: Option<Vec<String>>,
+    /// Overrides `server.poll_interval` for this agent, in seconds
+    poll_interval: Option<u64>,
+    /// Overrides `server.heartbeat_interval` for this agent, in seconds
+    heartbeat_interval: Option<u64>,
     #[serde(default = "default_wake_mode")]
     wake_mode: WakeMode,
+    /// Attempts after a failed wake, waiting `wake_retry_delay` seconds and doubling
+    #[serde(default = "default_wake_retries")]
+    wake_retries: u32,
+    #[serde(default = "default_wake_retry_delay")]
+    wake_retry_delay: u64,
+    /// Longest one wake attempt may take, in seconds; a wake process still running then
+    /// is killed and the attempt counts as failed
+    #[serde(default = "default_wake_timeout")]
+    wake_timeout: u64,
+    /// Post an activity on the notifications' tasks when every attempt failed
+    #[serde(default)]
+    report_wake_failures: bool,
+    /// Replaces the fixed summary sent on wake: a Handlebars-style template over
+    /// `agent`, `count` and `notifications` (each with `task_url` when it has a task)
+    message_template: Option<String>,
+    /// Only notifications of these event types wake the agent (`question_*` matches a
+    /// prefix); every type does when unset. The rest stay unread for its next wake.
+    wake_on: Option<Vec<String>>,
     /// For openclaw wake mode
     openclaw_id: Option<String>,
     /// For webhook wake mode
     webhook_url: Option<String>,
     /// For command wake mode
     command: Option<String>,
+    /// For docker wake mode: the container to wake
+    container: Option<String>,
+    /// For docker wake mode: run this in the container with `docker exec` instead of
+    /// `docker start`ing it
+    docker_exec: Option<String>,
+    /// For kubernetes wake mode: path to a Job manifest (YAML or JSON)
+    job_template: Option<String>,
+    /// For kubernetes wake mode; kubectl's current namespace when unset
+    namespace: Option<String>,
+}
+
+fn default_wake_retries() -> u32 {
+    2
+}
+fn default_wake_retry_delay() -> u64 {
+    10
+}
+fn default_wake_timeout() -> u64 {
+    300
 }
 
 fn default_wake_mode() -> WakeMode {
@@ -89,6 +163,8 @@ enum WakeMode {
     Openclaw,
     Webhook,
     Command,
+    Docker,
+    Kubernetes,
     Stdout,
 }
 
@@ -96,29 +172,50 @@ enum WakeMode {
 
 #[derive(Debug, Deserialize)]
 struct Notification {
-    #[allow(dead_code)]
     id: serde_json::Value,
     #[serde(alias = "type", alias = "event_type")]
     notification_type: String,
     title: String,
     #[serde(alias = "message", alias = "body")]
     body: Option<String>,
+    task_id: Option<String>,
+    /// Servers without notification preferences omit this; treat as wake-worthy
+    #[serde(default = "default_wake")]
+    wake: bool,
+}
+
+fn default_wake() -> bool {
+    true
 }
 
 // WebhookPayload and NotificationSummary removed — webhook mode now uses simple JSON
 
 // ── Resolved agent (config with key loaded) ─────────────────────────
 
+#[derive(PartialEq)]
 struct ResolvedAgent {
     name: String,
     api_url: String,
     api_key: String,
     poll_interval: Duration,
     heartbeat_interval: Duration,
+    max_backoff: Duration,
     wake_mode: WakeMode,
+    wake_retries: u32,
+    wake_retry_delay: Duration,
+    wake_timeout: Duration,
+    report_wake_failures: bool,
+    /// Checked to parse when the config loads
+    message_template: Option<String>,
+    wake_on: Option<Vec<String>>,
     openclaw_id: Option<String>,
     webhook_url: Option<String>,
     command: Option<String>,
+    container: Option<String>,
+    docker_exec: Option<String>,
+    /// The manifest itself, so a reload picks up edits to it
+    job_template: Option<String>,
+    namespace: Option<String>,
 }
 
 impl ResolvedAgent {
@@ -160,26 +257,163 @@ impl ResolvedAgent {
                     cfg.name
                 ));
             }
+            WakeMode::Docker if cfg.container.is_none() => {
+                return Err(format!(
+                    "agent '{}': docker wake_mode requires container",
+                    cfg.name
+                ));
+            }
+            WakeMode::Kubernetes if cfg.job_template.is_none() => {
+                return Err(format!(
+                    "agent '{}': kubernetes wake_mode requires job_template",
+                    cfg.name
+                ));
+            }
             _ => {}
         }
 
+        let job_template = match &cfg.job_template {
+            Some(path) if cfg.wake_mode == WakeMode::Kubernetes => {
+                Some(std::fs::read_to_string(path).map_err(|e| {
+                    format!(
+                        "agent '{}': can't read job template '{}': {}",
+                        cfg.name, path, e
+                    )
+                })?)
+            }
+            _ => None,
+        };
+
+        if let Some(source) = &cfg.message_template {
+            template::Template::parse(source)
+                .map_err(|e| format!("agent '{}': message_template: {}", cfg.name, e))?;
+        }
+
+        let poll_interval = cfg.poll_interval.unwrap_or(server.poll_interval);
+        let heartbeat_interval = cfg.heartbeat_interval.unwrap_or(server.heartbeat_interval);
+        if poll_interval == 0 || heartbeat_interval == 0 {
+            return Err(format!(
+                "agent '{}': poll_interval and heartbeat_interval must be at least 1 second",
+                cfg.name
+            ));
+        }
+
         Ok(Self {
             name: cfg.name.clone(),
             api_url: server.url.trim_end_matches('/').to_string(),
             api_key,
-            poll_interval: Duration::from_secs(server.poll_interval),
-            heartbeat_interval: Duration::from_secs(server.heartbeat_interval),
+            poll_interval: Duration::from_secs(poll_interval),
+            heartbeat_interval: Duration::from_secs(heartbeat_interval),
+            max_backoff: Duration::from_secs(server.max_backoff.max(poll_interval)),
             wake_mode: cfg.wake_mode.clone(),
+            wake_retries: cfg.wake_retries,
+            wake_retry_delay: Duration::from_secs(cfg.wake_retry_delay.max(1)),
+            wake_timeout: Duration::from_secs(cfg.wake_timeout.max(1)),
+            report_wake_failures: cfg.report_wake_failures,
+            message_template: cfg.message_template.clone(),
+            wake_on: cfg.wake_on.clone(),
             openclaw_id: cfg.openclaw_id.clone(),
             webhook_url: cfg.webhook_url.clone(),
             command: cfg.command.clone(),
+            container: cfg.container.clone(),
+            docker_exec: cfg.docker_exec.clone(),
+            job_template,
+            namespace: cfg.namespace.clone(),
         })
     }
+
+    /// Whether `notification` justifies waking this agent.
+    fn wakes_for(&self, notification: &Notification) -> bool {
+        notification.wake
+            && self.wake_on.as_ref().is_none_or(|types| {
+                types.iter().any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => notification.notification_type.starts_with(prefix),
+                    None => notification.notification_type == *pattern,
+                })
+            })
+    }
+}
+
+// ── Backoff ─────────────────────────────────────────────────────────
+
+/// Exponential backoff with jitter while the server is failing: the wait doubles from
+/// the poll interval up to `max`, and each wait is drawn from its upper half so agents
+/// sharing a server don't retry in lockstep.
+struct Backoff {
+    base: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            failures: 0,
+        }
+    }
+
+    fn failing(&self) -> bool {
+        self.failures > 0
+    }
+
+    /// Record a failure and return how long to wait before trying again.
+    fn failure(&mut self) -> Duration {
+        self.failures += 1;
+        let ceiling = self
+            .base
+            .saturating_mul(2u32.saturating_pow(self.failures.min(16)))
+            .min(self.max);
+        let half = ceiling / 2;
+        let jitter = RandomState::new().hash_one(self.failures) % (half.as_millis() as u64 + 1);
+        half + Duration::from_millis(jitter)
+    }
+
+    /// Record a success; returns how many failures it ends, if any.
+    fn success(&mut self) -> Option<u32> {
+        let failures = std::mem::take(&mut self.failures);
+        (failures > 0).then_some(failures)
+    }
+}
+
+// ── Wakes ───────────────────────────────────────────────────────────
+
+/// One agent's wake in progress, kept across restarts so a loop restarted by a reload
+/// won't wake an agent whose previous wake is still running.
+#[derive(Default)]
+struct WakeState {
+    /// A wake is running or waiting for a slot
+    running: AtomicBool,
+    /// Notification ids the running wake was started for
+    woken: Mutex<HashSet<i64>>,
+    /// New notifications arrived during the wake; poll again as soon as it ends
+    queued: AtomicBool,
+    /// Tells the agent loop to poll now
+    repoll: Notify,
+}
+
+/// The configured limit and the semaphore enforcing it
+type WakeSlots = (Option<usize>, Option<Arc<Semaphore>>);
+
+fn wake_slots() -> &'static Mutex<WakeSlots> {
+    static SLOTS: OnceLock<Mutex<WakeSlots>> = OnceLock::new();
+    SLOTS.get_or_init(Default::default)
+}
+
+/// Cap the wakes running across all agents. Wakes already holding a slot under an
+/// older limit keep it until they finish.
+fn set_wake_limit(limit: Option<usize>) {
+    let mut slots = wake_slots().lock().unwrap();
+    if slots.0 != limit {
+        *slots = (limit, limit.map(|n| Arc::new(Semaphore::new(n.max(1)))));
+    }
 }
 
 // ── Core logic ──────────────────────────────────────────────────────
 
 async fn do_heartbeat(client: &reqwest::Client, agent: &ResolvedAgent) {
+    let stats = metrics::agent(&agent.name);
     let url = format!("{}/api/agents/heartbeat", agent.api_url);
     match client
         .post(&url)
@@ -189,29 +423,32 @@ async fn do_heartbeat(client: &reqwest::Client, agent: &ResolvedAgent) {
     {
         Ok(resp) if resp.status().is_success() => {
             log!(agent.name, "Heartbeat OK");
+            metrics::touch(&stats.last_heartbeat_success);
         }
         Ok(resp) => {
             log!(agent.name, "Heartbeat failed (HTTP {})", resp.status());
+            metrics::inc(&stats.heartbeat_failures);
         }
         Err(e) => {
             log!(agent.name, "Heartbeat error: {}", e);
+            metrics::inc(&stats.heartbeat_failures);
         }
     }
 }
 
-/// Track whether a wake process is already running for an agent.
-/// Uses an Arc<AtomicBool> per agent to prevent double-waking.
+/// Poll, and wake the agent when there's something for it. Notifications that arrive
+/// while a wake is running queue one more wake for when it ends.
+///
+/// `Err` means the server couldn't be reached or failed (5xx/429) — worth backing off
+/// for; anything else is logged here.
 async fn poll_and_wake(
     client: &reqwest::Client,
-    agent: &ResolvedAgent,
-    waking: &std::sync::Arc<std::sync::atomic::AtomicBool>,
-) {
-    // If a wake is already in progress, skip this poll cycle
-    if waking.load(std::sync::atomic::Ordering::Relaxed) {
-        return;
-    }
-
+    agent: &Arc<ResolvedAgent>,
+    wake: &Arc<WakeState>,
+) -> Result<(), String> {
     // Fetch unread notifications
+    let stats = metrics::agent(&agent.name);
+    metrics::inc(&stats.polls);
     let url = format!("{}/api/agents/me/notifications?unread=true", agent.api_url);
     let resp = match client
         .get(&url)
@@ -221,26 +458,63 @@ async fn poll_and_wake(
     {
         Ok(r) => r,
         Err(e) => {
-            log!(agent.name, "Poll error: {}", e);
-            return;
+            metrics::inc(&stats.poll_failures);
+            return Err(format!("Poll error: {}", e));
         }
     };
 
-    if !resp.status().is_success() {
-        log!(agent.name, "Poll failed (HTTP {})", resp.status());
-        return;
+    let status = resp.status();
+    if !status.is_success() {
+        metrics::inc(&stats.poll_failures);
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(format!("Poll failed (HTTP {})", status));
+        }
+        log!(agent.name, "Poll failed (HTTP {})", status);
+        return Ok(());
     }
 
-    let notifications: Vec<Notification> = match resp.json().await {
+    // Kept as sent, for command mode's stdin
+    let raw: Vec<serde_json::Value> = match resp.json().await {
+        Ok(n) => n,
+        Err(e) => {
+            log!(agent.name, "Failed to parse notifications: {}", e);
+            metrics::inc(&stats.poll_failures);
+            return Ok(());
+        }
+    };
+    let notifications: Vec<Notification> = match raw.iter().map(Notification::deserialize).collect()
+    {
         Ok(n) => n,
         Err(e) => {
             log!(agent.name, "Failed to parse notifications: {}", e);
-            return;
+            metrics::inc(&stats.poll_failures);
+            return Ok(());
         }
     };
+    stats.saw_notifications(notifications.iter().filter_map(|n| n.id.as_i64()));
 
-    if notifications.is_empty() {
-        return;
+    // Quiet or filtered-out notifications stay unread for the agent's next wake, but
+    // don't trigger one
+    let wakers: Vec<&Notification> = notifications
+        .iter()
+        .filter(|n| agent.wakes_for(n))
+        .collect();
+    if wakers.is_empty() {
+        return Ok(());
+    }
+    let wake_ids: HashSet<i64> = wakers.iter().filter_map(|n| n.id.as_i64()).collect();
+
+    if wake.running.load(Ordering::Relaxed) {
+        let woken = wake.woken.lock().unwrap();
+        let new = wake_ids.difference(&woken).count();
+        if new > 0 && !wake.queued.swap(true, Ordering::Relaxed) {
+            log!(
+                agent.name,
+                "{} new notification(s) during the running wake, queued",
+                new
+            );
+        }
+        return Ok(());
     }
 
     log!(
@@ -253,52 +527,152 @@ async fn poll_and_wake(
     // Fire-and-forget: spawn the wake as a background task.
     // The AGENT is responsible for acking its own notifications after processing.
     // Bridge only detects and wakes — it does NOT ack.
-    waking.store(true, std::sync::atomic::Ordering::Relaxed);
-    let name = agent.name.clone();
-    let wake_mode = agent.wake_mode.clone();
-    let openclaw_id = agent.openclaw_id.clone();
-    let webhook_url = agent.webhook_url.clone();
-    let command = agent.command.clone();
-    let summary = build_summary(&notifications);
-    let waking_flag = waking.clone();
+    wake.running.store(true, Ordering::Relaxed);
+    *wake.woken.lock().unwrap() = wake_ids;
+    let agent = agent.clone();
+    let summary = match &agent.message_template {
+        Some(source) => render_message(source, &agent, &raw),
+        None => build_summary(&notifications),
+    };
+    let mut task_ids: Vec<String> = wakers.iter().filter_map(|n| n.task_id.clone()).collect();
+    task_ids.sort();
+    task_ids.dedup();
+    let notifications_json = serde_json::Value::Array(raw).to_string();
+    let wake = wake.clone();
+    let client = client.clone();
+    metrics::inc(&stats.wakes_attempted);
 
     tokio::spawn(async move {
-        let ok = do_wake(
-            &name,
-            &wake_mode,
-            &openclaw_id,
-            &webhook_url,
-            &command,
-            &summary,
-        )
-        .await;
-        if !ok {
-            log!(name, "Wake failed");
+        match wake_with_retries(&agent, &summary, &notifications_json).await {
+            Ok(()) => {
+                metrics::inc(&stats.wakes_succeeded);
+                metrics::touch(&stats.last_wake_success);
+            }
+            Err(attempts) => {
+                log!(agent.name, "Wake failed after {} attempt(s)", attempts);
+                metrics::inc(&stats.wakes_failed);
+                if agent.report_wake_failures {
+                    report_wake_failure(&client, &agent, &task_ids, attempts).await;
+                }
+            }
+        }
+        wake.running.store(false, Ordering::Relaxed);
+        if wake.queued.swap(false, Ordering::Relaxed) {
+            wake.repoll.notify_one();
         }
-        waking_flag.store(false, std::sync::atomic::Ordering::Relaxed);
     });
+    Ok(())
 }
 
-/// Perform the actual wake call (extracted from wake_agent for spawned task use)
-async fn do_wake(
-    agent_name: &str,
-    wake_mode: &WakeMode,
-    openclaw_id: &Option<String>,
-    webhook_url: &Option<String>,
-    command: &Option<String>,
+/// Wake the agent, retrying up to `wake_retries` times with backoff from
+/// `wake_retry_delay`. On failure, returns how many attempts were made.
+async fn wake_with_retries(
+    agent: &ResolvedAgent,
     summary: &str,
-) -> bool {
-    match wake_mode {
+    notifications_json: &str,
+) -> Result<(), u32> {
+    let stats = metrics::agent(&agent.name);
+    let mut retry = Backoff::new(agent.wake_retry_delay, agent.max_backoff);
+    while !wake_in_slot(agent, summary, notifications_json).await {
+        if retry.failures >= agent.wake_retries {
+            return Err(retry.failures + 1);
+        }
+        let delay = retry.failure();
+        log!(
+            agent.name,
+            "Wake failed, retry {}/{} in {}s",
+            retry.failures,
+            agent.wake_retries,
+            delay.as_secs()
+        );
+        metrics::inc(&stats.wake_retries);
+        time::sleep(delay).await;
+    }
+    Ok(())
+}
+
+/// [`do_wake`] once a slot under the global wake limit is free, for at most
+/// `wake_timeout`. The slot is held only while the wake runs, not while it waits to retry.
+async fn wake_in_slot(agent: &ResolvedAgent, summary: &str, notifications_json: &str) -> bool {
+    let slots = wake_slots().lock().unwrap().1.clone();
+    let _permit = match &slots {
+        Some(slots) if slots.available_permits() == 0 => {
+            log!(agent.name, "Waiting for a wake slot");
+            slots.acquire().await.ok()
+        }
+        Some(slots) => slots.acquire().await.ok(),
+        None => None,
+    };
+    // Dropping the wake kills its process, if it started one
+    match time::timeout(
+        agent.wake_timeout,
+        do_wake(agent, summary, notifications_json),
+    )
+    .await
+    {
+        Ok(woken) => woken,
+        Err(_) => {
+            log!(
+                agent.name,
+                "Wake timed out after {}s",
+                agent.wake_timeout.as_secs()
+            );
+            false
+        }
+    }
+}
+
+/// Note on each task that the agent couldn't be woken for it.
+async fn report_wake_failure(
+    client: &reqwest::Client,
+    agent: &ResolvedAgent,
+    task_ids: &[String],
+    attempts: u32,
+) {
+    for task_id in task_ids {
+        let url = format!("{}/api/tasks/{}/activity", agent.api_url, task_id);
+        let content = format!(
+            "opengate-bridge couldn't wake {} via {:?} after {} attempt(s); its notifications stay unread",
+            agent.name, agent.wake_mode, attempts
+        );
+        let sent = client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", agent.api_key))
+            .json(&serde_json::json!({
+                "content": content,
+                "activity_type": "wake_failed",
+            }))
+            .send()
+            .await;
+        match sent {
+            Ok(r) if r.status().is_success() => {}
+            Ok(r) => log!(
+                agent.name,
+                "Failure report on {} failed (HTTP {})",
+                task_id,
+                r.status()
+            ),
+            Err(e) => log!(agent.name, "Failure report on {} error: {}", task_id, e),
+        }
+    }
+}
+
+/// Perform the actual wake call (extracted from wake_agent for spawned task use).
+/// `notifications_json` is the unread notifications as the server returned them.
+async fn do_wake(agent: &ResolvedAgent, summary: &str, notifications_json: &str) -> bool {
+    let agent_name = &agent.name;
+    match agent.wake_mode {
         WakeMode::Stdout => {
             eprintln!("[{}] Notifications:\n{}", agent_name, summary);
             true
         }
         WakeMode::Openclaw => {
-            let oc_id = openclaw_id.as_ref().unwrap();
+            let oc_id = agent.openclaw_id.as_ref().unwrap();
             let message = format!("OpenGate: {}", summary);
 
             match tokio::process::Command::new("openclaw")
                 .args(["agent", "--agent", oc_id, "--message", &message])
+                .kill_on_drop(true)
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
                 .status()
@@ -323,7 +697,7 @@ async fn do_wake(
             }
         }
         WakeMode::Webhook => {
-            let url = webhook_url.as_ref().unwrap();
+            let url = agent.webhook_url.as_ref().unwrap();
             let payload = serde_json::json!({
                 "agent": agent_name,
                 "summary": summary,
@@ -345,30 +719,139 @@ async fn do_wake(
             }
         }
         WakeMode::Command => {
-            let cmd = command.as_ref().unwrap();
+            let cmd = agent.command.as_ref().unwrap();
 
-            match tokio::process::Command::new("sh")
+            // The full notifications (ids included) go on stdin as a JSON array, so a
+            // launcher can triage and ack them selectively
+            let child = tokio::process::Command::new("sh")
                 .args(["-c", cmd])
+                .kill_on_drop(true)
                 .env("OPENGATE_NOTIFICATIONS", summary)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    log!(agent_name, "Command exec error: {}", e);
+                    return false;
+                }
+            };
+            // Written alongside the wait, so a command that reads only part of a large
+            // payload before exiting doesn't block on it
+            let stdin = child.stdin.take();
+            let write = async {
+                if let Some(mut stdin) = stdin {
+                    // Commands that ignore stdin close it early; that's fine
+                    let _ = stdin.write_all(notifications_json.as_bytes()).await;
+                }
+            };
+            let ((), status) = tokio::join!(write, child.wait());
+
+            match status {
+                Ok(s) if s.success() => {
+                    log!(agent_name, "Command wake succeeded");
+                    true
+                }
+                Ok(s) => {
+                    log!(
+                        agent_name,
+                        "Command wake failed (exit {})",
+                        s.code().unwrap_or(-1)
+                    );
+                    false
+                }
+                Err(e) => {
+                    log!(agent_name, "Command exec error: {}", e);
+                    false
+                }
+            }
+        }
+        WakeMode::Docker => {
+            let container = agent.container.as_ref().unwrap();
+            let env = format!("OPENGATE_NOTIFICATIONS={}", summary);
+            let args: Vec<&str> = match &agent.docker_exec {
+                Some(cmd) => vec!["exec", "-e", &env, container, "sh", "-c", cmd],
+                // Starting a running container is a no-op, so this only wakes stopped ones
+                None => vec!["start", container],
+            };
+
+            match tokio::process::Command::new("docker")
+                .args(&args)
+                .kill_on_drop(true)
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
                 .status()
                 .await
             {
                 Ok(s) if s.success() => {
-                    log!(agent_name, "Command wake succeeded");
+                    log!(agent_name, "Docker {} wake succeeded", args[0]);
                     true
                 }
                 Ok(s) => {
                     log!(
                         agent_name,
-                        "Command wake failed (exit {})",
+                        "Docker {} wake failed (exit {})",
+                        args[0],
                         s.code().unwrap_or(-1)
                     );
                     false
                 }
                 Err(e) => {
-                    log!(agent_name, "Command exec error: {}", e);
+                    log!(agent_name, "Docker exec error: {}", e);
+                    false
+                }
+            }
+        }
+        WakeMode::Kubernetes => {
+            let manifest =
+                render_job_template(agent.job_template.as_ref().unwrap(), agent_name, summary);
+            let mut args = vec!["create", "-f", "-"];
+            if let Some(namespace) = &agent.namespace {
+                args.extend(["--namespace", namespace]);
+            }
+
+            let child = tokio::process::Command::new("kubectl")
+                .args(&args)
+                .kill_on_drop(true)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    log!(agent_name, "kubectl exec error: {}", e);
+                    return false;
+                }
+            };
+            // Dropping stdin closes it, so kubectl reads to the end
+            let stdin = child.stdin.take();
+            let write = async {
+                if let Some(mut stdin) = stdin {
+                    if let Err(e) = stdin.write_all(manifest.as_bytes()).await {
+                        log!(agent_name, "kubectl write error: {}", e);
+                    }
+                }
+            };
+            let ((), status) = tokio::join!(write, child.wait());
+
+            match status {
+                Ok(s) if s.success() => {
+                    log!(agent_name, "Kubernetes Job created");
+                    true
+                }
+                Ok(s) => {
+                    log!(
+                        agent_name,
+                        "Kubernetes Job creation failed (exit {})",
+                        s.code().unwrap_or(-1)
+                    );
+                    false
+                }
+                Err(e) => {
+                    log!(agent_name, "kubectl exec error: {}", e);
                     false
                 }
             }
@@ -376,6 +859,44 @@ async fn do_wake(
     }
 }
 
+/// Fill `{{agent}}` and `{{summary}}` into a Job manifest. Values are escaped for a
+/// double-quoted string (`value: "{{summary}}"`), which reads the same in YAML and JSON.
+/// Give the Job `metadata.generateName` so every wake creates a new one.
+fn render_job_template(template: &str, agent_name: &str, summary: &str) -> String {
+    let escape = |value: &str| {
+        let quoted = serde_json::Value::from(value).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+    template
+        .replace("{{agent}}", &escape(agent_name))
+        .replace("{{summary}}", &escape(summary))
+}
+
+/// The wake message from the agent's `message_template`.
+fn render_message(source: &str, agent: &ResolvedAgent, raw: &[serde_json::Value]) -> String {
+    let notifications: Vec<serde_json::Value> = raw
+        .iter()
+        .cloned()
+        .map(|mut n| {
+            if let Some(task_id) = n.get("task_id").and_then(|t| t.as_str()) {
+                let url = format!("{}/api/tasks/{}", agent.api_url, task_id);
+                n["task_url"] = url.into();
+            }
+            n
+        })
+        .collect();
+    let context = serde_json::json!({
+        "agent": agent.name,
+        "server_url": agent.api_url,
+        "count": notifications.len(),
+        "notifications": notifications,
+    });
+    // Validated when the config loaded
+    template::Template::parse(source)
+        .map(|t| t.render(&context))
+        .unwrap_or_default()
+}
+
 fn build_summary(notifications: &[Notification]) -> String {
     notifications
         .iter()
@@ -396,120 +917,866 @@ fn build_summary(notifications: &[Notification]) -> String {
 
 // ── Agent loop ──────────────────────────────────────────────────────
 
-async fn run_agent_loop(agent: ResolvedAgent) {
+async fn run_agent_loop(agent: Arc<ResolvedAgent>, wake: Arc<WakeState>) {
     let client = reqwest::Client::new();
-    let waking = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let mut hb_interval = time::interval(agent.heartbeat_interval);
-    let mut poll_interval = time::interval(agent.poll_interval);
+    let mut backoff = Backoff::new(agent.poll_interval, agent.max_backoff);
 
     // First tick fires immediately
     do_heartbeat(&client, &agent).await;
-    poll_and_wake(&client, &agent, &waking).await;
+    let mut next_poll = Instant::now();
 
     loop {
         tokio::select! {
-            _ = hb_interval.tick() => {
+            // Polls probe the server while it's failing; heartbeats wait for it to recover
+            _ = hb_interval.tick(), if !backoff.failing() => {
                 do_heartbeat(&client, &agent).await;
             }
-            _ = poll_interval.tick() => {
-                poll_and_wake(&client, &agent, &waking).await;
+            // A wake ended with notifications queued behind it
+            _ = wake.repoll.notified(), if !backoff.failing() => {
+                next_poll = Instant::now();
+            }
+            _ = time::sleep_until(next_poll) => {
+                let delay = match poll_and_wake(&client, &agent, &wake).await {
+                    Ok(()) => {
+                        if let Some(failures) = backoff.success() {
+                            log!(
+                                agent.name,
+                                "Server reachable again after {} failed poll(s), back to every {}s",
+                                failures,
+                                agent.poll_interval.as_secs()
+                            );
+                        }
+                        agent.poll_interval
+                    }
+                    Err(e) => {
+                        let delay = backoff.failure();
+                        log!(
+                            agent.name,
+                            "{} — failure {}, retrying in {}s",
+                            e,
+                            backoff.failures,
+                            delay.as_secs()
+                        );
+                        delay
+                    }
+                };
+                next_poll = Instant::now() + delay;
             }
         }
     }
 }
 
-async fn run_once(agent: &ResolvedAgent) {
+async fn run_once(agent: &Arc<ResolvedAgent>) {
     let client = reqwest::Client::new();
-    let waking = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let wake = Arc::new(WakeState::default());
     do_heartbeat(&client, agent).await;
-    poll_and_wake(&client, agent, &waking).await;
+    if let Err(e) = poll_and_wake(&client, agent, &wake).await {
+        log!(agent.name, "{}", e);
+    }
     // In one-shot mode, wait a bit for the spawned wake to start
     time::sleep(Duration::from_secs(5)).await;
 }
 
+// ── Config loading ──────────────────────────────────────────────────
+
+/// Register each agent in `agents` whose key file is missing and write its new key
+/// there, readable only by the bridge's user.
+async fn register_missing(server: &ServerConfig, agents: &[&AgentConfig]) -> Result<(), String> {
+    let Some(setup_token) = &server.setup_token else {
+        return Ok(());
+    };
+    let client = reqwest::Client::new();
+    for cfg in agents {
+        if Path::new(&cfg.api_key_file).exists() {
+            continue;
+        }
+        let url = format!("{}/api/agents/register", server.url.trim_end_matches('/'));
+        let resp = client
+            .post(&url)
+            .json(&serde_json::json!({
+                "name": cfg.name,
+                "skills": cfg.skills,
+                "setup_token": setup_token,
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("agent '{}': registration error: {}", cfg.name, e))?;
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await.unwrap_or_default();
+        let Some(api_key) = body["api_key"].as_str().filter(|_| status.is_success()) else {
+            return Err(format!(
+                "agent '{}': registration failed (HTTP {}): {}",
+                cfg.name,
+                status,
+                body["error"].as_str().unwrap_or("no api_key in response")
+            ));
+        };
+        let agent_id = body["agent"]["id"].as_str().unwrap_or("?");
+        write_key_file(&cfg.api_key_file, api_key).map_err(|e| {
+            format!(
+                "agent '{}': registered as {} but can't write key file '{}': {}",
+                cfg.name, agent_id, cfg.api_key_file, e
+            )
+        })?;
+        log!(
+            cfg.name,
+            "Registered as {}, key written to {}",
+            agent_id,
+            cfg.api_key_file
+        );
+    }
+    Ok(())
+}
+
+fn write_key_file(path: &str, api_key: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(dir) = Path::new(path)
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    writeln!(file, "{}", api_key)
+}
+
+/// Read the config and resolve its agents (keys loaded, wake modes validated), keeping
+/// only `only` when given. Agents without a key file are registered first when the
+/// config has a `setup_token`.
+async fn load_agents(
+    path: &Path,
+    only: Option<&str>,
+) -> Result<(ServerConfig, Vec<ResolvedAgent>), String> {
+    let config = read_config(path)?;
+    let selected = select_agents(&config, only);
+    register_missing(&config.server, &selected).await?;
+
+    let agents = selected
+        .into_iter()
+        .map(|cfg| {
+            ResolvedAgent::from_config(cfg, &config.server)
+                .map_err(|e| format!("Config error: {}", e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if agents.is_empty() {
+        return Err(no_agents(only));
+    }
+    Ok((config.server, agents))
+}
+
+fn read_config(path: &Path) -> Result<Config, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading config '{}': {}", path.display(), e))?;
+    let config: Config =
+        toml::from_str(&text).map_err(|e| format!("Error parsing config: {}", e))?;
+    if config.agents.is_empty() {
+        return Err("No agents configured in config file".to_string());
+    }
+    Ok(config)
+}
+
+fn select_agents<'a>(config: &'a Config, only: Option<&str>) -> Vec<&'a AgentConfig> {
+    config
+        .agents
+        .iter()
+        .filter(|cfg| only.is_none_or(|name| cfg.name == name))
+        .collect()
+}
+
+fn no_agents(only: Option<&str>) -> String {
+    match only {
+        Some(name) => format!("No agent named '{}' found in config", name),
+        None => "No agents configured".to_string(),
+    }
+}
+
+/// `--check`: report every problem with the config rather than stopping at the first,
+/// and confirm each agent's key against `GET /api/auth/me`. Registers no one.
+async fn check_config(path: &Path, only: Option<&str>) -> bool {
+    let config = match read_config(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let selected = select_agents(&config, only);
+    if selected.is_empty() {
+        eprintln!("{}", no_agents(only));
+        return false;
+    }
+
+    let client = reqwest::Client::new();
+    let mut ok = true;
+    for cfg in selected {
+        if config.server.setup_token.is_some() && !Path::new(&cfg.api_key_file).exists() {
+            println!(
+                "OK   {}: no key file yet, will register with the setup token",
+                cfg.name
+            );
+            continue;
+        }
+        let agent = match ResolvedAgent::from_config(cfg, &config.server) {
+            Ok(agent) => agent,
+            Err(e) => {
+                eprintln!("FAIL {}", e);
+                ok = false;
+                continue;
+            }
+        };
+        let url = format!("{}/api/auth/me", agent.api_url);
+        let me = client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", agent.api_key))
+            .send()
+            .await;
+        let problem = match me {
+            Err(e) => Some(format!("can't reach {}: {}", agent.api_url, e)),
+            Ok(resp) if !resp.status().is_success() => {
+                Some(format!("GET /api/auth/me returned HTTP {}", resp.status()))
+            }
+            Ok(resp) => {
+                let me: serde_json::Value = resp.json().await.unwrap_or_default();
+                match me["type"].as_str() {
+                    Some("agent") => {
+                        println!(
+                            "OK   {}: authenticated as {} ({}), wakes via {:?}",
+                            agent.name,
+                            me["name"].as_str().unwrap_or("?"),
+                            me["id"].as_str().unwrap_or("?"),
+                            agent.wake_mode
+                        );
+                        None
+                    }
+                    _ => Some("the API key isn't accepted as an agent key".to_string()),
+                }
+            }
+        };
+        if let Some(problem) = problem {
+            eprintln!("FAIL agent '{}': {}", agent.name, problem);
+            ok = false;
+        }
+    }
+    ok
+}
+
+// ── Supervisor ──────────────────────────────────────────────────────
+
+/// The running agent loops, restarted selectively when the config is reloaded.
+#[derive(Default)]
+struct Supervisor {
+    running: HashMap<String, RunningAgent>,
+    /// Per-agent wakes in progress, kept across restarts
+    wakes: HashMap<String, Arc<WakeState>>,
+}
+
+struct RunningAgent {
+    agent: Arc<ResolvedAgent>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl Supervisor {
+    /// Start `agent`'s loop after `delay`.
+    fn start(&mut self, agent: ResolvedAgent, delay: Duration) {
+        let agent = Arc::new(agent);
+        let wake = self.wakes.entry(agent.name.clone()).or_default().clone();
+        let task_agent = agent.clone();
+        let handle = tokio::spawn(async move {
+            if !delay.is_zero() {
+                log!(
+                    task_agent.name,
+                    "Starting in {}s (staggered)",
+                    delay.as_secs()
+                );
+                time::sleep(delay).await;
+            }
+            log!(
+                task_agent.name,
+                "Polling every {}s, heartbeat every {}s",
+                task_agent.poll_interval.as_secs(),
+                task_agent.heartbeat_interval.as_secs()
+            );
+            run_agent_loop(task_agent, wake).await;
+        });
+        self.running
+            .insert(agent.name.clone(), RunningAgent { agent, handle });
+    }
+
+    /// Bring the running loops in line with `agents`: stop removed ones, start new ones,
+    /// restart changed ones and leave the rest alone. Wakes already under way finish on
+    /// their own.
+    fn apply(&mut self, agents: Vec<ResolvedAgent>) {
+        let keep: Vec<String> = agents.iter().map(|a| a.name.clone()).collect();
+        self.running.retain(|name, running| {
+            let kept = keep.contains(name);
+            if !kept {
+                log!(name, "Removed from config, stopping");
+                running.handle.abort();
+            }
+            kept
+        });
+        self.wakes.retain(|name, _| keep.contains(name));
+        metrics::retain(&keep);
+
+        for agent in agents {
+            match self.running.get(&agent.name) {
+                Some(running) if *running.agent == agent => continue,
+                Some(running) => {
+                    log!(agent.name, "Config changed, restarting");
+                    running.handle.abort();
+                }
+                None => log!(agent.name, "Added to config, starting"),
+            }
+            self.start(agent, Duration::ZERO);
+        }
+    }
+}
+
 // ── Main ────────────────────────────────────────────────────────────
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    // Load config
-    let text = match std::fs::read_to_string(&cli.config) {
-        Ok(t) => t,
+    if cli.check {
+        let ok = check_config(&cli.config, cli.agent.as_deref()).await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // Load config, resolve agents (load keys, validate config)
+    let agents = match load_agents(&cli.config, cli.agent.as_deref()).await {
+        Ok((server, agents)) => {
+            set_wake_limit(server.max_concurrent_wakes);
+            agents
+        }
         Err(e) => {
-            eprintln!("Error reading config '{}': {}", cli.config.display(), e);
+            eprintln!("{}", e);
             std::process::exit(1);
         }
     };
 
-    let config: Config = match toml::from_str(&text) {
-        Ok(c) => c,
+    log_global!("Starting bridge for {} agent(s)", agents.len());
+
+    // --once mode: single poll cycle
+    if cli.once {
+        for agent in agents {
+            run_once(&Arc::new(agent)).await;
+        }
+        return;
+    }
+
+    if let Some(addr) = cli.metrics_addr {
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => {
+                log_global!("Serving metrics on http://{}/metrics", addr);
+                tokio::spawn(metrics::serve(listener));
+            }
+            Err(e) => {
+                eprintln!("Error binding metrics address {}: {}", addr, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Daemon mode: spawn a task per agent with staggered starts
+    let mut supervisor = Supervisor::default();
+    for (i, agent) in agents.into_iter().enumerate() {
+        // Stagger agent starts by 2 seconds each
+        supervisor.start(agent, Duration::from_secs(i as u64 * 2));
+    }
+
+    let signal = |kind| match tokio::signal::unix::signal(kind) {
+        Ok(s) => s,
         Err(e) => {
-            eprintln!("Error parsing config: {}", e);
+            eprintln!("Signal handler error: {}", e);
             std::process::exit(1);
         }
     };
+    let mut hangup = signal(tokio::signal::unix::SignalKind::hangup());
+    let mut terminate = signal(tokio::signal::unix::SignalKind::terminate());
 
-    if config.agents.is_empty() {
-        eprintln!("No agents configured in config file");
-        std::process::exit(1);
+    if let Some(interval) = systemd::watchdog_interval() {
+        tokio::spawn(systemd::run_watchdog(interval));
     }
+    systemd::notify(&format!(
+        "READY=1\nSTATUS=Polling for {} agent(s)",
+        supervisor.running.len()
+    ));
 
-    // Resolve agents (load keys, validate config)
-    let mut agents: Vec<ResolvedAgent> = Vec::new();
-    for cfg in &config.agents {
-        // Filter by --agent if specified
-        if let Some(ref filter) = cli.agent {
-            if cfg.name != *filter {
-                continue;
+    // Reload on SIGHUP until ctrl+c or SIGTERM
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                match result {
+                    Ok(()) => log_global!("Shutting down"),
+                    Err(e) => eprintln!("Signal handler error: {}", e),
+                }
+                break;
+            }
+            _ = terminate.recv() => {
+                log_global!("SIGTERM: shutting down");
+                break;
+            }
+            _ = hangup.recv() => {
+                log_global!("SIGHUP: reloading {}", cli.config.display());
+                systemd::notify("RELOADING=1");
+                match load_agents(&cli.config, cli.agent.as_deref()).await {
+                    Ok((server, agents)) => {
+                        set_wake_limit(server.max_concurrent_wakes);
+                        supervisor.apply(agents);
+                    }
+                    // A broken edit keeps the agents running as they were
+                    Err(e) => log_global!("Reload failed, keeping current config: {}", e),
+                }
+                systemd::notify(&format!(
+                    "READY=1\nSTATUS=Polling for {} agent(s)",
+                    supervisor.running.len()
+                ));
             }
         }
+    }
+    systemd::notify("STOPPING=1");
+}
 
-        match ResolvedAgent::from_config(cfg, &config.server) {
-            Ok(a) => agents.push(a),
-            Err(e) => {
-                eprintln!("Config error: {}", e);
-                std::process::exit(1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::AsyncReadExt;
+
+    /// Nothing listens here, so requests fail fast.
+    const UNREACHABLE: &str = "http://127.0.0.1:9";
+
+    /// A stdout-mode agent with quick retries.
+    fn test_agent(name: &str, api_url: &str) -> ResolvedAgent {
+        ResolvedAgent {
+            name: name.to_string(),
+            api_url: api_url.to_string(),
+            api_key: "key".to_string(),
+            poll_interval: Duration::from_secs(60),
+            heartbeat_interval: Duration::from_secs(300),
+            max_backoff: Duration::from_millis(40),
+            wake_mode: WakeMode::Stdout,
+            wake_retries: 0,
+            wake_retry_delay: Duration::from_millis(10),
+            wake_timeout: Duration::from_secs(10),
+            report_wake_failures: false,
+            message_template: None,
+            wake_on: None,
+            openclaw_id: None,
+            webhook_url: None,
+            command: None,
+            container: None,
+            docker_exec: None,
+            job_template: None,
+            namespace: None,
+        }
+    }
+
+    fn notification(notification_type: &str, wake: bool) -> Notification {
+        Notification {
+            id: json!(1),
+            notification_type: notification_type.to_string(),
+            title: "Title".to_string(),
+            body: None,
+            task_id: None,
+            wake,
+        }
+    }
+
+    /// A directory of its own for `test` under the system temp dir.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("opengate-bridge-{}-{}", std::process::id(), test));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A server answering every request with `respond(request)` as JSON, and the
+    /// requests it has seen (head and body).
+    async fn serve(respond: fn(&str) -> (u16, String)) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let seen = seen.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    // The head, then as much body as Content-Length announces
+                    loop {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                        let text = String::from_utf8_lossy(&request);
+                        let Some(head) = text.find("\r\n\r\n") else {
+                            continue;
+                        };
+                        let length = text[..head]
+                            .lines()
+                            .filter_map(|line| line.split_once(':'))
+                            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if request.len() >= head + 4 + length {
+                            break;
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let (status, body) = respond(&request);
+                    seen.lock().unwrap().push(request);
+                    let response = format!(
+                        "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
             }
+        });
+        (url, requests)
+    }
+
+    /// Wait up to a second for `done`.
+    async fn eventually(done: impl Fn() -> bool) -> bool {
+        for _ in 0..100 {
+            if done() {
+                return true;
+            }
+            time::sleep(Duration::from_millis(10)).await;
         }
+        done()
     }
 
-    if agents.is_empty() {
-        if let Some(ref filter) = cli.agent {
-            eprintln!("No agent named '{}' found in config", filter);
-        } else {
-            eprintln!("No agents configured");
+    #[test]
+    fn backoff_doubles_with_jitter_up_to_the_cap() {
+        let base = Duration::from_secs(10);
+        let max = Duration::from_secs(60);
+        let mut backoff = Backoff::new(base, max);
+        assert!(!backoff.failing());
+        for ceiling in [20, 40, 60, 60] {
+            let ceiling = Duration::from_secs(ceiling);
+            let wait = backoff.failure();
+            assert!(
+                wait >= ceiling / 2 && wait <= ceiling,
+                "{:?} is outside the upper half of {:?}",
+                wait,
+                ceiling
+            );
         }
-        std::process::exit(1);
+        assert!(backoff.failing());
+        assert_eq!(backoff.success(), Some(4));
+        assert_eq!(backoff.success(), None);
+        assert!(!backoff.failing());
+
+        // Agents failing together don't all wait the same
+        let waits: HashSet<Duration> = (0..20).map(|_| Backoff::new(base, max).failure()).collect();
+        assert!(waits.len() > 1);
     }
 
-    log_global!("Starting bridge for {} agent(s)", agents.len());
+    #[tokio::test]
+    async fn reload_restarts_only_changed_agents() {
+        let mut supervisor = Supervisor::default();
+        supervisor.apply(vec![
+            test_agent("same", UNREACHABLE),
+            test_agent("edited", UNREACHABLE),
+            test_agent("dropped", UNREACHABLE),
+        ]);
+        let same = supervisor.running["same"].agent.clone();
+        let edited = supervisor.running["edited"].agent.clone();
+        let edited_wake = supervisor.wakes["edited"].clone();
 
-    // --once mode: single poll cycle
-    if cli.once {
-        for agent in &agents {
-            run_once(agent).await;
+        let mut changed = test_agent("edited", UNREACHABLE);
+        changed.poll_interval = Duration::from_secs(5);
+        supervisor.apply(vec![
+            test_agent("same", UNREACHABLE),
+            changed,
+            test_agent("added", UNREACHABLE),
+        ]);
+
+        assert!(Arc::ptr_eq(&supervisor.running["same"].agent, &same));
+        let restarted = &supervisor.running["edited"].agent;
+        assert!(!Arc::ptr_eq(restarted, &edited));
+        assert_eq!(restarted.poll_interval, Duration::from_secs(5));
+        // A restart keeps the wake in progress, so it isn't woken twice
+        assert!(Arc::ptr_eq(&supervisor.wakes["edited"], &edited_wake));
+        assert!(supervisor.running.contains_key("added"));
+        assert!(!supervisor.running.contains_key("dropped"));
+        assert!(!supervisor.wakes.contains_key("dropped"));
+
+        for running in supervisor.running.values() {
+            running.handle.abort();
         }
-        return;
     }
 
-    // Daemon mode: spawn a task per agent with staggered starts
-    let mut handles = Vec::new();
-    for (i, agent) in agents.into_iter().enumerate() {
-        // Stagger agent starts by 2 seconds each
-        let delay = Duration::from_secs(i as u64 * 2);
-        handles.push(tokio::spawn(async move {
-            if !delay.is_zero() {
-                log!(agent.name, "Starting in {}s (staggered)", delay.as_secs());
-                time::sleep(delay).await;
+    #[test]
+    fn wake_on_filters_event_types() {
+        let mut agent = test_agent("filter", UNREACHABLE);
+        assert!(agent.wakes_for(&notification("task_assigned", true)));
+        assert!(!agent.wakes_for(&notification("task_assigned", false)));
+
+        agent.wake_on = Some(vec!["task_assigned".to_string(), "question_*".to_string()]);
+        assert!(agent.wakes_for(&notification("task_assigned", true)));
+        assert!(agent.wakes_for(&notification("question_asked", true)));
+        assert!(!agent.wakes_for(&notification("task_assigned_to_team", true)));
+        assert!(!agent.wakes_for(&notification("review_requested", true)));
+        assert!(!agent.wakes_for(&notification("question_asked", false)));
+
+        // Servers name the type differently; all of them parse
+        for kind in ["type", "event_type", "notification_type"] {
+            let n: Notification =
+                serde_json::from_value(json!({"id": 1, kind: "task_assigned", "title": "T"}))
+                    .unwrap();
+            assert_eq!(n.notification_type, "task_assigned");
+            assert!(n.wake);
+        }
+    }
+
+    #[tokio::test]
+    async fn notifications_during_a_wake_queue_one_more() {
+        let (url, _) = serve(|_| {
+            let notifications = json!([
+                {"id": 1, "type": "task_assigned", "title": "One"},
+                {"id": 2, "type": "task_assigned", "title": "Two"},
+            ]);
+            (200, notifications.to_string())
+        })
+        .await;
+        let agent = Arc::new(test_agent("queue", &url));
+        let client = reqwest::Client::new();
+        let wake = Arc::new(WakeState::default());
+
+        // A wake already covering both notifications queues nothing
+        wake.running.store(true, Ordering::Relaxed);
+        *wake.woken.lock().unwrap() = HashSet::from([1, 2]);
+        poll_and_wake(&client, &agent, &wake).await.unwrap();
+        assert!(!wake.queued.load(Ordering::Relaxed));
+        wake.woken.lock().unwrap().remove(&2);
+        poll_and_wake(&client, &agent, &wake).await.unwrap();
+        assert!(wake.queued.load(Ordering::Relaxed));
+
+        // The next wake covers both, and asks for a poll when it ends with one queued
+        wake.running.store(false, Ordering::Relaxed);
+        poll_and_wake(&client, &agent, &wake).await.unwrap();
+        assert!(wake.running.load(Ordering::Relaxed));
+        assert_eq!(*wake.woken.lock().unwrap(), HashSet::from([1, 2]));
+        time::timeout(Duration::from_secs(1), wake.repoll.notified())
+            .await
+            .expect("no repoll after the wake");
+        assert!(!wake.running.load(Ordering::Relaxed));
+        assert!(!wake.queued.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn wakes_wait_for_a_free_slot() {
+        set_wake_limit(Some(1));
+        let slots = wake_slots().lock().unwrap().1.clone().unwrap();
+        // Reapplying the same limit (a reload) keeps the slots in use
+        set_wake_limit(Some(1));
+        assert!(Arc::ptr_eq(
+            &slots,
+            wake_slots().lock().unwrap().1.as_ref().unwrap()
+        ));
+
+        let held = slots.clone().acquire_owned().await.unwrap();
+        let agent = test_agent("slots", UNREACHABLE);
+        let waiting = tokio::spawn(async move { wake_in_slot(&agent, "summary", "[]").await });
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        drop(held);
+        assert!(waiting.await.unwrap());
+
+        // A hung wake is killed at its timeout, and gives its slot back
+        let pid_file = temp_dir("slots").join("pid");
+        let mut hung = test_agent("slots", UNREACHABLE);
+        hung.wake_mode = WakeMode::Command;
+        hung.command = Some(format!("echo $$ > {}; exec sleep 30", pid_file.display()));
+        hung.wake_timeout = Duration::from_millis(200);
+        let started = std::time::Instant::now();
+        assert!(!wake_in_slot(&hung, "summary", "[]").await);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(slots.available_permits(), 1);
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        // Gone, or a zombie until the runtime reaps it
+        let alive = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+                .is_ok_and(|stat| !stat.contains(") Z "))
+        };
+        assert!(
+            eventually(|| !alive()).await,
+            "the wake process is still running"
+        );
+        set_wake_limit(None);
+    }
+
+    #[tokio::test]
+    async fn failed_wakes_are_retried_then_reported() {
+        let (url, requests) = serve(|request| {
+            if request.starts_with("GET ") {
+                let notifications = json!([
+                    {"id": 7, "type": "task_assigned", "title": "Fix CI", "task_id": "t1"},
+                ]);
+                (200, notifications.to_string())
+            } else {
+                (201, "{}".to_string())
             }
-            run_agent_loop(agent).await;
-        }));
+        })
+        .await;
+        let attempts = temp_dir("retries").join("attempts");
+        let _ = std::fs::remove_file(&attempts);
+        let mut agent = test_agent("retry", &url);
+        agent.wake_mode = WakeMode::Command;
+        agent.command = Some(format!("echo attempt >> {}; exit 1", attempts.display()));
+        agent.wake_retries = 2;
+
+        assert_eq!(wake_with_retries(&agent, "summary", "[]").await, Err(3));
+        let count = || {
+            std::fs::read_to_string(&attempts)
+                .map(|s| s.lines().count())
+                .unwrap_or(0)
+        };
+        assert_eq!(count(), 3);
+
+        // Once every attempt fails, the task gets an activity saying so
+        agent.report_wake_failures = true;
+        let agent = Arc::new(agent);
+        let wake = Arc::new(WakeState::default());
+        poll_and_wake(&reqwest::Client::new(), &agent, &wake)
+            .await
+            .unwrap();
+        let reported =
+            || {
+                requests.lock().unwrap().iter().any(|r| {
+                    r.starts_with("POST /api/tasks/t1/activity") && r.contains("wake_failed")
+                })
+            };
+        assert!(eventually(reported).await, "no failure report");
+        assert_eq!(count(), 6);
+
+        let mut fixed = test_agent("retry", &url);
+        fixed.wake_mode = WakeMode::Command;
+        fixed.command = Some("true".to_string());
+        assert_eq!(wake_with_retries(&fixed, "summary", "[]").await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn commands_get_large_payloads_without_deadlocking() {
+        let received = temp_dir("payload").join("received");
+        let mut agent = test_agent("payload", UNREACHABLE);
+        agent.wake_mode = WakeMode::Command;
+        // More output than a pipe holds before reading any of stdin
+        agent.command = Some(format!(
+            "head -c 200000 /dev/zero; wc -c > {}",
+            received.display()
+        ));
+        let payload = format!("[\"{}\"]", "x".repeat(200_000));
+        let woken = time::timeout(
+            Duration::from_secs(10),
+            do_wake(&agent, "summary", &payload),
+        )
+        .await
+        .expect("the wake deadlocked");
+        assert!(woken);
+        let count = std::fs::read_to_string(&received).unwrap();
+        assert_eq!(count.trim().parse::<usize>().unwrap(), payload.len());
+    }
+
+    #[test]
+    fn message_template_links_tasks() {
+        let agent = test_agent("builder", "http://gate.example.com");
+        let raw = [
+            json!({"id": 1, "type": "task_assigned", "title": "Fix CI", "task_id": "t1"}),
+            json!({"id": 2, "type": "mention", "title": "Ping"}),
+        ];
+        let source = "{{agent}} has {{count}}:{{#each notifications}} {{title}}{{#if task_url}} <{{task_url}}>{{/if}};{{/each}}";
+        assert_eq!(
+            render_message(source, &agent, &raw),
+            "builder has 2: Fix CI <http://gate.example.com/api/tasks/t1>; Ping;"
+        );
     }
 
-    // Wait for ctrl+c
-    match tokio::signal::ctrl_c().await {
-        Ok(()) => log_global!("Shutting down"),
-        Err(e) => eprintln!("Signal handler error: {}", e),
+    #[tokio::test]
+    async fn check_confirms_each_key_with_the_server() {
+        let (url, _) = serve(|request| {
+            let me = if request.contains("Bearer good") {
+                json!({"type": "agent", "id": "a1", "name": "good"})
+            } else {
+                json!({"type": "anonymous"})
+            };
+            (200, me.to_string())
+        })
+        .await;
+        let dir = temp_dir("check");
+        let key = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.display().to_string()
+        };
+        let config = dir.join("bridge.toml");
+        std::fs::write(
+            &config,
+            format!(
+                r#"
+                [server]
+                url = "{url}"
+
+                [[agents]]
+                name = "good"
+                api_key_file = "{good}"
+
+                [[agents]]
+                name = "rejected"
+                api_key_file = "{bad}"
+
+                [[agents]]
+                name = "empty"
+                api_key_file = "{empty}"
+
+                [[agents]]
+                name = "incomplete"
+                api_key_file = "{good}"
+                wake_mode = "webhook"
+                "#,
+                url = url,
+                good = key("good.key", "good\n"),
+                bad = key("bad.key", "bad"),
+                empty = key("empty.key", ""),
+            ),
+        )
+        .unwrap();
+
+        assert!(check_config(&config, Some("good")).await);
+        for name in ["rejected", "empty", "incomplete", "unknown"] {
+            assert!(!check_config(&config, Some(name)).await, "{}", name);
+        }
+        assert!(!check_config(&config, None).await);
+        assert!(!check_config(&dir.join("missing.toml"), None).await);
+
+        // With a setup token, a missing key file is fine and nobody is registered
+        let unregistered = dir.join("new.key");
+        std::fs::write(
+            &config,
+            format!(
+                "[server]\nurl = \"{}\"\nsetup_token = \"s\"\n\n[[agents]]\nname = \"new\"\napi_key_file = \"{}\"\n",
+                url,
+                unregistered.display()
+            ),
+        )
+        .unwrap();
+        assert!(check_config(&config, None).await);
+        assert!(!unregistered.exists());
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
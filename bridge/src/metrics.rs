@@ -0,0 +1,211 @@
+//! Per-agent counters, served over HTTP with `--metrics-addr`: `GET /metrics` in the
+//! Prometheus text format, `GET /status` as JSON. Alert on `last_wake_success` or
+//! `last_heartbeat_success` going stale to catch an agent that stopped being woken.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Default)]
+pub struct AgentMetrics {
+    pub polls: AtomicU64,
+    pub poll_failures: AtomicU64,
+    pub notifications_seen: AtomicU64,
+    pub wakes_attempted: AtomicU64,
+    pub wakes_succeeded: AtomicU64,
+    /// Wakes that failed every retry
+    pub wakes_failed: AtomicU64,
+    pub wake_retries: AtomicU64,
+    pub heartbeat_failures: AtomicU64,
+    /// Unix seconds; 0 until the first success
+    pub last_heartbeat_success: AtomicI64,
+    pub last_wake_success: AtomicI64,
+    /// Highest notification id counted in `notifications_seen`, so a notification
+    /// that stays unread across polls counts once
+    pub highest_notification_id: AtomicI64,
+}
+
+pub fn inc(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Set `timestamp` to now.
+pub fn touch(timestamp: &AtomicI64) {
+    timestamp.store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+}
+
+impl AgentMetrics {
+    /// Count the ids above the highest seen so far.
+    pub fn saw_notifications(&self, ids: impl Iterator<Item = i64>) {
+        let highest = self.highest_notification_id.load(Ordering::Relaxed);
+        let mut new_highest = highest;
+        let mut new = 0;
+        for id in ids.filter(|id| *id > highest) {
+            new += 1;
+            new_highest = new_highest.max(id);
+        }
+        self.notifications_seen.fetch_add(new, Ordering::Relaxed);
+        self.highest_notification_id
+            .fetch_max(new_highest, Ordering::Relaxed);
+    }
+}
+
+fn registry() -> &'static Mutex<BTreeMap<String, Arc<AgentMetrics>>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<String, Arc<AgentMetrics>>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// The metrics for `agent`, created on first use. They survive config reloads.
+pub fn agent(agent: &str) -> Arc<AgentMetrics> {
+    registry()
+        .lock()
+        .unwrap()
+        .entry(agent.to_string())
+        .or_default()
+        .clone()
+}
+
+/// Forget agents that are no longer configured.
+pub fn retain(names: &[String]) {
+    registry()
+        .lock()
+        .unwrap()
+        .retain(|name, _| names.contains(name));
+}
+
+type Field = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&AgentMetrics) -> i64,
+);
+
+const FIELDS: &[Field] = &[
+    ("polls", "counter", "Notification polls attempted", |m| {
+        m.polls.load(Ordering::Relaxed) as i64
+    }),
+    (
+        "poll_failures",
+        "counter",
+        "Polls that failed to reach the server",
+        |m| m.poll_failures.load(Ordering::Relaxed) as i64,
+    ),
+    (
+        "notifications_seen",
+        "counter",
+        "Distinct unread notifications seen",
+        |m| m.notifications_seen.load(Ordering::Relaxed) as i64,
+    ),
+    ("wakes_attempted", "counter", "Wakes started", |m| {
+        m.wakes_attempted.load(Ordering::Relaxed) as i64
+    }),
+    ("wakes_succeeded", "counter", "Wakes that succeeded", |m| {
+        m.wakes_succeeded.load(Ordering::Relaxed) as i64
+    }),
+    (
+        "wakes_failed",
+        "counter",
+        "Wakes that failed every retry",
+        |m| m.wakes_failed.load(Ordering::Relaxed) as i64,
+    ),
+    ("wake_retries", "counter", "Retries of failed wakes", |m| {
+        m.wake_retries.load(Ordering::Relaxed) as i64
+    }),
+    (
+        "heartbeat_failures",
+        "counter",
+        "Heartbeats that failed",
+        |m| m.heartbeat_failures.load(Ordering::Relaxed) as i64,
+    ),
+    (
+        "last_heartbeat_success",
+        "gauge",
+        "Unix time of the last successful heartbeat",
+        |m| m.last_heartbeat_success.load(Ordering::Relaxed),
+    ),
+    (
+        "last_wake_success",
+        "gauge",
+        "Unix time of the last successful wake",
+        |m| m.last_wake_success.load(Ordering::Relaxed),
+    ),
+];
+
+/// Every agent's metrics in the Prometheus text exposition format.
+pub fn prometheus() -> String {
+    let agents = registry().lock().unwrap();
+    let mut out = String::new();
+    for (field, kind, help, read) in FIELDS {
+        let suffix = if *kind == "counter" { "_total" } else { "" };
+        let name = format!("opengate_bridge_{}{}", field, suffix);
+        out.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} {}\n",
+            name, help, name, kind
+        ));
+        for (agent, metrics) in agents.iter() {
+            out.push_str(&format!(
+                "{}{{agent=\"{}\"}} {}\n",
+                name,
+                escape_label(agent),
+                read(metrics)
+            ));
+        }
+    }
+    out
+}
+
+/// Every agent's metrics as JSON.
+pub fn status() -> serde_json::Value {
+    let agents = registry().lock().unwrap();
+    let agents: serde_json::Map<String, serde_json::Value> = agents
+        .iter()
+        .map(|(agent, metrics)| {
+            let fields = FIELDS
+                .iter()
+                .map(|(field, _, _, read)| (field.to_string(), read(metrics).into()))
+                .collect();
+            (agent.clone(), serde_json::Value::Object(fields))
+        })
+        .collect();
+    serde_json::json!({ "agents": agents })
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serve `/metrics` and `/status` on `listener` until the process exits.
+pub async fn serve(listener: TcpListener) {
+    loop {
+        let Ok((mut socket, _)) = listener.accept().await else {
+            continue;
+        };
+        tokio::spawn(async move {
+            let mut buf = [0u8; 2048];
+            let Ok(n) = socket.read(&mut buf).await else {
+                return;
+            };
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let (status, content_type, body) = match path {
+                "/metrics" => ("200 OK", "text/plain; version=0.0.4", prometheus()),
+                "/status" => ("200 OK", "application/json", status().to_string()),
+                _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+    }
+}
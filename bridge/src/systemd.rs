@@ -0,0 +1,50 @@
+//! `sd_notify` for running under systemd with `Type=notify`: readiness, reload and
+//! shutdown states, and watchdog pings when the unit sets `WatchdogSec=`. Everything is
+//! a no-op outside systemd.
+
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+/// Send `state` (e.g. `READY=1`) to the service manager, if there is one.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+    let path = path.to_string_lossy();
+    let sent = match path.strip_prefix('@') {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        None => socket.send_to(state.as_bytes(), path.as_ref()),
+    };
+    if let Err(e) = sent {
+        eprintln!("sd_notify error: {}", e);
+    }
+}
+
+/// How often to ping the watchdog: half of `WatchdogSec=`, or `None` when it's off or
+/// meant for another process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Ping the watchdog until the process exits. The pings come from the same runtime as
+/// the agent loops, so a wedged runtime stops them and systemd restarts the bridge.
+pub async fn run_watchdog(interval: Duration) {
+    let mut tick = tokio::time::interval(interval);
+    loop {
+        tick.tick().await;
+        notify("WATCHDOG=1");
+    }
+}
@@ -0,0 +1,238 @@
+//! Wake message templates: a small subset of Handlebars over the notification list.
+//!
+//! `{{path.to.value}}` inserts a value (no HTML escaping — the output is plain text),
+//! `{{#each notifications}}…{{/each}}` repeats for each element with it as `this` and
+//! its position as `@index` (and `@number`, counting from 1), and
+//! `{{#if task_id}}…{{else}}…{{/if}}` tests for a value that isn't missing, null,
+//! false, 0, "" or empty. Names resolve from the innermost `#each` outwards.
+
+use serde_json::Value;
+
+enum Node {
+    Text(String),
+    Var(String),
+    Each(String, Vec<Node>),
+    If(String, Vec<Node>, Vec<Node>),
+}
+
+pub struct Template(Vec<Node>);
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut rest = source;
+        let (nodes, end) = parse_nodes(&mut rest)?;
+        match end {
+            None => Ok(Self(nodes)),
+            Some(tag) => Err(format!("unexpected {{{{{}}}}}", tag)),
+        }
+    }
+
+    pub fn render(&self, context: &Value) -> String {
+        let mut out = String::new();
+        render_nodes(&self.0, &[Scope::root(context)], &mut out);
+        out
+    }
+}
+
+/// Parse until the end of input or a closing tag (`/each`, `/if`, `else`), which is
+/// returned.
+fn parse_nodes(rest: &mut &str) -> Result<(Vec<Node>, Option<String>), String> {
+    let mut nodes = Vec::new();
+    loop {
+        let Some(start) = rest.find("{{") else {
+            if !rest.is_empty() {
+                nodes.push(Node::Text(rest.to_string()));
+            }
+            *rest = "";
+            return Ok((nodes, None));
+        };
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_string()));
+        }
+        let after = &rest[start + 2..];
+        let triple = after.starts_with('{');
+        let (open, close) = if triple { (3, "}}}") } else { (2, "}}") };
+        let end = rest[start + open..]
+            .find(close)
+            .ok_or_else(|| "unclosed {{".to_string())?;
+        let tag = rest[start + open..start + open + end].trim().to_string();
+        *rest = &rest[start + open + end + close.len()..];
+
+        if let Some(block) = tag.strip_prefix('#') {
+            let (helper, arg) = block.split_once(char::is_whitespace).unwrap_or((block, ""));
+            let arg = arg.trim().to_string();
+            let (body, end) = parse_nodes(rest)?;
+            match (helper, end.as_deref()) {
+                ("each", Some("/each")) => nodes.push(Node::Each(arg, body)),
+                ("if", Some("/if")) => nodes.push(Node::If(arg, body, Vec::new())),
+                ("if", Some("else")) => {
+                    let (otherwise, end) = parse_nodes(rest)?;
+                    if end.as_deref() != Some("/if") {
+                        return Err("{{else}} without a closing {{/if}}".to_string());
+                    }
+                    nodes.push(Node::If(arg, body, otherwise));
+                }
+                ("each" | "if", _) => return Err(format!("{{{{#{}}}}} is not closed", helper)),
+                _ => return Err(format!("unknown block {{{{#{}}}}}", helper)),
+            }
+        } else if tag.starts_with('/') || tag == "else" {
+            return Ok((nodes, Some(tag)));
+        } else if !tag.starts_with('!') {
+            nodes.push(Node::Var(tag));
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    this: &'a Value,
+    index: Option<usize>,
+}
+
+impl<'a> Scope<'a> {
+    fn root(this: &'a Value) -> Self {
+        Self { this, index: None }
+    }
+}
+
+fn lookup(scopes: &[Scope], path: &str) -> Value {
+    let innermost = scopes.last().unwrap();
+    match path {
+        "@index" => return innermost.index.map(Value::from).unwrap_or_default(),
+        "@number" => {
+            return innermost
+                .index
+                .map(|i| Value::from(i + 1))
+                .unwrap_or_default()
+        }
+        "this" | "." => return innermost.this.clone(),
+        _ => {}
+    }
+    let (path, only_innermost) = match path.strip_prefix("this.") {
+        Some(rest) => (rest, true),
+        None => (path, false),
+    };
+    let found = if only_innermost {
+        resolve(innermost.this, path)
+    } else {
+        scopes
+            .iter()
+            .rev()
+            .find_map(|scope| resolve(scope.this, path))
+    };
+    found.cloned().unwrap_or_default()
+}
+
+fn resolve<'a>(root: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(root, |value, key| match value {
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => value.get(key),
+    })
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+fn render_nodes(nodes: &[Node], scopes: &[Scope], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(path) => match lookup(scopes, path) {
+                Value::Null => {}
+                Value::String(s) => out.push_str(&s),
+                other => out.push_str(&other.to_string()),
+            },
+            Node::Each(path, body) => {
+                let Value::Array(items) = lookup(scopes, path) else {
+                    continue;
+                };
+                for (i, item) in items.iter().enumerate() {
+                    let mut inner = scopes.to_vec();
+                    inner.push(Scope {
+                        this: item,
+                        index: Some(i),
+                    });
+                    render_nodes(body, &inner, out);
+                }
+            }
+            Node::If(path, then, otherwise) => {
+                let branch = if truthy(&lookup(scopes, path)) {
+                    then
+                } else {
+                    otherwise
+                };
+                render_nodes(branch, scopes, out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(source: &str, context: &Value) -> String {
+        Template::parse(source).unwrap().render(context)
+    }
+
+    #[test]
+    fn renders_values_loops_and_conditionals() {
+        let context = json!({
+            "agent": "builder",
+            "count": 2,
+            "notifications": [
+                {"title": "Fix CI", "task_id": "t1"},
+                {"title": "Review", "task_id": null},
+            ],
+        });
+        assert_eq!(render("{{agent}}: {{count}}", &context), "builder: 2");
+        assert_eq!(
+            render(
+                "{{#each notifications}}{{@number}}. {{title}}{{#if task_id}} ({{task_id}}){{else}} (no task){{/if}} for {{agent}}\n{{/each}}",
+                &context
+            ),
+            "1. Fix CI (t1) for builder\n2. Review (no task) for builder\n"
+        );
+        assert_eq!(
+            render(
+                "{{notifications.0.title}}|{{missing}}|{{! a comment }}|{{{agent}}}",
+                &context
+            ),
+            "Fix CI|||builder"
+        );
+        // `this.` looks only at the element, not the scopes around it
+        assert_eq!(
+            render(
+                "{{#each notifications}}[{{@index}}{{this.agent}}]{{/each}}",
+                &context
+            ),
+            "[0][1]"
+        );
+        assert_eq!(
+            render("{{#if count}}yes{{/if}}{{#if none}}no{{/if}}", &context),
+            "yes"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for source in [
+            "{{#each notifications}}unclosed",
+            "{{#if a}}x{{else}}y",
+            "{{/if}}",
+            "{{#with agent}}{{/with}}",
+            "{{agent",
+        ] {
+            assert!(Template::parse(source).is_err(), "{}", source);
+        }
+    }
+}
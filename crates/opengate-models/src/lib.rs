@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 // --- Enums ---
 
@@ -134,6 +135,9 @@ pub struct Project {
     pub join_mode: Option<String>,
     pub cta_enabled: Option<bool>,
     pub is_public: Option<bool>,
+    /// Reject knowledge writes missing their category template's required sections
+    #[serde(default)]
+    pub enforce_knowledge_templates: bool,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -158,10 +162,12 @@ pub struct Task {
     pub artifacts: Vec<TaskArtifact>,
     /// ISO8601 datetime: task stays in backlog until this passes
     pub scheduled_at: Option<String>,
-    /// JSON recurrence rule: {frequency, interval, cron, ...}
+    /// JSON recurrence rule: {frequency, interval, cron, ...} or {rrule: "FREQ=...;BYDAY=..."}
     pub recurrence_rule: Option<serde_json::Value>,
     /// Points to the original recurring task (parent)
     pub recurrence_parent_id: Option<String>,
+    /// True if this is a recurrence parent whose series is paused (no new occurrences)
+    pub recurrence_paused: bool,
     /// IDs of tasks this task depends on (loaded from task_dependencies)
     pub dependencies: Vec<String>,
     /// True if this task has blocking open questions
@@ -173,6 +179,9 @@ pub struct Task {
     pub updated_at: String,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub activities: Vec<TaskActivity>,
+    /// Knowledge entries linked to this task (loaded with the full task view)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub knowledge: Vec<KnowledgeSummary>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -204,7 +213,10 @@ pub struct Agent {
     pub api_key_hash: String,
     pub skills: Vec<String>,
     pub description: Option<String>,
+    /// available | busy | offline — routing status
     pub status: String,
+    /// online | idle | stale | offline — graduated heartbeat freshness
+    pub presence: String,
     pub max_concurrent_tasks: i64,
     pub current_task_count: i64,
     /// Count of tasks where this agent is reviewer and status = review
@@ -212,6 +224,9 @@ pub struct Agent {
     pub webhook_url: Option<String>,
     /// Optional JSON array of event types to push via webhook. If null/empty, all events trigger push.
     pub webhook_events: Option<Vec<String>>,
+    /// Optional JSON body template for webhook pushes; `{{field.path}}` placeholders are
+    /// filled from the event. Replaces the default payload when set.
+    pub webhook_template: Option<serde_json::Value>,
     pub config: Option<serde_json::Value>,
     pub model: Option<String>,
     pub provider: Option<String>,
@@ -229,6 +244,35 @@ pub struct Agent {
     pub owner_id: Option<String>,
     /// Free-form category tags (e.g. ["rust", "frontend", "devops"])
     pub tags: Vec<String>,
+    /// Max tasks the agent may take on (claim or assignment) per UTC day. None = unlimited.
+    pub daily_task_quota: Option<i64>,
+    /// Max tasks the agent may take on per ISO week (Monday 00:00 UTC). None = unlimited.
+    pub weekly_task_quota: Option<i64>,
+    /// Notification preferences keyed by event type, with "*" as the fallback
+    pub notification_preferences: BTreeMap<String, NotificationPreference>,
+    /// Last status reported with a heartbeat, if the agent sends one
+    pub heartbeat_status: Option<HeartbeatStatus>,
+}
+
+/// Longest accepted heartbeat `status_message`, in characters.
+pub const MAX_STATUS_MESSAGE_CHARS: usize = 500;
+
+/// Optional body of `POST /api/agents/heartbeat`. Sending one replaces the agent's
+/// stored `heartbeat_status`; a bare heartbeat only refreshes `last_seen_at`.
+#[derive(Debug, Default, Deserialize)]
+pub struct HeartbeatRequest {
+    /// Self-reported load (e.g. active jobs, or 0.0–1.0 utilisation)
+    pub load: Option<f64>,
+    pub version: Option<String>,
+    pub status_message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeartbeatStatus {
+    pub load: Option<f64>,
+    pub version: Option<String>,
+    pub status_message: Option<String>,
+    pub reported_at: String,
 }
 
 // --- DTOs ---
@@ -254,6 +298,7 @@ pub struct UpdateProject {
     pub join_mode: Option<String>,
     pub cta_enabled: Option<bool>,
     pub is_public: Option<bool>,
+    pub enforce_knowledge_templates: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -308,9 +353,11 @@ pub struct ScheduledTaskEntry {
     pub priority: String,
     pub scheduled_at: String,
     pub assignee_id: Option<String>,
+    /// True if the task belongs to a paused recurring series
+    pub recurrence_paused: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct CreateAgent {
     pub name: String,
     pub skills: Option<Vec<String>>,
@@ -382,7 +429,7 @@ pub struct CreateActivity {
     pub mentions: Option<Vec<String>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct TaskFilters {
     pub project_id: Option<String>,
     pub status: Option<String>,
@@ -391,6 +438,13 @@ pub struct TaskFilters {
     pub tag: Option<String>,
 }
 
+/// `?format=` on endpoints that can also answer in CSV
+#[derive(Debug, Deserialize)]
+pub struct FormatQuery {
+    /// json (default) | csv
+    pub format: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct BatchStatusUpdate {
     pub updates: Vec<BatchStatusItem>,
@@ -402,6 +456,15 @@ pub struct BatchStatusItem {
     pub status: String,
 }
 
+/// Response for skipping the upcoming occurrence of a recurring series
+#[derive(Debug, Serialize)]
+pub struct RecurrenceSkipResult {
+    /// The occurrence that was skipped (now cancelled)
+    pub skipped: Task,
+    /// The occurrence scheduled in its place, if the series continues
+    pub next: Option<Task>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct BatchResult {
     pub succeeded: Vec<String>,
@@ -414,6 +477,17 @@ pub struct BatchError {
     pub error: String,
 }
 
+/// Filters for `/api/stats`; all optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct StatsQuery {
+    pub project_id: Option<String>,
+    pub tag: Option<String>,
+    /// YYYY-MM-DD, inclusive
+    pub from: Option<String>,
+    /// YYYY-MM-DD, inclusive
+    pub to: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct DashboardStats {
     pub tasks_by_status: std::collections::HashMap<String, i64>,
@@ -423,6 +497,254 @@ pub struct DashboardStats {
     pub recent_activity: Vec<TaskActivity>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TimeseriesQuery {
+    /// tasks_completed | tasks_created | tasks_blocked
+    pub metric: Option<String>,
+    /// day (default) | week; weeks start on Monday
+    pub interval: Option<String>,
+    /// First day, YYYY-MM-DD (default: 29 days before `to`)
+    pub from: Option<String>,
+    /// Last day, inclusive (default: today, UTC)
+    pub to: Option<String>,
+    pub project_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeseriesPoint {
+    /// First day of the bucket
+    pub date: String,
+    pub count: i64,
+}
+
+/// A metric counted per day or week, every bucket in range present (zero when empty).
+#[derive(Debug, Clone, Serialize)]
+pub struct Timeseries {
+    pub metric: String,
+    pub interval: String,
+    pub from: String,
+    pub to: String,
+    pub points: Vec<TimeseriesPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BurndownQuery {
+    /// First day, YYYY-MM-DD (default: 29 days before `to`)
+    pub from: Option<String>,
+    /// Last day, inclusive (default: today, UTC)
+    pub to: Option<String>,
+    /// count (default) | estimate: sum each task's numeric `context.estimate`, 1 when unset
+    pub weight: Option<String>,
+    /// Length of one iteration for velocity, from `from` on (default 7)
+    pub iteration_days: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BurndownPoint {
+    pub date: String,
+    /// Open work at the end of the day; cancelled tasks leave the scope
+    pub remaining: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IterationVelocity {
+    pub start: String,
+    /// Last day, inclusive; the final iteration may be cut short by `to`
+    pub end: String,
+    /// Work that was open at the start and done by the end
+    pub completed: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Burndown {
+    pub project_id: String,
+    pub weight: String,
+    pub from: String,
+    pub to: String,
+    pub points: Vec<BurndownPoint>,
+    pub iterations: Vec<IterationVelocity>,
+    /// Mean `completed` over full-length iterations (all of them when none is full)
+    pub average_velocity: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewStatsQuery {
+    /// Count reviews submitted in the last `days` days (default 30, at most 365);
+    /// reviews still pending are always counted
+    pub days: Option<i64>,
+    pub project_id: Option<String>,
+}
+
+/// Review turnaround for one reviewer or project. A review is one stint of a task in
+/// `review`; its first response is the reviewer starting it, or else its verdict.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewLatency {
+    /// Reviewer agent id, or project id
+    pub id: String,
+    pub name: Option<String>,
+    /// Reviews that have left `review`
+    pub completed: i64,
+    pub pending: i64,
+    pub avg_time_to_first_review_secs: Option<i64>,
+    /// Over completed reviews
+    pub avg_time_in_review_secs: Option<i64>,
+    /// How long the longest-waiting pending review has been in `review`
+    pub oldest_pending_secs: Option<i64>,
+}
+
+/// Slowest first: by average time to first review, then longest wait.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewLatencyReport {
+    pub days: i64,
+    pub reviewers: Vec<ReviewLatency>,
+    pub projects: Vec<ReviewLatency>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CostStatsQuery {
+    /// cost_tier (default) | model | project | agent
+    pub group_by: Option<String>,
+    /// day (default) | week
+    pub interval: Option<String>,
+    /// YYYY-MM-DD, default 29 days before `to`
+    pub from: Option<String>,
+    /// YYYY-MM-DD, default today (UTC)
+    pub to: Option<String>,
+    pub project_id: Option<String>,
+}
+
+/// Usage reported in one period by one group. `group` is null for usage reported
+/// without a tier or model.
+#[derive(Debug, Clone, Serialize)]
+pub struct CostBucket {
+    /// First day of the period
+    pub period: String,
+    pub group: Option<String>,
+    /// Distinct tasks the usage was reported against
+    pub tasks: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: f64,
+}
+
+/// Reported spend over time for `/api/stats/costs`; periods without usage are left out.
+#[derive(Debug, Clone, Serialize)]
+pub struct CostReport {
+    pub group_by: String,
+    pub interval: String,
+    pub from: String,
+    pub to: String,
+    pub total_cost_usd: f64,
+    /// By period, then by cost, highest first
+    pub buckets: Vec<CostBucket>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockedTimeQuery {
+    pub project_id: Option<String>,
+    /// Length of the top blockers and most-blocked task lists (default 10, at most 100)
+    pub limit: Option<i64>,
+}
+
+/// Cumulative time a task has spent `blocked`, and open while waiting on unfinished
+/// dependencies. The two can overlap.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskBlockedTime {
+    pub task_id: String,
+    pub title: String,
+    pub project_id: String,
+    pub status: String,
+    pub blocked_secs: i64,
+    pub blocked_by_deps_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectBlockedTime {
+    pub project_id: String,
+    pub name: String,
+    /// Tasks with any blocked or dependency-wait time
+    pub tasks: i64,
+    pub blocked_secs: i64,
+    pub blocked_by_deps_secs: i64,
+}
+
+/// A dependency by how long the open tasks depending on it have waited for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Blocker {
+    pub task_id: String,
+    pub title: String,
+    pub project_id: String,
+    pub status: String,
+    /// Tasks that waited on it
+    pub dependents: i64,
+    pub dependent_wait_secs: i64,
+}
+
+/// Blocked-time accounting for `/api/stats/blocked`, replayed from status history and
+/// dependency creation times. Each list is largest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockedTimeReport {
+    pub projects: Vec<ProjectBlockedTime>,
+    pub top_blockers: Vec<Blocker>,
+    pub most_blocked: Vec<TaskBlockedTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgentStatsQuery {
+    /// Throughput window in days (default 7, at most 365)
+    pub days: Option<i64>,
+}
+
+/// One agent's load and recent output, for `/api/stats/agents`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentWorkload {
+    pub agent_id: String,
+    pub name: String,
+    pub role: String,
+    pub presence: String,
+    /// Assigned but not started: backlog, todo, blocked or handoff
+    pub open: i64,
+    pub in_progress: i64,
+    /// Assigned tasks waiting on a reviewer
+    pub in_review: i64,
+    /// Tasks in review with this agent as the reviewer
+    pub reviewing: i64,
+    /// Assigned tasks that reached done in the window
+    pub completed: i64,
+    pub throughput_per_day: f64,
+    pub last_seen_at: Option<String>,
+    /// None when the agent has never been seen
+    pub seconds_since_heartbeat: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentWorkloadStats {
+    pub days: i64,
+    pub agents: Vec<AgentWorkload>,
+}
+
+/// One capped resource of a tenant.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QuotaUsage {
+    pub used: i64,
+    /// None = unlimited
+    pub limit: Option<u64>,
+}
+
+/// What a tenant is using against its `[tenant_limits]`, for `/api/tenant/usage`.
+/// Daily counts reset at 00:00 UTC.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TenantUsage {
+    pub tenant_id: String,
+    /// Projects that aren't archived
+    pub projects: QuotaUsage,
+    /// Tasks that aren't done or cancelled
+    pub open_tasks: QuotaUsage,
+    pub agents: QuotaUsage,
+    pub events_today: QuotaUsage,
+    pub webhook_deliveries_today: QuotaUsage,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ProjectWithStats {
     pub project: Project,
@@ -436,6 +758,48 @@ pub struct AgentCreated {
     pub api_key: String,
 }
 
+/// Restrictions on an additional API key. An all-default scope is as powerful as the
+/// agent's primary key.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyScope {
+    /// Only GET requests are allowed
+    #[serde(default)]
+    pub read_only: bool,
+    /// Only routes under this project (and its tasks) are allowed
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// Agent, key, trigger, integration, import and project management routes are refused
+    #[serde(default)]
+    pub no_admin: bool,
+}
+
+/// An additional, optionally scoped API key minted for an agent. The secret is only
+/// returned once, at creation.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub agent_id: String,
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub scope: KeyScope,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKey {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub scope: KeyScope,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyCreated {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    pub api_key: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CompleteRequest {
     pub summary: Option<String>,
@@ -528,6 +892,8 @@ pub struct UpdateAgent {
     pub webhook_url: Option<String>,
     /// JSON array of event types to subscribe to for webhook push. null = all events.
     pub webhook_events: Option<Vec<String>>,
+    /// JSON body template for webhook pushes (e.g. Slack blocks); `{}` removes it
+    pub webhook_template: Option<serde_json::Value>,
     pub config: Option<serde_json::Value>,
     pub model: Option<String>,
     pub provider: Option<String>,
@@ -539,6 +905,12 @@ pub struct UpdateAgent {
     pub stale_timeout: Option<i64>,
     /// Free-form category tags (e.g. ["rust", "frontend", "devops"])
     pub tags: Option<Vec<String>>,
+    /// Tasks per UTC day; 0 removes the quota
+    pub daily_task_quota: Option<i64>,
+    /// Tasks per ISO week; 0 removes the quota
+    pub weekly_task_quota: Option<i64>,
+    /// Replaces the whole preference map; an empty map restores the defaults
+    pub notification_preferences: Option<BTreeMap<String, NotificationPreference>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -565,8 +937,109 @@ pub struct RequestChangesRequest {
 // --- Knowledge Base ---
 
 /// Valid category values for knowledge entries.
-pub const VALID_CATEGORIES: &[&str] =
-    &["architecture", "pattern", "gotcha", "decision", "reference"];
+pub const VALID_CATEGORIES: &[&str] = &[
+    "architecture",
+    "pattern",
+    "gotcha",
+    "decision",
+    "reference",
+    "runbook",
+];
+
+/// Suggested structure for entries of one category. Projects with
+/// `enforce_knowledge_templates` require every `required_sections` heading.
+#[derive(Debug, Clone, Serialize)]
+pub struct KnowledgeTemplate {
+    pub category: &'static str,
+    pub description: &'static str,
+    pub required_sections: &'static [&'static str],
+    /// Markdown skeleton to start an entry from
+    pub content: String,
+}
+
+const TEMPLATE_SECTIONS: &[(&str, &str, &[&str])] = &[
+    (
+        "architecture",
+        "How a component is put together and why",
+        &["Overview", "Components", "Data flow", "Trade-offs"],
+    ),
+    (
+        "pattern",
+        "A reusable way of solving a recurring problem",
+        &["Problem", "Solution", "Example", "When not to use"],
+    ),
+    (
+        "gotcha",
+        "A pitfall that has bitten someone before",
+        &["Symptom", "Cause", "Fix", "How to avoid"],
+    ),
+    (
+        "decision",
+        "Architecture decision record",
+        &[
+            "Context",
+            "Decision",
+            "Alternatives considered",
+            "Consequences",
+        ],
+    ),
+    (
+        "reference",
+        "Facts to look up: endpoints, limits, credentials locations",
+        &["Summary", "Details"],
+    ),
+    (
+        "runbook",
+        "Step-by-step operational procedure",
+        &[
+            "When to use",
+            "Prerequisites",
+            "Steps",
+            "Verification",
+            "Rollback",
+        ],
+    ),
+];
+
+/// Templates for every category, in `VALID_CATEGORIES` order.
+pub fn knowledge_templates() -> Vec<KnowledgeTemplate> {
+    TEMPLATE_SECTIONS
+        .iter()
+        .map(|&(category, description, sections)| KnowledgeTemplate {
+            category,
+            description,
+            required_sections: sections,
+            content: sections
+                .iter()
+                .map(|s| format!("## {}\n\n", s))
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+                + "\n",
+        })
+        .collect()
+}
+
+/// Required sections of `category`'s template with no matching markdown heading in
+/// `content` (any level, case-insensitive). Empty for categories without a template.
+pub fn missing_template_sections(category: Option<&str>, content: &str) -> Vec<&'static str> {
+    let Some(&(_, _, sections)) = TEMPLATE_SECTIONS
+        .iter()
+        .find(|(c, _, _)| Some(*c) == category)
+    else {
+        return Vec::new();
+    };
+    let headings: Vec<String> = content
+        .lines()
+        .filter_map(|l| l.trim_start().strip_prefix('#'))
+        .map(|h| h.trim_start_matches('#').trim().to_lowercase())
+        .collect();
+    sections
+        .iter()
+        .copied()
+        .filter(|s| !headings.iter().any(|h| h == &s.to_lowercase()))
+        .collect()
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KnowledgeEntry {
@@ -578,12 +1051,100 @@ pub struct KnowledgeEntry {
     pub metadata: Option<serde_json::Value>,
     /// Tag list stored as JSON array in SQLite.
     pub tags: Vec<String>,
-    /// Optional category: architecture | pattern | gotcha | decision | reference
+    /// Optional category: architecture | pattern | gotcha | decision | reference | runbook
     pub category: Option<String>,
     pub created_by_type: String,
     pub created_by_id: String,
+    /// Starts at 1 and increases with every write (including reverts)
+    pub version: i64,
+    /// Review deadline (UTC); once it passes the entry is `stale` until reviewed
+    pub review_after: Option<String>,
+    /// True when `review_after` has passed — treat the content as possibly outdated
+    pub stale: bool,
     pub updated_at: String,
     pub created_at: String,
+    /// Files/URLs attached to the entry (loaded when fetching a single entry)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<KnowledgeAttachment>,
+}
+
+/// An artifact attached to a knowledge entry. Same types and limits as task artifacts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeAttachment {
+    pub id: String,
+    pub knowledge_id: String,
+    pub name: String,
+    pub artifact_type: String,
+    pub value: String,
+    pub created_by_type: String,
+    pub created_by_id: String,
+    pub created_at: String,
+}
+
+/// A past (or the current) revision of a knowledge entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeVersion {
+    pub version: i64,
+    pub title: String,
+    pub content: String,
+    pub metadata: Option<serde_json::Value>,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub author_type: String,
+    pub author_id: String,
+    pub created_at: String,
+}
+
+/// Short form of a knowledge entry, as linked from a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeSummary {
+    pub key: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkKnowledge {
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevertKnowledge {
+    pub version: i64,
+}
+
+/// Outcome of importing knowledge. Entries whose content already matches are left
+/// alone, so re-importing an unchanged export doesn't bump versions.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KnowledgeImportResult {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    /// Existing entries left untouched by `on_conflict=skip`
+    #[serde(default)]
+    pub skipped: Vec<String>,
+}
+
+/// One entry of a JSON import: the upsert body plus its key.
+#[derive(Debug, Deserialize)]
+pub struct ImportKnowledgeEntry {
+    pub key: String,
+    #[serde(flatten)]
+    pub entry: UpsertKnowledge,
+}
+
+/// How an import treats keys that already exist:
+/// - `overwrite` (default): replace the entry, like a PUT
+/// - `skip`: leave the existing entry untouched
+/// - `merge`: take title/content from the import, union tags, merge metadata objects,
+///   and keep the existing category / review deadline when the import has none
+pub const KNOWLEDGE_CONFLICT_STRATEGIES: &[&str] = &["overwrite", "skip", "merge"];
+
+#[derive(Debug, Deserialize)]
+pub struct KnowledgeImportQuery {
+    pub on_conflict: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -593,8 +1154,12 @@ pub struct UpsertKnowledge {
     pub metadata: Option<serde_json::Value>,
     /// Tags to attach (optional, defaults to empty list on create).
     pub tags: Option<Vec<String>>,
-    /// Category: architecture | pattern | gotcha | decision | reference
+    /// Category: architecture | pattern | gotcha | decision | reference | runbook
     pub category: Option<String>,
+    /// Review deadline: RFC 3339 or YYYY-MM-DD. Omit to keep the current one, or pass
+    /// an empty string to clear it.
+    #[serde(default)]
+    pub review_after: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -605,6 +1170,65 @@ pub struct KnowledgeSearchQuery {
     pub tags: Option<String>,
     /// Filter by category: ?category=pattern
     pub category: Option<String>,
+    /// `project` (default) or `all` to include the shared knowledge space
+    pub scope: Option<String>,
+}
+
+/// A `[[key]]` reference from one entry to another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeEdge {
+    pub from: String,
+    pub to: String,
+}
+
+/// Every entry of a project and the references between them. References to keys with
+/// no entry are listed separately as `unresolved`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KnowledgeGraph {
+    pub nodes: Vec<KnowledgeSummary>,
+    pub edges: Vec<KnowledgeEdge>,
+    pub unresolved: Vec<KnowledgeEdge>,
+}
+
+/// Read counts for one entry.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KnowledgeUsage {
+    pub key: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub reads: i64,
+    /// Distinct agents/users that fetched the entry
+    pub readers: i64,
+    pub last_read_at: String,
+}
+
+/// Which entries of a project get read, and which never do (pruning candidates).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KnowledgeUsageStats {
+    pub total_entries: i64,
+    pub most_read: Vec<KnowledgeUsage>,
+    pub never_read: Vec<KnowledgeSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KnowledgeStatsQuery {
+    /// Max entries in `most_read` (default 10)
+    pub limit: Option<i64>,
+    /// Only count readers of this type: agent | human
+    pub reader_type: Option<String>,
+}
+
+/// Pseudo project id holding organization-wide knowledge (one space per tenant).
+/// Shared entries report this as their `project_id`.
+pub fn shared_knowledge_scope(tenant: Option<&str>) -> String {
+    match tenant {
+        Some(t) => format!("shared:{}", t),
+        None => "shared".to_string(),
+    }
+}
+
+pub fn is_shared_knowledge_scope(project_id: &str) -> bool {
+    project_id == "shared" || project_id.starts_with("shared:")
 }
 
 // --- Task Artifacts ---
@@ -630,6 +1254,47 @@ pub struct CreateArtifact {
     pub value: String,
 }
 
+/// Tokens and spend an agent reports against a task, one row per report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskUsage {
+    pub id: String,
+    pub task_id: String,
+    pub agent_id: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: Option<f64>,
+    /// The agent's `cost_tier` and `model` when it reported
+    pub cost_tier: Option<String>,
+    pub model: Option<String>,
+    pub reported_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportUsage {
+    #[serde(default)]
+    pub input_tokens: i64,
+    #[serde(default)]
+    pub output_tokens: i64,
+    pub cost_usd: Option<f64>,
+}
+
+impl ReportUsage {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.input_tokens < 0 || self.output_tokens < 0 {
+            return Err("input_tokens and output_tokens must not be negative".to_string());
+        }
+        match self.cost_usd {
+            Some(cost) if !cost.is_finite() || cost < 0.0 => {
+                Err("cost_usd must be a non-negative number".to_string())
+            }
+            None if self.input_tokens == 0 && self.output_tokens == 0 => {
+                Err("Report input_tokens, output_tokens or cost_usd".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateArtifact {
     /// Optional new name for the artifact.
@@ -666,9 +1331,266 @@ pub struct Notification {
     /// Webhook delivery status: "delivered" | "failed" | null (not attempted)
     pub webhook_status: Option<String>,
     pub task_id: Option<String>,
+    /// Whether the bridge should wake the agent for this notification
+    pub wake: bool,
+    /// Whether an email relay should forward this notification
+    pub email: bool,
+    /// Expired unread by the agent's `archive_after_hours` policy
+    pub archived: bool,
     pub created_at: String,
 }
 
+/// What a pass of the notification expiry policies changed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NotificationSweep {
+    pub acknowledged: i64,
+    pub archived: i64,
+}
+
+/// How long history is kept, in days; 0 keeps it forever.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionPolicy {
+    /// Events no remaining notification refers to
+    pub events_days: i64,
+    /// Read (or archived) notifications
+    pub notifications_days: i64,
+    /// Outbound webhook deliveries and inbound trigger logs
+    pub webhook_logs_days: i64,
+}
+
+/// Server behaviours that can be changed at runtime via `/api/admin/settings`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeSettings {
+    /// Refuse to claim or start a task until its dependencies are done; when off, unmet
+    /// dependencies are only reported
+    pub enforce_dependencies: bool,
+    /// Pick a reviewer when a task is submitted for review without one; when off, it
+    /// waits in review unassigned
+    pub auto_assign_reviewers: bool,
+    /// Minutes without a heartbeat before an agent's tasks are released
+    pub stale_release_minutes: i64,
+}
+
+impl Default for RuntimeSettings {
+    fn default() -> Self {
+        Self {
+            enforce_dependencies: true,
+            auto_assign_reviewers: true,
+            stale_release_minutes: 240,
+        }
+    }
+}
+
+/// PATCH /api/admin/settings — only the given settings change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enforce_dependencies: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_assign_reviewers: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_release_minutes: Option<i64>,
+}
+
+/// A registered tenant: the organization behind an `owner_id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Tenant {
+    pub id: String,
+    pub name: String,
+    /// active | suspended — a suspended tenant's keys and setup tokens are refused
+    pub status: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// POST /api/admin/tenants
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateTenant {
+    /// The owner_id its projects, tasks and agents carry
+    pub id: String,
+    /// Defaults to the id
+    pub name: Option<String>,
+}
+
+impl CreateTenant {
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if self.id.is_empty() || self.id.len() > 64 || !self.id.chars().all(valid) {
+            return Err("id must be 1-64 letters, digits, '-' or '_'".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A tenant with what it holds, for the tenant admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantSummary {
+    #[serde(flatten)]
+    pub tenant: Tenant,
+    pub usage: TenantUsage,
+}
+
+/// A freshly minted tenant setup token; shown once, only its hash is stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantSetupToken {
+    pub tenant_id: String,
+    pub setup_token: String,
+}
+
+/// Rows a retention pass deleted.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionSweep {
+    pub events: i64,
+    pub notifications: i64,
+    pub webhook_logs: i64,
+}
+
+/// An event waiting in the outbox for an external broker to acknowledge it.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub topic: String,
+    /// Partitioning key (the event's project), so a project's events stay ordered
+    pub event_key: Option<String>,
+    pub payload: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
+/// Valid `min_priority` values, highest first.
+pub const NOTIFICATION_PRIORITIES: &[&str] = &["critical", "high", "medium", "low"];
+
+/// How an agent wants to be notified about one event type: one row of the routing
+/// matrix, with a flag per channel. Missing fields keep the default behaviour: notify
+/// in-app, push to the webhook, wake via the bridge, no email, no phone push, no
+/// priority floor, and unread notifications stay unread until the agent acks them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationPreference {
+    /// Record the notification in the agent's inbox. Without it no other channel fires.
+    #[serde(default = "default_true")]
+    pub in_app: bool,
+    /// Push the notification to the agent's webhook_url
+    #[serde(default = "default_true")]
+    pub webhook: bool,
+    /// Let the bridge wake the agent for it
+    #[serde(default = "default_true")]
+    pub wake: bool,
+    /// Flag the notification for email relay
+    #[serde(default)]
+    pub email: bool,
+    /// Buzz a phone through the agent's (or its owner's) ntfy / Pushover target
+    #[serde(default)]
+    pub push: bool,
+    /// Skip task notifications below this priority (critical | high | medium | low)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_priority: Option<String>,
+    /// Only push at or above this priority
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push_min_priority: Option<String>,
+    /// Acknowledge unread notifications automatically once they are this many hours old
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ack_after_hours: Option<i64>,
+    /// Archive unread notifications once they are this many hours old: they are marked
+    /// read and flagged `archived` instead of waking the agent when it comes back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_after_hours: Option<i64>,
+}
+
+impl Default for NotificationPreference {
+    fn default() -> Self {
+        Self {
+            in_app: true,
+            webhook: true,
+            wake: true,
+            email: false,
+            push: false,
+            min_priority: None,
+            push_min_priority: None,
+            ack_after_hours: None,
+            archive_after_hours: None,
+        }
+    }
+}
+
+impl NotificationPreference {
+    /// Built-in routing for `event_type` when the agent has no entry for it (nor "*").
+    /// Progress updates are informational and don't wake anyone; events where someone
+    /// is waiting on the agent — reviews, blockers, questions — are also flagged for email.
+    /// Only blocked tasks and critical questions nobody picked up buzz a phone.
+    pub fn default_for(event_type: &str) -> Self {
+        let mut pref = Self::default();
+        match event_type {
+            "task.progress" => {
+                pref.wake = false;
+                pref.ack_after_hours = Some(24);
+            }
+            "task.blocked" => {
+                pref.email = true;
+                pref.push = true;
+            }
+            "task.question_unrouted" => {
+                pref.push = true;
+                pref.push_min_priority = Some(Priority::Critical.as_str().to_string());
+            }
+            "task.review_requested"
+            | "task.changes_requested"
+            | "task.question_asked"
+            | "task.question_assigned"
+            | "task.question_rerouted" => pref.email = true,
+            _ => {}
+        }
+        pref
+    }
+
+    /// Resolve an agent's preference map for `event_type`: its own entry, then "*", then
+    /// [`NotificationPreference::default_for`].
+    pub fn resolve(prefs: &BTreeMap<String, NotificationPreference>, event_type: &str) -> Self {
+        prefs
+            .get(event_type)
+            .or_else(|| prefs.get("*"))
+            .cloned()
+            .unwrap_or_else(|| Self::default_for(event_type))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (field, value) in [
+            ("min_priority", &self.min_priority),
+            ("push_min_priority", &self.push_min_priority),
+        ] {
+            if let Some(p) = value.as_deref() {
+                if !NOTIFICATION_PRIORITIES.contains(&p) {
+                    return Err(format!(
+                        "Invalid {} '{}'. Must be one of: {}",
+                        field,
+                        p,
+                        NOTIFICATION_PRIORITIES.join(", ")
+                    ));
+                }
+            }
+        }
+        for (field, value) in [
+            ("ack_after_hours", self.ack_after_hours),
+            ("archive_after_hours", self.archive_after_hours),
+        ] {
+            if value.is_some_and(|h| h < 1) {
+                return Err(format!("{} must be at least 1", field));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_question_priority() -> String {
+    Priority::Medium.as_str().to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
 /// Carries information about a newly-inserted notification that may need webhook delivery.
 #[derive(Debug, Clone)]
 pub struct PendingNotifWebhook {
@@ -677,6 +1599,8 @@ pub struct PendingNotifWebhook {
     pub event_type: String,
     pub title: String,
     pub body: Option<String>,
+    /// Also send to the agent's phone push target
+    pub push: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -684,9 +1608,59 @@ pub struct NotificationQuery {
     pub unread: Option<bool>,
 }
 
-// --- Pulse ---
+#[derive(Debug, Deserialize)]
+pub struct RedeliverWebhooks {
+    /// Only these dead-lettered notifications; all of them when omitted
+    pub notification_ids: Option<Vec<i64>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookRedelivery {
+    /// Notifications queued for another delivery attempt
+    pub notification_ids: Vec<i64>,
+}
+
+/// Outcome of a synthetic webhook push.
+#[derive(Debug, Serialize)]
+pub struct WebhookTestResult {
+    pub url: String,
+    /// Whether the receiver answered 2xx
+    pub delivered: bool,
+    pub status_code: Option<u16>,
+    pub latency_ms: i64,
+    /// Start of the receiver's response body
+    pub response_body: Option<String>,
+    /// Transport error (connection refused, timeout, ...)
+    pub error: Option<String>,
+}
+
+/// Re-run notification routing over past events. At least one of the id bounds or
+/// `task_id` is required.
+#[derive(Debug, Deserialize)]
+pub struct EventReplayRequest {
+    pub from_event_id: Option<i64>,
+    pub to_event_id: Option<i64>,
+    pub task_id: Option<String>,
+    /// Only replay notifications for this agent
+    pub agent_id: Option<String>,
+    /// Also fire webhooks for the replayed notifications (default false)
+    #[serde(default)]
+    pub deliver_webhooks: bool,
+}
 
 #[derive(Debug, Serialize)]
+pub struct EventReplayResult {
+    pub events_replayed: i64,
+    /// Notifications routing produces now that didn't exist before
+    pub notifications_created: i64,
+    /// Existing unread notifications whose wake-up is sent again
+    pub notifications_redelivered: i64,
+    pub webhooks_fired: bool,
+}
+
+// --- Pulse ---
+
+#[derive(Debug, Default, Serialize)]
 pub struct PulseResponse {
     pub active_tasks: Vec<PulseTask>,
     pub blocked_tasks: Vec<PulseTask>,
@@ -695,8 +1669,14 @@ pub struct PulseResponse {
     pub unread_events: i64,
     pub agents: Vec<PulseAgent>,
     pub recent_knowledge_updates: Vec<PulseKnowledge>,
+    /// Entries whose `review_after` deadline has passed, oldest deadline first
+    pub knowledge_needing_review: Vec<PulseKnowledge>,
+    /// The project's most-read entries (top 5)
+    pub most_read_knowledge: Vec<PulseKnowledge>,
     /// Number of tasks currently blocked by unmet dependencies
     pub blocked_by_deps: i64,
+    /// Reviewers with pending reviews in this project, longest wait first (top 3)
+    pub slowest_review_queues: Vec<ReviewLatency>,
 }
 
 #[derive(Debug, Serialize)]
@@ -715,11 +1695,13 @@ pub struct PulseTask {
 pub struct PulseAgent {
     pub id: String,
     pub name: String,
+    /// online | idle | stale | offline
     pub status: String,
     pub seniority: String,
     pub role: String,
     pub current_task: Option<String>,
     pub last_seen_at: Option<String>,
+    pub heartbeat_status: Option<HeartbeatStatus>,
 }
 
 #[derive(Debug, Serialize)]
@@ -728,6 +1710,10 @@ pub struct PulseKnowledge {
     pub title: String,
     pub category: Option<String>,
     pub updated_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reads: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -736,6 +1722,31 @@ pub struct AgentQuery {
     pub seniority: Option<String>,
 }
 
+pub const DEFAULT_SUGGESTION_MIN_TASKS: i64 = 3;
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestedSkillsQuery {
+    /// Minimum completed tasks carrying a tag before it is suggested (default: 3)
+    pub min_tasks: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkillSuggestion {
+    pub name: String,
+    /// Completed tasks tagged with this skill
+    pub completed_tasks: i64,
+}
+
+/// Skills and capabilities inferred from the tags of an agent's completed tasks that
+/// are not yet on its profile. Tags of the form `domain:detail` become capabilities.
+#[derive(Debug, Clone, Serialize)]
+pub struct SuggestedSkills {
+    pub agent_id: String,
+    pub completed_tasks: i64,
+    pub skills: Vec<SkillSuggestion>,
+    pub capabilities: Vec<SkillSuggestion>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AgentMatchQuery {
     pub capability: Option<String>,
@@ -743,9 +1754,57 @@ pub struct AgentMatchQuery {
     pub role: Option<String>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct OffboardRequest {
+    /// Where to move the agent's work; omit to release it back to the pool
+    pub reassign_to: Option<AssignStrategy>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OffboardReassignment {
+    /// Task or question id
+    pub id: String,
+    pub agent_id: String,
+}
+
+/// An agent whose presence changed since the last sweep
+#[derive(Debug, Clone, Serialize)]
+pub struct PresenceChange {
+    pub agent_id: String,
+    pub agent_name: String,
+    pub owner_id: Option<String>,
+    pub from: String,
+    pub to: String,
+}
+
+/// What happened to an offboarded agent's work
+#[derive(Debug, Clone, Serialize)]
+pub struct OffboardResult {
+    pub agent_id: String,
+    pub reassigned_tasks: Vec<OffboardReassignment>,
+    pub released_tasks: Vec<String>,
+    pub reassigned_reviews: Vec<OffboardReassignment>,
+    pub cleared_reviews: Vec<String>,
+    pub rerouted_questions: Vec<OffboardReassignment>,
+    pub unrouted_questions: Vec<String>,
+}
+
+/// `seniority` and `explicit` are the original names, still accepted: `seniority` picks
+/// like `capability` within the `seniority` filter, `explicit` takes `agent_id`.
+pub const VALID_ASSIGN_STRATEGIES: &[&str] = &[
+    "capability",
+    "round_robin",
+    "least_loaded",
+    "highest_seniority",
+    "random",
+    "seniority",
+    "explicit",
+];
+
 /// Strategy for auto-assigning agents based on capability, seniority, or explicit ID
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssignStrategy {
+    /// capability | round_robin | least_loaded | highest_seniority | random | seniority | explicit
     pub strategy: String,
     pub capabilities: Option<Vec<String>>,
     pub seniority: Option<String>,
@@ -753,6 +1812,20 @@ pub struct AssignStrategy {
     pub agent_id: Option<String>,
 }
 
+impl AssignStrategy {
+    /// Why this strategy can't pick anyone, if it can't: an unknown name, or `explicit`
+    /// without an `agent_id`.
+    pub fn validate(&self) -> Result<(), String> {
+        if !VALID_ASSIGN_STRATEGIES.contains(&self.strategy.as_str()) {
+            return Err(format!("Unknown assignment strategy '{}'", self.strategy));
+        }
+        if self.strategy == "explicit" && self.agent_id.is_none() {
+            return Err("The explicit strategy needs an agent_id".to_string());
+        }
+        Ok(())
+    }
+}
+
 // --- Capability Targeting ---
 
 #[derive(Debug, Clone)]
@@ -766,7 +1839,9 @@ pub struct CapabilityTarget {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskQuestion {
     pub id: String,
-    pub task_id: String,
+    /// `None` for project-level questions that no task owns
+    pub task_id: Option<String>,
+    pub project_id: String,
     pub question: String,
     pub question_type: String,
     pub context: Option<String>,
@@ -777,11 +1852,62 @@ pub struct TaskQuestion {
     pub required_capability: Option<String>,
     pub status: String,
     pub blocking: bool,
+    /// critical | high | medium | low — orders inboxes and gates `min_priority`
+    /// notification preferences
+    #[serde(default = "default_question_priority")]
+    pub priority: String,
     pub resolved_by_type: Option<String>,
     pub resolved_by_id: Option<String>,
     pub resolution: Option<String>,
     pub created_at: String,
     pub resolved_at: Option<String>,
+    /// When the current target was set
+    #[serde(default)]
+    pub targeted_at: Option<String>,
+    /// Every target change, oldest first
+    #[serde(default)]
+    pub routing_history: Vec<QuestionRoute>,
+    /// Artifacts attached to the question itself (loaded when fetching a single question)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<QuestionAttachment>,
+}
+
+/// An artifact (log, screenshot, config snippet) attached to a question or one of its
+/// replies. Same types and limits as task artifacts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionAttachment {
+    pub id: String,
+    pub question_id: String,
+    /// Set when attached to a reply rather than the question
+    pub reply_id: Option<String>,
+    pub name: String,
+    pub artifact_type: String,
+    pub value: String,
+    pub created_by_type: String,
+    pub created_by_id: String,
+    pub created_at: String,
+}
+
+/// One routing step of a question. `target_*` are `None` when the question was left
+/// unrouted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionRoute {
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    /// asked | capability_match | assigned | target_offboarded | target_inactive | unrouted
+    pub reason: String,
+    pub routed_at: String,
+}
+
+/// A question moved off an inactive target by the re-routing sweep.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuestionReroute {
+    pub question_id: String,
+    pub task_id: Option<String>,
+    pub project_id: String,
+    pub from_agent_id: String,
+    /// `None` when no other capability match was available
+    pub to_agent_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -793,6 +1919,8 @@ pub struct CreateQuestion {
     pub target_id: Option<String>,
     pub required_capability: Option<String>,
     pub blocking: Option<bool>,
+    /// critical | high | medium | low; defaults to the owning task's priority
+    pub priority: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -815,13 +1943,63 @@ pub struct QuestionReply {
     pub author_name: Option<String>,
     pub body: String,
     pub is_resolution: bool,
+    /// The reply this one answers; `None` for top-level replies
+    pub parent_reply_id: Option<String>,
     pub created_at: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<QuestionAttachment>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateReply {
     pub body: String,
     pub is_resolution: Option<bool>,
+    pub parent_reply_id: Option<String>,
+}
+
+/// A reply with its nested answers, oldest first at every level.
+#[derive(Debug, Serialize)]
+pub struct ReplyThread {
+    #[serde(flatten)]
+    pub reply: QuestionReply,
+    pub replies: Vec<ReplyThread>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplyListQuery {
+    /// Return nested `ReplyThread`s instead of a flat chronological list
+    pub threaded: Option<bool>,
+}
+
+/// Nest replies under their parents. Replies whose parent is missing are
+/// treated as top-level so nothing drops out of the thread.
+pub fn build_reply_threads(replies: Vec<QuestionReply>) -> Vec<ReplyThread> {
+    let ids: std::collections::HashSet<String> = replies.iter().map(|r| r.id.clone()).collect();
+    let mut children: std::collections::HashMap<String, Vec<QuestionReply>> =
+        std::collections::HashMap::new();
+    let mut roots = Vec::new();
+    for reply in replies {
+        match reply.parent_reply_id.clone().filter(|p| ids.contains(p)) {
+            Some(parent) => children.entry(parent).or_default().push(reply),
+            None => roots.push(reply),
+        }
+    }
+    fn attach(
+        reply: QuestionReply,
+        children: &mut std::collections::HashMap<String, Vec<QuestionReply>>,
+    ) -> ReplyThread {
+        let replies = children
+            .remove(&reply.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| attach(child, children))
+            .collect();
+        ReplyThread { reply, replies }
+    }
+    roots
+        .into_iter()
+        .map(|root| attach(root, &mut children))
+        .collect()
 }
 
 #[derive(Debug, Deserialize)]
@@ -856,7 +2034,12 @@ pub struct InboxItem {
 pub struct InboxCapacity {
     pub max_concurrent_tasks: i64,
     pub current_active_tasks: i64,
+    /// False when either the concurrency limit or a task quota is exhausted
     pub has_capacity: bool,
+    pub daily_task_quota: Option<i64>,
+    pub tasks_today: i64,
+    pub weekly_task_quota: Option<i64>,
+    pub tasks_this_week: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -870,6 +2053,7 @@ pub struct AgentInbox {
     pub open_questions: Vec<InboxItem>,
     pub unread_notifications: Vec<InboxItem>,
     pub capacity: InboxCapacity,
+    pub heartbeat_status: Option<HeartbeatStatus>,
 }
 
 // ===== Inbound Webhook Triggers =====
@@ -884,10 +2068,40 @@ pub struct WebhookTrigger {
     pub enabled: bool,
     #[serde(default)]
     pub ip_allowlist: Vec<String>,
+    /// How inbound requests prove they're genuine; one of [`TRIGGER_VERIFICATIONS`]
+    #[serde(default = "default_trigger_verification")]
+    pub verification: String,
+    /// The provider's webhook secret, for every verification but `secret`
+    #[serde(default, skip_serializing)]
+    pub signing_secret: Option<String>,
+    /// Payload path (`payload.delivery_id`) whose value identifies a delivery; a repeat
+    /// within `dedup_window_hours` is logged as `deduplicated` instead of run
+    #[serde(default)]
+    pub dedup_key: Option<String>,
+    #[serde(default = "default_dedup_window_hours")]
+    pub dedup_window_hours: i64,
+    /// Most runs per minute; deliveries past it get 429 and are logged `rate_limited`
+    #[serde(default)]
+    pub rate_limit_per_minute: Option<i64>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+pub const DEFAULT_DEDUP_WINDOW_HOURS: i64 = 24;
+
+fn default_dedup_window_hours() -> i64 {
+    DEFAULT_DEDUP_WINDOW_HOURS
+}
+
+/// `secret`: our `X-Webhook-Secret` header. `github`: `X-Hub-Signature-256`. `gitlab`:
+/// `X-Gitlab-Token`. `stripe`: `Stripe-Signature`. The provider modes check against the
+/// trigger's `signing_secret`, so a stock provider webhook can point straight at it.
+pub const TRIGGER_VERIFICATIONS: &[&str] = &["secret", "github", "gitlab", "stripe"];
+
+fn default_trigger_verification() -> String {
+    "secret".to_string()
+}
+
 /// Returned only on creation (raw secret is not stored)
 #[derive(Debug, Serialize)]
 pub struct TriggerCreatedResponse {
@@ -900,6 +2114,12 @@ pub struct CreateTriggerRequest {
     pub name: String,
     pub action_type: String,
     pub action_config: serde_json::Value,
+    /// Defaults to `secret`
+    pub verification: Option<String>,
+    pub signing_secret: Option<String>,
+    pub dedup_key: Option<String>,
+    pub dedup_window_hours: Option<i64>,
+    pub rate_limit_per_minute: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -909,6 +2129,31 @@ pub struct UpdateTriggerRequest {
     pub action_config: Option<serde_json::Value>,
     pub enabled: Option<bool>,
     pub ip_allowlist: Option<Vec<String>>,
+    pub verification: Option<String>,
+    pub signing_secret: Option<String>,
+    /// Empty string turns dedup off
+    pub dedup_key: Option<String>,
+    pub dedup_window_hours: Option<i64>,
+    /// 0 turns the rate limit off
+    pub rate_limit_per_minute: Option<i64>,
+}
+
+/// Body of `POST /api/projects/:id/triggers/validate`: a trigger's action, checked
+/// against a sample webhook body without running it.
+#[derive(Debug, Deserialize)]
+pub struct ValidateTriggerRequest {
+    pub action_type: String,
+    pub action_config: serde_json::Value,
+    #[serde(default)]
+    pub sample_payload: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TriggerValidation {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    /// The action_config as each run would see it: one entry, or one per `for_each` item
+    pub preview: Vec<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -921,3 +2166,315 @@ pub struct WebhookTriggerLog {
     pub result: Option<serde_json::Value>,
     pub error: Option<String>,
 }
+
+// ===== Slack integration =====
+
+/// Event types posted to Slack when a project doesn't pick its own.
+pub const DEFAULT_SLACK_EVENTS: &[&str] = &[
+    "task.assigned",
+    "task.blocked",
+    "task.review_requested",
+    "task.completed",
+];
+
+/// A project's Slack channel. Events are posted through an incoming webhook, or — with a
+/// bot token — via `chat.postMessage`, which keeps each task in its own thread so replies
+/// can be ingested back with a `slack_thread_reply` trigger.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SlackIntegration {
+    pub project_id: String,
+    pub webhook_url: Option<String>,
+    #[serde(skip_serializing)]
+    pub bot_token: Option<String>,
+    pub channel: Option<String>,
+    /// Verifies `X-Slack-Signature` on inbound replies
+    #[serde(skip_serializing)]
+    pub signing_secret: Option<String>,
+    pub events: Vec<String>,
+    /// Base URL of the dashboard; task titles link to `<dashboard_url>/tasks/<id>`
+    pub dashboard_url: Option<String>,
+    /// Posting with the bot token: one thread per task, replies can be ingested
+    pub threaded: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Body of `PUT /api/projects/:id/integrations/slack`. Needs a `webhook_url`, or a
+/// `bot_token` with a `channel`.
+#[derive(Debug, Deserialize)]
+pub struct SetSlackIntegration {
+    pub webhook_url: Option<String>,
+    pub bot_token: Option<String>,
+    pub channel: Option<String>,
+    pub signing_secret: Option<String>,
+    /// Event types to post ("*" for all); defaults to [`DEFAULT_SLACK_EVENTS`]
+    pub events: Option<Vec<String>>,
+    pub dashboard_url: Option<String>,
+}
+
+/// A project's GitHub integration. Pull requests are linked to tasks without one; the
+/// token lets OpenGate comment on them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GithubIntegration {
+    pub project_id: String,
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+    pub has_token: bool,
+    /// Comment on a task's pull request when the task is completed
+    pub comment_on_complete: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Body of `PUT /api/projects/:id/integrations/github`.
+#[derive(Debug, Deserialize)]
+pub struct SetGithubIntegration {
+    /// Token with write access to the repository's pull requests
+    pub token: Option<String>,
+    /// Defaults to true
+    pub comment_on_complete: Option<bool>,
+}
+
+/// A project's GitLab integration, the alternative to GitHub. Merge requests are linked to
+/// tasks without one; the token lets OpenGate comment on them and import issues.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GitlabIntegration {
+    pub project_id: String,
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+    pub has_token: bool,
+    /// GitLab project path (`group/repo`) or numeric id issues are imported from
+    pub gitlab_project: Option<String>,
+    /// Comment on a task's merge request when the task is completed
+    pub comment_on_complete: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Body of `PUT /api/projects/:id/integrations/gitlab`.
+#[derive(Debug, Deserialize)]
+pub struct SetGitlabIntegration {
+    /// Token with the `api` scope on the GitLab project
+    pub token: Option<String>,
+    pub gitlab_project: Option<String>,
+    /// Defaults to true
+    pub comment_on_complete: Option<bool>,
+}
+
+/// Result of `POST /api/projects/:id/integrations/gitlab/import`.
+#[derive(Debug, Serialize)]
+pub struct GitlabImport {
+    /// Tasks created, one per newly imported issue
+    pub imported: Vec<Task>,
+    /// Open issues already imported earlier
+    pub skipped: usize,
+}
+
+// ===== Imports =====
+
+/// Query of the `/api/projects/:id/import/*` endpoints.
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    /// Report the mapping without creating anything
+    pub dry_run: Option<bool>,
+}
+
+/// Outcome (or, with `dry_run`, plan) of `POST /api/projects/:id/import/jira`.
+#[derive(Debug, Serialize)]
+pub struct JiraImportResult {
+    pub dry_run: bool,
+    /// Issues that become tasks
+    pub issues: Vec<JiraIssueMapping>,
+    /// Dependencies between tasks of this or earlier imports, by issue key
+    pub dependencies: Vec<JiraDependencyMapping>,
+    /// Keys of issues imported before, left as they are
+    pub skipped: Vec<String>,
+    /// Statuses and priorities with no obvious counterpart, links left out
+    pub warnings: Vec<String>,
+}
+
+/// How one Jira issue maps onto a task.
+#[derive(Debug, Serialize)]
+pub struct JiraIssueMapping {
+    pub key: String,
+    pub title: String,
+    pub jira_status: String,
+    pub status: String,
+    pub jira_priority: Option<String>,
+    pub priority: String,
+    pub tags: Vec<String>,
+    /// The created task; unset on a dry run
+    pub task_id: Option<String>,
+}
+
+/// `key`'s task depends on `depends_on`'s.
+#[derive(Debug, Serialize)]
+pub struct JiraDependencyMapping {
+    pub key: String,
+    pub depends_on: String,
+}
+
+/// How a Trello import turns checklists into work: `text` artifacts on the card's task,
+/// or a task per item that the card's task depends on.
+pub const TRELLO_CHECKLIST_MODES: &[&str] = &["artifacts", "subtasks"];
+
+/// Options of `POST /api/import/trello`, sent next to the export as
+/// `{"board": <export>, ...}`.
+#[derive(Debug, Default, Deserialize)]
+pub struct TrelloImportOptions {
+    /// Project name; defaults to the board's
+    pub name: Option<String>,
+    /// List name → task status, for lists whose status isn't guessed right from the name
+    #[serde(default)]
+    pub status_map: BTreeMap<String, String>,
+    /// artifacts (default) | subtasks
+    pub checklists: Option<String>,
+}
+
+impl TrelloImportOptions {
+    pub fn checklists(&self) -> &str {
+        self.checklists.as_deref().unwrap_or("artifacts")
+    }
+}
+
+/// Outcome (or, with `dry_run`, plan) of `POST /api/import/trello`.
+#[derive(Debug, Serialize)]
+pub struct TrelloImportResult {
+    pub dry_run: bool,
+    /// The created project; unset on a dry run
+    pub project: Option<Project>,
+    pub checklists: String,
+    pub lists: Vec<TrelloListMapping>,
+    pub cards: Vec<TrelloCardMapping>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrelloListMapping {
+    pub name: String,
+    pub status: String,
+    /// Open cards on the list
+    pub cards: usize,
+}
+
+/// How one Trello card maps onto a task.
+#[derive(Debug, Serialize)]
+pub struct TrelloCardMapping {
+    pub card_id: String,
+    pub name: String,
+    pub list: String,
+    pub status: String,
+    pub tags: Vec<String>,
+    pub checklist_items: usize,
+    /// Link attachments, each becoming a `url` artifact
+    pub attachments: usize,
+    /// The created task; unset on a dry run
+    pub task_id: Option<String>,
+    /// Tasks created for checklist items with `checklists: subtasks`
+    pub subtask_ids: Vec<String>,
+}
+
+/// A project's Linear integration: the API token imports and completion sync use.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinearIntegration {
+    pub project_id: String,
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+    pub has_token: bool,
+    /// Key of the Linear team issues are imported from, e.g. `ENG`
+    pub team: Option<String>,
+    /// Complete a task's Linear issue when the task is completed
+    pub sync_on_complete: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Body of `PUT /api/projects/:id/integrations/linear`.
+#[derive(Debug, Deserialize)]
+pub struct SetLinearIntegration {
+    /// Personal API key or OAuth token
+    pub token: Option<String>,
+    pub team: Option<String>,
+    /// Defaults to true
+    pub sync_on_complete: Option<bool>,
+}
+
+/// Outcome (or, with `dry_run`, plan) of `POST /api/projects/:id/import/linear`.
+#[derive(Debug, Serialize)]
+pub struct LinearImportResult {
+    pub dry_run: bool,
+    /// Issues that become tasks
+    pub issues: Vec<LinearIssueMapping>,
+    /// Identifiers of issues imported before, left as they are
+    pub skipped: Vec<String>,
+    /// States and priorities with no obvious counterpart
+    pub warnings: Vec<String>,
+}
+
+/// How one Linear issue maps onto a task.
+#[derive(Debug, Serialize)]
+pub struct LinearIssueMapping {
+    pub identifier: String,
+    pub title: String,
+    pub linear_status: String,
+    pub status: String,
+    pub linear_priority: Option<String>,
+    pub priority: String,
+    pub tags: Vec<String>,
+    /// The created task; unset on a dry run
+    pub task_id: Option<String>,
+}
+
+/// A row of the persisted event log.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredEvent {
+    pub id: i64,
+    pub event_type: String,
+    pub task_id: Option<String>,
+    pub project_id: String,
+    pub actor_type: String,
+    pub actor_id: String,
+    pub payload: serde_json::Value,
+    pub created_at: String,
+}
+
+// ===== Phone push =====
+
+/// Valid push providers.
+pub const PUSH_PROVIDERS: &[&str] = &["ntfy", "pushover"];
+
+/// Where to buzz a phone for notifications routed to the push channel: an ntfy topic
+/// URL (e.g. `https://ntfy.sh/my-topic`) or a Pushover user key. Set per agent, or per
+/// user for the agents they own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushTarget {
+    /// ntfy | pushover
+    pub provider: String,
+    /// ntfy topic URL, or Pushover user key
+    pub target: String,
+    /// Pushover application token; ntfy access token for protected topics
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+}
+
+impl PushTarget {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.provider.as_str() {
+            "ntfy"
+                if !self.target.starts_with("http://") && !self.target.starts_with("https://") =>
+            {
+                Err("ntfy target must be a topic URL, e.g. https://ntfy.sh/my-topic".to_string())
+            }
+            "pushover" if self.token.as_deref().is_none_or(str::is_empty) => {
+                Err("Pushover needs the application token in 'token'".to_string())
+            }
+            "ntfy" | "pushover" if self.target.is_empty() => Err("target is required".to_string()),
+            "ntfy" | "pushover" => Ok(()),
+            other => Err(format!(
+                "Invalid provider '{}'. Must be one of: {}",
+                other,
+                PUSH_PROVIDERS.join(", ")
+            )),
+        }
+    }
+}
@@ -1,14 +1,16 @@
 use axum::{
-    http::StatusCode,
     routing::{delete, get, patch, post},
     Router,
 };
-use std::sync::{Arc, Mutex};
-use tower_http::cors::{Any, CorsLayer};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
+use crate::config::ServerConfig;
 use crate::db;
-use crate::events::EventBus;
+use crate::events::{Event, EventBus};
 use crate::handlers;
+use crate::listen::Listen;
 use crate::storage::StorageBackend;
 use opengate_models::CreateActivity;
 
@@ -19,253 +21,301 @@ pub struct AppState {
     pub event_bus: EventBus,
 }
 
+static CORS_ORIGINS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Only let browsers on these origins call the API (any origin when never set or empty).
+pub fn set_cors_origins(origins: Vec<String>) {
+    let _ = CORS_ORIGINS.set(origins);
+}
+
+/// Serves each `path => method(handler)` entry from `api_routes`, and lists every
+/// (method, path) in `ROUTES` so the OpenAPI document covers exactly what is served.
+macro_rules! routes {
+    ($($path:literal => $method:ident($handler:expr) $(.$more:ident($more_handler:expr))*),* $(,)?) => {
+        /// Every (method, path) the API serves, in axum's `:param` / `*param` syntax.
+        pub const ROUTES: &[(&str, &str)] = &[
+            $((stringify!($method), $path), $((stringify!($more), $path),)*)*
+        ];
+
+        fn api_routes() -> Router<AppState> {
+            Router::new()$(.route($path, $method($handler)$(.$more($more_handler))*))*
+        }
+    };
+}
+
+routes! {
+    // Auth
+    "/api/auth/me" => get(handlers::auth::me),
+    // Schema
+    "/api/schema" => get(handlers::schema::get_schema),
+    "/api/openapi.json" => get(handlers::schema::get_openapi),
+    // Projects
+    "/api/projects" => get(handlers::projects::list_projects)
+        .post(handlers::projects::create_project),
+    "/api/projects/:id" => get(handlers::projects::get_project)
+        .patch(handlers::projects::update_project)
+        .delete(handlers::projects::archive_project),
+    // Pulse
+    "/api/projects/:id/pulse" => get(handlers::projects::get_pulse),
+    "/api/projects/:id/burndown" => get(handlers::projects::get_burndown),
+    // v4: Schedule
+    "/api/projects/:id/schedule" => get(handlers::projects::get_schedule),
+    "/api/projects/:id/schedule.ics" => get(handlers::projects::get_schedule_ics),
+    // Project Questions
+    "/api/projects/:id/questions" => get(handlers::questions::project_questions)
+        .post(handlers::questions::create_project_question),
+    "/api/projects/:id/questions/:qid" => get(handlers::questions::get_project_question),
+    "/api/projects/:id/questions/:qid/resolve" =>
+        post(handlers::questions::resolve_project_question),
+    "/api/projects/:id/questions/:qid/replies" => get(handlers::questions::list_project_replies)
+        .post(handlers::questions::create_project_reply),
+    "/api/projects/:id/questions/:qid/dismiss" =>
+        post(handlers::questions::dismiss_project_question),
+    "/api/projects/:id/questions/:qid/assign" => post(handlers::questions::assign_project_question),
+    // Tasks - project scoped
+    "/api/projects/:id/tasks" => get(handlers::tasks::list_tasks_by_project)
+        .post(handlers::tasks::create_task),
+    // Tasks - global
+    "/api/tasks" => get(handlers::tasks::list_tasks_global),
+    "/api/tasks/mine" => get(handlers::tasks::my_tasks),
+    "/api/tasks/next" => get(handlers::tasks::next_task),
+    "/api/tasks/batch/status" => post(handlers::tasks::batch_status),
+    "/api/tasks/:id" => get(handlers::tasks::get_task)
+        .patch(handlers::tasks::update_task)
+        .delete(handlers::tasks::delete_task),
+    "/api/tasks/:id/context" => patch(handlers::tasks::update_context),
+    "/api/tasks/:id/claim" => post(handlers::tasks::claim_task),
+    "/api/tasks/:id/release" => post(handlers::tasks::release_task),
+    "/api/tasks/:id/complete" => post(handlers::tasks::complete_task),
+    "/api/tasks/:id/block" => post(handlers::tasks::block_task),
+    // v2: Assignment, handoff, review
+    "/api/tasks/:id/assign" => post(handlers::tasks::assign_task),
+    "/api/tasks/:id/handoff" => post(handlers::tasks::handoff_task),
+    "/api/tasks/:id/approve" => post(handlers::tasks::approve_task),
+    "/api/tasks/:id/request-changes" => post(handlers::tasks::request_changes),
+    "/api/tasks/:id/submit-review" => post(handlers::tasks::submit_review),
+    "/api/tasks/:id/start-review" => post(handlers::tasks::start_review),
+    // v4: Dependencies
+    "/api/tasks/:id/dependencies" => get(handlers::tasks::list_dependencies)
+        .post(handlers::tasks::add_dependencies),
+    "/api/tasks/:id/dependencies/:dep_id" => delete(handlers::tasks::remove_dependency),
+    "/api/tasks/:id/dependents" => get(handlers::tasks::list_dependents),
+    "/api/tasks/:id/usage" => get(handlers::tasks::list_usage).post(handlers::tasks::report_usage),
+    // Recurrence series controls
+    "/api/tasks/:id/recurrence/pause" => post(handlers::tasks::pause_recurrence),
+    "/api/tasks/:id/recurrence/resume" => post(handlers::tasks::resume_recurrence),
+    "/api/tasks/:id/recurrence/skip" => post(handlers::tasks::skip_recurrence),
+    // v4: Scheduled task auto-transition (manual trigger)
+    "/api/tasks/scheduled/transition" => post(handlers::tasks::trigger_scheduled_transition),
+    // Questions
+    "/api/tasks/:id/questions" => get(handlers::questions::list_questions)
+        .post(handlers::questions::create_question),
+    "/api/tasks/:id/questions/:qid" => get(handlers::questions::get_question),
+    "/api/tasks/:id/questions/:qid/resolve" => post(handlers::questions::resolve_question),
+    "/api/tasks/:id/questions/:qid/replies" => get(handlers::questions::list_replies)
+        .post(handlers::questions::create_reply),
+    "/api/tasks/:id/questions/:qid/replies/:rid/artifacts" =>
+        post(handlers::questions::create_reply_artifact),
+    "/api/tasks/:id/questions/:qid/artifacts" => get(handlers::questions::list_question_artifacts)
+        .post(handlers::questions::create_question_artifact),
+    "/api/tasks/:id/questions/:qid/dismiss" => post(handlers::questions::dismiss_question),
+    "/api/tasks/:id/questions/:qid/assign" => post(handlers::questions::assign_question),
+    // Linked knowledge
+    "/api/tasks/:id/knowledge" => get(handlers::knowledge::list_task_knowledge)
+        .post(handlers::knowledge::link_task_knowledge),
+    "/api/tasks/:id/knowledge/*key" => delete(handlers::knowledge::unlink_task_knowledge),
+    // Activity
+    "/api/tasks/:id/activity" => get(handlers::activity::list_activity)
+        .post(handlers::activity::create_activity),
+    // Artifacts
+    "/api/tasks/:id/artifacts" => get(handlers::artifacts::list_artifacts)
+        .post(handlers::artifacts::create_artifact),
+    "/api/tasks/:id/artifacts/:artifact_id" => patch(handlers::artifacts::update_artifact)
+        .delete(handlers::artifacts::delete_artifact),
+    // Agents
+    "/api/agents" => get(handlers::agents::list_agents).post(handlers::agents::create_agent),
+    "/api/agents/register" => post(handlers::agents::register_agent),
+    "/api/agents/match" => get(handlers::agents::match_best_agent),
+    "/api/agents/:id" => get(handlers::agents::get_agent)
+        .patch(handlers::agents::update_agent)
+        .delete(handlers::agents::delete_agent),
+    "/api/agents/:id/offboard" => post(handlers::agents::offboard_agent),
+    "/api/agents/:id/keys" => get(handlers::agents::list_api_keys)
+        .post(handlers::agents::create_api_key),
+    "/api/agents/:id/webhook/test" => post(handlers::agents::test_webhook),
+    "/api/agents/:id/webhooks/failed" => get(handlers::agents::list_failed_webhooks),
+    "/api/agents/:id/webhooks/failed/redeliver" =>
+        post(handlers::agents::redeliver_failed_webhooks),
+    "/api/agents/:id/push" => get(handlers::agents::get_push_target)
+        .put(handlers::agents::set_push_target)
+        .delete(handlers::agents::delete_push_target),
+    "/api/users/me/push" => get(handlers::agents::get_my_push_target)
+        .put(handlers::agents::set_my_push_target)
+        .delete(handlers::agents::delete_my_push_target),
+    "/api/agents/:id/keys/:key_id" => delete(handlers::agents::revoke_api_key),
+    "/api/agents/:id/suggested-skills" => get(handlers::agents::suggested_skills),
+    "/api/agents/:id/suggested-skills/apply" => post(handlers::agents::apply_suggested_skills),
+    "/api/agents/heartbeat" => post(handlers::agents::heartbeat),
+    "/api/agents/me" => patch(handlers::agents::update_agent_self),
+    "/api/agents/me/inbox" => get(handlers::agents::inbox),
+    "/api/agents/me/questions" => get(handlers::questions::my_questions),
+    "/api/agents/me/notifications" => get(handlers::agents::my_notifications),
+    "/api/agents/me/notifications/:id/ack" => post(handlers::agents::ack_notification),
+    "/api/agents/me/notifications/ack-all" => post(handlers::agents::ack_all_notifications),
+    // Knowledge base
+    "/api/projects/:id/knowledge" => get(handlers::knowledge::list_knowledge),
+    "/api/projects/:id/knowledge/search" => get(handlers::knowledge::search_knowledge),
+    // Shared (organization-wide) knowledge
+    "/api/knowledge" => get(handlers::knowledge::list_shared_knowledge),
+    "/api/knowledge/templates" => get(handlers::knowledge::list_knowledge_templates),
+    "/api/knowledge/search" => get(handlers::knowledge::search_shared_knowledge),
+    "/api/knowledge/*key" => get(handlers::knowledge::get_shared_knowledge)
+        .put(handlers::knowledge::upsert_shared_knowledge)
+        .post(handlers::knowledge::post_shared_knowledge)
+        .delete(handlers::knowledge::delete_shared_knowledge),
+    "/api/projects/:id/knowledge/stats" => get(handlers::knowledge::get_knowledge_stats),
+    "/api/projects/:id/knowledge/graph" => get(handlers::knowledge::get_knowledge_graph),
+    "/api/projects/:id/knowledge/export" => get(handlers::knowledge::export_knowledge),
+    "/api/projects/:id/knowledge/import" => post(handlers::knowledge::import_knowledge),
+    "/api/projects/:id/knowledge/*key" => get(handlers::knowledge::get_knowledge)
+        .put(handlers::knowledge::upsert_knowledge)
+        .post(handlers::knowledge::post_knowledge)
+        .delete(handlers::knowledge::delete_knowledge),
+    // Stats
+    "/api/stats" => get(handlers::stats::get_stats),
+    "/api/stats/timeseries" => get(handlers::stats::get_timeseries),
+    "/api/stats/agents" => get(handlers::stats::get_agent_stats),
+    "/api/stats/reviews" => get(handlers::stats::get_review_stats),
+    "/api/stats/costs" => get(handlers::stats::get_cost_stats),
+    "/api/stats/blocked" => get(handlers::stats::get_blocked_stats),
+    "/api/tenant/usage" => get(handlers::stats::tenant_usage),
+    "/api/admin/events/replay" => post(handlers::admin::replay_events),
+    "/api/admin/event-bus" => get(handlers::admin::event_bus_stats),
+    "/api/admin/queries" => get(handlers::admin::query_stats)
+        .delete(handlers::admin::reset_query_stats),
+    "/api/admin/settings" => get(handlers::admin::get_settings)
+        .patch(handlers::admin::update_settings),
+    "/api/admin/tenants" => get(handlers::admin::list_tenants).post(handlers::admin::create_tenant),
+    "/api/admin/tenants/:id" => get(handlers::admin::get_tenant)
+        .delete(handlers::admin::delete_tenant),
+    "/api/admin/tenants/:id/suspend" => post(handlers::admin::suspend_tenant),
+    "/api/admin/tenants/:id/resume" => post(handlers::admin::resume_tenant),
+    "/api/admin/tenants/:id/setup-tokens" => post(handlers::admin::create_tenant_setup_token),
+    // v4: Inbound webhook triggers (management — require auth)
+    "/api/projects/:id/triggers" => get(handlers::triggers::list_triggers)
+        .post(handlers::triggers::create_trigger),
+    "/api/projects/:id/triggers/validate" => post(handlers::triggers::validate_trigger),
+    "/api/projects/:id/triggers/:tid" => delete(handlers::triggers::delete_trigger)
+        .patch(handlers::triggers::update_trigger),
+    "/api/projects/:id/triggers/:tid/logs" => get(handlers::triggers::list_trigger_logs),
+    // Outbound integrations
+    "/api/projects/:id/integrations/slack" => get(handlers::integrations::get_slack)
+        .put(handlers::integrations::set_slack)
+        .delete(handlers::integrations::delete_slack),
+    "/api/projects/:id/integrations/github" => get(handlers::integrations::get_github)
+        .put(handlers::integrations::set_github)
+        .delete(handlers::integrations::delete_github),
+    "/api/projects/:id/integrations/gitlab" => get(handlers::integrations::get_gitlab)
+        .put(handlers::integrations::set_gitlab)
+        .delete(handlers::integrations::delete_gitlab),
+    "/api/projects/:id/integrations/gitlab/import" =>
+        post(handlers::integrations::import_gitlab_issues),
+    "/api/projects/:id/integrations/linear" => get(handlers::integrations::get_linear)
+        .put(handlers::integrations::set_linear)
+        .delete(handlers::integrations::delete_linear),
+    "/api/projects/:id/import/jira" => post(handlers::imports::import_jira),
+    "/api/projects/:id/import/linear" => post(handlers::imports::import_linear),
+    "/api/import/trello" => post(handlers::imports::import_trello),
+    // v4: Inbound webhook receiver (no auth — secret-validated)
+    "/api/webhooks/trigger/:trigger_id" => post(handlers::triggers::receive_webhook),
+    // WebSocket, and SSE for clients that can't hold a socket open
+    "/api/ws" => get(handlers::ws::ws_handler),
+    "/api/events/stream" => get(handlers::sse::event_stream),
+    // MCP over Streamable HTTP, for agents without access to the database file
+    "/api/mcp" => post(handlers::mcp::handle).get(handlers::mcp::stream),
+    // Prometheus scrape endpoint
+    "/metrics" => get(crate::metrics::metrics),
+    // Liveness for load balancers; never shed
+    "/health" => get(health),
+}
+
+/// Liveness for load balancers; never shed
+async fn health() -> axum::Json<serde_json::Value> {
+    axum::Json(serde_json::json!({"status": "ok"}))
+}
+
 pub fn build_router(state: AppState) -> Router {
+    let origins: Vec<_> = CORS_ORIGINS
+        .get()
+        .into_iter()
+        .flatten()
+        .filter_map(|origin| origin.parse().ok())
+        .collect();
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(if origins.is_empty() {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(origins)
+        })
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let api = Router::new()
-        // Auth
-        .route("/api/auth/me", get(handlers::auth::me))
-        // Schema
-        .route("/api/schema", get(handlers::schema::get_schema))
-        // Projects
-        .route(
-            "/api/projects",
-            get(handlers::projects::list_projects).post(handlers::projects::create_project),
-        )
-        .route(
-            "/api/projects/:id",
-            get(handlers::projects::get_project)
-                .patch(handlers::projects::update_project)
-                .delete(handlers::projects::archive_project),
-        )
-        // Pulse
-        .route(
-            "/api/projects/:id/pulse",
-            get(handlers::projects::get_pulse),
-        )
-        // v4: Schedule
-        .route(
-            "/api/projects/:id/schedule",
-            get(handlers::projects::get_schedule),
-        )
-        // Project Questions
-        .route(
-            "/api/projects/:id/questions",
-            get(handlers::questions::project_questions),
-        )
-        // Tasks - project scoped
-        .route(
-            "/api/projects/:id/tasks",
-            get(handlers::tasks::list_tasks_by_project).post(handlers::tasks::create_task),
-        )
-        // Tasks - global
-        .route("/api/tasks", get(handlers::tasks::list_tasks_global))
-        .route("/api/tasks/mine", get(handlers::tasks::my_tasks))
-        .route("/api/tasks/next", get(handlers::tasks::next_task))
-        .route(
-            "/api/tasks/batch/status",
-            post(handlers::tasks::batch_status),
-        )
-        .route(
-            "/api/tasks/:id",
-            get(handlers::tasks::get_task)
-                .patch(handlers::tasks::update_task)
-                .delete(handlers::tasks::delete_task),
-        )
-        .route(
-            "/api/tasks/:id/context",
-            patch(handlers::tasks::update_context),
-        )
-        .route("/api/tasks/:id/claim", post(handlers::tasks::claim_task))
-        .route(
-            "/api/tasks/:id/release",
-            post(handlers::tasks::release_task),
-        )
-        .route(
-            "/api/tasks/:id/complete",
-            post(handlers::tasks::complete_task),
-        )
-        .route("/api/tasks/:id/block", post(handlers::tasks::block_task))
-        // v2: Assignment, handoff, review
-        .route("/api/tasks/:id/assign", post(handlers::tasks::assign_task))
-        .route(
-            "/api/tasks/:id/handoff",
-            post(handlers::tasks::handoff_task),
-        )
-        .route(
-            "/api/tasks/:id/approve",
-            post(handlers::tasks::approve_task),
-        )
-        .route(
-            "/api/tasks/:id/request-changes",
-            post(handlers::tasks::request_changes),
-        )
-        .route(
-            "/api/tasks/:id/submit-review",
-            post(handlers::tasks::submit_review),
-        )
-        .route(
-            "/api/tasks/:id/start-review",
-            post(handlers::tasks::start_review),
-        )
-        // v4: Dependencies
-        .route(
-            "/api/tasks/:id/dependencies",
-            get(handlers::tasks::list_dependencies).post(handlers::tasks::add_dependencies),
-        )
-        .route(
-            "/api/tasks/:id/dependencies/:dep_id",
-            delete(handlers::tasks::remove_dependency),
-        )
-        .route(
-            "/api/tasks/:id/dependents",
-            get(handlers::tasks::list_dependents),
-        )
-        // v4: Scheduled task auto-transition (manual trigger)
-        .route(
-            "/api/tasks/scheduled/transition",
-            post(handlers::tasks::trigger_scheduled_transition),
-        )
-        // Questions
-        .route(
-            "/api/tasks/:id/questions",
-            get(handlers::questions::list_questions).post(handlers::questions::create_question),
-        )
-        .route(
-            "/api/tasks/:id/questions/:qid",
-            get(handlers::questions::get_question),
-        )
-        .route(
-            "/api/tasks/:id/questions/:qid/resolve",
-            post(handlers::questions::resolve_question),
-        )
-        .route(
-            "/api/tasks/:id/questions/:qid/replies",
-            get(handlers::questions::list_replies).post(handlers::questions::create_reply),
-        )
-        .route(
-            "/api/tasks/:id/questions/:qid/dismiss",
-            post(handlers::questions::dismiss_question),
-        )
-        .route(
-            "/api/tasks/:id/questions/:qid/assign",
-            post(handlers::questions::assign_question),
-        )
-        // Activity
-        .route(
-            "/api/tasks/:id/activity",
-            get(handlers::activity::list_activity).post(handlers::activity::create_activity),
-        )
-        // Artifacts
-        .route(
-            "/api/tasks/:id/artifacts",
-            get(handlers::artifacts::list_artifacts).post(handlers::artifacts::create_artifact),
-        )
-        .route(
-            "/api/tasks/:id/artifacts/:artifact_id",
-            patch(handlers::artifacts::update_artifact)
-                .delete(handlers::artifacts::delete_artifact),
-        )
-        // Agents
-        .route(
-            "/api/agents",
-            get(handlers::agents::list_agents).post(handlers::agents::create_agent),
-        )
-        .route(
-            "/api/agents/register",
-            post(handlers::agents::register_agent),
-        )
-        .route("/api/agents/match", get(handlers::agents::match_best_agent))
-        .route(
-            "/api/agents/:id",
-            get(handlers::agents::get_agent)
-                .patch(handlers::agents::update_agent)
-                .delete(handlers::agents::delete_agent),
-        )
-        .route("/api/agents/heartbeat", post(handlers::agents::heartbeat))
-        .route("/api/agents/me", patch(handlers::agents::update_agent_self))
-        .route("/api/agents/me/inbox", get(handlers::agents::inbox))
-        .route(
-            "/api/agents/me/questions",
-            get(handlers::questions::my_questions),
-        )
-        .route(
-            "/api/agents/me/notifications",
-            get(handlers::agents::my_notifications),
-        )
-        .route(
-            "/api/agents/me/notifications/:id/ack",
-            post(handlers::agents::ack_notification),
-        )
-        .route(
-            "/api/agents/me/notifications/ack-all",
-            post(handlers::agents::ack_all_notifications),
-        )
-        // Knowledge base
-        .route(
-            "/api/projects/:id/knowledge",
-            get(handlers::knowledge::list_knowledge),
-        )
-        .route(
-            "/api/projects/:id/knowledge/search",
-            get(handlers::knowledge::search_knowledge),
-        )
-        .route(
-            "/api/projects/:id/knowledge/*key",
-            get(handlers::knowledge::get_knowledge)
-                .put(handlers::knowledge::upsert_knowledge)
-                .delete(handlers::knowledge::delete_knowledge),
-        )
-        // Stats
-        .route("/api/stats", get(handlers::stats::get_stats))
-        // v4: Inbound webhook triggers (management — require auth)
-        .route(
-            "/api/projects/:id/triggers",
-            get(handlers::triggers::list_triggers).post(handlers::triggers::create_trigger),
-        )
-        .route(
-            "/api/projects/:id/triggers/:tid",
-            delete(handlers::triggers::delete_trigger).patch(handlers::triggers::update_trigger),
-        )
-        .route(
-            "/api/projects/:id/triggers/:tid/logs",
-            get(handlers::triggers::list_trigger_logs),
-        )
-        // v4: Inbound webhook receiver (no auth — secret-validated)
-        .route(
-            "/api/webhooks/trigger/:trigger_id",
-            post(handlers::triggers::receive_webhook),
-        )
-        // WebSocket
-        .route("/api/ws", get(handlers::ws::ws_handler));
+    let api = api_routes();
 
-    api.fallback(|| async { (StatusCode::NOT_FOUND, "Not found") })
+    api.fallback(crate::ui::fallback)
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::quotas::limit_events,
+        ))
+        .layer(axum::middleware::from_fn(crate::load_shed::shed_load))
+        .layer(axum::middleware::from_fn(crate::metrics::track_requests))
+        .layer(axum::middleware::from_fn(crate::telemetry::trace_requests))
+        .layer(axum::middleware::from_fn(crate::rate_limit::limit_requests))
+        .layer(axum::middleware::from_fn(crate::compression::compress))
         .layer(cors)
         .with_state(state)
 }
 
-pub async fn run_server(port: u16, db_path: &str, setup_token: &str) {
+pub async fn run_server(config: &ServerConfig) {
+    let schedule = config.scheduler;
+    let every = |secs: u64| tokio::time::Duration::from_secs(secs);
+
     // Keep raw connection for WAL checkpoint on shutdown (SQLite-only behavior)
-    let raw_conn = Arc::new(Mutex::new(db::init_db(db_path)));
-    let storage = Arc::new(crate::storage::sqlite::SqliteBackend::new(raw_conn.clone()))
-        as Arc<dyn StorageBackend>;
+    let raw_conn = Arc::new(Mutex::new(db::init_db(&config.db)));
+    let storage = Arc::new(
+        crate::storage::sqlite::SqliteBackend::new(raw_conn.clone())
+            .with_max_recurrence_occurrences(config.max_recurrence_occurrences),
+    ) as Arc<dyn StorageBackend>;
 
     let state = AppState {
         storage: storage.clone(),
-        setup_token: setup_token.to_string(),
+        setup_token: config.setup_token.clone(),
         event_bus: EventBus::default(),
     };
 
+    // Republish bus events to an external broker, if configured
+    if let Some(config) = crate::event_sink::configured_sink() {
+        crate::event_sink::spawn(config.clone(), &state.event_bus);
+    }
+    if let Some(config) = crate::kafka::configured_kafka() {
+        crate::kafka::spawn(config.clone(), storage.clone());
+    }
+    crate::telemetry::spawn_exporter();
+    // Post project events to Slack channels
+    crate::slack::spawn(storage.clone());
+
     // Spawn background stale agent cleanup (with startup grace period)
     let bg_storage = storage.clone();
+    let (grace, cleanup_every) = (
+        every(schedule.stale_cleanup_grace_secs),
+        every(schedule.stale_cleanup_secs),
+    );
     tokio::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
+        tokio::time::sleep(grace).await;
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-            let released = bg_storage.release_stale_tasks(None, 240);
+            tokio::time::sleep(cleanup_every).await;
+            let release_after = bg_storage.get_settings(None).stale_release_minutes;
+            let released = bg_storage.release_stale_tasks(None, release_after);
             for task in &released {
                 eprintln!(
                     "[cleanup] Auto-released stale task: {} ({})",
@@ -287,11 +337,130 @@ pub async fn run_server(port: u16, db_path: &str, setup_token: &str) {
         }
     });
 
+    // Spawn background presence sweeper — announces agents dropping offline / coming back
+    {
+        let presence_storage = storage.clone();
+        let presence_bus = state.event_bus.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every(schedule.presence_secs));
+            loop {
+                interval.tick().await;
+                for change in presence_storage.sweep_agent_presence(None) {
+                    let Some(event_type) =
+                        crate::presence::transition_event(&change.from, &change.to)
+                    else {
+                        continue;
+                    };
+                    eprintln!(
+                        "[presence] {} ({}): {} \u{2192} {}",
+                        change.agent_name, change.agent_id, change.from, change.to
+                    );
+                    presence_bus.emit(Event {
+                        event_type: event_type.to_string(),
+                        project_id: None,
+                        agent_id: Some(change.agent_id.clone()),
+                        event_id: None,
+                        data: serde_json::to_value(&change).unwrap_or_default(),
+                        timestamp: chrono::Utc::now(),
+                    });
+                }
+            }
+        });
+    }
+
+    // Spawn background knowledge freshness check — flags entries past their review deadline
+    {
+        let kb_storage = storage.clone();
+        let kb_bus = state.event_bus.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every(schedule.knowledge_freshness_secs));
+            loop {
+                interval.tick().await;
+                let (stale, pending) = kb_storage.sweep_stale_knowledge(None);
+                for entry in &stale {
+                    eprintln!(
+                        "[knowledge] Entry due for review: {} ({})",
+                        entry.key, entry.project_id
+                    );
+                    kb_bus.emit(Event {
+                        event_type: "knowledge.stale".to_string(),
+                        project_id: Some(entry.project_id.clone()),
+                        agent_id: None,
+                        event_id: None,
+                        data: serde_json::json!({
+                            "key": entry.key,
+                            "title": entry.title,
+                            "review_after": entry.review_after,
+                        }),
+                        timestamp: chrono::Utc::now(),
+                    });
+                }
+                handlers::webhooks::fire_notification_webhooks(kb_storage.clone(), pending);
+            }
+        });
+    }
+
+    // Spawn background question re-router — moves questions off targets that went quiet
+    {
+        let route_storage = storage.clone();
+        let route_bus = state.event_bus.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every(schedule.question_reroute_secs));
+            loop {
+                interval.tick().await;
+                let window = crate::question_routing::reroute_after_minutes();
+                if window < 1 {
+                    continue;
+                }
+                let (rerouted, pending) = route_storage.reroute_inactive_questions(None, window);
+                for r in &rerouted {
+                    eprintln!(
+                        "[questions] Re-routed {} from inactive agent {} to {}",
+                        r.question_id,
+                        r.from_agent_id,
+                        r.to_agent_id.as_deref().unwrap_or("nobody")
+                    );
+                    route_bus.emit(Event {
+                        event_type: if r.to_agent_id.is_some() {
+                            "task.question_rerouted".to_string()
+                        } else {
+                            "task.question_unrouted".to_string()
+                        },
+                        project_id: Some(r.project_id.clone()),
+                        agent_id: r.to_agent_id.clone(),
+                        event_id: None,
+                        data: serde_json::to_value(r).unwrap_or_default(),
+                        timestamp: chrono::Utc::now(),
+                    });
+                }
+                handlers::webhooks::fire_notification_webhooks(route_storage.clone(), pending);
+            }
+        });
+    }
+
+    // Spawn background notification expiry — applies agents' auto-ack / archive policies
+    {
+        let notif_storage = storage.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every(schedule.notification_expiry_secs));
+            loop {
+                interval.tick().await;
+                let sweep = notif_storage.sweep_notification_policies(None);
+                if sweep.acknowledged > 0 || sweep.archived > 0 {
+                    eprintln!(
+                        "[notifications] Auto-acked {}, archived {} stale notification(s)",
+                        sweep.acknowledged, sweep.archived
+                    );
+                }
+            }
+        });
+    }
+
     // Spawn background scheduled-task promoter
     {
         let sched_storage = storage.clone();
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+            let mut interval = tokio::time::interval(every(schedule.scheduled_tasks_secs));
             loop {
                 interval.tick().await;
                 let count = sched_storage.transition_ready_scheduled_tasks(None);
@@ -305,6 +474,25 @@ pub async fn run_server(port: u16, db_path: &str, setup_token: &str) {
         });
     }
 
+    // Spawn background retention sweep — deletes history past the configured age
+    {
+        let retention_storage = storage.clone();
+        let policy = config.retention.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every(schedule.retention_secs));
+            loop {
+                interval.tick().await;
+                let sweep = retention_storage.prune_history(None, &policy);
+                if sweep.events > 0 || sweep.notifications > 0 || sweep.webhook_logs > 0 {
+                    eprintln!(
+                        "[retention] Deleted {} event(s), {} notification(s), {} webhook log(s)",
+                        sweep.events, sweep.notifications, sweep.webhook_logs
+                    );
+                }
+            }
+        });
+    }
+
     // Graceful shutdown: checkpoint WAL on SIGTERM/SIGINT (SQLite-only)
     let shutdown_conn = raw_conn.clone();
     let shutdown_signal = async move {
@@ -322,14 +510,72 @@ pub async fn run_server(port: u16, db_path: &str, setup_token: &str) {
     };
 
     let router = build_router(state.clone());
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
-        .await
-        .expect("Failed to bind port");
+    let listeners = match config.listeners() {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let acceptor = if config.tls.enabled() {
+        match crate::tls::acceptor(&config.tls.cert, &config.tls.key) {
+            Ok(acceptor) => Some(acceptor),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    } else {
+        None
+    };
 
-    eprintln!("OpenGate listening on http://0.0.0.0:{}", port);
+    // Every listener stops on the same signal
+    let (stop_tx, stop_rx) = tokio::sync::watch::channel(());
+    tokio::spawn(async move {
+        shutdown_signal.await;
+        let _ = stop_tx.send(());
+    });
+    let stopped = move || {
+        let mut stop_rx = stop_rx.clone();
+        async move {
+            let _ = stop_rx.changed().await;
+        }
+    };
 
-    axum::serve(listener, router)
-        .with_graceful_shutdown(shutdown_signal)
-        .await
-        .expect("Server error");
+    let mut servers = tokio::task::JoinSet::new();
+    for listen in listeners {
+        let router = router.clone();
+        let shutdown = stopped();
+        match listen {
+            Listen::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(&addr)
+                    .await
+                    .expect("Failed to bind port");
+                if let Some(acceptor) = acceptor.clone() {
+                    eprintln!("OpenGate listening on https://{}", addr);
+                    servers.spawn(crate::tls::serve(listener, acceptor, router, shutdown));
+                    continue;
+                }
+                eprintln!("OpenGate listening on http://{}", addr);
+                servers.spawn(async move {
+                    axum::serve(
+                        listener,
+                        router.into_make_service_with_connect_info::<SocketAddr>(),
+                    )
+                    .with_graceful_shutdown(shutdown)
+                    .await
+                    .expect("Server error");
+                });
+            }
+            Listen::Unix(path) => {
+                let listener = crate::listen::bind_unix(&path).unwrap_or_else(|e| {
+                    eprintln!("Cannot listen on {}: {}", path.display(), e);
+                    std::process::exit(2);
+                });
+                eprintln!("OpenGate listening on unix:{}", path.display());
+                servers.spawn(crate::listen::serve_unix(listener, router, shutdown));
+            }
+        }
+    }
+    while servers.join_next().await.is_some() {}
 }
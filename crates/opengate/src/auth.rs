@@ -1,14 +1,20 @@
 use async_trait::async_trait;
 use axum::{
     extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    http::{request::Parts, Method, StatusCode},
+    Json,
 };
 
 use crate::app::AppState;
-use opengate_models::Identity;
+use crate::storage::StorageBackend;
+use opengate_models::{Identity, KeyScope};
 
-// Axum extractor for Identity — always succeeds.
-// Returns AgentIdentity if a valid API key is provided, Anonymous otherwise.
+/// Header an orchestrator sets to act on behalf of another agent.
+pub const ON_BEHALF_OF_HEADER: &str = "x-on-behalf-of";
+
+// Axum extractor for Identity.
+// Returns AgentIdentity if a valid API key is provided, Anonymous otherwise. Anonymous
+// callers see every unowned row, so once any tenant exists they are refused instead.
 #[async_trait]
 impl FromRequestParts<AppState> for Identity {
     type Rejection = (StatusCode, String);
@@ -29,17 +35,213 @@ impl FromRequestParts<AppState> for Identity {
             .and_then(|h| h.strip_prefix("Bearer ").map(|s| s.to_string()));
 
         if let Some(token) = token {
-            let hash = state.storage.hash_api_key(&token);
-            if let Some(agent) = state.storage.get_agent_by_key_hash(None, &hash) {
-                state.storage.update_heartbeat(None, &agent.id);
-                return Ok(Identity::AgentIdentity {
-                    id: agent.id,
-                    name: agent.name,
-                    tenant_id: agent.owner_id,
-                });
+            match resolve_api_key(state, &token, &parts.method, parts.uri.path()) {
+                Ok(Some(identity)) => return Ok(identity),
+                Ok(None) => {}
+                Err(msg) => return Err((StatusCode::FORBIDDEN, msg)),
             }
         }
 
+        if state.storage.has_tenants() {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                "Authentication required".to_string(),
+            ));
+        }
         Ok(Identity::Anonymous)
     }
 }
+
+/// Resolve a bearer token to the agent it belongs to — either the agent's primary key or
+/// one of its additional keys. Scoped keys are checked against the request here, so a
+/// key outside its scope is refused (`Err`) rather than treated as anonymous.
+pub fn resolve_api_key(
+    state: &AppState,
+    token: &str,
+    method: &Method,
+    path: &str,
+) -> Result<Option<Identity>, String> {
+    let hash = state.storage.hash_api_key(token);
+    let agent = match state.storage.get_agent_by_key_hash(None, &hash) {
+        Some(agent) => agent,
+        None => {
+            let Some(key) = state.storage.get_api_key_by_hash(None, &hash) else {
+                return Ok(None);
+            };
+            let Some(agent) = state.storage.get_agent(None, &key.agent_id) else {
+                return Ok(None);
+            };
+            check_key_scope(state, &key.scope, agent.owner_id.as_deref(), method, path)?;
+            agent
+        }
+    };
+    if let Some(tenant) = agent.owner_id.as_deref() {
+        if tenant_suspended(&*state.storage, tenant) {
+            return Err(format!("Tenant '{}' is suspended", tenant));
+        }
+    }
+    state.storage.update_heartbeat(None, &agent.id);
+    Ok(Some(Identity::AgentIdentity {
+        id: agent.id,
+        name: agent.name,
+        tenant_id: agent.owner_id,
+    }))
+}
+
+/// Whether `tenant` is registered and suspended; its keys are refused until it resumes.
+pub fn tenant_suspended(storage: &dyn StorageBackend, tenant: &str) -> bool {
+    storage
+        .get_tenant(tenant)
+        .is_some_and(|t| t.status == "suspended")
+}
+
+fn check_key_scope(
+    state: &AppState,
+    scope: &KeyScope,
+    tenant: Option<&str>,
+    method: &Method,
+    path: &str,
+) -> Result<(), String> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+
+    if scope.read_only && !is_read {
+        return Err("This API key is read-only".to_string());
+    }
+
+    if scope.no_admin && !is_read && is_admin_route(&segments) {
+        return Err("This API key cannot use management endpoints".to_string());
+    }
+
+    if let Some(ref project_id) = scope.project_id {
+        let allowed = match segments.as_slice() {
+            ["api", "projects", id, ..] => id == project_id,
+            ["api", "tasks", "mine" | "next" | "batch" | "scheduled", ..] => false,
+            ["api", "tasks", id, ..] => state
+                .storage
+                .get_task(tenant, id)
+                .is_none_or(|t| &t.project_id == project_id),
+            ["api", "agents", "heartbeat"] | ["api", "auth", "me"] | ["api", "schema"] => true,
+            // Shared knowledge is readable from any project
+            ["api", "knowledge", ..] => is_read,
+            _ => false,
+        };
+        if !allowed {
+            return Err(format!("This API key is limited to project {}", project_id));
+        }
+    }
+
+    Ok(())
+}
+
+/// Write routes that manage agents, keys, triggers, integrations or projects themselves,
+/// the bulk imports, and the `/api/admin` operations.
+fn is_admin_route(segments: &[&str]) -> bool {
+    match segments {
+        ["api", "agents"] | ["api", "agents", "register"] => true,
+        ["api", "agents", "me", ..] | ["api", "agents", "heartbeat"] => false,
+        ["api", "agents", _, ..] => true,
+        ["api", "projects"] => false,
+        ["api", "projects", _] => true,
+        ["api", "projects", _, "triggers", ..] => true,
+        ["api", "projects", _, "integrations" | "import", ..] => true,
+        ["api", "import", ..] => true,
+        ["api", "admin", ..] => true,
+        _ => false,
+    }
+}
+
+/// The orchestrator behind a delegated request.
+#[derive(Debug, Clone)]
+pub struct Delegator {
+    pub id: String,
+    pub name: String,
+}
+
+impl Delegator {
+    /// Audit block recorded in activity metadata for delegated actions.
+    pub fn audit_metadata(&self, on_behalf_of: &Identity) -> serde_json::Value {
+        serde_json::json!({
+            "delegated_by": self.id,
+            "delegated_by_name": self.name,
+            "on_behalf_of": on_behalf_of.author_id(),
+        })
+    }
+}
+
+/// Identity for endpoints that support delegation. Without an `X-On-Behalf-Of` header
+/// this is just the caller; with it, an orchestrator-role agent acts as the named agent
+/// and `delegated_by` records who really made the call.
+#[derive(Debug, Clone)]
+pub struct ActingIdentity {
+    pub identity: Identity,
+    pub delegated_by: Option<Delegator>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ActingIdentity {
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let identity = match Identity::from_request_parts(parts, state).await {
+            Ok(identity) => identity,
+            Err((status, msg)) => return Err((status, Json(serde_json::json!({"error": msg})))),
+        };
+
+        let target_id = match parts
+            .headers
+            .get(ON_BEHALF_OF_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+        {
+            Some(t) if t != identity.author_id() => t.to_string(),
+            _ => {
+                return Ok(ActingIdentity {
+                    identity,
+                    delegated_by: None,
+                })
+            }
+        };
+
+        let caller = match &identity {
+            Identity::AgentIdentity { id, .. } => state.storage.get_agent(identity.tenant_id(), id),
+            _ => None,
+        };
+        let caller = match caller {
+            Some(agent) if agent.role == "orchestrator" => agent,
+            _ => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({
+                        "error": "Only orchestrator agents can act on behalf of another agent"
+                    })),
+                ))
+            }
+        };
+
+        let target = state
+            .storage
+            .get_agent(identity.tenant_id(), &target_id)
+            .filter(|a| a.owner_id == caller.owner_id)
+            .ok_or((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": "Delegated agent not found"})),
+            ))?;
+
+        Ok(ActingIdentity {
+            identity: Identity::AgentIdentity {
+                id: target.id,
+                name: target.name,
+                tenant_id: target.owner_id,
+            },
+            delegated_by: Some(Delegator {
+                id: caller.id,
+                name: caller.name,
+            }),
+        })
+    }
+}
@@ -0,0 +1,51 @@
+//! `[[key]]` references between knowledge entries.
+//!
+//! Entries link to each other by writing `[[other/key]]` (or `[[other/key|label]]`) in
+//! their content. References are re-extracted on every write and stored in
+//! `knowledge_links`, which backs the backlinks and graph endpoints.
+
+/// Distinct keys referenced by `content`, in order of first appearance.
+pub fn extract_references(content: &str) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let inner = &after[..end];
+        // A nested `[[` means the first opener wasn't a reference; retry from the inner one
+        if let Some(nested) = inner.rfind("[[") {
+            rest = &after[nested..];
+            continue;
+        }
+        let key = inner.split('|').next().unwrap_or("").trim();
+        if !key.is_empty() && !key.contains('\n') && !keys.iter().any(|k| k == key) {
+            keys.push(key.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_distinct_references_in_order() {
+        let content = "See [[arch/payments]] and [[decisions/queue|the queue decision]].\n\
+                       Again: [[arch/payments]]. Not a link: [[ ]], [single], [[open";
+        assert_eq!(
+            extract_references(content),
+            vec!["arch/payments", "decisions/queue"]
+        );
+    }
+
+    #[test]
+    fn ignores_broken_brackets() {
+        assert_eq!(extract_references("[[a [[b]]"), vec!["b"]);
+        assert_eq!(extract_references("[[multi\nline]]"), Vec::<String>::new());
+        assert!(extract_references("no links here").is_empty());
+    }
+}
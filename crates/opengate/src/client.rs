@@ -0,0 +1,331 @@
+//! The client commands of the `opengate` binary (`opengate task ...`, `opengate agent
+//! ...`): a thin HTTP client for driving a running server from a terminal.
+//!
+//! The server and key come from `--url` / `--api-key`, then `OPENGATE_URL` /
+//! `OPENGATE_API_KEY`, then the client config file: `$OPENGATE_CLIENT_CONFIG`, or
+//! `opengate/client.toml` under `$XDG_CONFIG_HOME` (`~/.config` when unset).
+//!
+//! ```toml
+//! url = "https://opengate.example.com"
+//! api_key = "..."
+//! ```
+//!
+//! `opengate agent register --save` writes that file.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::task_refs::{self, TaskRef};
+use opengate_models::*;
+
+pub const DEFAULT_URL: &str = "http://localhost:8080";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+}
+
+impl ClientConfig {
+    /// Where the config is read from and saved to, or `None` without a home directory.
+    pub fn path() -> Option<PathBuf> {
+        let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+        if let Some(path) = var("OPENGATE_CLIENT_CONFIG") {
+            return Some(path.into());
+        }
+        let base = var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join("opengate").join("client.toml"))
+    }
+
+    /// The config at `path`; empty when there is no file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Write the config to `path`, readable only by the current user.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+pub struct Client {
+    url: String,
+    api_key: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(url: &str, api_key: Option<String>) -> Self {
+        Client {
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// A client for `url` and `api_key` as given on the command line or in the
+    /// environment, falling back to `config` and then `DEFAULT_URL`.
+    pub fn resolve(url: Option<String>, api_key: Option<String>, config: &ClientConfig) -> Self {
+        let url = url
+            .or_else(|| config.url.clone())
+            .unwrap_or_else(|| DEFAULT_URL.to_string());
+        Self::new(&url, api_key.or_else(|| config.api_key.clone()))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn api_key(&self) -> Option<&str> {
+        self.api_key.as_deref()
+    }
+
+    /// `GET path` as JSON, for endpoints without a typed method here.
+    pub async fn get(&self, path: &str) -> Result<Value, String> {
+        self.send(self.http.get(format!("{}{}", self.url, path)), true)
+            .await
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder, authed: bool) -> Result<Value, String> {
+        let request = match (&self.api_key, authed) {
+            (Some(key), true) => request.bearer_auth(key),
+            (None, true) => {
+                return Err("No API key: pass --api-key, set OPENGATE_API_KEY, or run `opengate agent register --save`".to_string());
+            }
+            (_, false) => request,
+        };
+        let resp = request
+            .timeout(Duration::from_secs(30))
+            .send()
+            .await
+            .map_err(|e| format!("{}: {}", self.url, e))?;
+        let status = resp.status();
+        let body: Value = resp.json().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(match body["error"].as_str() {
+                Some(error) => format!("{} (HTTP {})", error, status.as_u16()),
+                None => format!("HTTP {}", status.as_u16()),
+            });
+        }
+        Ok(body)
+    }
+
+    async fn send_as<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, String> {
+        let body = self.send(request, true).await?;
+        serde_json::from_value(body).map_err(|e| format!("Unexpected response: {}", e))
+    }
+
+    pub async fn list_tasks(&self, filters: &TaskFilters) -> Result<Vec<Task>, String> {
+        let query: Vec<(&str, &str)> = [
+            ("project_id", &filters.project_id),
+            ("status", &filters.status),
+            ("priority", &filters.priority),
+            ("assignee_id", &filters.assignee_id),
+            ("tag", &filters.tag),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| Some((k, v.as_deref()?)))
+        .collect();
+        self.send_as(
+            self.http
+                .get(format!("{}/api/tasks", self.url))
+                .query(&query),
+        )
+        .await
+    }
+
+    /// The full id of the task `id` names: itself, or the one task whose id starts with
+    /// it, the way `opengate task list` abbreviates them.
+    pub async fn resolve_task_id(&self, id: &str) -> Result<String, String> {
+        let id = id.trim().to_ascii_lowercase();
+        let id = id.strip_prefix("og-").unwrap_or(&id);
+        if id.len() == 36 {
+            return Ok(id.to_string());
+        }
+        let tasks = self.list_tasks(&TaskFilters::default()).await?;
+        let prefix = TaskRef::Prefix(id.to_string());
+        match task_refs::resolve(&prefix, &tasks) {
+            Some(task) => Ok(task.id.clone()),
+            None if tasks.iter().any(|t| t.id.starts_with(id)) => {
+                Err(format!("'{}' matches more than one task", id))
+            }
+            None => Err(format!("No task matches '{}'", id)),
+        }
+    }
+
+    pub async fn get_task(&self, id: &str) -> Result<Task, String> {
+        let id = self.resolve_task_id(id).await?;
+        self.send_as(self.http.get(format!("{}/api/tasks/{}", self.url, id)))
+            .await
+    }
+
+    pub async fn claim_task(&self, id: &str) -> Result<Task, String> {
+        let id = self.resolve_task_id(id).await?;
+        self.send_as(
+            self.http
+                .post(format!("{}/api/tasks/{}/claim", self.url, id)),
+        )
+        .await
+    }
+
+    pub async fn complete_task(&self, id: &str, summary: Option<&str>) -> Result<Task, String> {
+        let id = self.resolve_task_id(id).await?;
+        self.send_as(
+            self.http
+                .post(format!("{}/api/tasks/{}/complete", self.url, id))
+                .json(&serde_json::json!({ "summary": summary })),
+        )
+        .await
+    }
+
+    /// Register an agent with the server's setup token: its id and API key.
+    pub async fn register_agent(
+        &self,
+        name: &str,
+        skills: &[String],
+        setup_token: &str,
+    ) -> Result<(String, String), String> {
+        let body = self
+            .send(
+                self.http
+                    .post(format!("{}/api/agents/register", self.url))
+                    .json(&serde_json::json!({
+                        "name": name,
+                        "skills": skills,
+                        "setup_token": setup_token,
+                    })),
+                false,
+            )
+            .await?;
+        let api_key = body["api_key"]
+            .as_str()
+            .ok_or("No api_key in the response")?
+            .to_string();
+        let agent_id = body["agent"]["id"].as_str().unwrap_or_default().to_string();
+        Ok((agent_id, api_key))
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+/// `tasks` as a table for `opengate task list`, with ids cut to their first 8 characters.
+pub fn task_table(tasks: &[Task]) -> String {
+    let rows: Vec<[&str; 5]> = tasks
+        .iter()
+        .map(|t| {
+            [
+                short_id(&t.id),
+                t.status.as_str(),
+                t.priority.as_str(),
+                t.assignee_id.as_deref().map(short_id).unwrap_or("-"),
+                t.title.as_str(),
+            ]
+        })
+        .collect();
+    let header = ["ID", "STATUS", "PRIORITY", "ASSIGNEE", "TITLE"];
+    let widths: Vec<usize> = (0..4)
+        .map(|i| {
+            rows.iter()
+                .map(|r| r[i].chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    std::iter::once(header)
+        .chain(rows)
+        .map(|row| {
+            let mut line = String::new();
+            for (i, width) in widths.iter().enumerate() {
+                line.push_str(&format!("{:<width$}  ", row[i], width = width));
+            }
+            line.push_str(row[4]);
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One task in detail, for `opengate task show`.
+pub fn task_details(task: &Task) -> String {
+    let mut lines = vec![
+        format!("{}  {}", task.id, task.title),
+        format!("status:   {}", task.status),
+        format!("priority: {}", task.priority),
+        format!("project:  {}", task.project_id),
+    ];
+    if let Some(ref assignee) = task.assignee_id {
+        lines.push(format!(
+            "assignee: {} ({})",
+            assignee,
+            task.assignee_type.as_deref().unwrap_or("agent")
+        ));
+    }
+    if !task.tags.is_empty() {
+        lines.push(format!("tags:     {}", task.tags.join(", ")));
+    }
+    if let Some(ref due) = task.due_date {
+        lines.push(format!("due:      {}", due));
+    }
+    if let Some(description) = task.description.as_deref().filter(|d| !d.trim().is_empty()) {
+        lines.push(String::new());
+        lines.push(description.trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_url_and_key_in_order() {
+        let config = ClientConfig {
+            url: Some("https://gate.example.com/".to_string()),
+            api_key: Some("from-config".to_string()),
+        };
+        let client = Client::resolve(None, Some("from-env".to_string()), &config);
+        assert_eq!(client.url(), "https://gate.example.com");
+        assert_eq!(client.api_key.as_deref(), Some("from-env"));
+
+        let client = Client::resolve(None, None, &ClientConfig::default());
+        assert_eq!(client.url(), DEFAULT_URL);
+        assert!(client.api_key.is_none());
+
+        let tmp = std::env::temp_dir().join(format!("opengate-client-{}", uuid::Uuid::new_v4()));
+        let path = tmp.join("client.toml");
+        assert_eq!(ClientConfig::load(&path).unwrap(), ClientConfig::default());
+        config.save(&path).unwrap();
+        assert_eq!(ClientConfig::load(&path).unwrap(), config);
+        std::fs::remove_dir_all(tmp).unwrap();
+    }
+}
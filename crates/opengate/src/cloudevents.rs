@@ -0,0 +1,152 @@
+//! CloudEvents 1.0 envelopes for outbound events.
+//!
+//! Webhooks, WS messages and the SSE stream use OpenGate's own JSON shapes by default.
+//! With `--event-format cloudevents` (or per connection: `"format": "cloudevents"` on a
+//! WS subscribe, `?format=cloudevents` or `Accept: application/cloudevents+json` on the
+//! SSE stream) each event is sent as a structured-mode CloudEvent instead:
+//! `specversion`, `id`, `source` (`/opengate`, or `/opengate/projects/<id>`), `type`
+//! (the OpenGate event type), `time`, optional `subject` (the task) and `data`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::events::Event;
+
+pub const CONTENT_TYPE: &str = "application/cloudevents+json";
+pub const SOURCE: &str = "/opengate";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFormat {
+    Native,
+    CloudEvents,
+}
+
+impl EventFormat {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "opengate" | "native" => Some(EventFormat::Native),
+            "cloudevents" => Some(EventFormat::CloudEvents),
+            _ => None,
+        }
+    }
+}
+
+static CLOUDEVENTS_BY_DEFAULT: AtomicBool = AtomicBool::new(false);
+
+/// Set the server-wide format for webhooks and for connections that don't pick one.
+pub fn set_default_format(format: EventFormat) {
+    CLOUDEVENTS_BY_DEFAULT.store(format == EventFormat::CloudEvents, Ordering::Relaxed);
+}
+
+pub fn default_format() -> EventFormat {
+    if CLOUDEVENTS_BY_DEFAULT.load(Ordering::Relaxed) {
+        EventFormat::CloudEvents
+    } else {
+        EventFormat::Native
+    }
+}
+
+/// Wrap `data` in a CloudEvents 1.0 envelope.
+pub fn envelope(
+    id: &str,
+    event_type: &str,
+    project_id: Option<&str>,
+    subject: Option<&str>,
+    time: DateTime<Utc>,
+    data: &serde_json::Value,
+) -> serde_json::Value {
+    let source = match project_id {
+        Some(pid) => format!("{}/projects/{}", SOURCE, pid),
+        None => SOURCE.to_string(),
+    };
+    let mut event = serde_json::json!({
+        "specversion": "1.0",
+        "id": id,
+        "source": source,
+        "type": event_type,
+        "time": time.to_rfc3339(),
+        "datacontenttype": "application/json",
+        "data": data,
+    });
+    if let Some(subject) = subject {
+        event["subject"] = serde_json::json!(subject);
+    }
+    event
+}
+
+/// A bus event as a CloudEvent. Bus events carry no id, so one is derived from the event
+/// itself — every subscriber sees the same id for the same event, which lets consumers
+/// deduplicate across WS, SSE and brokers.
+pub fn from_bus_event(event: &Event) -> serde_json::Value {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(event).unwrap_or_default());
+    let id = hex::encode(&hasher.finalize()[..16]);
+    let subject = match event.data.get("task_id").and_then(|v| v.as_str()) {
+        Some(task_id) => Some(task_id),
+        // Task lifecycle events carry the task itself as data
+        None if event.event_type.starts_with("task.") => {
+            event.data.get("id").and_then(|v| v.as_str())
+        }
+        None => None,
+    };
+    envelope(
+        &id,
+        &event.event_type,
+        event.project_id.as_deref(),
+        subject,
+        event.timestamp,
+        &event.data,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_bus_events() {
+        let event = Event {
+            event_type: "task.created".to_string(),
+            project_id: Some("p1".to_string()),
+            agent_id: None,
+            event_id: None,
+            data: serde_json::json!({"id": "t1", "title": "Ship it"}),
+            timestamp: Utc::now(),
+        };
+        let wrapped = from_bus_event(&event);
+        assert_eq!(wrapped["specversion"], "1.0");
+        assert_eq!(wrapped["type"], "task.created");
+        assert_eq!(wrapped["source"], "/opengate/projects/p1");
+        assert_eq!(wrapped["subject"], "t1");
+        assert_eq!(wrapped["data"]["title"], "Ship it");
+        // Stable per event
+        assert_eq!(wrapped["id"], from_bus_event(&event)["id"]);
+    }
+
+    #[test]
+    fn omits_missing_subject() {
+        let wrapped = envelope(
+            "42",
+            "agent.offline",
+            None,
+            None,
+            Utc::now(),
+            &serde_json::json!({}),
+        );
+        assert_eq!(wrapped["source"], SOURCE);
+        assert!(wrapped.get("subject").is_none());
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!(
+            EventFormat::from_str("cloudevents"),
+            Some(EventFormat::CloudEvents)
+        );
+        assert_eq!(EventFormat::from_str("opengate"), Some(EventFormat::Native));
+        assert_eq!(EventFormat::from_str("xml"), None);
+    }
+}
@@ -0,0 +1,412 @@
+//! `opengate serve --config server.toml`: the server's deployment settings in one file.
+//!
+//! Every key can be overridden from the environment as `OPENGATE_<SECTION>_<KEY>`
+//! (top-level keys as `OPENGATE_<KEY>`), e.g. `OPENGATE_TLS_CERT` or
+//! `OPENGATE_RATE_LIMIT_REQUESTS_PER_MINUTE`; lists take comma-separated values.
+//! Command-line flags override both.
+//!
+//! ```toml
+//! port = 8443
+//! db = "/var/lib/opengate/opengate.db"
+//! # Serve on more than host:port, or instead of it
+//! listen = ["127.0.0.1:8443", "unix:/run/opengate/opengate.sock"]
+//!
+//! [tls]
+//! cert = "/etc/opengate/cert.pem"
+//! key = "/etc/opengate/key.pem"
+//!
+//! [cors]
+//! allowed_origins = ["https://dashboard.example.com"]
+//!
+//! [retention]
+//! events_days = 90
+//!
+//! [tenant_limits]
+//! projects = 20
+//!
+//! [tenant_limits.tenants.acme]
+//! projects = 100
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+pub use opengate_models::RetentionPolicy;
+
+use crate::listen::Listen;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Addresses to accept connections on (host:port or unix:/path); host:port when empty
+    pub listen: Vec<String>,
+    pub db: String,
+    pub setup_token: String,
+    /// Built web dashboard to serve on the same port; none when empty
+    pub ui_dir: String,
+    pub tls: TlsConfig,
+    pub cors: CorsConfig,
+    pub scheduler: SchedulerConfig,
+    pub retention: RetentionPolicy,
+    pub webhooks: WebhookPolicy,
+    pub rate_limit: RateLimitConfig,
+    pub load_shedding: LoadSheddingConfig,
+    pub compression: CompressionConfig,
+    pub tenant_limits: TenantLimitsConfig,
+    /// Most occurrences in one recurring series (root included); below 1 stops auto-creation
+    pub max_recurrence_occurrences: i64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            listen: Vec::new(),
+            db: "opengate.db".to_string(),
+            setup_token: String::new(),
+            ui_dir: String::new(),
+            tls: TlsConfig::default(),
+            cors: CorsConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            retention: RetentionPolicy::default(),
+            webhooks: WebhookPolicy::default(),
+            rate_limit: RateLimitConfig::default(),
+            load_shedding: LoadSheddingConfig::default(),
+            compression: CompressionConfig::default(),
+            tenant_limits: TenantLimitsConfig::default(),
+            max_recurrence_occurrences: crate::recurrence::DEFAULT_MAX_OCCURRENCES,
+        }
+    }
+}
+
+/// Serve HTTPS with this PEM certificate chain and private key; plain HTTP when unset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+}
+
+impl TlsConfig {
+    pub fn enabled(&self) -> bool {
+        !self.cert.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins browsers may call the API from; any origin when empty
+    pub allowed_origins: Vec<String>,
+}
+
+/// How often each background job runs, in seconds.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Wait after startup before releasing stale agents' tasks, so agents can reconnect
+    pub stale_cleanup_grace_secs: u64,
+    pub stale_cleanup_secs: u64,
+    pub presence_secs: u64,
+    pub knowledge_freshness_secs: u64,
+    pub question_reroute_secs: u64,
+    pub notification_expiry_secs: u64,
+    pub scheduled_tasks_secs: u64,
+    pub retention_secs: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            stale_cleanup_grace_secs: 300,
+            stale_cleanup_secs: 60,
+            presence_secs: 60,
+            knowledge_freshness_secs: 300,
+            question_reroute_secs: 60,
+            notification_expiry_secs: 300,
+            scheduled_tasks_secs: 60,
+            retention_secs: 3600,
+        }
+    }
+}
+
+/// Delivery of outbound agent webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookPolicy {
+    pub max_attempts: u32,
+    pub timeout_secs: u64,
+    /// Attempt n waits n² times this before retrying
+    pub retry_delay_secs: u64,
+}
+
+impl Default for WebhookPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            timeout_secs: 10,
+            retry_delay_secs: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Requests each client (API key, or address when anonymous) may make per minute;
+    /// 0 is unlimited
+    pub requests_per_minute: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoadSheddingConfig {
+    /// Requests served at once across all clients before others get 503; 0 is unlimited
+    pub max_in_flight: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// brotli or gzip JSON and text responses for clients that accept it
+    pub enabled: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Caps for each tenant (an owner_id); 0 is unlimited. Operators and unowned data are
+/// never limited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantLimitsConfig {
+    pub projects: u64,
+    pub open_tasks: u64,
+    pub agents: u64,
+    pub events_per_day: u64,
+    pub webhook_deliveries_per_day: u64,
+    /// Per-tenant overrides of the defaults above
+    pub tenants: BTreeMap<String, TenantLimits>,
+}
+
+/// The limits one tenant gets; None = unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantLimits {
+    pub projects: Option<u64>,
+    pub open_tasks: Option<u64>,
+    pub agents: Option<u64>,
+    pub events_per_day: Option<u64>,
+    pub webhook_deliveries_per_day: Option<u64>,
+}
+
+impl TenantLimitsConfig {
+    /// `tenant`'s overrides over the defaults.
+    pub fn for_tenant(&self, tenant: &str) -> TenantLimits {
+        let own = self.tenants.get(tenant).cloned().unwrap_or_default();
+        let pick = |own: Option<u64>, default: u64| Some(own.unwrap_or(default)).filter(|n| *n > 0);
+        TenantLimits {
+            projects: pick(own.projects, self.projects),
+            open_tasks: pick(own.open_tasks, self.open_tasks),
+            agents: pick(own.agents, self.agents),
+            events_per_day: pick(own.events_per_day, self.events_per_day),
+            webhook_deliveries_per_day: pick(
+                own.webhook_deliveries_per_day,
+                self.webhook_deliveries_per_day,
+            ),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read `path` (defaults only without one) and apply `OPENGATE_*` overrides.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let table = match path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read config {}: {}", path, e))?;
+                toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", path, e))?
+            }
+            None => toml::Table::new(),
+        };
+        let config = Self::from_table(table, |name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_table(
+        mut table: toml::Table,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, String> {
+        let Ok(toml::Value::Table(defaults)) = toml::Value::try_from(Self::default()) else {
+            unreachable!("the defaults serialize to a table");
+        };
+        apply_env(&mut table, &defaults, "OPENGATE", &env)?;
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| format!("Invalid config: {}", e.message()))
+    }
+
+    /// Where to accept connections: `listen`, or `host:port` without it.
+    pub fn listeners(&self) -> Result<Vec<Listen>, String> {
+        if self.listen.is_empty() {
+            return Ok(vec![Listen::Tcp(format!("{}:{}", self.host, self.port))]);
+        }
+        self.listen.iter().map(|spec| Listen::parse(spec)).collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.listeners()?;
+        if !self.ui_dir.is_empty() && !std::path::Path::new(&self.ui_dir).is_dir() {
+            return Err(format!("ui_dir {} is not a directory", self.ui_dir));
+        }
+        if self.tls.cert.is_empty() != self.tls.key.is_empty() {
+            return Err("[tls] needs both cert and key".to_string());
+        }
+        if self.webhooks.max_attempts == 0 {
+            return Err("[webhooks] max_attempts must be at least 1".to_string());
+        }
+        let s = &self.scheduler;
+        let intervals = [
+            ("stale_cleanup_secs", s.stale_cleanup_secs),
+            ("presence_secs", s.presence_secs),
+            ("knowledge_freshness_secs", s.knowledge_freshness_secs),
+            ("question_reroute_secs", s.question_reroute_secs),
+            ("notification_expiry_secs", s.notification_expiry_secs),
+            ("scheduled_tasks_secs", s.scheduled_tasks_secs),
+            ("retention_secs", s.retention_secs),
+        ];
+        if let Some((name, _)) = intervals.iter().find(|(_, secs)| *secs == 0) {
+            return Err(format!("[scheduler] {} must be at least 1", name));
+        }
+        for origin in &self.cors.allowed_origins {
+            if axum::http::HeaderValue::from_str(origin).is_err() {
+                return Err(format!("[cors] invalid origin '{}'", origin));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Overlay `<prefix>_<KEY>` variables for every key `defaults` has, parsed as the
+/// default's type.
+fn apply_env(
+    table: &mut toml::Table,
+    defaults: &toml::Table,
+    prefix: &str,
+    env: &impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    for (key, default) in defaults {
+        let name = format!("{}_{}", prefix, key.to_uppercase());
+        if let toml::Value::Table(section) = default {
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(entry) = entry {
+                apply_env(entry, section, &name, env)?;
+            }
+            continue;
+        }
+        let Some(raw) = env(&name) else {
+            continue;
+        };
+        let invalid = |kind: &str| format!("{} must be {}, got '{}'", name, kind, raw);
+        let value = match default {
+            toml::Value::Integer(_) => {
+                toml::Value::Integer(raw.trim().parse().map_err(|_| invalid("an integer"))?)
+            }
+            toml::Value::Boolean(_) => {
+                toml::Value::Boolean(raw.trim().parse().map_err(|_| invalid("true or false"))?)
+            }
+            toml::Value::Array(_) => toml::Value::Array(
+                raw.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| toml::Value::String(item.to_string()))
+                    .collect(),
+            ),
+            _ => toml::Value::String(raw),
+        };
+        table.insert(key.clone(), value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_file() {
+        let file: toml::Table = toml::from_str(
+            r#"
+            port = 9000
+            db = "/data/og.db"
+
+            [tls]
+            cert = "cert.pem"
+            key = "key.pem"
+
+            [scheduler]
+            presence_secs = 30
+
+            [tenant_limits.tenants.acme]
+            projects = 0
+            agents = 3
+            "#,
+        )
+        .unwrap();
+        let env = |name: &str| match name {
+            "OPENGATE_PORT" => Some("9100".to_string()),
+            "OPENGATE_CORS_ALLOWED_ORIGINS" => {
+                Some("https://a.example, https://b.example".to_string())
+            }
+            "OPENGATE_RATE_LIMIT_REQUESTS_PER_MINUTE" => Some("120".to_string()),
+            "OPENGATE_TENANT_LIMITS_PROJECTS" => Some("10".to_string()),
+            "OPENGATE_LISTEN" => Some("unix:/run/og.sock, 127.0.0.1:9100".to_string()),
+            _ => None,
+        };
+        let config = ServerConfig::from_table(file, env).unwrap();
+        assert_eq!(config.port, 9100);
+        assert_eq!(
+            config.listeners().unwrap(),
+            [
+                Listen::Unix("/run/og.sock".into()),
+                Listen::Tcp("127.0.0.1:9100".to_string())
+            ]
+        );
+        assert_eq!(config.db, "/data/og.db");
+        assert!(config.tls.enabled());
+        assert_eq!(config.scheduler.presence_secs, 30);
+        assert_eq!(config.scheduler.scheduled_tasks_secs, 60);
+        assert_eq!(
+            config.cors.allowed_origins,
+            ["https://a.example", "https://b.example"]
+        );
+        assert_eq!(config.rate_limit.requests_per_minute, 120);
+        assert_eq!(config.webhooks.max_attempts, 3);
+        let acme = config.tenant_limits.for_tenant("acme");
+        assert_eq!(
+            (acme.projects, acme.agents, acme.open_tasks),
+            (None, Some(3), None)
+        );
+        assert_eq!(config.tenant_limits.for_tenant("globex").projects, Some(10));
+
+        let bad = ServerConfig::from_table(toml::Table::new(), |name| {
+            (name == "OPENGATE_PORT").then(|| "high".to_string())
+        });
+        assert_eq!(
+            bad.unwrap_err(),
+            "OPENGATE_PORT must be an integer, got 'high'"
+        );
+        let unknown: toml::Table = toml::from_str("[tls]\ncertificate = \"x\"").unwrap();
+        assert!(ServerConfig::from_table(unknown, |_| None).is_err());
+    }
+}
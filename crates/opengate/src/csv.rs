@@ -0,0 +1,158 @@
+//! `?format=csv` on task lists and `/api/stats/*`, for pasting into spreadsheets: RFC
+//! 4180 text with a header row in a fixed column order and CRLF line ends. Fields a
+//! spreadsheet would evaluate as a formula get a leading `'`. [`parse`] reads CSV
+//! uploads such as Jira exports.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Whether `format` asks for CSV rather than JSON, the default. Any other value is a 400.
+pub fn wanted(format: Option<&str>) -> Result<bool, (StatusCode, Json<serde_json::Value>)> {
+    match format {
+        None | Some("json") => Ok(false),
+        Some("csv") => Ok(true),
+        Some(other) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("Unknown format '{}'; use json or csv", other)
+            })),
+        )),
+    }
+}
+
+fn field(value: &str) -> String {
+    let formula =
+        value.starts_with(['=', '+', '-', '@', '\t', '\r']) && value.parse::<f64>().is_err();
+    let value = if formula {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\r', '\n']) || value.starts_with(' ') || value.ends_with(' ') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// An optional value as a field, empty when unset.
+pub fn opt<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(T::to_string).unwrap_or_default()
+}
+
+/// `rows` under `columns` as a `<name>.csv` download.
+pub fn table<R>(name: &str, columns: &[&str], rows: impl IntoIterator<Item = R>) -> Response
+where
+    R: IntoIterator<Item = String>,
+{
+    let mut out = String::new();
+    let mut line = |fields: Vec<String>| {
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    };
+    line(columns.iter().map(|c| field(c)).collect());
+    for row in rows {
+        line(row.into_iter().map(|v| field(&v)).collect());
+    }
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.csv\"", name),
+            ),
+        ],
+        out,
+    )
+        .into_response()
+}
+
+/// The records of RFC 4180 `text`, header row included. Quoted fields may hold commas,
+/// line breaks and doubled quotes; LF and CRLF line ends are both accepted and blank
+/// lines are dropped.
+pub fn parse(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = text
+        .strip_prefix('\u{feff}')
+        .unwrap_or(text)
+        .chars()
+        .peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+                line += 1;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(format!("Unterminated quoted field at line {}", line));
+    }
+    record.push(field);
+    if record.iter().any(|f| !f.is_empty()) {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_fields() {
+        assert_eq!(field("plain"), "plain");
+        assert_eq!(field("a,b"), "\"a,b\"");
+        assert_eq!(field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(field(" padded"), "\" padded\"");
+        assert_eq!(field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(field("-3.5"), "-3.5");
+        assert_eq!(opt(&None::<i64>), "");
+        assert_eq!(opt(&Some(4)), "4");
+    }
+
+    #[test]
+    fn parses_records() {
+        let text = "key,summary\r\nA-1,\"Fix, then \"\"ship\"\"\"\n\nA-2,\"two\nlines\"\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                vec!["key", "summary"],
+                vec!["A-1", "Fix, then \"ship\""],
+                vec!["A-2", "two\nlines"],
+            ]
+        );
+        assert_eq!(parse("a,,b").unwrap(), vec![vec!["a", "", "b"]]);
+        assert!(parse("a,\"open").is_err());
+    }
+}
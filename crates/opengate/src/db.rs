@@ -1,5 +1,7 @@
 use rusqlite::Connection;
 
+use crate::backlinks;
+
 pub fn init_db(path: &str) -> Connection {
     let conn = Connection::open(path).expect("Failed to open database");
 
@@ -392,6 +394,501 @@ pub fn init_db(path: &str) -> Connection {
         [],
     );
 
+    // v20: pausable recurrence — flag lives on the series parent
+    let _ = conn.execute(
+        "ALTER TABLE tasks ADD COLUMN recurrence_paused INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // v21: round-robin assignment cursor — monotonic sequence of the agent's last auto-assignment
+    let _ = conn.execute(
+        "ALTER TABLE agents ADD COLUMN last_assigned_seq INTEGER",
+        [],
+    );
+
+    // v22: per-agent task quotas — intake ledger counts each task once per agent
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN daily_task_quota INTEGER", []);
+    let _ = conn.execute(
+        "ALTER TABLE agents ADD COLUMN weekly_task_quota INTEGER",
+        [],
+    );
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS agent_task_intake (
+            agent_id TEXT NOT NULL,
+            task_id TEXT NOT NULL,
+            taken_at TEXT NOT NULL,
+            PRIMARY KEY (agent_id, task_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_intake_agent_time ON agent_task_intake(agent_id, taken_at);
+        ",
+    )
+    .expect("Failed to create agent_task_intake table");
+
+    // v23: last presence state seen by the sweeper, to detect offline/online transitions
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN presence_state TEXT", []);
+
+    // v24: per-event notification preferences; wake flag on each notification for the bridge
+    let _ = conn.execute(
+        "ALTER TABLE agents ADD COLUMN notification_preferences TEXT",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN wake INTEGER NOT NULL DEFAULT 1",
+        [],
+    );
+
+    // v25: additional scoped API keys per agent
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS agent_api_keys (
+            id TEXT PRIMARY KEY,
+            agent_id TEXT NOT NULL REFERENCES agents(id),
+            name TEXT,
+            key_hash TEXT NOT NULL UNIQUE,
+            read_only INTEGER NOT NULL DEFAULT 0,
+            project_id TEXT,
+            no_admin INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            last_used_at TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_agent_api_keys_agent ON agent_api_keys(agent_id);
+        ",
+    )
+    .expect("Failed to create agent_api_keys table");
+
+    // v26: last self-reported heartbeat status (load, version, message) as JSON
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN heartbeat_status TEXT", []);
+
+    // v27: knowledge versioning — every write snapshots the entry as a numbered version
+    let _ = conn.execute(
+        "ALTER TABLE project_knowledge ADD COLUMN version INTEGER NOT NULL DEFAULT 1",
+        [],
+    );
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS knowledge_versions (
+            project_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            version INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            metadata TEXT,
+            tags TEXT NOT NULL DEFAULT '[]',
+            category TEXT,
+            author_type TEXT NOT NULL,
+            author_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (project_id, key, version)
+        );
+        ",
+    )
+    .expect("Failed to create knowledge_versions table");
+
+    // v28: knowledge entries linked to tasks
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS task_knowledge_links (
+            task_id TEXT NOT NULL,
+            knowledge_id TEXT NOT NULL,
+            linked_by_type TEXT NOT NULL,
+            linked_by_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (task_id, knowledge_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_task_knowledge_links_knowledge ON task_knowledge_links(knowledge_id);
+        ",
+    )
+    .expect("Failed to create task_knowledge_links table");
+
+    // v29: artifacts attached to knowledge entries
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS knowledge_attachments (
+            id TEXT PRIMARY KEY,
+            knowledge_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            artifact_type TEXT NOT NULL,
+            value TEXT NOT NULL,
+            created_by_type TEXT NOT NULL,
+            created_by_id TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_knowledge_attachments_entry ON knowledge_attachments(knowledge_id);
+        ",
+    )
+    .expect("Failed to create knowledge_attachments table");
+
+    // v30: knowledge review deadlines; stale_notified_at marks entries already announced
+    let _ = conn.execute(
+        "ALTER TABLE project_knowledge ADD COLUMN review_after TEXT",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE project_knowledge ADD COLUMN stale_notified_at TEXT",
+        [],
+    );
+
+    // v31: [[key]] references between knowledge entries, backfilled from existing content
+    let links_existed: bool = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'knowledge_links'",
+            [],
+            |r| r.get::<_, i64>(0),
+        )
+        .map(|n| n > 0)
+        .unwrap_or(false);
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS knowledge_links (
+            project_id TEXT NOT NULL,
+            from_key TEXT NOT NULL,
+            to_key TEXT NOT NULL,
+            PRIMARY KEY (project_id, from_key, to_key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_knowledge_links_target ON knowledge_links(project_id, to_key);
+        ",
+    )
+    .expect("Failed to create knowledge_links table");
+    if !links_existed {
+        let entries: Vec<(String, String, String)> = conn
+            .prepare("SELECT project_id, key, content FROM project_knowledge")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+                    .collect()
+            })
+            .unwrap_or_default();
+        for (project_id, key, content) in entries {
+            for target in backlinks::extract_references(&content) {
+                let _ = conn.execute(
+                    "INSERT OR IGNORE INTO knowledge_links (project_id, from_key, to_key) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![project_id, key, target],
+                );
+            }
+        }
+    }
+
+    // v32: opt-in enforcement of knowledge category templates
+    let _ = conn.execute(
+        "ALTER TABLE projects ADD COLUMN enforce_knowledge_templates INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // v33: knowledge reads per entry and reader
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS knowledge_reads (
+            project_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            reader_type TEXT NOT NULL,
+            reader_id TEXT NOT NULL,
+            read_count INTEGER NOT NULL DEFAULT 0,
+            last_read_at TEXT NOT NULL,
+            PRIMARY KEY (project_id, key, reader_type, reader_id)
+        );
+        ",
+    )
+    .expect("Failed to create knowledge_reads table");
+
+    // v34: threaded question replies
+    let _ = conn.execute(
+        "ALTER TABLE question_replies ADD COLUMN parent_reply_id TEXT",
+        [],
+    );
+
+    // v35: question routing history + when the current target was set
+    let _ = conn.execute("ALTER TABLE task_questions ADD COLUMN targeted_at TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE task_questions ADD COLUMN routing_history TEXT",
+        [],
+    );
+    let _ = conn.execute(
+        "UPDATE task_questions SET targeted_at = created_at WHERE target_id IS NOT NULL AND targeted_at IS NULL",
+        [],
+    );
+
+    // v36: artifacts attached to questions and replies
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS question_attachments (
+            id TEXT PRIMARY KEY,
+            question_id TEXT NOT NULL REFERENCES task_questions(id) ON DELETE CASCADE,
+            reply_id TEXT,
+            name TEXT NOT NULL,
+            artifact_type TEXT NOT NULL,
+            value TEXT NOT NULL,
+            created_by_type TEXT NOT NULL,
+            created_by_id TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_question_attachments_question ON question_attachments(question_id);
+        ",
+    )
+    .expect("Failed to create question_attachments table");
+
+    // v37: project-level questions — task_id becomes optional and questions carry their
+    // project. SQLite can't relax NOT NULL in place, so the table is rebuilt once.
+    let task_id_required: bool = conn
+        .query_row(
+            "SELECT \"notnull\" FROM pragma_table_info('task_questions') WHERE name = 'task_id'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|v| v == 1)
+        .unwrap_or(false);
+    if task_id_required {
+        conn.execute_batch(
+            "BEGIN;
+            CREATE TABLE task_questions_v37 (
+                id TEXT PRIMARY KEY,
+                task_id TEXT REFERENCES tasks(id) ON DELETE CASCADE,
+                question TEXT NOT NULL,
+                question_type TEXT NOT NULL DEFAULT 'clarification',
+                context TEXT,
+                asked_by_type TEXT NOT NULL,
+                asked_by_id TEXT NOT NULL,
+                target_type TEXT,
+                target_id TEXT,
+                required_capability TEXT,
+                status TEXT NOT NULL DEFAULT 'open',
+                blocking INTEGER NOT NULL DEFAULT 1,
+                resolved_by_type TEXT,
+                resolved_by_id TEXT,
+                resolution TEXT,
+                created_at TEXT NOT NULL,
+                resolved_at TEXT,
+                dismissed_at TEXT,
+                dismissed_reason TEXT,
+                targeted_at TEXT,
+                routing_history TEXT,
+                project_id TEXT
+            );
+            INSERT INTO task_questions_v37 (id, task_id, question, question_type, context, asked_by_type, asked_by_id, target_type, target_id, required_capability, status, blocking, resolved_by_type, resolved_by_id, resolution, created_at, resolved_at, dismissed_at, dismissed_reason, targeted_at, routing_history, project_id)
+                SELECT q.id, q.task_id, q.question, q.question_type, q.context, q.asked_by_type, q.asked_by_id, q.target_type, q.target_id, q.required_capability, q.status, q.blocking, q.resolved_by_type, q.resolved_by_id, q.resolution, q.created_at, q.resolved_at, q.dismissed_at, q.dismissed_reason, q.targeted_at, q.routing_history, t.project_id
+                FROM task_questions q LEFT JOIN tasks t ON t.id = q.task_id;
+            DROP TABLE task_questions;
+            ALTER TABLE task_questions_v37 RENAME TO task_questions;
+            CREATE INDEX IF NOT EXISTS idx_questions_task ON task_questions(task_id);
+            CREATE INDEX IF NOT EXISTS idx_questions_target ON task_questions(target_type, target_id, status);
+            CREATE INDEX IF NOT EXISTS idx_questions_project ON task_questions(project_id, status);
+            COMMIT;",
+        )
+        .expect("Failed to rebuild task_questions for project-level questions");
+    }
+
+    // v38: question priority — existing task questions inherit their task's priority
+    if conn
+        .execute(
+            "ALTER TABLE task_questions ADD COLUMN priority TEXT NOT NULL DEFAULT 'medium'",
+            [],
+        )
+        .is_ok()
+    {
+        conn.execute(
+            "UPDATE task_questions SET priority = COALESCE(
+                (SELECT t.priority FROM tasks t WHERE t.id = task_questions.task_id), 'medium')",
+            [],
+        )
+        .expect("Failed to backfill question priorities");
+    }
+
+    // v39: outbox for the Kafka sink — events wait here until the broker acknowledges them
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS event_outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            topic TEXT NOT NULL,
+            event_key TEXT,
+            payload TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            created_at TEXT NOT NULL
+        );
+        ",
+    )
+    .expect("Failed to create event_outbox table");
+
+    // v40: per-agent webhook payload template (JSON with {{field}} placeholders)
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN webhook_template TEXT", []);
+
+    // v41: email channel of the notification routing matrix
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN email INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // v42: Slack integration per project, and the thread each task is posted in
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS slack_integrations (
+            project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+            webhook_url TEXT,
+            bot_token TEXT,
+            channel TEXT,
+            signing_secret TEXT,
+            events TEXT NOT NULL,
+            dashboard_url TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS slack_threads (
+            channel TEXT NOT NULL,
+            thread_ts TEXT NOT NULL,
+            task_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (channel, thread_ts)
+        );
+        CREATE INDEX IF NOT EXISTS idx_slack_threads_task ON slack_threads(task_id);
+        ",
+    )
+    .expect("Failed to create slack tables");
+
+    // v43: phone push targets (ntfy / Pushover) for agents and the users who own them
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS push_targets (
+            subject_type TEXT NOT NULL CHECK(subject_type IN ('agent','user')),
+            subject_id TEXT NOT NULL,
+            provider TEXT NOT NULL CHECK(provider IN ('ntfy','pushover')),
+            target TEXT NOT NULL,
+            token TEXT,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (subject_type, subject_id)
+        );
+        ",
+    )
+    .expect("Failed to create push_targets table");
+
+    // v44: notifications expired by an agent's archive_after_hours policy
+    conn.execute(
+        "ALTER TABLE notifications ADD COLUMN archived INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // v45: provider-native signature verification for inbound triggers
+    let _ = conn.execute(
+        "ALTER TABLE webhook_triggers ADD COLUMN verification TEXT NOT NULL DEFAULT 'secret'",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE webhook_triggers ADD COLUMN signing_secret TEXT",
+        [],
+    );
+
+    // v46: trigger dedup and rate limiting, and the deliveries dedup has seen
+    let _ = conn.execute("ALTER TABLE webhook_triggers ADD COLUMN dedup_key TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE webhook_triggers ADD COLUMN dedup_window_hours INTEGER NOT NULL DEFAULT 24",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE webhook_triggers ADD COLUMN rate_limit_per_minute INTEGER",
+        [],
+    );
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS trigger_deliveries (
+            trigger_id TEXT NOT NULL REFERENCES webhook_triggers(id) ON DELETE CASCADE,
+            dedup_value TEXT NOT NULL,
+            received_at TEXT NOT NULL,
+            PRIMARY KEY (trigger_id, dedup_value)
+        );
+        CREATE INDEX IF NOT EXISTS idx_trigger_log_received ON webhook_trigger_logs(trigger_id, received_at);
+        ",
+    )
+    .expect("Failed to create trigger_deliveries table");
+
+    // Runtime settings: one row per key that was changed from its default
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_by TEXT,
+            updated_at TEXT NOT NULL
+        );
+        ",
+    )
+    .expect("Failed to create settings table");
+
+    // Tenant isolation for the event log: the owner of the event's project, stamped at
+    // insert so replays and resumes can be scoped without a join
+    if conn
+        .execute("ALTER TABLE events ADD COLUMN owner_id TEXT", [])
+        .is_ok()
+    {
+        let _ = conn.execute(
+            "UPDATE events SET owner_id = (SELECT owner_id FROM projects WHERE projects.id = events.project_id)",
+            [],
+        );
+    }
+    let _ = conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_events_owner_id ON events(owner_id, id)",
+        [],
+    );
+
+    // Registered tenants (owner_ids) and the setup tokens that register agents into them
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tenants (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'active',
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS tenant_setup_tokens (
+            token_hash TEXT PRIMARY KEY,
+            tenant_id TEXT NOT NULL REFERENCES tenants(id),
+            created_at TEXT NOT NULL
+        );
+        ",
+    )
+    .expect("Failed to create tenants tables");
+
+    // Usage rows remember the reporting agent's tier and model, so cost analytics don't
+    // shift when an agent is later reconfigured
+    let _ = conn.execute("ALTER TABLE task_usage ADD COLUMN cost_tier TEXT", []);
+    let _ = conn.execute("ALTER TABLE task_usage ADD COLUMN model TEXT", []);
+    let _ = conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_usage_reported_at ON task_usage(reported_at)",
+        [],
+    );
+
+    // When a dependency was added, for blocked-time accounting; null on older rows
+    let _ = conn.execute(
+        "ALTER TABLE task_dependencies ADD COLUMN created_at TEXT",
+        [],
+    );
+
+    // GitHub integration: the token OpenGate comments on a project's pull requests with
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS github_integrations (
+            project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+            token TEXT,
+            comment_on_complete INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        ",
+    )
+    .expect("Failed to create github_integrations table");
+
+    // GitLab integration, the per-project alternative to GitHub
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS gitlab_integrations (
+            project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+            token TEXT,
+            gitlab_project TEXT,
+            comment_on_complete INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        ",
+    )
+    .expect("Failed to create gitlab_integrations table");
+
+    // Linear integration: the API token for issue import and completion sync
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS linear_integrations (
+            project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+            token TEXT,
+            team TEXT,
+            sync_on_complete INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        ",
+    )
+    .expect("Failed to create linear_integrations table");
+
     conn
 }
 
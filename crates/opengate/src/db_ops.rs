@@ -1,9 +1,15 @@
-use chrono::Utc;
+use chrono::{Datelike, Utc};
 use rusqlite::{params, Connection};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 
+use crate::backlinks;
+use crate::freshness;
+use crate::presence;
+use crate::question_routing;
+use crate::recurrence;
+
 use opengate_models::*;
 
 // --- Helpers ---
@@ -42,15 +48,183 @@ pub fn emit_event(
     actor_id: &str,
     payload: &serde_json::Value,
 ) -> Vec<PendingNotifWebhook> {
+    record_event(
+        conn, event_type, task_id, project_id, actor_type, actor_id, payload,
+    )
+    .1
+}
+
+/// Like [`emit_event`], also returning the stored event's id (the cursor WS clients
+/// resume from).
+pub fn record_event(
+    conn: &Connection,
+    event_type: &str,
+    task_id: Option<&str>,
+    project_id: &str,
+    actor_type: &str,
+    actor_id: &str,
+    payload: &serde_json::Value,
+) -> (i64, Vec<PendingNotifWebhook>) {
     let payload_str = serde_json::to_string(payload).unwrap_or_else(|_| "{}".to_string());
+    // The Kafka outbox row commits with the event or not at all
+    conn.execute_batch("SAVEPOINT record_event").unwrap();
     conn.execute(
-        "INSERT INTO events (event_type, task_id, project_id, actor_type, actor_id, payload) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        "INSERT INTO events (event_type, task_id, project_id, actor_type, actor_id, payload, owner_id) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, (SELECT owner_id FROM projects WHERE id = ?3))",
         params![event_type, task_id, project_id, actor_type, actor_id, payload_str],
     )
     .unwrap();
-
     let event_id = conn.last_insert_rowid();
-    route_event_notifications(conn, event_id, event_type, task_id, project_id, payload)
+    enqueue_kafka_event(conn, event_id, event_type, task_id, project_id, payload);
+    conn.execute_batch("RELEASE record_event").unwrap();
+
+    (
+        event_id,
+        route_event_notifications(conn, event_id, event_type, task_id, project_id, payload),
+    )
+}
+
+/// Queue a just-stored event for the Kafka sink if a route selects its type, in the
+/// shape it has on the bus: task events carry the task as data, others their payload.
+fn enqueue_kafka_event(
+    conn: &Connection,
+    event_id: i64,
+    event_type: &str,
+    task_id: Option<&str>,
+    project_id: &str,
+    payload: &serde_json::Value,
+) {
+    let Some(topic) = crate::kafka::configured_kafka().and_then(|k| k.topic_for(event_type)) else {
+        return;
+    };
+    let task = task_id.and_then(|id| get_task(conn, None, id));
+    let event = crate::events::Event {
+        event_type: event_type.to_string(),
+        project_id: Some(project_id.to_string()),
+        agent_id: task.as_ref().and_then(|t| t.assignee_id.clone()),
+        event_id: Some(event_id),
+        data: match task {
+            Some(task) => serde_json::to_value(task).unwrap_or_default(),
+            None => payload.clone(),
+        },
+        timestamp: Utc::now(),
+    };
+    let event = serde_json::to_string(&event).unwrap_or_default();
+    enqueue_outbox_event(conn, topic, Some(project_id), &event);
+}
+
+/// Most events one replay call will route.
+const REPLAY_LIMIT: i64 = 1000;
+
+/// Re-run notification routing over stored events, oldest first. Routing that lands on
+/// a notification the agent already has doesn't duplicate it: an unread one is handed
+/// back for redelivery (the wake-up that was missed), a read one is left alone. The
+/// returned webhooks are for the caller to fire or drop.
+pub fn replay_events(
+    conn: &Connection,
+    tenant: Option<&str>,
+    input: &EventReplayRequest,
+) -> (EventReplayResult, Vec<PendingNotifWebhook>) {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, event_type, task_id, project_id, payload FROM events
+             WHERE (?1 IS NULL OR id >= ?1) AND (?2 IS NULL OR id <= ?2) AND (?3 IS NULL OR task_id = ?3)
+             AND (?5 IS NULL OR owner_id IS NULL OR owner_id = ?5)
+             ORDER BY id ASC LIMIT ?4",
+        )
+        .unwrap();
+    let events: Vec<(i64, String, Option<String>, String, String)> = stmt
+        .query_map(
+            params![
+                input.from_event_id,
+                input.to_event_id,
+                input.task_id,
+                REPLAY_LIMIT,
+                tenant
+            ],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut result = EventReplayResult {
+        events_replayed: events.len() as i64,
+        notifications_created: 0,
+        notifications_redelivered: 0,
+        webhooks_fired: false,
+    };
+    let mut pending = Vec::new();
+    for (event_id, event_type, task_id, project_id, payload) in &events {
+        let payload: serde_json::Value = serde_json::from_str(payload).unwrap_or_default();
+        let routed = route_event_notifications(
+            conn,
+            *event_id,
+            event_type,
+            task_id.as_deref(),
+            project_id,
+            &payload,
+        );
+        for notif in routed {
+            if input
+                .agent_id
+                .as_deref()
+                .is_some_and(|agent| agent != notif.agent_id)
+            {
+                delete_notification(conn, notif.notification_id);
+                continue;
+            }
+            let earlier: Option<(i64, bool)> = conn
+                .query_row(
+                    "SELECT id, read FROM notifications
+                     WHERE agent_id = ?1 AND event_id = ?2 AND event_type = ?3 AND id != ?4
+                     ORDER BY id ASC LIMIT 1",
+                    params![
+                        notif.agent_id,
+                        event_id,
+                        notif.event_type,
+                        notif.notification_id
+                    ],
+                    |row| Ok((row.get(0)?, row.get::<_, i64>(1)? != 0)),
+                )
+                .ok();
+            match earlier {
+                Some((earlier_id, read)) => {
+                    delete_notification(conn, notif.notification_id);
+                    if !read {
+                        result.notifications_redelivered += 1;
+                        // Re-deliver to the endpoint, but don't buzz a phone twice
+                        pending.push(PendingNotifWebhook {
+                            notification_id: earlier_id,
+                            push: false,
+                            ..notif
+                        });
+                    }
+                }
+                None => {
+                    result.notifications_created += 1;
+                    pending.push(notif);
+                }
+            }
+        }
+    }
+    (result, pending)
+}
+
+fn delete_notification(conn: &Connection, notification_id: i64) {
+    conn.execute(
+        "DELETE FROM notifications WHERE id = ?1",
+        params![notification_id],
+    )
+    .unwrap_or(0);
 }
 
 fn actor_name_from_payload(payload: &serde_json::Value) -> String {
@@ -81,9 +255,73 @@ pub fn insert_question_notification(
     body: Option<&str>,
     task_id: Option<&str>,
 ) -> PendingNotifWebhook {
-    insert_notification(conn, agent_id, event_id, event_type, title, body, task_id)
+    insert_notification(
+        conn, agent_id, event_id, event_type, title, body, task_id, None,
+    )
+}
+
+/// The agent's preference for `event_type`, falling back to its "*" entry, then the
+/// built-in defaults for that event type.
+fn notification_preference(
+    conn: &Connection,
+    agent_id: &str,
+    event_type: &str,
+) -> NotificationPreference {
+    let prefs: BTreeMap<String, NotificationPreference> = conn
+        .query_row(
+            "SELECT notification_preferences FROM agents WHERE id = ?1",
+            params![agent_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    NotificationPreference::resolve(&prefs, event_type)
+}
+
+fn priority_rank(priority: &str) -> usize {
+    NOTIFICATION_PRIORITIES
+        .iter()
+        .position(|p| *p == priority)
+        .unwrap_or(NOTIFICATION_PRIORITIES.len())
+}
+
+/// Insert a routed notification unless the recipient opted out of this event type,
+/// or the task is below their `min_priority`.
+#[allow(clippy::too_many_arguments)]
+fn notify(
+    conn: &Connection,
+    agent_id: &str,
+    event_id: i64,
+    event_type: &str,
+    title: &str,
+    body: Option<&str>,
+    task_id: Option<&str>,
+    task_priority: Option<&str>,
+) -> Option<PendingNotifWebhook> {
+    let pref = notification_preference(conn, agent_id, event_type);
+    if !pref.in_app {
+        return None;
+    }
+    if let (Some(min), Some(priority)) = (pref.min_priority.as_deref(), task_priority) {
+        if priority_rank(priority) > priority_rank(min) {
+            return None;
+        }
+    }
+    Some(insert_notification(
+        conn,
+        agent_id,
+        event_id,
+        event_type,
+        title,
+        body,
+        task_id,
+        task_priority,
+    ))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn insert_notification(
     conn: &Connection,
     agent_id: &str,
@@ -92,10 +330,18 @@ fn insert_notification(
     title: &str,
     body: Option<&str>,
     task_id: Option<&str>,
+    priority: Option<&str>,
 ) -> PendingNotifWebhook {
+    let pref = notification_preference(conn, agent_id, event_type);
+    // A push floor needs a priority to compare against
+    let push = pref.push
+        && match pref.push_min_priority.as_deref() {
+            Some(min) => priority.is_some_and(|p| priority_rank(p) <= priority_rank(min)),
+            None => true,
+        };
     conn.execute(
-        "INSERT INTO notifications (agent_id, event_id, event_type, title, body, read, task_id) VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)",
-        params![agent_id, event_id, event_type, title, body, task_id],
+        "INSERT INTO notifications (agent_id, event_id, event_type, title, body, read, task_id, wake, email) VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7, ?8)",
+        params![agent_id, event_id, event_type, title, body, task_id, pref.wake, pref.email],
     )
     .unwrap();
     let notification_id = conn.last_insert_rowid();
@@ -105,6 +351,7 @@ fn insert_notification(
         event_type: event_type.to_string(),
         title: title.to_string(),
         body: body.map(|s| s.to_string()),
+        push,
     }
 }
 
@@ -119,13 +366,14 @@ fn route_event_notifications(
     let actor_name = actor_name_from_payload(payload);
     let task = task_id.and_then(|id| get_task(conn, None, id));
     let creator_id = task_creator_agent_id(task.as_ref());
+    let task_priority = task.as_ref().map(|t| t.priority.clone());
     let mut pending: Vec<PendingNotifWebhook> = Vec::new();
 
     match event_type {
         "task.assigned" => {
             if let Some(task) = task {
                 if let Some(executor_id) = task.assignee_id {
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         &executor_id,
                         event_id,
@@ -133,6 +381,7 @@ fn route_event_notifications(
                         &format!("Assigned: {}", task.title),
                         Some(&format!("{} assigned you this task.", actor_name)),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
             }
@@ -141,7 +390,7 @@ fn route_event_notifications(
             // Notify the task creator that someone claimed their task
             if let (Some(task), Some(creator_id)) = (&task, &creator_id) {
                 if task.assignee_id.as_deref() != Some(creator_id.as_str()) {
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         creator_id,
                         event_id,
@@ -149,6 +398,7 @@ fn route_event_notifications(
                         &format!("Claimed: {}", task.title),
                         Some(&format!("{} claimed this task.", actor_name)),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
             }
@@ -158,7 +408,7 @@ fn route_event_notifications(
                 // Notify task creator
                 if let Some(creator_id) = &creator_id {
                     if task.assignee_id.as_deref() != Some(creator_id.as_str()) {
-                        pending.push(insert_notification(
+                        pending.extend(notify(
                             conn,
                             creator_id,
                             event_id,
@@ -166,13 +416,14 @@ fn route_event_notifications(
                             &format!("Progress: {}", task.title),
                             Some("New task activity posted."),
                             task_id,
+                            task_priority.as_deref(),
                         ));
                     }
                 }
                 // Notify reviewer if different from creator
                 if let Some(reviewer_id) = task.reviewer_id.as_deref() {
                     if Some(reviewer_id) != creator_id.as_deref() {
-                        pending.push(insert_notification(
+                        pending.extend(notify(
                             conn,
                             reviewer_id,
                             event_id,
@@ -180,6 +431,7 @@ fn route_event_notifications(
                             &format!("Progress: {}", task.title),
                             Some("Task progress update posted."),
                             task_id,
+                            task_priority.as_deref(),
                         ));
                     }
                 }
@@ -188,7 +440,7 @@ fn route_event_notifications(
         "task.blocked" => {
             // Notify task creator about blocked tasks
             if let (Some(task), Some(creator_id)) = (&task, &creator_id) {
-                pending.push(insert_notification(
+                pending.extend(notify(
                     conn,
                     creator_id,
                     event_id,
@@ -196,13 +448,14 @@ fn route_event_notifications(
                     &format!("🚨 Blocked: {}", task.title),
                     Some("Task is blocked and needs intervention."),
                     task_id,
+                    task_priority.as_deref(),
                 ));
             }
         }
         "task.completed" | "task.review_requested" => {
             if let Some(task) = &task {
                 if let Some(reviewer_id) = task.reviewer_id.as_deref() {
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         reviewer_id,
                         event_id,
@@ -210,9 +463,10 @@ fn route_event_notifications(
                         &format!("Review needed: {}", task.title),
                         Some("Task is ready for review."),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 } else if let Some(creator_id) = &creator_id {
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         creator_id,
                         event_id,
@@ -220,6 +474,7 @@ fn route_event_notifications(
                         &format!("Completed: {}", task.title),
                         Some("Task has been completed."),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
             }
@@ -228,7 +483,7 @@ fn route_event_notifications(
             if let Some(task) = &task {
                 // Notify creator
                 if let Some(creator_id) = &creator_id {
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         creator_id,
                         event_id,
@@ -236,12 +491,13 @@ fn route_event_notifications(
                         &format!("Approved: {}", task.title),
                         Some("Task was approved."),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
                 // Notify assignee if different from creator
                 if let Some(executor_id) = task.assignee_id.as_deref() {
                     if Some(executor_id) != creator_id.as_deref() {
-                        pending.push(insert_notification(
+                        pending.extend(notify(
                             conn,
                             executor_id,
                             event_id,
@@ -249,6 +505,7 @@ fn route_event_notifications(
                             &format!("Approved: {}", task.title),
                             Some("Your task was approved."),
                             task_id,
+                            task_priority.as_deref(),
                         ));
                     }
                 }
@@ -258,7 +515,7 @@ fn route_event_notifications(
             // Notify the task assignee that the reviewer has started reviewing
             if let Some(task) = &task {
                 if let Some(assignee_id) = task.assignee_id.as_deref() {
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         assignee_id,
                         event_id,
@@ -266,6 +523,7 @@ fn route_event_notifications(
                         &format!("Review started: {}", task.title),
                         Some(&format!("{} started reviewing your task.", actor_name)),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
             }
@@ -273,7 +531,7 @@ fn route_event_notifications(
         "task.changes_requested" => {
             if let Some(task) = &task {
                 if let Some(executor_id) = task.assignee_id.as_deref() {
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         executor_id,
                         event_id,
@@ -281,6 +539,7 @@ fn route_event_notifications(
                         &format!("Changes requested: {}", task.title),
                         Some("Reviewer requested changes."),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
             }
@@ -292,7 +551,7 @@ fn route_event_notifications(
                         .get("unblocked_by")
                         .and_then(|v| v.as_str())
                         .unwrap_or("a dependency");
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         assignee_id,
                         event_id,
@@ -303,6 +562,7 @@ fn route_event_notifications(
                             unblocked_by
                         )),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
             }
@@ -310,7 +570,36 @@ fn route_event_notifications(
         "knowledge.updated" => {
             // No longer notify a specific "orchestrator" — knowledge updates are visible in dashboard
         }
-        "task.question_asked" | "task.question_assigned" => {
+        "knowledge.stale" => {
+            // Ask the agent that created the entry to review it
+            let author_agent = payload
+                .get("author_id")
+                .and_then(|v| v.as_str())
+                .filter(|_| payload.get("author_type").and_then(|v| v.as_str()) == Some("agent"));
+            if let Some(author_id) = author_agent {
+                let title = payload
+                    .get("knowledge_title")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                pending.extend(notify(
+                    conn,
+                    author_id,
+                    event_id,
+                    event_type,
+                    &format!("Knowledge needs review: {}", title),
+                    payload.get("knowledge_key").and_then(|v| v.as_str()),
+                    None,
+                    None,
+                ));
+            }
+        }
+        "task.question_asked" | "task.question_assigned" | "task.question_rerouted" => {
+            // The question's own priority decides urgency, not its task's
+            let question_priority = payload
+                .get("priority")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .or(task_priority.clone());
             // Notify the question target if they are an agent
             if let Some(target_id) = payload.get("target_id").and_then(|v| v.as_str()) {
                 let target_type = payload
@@ -327,7 +616,7 @@ fn route_event_notifications(
                         .and_then(|v| v.as_str())
                         .unwrap_or("");
                     let snippet: String = question_text.chars().take(200).collect();
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         target_id,
                         event_id,
@@ -335,10 +624,53 @@ fn route_event_notifications(
                         &format!("Question on: {}", task_title),
                         Some(&snippet),
                         task_id,
+                        question_priority.as_deref(),
                     ));
                 }
             }
         }
+        "task.question_unrouted" => {
+            // Nobody picked the question up — hand it to the orchestrators, or back to an
+            // agent asker when there are none
+            let mut recipients: Vec<String> = conn
+                .prepare("SELECT id FROM agents WHERE role = 'orchestrator'")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .filter_map(|r| r.ok())
+                .collect();
+            if recipients.is_empty()
+                && payload.get("asked_by_type").and_then(|v| v.as_str()) == Some("agent")
+            {
+                recipients.extend(
+                    payload
+                        .get("asked_by_id")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string),
+                );
+            }
+            let task_title = payload
+                .get("task_title")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let question_text = payload
+                .get("question")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let snippet: String = question_text.chars().take(200).collect();
+            for agent_id in recipients {
+                pending.extend(notify(
+                    conn,
+                    &agent_id,
+                    event_id,
+                    event_type,
+                    &format!("Unrouted question on: {}", task_title),
+                    Some(&snippet),
+                    task_id,
+                    task_priority.as_deref(),
+                ));
+            }
+        }
         "task.question_replied" => {
             // Notify the task assignee about the reply
             if let Some(task) = &task {
@@ -349,7 +681,7 @@ fn route_event_notifications(
                         .unwrap_or("Someone");
                     let body_text = payload.get("body").and_then(|v| v.as_str()).unwrap_or("");
                     let snippet: String = body_text.chars().take(150).collect();
-                    pending.push(insert_notification(
+                    pending.extend(notify(
                         conn,
                         assignee_id,
                         event_id,
@@ -357,6 +689,7 @@ fn route_event_notifications(
                         &format!("Reply on: {}", task.title),
                         Some(&format!("{}: {}", actor, snippet)),
                         task_id,
+                        task_priority.as_deref(),
                     ));
                 }
             }
@@ -370,7 +703,7 @@ fn route_event_notifications(
                 let author = actor_name_from_payload(payload);
                 let task_title = task.as_ref().map(|t| t.title.as_str()).unwrap_or("");
                 let snippet: String = comment.chars().take(300).collect();
-                pending.push(insert_notification(
+                pending.extend(notify(
                     conn,
                     mentioned_id,
                     event_id,
@@ -378,6 +711,7 @@ fn route_event_notifications(
                     &format!("Mentioned in: {}", task_title),
                     Some(&format!("{}: {}", author, snippet)),
                     task_id,
+                    task_priority.as_deref(),
                 ));
             }
         }
@@ -390,8 +724,8 @@ fn route_event_notifications(
     pending
 }
 
-const TASK_COLS: &str = "id, project_id, title, description, status, priority, assignee_type, assignee_id, context, output, due_date, reviewer_type, reviewer_id, status_history, created_by, created_at, updated_at, scheduled_at, recurrence_rule, recurrence_parent_id, has_open_questions, started_review_at";
-const TASK_COLS_T: &str = "t.id, t.project_id, t.title, t.description, t.status, t.priority, t.assignee_type, t.assignee_id, t.context, t.output, t.due_date, t.reviewer_type, t.reviewer_id, t.status_history, t.created_by, t.created_at, t.updated_at, t.scheduled_at, t.recurrence_rule, t.recurrence_parent_id, t.has_open_questions, t.started_review_at";
+const TASK_COLS: &str = "id, project_id, title, description, status, priority, assignee_type, assignee_id, context, output, due_date, reviewer_type, reviewer_id, status_history, created_by, created_at, updated_at, scheduled_at, recurrence_rule, recurrence_parent_id, has_open_questions, started_review_at, recurrence_paused";
+const TASK_COLS_T: &str = "t.id, t.project_id, t.title, t.description, t.status, t.priority, t.assignee_type, t.assignee_id, t.context, t.output, t.due_date, t.reviewer_type, t.reviewer_id, t.status_history, t.created_by, t.created_at, t.updated_at, t.scheduled_at, t.recurrence_rule, t.recurrence_parent_id, t.has_open_questions, t.started_review_at, t.recurrence_paused";
 
 fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<Task> {
     let context_str: Option<String> = row.get(8)?;
@@ -405,6 +739,7 @@ fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<Task> {
     let recurrence_rule_str: Option<String> = row.get(18)?;
     let recurrence_rule = recurrence_rule_str.and_then(|s| serde_json::from_str(&s).ok());
     let has_open_questions: i64 = row.get::<_, Option<i64>>(20)?.unwrap_or(0);
+    let recurrence_paused: i64 = row.get::<_, Option<i64>>(22)?.unwrap_or(0);
     Ok(Task {
         id: row.get(0)?,
         project_id: row.get(1)?,
@@ -425,6 +760,7 @@ fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<Task> {
         scheduled_at: row.get(17)?,
         recurrence_rule,
         recurrence_parent_id: row.get(19)?,
+        recurrence_paused: recurrence_paused != 0,
         dependencies: vec![],
         has_open_questions: has_open_questions != 0,
         started_review_at: row.get(21)?,
@@ -432,6 +768,7 @@ fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<Task> {
         created_at: row.get(15)?,
         updated_at: row.get(16)?,
         activities: vec![],
+        knowledge: vec![],
     })
 }
 
@@ -521,19 +858,20 @@ fn get_project_inner(
             join_mode: None,
             cta_enabled: None,
             is_public: None,
+            enforce_knowledge_templates: row.get::<_, Option<bool>>(8)?.unwrap_or(false),
             created_at: row.get(6)?,
             updated_at: row.get(7)?,
         })
     };
     if let Some(t) = tenant {
         conn.query_row(
-            "SELECT id, name, description, status, repo_url, default_branch, created_at, updated_at FROM projects WHERE id = ?1 AND (owner_id IS NULL OR owner_id = ?2)",
+            "SELECT id, name, description, status, repo_url, default_branch, created_at, updated_at, enforce_knowledge_templates FROM projects WHERE id = ?1 AND status != 'shared' AND (owner_id IS NULL OR owner_id = ?2)",
             params![id, t],
             row_mapper,
         ).ok()
     } else {
         conn.query_row(
-            "SELECT id, name, description, status, repo_url, default_branch, created_at, updated_at FROM projects WHERE id = ?1",
+            "SELECT id, name, description, status, repo_url, default_branch, created_at, updated_at, enforce_knowledge_templates FROM projects WHERE id = ?1 AND status != 'shared'",
             params![id],
             row_mapper,
         ).ok()
@@ -549,7 +887,7 @@ pub fn list_projects(
     tenant: Option<&str>,
     status_filter: Option<&str>,
 ) -> Vec<Project> {
-    let mut conditions = vec!["1=1".to_string()];
+    let mut conditions = vec!["status != 'shared'".to_string()];
     let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = vec![];
     let mut idx = 1usize;
 
@@ -564,7 +902,7 @@ pub fn list_projects(
     }
 
     let sql = format!(
-        "SELECT id, name, description, status, repo_url, default_branch, created_at, updated_at FROM projects WHERE {} ORDER BY updated_at DESC",
+        "SELECT id, name, description, status, repo_url, default_branch, created_at, updated_at, enforce_knowledge_templates FROM projects WHERE {} ORDER BY updated_at DESC",
         conditions.join(" AND ")
     );
     let mut stmt = conn.prepare(&sql).unwrap();
@@ -581,6 +919,7 @@ pub fn list_projects(
             join_mode: None,
             cta_enabled: None,
             is_public: None,
+            enforce_knowledge_templates: row.get::<_, Option<bool>>(8)?.unwrap_or(false),
             created_at: row.get(6)?,
             updated_at: row.get(7)?,
         })
@@ -605,17 +944,20 @@ pub fn update_project(
         .default_branch
         .as_ref()
         .or(existing.default_branch.as_ref());
+    let enforce_templates = input
+        .enforce_knowledge_templates
+        .unwrap_or(existing.enforce_knowledge_templates);
     let now = now();
     if let Some(t) = tenant {
         conn.execute(
-            "UPDATE projects SET name = ?1, description = ?2, status = ?3, repo_url = ?4, default_branch = ?5, updated_at = ?6 WHERE id = ?7 AND (owner_id IS NULL OR owner_id = ?8)",
-            params![name, description, status, repo_url, default_branch, now, id, t],
+            "UPDATE projects SET name = ?1, description = ?2, status = ?3, repo_url = ?4, default_branch = ?5, updated_at = ?6, enforce_knowledge_templates = ?9 WHERE id = ?7 AND (owner_id IS NULL OR owner_id = ?8)",
+            params![name, description, status, repo_url, default_branch, now, id, t, enforce_templates],
         )
         .unwrap();
     } else {
         conn.execute(
-            "UPDATE projects SET name = ?1, description = ?2, status = ?3, repo_url = ?4, default_branch = ?5, updated_at = ?6 WHERE id = ?7",
-            params![name, description, status, repo_url, default_branch, now, id],
+            "UPDATE projects SET name = ?1, description = ?2, status = ?3, repo_url = ?4, default_branch = ?5, updated_at = ?6, enforce_knowledge_templates = ?8 WHERE id = ?7",
+            params![name, description, status, repo_url, default_branch, now, id, enforce_templates],
         )
         .unwrap();
     }
@@ -729,6 +1071,7 @@ pub fn get_task(conn: &Connection, tenant: Option<&str>, id: &str) -> Option<Tas
 pub fn get_task_full(conn: &Connection, tenant: Option<&str>, id: &str) -> Option<Task> {
     let mut task = get_task(conn, tenant, id)?;
     task.activities = list_activity(conn, &task.id);
+    task.knowledge = list_task_knowledge(conn, &task.id);
 
     // Enrich context with project repo info if not already set
     if let Some(project) = get_project(conn, tenant, &task.project_id) {
@@ -814,12 +1157,18 @@ pub fn update_task(
     tenant: Option<&str>,
     id: &str,
     input: &UpdateTask,
+    settings: &RuntimeSettings,
 ) -> Result<Option<Task>, String> {
     let existing = match get_task(conn, tenant, id) {
         Some(t) => t,
         None => return Ok(None),
     };
 
+    if let Some(ref rule) = input.recurrence_rule {
+        recurrence::validate_recurrence_rule(rule)
+            .map_err(|e| format!("Invalid recurrence_rule: {}", e))?;
+    }
+
     // Validate status transition if status is being changed
     if let Some(ref new_status_str) = input.status {
         let current = TaskStatus::from_str(&existing.status)
@@ -834,7 +1183,10 @@ pub fn update_task(
         }
 
         // Dependency check when moving to in_progress
-        if target == TaskStatus::InProgress && current != TaskStatus::InProgress {
+        if settings.enforce_dependencies
+            && target == TaskStatus::InProgress
+            && current != TaskStatus::InProgress
+        {
             if let Err(pending) = check_dependencies(conn, tenant, &existing) {
                 return Err(format!(
                     "Cannot move to in_progress: dependencies not met. Pending: {}",
@@ -958,6 +1310,11 @@ pub fn delete_task(conn: &Connection, tenant: Option<&str>, id: &str) -> bool {
         .unwrap();
     conn.execute("DELETE FROM task_activity WHERE task_id = ?1", params![id])
         .unwrap();
+    conn.execute(
+        "DELETE FROM task_knowledge_links WHERE task_id = ?1",
+        params![id],
+    )
+    .unwrap();
     let rows = conn
         .execute("DELETE FROM tasks WHERE id = ?1", params![id])
         .unwrap();
@@ -1016,8 +1373,8 @@ pub fn add_dependency(
         ));
     }
     conn.execute(
-        "INSERT OR IGNORE INTO task_dependencies (task_id, depends_on) VALUES (?1, ?2)",
-        params![task_id, depends_on_id],
+        "INSERT OR IGNORE INTO task_dependencies (task_id, depends_on, created_at) VALUES (?1, ?2, ?3)",
+        params![task_id, depends_on_id, now()],
     )
     .map_err(|e| e.to_string())?;
     Ok(())
@@ -1047,14 +1404,18 @@ fn has_dependency_cycle(conn: &Connection, start: &str, target: &str) -> bool {
 /// Remove a single dependency edge.
 pub fn remove_dependency(
     conn: &Connection,
-    _tenant: Option<&str>,
+    tenant: Option<&str>,
     task_id: &str,
     depends_on_id: &str,
 ) -> bool {
+    // Both ends must be visible to the tenant
     let rows = conn
         .execute(
-            "DELETE FROM task_dependencies WHERE task_id = ?1 AND depends_on = ?2",
-            params![task_id, depends_on_id],
+            "DELETE FROM task_dependencies WHERE task_id = ?1 AND depends_on = ?2
+             AND (?3 IS NULL OR NOT EXISTS (
+                 SELECT 1 FROM tasks WHERE id IN (?1, ?2) AND owner_id IS NOT NULL AND owner_id != ?3
+             ))",
+            params![task_id, depends_on_id, tenant],
         )
         .unwrap_or(0);
     rows > 0
@@ -1179,88 +1540,85 @@ pub fn transition_ready_scheduled_tasks(conn: &Connection) -> usize {
     count
 }
 
-/// Calculate next scheduled_at from a recurrence rule and previous scheduled_at / now.
-fn next_recurrence_time(rule: &serde_json::Value, from: &str) -> Option<String> {
-    use chrono::{DateTime, Datelike, Duration, Utc};
-
-    let frequency = rule.get("frequency")?.as_str()?;
-    let interval = rule
-        .get("interval")
-        .and_then(|v| v.as_i64())
-        .unwrap_or(1)
-        .max(1);
-
-    // Parse from as UTC datetime, fall back to now
-    let base: DateTime<Utc> = DateTime::parse_from_rfc3339(from)
-        .map(|dt| dt.with_timezone(&Utc))
-        .unwrap_or_else(|_| Utc::now());
-
-    let next = match frequency {
-        "daily" => base + Duration::days(interval),
-        "weekly" => base + Duration::weeks(interval),
-        "monthly" => {
-            // Add months by manipulating year/month
-            let mut month = base.month() as i64 + interval;
-            let mut year = base.year() as i64;
-            while month > 12 {
-                month -= 12;
-                year += 1;
-            }
-            base.with_month(month as u32)
-                .and_then(|d| d.with_year(year as i32))
-                .unwrap_or(base + Duration::days(30 * interval))
-        }
-        "cron" => {
-            // Cron support: try to parse using simple next-occurrence logic
-            // For now, fall back to daily if we can't compute cron
-            base + Duration::days(1)
-        }
-        _ => return None,
-    };
-
-    // Check end conditions
-    if let Some(end_date) = rule.get("end_date").and_then(|v| v.as_str()) {
-        if !end_date.is_empty() {
-            if let Ok(end) = DateTime::parse_from_rfc3339(end_date) {
-                if next > end.with_timezone(&Utc) {
-                    return None; // Past end date
-                }
-            }
-        }
-    }
-
-    Some(next.to_rfc3339())
-}
-
-/// Create the next recurrence of a completed recurring task.
+/// Create the next recurrence of a completed recurring task, keeping the series (root
+/// included) under `max_occurrences`.
 /// Returns the new task ID if created, None if recurrence is exhausted.
-pub fn create_next_recurrence(conn: &Connection, completed_task: &Task) -> Option<String> {
+pub fn create_next_recurrence(
+    conn: &Connection,
+    completed_task: &Task,
+    max_occurrences: i64,
+) -> Option<String> {
     let rule = completed_task.recurrence_rule.as_ref()?;
 
-    // Check end_after: count how many recurrences already exist
+    // Paused series: the flag lives on the root task
+    let root_id = completed_task
+        .recurrence_parent_id
+        .as_deref()
+        .unwrap_or(&completed_task.id);
+    let paused: i64 = conn
+        .query_row(
+            "SELECT recurrence_paused FROM tasks WHERE id = ?1",
+            params![root_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+    if paused != 0 {
+        return None;
+    }
+
+    // Check end_after and the server-wide cap: count how many recurrences already exist
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM tasks WHERE recurrence_parent_id = ?1",
+            params![root_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
     if let Some(end_after) = rule.get("end_after").and_then(|v| v.as_i64()) {
-        let parent_id = completed_task
-            .recurrence_parent_id
-            .as_deref()
-            .unwrap_or(&completed_task.id);
-        let count: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM tasks WHERE recurrence_parent_id = ?1",
-                params![parent_id],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
         if count >= end_after {
             return None; // Recurrence exhausted
         }
     }
+    let max = max_occurrences;
+    if count + 1 >= max {
+        eprintln!(
+            "[recurrence] Series {} reached the cap of {} occurrences; not creating more",
+            root_id, max
+        );
+        create_activity(
+            conn,
+            &completed_task.id,
+            "system",
+            "system",
+            &CreateActivity {
+                content: format!(
+                    "Recurrence stopped: series reached the server limit of {} occurrences",
+                    max
+                ),
+                activity_type: Some("status_change".to_string()),
+                metadata: None,
+                mentions: None,
+            },
+        );
+        return None;
+    }
 
     // Calculate next scheduled_at
     let from = completed_task
         .scheduled_at
         .as_deref()
         .unwrap_or(&completed_task.created_at);
-    let next_scheduled = next_recurrence_time(rule, from)?;
+    let anchor = match completed_task.recurrence_parent_id.as_deref() {
+        Some(parent_id) => conn
+            .query_row(
+                "SELECT COALESCE(scheduled_at, created_at) FROM tasks WHERE id = ?1",
+                params![parent_id],
+                |row| row.get::<_, String>(0),
+            )
+            .unwrap_or_else(|_| from.to_string()),
+        None => from.to_string(),
+    };
+    let next_scheduled = recurrence::next_recurrence_time(rule, &anchor, from)?;
 
     let new_id = Uuid::new_v4().to_string();
     let created_at_now = now();
@@ -1276,8 +1634,8 @@ pub fn create_next_recurrence(conn: &Connection, completed_task: &Task) -> Optio
         .unwrap_or(&completed_task.id);
 
     conn.execute(
-        "INSERT INTO tasks (id, project_id, title, description, status, priority, assignee_type, assignee_id, context, created_by, created_at, updated_at, scheduled_at, recurrence_rule, recurrence_parent_id, status_history)
-         VALUES (?1, ?2, ?3, ?4, 'backlog', ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, '[]')",
+        "INSERT INTO tasks (id, project_id, title, description, status, priority, assignee_type, assignee_id, context, created_by, created_at, updated_at, scheduled_at, recurrence_rule, recurrence_parent_id, status_history, owner_id)
+         VALUES (?1, ?2, ?3, ?4, 'backlog', ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, '[]', (SELECT owner_id FROM tasks WHERE id = ?15))",
         params![
             new_id,
             completed_task.project_id,
@@ -1293,6 +1651,7 @@ pub fn create_next_recurrence(conn: &Connection, completed_task: &Task) -> Optio
             next_scheduled,
             rule_str,
             parent_id,
+            completed_task.id,
         ],
     ).unwrap();
 
@@ -1317,33 +1676,158 @@ pub fn create_next_recurrence(conn: &Connection, completed_task: &Task) -> Optio
     Some(new_id)
 }
 
+/// Pause or resume a recurring series. `task_id` may be the series root or any of its
+/// occurrences; the flag is stored on the root. On resume, if the series has no open
+/// occurrence left (it was completed while paused), the next one is created.
+pub fn set_recurrence_paused(
+    conn: &Connection,
+    tenant: Option<&str>,
+    task_id: &str,
+    paused: bool,
+    max_occurrences: i64,
+) -> Result<Task, String> {
+    let task = get_task(conn, tenant, task_id).ok_or_else(|| "Task not found".to_string())?;
+    if task.recurrence_rule.is_none() {
+        return Err("Task is not part of a recurring series".to_string());
+    }
+    let root_id = task
+        .recurrence_parent_id
+        .clone()
+        .unwrap_or_else(|| task.id.clone());
+
+    conn.execute(
+        "UPDATE tasks SET recurrence_paused = ?1, updated_at = ?2 WHERE id = ?3",
+        params![paused as i64, now(), root_id],
+    )
+    .unwrap();
+
+    if !paused {
+        let latest_id: Option<String> = conn
+            .query_row(
+                "SELECT id FROM tasks WHERE id = ?1 OR recurrence_parent_id = ?1
+                 ORDER BY COALESCE(scheduled_at, created_at) DESC LIMIT 1",
+                params![root_id],
+                |row| row.get(0),
+            )
+            .ok();
+        if let Some(latest) = latest_id.and_then(|id| get_task(conn, None, &id)) {
+            if latest.status == "done" || latest.status == "cancelled" {
+                create_next_recurrence(conn, &latest, max_occurrences);
+            }
+        }
+    }
+
+    get_task(conn, tenant, &root_id).ok_or_else(|| "Task not found".to_string())
+}
+
+/// Skip the upcoming (not yet started) occurrence of a recurring series: it is cancelled,
+/// recorded in its status history, and the occurrence after it is created in its place.
+/// `task_id` may be the series root or any of its occurrences.
+pub fn skip_next_recurrence(
+    conn: &Connection,
+    tenant: Option<&str>,
+    task_id: &str,
+    actor_type: &str,
+    actor_id: &str,
+    max_occurrences: i64,
+) -> Result<(Task, Option<Task>), String> {
+    let task = get_task(conn, tenant, task_id).ok_or_else(|| "Task not found".to_string())?;
+    if task.recurrence_rule.is_none() {
+        return Err("Task is not part of a recurring series".to_string());
+    }
+    let root_id = task
+        .recurrence_parent_id
+        .clone()
+        .unwrap_or_else(|| task.id.clone());
+
+    let upcoming_id: String = conn
+        .query_row(
+            "SELECT id FROM tasks WHERE (id = ?1 OR recurrence_parent_id = ?1)
+             AND status IN ('backlog', 'todo')
+             ORDER BY COALESCE(scheduled_at, created_at) ASC LIMIT 1",
+            params![root_id],
+            |row| row.get(0),
+        )
+        .map_err(|_| "No upcoming occurrence to skip".to_string())?;
+
+    // The cancel, the next occurrence and the activity land together or not at all
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE tasks SET status = 'cancelled', updated_at = ?1 WHERE id = ?2",
+        params![now(), upcoming_id],
+    )
+    .map_err(|e| e.to_string())?;
+    append_status_history(
+        conn,
+        &upcoming_id,
+        "cancelled",
+        Some(actor_type),
+        Some(actor_id),
+    );
+
+    let skipped = get_task(conn, None, &upcoming_id).ok_or_else(|| "Task not found".to_string())?;
+    let next = create_next_recurrence(conn, &skipped, max_occurrences)
+        .and_then(|id| get_task(conn, None, &id));
+
+    create_activity(
+        conn,
+        &skipped.id,
+        actor_type,
+        actor_id,
+        &CreateActivity {
+            content: match next.as_ref().and_then(|t| t.scheduled_at.as_deref()) {
+                Some(at) => format!("Occurrence skipped; next scheduled at {}", at),
+                None => "Occurrence skipped; no further occurrences".to_string(),
+            },
+            activity_type: Some("status_change".to_string()),
+            metadata: Some(serde_json::json!({
+                "recurrence_skipped": true,
+                "next_task_id": next.as_ref().map(|t| t.id.clone()),
+            })),
+            mentions: None,
+        },
+    );
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok((skipped, next))
+}
+
 /// Get scheduled tasks for a project within a date range.
 pub fn get_schedule(
     conn: &Connection,
-    _tenant: Option<&str>,
+    tenant: Option<&str>,
     project_id: &str,
     from: Option<&str>,
     to: Option<&str>,
 ) -> Vec<ScheduledTaskEntry> {
     let mut conditions = vec![
-        "project_id = ?1".to_string(),
-        "scheduled_at IS NOT NULL".to_string(),
+        "t.project_id = ?1".to_string(),
+        "t.scheduled_at IS NOT NULL".to_string(),
     ];
     let mut params_vec: Vec<String> = vec![project_id.to_string()];
     let mut idx = 2;
 
+    if let Some(t) = tenant {
+        conditions.push(format!("(t.owner_id IS NULL OR t.owner_id = ?{})", idx));
+        params_vec.push(t.to_string());
+        idx += 1;
+    }
+
     if let Some(f) = from {
-        conditions.push(format!("scheduled_at >= ?{}", idx));
+        conditions.push(format!("t.scheduled_at >= ?{}", idx));
         params_vec.push(f.to_string());
         idx += 1;
     }
     if let Some(t) = to {
-        conditions.push(format!("scheduled_at <= ?{}", idx));
+        conditions.push(format!("t.scheduled_at <= ?{}", idx));
         params_vec.push(t.to_string());
     }
 
     let sql = format!(
-        "SELECT id, title, status, priority, scheduled_at, assignee_id FROM tasks WHERE {} ORDER BY scheduled_at ASC",
+        "SELECT t.id, t.title, t.status, t.priority, t.scheduled_at, t.assignee_id,
+                COALESCE(p.recurrence_paused, t.recurrence_paused, 0)
+         FROM tasks t LEFT JOIN tasks p ON p.id = t.recurrence_parent_id
+         WHERE {} ORDER BY t.scheduled_at ASC",
         conditions.join(" AND ")
     );
 
@@ -1360,6 +1844,7 @@ pub fn get_schedule(
             priority: row.get(3)?,
             scheduled_at: row.get(4)?,
             assignee_id: row.get(5)?,
+            recurrence_paused: row.get::<_, i64>(6)? != 0,
         })
     })
     .unwrap()
@@ -1373,6 +1858,7 @@ pub fn claim_task(
     task_id: &str,
     agent_id: &str,
     agent_name: &str,
+    settings: &RuntimeSettings,
 ) -> Result<Task, String> {
     let task = get_task(conn, tenant, task_id).ok_or_else(|| "Task not found".to_string())?;
 
@@ -1415,13 +1901,16 @@ pub fn claim_task(
             current_tasks, agent.max_concurrent_tasks
         ));
     }
+    check_agent_quota(conn, &agent, task_id)?;
 
     // Check dependencies before allowing claim
-    if let Err(pending) = check_dependencies(conn, tenant, &task) {
-        return Err(format!(
-            "Cannot claim: dependencies not met. Pending tasks: {}",
-            pending.join(", ")
-        ));
+    if settings.enforce_dependencies {
+        if let Err(pending) = check_dependencies(conn, tenant, &task) {
+            return Err(format!(
+                "Cannot claim: dependencies not met. Pending tasks: {}",
+                pending.join(", ")
+            ));
+        }
     }
 
     let new_status = match status {
@@ -1436,6 +1925,8 @@ pub fn claim_task(
     )
     .unwrap();
 
+    record_task_intake(conn, agent_id, task_id);
+
     if new_status != task.status {
         append_status_history(conn, task_id, new_status, Some("agent"), Some(agent_id));
     }
@@ -1646,6 +2137,7 @@ pub fn batch_update_status(
     conn: &Connection,
     tenant: Option<&str>,
     updates: &[(String, String)],
+    settings: &RuntimeSettings,
 ) -> BatchResult {
     let mut succeeded = vec![];
     let mut failed = vec![];
@@ -1671,6 +2163,7 @@ pub fn batch_update_status(
                 scheduled_at: None,
                 recurrence_rule: None,
             },
+            settings,
         ) {
             Ok(Some(_)) => succeeded.push(task_id.clone()),
             Ok(None) => failed.push(BatchError {
@@ -1827,7 +2320,7 @@ pub fn list_activity(conn: &Connection, task_id: &str) -> Vec<TaskActivity> {
 
 // --- Agents ---
 
-const AGENT_COLS: &str = "id, name, api_key_hash, skills, description, status, max_concurrent_tasks, webhook_url, config, last_seen_at, created_at, model, provider, cost_tier, capabilities, seniority, role, webhook_events, stale_timeout, owner_id, tags";
+const AGENT_COLS: &str = "id, name, api_key_hash, skills, description, status, max_concurrent_tasks, webhook_url, config, last_seen_at, created_at, model, provider, cost_tier, capabilities, seniority, role, webhook_events, stale_timeout, owner_id, tags, daily_task_quota, weekly_task_quota, notification_preferences, heartbeat_status, webhook_template";
 
 fn row_to_agent(conn: &Connection, row: &rusqlite::Row) -> rusqlite::Result<Agent> {
     let id: String = row.get(0)?;
@@ -1888,11 +2381,15 @@ fn row_to_agent(conn: &Connection, row: &rusqlite::Row) -> rusqlite::Result<Agen
         skills,
         description: row.get(4)?,
         status: computed_status,
+        presence: presence::presence(last_seen.as_deref(), stale_timeout).to_string(),
         max_concurrent_tasks: max_concurrent,
         current_task_count,
         review_task_count,
         webhook_url: row.get(7)?,
         webhook_events,
+        webhook_template: row
+            .get::<_, Option<String>>(25)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
         config,
         model: row.get(11)?,
         provider: row.get(12)?,
@@ -1909,6 +2406,15 @@ fn row_to_agent(conn: &Connection, row: &rusqlite::Row) -> rusqlite::Result<Agen
         created_at: row.get(10)?,
         owner_id: row.get(19)?,
         tags,
+        daily_task_quota: row.get(21)?,
+        weekly_task_quota: row.get(22)?,
+        notification_preferences: row
+            .get::<_, Option<String>>(23)?
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        heartbeat_status: row
+            .get::<_, Option<String>>(24)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
     })
 }
 
@@ -1919,14 +2425,7 @@ fn compute_agent_status(
     stale_timeout: i64,
 ) -> String {
     // Offline if no heartbeat within stale_timeout minutes
-    if let Some(ref ts) = last_seen {
-        if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(ts) {
-            let cutoff = Utc::now() - chrono::Duration::minutes(stale_timeout);
-            if dt < cutoff {
-                return "offline".to_string();
-            }
-        }
-    } else {
+    if presence::presence(last_seen.as_deref(), stale_timeout) == presence::OFFLINE {
         return "offline".to_string();
     }
     // Busy if at or above max concurrent
@@ -2030,6 +2529,12 @@ pub fn update_agent(conn: &Connection, id: &str, input: &UpdateAgent) -> Option<
             .as_ref()
             .map(|e| serde_json::to_string(e).unwrap())
     };
+    // An empty template object clears it
+    let template_json = match input.webhook_template {
+        Some(ref t) if t.as_object().is_some_and(|o| o.is_empty()) => None,
+        Some(ref t) => Some(t.to_string()),
+        None => existing.webhook_template.as_ref().map(|t| t.to_string()),
+    };
     let config_str = input
         .config
         .as_ref()
@@ -2056,10 +2561,26 @@ pub fn update_agent(conn: &Connection, id: &str, input: &UpdateAgent) -> Option<
         .as_ref()
         .map(|t| serde_json::to_string(t).unwrap())
         .unwrap_or_else(|| serde_json::to_string(&existing.tags).unwrap());
+    // A quota of 0 (or less) clears it
+    let daily_quota = match input.daily_task_quota {
+        Some(q) => (q > 0).then_some(q),
+        None => existing.daily_task_quota,
+    };
+    let weekly_quota = match input.weekly_task_quota {
+        Some(q) => (q > 0).then_some(q),
+        None => existing.weekly_task_quota,
+    };
+    let preferences_json = serde_json::to_string(
+        input
+            .notification_preferences
+            .as_ref()
+            .unwrap_or(&existing.notification_preferences),
+    )
+    .unwrap();
 
     conn.execute(
-        "UPDATE agents SET description=?1, skills=?2, max_concurrent_tasks=?3, webhook_url=?4, config=?5, model=?6, provider=?7, cost_tier=?8, capabilities=?9, seniority=?10, role=?11, webhook_events=?12, stale_timeout=?13, tags=?15 WHERE id=?14",
-        params![description, skills_json, max_concurrent, webhook_url, config_str, model, provider, cost_tier, capabilities_json, seniority, role, webhook_events_json, stale_timeout, id, tags_json],
+        "UPDATE agents SET description=?1, skills=?2, max_concurrent_tasks=?3, webhook_url=?4, config=?5, model=?6, provider=?7, cost_tier=?8, capabilities=?9, seniority=?10, role=?11, webhook_events=?12, stale_timeout=?13, tags=?15, daily_task_quota=?16, weekly_task_quota=?17, notification_preferences=?18, webhook_template=?19 WHERE id=?14",
+        params![description, skills_json, max_concurrent, webhook_url, config_str, model, provider, cost_tier, capabilities_json, seniority, role, webhook_events_json, stale_timeout, id, tags_json, daily_quota, weekly_quota, preferences_json, template_json],
     ).unwrap();
 
     get_agent(conn, id)
@@ -2081,12 +2602,484 @@ pub fn delete_agent(conn: &Connection, id: &str) -> bool {
         append_status_history(conn, task_id, "todo", Some("system"), Some("agent_deleted"));
     }
 
+    conn.execute(
+        "DELETE FROM agent_api_keys WHERE agent_id = ?1",
+        params![id],
+    )
+    .unwrap();
     let rows = conn
         .execute("DELETE FROM agents WHERE id = ?1", params![id])
         .unwrap();
     rows > 0
 }
 
+// --- Skill Inference ---
+
+/// Derive skills/capabilities from tags on tasks the agent completed, skipping ones its
+/// profile already lists. Ordered by completed-task count, then name.
+pub fn suggest_skills(
+    conn: &Connection,
+    agent_id: &str,
+    min_tasks: i64,
+) -> Option<SuggestedSkills> {
+    let agent = get_agent(conn, agent_id)?;
+    let completed_tasks: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM tasks WHERE assignee_type = 'agent' AND assignee_id = ?1 AND status = 'done'",
+            params![agent_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT tt.tag, COUNT(*) AS n FROM task_tags tt
+             JOIN tasks t ON t.id = tt.task_id
+             WHERE t.assignee_type = 'agent' AND t.assignee_id = ?1 AND t.status = 'done'
+             GROUP BY tt.tag HAVING n >= ?2
+             ORDER BY n DESC, tt.tag ASC",
+        )
+        .unwrap();
+    let tag_counts: Vec<(String, i64)> = stmt
+        .query_map(params![agent_id, min_tasks.max(1)], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let known = |list: &[String], tag: &str| list.iter().any(|k| k.eq_ignore_ascii_case(tag));
+    let mut skills = Vec::new();
+    let mut capabilities = Vec::new();
+    for (tag, count) in tag_counts {
+        let suggestion = SkillSuggestion {
+            name: tag.clone(),
+            completed_tasks: count,
+        };
+        if tag.contains(':') {
+            if !known(&agent.capabilities, &tag) {
+                capabilities.push(suggestion);
+            }
+        } else if !known(&agent.skills, &tag) {
+            skills.push(suggestion);
+        }
+    }
+
+    Some(SuggestedSkills {
+        agent_id: agent.id,
+        completed_tasks,
+        skills,
+        capabilities,
+    })
+}
+
+/// Append every current suggestion to the agent's profile.
+pub fn apply_suggested_skills(conn: &Connection, agent_id: &str, min_tasks: i64) -> Option<Agent> {
+    let suggestions = suggest_skills(conn, agent_id, min_tasks)?;
+    let agent = get_agent(conn, agent_id)?;
+    if suggestions.skills.is_empty() && suggestions.capabilities.is_empty() {
+        return Some(agent);
+    }
+
+    let mut skills = agent.skills;
+    skills.extend(suggestions.skills.into_iter().map(|s| s.name));
+    let mut capabilities = agent.capabilities;
+    capabilities.extend(suggestions.capabilities.into_iter().map(|s| s.name));
+    conn.execute(
+        "UPDATE agents SET skills = ?1, capabilities = ?2 WHERE id = ?3",
+        params![
+            serde_json::to_string(&skills).unwrap(),
+            serde_json::to_string(&capabilities).unwrap(),
+            agent_id
+        ],
+    )
+    .unwrap();
+    get_agent(conn, agent_id)
+}
+
+// --- Agent Quotas ---
+
+/// Start of the current UTC day and ISO week (Monday), as RFC 3339 strings.
+fn quota_window_starts() -> (String, String) {
+    use chrono::Datelike;
+    let today = Utc::now().date_naive();
+    let week_start = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+    let to_ts = |d: chrono::NaiveDate| d.and_hms_opt(0, 0, 0).unwrap().and_utc().to_rfc3339();
+    (to_ts(today), to_ts(week_start))
+}
+
+/// Tasks taken on by the agent (today, this week).
+pub fn agent_quota_usage(conn: &Connection, agent_id: &str) -> (i64, i64) {
+    let (day_start, week_start) = quota_window_starts();
+    let count_since = |since: &str| -> i64 {
+        conn.query_row(
+            "SELECT COUNT(*) FROM agent_task_intake WHERE agent_id = ?1 AND taken_at >= ?2",
+            params![agent_id, since],
+            |row| row.get(0),
+        )
+        .unwrap_or(0)
+    };
+    (count_since(&day_start), count_since(&week_start))
+}
+
+fn quota_exhausted(agent: &Agent, tasks_today: i64, tasks_this_week: i64) -> bool {
+    agent.daily_task_quota.is_some_and(|q| tasks_today >= q)
+        || agent
+            .weekly_task_quota
+            .is_some_and(|q| tasks_this_week >= q)
+}
+
+/// Reject taking on `task_id` if it would exceed the agent's daily or weekly quota.
+/// A task the agent already took on (e.g. assigned, then claimed) is not counted twice.
+fn check_agent_quota(conn: &Connection, agent: &Agent, task_id: &str) -> Result<(), String> {
+    if agent.daily_task_quota.is_none() && agent.weekly_task_quota.is_none() {
+        return Ok(());
+    }
+    let already_taken: bool = conn
+        .query_row(
+            "SELECT COUNT(*) FROM agent_task_intake WHERE agent_id = ?1 AND task_id = ?2",
+            params![agent.id, task_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|c| c > 0)
+        .unwrap_or(false);
+    if already_taken {
+        return Ok(());
+    }
+
+    let (today, week) = agent_quota_usage(conn, &agent.id);
+    if let Some(q) = agent.daily_task_quota.filter(|q| today >= *q) {
+        return Err(format!(
+            "Agent daily quota reached ({}/{} tasks today). Cannot take more work.",
+            today, q
+        ));
+    }
+    if let Some(q) = agent.weekly_task_quota.filter(|q| week >= *q) {
+        return Err(format!(
+            "Agent weekly quota reached ({}/{} tasks this week). Cannot take more work.",
+            week, q
+        ));
+    }
+    Ok(())
+}
+
+fn record_task_intake(conn: &Connection, agent_id: &str, task_id: &str) {
+    let _ = conn.execute(
+        "INSERT OR IGNORE INTO agent_task_intake (agent_id, task_id, taken_at) VALUES (?1, ?2, ?3)",
+        params![agent_id, task_id, now()],
+    );
+}
+
+/// Hand off everything an agent holds, then delete it.
+///
+/// Assigned tasks and reviews go to an agent picked by `strategy` (one pick per item,
+/// so round-robin and least-loaded spread the work), or are released when no strategy
+/// is given or nobody matches. In-progress work is reset to `todo` for its new owner;
+/// review and handoff keep their status. Open questions targeting the agent are
+/// rerouted the same way, falling back to capability auto-targeting.
+pub fn offboard_agent(
+    conn: &Connection,
+    tenant: Option<&str>,
+    agent_id: &str,
+    strategy: Option<&AssignStrategy>,
+) -> Result<OffboardResult, String> {
+    let agent = get_agent(conn, agent_id).ok_or("Agent not found")?;
+    if let Some(target) = strategy.and_then(|s| s.agent_id.as_deref()) {
+        if target == agent_id {
+            return Err("Cannot reassign work to the agent being offboarded".to_string());
+        }
+        if !agent_in_tenant(conn, tenant, target) {
+            return Err("Reassignment target agent not found".to_string());
+        }
+    }
+    let pick = |exclude: &[&str]| {
+        strategy.and_then(|s| find_best_agent_excluding(conn, tenant, s, exclude))
+    };
+    let agent_label = |id: &str| get_agent_name(conn, id).unwrap_or_else(|| id.to_string());
+    // The handoff and the delete land together or not at all
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+    let mut result = OffboardResult {
+        agent_id: agent.id.clone(),
+        reassigned_tasks: Vec::new(),
+        released_tasks: Vec::new(),
+        reassigned_reviews: Vec::new(),
+        cleared_reviews: Vec::new(),
+        rerouted_questions: Vec::new(),
+        unrouted_questions: Vec::new(),
+    };
+    let now = now();
+
+    // 1. Assigned work
+    let assigned: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT id, status FROM tasks WHERE assignee_id = ?1 AND assignee_type = 'agent' AND status NOT IN ('done', 'cancelled') ORDER BY created_at",
+        )
+        .unwrap()
+        .query_map(params![agent_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for (task_id, status) in assigned {
+        match pick(&[agent_id]) {
+            Some(new_id) => {
+                let new_status = if status == "in_progress" {
+                    "todo"
+                } else {
+                    status.as_str()
+                };
+                conn.execute(
+                    "UPDATE tasks SET assignee_id = ?1, status = ?2, updated_at = ?3 WHERE id = ?4",
+                    params![new_id, new_status, now, task_id],
+                )
+                .unwrap();
+                if new_status != status {
+                    append_status_history(
+                        conn,
+                        &task_id,
+                        new_status,
+                        Some("system"),
+                        Some("agent_offboarded"),
+                    );
+                }
+                record_task_intake(conn, &new_id, &task_id);
+                create_activity(
+                    conn,
+                    &task_id,
+                    "system",
+                    "system",
+                    &CreateActivity {
+                        content: format!(
+                            "Reassigned from offboarded agent '{}' to '{}'",
+                            agent.name,
+                            agent_label(&new_id)
+                        ),
+                        activity_type: Some("assignment".to_string()),
+                        metadata: Some(serde_json::json!({
+                            "offboarded_agent_id": agent.id,
+                            "reassigned_to": new_id,
+                        })),
+                        mentions: None,
+                    },
+                );
+                result.reassigned_tasks.push(OffboardReassignment {
+                    id: task_id,
+                    agent_id: new_id,
+                });
+            }
+            None => {
+                let new_status = match status.as_str() {
+                    "review" | "handoff" => status.as_str(),
+                    _ => "todo",
+                };
+                conn.execute(
+                    "UPDATE tasks SET assignee_type = NULL, assignee_id = NULL, status = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![new_status, now, task_id],
+                )
+                .unwrap();
+                if new_status != status {
+                    append_status_history(
+                        conn,
+                        &task_id,
+                        new_status,
+                        Some("system"),
+                        Some("agent_offboarded"),
+                    );
+                }
+                create_activity(
+                    conn,
+                    &task_id,
+                    "system",
+                    "system",
+                    &CreateActivity {
+                        content: format!("Task released: agent '{}' was offboarded", agent.name),
+                        activity_type: Some("assignment".to_string()),
+                        metadata: Some(serde_json::json!({ "offboarded_agent_id": agent.id })),
+                        mentions: None,
+                    },
+                );
+                result.released_tasks.push(task_id);
+            }
+        }
+    }
+
+    // 2. Reviews — a replacement reviewer must not be the task's own assignee
+    let reviewing: Vec<(String, Option<String>)> = conn
+        .prepare(
+            "SELECT id, assignee_id FROM tasks WHERE reviewer_id = ?1 AND reviewer_type = 'agent' AND status NOT IN ('done', 'cancelled') ORDER BY created_at",
+        )
+        .unwrap()
+        .query_map(params![agent_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for (task_id, assignee) in reviewing {
+        let mut exclude = vec![agent_id];
+        if let Some(ref a) = assignee {
+            exclude.push(a.as_str());
+        }
+        match pick(&exclude) {
+            Some(new_id) => {
+                conn.execute(
+                    "UPDATE tasks SET reviewer_id = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![new_id, now, task_id],
+                )
+                .unwrap();
+                result.reassigned_reviews.push(OffboardReassignment {
+                    id: task_id,
+                    agent_id: new_id,
+                });
+            }
+            None => {
+                conn.execute(
+                    "UPDATE tasks SET reviewer_type = NULL, reviewer_id = NULL, updated_at = ?1 WHERE id = ?2",
+                    params![now, task_id],
+                )
+                .unwrap();
+                result.cleared_reviews.push(task_id);
+            }
+        }
+    }
+
+    // 3. Questions — unrouted ones with a required capability are auto-targeted once the
+    // agent is gone, so it cannot match itself
+    let mut retarget_by_capability: Vec<(String, String)> = Vec::new();
+    for q in list_questions_for_agent(conn, agent_id, Some("open")) {
+        match pick(&[agent_id]) {
+            Some(new_id) => {
+                assign_question(conn, &q.id, "agent", &new_id);
+                result.rerouted_questions.push(OffboardReassignment {
+                    id: q.id,
+                    agent_id: new_id,
+                });
+            }
+            None => {
+                record_question_route(conn, &q.id, None, None, "target_offboarded");
+                match q.required_capability {
+                    Some(cap) => retarget_by_capability.push((q.id, cap)),
+                    None => result.unrouted_questions.push(q.id),
+                }
+            }
+        }
+    }
+
+    // 4. Nothing is left pointing at the agent — remove it
+    delete_agent(conn, agent_id);
+
+    for (question_id, cap) in retarget_by_capability {
+        let targets = auto_target_question(conn, &question_id, &cap);
+        match targets.as_slice() {
+            [only] => result.rerouted_questions.push(OffboardReassignment {
+                id: question_id,
+                agent_id: only.target_id.clone(),
+            }),
+            _ => result.unrouted_questions.push(question_id),
+        }
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Recompute every agent's presence and persist it, returning the agents whose state
+/// changed since the previous sweep. The first sweep after an agent appears records its
+/// state silently. Agents listing `agent.went_offline` / `agent.back_online` in their
+/// `webhook_events` get a notification when a colleague (same owner) crosses the
+/// offline boundary.
+pub fn sweep_agent_presence(conn: &Connection) -> Vec<PresenceChange> {
+    // (id, name, owner_id, last_seen_at, stale_timeout, presence_state)
+    type PresenceRow = (
+        String,
+        String,
+        Option<String>,
+        Option<String>,
+        i64,
+        Option<String>,
+    );
+    let rows: Vec<PresenceRow> = conn
+        .prepare(
+            "SELECT id, name, owner_id, last_seen_at, stale_timeout, presence_state FROM agents",
+        )
+        .unwrap()
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get::<_, Option<i64>>(4)?.unwrap_or(30),
+                row.get(5)?,
+            ))
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut changes = Vec::new();
+    for (id, name, owner_id, last_seen, stale_timeout, previous) in rows {
+        let current = presence::presence(last_seen.as_deref(), stale_timeout);
+        if previous.as_deref() == Some(current) {
+            continue;
+        }
+        conn.execute(
+            "UPDATE agents SET presence_state = ?1 WHERE id = ?2",
+            params![current, id],
+        )
+        .unwrap();
+        if let Some(from) = previous {
+            changes.push(PresenceChange {
+                agent_id: id,
+                agent_name: name,
+                owner_id,
+                from,
+                to: current.to_string(),
+            });
+        }
+    }
+
+    let notable: Vec<(&PresenceChange, &str)> = changes
+        .iter()
+        .filter_map(|c| presence::transition_event(&c.from, &c.to).map(|e| (c, e)))
+        .collect();
+    if !notable.is_empty() {
+        let agents = list_agents(conn, None);
+        for (change, event_type) in notable {
+            let title = match event_type {
+                "agent.went_offline" => format!("Agent offline: {}", change.agent_name),
+                _ => format!("Agent back online: {}", change.agent_name),
+            };
+            for subscriber in agents.iter().filter(|a| {
+                a.id != change.agent_id
+                    && a.owner_id == change.owner_id
+                    && a.webhook_events
+                        .as_ref()
+                        .is_some_and(|evs| evs.iter().any(|e| e == event_type))
+            }) {
+                let pref = notification_preference(conn, &subscriber.id, event_type);
+                if !pref.in_app {
+                    continue;
+                }
+                conn.execute(
+                    "INSERT INTO notifications (agent_id, event_type, title, body, read, wake, email) VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6)",
+                    params![
+                        subscriber.id,
+                        event_type,
+                        title,
+                        format!("Presence changed from {} to {}.", change.from, change.to),
+                        pref.wake,
+                        pref.email
+                    ],
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    changes
+}
+
 pub fn update_heartbeat(conn: &Connection, agent_id: &str) -> bool {
     let rows = conn
         .execute(
@@ -2097,206 +3090,1543 @@ pub fn update_heartbeat(conn: &Connection, agent_id: &str) -> bool {
     rows > 0
 }
 
-pub fn list_notifications(
-    conn: &Connection,
-    agent_id: &str,
-    unread: Option<bool>,
-) -> Vec<Notification> {
-    let mut sql = "SELECT id, agent_id, event_id, event_type, title, body, read, created_at, webhook_status, task_id FROM notifications WHERE agent_id = ?1".to_string();
-    if let Some(true) = unread {
-        sql.push_str(" AND read = 0");
+/// Heartbeat carrying a status payload: refreshes `last_seen_at` and replaces the stored
+/// `heartbeat_status`.
+pub fn update_heartbeat_status(
+    conn: &Connection,
+    agent_id: &str,
+    input: &HeartbeatRequest,
+) -> bool {
+    let now = now();
+    let status = HeartbeatStatus {
+        load: input.load,
+        version: input.version.clone(),
+        status_message: input.status_message.clone(),
+        reported_at: now.clone(),
+    };
+    conn.execute(
+        "UPDATE agents SET last_seen_at = ?1, heartbeat_status = ?2 WHERE id = ?3",
+        params![now, serde_json::to_string(&status).unwrap(), agent_id],
+    )
+    .unwrap()
+        > 0
+}
+
+pub fn list_notifications(
+    conn: &Connection,
+    agent_id: &str,
+    unread: Option<bool>,
+) -> Vec<Notification> {
+    let mut sql = format!("{} WHERE agent_id = ?1", NOTIFICATION_SELECT);
+    if let Some(true) = unread {
+        sql.push_str(" AND read = 0");
+    }
+    sql.push_str(" ORDER BY created_at DESC");
+
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map(params![agent_id], map_notification_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+/// The agent's notifications with an id above `after_id`, oldest first.
+pub fn list_notifications_after(
+    conn: &Connection,
+    agent_id: &str,
+    after_id: i64,
+    limit: i64,
+) -> Vec<Notification> {
+    let sql = format!(
+        "{} WHERE agent_id = ?1 AND id > ?2 ORDER BY id ASC LIMIT ?3",
+        NOTIFICATION_SELECT
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map(params![agent_id, after_id, limit], map_notification_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+pub fn get_last_notification_id(conn: &Connection, agent_id: &str) -> i64 {
+    conn.query_row(
+        "SELECT COALESCE(MAX(id), 0) FROM notifications WHERE agent_id = ?1",
+        params![agent_id],
+        |row| row.get(0),
+    )
+    .unwrap_or(0)
+}
+
+const NOTIFICATION_SELECT: &str = "SELECT id, agent_id, event_id, event_type, title, body, read, created_at, webhook_status, task_id, wake, email, archived FROM notifications";
+
+fn map_notification_row(row: &rusqlite::Row) -> rusqlite::Result<Notification> {
+    Ok(Notification {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        event_id: row.get(2)?,
+        event_type: row.get(3)?,
+        title: row.get(4)?,
+        body: row.get(5)?,
+        read: row.get::<_, i64>(6)? != 0,
+        webhook_status: row.get(8)?,
+        task_id: row.get(9)?,
+        wake: row.get::<_, Option<i64>>(10)?.unwrap_or(1) != 0,
+        email: row.get::<_, Option<i64>>(11)?.unwrap_or(0) != 0,
+        archived: row.get::<_, Option<i64>>(12)?.unwrap_or(0) != 0,
+        created_at: row.get(7)?,
+    })
+}
+
+pub fn ack_notification(conn: &Connection, agent_id: &str, notification_id: i64) -> bool {
+    conn.execute(
+        "UPDATE notifications SET read = 1 WHERE id = ?1 AND agent_id = ?2",
+        params![notification_id, agent_id],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
+pub fn ack_all_notifications(conn: &Connection, agent_id: &str) -> i64 {
+    conn.execute(
+        "UPDATE notifications SET read = 1 WHERE agent_id = ?1 AND read = 0",
+        params![agent_id],
+    )
+    .unwrap_or(0) as i64
+}
+
+/// Mark a notification as read from internal system (e.g. after successful webhook delivery).
+pub fn ack_notification_system(conn: &Connection, notification_id: i64) {
+    conn.execute(
+        "UPDATE notifications SET read = 1 WHERE id = ?1",
+        params![notification_id],
+    )
+    .unwrap_or(0);
+}
+
+/// Apply every agent's expiry policies to its unread notifications: archive those past
+/// `archive_after_hours`, then acknowledge those past `ack_after_hours`.
+pub fn sweep_notification_policies(conn: &Connection) -> NotificationSweep {
+    let unread: Vec<(String, String)> = conn
+        .prepare("SELECT DISTINCT agent_id, event_type FROM notifications WHERE read = 0")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut sweep = NotificationSweep::default();
+    for (agent_id, event_type) in unread {
+        let pref = notification_preference(conn, &agent_id, &event_type);
+        if let Some(hours) = pref.archive_after_hours {
+            sweep.archived += conn
+                .execute(
+                    "UPDATE notifications SET read = 1, archived = 1
+                     WHERE agent_id = ?1 AND event_type = ?2 AND read = 0 AND created_at <= datetime('now', ?3)",
+                    params![agent_id, event_type, format!("-{} hours", hours)],
+                )
+                .unwrap_or(0) as i64;
+        }
+        if let Some(hours) = pref.ack_after_hours {
+            sweep.acknowledged += conn
+                .execute(
+                    "UPDATE notifications SET read = 1
+                     WHERE agent_id = ?1 AND event_type = ?2 AND read = 0 AND created_at <= datetime('now', ?3)",
+                    params![agent_id, event_type, format!("-{} hours", hours)],
+                )
+                .unwrap_or(0) as i64;
+        }
+    }
+    sweep
+}
+
+/// Apply the retention policy: read notifications go first, so the events they pointed
+/// at can follow. Unread notifications, and the events behind them, are always kept.
+pub fn prune_history(conn: &Connection, policy: &RetentionPolicy) -> RetentionSweep {
+    let cutoff = |days: i64| format!("-{} days", days);
+    let mut sweep = RetentionSweep::default();
+    if policy.notifications_days > 0 {
+        sweep.notifications = conn
+            .execute(
+                "DELETE FROM notifications WHERE read = 1 AND datetime(created_at) < datetime('now', ?1)",
+                params![cutoff(policy.notifications_days)],
+            )
+            .unwrap_or(0) as i64;
+    }
+    if policy.events_days > 0 {
+        sweep.events = conn
+            .execute(
+                "DELETE FROM events WHERE datetime(created_at) < datetime('now', ?1)
+                 AND id NOT IN (SELECT event_id FROM notifications WHERE event_id IS NOT NULL)",
+                params![cutoff(policy.events_days)],
+            )
+            .unwrap_or(0) as i64;
+    }
+    if policy.webhook_logs_days > 0 {
+        let days = cutoff(policy.webhook_logs_days);
+        sweep.webhook_logs = conn
+            .execute(
+                "DELETE FROM webhook_log WHERE datetime(created_at) < datetime('now', ?1)",
+                params![days],
+            )
+            .unwrap_or(0) as i64
+            + conn
+                .execute(
+                    "DELETE FROM webhook_trigger_logs WHERE datetime(received_at) < datetime('now', ?1)",
+                    params![days],
+                )
+                .unwrap_or(0) as i64;
+    }
+    sweep
+}
+
+/// The runtime settings: defaults, overlaid with every row of the settings table.
+pub fn get_settings(conn: &Connection) -> RuntimeSettings {
+    let mut settings = serde_json::to_value(RuntimeSettings::default()).unwrap();
+    let rows: Vec<(String, String)> = conn
+        .prepare("SELECT key, value FROM settings")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    for (key, value) in rows {
+        if let (Some(slot), Ok(value)) = (settings.get_mut(&key), serde_json::from_str(&value)) {
+            *slot = value;
+        }
+    }
+    serde_json::from_value(settings).unwrap_or_default()
+}
+
+/// Store the settings given in `input` and return them all.
+pub fn update_settings(
+    conn: &Connection,
+    input: &UpdateSettings,
+    updated_by: &str,
+) -> Result<RuntimeSettings, String> {
+    if input.stale_release_minutes.is_some_and(|m| m < 1) {
+        return Err("stale_release_minutes must be at least 1".to_string());
+    }
+    let now = now();
+    if let serde_json::Value::Object(changes) = serde_json::to_value(input).unwrap() {
+        for (key, value) in changes {
+            conn.execute(
+                "INSERT INTO settings (key, value, updated_by, updated_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(key) DO UPDATE SET value = ?2, updated_by = ?3, updated_at = ?4",
+                params![key, value.to_string(), updated_by, now],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(get_settings(conn))
+}
+
+/// Update the webhook_status of a notification.
+pub fn update_notification_webhook_status(conn: &Connection, notification_id: i64, status: &str) {
+    conn.execute(
+        "UPDATE notifications SET webhook_status = ?1 WHERE id = ?2",
+        params![status, notification_id],
+    )
+    .unwrap_or(0);
+}
+
+// --- Event outbox ---
+
+pub fn enqueue_outbox_event(
+    conn: &Connection,
+    topic: &str,
+    event_key: Option<&str>,
+    payload: &str,
+) -> i64 {
+    conn.execute(
+        "INSERT INTO event_outbox (topic, event_key, payload, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![topic, event_key, payload, Utc::now().to_rfc3339()],
+    )
+    .unwrap_or(0);
+    conn.last_insert_rowid()
+}
+
+/// Oldest undelivered events first, so redelivery preserves emission order.
+pub fn list_outbox_events(conn: &Connection, limit: i64) -> Vec<OutboxEvent> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, topic, event_key, payload, attempts, last_error, created_at
+             FROM event_outbox ORDER BY id ASC LIMIT ?1",
+        )
+        .unwrap();
+    stmt.query_map(params![limit], |row| {
+        Ok(OutboxEvent {
+            id: row.get(0)?,
+            topic: row.get(1)?,
+            event_key: row.get(2)?,
+            payload: row.get(3)?,
+            attempts: row.get(4)?,
+            last_error: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
+/// Drop events the broker has acknowledged.
+pub fn ack_outbox_events(conn: &Connection, ids: &[i64]) {
+    for id in ids {
+        conn.execute("DELETE FROM event_outbox WHERE id = ?1", params![id])
+            .unwrap_or(0);
+    }
+}
+
+/// Record a failed delivery attempt; the events stay queued for the next flush.
+pub fn fail_outbox_events(conn: &Connection, ids: &[i64], error: &str) {
+    for id in ids {
+        conn.execute(
+            "UPDATE event_outbox SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+            params![error, id],
+        )
+        .unwrap_or(0);
+    }
+}
+
+/// Persisted events with an id above `after_id` that `tenant` may see, oldest first.
+pub fn list_events_after(
+    conn: &Connection,
+    tenant: Option<&str>,
+    after_id: i64,
+    limit: i64,
+) -> Vec<StoredEvent> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, event_type, task_id, project_id, actor_type, actor_id, payload, created_at
+             FROM events WHERE id > ?1 AND (?3 IS NULL OR owner_id IS NULL OR owner_id = ?3)
+             ORDER BY id ASC LIMIT ?2",
+        )
+        .unwrap();
+    stmt.query_map(params![after_id, limit, tenant], |row| {
+        Ok(StoredEvent {
+            id: row.get(0)?,
+            event_type: row.get(1)?,
+            task_id: row.get(2)?,
+            project_id: row.get(3)?,
+            actor_type: row.get(4)?,
+            actor_id: row.get(5)?,
+            payload: row
+                .get::<_, Option<String>>(6)?
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            created_at: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
+// --- Users ---
+
+// --- Tenancy ---
+// Projects, tasks and agents carry the tenant as `owner_id`; everything else belongs to
+// a tenant through the project, task or agent it hangs off. Unscoped callers (`None`)
+// see every tenant. Unowned projects and tasks are shared with every tenant, while an
+// agent belongs to exactly one (unowned agents only to unscoped callers).
+
+/// Whether the row `sql` finds for `id` (selecting its owner) is visible to `tenant`.
+fn owner_visible(
+    conn: &Connection,
+    tenant: Option<&str>,
+    sql: &str,
+    id: &str,
+    shared_if_unowned: bool,
+) -> bool {
+    let Some(t) = tenant else {
+        return true;
+    };
+    match conn.query_row(sql, params![id], |row| row.get::<_, Option<String>>(0)) {
+        Ok(Some(owner)) => owner == t,
+        Ok(None) => shared_if_unowned,
+        Err(_) => false,
+    }
+}
+
+pub fn project_in_tenant(conn: &Connection, tenant: Option<&str>, project_id: &str) -> bool {
+    owner_visible(
+        conn,
+        tenant,
+        "SELECT owner_id FROM projects WHERE id = ?1",
+        project_id,
+        true,
+    )
+}
+
+pub fn task_in_tenant(conn: &Connection, tenant: Option<&str>, task_id: &str) -> bool {
+    owner_visible(
+        conn,
+        tenant,
+        "SELECT owner_id FROM tasks WHERE id = ?1",
+        task_id,
+        true,
+    )
+}
+
+pub fn agent_in_tenant(conn: &Connection, tenant: Option<&str>, agent_id: &str) -> bool {
+    owner_visible(
+        conn,
+        tenant,
+        "SELECT owner_id FROM agents WHERE id = ?1",
+        agent_id,
+        false,
+    )
+}
+
+pub fn question_in_tenant(conn: &Connection, tenant: Option<&str>, question_id: &str) -> bool {
+    owner_visible(
+        conn,
+        tenant,
+        "SELECT p.owner_id FROM task_questions q JOIN projects p ON p.id = q.project_id WHERE q.id = ?1",
+        question_id,
+        true,
+    )
+}
+
+pub fn artifact_in_tenant(conn: &Connection, tenant: Option<&str>, artifact_id: &str) -> bool {
+    owner_visible(
+        conn,
+        tenant,
+        "SELECT t.owner_id FROM task_artifacts a JOIN tasks t ON t.id = a.task_id WHERE a.id = ?1",
+        artifact_id,
+        true,
+    )
+}
+
+pub fn trigger_in_tenant(conn: &Connection, tenant: Option<&str>, trigger_id: &str) -> bool {
+    owner_visible(
+        conn,
+        tenant,
+        "SELECT p.owner_id FROM webhook_triggers w JOIN projects p ON p.id = w.project_id WHERE w.id = ?1",
+        trigger_id,
+        true,
+    )
+}
+
+/// The agent, if `tenant` may see it.
+pub fn get_tenant_agent(conn: &Connection, tenant: Option<&str>, id: &str) -> Option<Agent> {
+    get_agent(conn, id).filter(|a| tenant.is_none() || a.owner_id.as_deref() == tenant)
+}
+
+/// The tenant a project belongs to (`None` for unowned or unknown projects).
+pub fn project_owner(conn: &Connection, project_id: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT owner_id FROM projects WHERE id = ?1",
+        params![project_id],
+        |row| row.get(0),
+    )
+    .ok()
+    .flatten()
+}
+
+// --- Tenants ---
+
+fn row_to_tenant(row: &rusqlite::Row) -> rusqlite::Result<Tenant> {
+    Ok(Tenant {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        status: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+pub fn create_tenant(conn: &Connection, input: &CreateTenant) -> Result<Tenant, String> {
+    if get_tenant(conn, &input.id).is_some() {
+        return Err(format!("Tenant '{}' already exists", input.id));
+    }
+    let now = now();
+    let name = input.name.as_deref().unwrap_or(&input.id);
+    conn.execute(
+        "INSERT INTO tenants (id, name, status, created_at, updated_at) VALUES (?1, ?2, 'active', ?3, ?3)",
+        params![input.id, name, now],
+    )
+    .map_err(|e| e.to_string())?;
+    get_tenant(conn, &input.id).ok_or_else(|| "Tenant not found".to_string())
+}
+
+pub fn get_tenant(conn: &Connection, id: &str) -> Option<Tenant> {
+    conn.query_row(
+        "SELECT id, name, status, created_at, updated_at FROM tenants WHERE id = ?1",
+        params![id],
+        row_to_tenant,
+    )
+    .ok()
+}
+
+pub fn list_tenants(conn: &Connection) -> Vec<Tenant> {
+    let mut stmt = conn
+        .prepare("SELECT id, name, status, created_at, updated_at FROM tenants ORDER BY id")
+        .unwrap();
+    stmt.query_map([], row_to_tenant)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+/// Registered tenants, and the ones that only exist as the `owner_id` of agents or projects.
+pub fn has_tenants(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM tenants)
+             OR EXISTS(SELECT 1 FROM agents WHERE owner_id IS NOT NULL)
+             OR EXISTS(SELECT 1 FROM projects WHERE owner_id IS NOT NULL)",
+        [],
+        |r| r.get(0),
+    )
+    .unwrap_or(true)
+}
+
+pub fn set_tenant_status(conn: &Connection, id: &str, status: &str) -> Option<Tenant> {
+    conn.execute(
+        "UPDATE tenants SET status = ?1, updated_at = ?2 WHERE id = ?3",
+        params![status, now(), id],
+    )
+    .unwrap();
+    get_tenant(conn, id)
+}
+
+/// Delete a tenant with everything it owns: projects and all that hangs off them,
+/// tasks, agents and their keys, events and notifications.
+pub fn delete_tenant(conn: &Connection, id: &str) -> bool {
+    if get_tenant(conn, id).is_none() {
+        return false;
+    }
+    const PROJECTS: &str = "(SELECT id FROM projects WHERE owner_id = ?1)";
+    const TASKS: &str =
+        "(SELECT id FROM tasks WHERE owner_id = ?1 OR project_id IN (SELECT id FROM projects WHERE owner_id = ?1))";
+    const AGENTS: &str = "(SELECT id FROM agents WHERE owner_id = ?1)";
+    let statements = [
+        format!("DELETE FROM question_attachments WHERE question_id IN (SELECT id FROM task_questions WHERE project_id IN {PROJECTS})"),
+        format!("DELETE FROM question_replies WHERE question_id IN (SELECT id FROM task_questions WHERE project_id IN {PROJECTS})"),
+        format!("DELETE FROM task_questions WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM knowledge_attachments WHERE knowledge_id IN (SELECT id FROM project_knowledge WHERE project_id IN {PROJECTS})"),
+        format!("DELETE FROM task_knowledge_links WHERE task_id IN {TASKS} OR knowledge_id IN (SELECT id FROM project_knowledge WHERE project_id IN {PROJECTS})"),
+        format!("DELETE FROM knowledge_versions WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM knowledge_links WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM knowledge_reads WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM project_knowledge WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM webhook_trigger_logs WHERE trigger_id IN (SELECT id FROM webhook_triggers WHERE project_id IN {PROJECTS})"),
+        format!("DELETE FROM trigger_deliveries WHERE trigger_id IN (SELECT id FROM webhook_triggers WHERE project_id IN {PROJECTS})"),
+        format!("DELETE FROM webhook_triggers WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM slack_threads WHERE task_id IN {TASKS}"),
+        format!("DELETE FROM slack_integrations WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM github_integrations WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM gitlab_integrations WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM linear_integrations WHERE project_id IN {PROJECTS}"),
+        format!("DELETE FROM task_tags WHERE task_id IN {TASKS}"),
+        format!("DELETE FROM task_activity WHERE task_id IN {TASKS}"),
+        format!("DELETE FROM task_artifacts WHERE task_id IN {TASKS}"),
+        format!("DELETE FROM task_usage WHERE task_id IN {TASKS}"),
+        format!("DELETE FROM agent_task_intake WHERE task_id IN {TASKS} OR agent_id IN {AGENTS}"),
+        format!("DELETE FROM task_dependencies WHERE task_id IN {TASKS} OR depends_on IN {TASKS}"),
+        format!("DELETE FROM notifications WHERE agent_id IN {AGENTS} OR task_id IN {TASKS}"),
+        format!("DELETE FROM webhook_log WHERE agent_id IN {AGENTS}"),
+        format!("DELETE FROM push_targets WHERE subject_type = 'agent' AND subject_id IN {AGENTS}"),
+        format!("DELETE FROM agent_api_keys WHERE agent_id IN {AGENTS}"),
+        format!("DELETE FROM events WHERE owner_id = ?1 OR project_id IN {PROJECTS}"),
+        format!("DELETE FROM tasks WHERE id IN {TASKS}"),
+        "DELETE FROM agents WHERE owner_id = ?1".to_string(),
+        "DELETE FROM projects WHERE owner_id = ?1".to_string(),
+        "DELETE FROM tenant_setup_tokens WHERE tenant_id = ?1".to_string(),
+        "DELETE FROM tenants WHERE id = ?1".to_string(),
+    ];
+    for sql in &statements {
+        conn.execute(sql, params![id]).unwrap();
+    }
+    true
+}
+
+/// Mint a setup token that registers agents into `tenant_id`; only its hash is kept.
+pub fn create_tenant_setup_token(conn: &Connection, tenant_id: &str) -> TenantSetupToken {
+    let token = format!("ts_{}", Uuid::new_v4().to_string().replace('-', ""));
+    conn.execute(
+        "INSERT INTO tenant_setup_tokens (token_hash, tenant_id, created_at) VALUES (?1, ?2, ?3)",
+        params![hash_api_key(&token), tenant_id, now()],
+    )
+    .unwrap();
+    TenantSetupToken {
+        tenant_id: tenant_id.to_string(),
+        setup_token: token,
+    }
+}
+
+pub fn get_tenant_by_setup_token(conn: &Connection, token: &str) -> Option<Tenant> {
+    conn.query_row(
+        "SELECT t.id, t.name, t.status, t.created_at, t.updated_at FROM tenants t \
+         JOIN tenant_setup_tokens s ON s.tenant_id = t.id WHERE s.token_hash = ?1",
+        params![hash_api_key(token)],
+        row_to_tenant,
+    )
+    .ok()
+}
+
+/// Counts `[tenant_limits]` applies to; limits and webhook deliveries are left for the
+/// caller to fill in.
+pub fn get_tenant_usage(conn: &Connection, tenant: &str) -> TenantUsage {
+    let count = |sql: &str| -> i64 {
+        conn.query_row(sql, params![tenant], |row| row.get(0))
+            .unwrap_or(0)
+    };
+    let used = |used| QuotaUsage { used, limit: None };
+    TenantUsage {
+        tenant_id: tenant.to_string(),
+        projects: used(count(
+            "SELECT COUNT(*) FROM projects WHERE owner_id = ?1 AND status != 'archived'",
+        )),
+        open_tasks: used(count(
+            "SELECT COUNT(*) FROM tasks WHERE owner_id = ?1 AND status NOT IN ('done', 'cancelled')",
+        )),
+        agents: used(count("SELECT COUNT(*) FROM agents WHERE owner_id = ?1")),
+        events_today: used(count(
+            "SELECT COUNT(*) FROM events WHERE owner_id = ?1 AND created_at >= date('now')",
+        )),
+        webhook_deliveries_today: QuotaUsage::default(),
+    }
+}
+
+// --- Stats ---
+
+/// Dashboard counts. Task filters narrow the task counts and activity; `from`/`to`
+/// match task creation dates and activity dates. Active agents are never filtered.
+pub fn get_stats(conn: &Connection, tenant: Option<&str>, filter: &StatsQuery) -> DashboardStats {
+    // ?1 tenant, ?2 project, ?3 tag, ?4 from, ?5 to
+    const TASK_FILTER: &str = "(?1 IS NULL OR t.owner_id = ?1) AND (?2 IS NULL OR t.project_id = ?2)
+         AND (?3 IS NULL OR EXISTS (SELECT 1 FROM task_tags tt WHERE tt.task_id = t.id AND tt.tag = ?3))";
+    const DAYS: &str =
+        "(?4 IS NULL OR substr({col}, 1, 10) >= ?4) AND (?5 IS NULL OR substr({col}, 1, 10) <= ?5)";
+    let filter_params = params![
+        tenant,
+        filter.project_id,
+        filter.tag,
+        filter.from,
+        filter.to
+    ];
+
+    let mut tasks_by_status = HashMap::new();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT t.status, COUNT(*) FROM tasks t WHERE {TASK_FILTER} AND {} GROUP BY t.status",
+            DAYS.replace("{col}", "t.created_at")
+        ))
+        .unwrap();
+    let rows = stmt
+        .query_map(filter_params, |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .unwrap();
+    for row in rows.flatten() {
+        tasks_by_status.insert(row.0, row.1);
+    }
+    let total_tasks: i64 = tasks_by_status.values().sum();
+
+    let cutoff = (Utc::now() - chrono::Duration::minutes(30)).to_rfc3339();
+    let active_agents: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM agents WHERE last_seen_at > ?1 AND (?2 IS NULL OR owner_id = ?2)",
+            params![cutoff, tenant],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    let total_projects: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM projects WHERE status = 'active'
+             AND (?1 IS NULL OR owner_id = ?1) AND (?2 IS NULL OR id = ?2)",
+            params![tenant, filter.project_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT a.id, a.task_id, a.author_type, a.author_id, a.content, a.activity_type, a.metadata, a.created_at
+             FROM task_activity a
+             INNER JOIN tasks t ON t.id = a.task_id
+             WHERE {TASK_FILTER} AND {}
+             ORDER BY a.created_at DESC LIMIT 20",
+            DAYS.replace("{col}", "a.created_at")
+        ))
+        .unwrap();
+    let recent_activity: Vec<TaskActivity> = stmt
+        .query_map(filter_params, |row| {
+            let metadata_str: Option<String> = row.get(6)?;
+            let metadata = metadata_str.and_then(|s| serde_json::from_str(&s).ok());
+            Ok(TaskActivity {
+                id: row.get(0)?,
+                task_id: row.get(1)?,
+                author_type: row.get(2)?,
+                author_id: row.get(3)?,
+                author_name: None,
+                content: row.get(4)?,
+                activity_type: row.get(5)?,
+                metadata,
+                created_at: row.get(7)?,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    DashboardStats {
+        tasks_by_status,
+        total_tasks,
+        active_agents,
+        total_projects,
+        recent_activity,
+    }
+}
+
+fn utc_day(timestamp: &str) -> Option<chrono::NaiveDate> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&Utc).date_naive())
+}
+
+/// Tasks created, or moved to done/blocked per their status history (which outlives
+/// event retention), counted per day or per week starting Monday.
+pub fn get_timeseries(
+    conn: &Connection,
+    tenant: Option<&str>,
+    project_id: Option<&str>,
+    metric: &str,
+    interval: &str,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> Timeseries {
+    let in_range = |day: &chrono::NaiveDate| (from..=to).contains(day);
+    let days: Vec<chrono::NaiveDate> = if metric == "tasks_created" {
+        let mut stmt = conn
+            .prepare(
+                "SELECT created_at FROM tasks
+                 WHERE (?1 IS NULL OR owner_id = ?1) AND (?2 IS NULL OR project_id = ?2)
+                 AND substr(created_at, 1, 10) BETWEEN ?3 AND ?4",
+            )
+            .unwrap();
+        stmt.query_map(
+            params![tenant, project_id, from.to_string(), to.to_string()],
+            |row| row.get::<_, String>(0),
+        )
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .filter_map(|created| utc_day(&created))
+        .filter(in_range)
+        .collect()
+    } else {
+        let status = if metric == "tasks_completed" {
+            "done"
+        } else {
+            "blocked"
+        };
+        let mut stmt = conn
+            .prepare(
+                "SELECT status_history FROM tasks
+                 WHERE (?1 IS NULL OR owner_id = ?1) AND (?2 IS NULL OR project_id = ?2)",
+            )
+            .unwrap();
+        stmt.query_map(params![tenant, project_id], |row| {
+            row.get::<_, Option<String>>(0)
+        })
+        .unwrap()
+        .filter_map(|r| r.ok().flatten())
+        .flat_map(|history| {
+            serde_json::from_str::<Vec<StatusHistoryEntry>>(&history).unwrap_or_default()
+        })
+        .filter(|entry| entry.status == status)
+        .filter_map(|entry| utc_day(&entry.timestamp))
+        .filter(in_range)
+        .collect()
+    };
+
+    let step = if interval == "week" { 7 } else { 1 };
+    let bucket = |day: chrono::NaiveDate| {
+        if step == 7 {
+            day - chrono::Days::new(day.weekday().num_days_from_monday() as u64)
+        } else {
+            day
+        }
+    };
+    let mut counts: BTreeMap<chrono::NaiveDate, i64> = BTreeMap::new();
+    let mut start = bucket(from);
+    while start <= to {
+        counts.insert(start, 0);
+        start = start + chrono::Days::new(step);
+    }
+    for day in days {
+        *counts.entry(bucket(day)).or_default() += 1;
+    }
+    Timeseries {
+        metric: metric.to_string(),
+        interval: interval.to_string(),
+        from: from.to_string(),
+        to: to.to_string(),
+        points: counts
+            .into_iter()
+            .map(|(date, count)| TimeseriesPoint {
+                date: date.to_string(),
+                count,
+            })
+            .collect(),
+    }
+}
+
+/// Load per agent: assigned tasks by stage, reviews waiting on it, and assigned tasks
+/// whose latest move to done falls in the last `days` days.
+pub fn get_agent_workloads(
+    conn: &Connection,
+    tenant: Option<&str>,
+    days: i64,
+) -> AgentWorkloadStats {
+    let now = Utc::now();
+    let cutoff = now - chrono::Duration::days(days);
+    let mut workloads: Vec<AgentWorkload> = list_agents(conn, tenant)
+        .into_iter()
+        .map(|agent| AgentWorkload {
+            seconds_since_heartbeat: agent.last_seen_at.as_deref().and_then(|seen| {
+                chrono::DateTime::parse_from_rfc3339(seen)
+                    .ok()
+                    .map(|seen| (now - seen.with_timezone(&Utc)).num_seconds().max(0))
+            }),
+            agent_id: agent.id,
+            name: agent.name,
+            role: agent.role,
+            presence: agent.presence,
+            open: 0,
+            in_progress: 0,
+            in_review: 0,
+            reviewing: 0,
+            completed: 0,
+            throughput_per_day: 0.0,
+            last_seen_at: agent.last_seen_at,
+        })
+        .collect();
+    let index: HashMap<String, usize> = workloads
+        .iter()
+        .enumerate()
+        .map(|(i, w)| (w.agent_id.clone(), i))
+        .collect();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT assignee_id, status, COUNT(*) FROM tasks
+             WHERE assignee_type = 'agent' AND status NOT IN ('done', 'cancelled')
+             GROUP BY assignee_id, status",
+        )
+        .unwrap();
+    let assigned = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })
+        .unwrap()
+        .filter_map(|r| r.ok());
+    for (agent_id, status, count) in assigned {
+        let Some(&i) = index.get(&agent_id) else {
+            continue;
+        };
+        match status.as_str() {
+            "in_progress" => workloads[i].in_progress += count,
+            "review" => workloads[i].in_review += count,
+            _ => workloads[i].open += count,
+        }
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT reviewer_id, COUNT(*) FROM tasks
+             WHERE status = 'review' AND reviewer_type = 'agent' GROUP BY reviewer_id",
+        )
+        .unwrap();
+    let reviewing = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .unwrap()
+        .filter_map(|r| r.ok());
+    for (agent_id, count) in reviewing {
+        if let Some(&i) = index.get(&agent_id) {
+            workloads[i].reviewing = count;
+        }
+    }
+
+    // A status change bumps updated_at, so older rows can't have finished in the window
+    let mut stmt = conn
+        .prepare(
+            "SELECT assignee_id, status_history FROM tasks
+             WHERE assignee_type = 'agent' AND status = 'done' AND updated_at >= ?1",
+        )
+        .unwrap();
+    let done = stmt
+        .query_map(params![cutoff.to_rfc3339()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+        .unwrap()
+        .filter_map(|r| r.ok());
+    for (agent_id, history) in done {
+        let Some(&i) = index.get(&agent_id) else {
+            continue;
+        };
+        let history: Vec<StatusHistoryEntry> = history
+            .and_then(|h| serde_json::from_str(&h).ok())
+            .unwrap_or_default();
+        let finished = history
+            .iter()
+            .rev()
+            .find(|entry| entry.status == "done")
+            .and_then(|entry| chrono::DateTime::parse_from_rfc3339(&entry.timestamp).ok());
+        if finished.is_some_and(|t| t.with_timezone(&Utc) >= cutoff) {
+            workloads[i].completed += 1;
+        }
+    }
+    for workload in &mut workloads {
+        workload.throughput_per_day = workload.completed as f64 / days as f64;
+    }
+    AgentWorkloadStats {
+        days,
+        agents: workloads,
+    }
+}
+
+/// A task as burndown replays it: when it appeared, its weight, and its status changes.
+struct BurndownTask {
+    created: chrono::DateTime<Utc>,
+    weight: f64,
+    history: Vec<(chrono::DateTime<Utc>, String)>,
+}
+
+impl BurndownTask {
+    /// None before the task existed
+    fn status_at(&self, at: chrono::DateTime<Utc>) -> Option<&str> {
+        if self.created > at {
+            return None;
+        }
+        self.history
+            .iter()
+            .rev()
+            .find(|(changed, _)| *changed <= at)
+            .map(|(_, status)| status.as_str())
+            .or(Some("backlog"))
+    }
+
+    fn open_at(&self, at: chrono::DateTime<Utc>) -> bool {
+        self.status_at(at)
+            .is_some_and(|s| s != "done" && s != "cancelled")
+    }
+}
+
+pub fn get_burndown(
+    conn: &Connection,
+    project_id: &str,
+    by_estimate: bool,
+    iteration_days: i64,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> Burndown {
+    let parse = |ts: &str| {
+        chrono::DateTime::parse_from_rfc3339(ts)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    };
+    let mut stmt = conn
+        .prepare("SELECT created_at, context, status_history FROM tasks WHERE project_id = ?1")
+        .unwrap();
+    let tasks: Vec<BurndownTask> = stmt
+        .query_map(params![project_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .filter_map(|(created, context, history)| {
+            let estimate = context
+                .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+                .and_then(|c| c.get("estimate").and_then(|e| e.as_f64()));
+            let mut history: Vec<(chrono::DateTime<Utc>, String)> = history
+                .and_then(|h| serde_json::from_str::<Vec<StatusHistoryEntry>>(&h).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|entry| Some((parse(&entry.timestamp)?, entry.status)))
+                .collect();
+            history.sort_by_key(|(changed, _)| *changed);
+            Some(BurndownTask {
+                created: parse(&created)?,
+                weight: if by_estimate {
+                    estimate.unwrap_or(1.0)
+                } else {
+                    1.0
+                },
+                history,
+            })
+        })
+        .collect();
+
+    let end_of = |day: chrono::NaiveDate| {
+        (day + chrono::Days::new(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+            - chrono::Duration::nanoseconds(1)
+    };
+    let open_weight = |at: chrono::DateTime<Utc>| -> f64 {
+        tasks
+            .iter()
+            .filter(|t| t.open_at(at))
+            .map(|t| t.weight)
+            .sum()
+    };
+    let points = from
+        .iter_days()
+        .take_while(|day| *day <= to)
+        .map(|day| BurndownPoint {
+            date: day.to_string(),
+            remaining: open_weight(end_of(day)),
+        })
+        .collect();
+
+    let mut iterations = Vec::new();
+    let mut full = Vec::new();
+    let mut start = from;
+    while start <= to {
+        let end = (start + chrono::Days::new(iteration_days as u64 - 1)).min(to);
+        let opened =
+            start.and_hms_opt(0, 0, 0).unwrap().and_utc() - chrono::Duration::nanoseconds(1);
+        let closed = end_of(end);
+        let completed = tasks
+            .iter()
+            .filter(|t| t.status_at(closed) == Some("done") && t.status_at(opened) != Some("done"))
+            .map(|t| t.weight)
+            .sum();
+        if (end - start).num_days() + 1 == iteration_days {
+            full.push(completed);
+        }
+        iterations.push(IterationVelocity {
+            start: start.to_string(),
+            end: end.to_string(),
+            completed,
+        });
+        start = end + chrono::Days::new(1);
     }
-    sql.push_str(" ORDER BY created_at DESC");
+    let counted: Vec<f64> = if full.is_empty() {
+        iterations.iter().map(|it| it.completed).collect()
+    } else {
+        full
+    };
+    let average_velocity = if counted.is_empty() {
+        0.0
+    } else {
+        counted.iter().sum::<f64>() / counted.len() as f64
+    };
 
-    let mut stmt = conn.prepare(&sql).unwrap();
-    stmt.query_map(params![agent_id], |row| {
-        Ok(Notification {
-            id: row.get(0)?,
-            agent_id: row.get(1)?,
-            event_id: row.get(2)?,
-            event_type: row.get(3)?,
-            title: row.get(4)?,
-            body: row.get(5)?,
-            read: row.get::<_, i64>(6)? != 0,
-            webhook_status: row.get(8)?,
-            task_id: row.get(9)?,
-            created_at: row.get(7)?,
+    Burndown {
+        project_id: project_id.to_string(),
+        weight: if by_estimate { "estimate" } else { "count" }.to_string(),
+        from: from.to_string(),
+        to: to.to_string(),
+        points,
+        iterations,
+        average_velocity,
+    }
+}
+
+/// One stint of a task in `review`.
+struct ReviewCycle {
+    project_id: String,
+    reviewer_id: Option<String>,
+    submitted: chrono::DateTime<Utc>,
+    /// The reviewer starting the review, or else its verdict
+    first_response: Option<chrono::DateTime<Utc>>,
+    ended: Option<chrono::DateTime<Utc>>,
+}
+
+/// Review stints of every task matching the filters that may have been in review since
+/// `since`. `started_review_at` only survives for a task's latest stint; earlier ones
+/// count their verdict as the first response.
+fn review_cycles(
+    conn: &Connection,
+    tenant: Option<&str>,
+    project_id: Option<&str>,
+    since: chrono::DateTime<Utc>,
+) -> Vec<ReviewCycle> {
+    let parse = |ts: &str| {
+        chrono::DateTime::parse_from_rfc3339(ts)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    };
+    let mut stmt = conn
+        .prepare(
+            "SELECT project_id, reviewer_id, status_history, started_review_at FROM tasks
+             WHERE (?1 IS NULL OR owner_id = ?1) AND (?2 IS NULL OR project_id = ?2)
+             AND (updated_at >= ?3 OR status = 'review')",
+        )
+        .unwrap();
+    let rows = stmt
+        .query_map(params![tenant, project_id, since.to_rfc3339()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
         })
-    })
-    .unwrap()
-    .filter_map(|r| r.ok())
-    .collect()
+        .unwrap()
+        .filter_map(|r| r.ok());
+
+    let mut cycles = Vec::new();
+    for (project_id, reviewer_id, history, started_review_at) in rows {
+        let mut history: Vec<(chrono::DateTime<Utc>, String)> = history
+            .and_then(|h| serde_json::from_str::<Vec<StatusHistoryEntry>>(&h).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|entry| Some((parse(&entry.timestamp)?, entry.status)))
+            .collect();
+        history.sort_by_key(|(changed, _)| *changed);
+        let started = started_review_at.as_deref().and_then(parse);
+        let mut submitted: Option<chrono::DateTime<Utc>> = None;
+        let mut close = |submitted: chrono::DateTime<Utc>, ended: Option<chrono::DateTime<Utc>>| {
+            let started = started.filter(|s| *s >= submitted && ended.is_none_or(|e| *s <= e));
+            cycles.push(ReviewCycle {
+                project_id: project_id.clone(),
+                reviewer_id: reviewer_id.clone(),
+                submitted,
+                first_response: started.or(ended),
+                ended,
+            });
+        };
+        for (changed, status) in history {
+            match (submitted, status == "review") {
+                (None, true) => submitted = Some(changed),
+                (Some(since), false) => {
+                    close(since, Some(changed));
+                    submitted = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(since) = submitted {
+            close(since, None);
+        }
+    }
+    cycles
 }
 
-pub fn ack_notification(conn: &Connection, agent_id: &str, notification_id: i64) -> bool {
-    conn.execute(
-        "UPDATE notifications SET read = 1 WHERE id = ?1 AND agent_id = ?2",
-        params![notification_id, agent_id],
-    )
-    .unwrap_or(0)
-        > 0
+/// Roll `cycles` up by `key`, slowest first.
+fn review_latency_by(
+    cycles: &[ReviewCycle],
+    key: impl Fn(&ReviewCycle) -> Option<&str>,
+    name: impl Fn(&str) -> Option<String>,
+) -> Vec<ReviewLatency> {
+    let now = Utc::now();
+    let mut groups: BTreeMap<&str, Vec<&ReviewCycle>> = BTreeMap::new();
+    for cycle in cycles {
+        if let Some(k) = key(cycle) {
+            groups.entry(k).or_default().push(cycle);
+        }
+    }
+    let average =
+        |secs: Vec<i64>| (!secs.is_empty()).then(|| secs.iter().sum::<i64>() / secs.len() as i64);
+    let mut latencies: Vec<ReviewLatency> = groups
+        .into_iter()
+        .map(|(id, cycles)| ReviewLatency {
+            id: id.to_string(),
+            name: name(id),
+            completed: cycles.iter().filter(|c| c.ended.is_some()).count() as i64,
+            pending: cycles.iter().filter(|c| c.ended.is_none()).count() as i64,
+            avg_time_to_first_review_secs: average(
+                cycles
+                    .iter()
+                    .filter_map(|c| Some((c.first_response? - c.submitted).num_seconds()))
+                    .collect(),
+            ),
+            avg_time_in_review_secs: average(
+                cycles
+                    .iter()
+                    .filter_map(|c| Some((c.ended? - c.submitted).num_seconds()))
+                    .collect(),
+            ),
+            oldest_pending_secs: cycles
+                .iter()
+                .filter(|c| c.ended.is_none())
+                .map(|c| (now - c.submitted).num_seconds())
+                .max(),
+        })
+        .collect();
+    latencies.sort_by(|a, b| {
+        (b.avg_time_to_first_review_secs, b.oldest_pending_secs)
+            .cmp(&(a.avg_time_to_first_review_secs, a.oldest_pending_secs))
+    });
+    latencies
 }
 
-pub fn ack_all_notifications(conn: &Connection, agent_id: &str) -> i64 {
-    conn.execute(
-        "UPDATE notifications SET read = 1 WHERE agent_id = ?1 AND read = 0",
-        params![agent_id],
-    )
-    .unwrap_or(0) as i64
+fn agent_name(conn: &Connection, id: &str) -> Option<String> {
+    conn.query_row("SELECT name FROM agents WHERE id = ?1", params![id], |r| {
+        r.get(0)
+    })
+    .ok()
 }
 
-/// Mark a notification as read from internal system (e.g. after successful webhook delivery).
-pub fn ack_notification_system(conn: &Connection, notification_id: i64) {
-    conn.execute(
-        "UPDATE notifications SET read = 1 WHERE id = ?1",
-        params![notification_id],
-    )
-    .unwrap_or(0);
+pub fn get_review_latency(
+    conn: &Connection,
+    tenant: Option<&str>,
+    project_id: Option<&str>,
+    days: i64,
+) -> ReviewLatencyReport {
+    let since = Utc::now() - chrono::Duration::days(days);
+    let cycles: Vec<ReviewCycle> = review_cycles(conn, tenant, project_id, since)
+        .into_iter()
+        .filter(|c| c.submitted >= since || c.ended.is_none())
+        .collect();
+    ReviewLatencyReport {
+        days,
+        reviewers: review_latency_by(
+            &cycles,
+            |c| c.reviewer_id.as_deref(),
+            |id| agent_name(conn, id),
+        ),
+        projects: review_latency_by(
+            &cycles,
+            |c| Some(c.project_id.as_str()),
+            |id| {
+                conn.query_row(
+                    "SELECT name FROM projects WHERE id = ?1",
+                    params![id],
+                    |r| r.get(0),
+                )
+                .ok()
+            },
+        ),
+    }
 }
 
-/// Update the webhook_status of a notification.
-pub fn update_notification_webhook_status(conn: &Connection, notification_id: i64, status: &str) {
-    conn.execute(
-        "UPDATE notifications SET webhook_status = ?1 WHERE id = ?2",
-        params![status, notification_id],
-    )
-    .unwrap_or(0);
+/// Reported usage per period and per tier, model, project or agent. Usage recorded
+/// before rows carried a tier and model falls back to the agent's current ones.
+pub fn get_cost_report(
+    conn: &Connection,
+    tenant: Option<&str>,
+    project_id: Option<&str>,
+    group_by: &str,
+    interval: &str,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> CostReport {
+    let group = match group_by {
+        "model" => "COALESCE(u.model, a.model)",
+        "project" => "t.project_id",
+        "agent" => "u.agent_id",
+        _ => "COALESCE(u.cost_tier, a.cost_tier)",
+    };
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT u.reported_at, {group}, u.task_id, u.input_tokens, u.output_tokens,
+                    COALESCE(u.cost_usd, 0)
+             FROM task_usage u
+             JOIN tasks t ON t.id = u.task_id
+             LEFT JOIN agents a ON a.id = u.agent_id
+             WHERE (?1 IS NULL OR t.owner_id = ?1) AND (?2 IS NULL OR t.project_id = ?2)
+             AND substr(u.reported_at, 1, 10) BETWEEN ?3 AND ?4"
+        ))
+        .unwrap();
+    let period = |day: chrono::NaiveDate| {
+        if interval == "week" {
+            day - chrono::Days::new(day.weekday().num_days_from_monday() as u64)
+        } else {
+            day
+        }
+    };
+    let mut totals: BTreeMap<(chrono::NaiveDate, Option<String>), CostBucket> = BTreeMap::new();
+    let mut tasks: std::collections::HashSet<(chrono::NaiveDate, Option<String>, String)> =
+        std::collections::HashSet::new();
+    let rows = stmt
+        .query_map(
+            params![tenant, project_id, from.to_string(), to.to_string()],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, f64>(5)?,
+                ))
+            },
+        )
+        .unwrap()
+        .filter_map(|r| r.ok());
+    for (reported_at, group, task_id, input_tokens, output_tokens, cost_usd) in rows {
+        let Some(day) = utc_day(&reported_at).filter(|d| (from..=to).contains(d)) else {
+            continue;
+        };
+        let start = period(day);
+        let bucket = totals
+            .entry((start, group.clone()))
+            .or_insert_with(|| CostBucket {
+                period: start.to_string(),
+                group: group.clone(),
+                tasks: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                cost_usd: 0.0,
+            });
+        if tasks.insert((start, group, task_id)) {
+            bucket.tasks += 1;
+        }
+        bucket.input_tokens += input_tokens;
+        bucket.output_tokens += output_tokens;
+        bucket.cost_usd += cost_usd;
+    }
+    let mut buckets: Vec<CostBucket> = totals.into_values().collect();
+    buckets.sort_by(|a, b| {
+        a.period
+            .cmp(&b.period)
+            .then(b.cost_usd.total_cmp(&a.cost_usd))
+    });
+    CostReport {
+        group_by: group_by.to_string(),
+        interval: interval.to_string(),
+        from: from.to_string(),
+        to: to.to_string(),
+        total_cost_usd: buckets.iter().map(|b| b.cost_usd).sum(),
+        buckets,
+    }
 }
 
-// --- Users ---
+type Span = (chrono::DateTime<Utc>, chrono::DateTime<Utc>);
 
-// --- Stats ---
+/// A task as blocked-time accounting replays it: its status spans, the last one
+/// running until now.
+struct BlockedTask {
+    title: String,
+    project_id: String,
+    status: String,
+    created: chrono::DateTime<Utc>,
+    spans: Vec<(chrono::DateTime<Utc>, chrono::DateTime<Utc>, String)>,
+}
 
-pub fn get_stats(conn: &Connection, tenant: Option<&str>) -> DashboardStats {
-    let mut tasks_by_status = HashMap::new();
+impl BlockedTask {
+    fn spans_where(&self, keep: impl Fn(&str) -> bool) -> Vec<Span> {
+        self.spans
+            .iter()
+            .filter(|(_, _, status)| keep(status))
+            .map(|(from, to, _)| (*from, *to))
+            .collect()
+    }
+}
 
-    if let Some(t) = tenant {
-        let mut stmt = conn
-            .prepare("SELECT status, COUNT(*) FROM tasks WHERE owner_id = ?1 GROUP BY status")
-            .unwrap();
-        let rows = stmt
-            .query_map(params![t], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-            })
-            .unwrap();
-        for row in rows.flatten() {
-            tasks_by_status.insert(row.0, row.1);
+/// Overlap of two sorted lists of disjoint spans.
+fn intersect_spans(a: &[Span], b: &[Span]) -> Vec<Span> {
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let from = a[i].0.max(b[j].0);
+        let to = a[i].1.min(b[j].1);
+        if from < to {
+            out.push((from, to));
         }
-    } else {
-        let mut stmt = conn
-            .prepare("SELECT status, COUNT(*) FROM tasks GROUP BY status")
-            .unwrap();
-        let rows = stmt
-            .query_map([], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-            })
-            .unwrap();
-        for row in rows.flatten() {
-            tasks_by_status.insert(row.0, row.1);
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
         }
     }
+    out
+}
 
-    let total_tasks: i64 = if let Some(t) = tenant {
-        conn.query_row(
-            "SELECT COUNT(*) FROM tasks WHERE owner_id = ?1",
-            params![t],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-    } else {
-        conn.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0))
-            .unwrap_or(0)
-    };
+/// Union of spans, sorted and disjoint.
+fn merge_spans(mut spans: Vec<Span>) -> Vec<Span> {
+    spans.sort();
+    let mut out: Vec<Span> = Vec::new();
+    for (from, to) in spans {
+        match out.last_mut() {
+            Some(last) if from <= last.1 => last.1 = last.1.max(to),
+            _ => out.push((from, to)),
+        }
+    }
+    out
+}
 
-    let cutoff = (Utc::now() - chrono::Duration::minutes(30)).to_rfc3339();
-    let active_agents: i64 = if let Some(t) = tenant {
-        conn.query_row(
-            "SELECT COUNT(*) FROM agents WHERE last_seen_at > ?1 AND owner_id = ?2",
-            params![cutoff, t],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-    } else {
-        conn.query_row(
-            "SELECT COUNT(*) FROM agents WHERE last_seen_at > ?1",
-            params![cutoff],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-    };
+fn span_secs(spans: &[Span]) -> i64 {
+    spans
+        .iter()
+        .map(|(from, to)| (*to - *from).num_seconds())
+        .sum()
+}
 
-    let total_projects: i64 = if let Some(t) = tenant {
-        conn.query_row(
-            "SELECT COUNT(*) FROM projects WHERE status = 'active' AND owner_id = ?1",
-            params![t],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-    } else {
-        conn.query_row(
-            "SELECT COUNT(*) FROM projects WHERE status = 'active'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
+/// Time in `blocked` per task from its status history, and time each open task waited
+/// on a dependency that wasn't done, counted from when the dependency was added.
+/// Dependencies from before that was recorded count from when both tasks existed.
+pub fn get_blocked_time(
+    conn: &Connection,
+    tenant: Option<&str>,
+    project_id: Option<&str>,
+    limit: usize,
+) -> BlockedTimeReport {
+    let now = Utc::now();
+    let parse = |ts: &str| {
+        chrono::DateTime::parse_from_rfc3339(ts)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
     };
-
-    let recent_activity: Vec<TaskActivity> = if let Some(t) = tenant {
-        let mut stmt = conn
-            .prepare(
-                "SELECT a.id, a.task_id, a.author_type, a.author_id, a.content, a.activity_type, a.metadata, a.created_at                  FROM task_activity a                  INNER JOIN tasks tk ON tk.id = a.task_id                  WHERE tk.owner_id = ?1                  ORDER BY a.created_at DESC LIMIT 20",
-            )
-            .unwrap();
-        stmt.query_map(params![t], |row| {
-            let metadata_str: Option<String> = row.get(6)?;
-            let metadata = metadata_str.and_then(|s| serde_json::from_str(&s).ok());
-            Ok(TaskActivity {
-                id: row.get(0)?,
-                task_id: row.get(1)?,
-                author_type: row.get(2)?,
-                author_id: row.get(3)?,
-                author_name: None,
-                content: row.get(4)?,
-                activity_type: row.get(5)?,
-                metadata,
-                created_at: row.get(7)?,
-            })
+    // The whole tenant, since dependencies may cross projects
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, project_id, status, created_at, status_history FROM tasks
+             WHERE (?1 IS NULL OR owner_id = ?1)",
+        )
+        .unwrap();
+    let tasks: HashMap<String, BlockedTask> = stmt
+        .query_map(params![tenant], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
         })
         .unwrap()
         .filter_map(|r| r.ok())
-        .collect()
-    } else {
-        let mut stmt = conn
-            .prepare(
-                "SELECT id, task_id, author_type, author_id, content, activity_type, metadata, created_at                  FROM task_activity ORDER BY created_at DESC LIMIT 20",
-            )
-            .unwrap();
-        stmt.query_map([], |row| {
-            let metadata_str: Option<String> = row.get(6)?;
-            let metadata = metadata_str.and_then(|s| serde_json::from_str(&s).ok());
-            Ok(TaskActivity {
-                id: row.get(0)?,
-                task_id: row.get(1)?,
-                author_type: row.get(2)?,
-                author_id: row.get(3)?,
-                author_name: None,
-                content: row.get(4)?,
-                activity_type: row.get(5)?,
-                metadata,
-                created_at: row.get(7)?,
-            })
+        .filter_map(|(id, title, project_id, status, created, history)| {
+            let created = parse(&created)?;
+            let mut history: Vec<(chrono::DateTime<Utc>, String)> = history
+                .and_then(|h| serde_json::from_str::<Vec<StatusHistoryEntry>>(&h).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|entry| Some((parse(&entry.timestamp)?, entry.status)))
+                .collect();
+            history.sort_by_key(|(changed, _)| *changed);
+            if history.is_empty() {
+                history.push((created, status.clone()));
+            }
+            let spans = history
+                .iter()
+                .enumerate()
+                .map(|(i, (from, status))| {
+                    let to = history.get(i + 1).map_or(now, |(next, _)| *next);
+                    (*from, to.max(*from), status.clone())
+                })
+                .collect();
+            Some((
+                id,
+                BlockedTask {
+                    title,
+                    project_id,
+                    status,
+                    created,
+                    spans,
+                },
+            ))
         })
+        .collect();
+
+    let mut stmt = conn
+        .prepare("SELECT task_id, depends_on, created_at FROM task_dependencies")
+        .unwrap();
+    let edges: Vec<(String, String, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
         .unwrap()
         .filter_map(|r| r.ok())
-        .collect()
-    };
+        .collect();
 
-    DashboardStats {
-        tasks_by_status,
-        total_tasks,
-        active_agents,
-        total_projects,
-        recent_activity,
+    let mut waits: HashMap<&str, Vec<Span>> = HashMap::new();
+    let mut blockers: HashMap<&str, (i64, i64)> = HashMap::new();
+    for (task_id, depends_on, added) in &edges {
+        let (Some(task), Some(dep)) = (tasks.get(task_id), tasks.get(depends_on)) else {
+            continue;
+        };
+        if project_id.is_some_and(|p| p != task.project_id) {
+            continue;
+        }
+        let since = added
+            .as_deref()
+            .and_then(parse)
+            .unwrap_or(task.created.max(dep.created));
+        let open = task.spans_where(|s| s != "done" && s != "cancelled");
+        let unfinished = dep.spans_where(|s| s != "done");
+        let wait = intersect_spans(&intersect_spans(&open, &unfinished), &[(since, now)]);
+        let secs = span_secs(&wait);
+        if secs == 0 {
+            continue;
+        }
+        let blocker = blockers.entry(depends_on.as_str()).or_default();
+        blocker.0 += 1;
+        blocker.1 += secs;
+        waits.entry(task_id.as_str()).or_default().extend(wait);
+    }
+
+    let mut accounted: Vec<TaskBlockedTime> = tasks
+        .iter()
+        .filter(|(_, t)| project_id.is_none_or(|p| p == t.project_id))
+        .map(|(id, t)| TaskBlockedTime {
+            task_id: id.clone(),
+            title: t.title.clone(),
+            project_id: t.project_id.clone(),
+            status: t.status.clone(),
+            blocked_secs: span_secs(&t.spans_where(|s| s == "blocked")),
+            blocked_by_deps_secs: waits
+                .remove(id.as_str())
+                .map(|w| span_secs(&merge_spans(w)))
+                .unwrap_or(0),
+        })
+        .filter(|t| t.blocked_secs > 0 || t.blocked_by_deps_secs > 0)
+        .collect();
+    accounted.sort_by_key(|t| std::cmp::Reverse(t.blocked_secs + t.blocked_by_deps_secs));
+
+    let mut projects: BTreeMap<&str, ProjectBlockedTime> = BTreeMap::new();
+    for t in &accounted {
+        let project = projects
+            .entry(t.project_id.as_str())
+            .or_insert_with(|| ProjectBlockedTime {
+                project_id: t.project_id.clone(),
+                name: conn
+                    .query_row(
+                        "SELECT name FROM projects WHERE id = ?1",
+                        params![t.project_id],
+                        |r| r.get(0),
+                    )
+                    .unwrap_or_default(),
+                tasks: 0,
+                blocked_secs: 0,
+                blocked_by_deps_secs: 0,
+            });
+        project.tasks += 1;
+        project.blocked_secs += t.blocked_secs;
+        project.blocked_by_deps_secs += t.blocked_by_deps_secs;
+    }
+    let mut projects: Vec<ProjectBlockedTime> = projects.into_values().collect();
+    projects.sort_by_key(|p| std::cmp::Reverse(p.blocked_secs + p.blocked_by_deps_secs));
+
+    let mut top_blockers: Vec<Blocker> = blockers
+        .into_iter()
+        .map(|(id, (dependents, dependent_wait_secs))| {
+            let dep = &tasks[id];
+            Blocker {
+                task_id: id.to_string(),
+                title: dep.title.clone(),
+                project_id: dep.project_id.clone(),
+                status: dep.status.clone(),
+                dependents,
+                dependent_wait_secs,
+            }
+        })
+        .collect();
+    top_blockers.sort_by_key(|b| std::cmp::Reverse(b.dependent_wait_secs));
+    top_blockers.truncate(limit);
+    accounted.truncate(limit);
+
+    BlockedTimeReport {
+        projects,
+        top_blockers,
+        most_blocked: accounted,
     }
 }
 
@@ -2339,10 +4669,10 @@ pub fn get_project_with_stats(
 /// SELECT column order:
 /// id(0) project_id(1) key(2) title(3) content(4) metadata(5)
 /// tags(6) category(7) created_by_type(8) created_by_id(9)
-/// updated_at(10) created_at(11)
+/// updated_at(10) created_at(11) version(12)
 const KNOWLEDGE_SELECT: &str =
     "SELECT id, project_id, key, title, content, metadata, tags, category, \
-     created_by_type, created_by_id, updated_at, created_at \
+     created_by_type, created_by_id, updated_at, created_at, version, review_after \
      FROM project_knowledge";
 
 fn map_knowledge_row(row: &rusqlite::Row) -> rusqlite::Result<KnowledgeEntry> {
@@ -2352,6 +4682,7 @@ fn map_knowledge_row(row: &rusqlite::Row) -> rusqlite::Result<KnowledgeEntry> {
     let tags: Vec<String> = tags_str
         .and_then(|s| serde_json::from_str(&s).ok())
         .unwrap_or_default();
+    let review_after: Option<String> = row.get(13)?;
     Ok(KnowledgeEntry {
         id: row.get(0)?,
         project_id: row.get(1)?,
@@ -2363,8 +4694,12 @@ fn map_knowledge_row(row: &rusqlite::Row) -> rusqlite::Result<KnowledgeEntry> {
         category: row.get(7)?,
         created_by_type: row.get(8)?,
         created_by_id: row.get(9)?,
+        version: row.get::<_, Option<i64>>(12)?.unwrap_or(1),
+        stale: freshness::is_stale(review_after.as_deref()),
+        review_after,
         updated_at: row.get(10)?,
         created_at: row.get(11)?,
+        attachments: vec![],
     })
 }
 
@@ -2389,11 +4724,24 @@ pub fn upsert_knowledge(
         .as_deref()
         .filter(|c| opengate_models::VALID_CATEGORIES.contains(c));
 
+    if is_shared_knowledge_scope(project_id) {
+        ensure_shared_knowledge_scope(conn, project_id);
+    }
+
+    // Callers normalize review_after; an empty string clears it, None keeps it
+    let set_review = input.review_after.is_some();
+    let review_after = input.review_after.as_deref().filter(|r| !r.is_empty());
+
+    // Entries written before versioning have no snapshot of their current content yet
+    snapshot_knowledge(conn, project_id, key, None);
+
     // Try update first
     let updated = conn
         .execute(
             "UPDATE project_knowledge \
-         SET title=?1, content=?2, metadata=?3, tags=?4, category=?5, updated_at=?6 \
+         SET title=?1, content=?2, metadata=?3, tags=?4, category=?5, updated_at=?6, version=version+1, \
+             review_after = CASE WHEN ?9 THEN ?10 ELSE review_after END, \
+             stale_notified_at = CASE WHEN ?9 THEN NULL ELSE stale_notified_at END \
          WHERE project_id=?7 AND key=?8",
             params![
                 input.title,
@@ -2403,7 +4751,9 @@ pub fn upsert_knowledge(
                 category,
                 now,
                 project_id,
-                key
+                key,
+                set_review,
+                review_after
             ],
         )
         .unwrap();
@@ -2413,8 +4763,8 @@ pub fn upsert_knowledge(
         conn.execute(
             "INSERT INTO project_knowledge \
              (id, project_id, key, title, content, metadata, tags, category, \
-              created_by_type, created_by_id, updated_at, created_at) \
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+              created_by_type, created_by_id, updated_at, created_at, review_after) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 id,
                 project_id,
@@ -2427,19 +4777,186 @@ pub fn upsert_knowledge(
                 author_type,
                 author_id,
                 now,
-                now
+                now,
+                review_after
             ],
         )
         .unwrap();
     }
+    snapshot_knowledge(conn, project_id, key, Some((author_type, author_id)));
+    sync_knowledge_links(conn, project_id, key, &input.content);
 
     get_knowledge(conn, project_id, key).unwrap()
 }
 
+/// The shared knowledge space is backed by a hidden `projects` row (status `shared`,
+/// never returned by project lookups) so knowledge foreign keys hold.
+fn ensure_shared_knowledge_scope(conn: &Connection, scope: &str) {
+    let now = now();
+    conn.execute(
+        "INSERT OR IGNORE INTO projects (id, name, status, owner_id, created_at, updated_at) \
+         VALUES (?1, 'Shared knowledge', 'shared', ?2, ?3, ?3)",
+        params![scope, scope.strip_prefix("shared:"), now],
+    )
+    .unwrap();
+}
+
+/// Copy the entry's current state into `knowledge_versions`. With an author, the
+/// snapshot for the current version is (re)written; without one, it is only backfilled
+/// if missing, attributed to the entry's creator.
+fn snapshot_knowledge(
+    conn: &Connection,
+    project_id: &str,
+    key: &str,
+    author: Option<(&str, &str)>,
+) {
+    let columns = "INTO knowledge_versions \
+         (project_id, key, version, title, content, metadata, tags, category, author_type, author_id, created_at) \
+         SELECT project_id, key, version, title, content, metadata, tags, category";
+    match author {
+        Some((author_type, author_id)) => conn.execute(
+            &format!(
+                "INSERT OR REPLACE {}, ?3, ?4, updated_at FROM project_knowledge WHERE project_id = ?1 AND key = ?2",
+                columns
+            ),
+            params![project_id, key, author_type, author_id],
+        ),
+        None => conn.execute(
+            &format!(
+                "INSERT OR IGNORE {}, created_by_type, created_by_id, updated_at FROM project_knowledge WHERE project_id = ?1 AND key = ?2",
+                columns
+            ),
+            params![project_id, key],
+        ),
+    }
+    .unwrap();
+}
+
+/// All versions of an entry, newest first. Empty if the entry doesn't exist.
+pub fn list_knowledge_versions(
+    conn: &Connection,
+    project_id: &str,
+    key: &str,
+) -> Vec<KnowledgeVersion> {
+    if get_knowledge(conn, project_id, key).is_none() {
+        return Vec::new();
+    }
+    snapshot_knowledge(conn, project_id, key, None);
+    let mut stmt = conn
+        .prepare(
+            "SELECT version, title, content, metadata, tags, category, author_type, author_id, created_at \
+             FROM knowledge_versions WHERE project_id = ?1 AND key = ?2 ORDER BY version DESC",
+        )
+        .unwrap();
+    stmt.query_map(params![project_id, key], |row| {
+        let metadata_str: Option<String> = row.get(3)?;
+        let tags_str: Option<String> = row.get(4)?;
+        Ok(KnowledgeVersion {
+            version: row.get(0)?,
+            title: row.get(1)?,
+            content: row.get(2)?,
+            metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
+            tags: tags_str
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            category: row.get(5)?,
+            author_type: row.get(6)?,
+            author_id: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
+/// Restore an earlier version's content. The revert is itself a new version, so
+/// history is never rewritten.
+pub fn revert_knowledge(
+    conn: &Connection,
+    project_id: &str,
+    key: &str,
+    version: i64,
+    author_type: &str,
+    author_id: &str,
+) -> Result<KnowledgeEntry, String> {
+    let target = list_knowledge_versions(conn, project_id, key);
+    if target.is_empty() {
+        return Err("Knowledge entry not found".to_string());
+    }
+    let target = target
+        .into_iter()
+        .find(|v| v.version == version)
+        .ok_or_else(|| format!("Version {} not found", version))?;
+    let input = UpsertKnowledge {
+        title: target.title,
+        content: target.content,
+        metadata: target.metadata,
+        tags: Some(target.tags),
+        category: target.category,
+        review_after: None,
+    };
+    Ok(upsert_knowledge(
+        conn,
+        project_id,
+        key,
+        &input,
+        author_type,
+        author_id,
+    ))
+}
+
+/// Entries whose review deadline has passed and haven't been announced yet. Each one is
+/// marked and gets a `knowledge.stale` event; announcing again needs a new deadline.
+pub fn sweep_stale_knowledge(conn: &Connection) -> (Vec<KnowledgeEntry>, Vec<PendingNotifWebhook>) {
+    let due: Vec<KnowledgeEntry> = conn
+        .prepare(&format!(
+            "{} WHERE review_after IS NOT NULL AND review_after <= ?1 AND stale_notified_at IS NULL \
+             ORDER BY review_after",
+            KNOWLEDGE_SELECT
+        ))
+        .unwrap()
+        .query_map(params![freshness::now_stamp()], map_knowledge_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut pending = Vec::new();
+    for entry in &due {
+        conn.execute(
+            "UPDATE project_knowledge SET stale_notified_at = ?1 WHERE id = ?2",
+            params![now(), entry.id],
+        )
+        .unwrap();
+        let payload = serde_json::json!({
+            "task_title": serde_json::Value::Null,
+            "actor_name": "system",
+            "knowledge_key": entry.key,
+            "knowledge_title": entry.title,
+            "review_after": entry.review_after,
+            "author_type": entry.created_by_type,
+            "author_id": entry.created_by_id,
+        });
+        pending.extend(emit_event(
+            conn,
+            "knowledge.stale",
+            None,
+            &entry.project_id,
+            "system",
+            "system",
+            &payload,
+        ));
+    }
+    (due, pending)
+}
+
 pub fn get_knowledge(conn: &Connection, project_id: &str, key: &str) -> Option<KnowledgeEntry> {
     let sql = format!("{} WHERE project_id = ?1 AND key = ?2", KNOWLEDGE_SELECT);
-    conn.query_row(&sql, params![project_id, key], map_knowledge_row)
-        .ok()
+    let mut entry = conn
+        .query_row(&sql, params![project_id, key], map_knowledge_row)
+        .ok()?;
+    entry.attachments = list_knowledge_attachments(conn, &entry.id);
+    Some(entry)
 }
 
 pub fn list_knowledge(
@@ -2534,6 +5051,34 @@ pub fn search_knowledge(
 }
 
 pub fn delete_knowledge(conn: &Connection, project_id: &str, key: &str) -> bool {
+    conn.execute(
+        "DELETE FROM knowledge_attachments WHERE knowledge_id IN \
+         (SELECT id FROM project_knowledge WHERE project_id = ?1 AND key = ?2)",
+        params![project_id, key],
+    )
+    .unwrap();
+    conn.execute(
+        "DELETE FROM task_knowledge_links WHERE knowledge_id IN \
+         (SELECT id FROM project_knowledge WHERE project_id = ?1 AND key = ?2)",
+        params![project_id, key],
+    )
+    .unwrap();
+    conn.execute(
+        "DELETE FROM knowledge_versions WHERE project_id = ?1 AND key = ?2",
+        params![project_id, key],
+    )
+    .unwrap();
+    conn.execute(
+        "DELETE FROM knowledge_reads WHERE project_id = ?1 AND key = ?2",
+        params![project_id, key],
+    )
+    .unwrap();
+    // Incoming references stay: they show up as unresolved until the key is rewritten
+    conn.execute(
+        "DELETE FROM knowledge_links WHERE project_id = ?1 AND from_key = ?2",
+        params![project_id, key],
+    )
+    .unwrap();
     let rows = conn
         .execute(
             "DELETE FROM project_knowledge WHERE project_id = ?1 AND key = ?2",
@@ -2543,6 +5088,296 @@ pub fn delete_knowledge(conn: &Connection, project_id: &str, key: &str) -> bool
     rows > 0
 }
 
+// --- Knowledge backlinks ---
+
+/// Replace the entry's outgoing references with those found in `content`.
+fn sync_knowledge_links(conn: &Connection, project_id: &str, key: &str, content: &str) {
+    conn.execute(
+        "DELETE FROM knowledge_links WHERE project_id = ?1 AND from_key = ?2",
+        params![project_id, key],
+    )
+    .unwrap();
+    for target in backlinks::extract_references(content) {
+        if target == key {
+            continue;
+        }
+        conn.execute(
+            "INSERT OR IGNORE INTO knowledge_links (project_id, from_key, to_key) VALUES (?1, ?2, ?3)",
+            params![project_id, key, target],
+        )
+        .unwrap();
+    }
+}
+
+fn map_knowledge_summary(row: &rusqlite::Row) -> rusqlite::Result<KnowledgeSummary> {
+    let tags_str: Option<String> = row.get(3)?;
+    Ok(KnowledgeSummary {
+        key: row.get(0)?,
+        title: row.get(1)?,
+        category: row.get(2)?,
+        tags: tags_str
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        updated_at: row.get(4)?,
+    })
+}
+
+/// Entries whose content references `key`, by key. None if the entry doesn't exist.
+pub fn list_knowledge_backlinks(
+    conn: &Connection,
+    project_id: &str,
+    key: &str,
+) -> Option<Vec<KnowledgeSummary>> {
+    get_knowledge(conn, project_id, key)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT k.key, k.title, k.category, k.tags, k.updated_at \
+             FROM knowledge_links l JOIN project_knowledge k \
+               ON k.project_id = l.project_id AND k.key = l.from_key \
+             WHERE l.project_id = ?1 AND l.to_key = ?2 ORDER BY k.key",
+        )
+        .unwrap();
+    Some(
+        stmt.query_map(params![project_id, key], map_knowledge_summary)
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect(),
+    )
+}
+
+pub fn get_knowledge_graph(conn: &Connection, project_id: &str) -> KnowledgeGraph {
+    let nodes: Vec<KnowledgeSummary> = conn
+        .prepare(
+            "SELECT key, title, category, tags, updated_at FROM project_knowledge \
+             WHERE project_id = ?1 ORDER BY key",
+        )
+        .unwrap()
+        .query_map(params![project_id], map_knowledge_summary)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let links: Vec<KnowledgeEdge> = conn
+        .prepare(
+            "SELECT from_key, to_key FROM knowledge_links WHERE project_id = ?1 \
+             ORDER BY from_key, to_key",
+        )
+        .unwrap()
+        .query_map(params![project_id], |row| {
+            Ok(KnowledgeEdge {
+                from: row.get(0)?,
+                to: row.get(1)?,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let (edges, unresolved) = links
+        .into_iter()
+        .partition(|edge| nodes.iter().any(|n| n.key == edge.to));
+    KnowledgeGraph {
+        nodes,
+        edges,
+        unresolved,
+    }
+}
+
+// --- Knowledge usage ---
+
+/// Count one fetch of an entry by a reader.
+pub fn record_knowledge_read(
+    conn: &Connection,
+    project_id: &str,
+    key: &str,
+    reader_type: &str,
+    reader_id: &str,
+) {
+    conn.execute(
+        "INSERT INTO knowledge_reads (project_id, key, reader_type, reader_id, read_count, last_read_at) \
+         VALUES (?1, ?2, ?3, ?4, 1, ?5) \
+         ON CONFLICT (project_id, key, reader_type, reader_id) \
+         DO UPDATE SET read_count = read_count + 1, last_read_at = excluded.last_read_at",
+        params![project_id, key, reader_type, reader_id, now()],
+    )
+    .unwrap();
+}
+
+pub fn get_knowledge_usage_stats(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+    reader_type: Option<&str>,
+) -> KnowledgeUsageStats {
+    let total_entries: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM project_knowledge WHERE project_id = ?1",
+            params![project_id],
+            |r| r.get(0),
+        )
+        .unwrap_or(0);
+
+    let most_read: Vec<KnowledgeUsage> = conn
+        .prepare(
+            "SELECT k.key, k.title, k.category, SUM(r.read_count) AS reads, COUNT(*), MAX(r.last_read_at) \
+             FROM knowledge_reads r JOIN project_knowledge k \
+               ON k.project_id = r.project_id AND k.key = r.key \
+             WHERE r.project_id = ?1 AND (?2 IS NULL OR r.reader_type = ?2) \
+             GROUP BY k.key ORDER BY reads DESC, k.key LIMIT ?3",
+        )
+        .unwrap()
+        .query_map(params![project_id, reader_type, limit], |row| {
+            Ok(KnowledgeUsage {
+                key: row.get(0)?,
+                title: row.get(1)?,
+                category: row.get(2)?,
+                reads: row.get(3)?,
+                readers: row.get(4)?,
+                last_read_at: row.get(5)?,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let never_read: Vec<KnowledgeSummary> = conn
+        .prepare(
+            "SELECT key, title, category, tags, updated_at FROM project_knowledge k \
+             WHERE project_id = ?1 AND NOT EXISTS ( \
+               SELECT 1 FROM knowledge_reads r WHERE r.project_id = k.project_id AND r.key = k.key \
+                 AND (?2 IS NULL OR r.reader_type = ?2)) \
+             ORDER BY updated_at",
+        )
+        .unwrap()
+        .query_map(params![project_id, reader_type], map_knowledge_summary)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    KnowledgeUsageStats {
+        total_entries,
+        most_read,
+        never_read,
+    }
+}
+
+// --- Knowledge Attachments ---
+
+fn list_knowledge_attachments(conn: &Connection, knowledge_id: &str) -> Vec<KnowledgeAttachment> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, knowledge_id, name, artifact_type, value, created_by_type, created_by_id, created_at
+             FROM knowledge_attachments WHERE knowledge_id = ?1 ORDER BY created_at ASC",
+        )
+        .unwrap();
+    stmt.query_map(params![knowledge_id], |row| {
+        Ok(KnowledgeAttachment {
+            id: row.get(0)?,
+            knowledge_id: row.get(1)?,
+            name: row.get(2)?,
+            artifact_type: row.get(3)?,
+            value: row.get(4)?,
+            created_by_type: row.get(5)?,
+            created_by_id: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
+/// Attach an artifact to the entry `key`. `None` if the entry doesn't exist.
+pub fn create_knowledge_attachment(
+    conn: &Connection,
+    project_id: &str,
+    key: &str,
+    input: &CreateArtifact,
+    author_type: &str,
+    author_id: &str,
+) -> Option<KnowledgeAttachment> {
+    let entry = get_knowledge(conn, project_id, key)?;
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO knowledge_attachments (id, knowledge_id, name, artifact_type, value, created_by_type, created_by_id, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![id, entry.id, input.name, input.artifact_type, input.value, author_type, author_id, now()],
+    )
+    .unwrap();
+    list_knowledge_attachments(conn, &entry.id)
+        .into_iter()
+        .find(|a| a.id == id)
+}
+
+pub fn delete_knowledge_attachment(
+    conn: &Connection,
+    project_id: &str,
+    key: &str,
+    attachment_id: &str,
+) -> bool {
+    conn.execute(
+        "DELETE FROM knowledge_attachments WHERE id = ?1 AND knowledge_id IN \
+         (SELECT id FROM project_knowledge WHERE project_id = ?2 AND key = ?3)",
+        params![attachment_id, project_id, key],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
+// --- Task ↔ Knowledge Links ---
+
+pub fn list_task_knowledge(conn: &Connection, task_id: &str) -> Vec<KnowledgeSummary> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT k.key, k.title, k.category, k.tags, k.updated_at \
+             FROM task_knowledge_links l JOIN project_knowledge k ON k.id = l.knowledge_id \
+             WHERE l.task_id = ?1 ORDER BY l.created_at, k.key",
+        )
+        .unwrap();
+    stmt.query_map(params![task_id], map_knowledge_summary)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+/// Link the entry `key` from the task's own project. Linking twice is a no-op.
+pub fn link_task_knowledge(
+    conn: &Connection,
+    tenant: Option<&str>,
+    task_id: &str,
+    key: &str,
+    linked_by_type: &str,
+    linked_by_id: &str,
+) -> Result<Vec<KnowledgeSummary>, String> {
+    let task = get_task(conn, tenant, task_id).ok_or("Task not found")?;
+    let entry = get_knowledge(conn, &task.project_id, key)
+        .ok_or_else(|| format!("Knowledge entry not found: {}", key))?;
+    conn.execute(
+        "INSERT OR IGNORE INTO task_knowledge_links (task_id, knowledge_id, linked_by_type, linked_by_id, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![task_id, entry.id, linked_by_type, linked_by_id, now()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(list_task_knowledge(conn, task_id))
+}
+
+pub fn unlink_task_knowledge(
+    conn: &Connection,
+    tenant: Option<&str>,
+    task_id: &str,
+    key: &str,
+) -> bool {
+    let Some(task) = get_task(conn, tenant, task_id) else {
+        return false;
+    };
+    conn.execute(
+        "DELETE FROM task_knowledge_links WHERE task_id = ?1 AND knowledge_id IN \
+         (SELECT id FROM project_knowledge WHERE project_id = ?2 AND key = ?3)",
+        params![task_id, task.project_id, key],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
 // --- Assignment ---
 
 pub fn assign_task(
@@ -2552,7 +5387,7 @@ pub fn assign_task(
     agent_id: &str,
 ) -> Result<Task, String> {
     let task = get_task(conn, tenant, task_id).ok_or("Task not found")?;
-    let agent = get_agent(conn, agent_id).ok_or("Agent not found")?;
+    let agent = get_tenant_agent(conn, tenant, agent_id).ok_or("Agent not found")?;
 
     // Offline agents can still be assigned — they will pick up the task on next heartbeat.
     // We record a note instead of blocking, so orchestrators can pre-assign work.
@@ -2565,6 +5400,8 @@ pub fn assign_task(
 
     // No capacity limit on assignment — assign is planning, not execution.
     // Capacity is enforced when the agent starts work (claim / start → in_progress).
+    // Quotas are cumulative, so they do apply here.
+    check_agent_quota(conn, &agent, task_id)?;
 
     let new_status = match status {
         TaskStatus::Backlog => {
@@ -2588,6 +5425,7 @@ pub fn assign_task(
         "UPDATE tasks SET assignee_type='agent', assignee_id=?1, status=?2, updated_at=?3 WHERE id=?4",
         params![agent_id, new_status, now, task_id],
     ).unwrap();
+    record_task_intake(conn, agent_id, task_id);
 
     let dep_note = match check_dependencies(conn, tenant, &task) {
         Err(pending_deps) => format!(
@@ -2639,7 +5477,7 @@ pub fn handoff_task(
     summary: Option<&str>,
 ) -> Result<Task, String> {
     let task = get_task(conn, tenant, task_id).ok_or("Task not found")?;
-    let to_agent = get_agent(conn, to_agent_id).ok_or("Target agent not found")?;
+    let to_agent = get_tenant_agent(conn, tenant, to_agent_id).ok_or("Target agent not found")?;
 
     let is_assignee = task.assignee_id.as_deref() == Some(from_agent_id);
     let is_reviewer = task.reviewer_id.as_deref() == Some(from_agent_id);
@@ -2798,19 +5636,31 @@ pub fn request_changes(
 /// Returns (reviewer_agent_id, reviewer_name) or None.
 fn pick_reviewer(
     conn: &Connection,
+    tenant: Option<&str>,
     task: &Task,
     explicit_reviewer_id: Option<&str>,
     submitter_id: &str,
 ) -> Option<String> {
+    // Reviewers come from the caller's tenant, or the task's for unscoped callers
+    let tenant = tenant.map(str::to_string).or_else(|| {
+        conn.query_row(
+            "SELECT owner_id FROM tasks WHERE id = ?1",
+            params![task.id],
+            |row| row.get(0),
+        )
+        .ok()
+        .flatten()
+    });
+    let tenant = tenant.as_deref();
+
     // 1. Explicit override
     if let Some(rid) = explicit_reviewer_id {
-        if get_agent(conn, rid).is_some() {
+        if get_tenant_agent(conn, tenant, rid).is_some() {
             return Some(rid.to_string());
         }
     }
 
-    // Reviewer search is scoped to tenant implicitly via task ownership
-    let all_agents = list_agents(conn, None);
+    let all_agents = list_agents(conn, tenant);
     let task_tags: Vec<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
 
     // Helper: agent is available for review (not the submitter, not offline)
@@ -2859,6 +5709,7 @@ pub fn submit_review_task(
     submitter_id: &str,
     summary: Option<&str>,
     explicit_reviewer_id: Option<&str>,
+    settings: &RuntimeSettings,
 ) -> Result<Task, String> {
     let task = get_task(conn, tenant, task_id).ok_or("Task not found")?;
 
@@ -2875,32 +5726,44 @@ pub fn submit_review_task(
         ));
     }
 
-    // Pick reviewer
-    let reviewer_id = pick_reviewer(conn, &task, explicit_reviewer_id, submitter_id)
-        .ok_or("No eligible senior reviewer found. Ask an orchestrator to assign one manually.")?;
+    // Pick reviewer, or leave it to a human or orchestrator when auto-assignment is off
+    let reviewer_id = if settings.auto_assign_reviewers {
+        Some(
+            pick_reviewer(conn, tenant, &task, explicit_reviewer_id, submitter_id).ok_or(
+                "No eligible senior reviewer found. Ask an orchestrator to assign one manually.",
+            )?,
+        )
+    } else {
+        explicit_reviewer_id
+            .filter(|rid| get_tenant_agent(conn, tenant, rid).is_some())
+            .map(str::to_string)
+    };
 
     let now = now();
     conn.execute(
-        "UPDATE tasks SET status='review', reviewer_type='agent', reviewer_id=?1, updated_at=?2 WHERE id=?3",
+        "UPDATE tasks SET status='review', reviewer_type=CASE WHEN ?1 IS NULL THEN NULL ELSE 'agent' END, reviewer_id=?1, updated_at=?2 WHERE id=?3",
         params![reviewer_id, now, task_id],
     ).unwrap();
 
     append_status_history(conn, task_id, "review", Some("agent"), Some(submitter_id));
 
     let summary_text = summary.unwrap_or("Submitted for review");
+    let reviewer_note = match &reviewer_id {
+        Some(rid) => format!(
+            "reviewer assigned: agent:{}",
+            get_agent(conn, rid)
+                .map(|a| a.name)
+                .unwrap_or_else(|| rid.clone())
+        ),
+        None => "no reviewer assigned".to_string(),
+    };
     create_activity(
         conn,
         task_id,
         "agent",
         submitter_id,
         &CreateActivity {
-            content: format!(
-                "{} (reviewer assigned: agent:{})",
-                summary_text,
-                get_agent(conn, &reviewer_id)
-                    .map(|a| a.name)
-                    .unwrap_or_else(|| reviewer_id.clone())
-            ),
+            content: format!("{} ({})", summary_text, reviewer_note),
             activity_type: Some("status_change".to_string()),
             metadata: None,
             mentions: None,
@@ -3157,6 +6020,54 @@ pub fn delete_artifact(conn: &Connection, artifact_id: &str) -> bool {
     rows > 0
 }
 
+// --- Usage ---
+
+pub fn record_task_usage(
+    conn: &Connection,
+    task_id: &str,
+    agent_id: &str,
+    input: &ReportUsage,
+) -> TaskUsage {
+    let id = Uuid::new_v4().to_string();
+    let now = now();
+    conn.execute(
+        "INSERT INTO task_usage (id, task_id, agent_id, input_tokens, output_tokens, cost_usd, cost_tier, model, reported_at)
+         SELECT ?1, ?2, ?3, ?4, ?5, ?6, a.cost_tier, a.model, ?7
+         FROM (SELECT 1) LEFT JOIN agents a ON a.id = ?3",
+        params![id, task_id, agent_id, input.input_tokens, input.output_tokens, input.cost_usd, now],
+    )
+    .unwrap();
+    list_task_usage(conn, task_id)
+        .into_iter()
+        .find(|u| u.id == id)
+        .unwrap()
+}
+
+pub fn list_task_usage(conn: &Connection, task_id: &str) -> Vec<TaskUsage> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, task_id, agent_id, input_tokens, output_tokens, cost_usd, cost_tier, model, reported_at
+             FROM task_usage WHERE task_id = ?1 ORDER BY reported_at ASC",
+        )
+        .unwrap();
+    stmt.query_map(params![task_id], |row| {
+        Ok(TaskUsage {
+            id: row.get(0)?,
+            task_id: row.get(1)?,
+            agent_id: row.get(2)?,
+            input_tokens: row.get(3)?,
+            output_tokens: row.get(4)?,
+            cost_usd: row.get(5)?,
+            cost_tier: row.get(6)?,
+            model: row.get(7)?,
+            reported_at: row.get(8)?,
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
 // --- Task Questions ---
 
 fn row_to_question(row: &rusqlite::Row) -> rusqlite::Result<TaskQuestion> {
@@ -3179,14 +6090,77 @@ fn row_to_question(row: &rusqlite::Row) -> rusqlite::Result<TaskQuestion> {
         resolution: row.get(14)?,
         created_at: row.get(15)?,
         resolved_at: row.get(16)?,
+        targeted_at: row.get(17)?,
+        routing_history: row
+            .get::<_, Option<String>>(18)?
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        attachments: vec![],
+        project_id: row.get::<_, Option<String>>(19)?.unwrap_or_default(),
+        priority: row.get(20)?,
     })
 }
 
-const QUESTION_COLS: &str = "id, task_id, question, question_type, context, asked_by_type, asked_by_id, target_type, target_id, required_capability, status, blocking, resolved_by_type, resolved_by_id, resolution, created_at, resolved_at";
+const QUESTION_COLS: &str = "id, task_id, question, question_type, context, asked_by_type, asked_by_id, target_type, target_id, required_capability, status, blocking, resolved_by_type, resolved_by_id, resolution, created_at, resolved_at, targeted_at, routing_history, project_id, priority";
+
+/// Most urgent first: priority, then blocking before non-blocking, then oldest.
+const QUESTION_URGENCY_ORDER: &str = "CASE priority WHEN 'critical' THEN 0 WHEN 'high' THEN 1 WHEN 'medium' THEN 2 ELSE 3 END, blocking DESC, created_at ASC";
+
+/// What a question is about, for notification titles: its task's title, or the project
+/// name for project-level questions.
+pub fn question_subject(conn: &Connection, question: &TaskQuestion) -> String {
+    match &question.task_id {
+        Some(task_id) => get_task(conn, None, task_id).map(|t| t.title),
+        None => get_project(conn, None, &question.project_id).map(|p| p.name),
+    }
+    .unwrap_or_default()
+}
+
+/// Refresh the owning task's `has_open_questions` flag; project-level questions have none.
+fn recalculate_question_task(conn: &Connection, question: &TaskQuestion) {
+    if let Some(task_id) = &question.task_id {
+        recalculate_has_open_questions(conn, task_id);
+    }
+}
+
+/// Point a question at a new target (or none) and append the step to its routing history.
+fn record_question_route(
+    conn: &Connection,
+    question_id: &str,
+    target_type: Option<&str>,
+    target_id: Option<&str>,
+    reason: &str,
+) -> bool {
+    let Some(question) = get_question(conn, question_id) else {
+        return false;
+    };
+    let now = now();
+    let mut history = question.routing_history;
+    history.push(QuestionRoute {
+        target_type: target_type.map(str::to_string),
+        target_id: target_id.map(str::to_string),
+        reason: reason.to_string(),
+        routed_at: now.clone(),
+    });
+    conn.execute(
+        "UPDATE task_questions SET target_type = ?1, target_id = ?2, targeted_at = ?3, routing_history = ?4 WHERE id = ?5",
+        params![
+            target_type,
+            target_id,
+            target_id.map(|_| now),
+            serde_json::to_string(&history).unwrap(),
+            question_id
+        ],
+    )
+    .unwrap();
+    true
+}
 
+/// Ask a question on a task, or on the project itself when `task_id` is `None`.
 pub fn create_question(
     conn: &Connection,
-    task_id: &str,
+    project_id: &str,
+    task_id: Option<&str>,
     input: &CreateQuestion,
     asked_by_type: &str,
     asked_by_id: &str,
@@ -3195,9 +6169,18 @@ pub fn create_question(
     let now = now();
     let question_type = input.question_type.as_deref().unwrap_or("clarification");
     let blocking: i64 = if input.blocking.unwrap_or(true) { 1 } else { 0 };
+    let priority = input
+        .priority
+        .clone()
+        .or_else(|| {
+            task_id
+                .and_then(|id| get_task(conn, None, id))
+                .map(|t| t.priority)
+        })
+        .unwrap_or_else(|| Priority::Medium.as_str().to_string());
     conn.execute(
-        "INSERT INTO task_questions (id, task_id, question, question_type, context, asked_by_type, asked_by_id, target_type, target_id, required_capability, status, blocking, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 'open', ?11, ?12)",
+        "INSERT INTO task_questions (id, task_id, question, question_type, context, asked_by_type, asked_by_id, target_type, target_id, required_capability, status, blocking, created_at, project_id, priority)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 'open', ?11, ?12, ?13, ?14)",
         params![
             id,
             task_id,
@@ -3210,17 +6193,35 @@ pub fn create_question(
             input.target_id,
             input.required_capability,
             blocking,
-            now
+            now,
+            project_id,
+            priority
         ],
     )
     .unwrap();
-    recalculate_has_open_questions(conn, task_id);
+    if input.target_id.is_some() {
+        record_question_route(
+            conn,
+            &id,
+            input.target_type.as_deref(),
+            input.target_id.as_deref(),
+            "asked",
+        );
+    }
+    if let Some(task_id) = task_id {
+        recalculate_has_open_questions(conn, task_id);
+    }
     get_question(conn, &id).unwrap()
 }
 
 pub fn get_question(conn: &Connection, id: &str) -> Option<TaskQuestion> {
     let sql = format!("SELECT {} FROM task_questions WHERE id = ?1", QUESTION_COLS);
-    conn.query_row(&sql, params![id], row_to_question).ok()
+    let mut question = conn.query_row(&sql, params![id], row_to_question).ok()?;
+    question.attachments = list_question_attachments(conn, id)
+        .into_iter()
+        .filter(|a| a.reply_id.is_none())
+        .collect();
+    Some(question)
 }
 
 pub fn list_questions(conn: &Connection, task_id: &str, status: Option<&str>) -> Vec<TaskQuestion> {
@@ -3256,8 +6257,8 @@ pub fn list_questions_for_agent(
 ) -> Vec<TaskQuestion> {
     let status_filter = status.unwrap_or("open");
     let sql = format!(
-        "SELECT {} FROM task_questions WHERE target_type = 'agent' AND target_id = ?1 AND status = ?2 ORDER BY created_at ASC",
-        QUESTION_COLS
+        "SELECT {} FROM task_questions WHERE target_type = 'agent' AND target_id = ?1 AND status = ?2 ORDER BY {}",
+        QUESTION_COLS, QUESTION_URGENCY_ORDER
     );
     let mut stmt = conn.prepare(&sql).unwrap();
     stmt.query_map(params![agent_id, status_filter], row_to_question)
@@ -3272,27 +6273,25 @@ pub fn list_questions_for_project(
     status: Option<&str>,
     unrouted: bool,
 ) -> Vec<TaskQuestion> {
-    let mut conditions = vec!["t.project_id = ?1".to_string()];
+    let mut conditions = vec!["project_id = ?1".to_string()];
     let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> =
         vec![Box::new(project_id.to_string())];
     let mut idx = 2;
 
     if let Some(s) = status {
-        conditions.push(format!("q.status = ?{}", idx));
+        conditions.push(format!("status = ?{}", idx));
         param_values.push(Box::new(s.to_string()));
         idx += 1;
     }
 
     if unrouted {
-        conditions.push("q.target_id IS NULL".to_string());
+        conditions.push("target_id IS NULL".to_string());
     }
     let _ = idx;
 
     let sql = format!(
-        "SELECT q.id, q.task_id, q.question, q.question_type, q.context, q.asked_by_type, q.asked_by_id, q.target_type, q.target_id, q.required_capability, q.status, q.blocking, q.resolved_by_type, q.resolved_by_id, q.resolution, q.created_at, q.resolved_at
-         FROM task_questions q
-         INNER JOIN tasks t ON t.id = q.task_id
-         WHERE {} ORDER BY q.created_at ASC",
+        "SELECT {} FROM task_questions WHERE {} ORDER BY created_at ASC",
+        QUESTION_COLS,
         conditions.join(" AND ")
     );
     let mut stmt = conn.prepare(&sql).unwrap();
@@ -3322,7 +6321,7 @@ pub fn resolve_question(
         return None;
     }
     let q = get_question(conn, question_id)?;
-    recalculate_has_open_questions(conn, &q.task_id);
+    recalculate_question_task(conn, &q);
     Some(q)
 }
 
@@ -3354,6 +6353,8 @@ fn row_to_reply(row: &rusqlite::Row) -> rusqlite::Result<QuestionReply> {
         body: row.get(4)?,
         is_resolution: row.get::<_, i64>(5)? != 0,
         created_at: row.get(6)?,
+        parent_reply_id: row.get(7)?,
+        attachments: vec![],
     })
 }
 
@@ -3368,9 +6369,9 @@ pub fn create_reply(
     let now = now();
     let is_resolution = input.is_resolution.unwrap_or(false);
     conn.execute(
-        "INSERT INTO question_replies (id, question_id, author_type, author_id, body, is_resolution, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        params![id, question_id, author_type, author_id, input.body, is_resolution as i64, now],
+        "INSERT INTO question_replies (id, question_id, author_type, author_id, body, is_resolution, created_at, parent_reply_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![id, question_id, author_type, author_id, input.body, is_resolution as i64, now, input.parent_reply_id],
     )
     .unwrap();
     // If is_resolution, auto-resolve the question
@@ -3381,11 +6382,11 @@ pub fn create_reply(
         ).unwrap();
         // Recalculate has_open_questions
         if let Some(q) = get_question(conn, question_id) {
-            recalculate_has_open_questions(conn, &q.task_id);
+            recalculate_question_task(conn, &q);
         }
     }
     conn.query_row(
-        "SELECT id, question_id, author_type, author_id, body, is_resolution, created_at FROM question_replies WHERE id = ?1",
+        "SELECT id, question_id, author_type, author_id, body, is_resolution, created_at, parent_reply_id FROM question_replies WHERE id = ?1",
         params![id],
         row_to_reply,
     ).unwrap()
@@ -3393,12 +6394,85 @@ pub fn create_reply(
 
 pub fn list_replies(conn: &Connection, question_id: &str) -> Vec<QuestionReply> {
     let mut stmt = conn.prepare(
-        "SELECT id, question_id, author_type, author_id, body, is_resolution, created_at FROM question_replies WHERE question_id = ?1 ORDER BY created_at ASC"
+        "SELECT id, question_id, author_type, author_id, body, is_resolution, created_at, parent_reply_id FROM question_replies WHERE question_id = ?1 ORDER BY created_at ASC, rowid ASC"
     ).unwrap();
-    stmt.query_map(params![question_id], row_to_reply)
+    let mut replies: Vec<QuestionReply> = stmt
+        .query_map(params![question_id], row_to_reply)
         .unwrap()
         .filter_map(|r| r.ok())
-        .collect()
+        .collect();
+    let mut by_reply: HashMap<String, Vec<QuestionAttachment>> = HashMap::new();
+    for attachment in list_question_attachments(conn, question_id) {
+        if let Some(reply_id) = attachment.reply_id.clone() {
+            by_reply.entry(reply_id).or_default().push(attachment);
+        }
+    }
+    for reply in &mut replies {
+        reply.attachments = by_reply.remove(&reply.id).unwrap_or_default();
+    }
+    replies
+}
+
+// --- Question Attachments ---
+
+/// Every attachment on a question, including those on its replies, oldest first.
+pub fn list_question_attachments(conn: &Connection, question_id: &str) -> Vec<QuestionAttachment> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, question_id, reply_id, name, artifact_type, value, created_by_type, created_by_id, created_at
+             FROM question_attachments WHERE question_id = ?1 ORDER BY created_at ASC, rowid ASC",
+        )
+        .unwrap();
+    stmt.query_map(params![question_id], |row| {
+        Ok(QuestionAttachment {
+            id: row.get(0)?,
+            question_id: row.get(1)?,
+            reply_id: row.get(2)?,
+            name: row.get(3)?,
+            artifact_type: row.get(4)?,
+            value: row.get(5)?,
+            created_by_type: row.get(6)?,
+            created_by_id: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
+/// Attach an artifact to a question, or to one of its replies when `reply_id` is set.
+/// `None` if the reply doesn't belong to the question.
+pub fn create_question_attachment(
+    conn: &Connection,
+    question_id: &str,
+    reply_id: Option<&str>,
+    input: &CreateArtifact,
+    author_type: &str,
+    author_id: &str,
+) -> Option<QuestionAttachment> {
+    if let Some(reply_id) = reply_id {
+        let belongs: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM question_replies WHERE id = ?1 AND question_id = ?2)",
+                params![reply_id, question_id],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !belongs {
+            return None;
+        }
+    }
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO question_attachments (id, question_id, reply_id, name, artifact_type, value, created_by_type, created_by_id, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![id, question_id, reply_id, input.name, input.artifact_type, input.value, author_type, author_id, now()],
+    )
+    .unwrap();
+    list_question_attachments(conn, question_id)
+        .into_iter()
+        .find(|a| a.id == id)
 }
 
 pub fn dismiss_question(
@@ -3417,7 +6491,7 @@ pub fn dismiss_question(
         return None;
     }
     let q = get_question(conn, question_id)?;
-    recalculate_has_open_questions(conn, &q.task_id);
+    recalculate_question_task(conn, &q);
     Some(q)
 }
 
@@ -3427,40 +6501,145 @@ pub fn assign_question(
     target_type: &str,
     target_id: &str,
 ) -> Option<TaskQuestion> {
-    let rows = conn
-        .execute(
-            "UPDATE task_questions SET target_type = ?1, target_id = ?2 WHERE id = ?3",
-            params![target_type, target_id, question_id],
-        )
-        .unwrap();
-    if rows == 0 {
+    if !record_question_route(
+        conn,
+        question_id,
+        Some(target_type),
+        Some(target_id),
+        "assigned",
+    ) {
         return None;
     }
     get_question(conn, question_id)
 }
 
+/// Move open questions off agent targets that went stale or offline without replying
+/// for `window_minutes`: to the best remaining capability match that is still around,
+/// or to nobody (`task.question_unrouted`) when there is none.
+pub fn reroute_inactive_questions(
+    conn: &Connection,
+    window_minutes: i64,
+) -> (Vec<QuestionReroute>, Vec<PendingNotifWebhook>) {
+    let sql = format!(
+        "SELECT {} FROM task_questions WHERE status = 'open' AND target_type = 'agent' \
+         AND targeted_at IS NOT NULL ORDER BY created_at",
+        QUESTION_COLS
+    );
+    let candidates: Vec<TaskQuestion> = conn
+        .prepare(&sql)
+        .unwrap()
+        .query_map([], row_to_question)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    let is_around =
+        |agent: &Agent| agent.presence != presence::STALE && agent.presence != presence::OFFLINE;
+
+    let mut rerouted = Vec::new();
+    let mut pending = Vec::new();
+    for q in candidates {
+        let (Some(from_id), Some(targeted_at)) = (q.target_id.clone(), q.targeted_at.clone())
+        else {
+            continue;
+        };
+        if !question_routing::is_overdue(&targeted_at, window_minutes) {
+            continue;
+        }
+        if get_agent(conn, &from_id).is_some_and(|a| is_around(&a)) {
+            continue;
+        }
+        let replied: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM question_replies WHERE question_id = ?1 AND author_id = ?2 AND created_at >= ?3)",
+                params![q.id, from_id, targeted_at],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if replied {
+            continue;
+        }
+        let title = question_subject(conn, &q);
+
+        // Never bounce back to an agent that already had the question
+        let tried: Vec<&str> = q
+            .routing_history
+            .iter()
+            .filter_map(|r| r.target_id.as_deref())
+            .chain(std::iter::once(from_id.as_str()))
+            .collect();
+        let next = q.required_capability.as_deref().and_then(|cap| {
+            find_capability_targets(conn, project_owner(conn, &q.project_id).as_deref(), cap)
+                .into_iter()
+                .filter(|t| t.target_type == "agent" && !tried.contains(&t.target_id.as_str()))
+                .find(|t| get_agent(conn, &t.target_id).is_some_and(|a| is_around(&a)))
+        });
+
+        let event_type = match &next {
+            Some(t) => {
+                record_question_route(
+                    conn,
+                    &q.id,
+                    Some("agent"),
+                    Some(&t.target_id),
+                    "target_inactive",
+                );
+                "task.question_rerouted"
+            }
+            None => {
+                record_question_route(conn, &q.id, None, None, "unrouted");
+                "task.question_unrouted"
+            }
+        };
+        let to_agent_id = next.map(|t| t.target_id);
+        let payload = serde_json::json!({
+            "task_title": title,
+            "actor_name": "system",
+            "question_id": q.id,
+            "question": q.question,
+            "priority": q.priority,
+            "target_type": to_agent_id.as_ref().map(|_| "agent"),
+            "target_id": to_agent_id,
+            "previous_target_id": from_id,
+            "asked_by_type": q.asked_by_type,
+            "asked_by_id": q.asked_by_id,
+        });
+        pending.extend(emit_event(
+            conn,
+            event_type,
+            q.task_id.as_deref(),
+            &q.project_id,
+            "system",
+            "system",
+            &payload,
+        ));
+        rerouted.push(QuestionReroute {
+            question_id: q.id,
+            task_id: q.task_id,
+            project_id: q.project_id,
+            from_agent_id: from_id,
+            to_agent_id,
+        });
+    }
+    (rerouted, pending)
+}
+
 // --- Capability-based Question Auto-targeting ---
 
 // CapabilityTarget is defined in opengate_models
 
-/// Find agents matching a required capability string.
+/// Find `tenant`'s agents matching a required capability string.
 /// Returns matches sorted: online agents first, scored by capability_match_score,
 /// then offline agents.
 pub fn find_capability_targets(
     conn: &Connection,
+    tenant: Option<&str>,
     required_capability: &str,
 ) -> Vec<CapabilityTarget> {
     let required = vec![required_capability.to_string()];
     let mut targets: Vec<CapabilityTarget> = Vec::new();
 
     // 1. Search agents — prefer online/available ones
-    let sql = format!("SELECT {} FROM agents", AGENT_COLS);
-    let mut stmt = conn.prepare(&sql).unwrap();
-    let agents: Vec<Agent> = stmt
-        .query_map([], |row| row_to_agent(conn, row))
-        .unwrap()
-        .filter_map(|r| r.ok())
-        .collect();
+    let agents = list_agents(conn, tenant);
 
     let mut scored_agents: Vec<(Agent, usize)> = agents
         .into_iter()
@@ -3506,16 +6685,27 @@ pub fn auto_target_question(
     question_id: &str,
     required_capability: &str,
 ) -> Vec<CapabilityTarget> {
-    let targets = find_capability_targets(conn, required_capability);
+    // Only the question's own tenant can be asked
+    let tenant = conn
+        .query_row(
+            "SELECT project_id FROM task_questions WHERE id = ?1",
+            params![question_id],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|project_id| project_owner(conn, &project_id));
+    let targets = find_capability_targets(conn, tenant.as_deref(), required_capability);
 
     if targets.len() == 1 {
         // Single match — assign directly
         let t = &targets[0];
-        conn.execute(
-            "UPDATE task_questions SET target_type = ?1, target_id = ?2 WHERE id = ?3",
-            params![t.target_type, t.target_id, question_id],
-        )
-        .unwrap();
+        record_question_route(
+            conn,
+            question_id,
+            Some(&t.target_type),
+            Some(&t.target_id),
+            "capability_match",
+        );
     }
 
     targets
@@ -3567,10 +6757,13 @@ pub fn get_agent_name(conn: &Connection, agent_id: &str) -> Option<String> {
 
 pub fn get_pulse(
     conn: &Connection,
-    _tenant: Option<&str>,
+    tenant: Option<&str>,
     project_id: &str,
     caller_agent_id: Option<&str>,
 ) -> PulseResponse {
+    if !project_in_tenant(conn, tenant, project_id) {
+        return PulseResponse::default();
+    }
     // Active tasks (backlog, todo, in_progress, handoff)
     let active_tasks = pulse_tasks_by_statuses(
         conn,
@@ -3620,7 +6813,7 @@ pub fn get_pulse(
     // Agents relevant to this project (have been assigned tasks)
     let agents: Vec<PulseAgent> = conn
         .prepare(
-            "SELECT DISTINCT a.id, a.name, a.last_seen_at, a.max_concurrent_tasks, a.seniority, a.role, a.stale_timeout
+            "SELECT DISTINCT a.id, a.name, a.last_seen_at, a.max_concurrent_tasks, a.seniority, a.role, a.stale_timeout, a.heartbeat_status
              FROM agents a
              WHERE a.id IN (
                  SELECT DISTINCT assignee_id FROM tasks
@@ -3633,19 +6826,14 @@ pub fn get_pulse(
             let agent_id: String = row.get(0)?;
             let name: String = row.get(1)?;
             let last_seen: Option<String> = row.get(2)?;
-            let max_concurrent: i64 = row.get::<_, Option<i64>>(3)?.unwrap_or(2);
             let seniority: String = row.get::<_, Option<String>>(4)?.unwrap_or_else(|| "mid".to_string());
             let role: String = row.get::<_, Option<String>>(5)?.unwrap_or_else(|| "executor".to_string());
             let stale_timeout: i64 = row.get::<_, Option<i64>>(6)?.unwrap_or(30);
+            let heartbeat_status: Option<HeartbeatStatus> = row
+                .get::<_, Option<String>>(7)?
+                .and_then(|s| serde_json::from_str(&s).ok());
 
-            // Current task count
-            let current_tasks: i64 = conn.query_row(
-                "SELECT COUNT(*) FROM tasks WHERE assignee_id = ?1 AND assignee_type = 'agent' AND status NOT IN ('done', 'cancelled')",
-                params![agent_id],
-                |r| r.get(0),
-            ).unwrap_or(0);
-
-            let status = compute_agent_status(&last_seen, current_tasks, max_concurrent, stale_timeout);
+            let status = presence::presence(last_seen.as_deref(), stale_timeout).to_string();
 
             // Current task title (if working on something in this project)
             let current_task: Option<String> = conn.query_row(
@@ -3662,6 +6850,7 @@ pub fn get_pulse(
                 role,
                 current_task,
                 last_seen_at: last_seen,
+                heartbeat_status,
             })
         })
         .unwrap()
@@ -3682,6 +6871,52 @@ pub fn get_pulse(
                 title: row.get(1)?,
                 category: row.get(2)?,
                 updated_at: row.get(3)?,
+                review_after: None,
+                reads: None,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let knowledge_needing_review: Vec<PulseKnowledge> = conn
+        .prepare(
+            "SELECT key, title, category, updated_at, review_after FROM project_knowledge
+             WHERE project_id = ?1 AND review_after IS NOT NULL AND review_after <= ?2
+             ORDER BY review_after",
+        )
+        .unwrap()
+        .query_map(params![project_id, freshness::now_stamp()], |row| {
+            Ok(PulseKnowledge {
+                key: row.get(0)?,
+                title: row.get(1)?,
+                category: row.get(2)?,
+                updated_at: row.get(3)?,
+                review_after: row.get(4)?,
+                reads: None,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let most_read_knowledge: Vec<PulseKnowledge> = conn
+        .prepare(
+            "SELECT k.key, k.title, k.category, k.updated_at, SUM(r.read_count) AS reads
+             FROM knowledge_reads r JOIN project_knowledge k
+               ON k.project_id = r.project_id AND k.key = r.key
+             WHERE r.project_id = ?1
+             GROUP BY k.key ORDER BY reads DESC, k.key LIMIT 5",
+        )
+        .unwrap()
+        .query_map(params![project_id], |row| {
+            Ok(PulseKnowledge {
+                key: row.get(0)?,
+                title: row.get(1)?,
+                category: row.get(2)?,
+                updated_at: row.get(3)?,
+                review_after: None,
+                reads: row.get(4)?,
             })
         })
         .unwrap()
@@ -3701,6 +6936,18 @@ pub fn get_pulse(
         )
         .unwrap_or(0);
 
+    let pending_cycles: Vec<ReviewCycle> = review_cycles(conn, None, Some(project_id), Utc::now())
+        .into_iter()
+        .filter(|c| c.ended.is_none())
+        .collect();
+    let mut slowest_review_queues = review_latency_by(
+        &pending_cycles,
+        |c| c.reviewer_id.as_deref(),
+        |id| agent_name(conn, id),
+    );
+    slowest_review_queues.sort_by_key(|q| std::cmp::Reverse(q.oldest_pending_secs));
+    slowest_review_queues.truncate(3);
+
     PulseResponse {
         active_tasks,
         blocked_tasks,
@@ -3709,7 +6956,10 @@ pub fn get_pulse(
         unread_events,
         agents,
         recent_knowledge_updates,
+        knowledge_needing_review,
+        most_read_knowledge,
         blocked_by_deps,
+        slowest_review_queues,
     }
 }
 
@@ -3787,14 +7037,117 @@ fn pulse_task_from_row(conn: &Connection, row: &rusqlite::Row) -> PulseTask {
     }
 }
 
-// --- API Key Hashing ---
+// --- API Key Hashing ---
+
+pub fn hash_api_key(key: &str) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+// --- Scoped API Keys ---
+
+const API_KEY_COLS: &str =
+    "id, agent_id, name, read_only, project_id, no_admin, created_at, last_used_at";
+
+fn row_to_api_key(row: &rusqlite::Row) -> rusqlite::Result<ApiKey> {
+    Ok(ApiKey {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        name: row.get(2)?,
+        scope: KeyScope {
+            read_only: row.get::<_, i64>(3)? != 0,
+            project_id: row.get(4)?,
+            no_admin: row.get::<_, i64>(5)? != 0,
+        },
+        created_at: row.get(6)?,
+        last_used_at: row.get(7)?,
+    })
+}
+
+/// Mint an additional key for `agent_id`. Returns the key record and the plaintext secret.
+pub fn create_api_key(
+    conn: &Connection,
+    agent_id: &str,
+    input: &CreateApiKey,
+) -> Result<(ApiKey, String), String> {
+    if get_agent(conn, agent_id).is_none() {
+        return Err("Agent not found".to_string());
+    }
+    if let Some(ref project_id) = input.scope.project_id {
+        if get_project(conn, None, project_id).is_none() {
+            return Err(format!("Project not found: {}", project_id));
+        }
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let api_key = format!("tf_{}", Uuid::new_v4().to_string().replace('-', ""));
+    conn.execute(
+        "INSERT INTO agent_api_keys (id, agent_id, name, key_hash, read_only, project_id, no_admin, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            id,
+            agent_id,
+            input.name,
+            hash_api_key(&api_key),
+            input.scope.read_only,
+            input.scope.project_id,
+            input.scope.no_admin,
+            now()
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let key = conn
+        .query_row(
+            &format!("SELECT {} FROM agent_api_keys WHERE id = ?1", API_KEY_COLS),
+            params![id],
+            row_to_api_key,
+        )
+        .map_err(|e| e.to_string())?;
+    Ok((key, api_key))
+}
+
+pub fn list_api_keys(conn: &Connection, agent_id: &str) -> Vec<ApiKey> {
+    let sql = format!(
+        "SELECT {} FROM agent_api_keys WHERE agent_id = ?1 ORDER BY created_at",
+        API_KEY_COLS
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map(params![agent_id], row_to_api_key)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+pub fn revoke_api_key(conn: &Connection, agent_id: &str, key_id: &str) -> bool {
+    conn.execute(
+        "DELETE FROM agent_api_keys WHERE id = ?1 AND agent_id = ?2",
+        params![key_id, agent_id],
+    )
+    .unwrap_or(0)
+        > 0
+}
 
-pub fn hash_api_key(key: &str) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    let mut hasher = DefaultHasher::new();
-    key.hash(&mut hasher);
-    format!("{:x}", hasher.finish())
+/// Look up an additional key by hash, stamping its `last_used_at`.
+pub fn get_api_key_by_hash(conn: &Connection, hash: &str) -> Option<ApiKey> {
+    let key = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM agent_api_keys WHERE key_hash = ?1",
+                API_KEY_COLS
+            ),
+            params![hash],
+            row_to_api_key,
+        )
+        .ok()?;
+    conn.execute(
+        "UPDATE agent_api_keys SET last_used_at = ?1 WHERE id = ?2",
+        params![now(), key.id],
+    )
+    .unwrap();
+    Some(key)
 }
 
 // ─── Agent Matching ──────────────────────────────────────────────────────────
@@ -3803,9 +7156,19 @@ pub fn find_best_agent(
     conn: &Connection,
     tenant: Option<&str>,
     strategy: &AssignStrategy,
+) -> Option<String> {
+    find_best_agent_excluding(conn, tenant, strategy, &[])
+}
+
+/// Like `find_best_agent`, but never picks one of the `exclude`d agents.
+fn find_best_agent_excluding(
+    conn: &Connection,
+    tenant: Option<&str>,
+    strategy: &AssignStrategy,
+    exclude: &[&str],
 ) -> Option<String> {
     if let Some(ref id) = strategy.agent_id {
-        return Some(id.clone());
+        return (!exclude.contains(&id.as_str())).then(|| id.clone());
     }
 
     let agents = list_agents(conn, tenant);
@@ -3813,7 +7176,12 @@ pub fn find_best_agent(
 
     let mut scored: Vec<(Agent, usize)> = agents
         .into_iter()
+        .filter(|a| !exclude.contains(&a.id.as_str()))
         .filter(|a| a.status != "offline")
+        .filter(|a| {
+            let (today, week) = agent_quota_usage(conn, &a.id);
+            !quota_exhausted(a, today, week)
+        })
         .filter(|a| {
             strategy
                 .seniority
@@ -3831,14 +7199,81 @@ pub fn find_best_agent(
         })
         .collect();
 
-    scored.sort_by(|a, b| {
-        b.1.cmp(&a.1)
-            .then_with(|| a.0.current_task_count.cmp(&b.0.current_task_count))
-    });
+    // Start from a stable order so every strategy breaks ties by agent id
+    scored.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+
+    match strategy.strategy.as_str() {
+        "least_loaded" => scored.sort_by(|a, b| {
+            a.0.current_task_count
+                .cmp(&b.0.current_task_count)
+                .then_with(|| b.1.cmp(&a.1))
+        }),
+        "highest_seniority" => scored.sort_by(|a, b| {
+            seniority_rank(&b.0.seniority)
+                .cmp(&seniority_rank(&a.0.seniority))
+                .then_with(|| b.1.cmp(&a.1))
+                .then_with(|| a.0.current_task_count.cmp(&b.0.current_task_count))
+        }),
+        "round_robin" => return pick_round_robin(conn, &scored),
+        "random" => {
+            if scored.is_empty() {
+                return None;
+            }
+            let idx = (Uuid::new_v4().as_u128() % scored.len() as u128) as usize;
+            return Some(scored.swap_remove(idx).0.id);
+        }
+        // Best capability match, then least busy
+        "capability" | "seniority" => scored.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| a.0.current_task_count.cmp(&b.0.current_task_count))
+        }),
+        // An unknown strategy (or `explicit` without an agent_id) assigns no one
+        _ => return None,
+    }
 
     scored.into_iter().next().map(|(a, _)| a.id)
 }
 
+fn seniority_rank(seniority: &str) -> u8 {
+    match seniority {
+        "senior" => 3,
+        "mid" => 2,
+        "junior" => 1,
+        _ => 0,
+    }
+}
+
+/// Pick the candidate that has gone longest without an auto-assignment (never-picked
+/// agents first, ties by id) and advance its cursor.
+fn pick_round_robin(conn: &Connection, candidates: &[(Agent, usize)]) -> Option<String> {
+    let mut best: Option<(Option<i64>, &str)> = None;
+    for (agent, _) in candidates {
+        let last: Option<i64> = conn
+            .query_row(
+                "SELECT last_assigned_seq FROM agents WHERE id = ?1",
+                params![agent.id],
+                |row| row.get(0),
+            )
+            .ok()
+            .flatten();
+        let better = match best {
+            None => true,
+            // None sorts before Some, so never-assigned agents win
+            Some((best_last, _)) => last < best_last,
+        };
+        if better {
+            best = Some((last, agent.id.as_str()));
+        }
+    }
+
+    let (_, id) = best?;
+    let _ = conn.execute(
+        "UPDATE agents SET last_assigned_seq = (SELECT COALESCE(MAX(last_assigned_seq), 0) + 1 FROM agents) WHERE id = ?1",
+        params![id],
+    );
+    Some(id.to_string())
+}
+
 fn capability_match_score(agent_caps: &[String], required: &[String]) -> usize {
     if required.is_empty() {
         return 1;
@@ -3866,61 +7301,93 @@ fn sha256_hex(input: &str) -> String {
 pub fn create_webhook_trigger(
     conn: &Connection,
     project_id: &str,
-    name: &str,
-    action_type: &str,
-    action_config: &serde_json::Value,
+    input: &opengate_models::CreateTriggerRequest,
 ) -> (opengate_models::WebhookTrigger, String) {
     let id = uuid::Uuid::new_v4().to_string();
     let raw_secret = uuid::Uuid::new_v4().to_string() + &uuid::Uuid::new_v4().to_string();
     let secret_hash = sha256_hex(&raw_secret);
     let now = Utc::now().to_rfc3339();
-    let config_str = action_config.to_string();
+    let config_str = input.action_config.to_string();
+    let verification = input.verification.as_deref().unwrap_or("secret");
+    let dedup_key = input.dedup_key.clone().filter(|k| !k.is_empty());
+    let dedup_window_hours = input
+        .dedup_window_hours
+        .unwrap_or(opengate_models::DEFAULT_DEDUP_WINDOW_HOURS);
+    let rate_limit = input.rate_limit_per_minute.filter(|n| *n > 0);
 
     conn.execute(
-        "INSERT INTO webhook_triggers (id, project_id, name, secret_hash, action_type, action_config, enabled, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, ?7, ?8)",
-        rusqlite::params![id, project_id, name, secret_hash, action_type, config_str, now, now],
+        "INSERT INTO webhook_triggers (id, project_id, name, secret_hash, action_type, action_config, enabled, verification, signing_secret, dedup_key, dedup_window_hours, rate_limit_per_minute, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        rusqlite::params![
+            id,
+            project_id,
+            input.name,
+            secret_hash,
+            input.action_type,
+            config_str,
+            verification,
+            input.signing_secret,
+            dedup_key,
+            dedup_window_hours,
+            rate_limit,
+            now,
+            now
+        ],
     ).expect("Failed to create webhook trigger");
 
     let trigger = opengate_models::WebhookTrigger {
         id,
         project_id: project_id.to_string(),
-        name: name.to_string(),
-        action_type: action_type.to_string(),
-        action_config: action_config.clone(),
+        name: input.name.clone(),
+        action_type: input.action_type.clone(),
+        action_config: input.action_config.clone(),
         enabled: true,
         ip_allowlist: vec![],
+        verification: verification.to_string(),
+        signing_secret: input.signing_secret.clone(),
+        dedup_key,
+        dedup_window_hours,
+        rate_limit_per_minute: rate_limit,
         created_at: now.clone(),
         updated_at: now,
     };
     (trigger, raw_secret)
 }
 
+const TRIGGER_COLUMNS: &str = "id, project_id, name, action_type, action_config, enabled, created_at, updated_at, verification, signing_secret, dedup_key, dedup_window_hours, rate_limit_per_minute";
+
+fn map_trigger_row(row: &rusqlite::Row) -> rusqlite::Result<opengate_models::WebhookTrigger> {
+    let config_str: String = row.get(4)?;
+    let config: serde_json::Value =
+        serde_json::from_str(&config_str).unwrap_or(serde_json::Value::Null);
+    Ok(opengate_models::WebhookTrigger {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        name: row.get(2)?,
+        action_type: row.get(3)?,
+        action_config: config,
+        enabled: row.get::<_, i64>(5)? != 0,
+        ip_allowlist: vec![],
+        verification: row.get(8)?,
+        signing_secret: row.get(9)?,
+        dedup_key: row.get(10)?,
+        dedup_window_hours: row.get(11)?,
+        rate_limit_per_minute: row.get(12)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
 pub fn list_webhook_triggers(
     conn: &Connection,
     project_id: &str,
 ) -> Vec<opengate_models::WebhookTrigger> {
-    conn.prepare(
-        "SELECT id, project_id, name, action_type, action_config, enabled, created_at, updated_at
-         FROM webhook_triggers WHERE project_id = ?1 ORDER BY created_at ASC",
-    )
+    conn.prepare(&format!(
+        "SELECT {} FROM webhook_triggers WHERE project_id = ?1 ORDER BY created_at ASC",
+        TRIGGER_COLUMNS
+    ))
     .unwrap()
-    .query_map(rusqlite::params![project_id], |row| {
-        let config_str: String = row.get(4)?;
-        let config: serde_json::Value =
-            serde_json::from_str(&config_str).unwrap_or(serde_json::Value::Null);
-        Ok(opengate_models::WebhookTrigger {
-            id: row.get(0)?,
-            project_id: row.get(1)?,
-            name: row.get(2)?,
-            action_type: row.get(3)?,
-            action_config: config,
-            enabled: row.get::<_, i64>(5)? != 0,
-            ip_allowlist: vec![],
-            created_at: row.get(6)?,
-            updated_at: row.get(7)?,
-        })
-    })
+    .query_map(rusqlite::params![project_id], map_trigger_row)
     .unwrap()
     .filter_map(|r| r.ok())
     .collect()
@@ -3932,24 +7399,14 @@ pub fn get_webhook_trigger_for_validation(
     trigger_id: &str,
 ) -> Option<(opengate_models::WebhookTrigger, String)> {
     conn.query_row(
-        "SELECT id, project_id, name, action_type, action_config, enabled, created_at, updated_at, secret_hash
-         FROM webhook_triggers WHERE id = ?1",
+        &format!(
+            "SELECT {}, secret_hash FROM webhook_triggers WHERE id = ?1",
+            TRIGGER_COLUMNS
+        ),
         rusqlite::params![trigger_id],
         |row| {
-            let config_str: String = row.get(4)?;
-            let config: serde_json::Value = serde_json::from_str(&config_str).unwrap_or(serde_json::Value::Null);
-            let trigger = opengate_models::WebhookTrigger {
-                id: row.get(0)?,
-                project_id: row.get(1)?,
-                name: row.get(2)?,
-                action_type: row.get(3)?,
-                action_config: config,
-                enabled: row.get::<_, i64>(5)? != 0,
-                ip_allowlist: vec![],
-                created_at: row.get(6)?,
-                updated_at: row.get(7)?,
-            };
-            let hash: String = row.get(8)?;
+            let trigger = map_trigger_row(row)?;
+            let hash: String = row.get(13)?;
             Ok((trigger, hash))
         },
     )
@@ -3980,6 +7437,26 @@ pub fn update_webhook_trigger(
         sets.push("enabled = ?".to_string());
         values.push(Box::new(enabled as i64));
     }
+    if let Some(ref verification) = input.verification {
+        sets.push("verification = ?".to_string());
+        values.push(Box::new(verification.clone()));
+    }
+    if let Some(ref signing_secret) = input.signing_secret {
+        sets.push("signing_secret = ?".to_string());
+        values.push(Box::new(signing_secret.clone()));
+    }
+    if let Some(ref dedup_key) = input.dedup_key {
+        sets.push("dedup_key = ?".to_string());
+        values.push(Box::new(Some(dedup_key.clone()).filter(|k| !k.is_empty())));
+    }
+    if let Some(hours) = input.dedup_window_hours {
+        sets.push("dedup_window_hours = ?".to_string());
+        values.push(Box::new(hours));
+    }
+    if let Some(limit) = input.rate_limit_per_minute {
+        sets.push("rate_limit_per_minute = ?".to_string());
+        values.push(Box::new(Some(limit).filter(|n| *n > 0)));
+    }
 
     let now_str = now();
     sets.push("updated_at = ?".to_string());
@@ -3998,25 +7475,12 @@ pub fn update_webhook_trigger(
 
     // Re-read the updated trigger
     conn.query_row(
-        "SELECT id, project_id, name, action_type, action_config, enabled, created_at, updated_at
-         FROM webhook_triggers WHERE id = ?1",
+        &format!(
+            "SELECT {} FROM webhook_triggers WHERE id = ?1",
+            TRIGGER_COLUMNS
+        ),
         rusqlite::params![trigger_id],
-        |row| {
-            let config_str: String = row.get(4)?;
-            let config: serde_json::Value =
-                serde_json::from_str(&config_str).unwrap_or(serde_json::Value::Null);
-            Ok(opengate_models::WebhookTrigger {
-                id: row.get(0)?,
-                project_id: row.get(1)?,
-                name: row.get(2)?,
-                action_type: row.get(3)?,
-                action_config: config,
-                enabled: row.get::<_, i64>(5)? != 0,
-                ip_allowlist: vec![],
-                created_at: row.get(6)?,
-                updated_at: row.get(7)?,
-            })
-        },
+        map_trigger_row,
     )
     .ok()
 }
@@ -4052,6 +7516,49 @@ pub fn log_trigger_execution(
     id
 }
 
+/// Record a delivery's dedup value; false if the trigger already saw it within
+/// `window_hours`.
+pub fn claim_trigger_delivery(
+    conn: &Connection,
+    trigger_id: &str,
+    dedup_value: &str,
+    window_hours: i64,
+) -> bool {
+    let cutoff = (Utc::now() - chrono::Duration::hours(window_hours)).to_rfc3339();
+    conn.execute(
+        "DELETE FROM trigger_deliveries WHERE trigger_id = ?1 AND received_at < ?2",
+        rusqlite::params![trigger_id, cutoff],
+    )
+    .ok();
+    conn.execute(
+        "INSERT OR IGNORE INTO trigger_deliveries (trigger_id, dedup_value, received_at)
+         VALUES (?1, ?2, ?3)",
+        rusqlite::params![trigger_id, dedup_value, now()],
+    )
+    .map(|n| n == 1)
+    .unwrap_or(true)
+}
+
+/// Forget a claimed delivery, so a retry of one that failed runs again.
+pub fn release_trigger_delivery(conn: &Connection, trigger_id: &str, dedup_value: &str) {
+    conn.execute(
+        "DELETE FROM trigger_deliveries WHERE trigger_id = ?1 AND dedup_value = ?2",
+        rusqlite::params![trigger_id, dedup_value],
+    )
+    .ok();
+}
+
+/// Runs of the trigger (successful or failed) logged since `since` (RFC 3339).
+pub fn count_trigger_runs_since(conn: &Connection, trigger_id: &str, since: &str) -> i64 {
+    conn.query_row(
+        "SELECT COUNT(*) FROM webhook_trigger_logs
+         WHERE trigger_id = ?1 AND received_at >= ?2 AND status IN ('success', 'failed')",
+        rusqlite::params![trigger_id, since],
+        |row| row.get(0),
+    )
+    .unwrap_or(0)
+}
+
 pub fn list_trigger_logs(
     conn: &Connection,
     trigger_id: &str,
@@ -4080,12 +7587,332 @@ pub fn list_trigger_logs(
     .collect()
 }
 
+// ===== Slack Integration =====
+
+pub fn get_slack_integration(conn: &Connection, project_id: &str) -> Option<SlackIntegration> {
+    conn.query_row(
+        "SELECT project_id, webhook_url, bot_token, channel, signing_secret, events, dashboard_url, created_at, updated_at
+         FROM slack_integrations WHERE project_id = ?1",
+        params![project_id],
+        |row| {
+            let bot_token: Option<String> = row.get(2)?;
+            let channel: Option<String> = row.get(3)?;
+            Ok(SlackIntegration {
+                project_id: row.get(0)?,
+                webhook_url: row.get(1)?,
+                threaded: bot_token.is_some() && channel.is_some(),
+                bot_token,
+                channel,
+                signing_secret: row.get(4)?,
+                events: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+                dashboard_url: row.get(6)?,
+                created_at: row.get(7)?,
+                updated_at: row.get(8)?,
+            })
+        },
+    )
+    .ok()
+}
+
+/// Create or replace a project's Slack integration.
+pub fn set_slack_integration(
+    conn: &Connection,
+    project_id: &str,
+    input: &SetSlackIntegration,
+) -> SlackIntegration {
+    let now = now();
+    let events = input
+        .events
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SLACK_EVENTS.iter().map(|e| e.to_string()).collect());
+    conn.execute(
+        "INSERT INTO slack_integrations (project_id, webhook_url, bot_token, channel, signing_secret, events, dashboard_url, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
+         ON CONFLICT(project_id) DO UPDATE SET webhook_url = ?2, bot_token = ?3, channel = ?4, signing_secret = ?5, events = ?6, dashboard_url = ?7, updated_at = ?8",
+        params![
+            project_id,
+            input.webhook_url,
+            input.bot_token,
+            input.channel,
+            input.signing_secret,
+            serde_json::to_string(&events).unwrap(),
+            input.dashboard_url.as_deref().map(|u| u.trim_end_matches('/')),
+            now
+        ],
+    )
+    .unwrap();
+    get_slack_integration(conn, project_id).unwrap()
+}
+
+pub fn delete_slack_integration(conn: &Connection, project_id: &str) -> bool {
+    conn.execute(
+        "DELETE FROM slack_integrations WHERE project_id = ?1",
+        params![project_id],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
+/// Remember the Slack thread a task's messages are posted in.
+pub fn record_slack_thread(conn: &Connection, task_id: &str, channel: &str, thread_ts: &str) {
+    conn.execute(
+        "INSERT OR IGNORE INTO slack_threads (channel, thread_ts, task_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![channel, thread_ts, task_id, now()],
+    )
+    .unwrap_or(0);
+}
+
+/// The `(channel, thread_ts)` a task was first posted in.
+pub fn get_slack_thread(conn: &Connection, task_id: &str) -> Option<(String, String)> {
+    conn.query_row(
+        "SELECT channel, thread_ts FROM slack_threads WHERE task_id = ?1 ORDER BY created_at ASC LIMIT 1",
+        params![task_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .ok()
+}
+
+/// The task a Slack thread belongs to.
+pub fn find_slack_thread_task(conn: &Connection, channel: &str, thread_ts: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT task_id FROM slack_threads WHERE channel = ?1 AND thread_ts = ?2",
+        params![channel, thread_ts],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+// ===== GitHub Integration =====
+
+pub fn get_github_integration(conn: &Connection, project_id: &str) -> Option<GithubIntegration> {
+    conn.query_row(
+        "SELECT project_id, token, comment_on_complete, created_at, updated_at
+         FROM github_integrations WHERE project_id = ?1",
+        params![project_id],
+        |row| {
+            let token: Option<String> = row.get(1)?;
+            Ok(GithubIntegration {
+                project_id: row.get(0)?,
+                has_token: token.is_some(),
+                token,
+                comment_on_complete: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        },
+    )
+    .ok()
+}
+
+/// Create or replace a project's GitHub integration.
+pub fn set_github_integration(
+    conn: &Connection,
+    project_id: &str,
+    input: &SetGithubIntegration,
+) -> GithubIntegration {
+    let now = now();
+    conn.execute(
+        "INSERT INTO github_integrations (project_id, token, comment_on_complete, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT(project_id) DO UPDATE SET token = ?2, comment_on_complete = ?3, updated_at = ?4",
+        params![
+            project_id,
+            input.token.as_deref().filter(|t| !t.is_empty()),
+            input.comment_on_complete.unwrap_or(true),
+            now
+        ],
+    )
+    .unwrap();
+    get_github_integration(conn, project_id).unwrap()
+}
+
+pub fn delete_github_integration(conn: &Connection, project_id: &str) -> bool {
+    conn.execute(
+        "DELETE FROM github_integrations WHERE project_id = ?1",
+        params![project_id],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
+// ===== GitLab Integration =====
+
+pub fn get_gitlab_integration(conn: &Connection, project_id: &str) -> Option<GitlabIntegration> {
+    conn.query_row(
+        "SELECT project_id, token, gitlab_project, comment_on_complete, created_at, updated_at
+         FROM gitlab_integrations WHERE project_id = ?1",
+        params![project_id],
+        |row| {
+            let token: Option<String> = row.get(1)?;
+            Ok(GitlabIntegration {
+                project_id: row.get(0)?,
+                has_token: token.is_some(),
+                token,
+                gitlab_project: row.get(2)?,
+                comment_on_complete: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        },
+    )
+    .ok()
+}
+
+/// Create or replace a project's GitLab integration.
+pub fn set_gitlab_integration(
+    conn: &Connection,
+    project_id: &str,
+    input: &SetGitlabIntegration,
+) -> GitlabIntegration {
+    let now = now();
+    conn.execute(
+        "INSERT INTO gitlab_integrations
+            (project_id, token, gitlab_project, comment_on_complete, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+         ON CONFLICT(project_id) DO UPDATE SET
+            token = ?2, gitlab_project = ?3, comment_on_complete = ?4, updated_at = ?5",
+        params![
+            project_id,
+            input.token.as_deref().filter(|t| !t.is_empty()),
+            input.gitlab_project.as_deref().filter(|p| !p.is_empty()),
+            input.comment_on_complete.unwrap_or(true),
+            now
+        ],
+    )
+    .unwrap();
+    get_gitlab_integration(conn, project_id).unwrap()
+}
+
+pub fn delete_gitlab_integration(conn: &Connection, project_id: &str) -> bool {
+    conn.execute(
+        "DELETE FROM gitlab_integrations WHERE project_id = ?1",
+        params![project_id],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
+// ===== Linear Integration =====
+
+pub fn get_linear_integration(conn: &Connection, project_id: &str) -> Option<LinearIntegration> {
+    conn.query_row(
+        "SELECT project_id, token, team, sync_on_complete, created_at, updated_at
+         FROM linear_integrations WHERE project_id = ?1",
+        params![project_id],
+        |row| {
+            let token: Option<String> = row.get(1)?;
+            Ok(LinearIntegration {
+                project_id: row.get(0)?,
+                has_token: token.is_some(),
+                token,
+                team: row.get(2)?,
+                sync_on_complete: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        },
+    )
+    .ok()
+}
+
+/// Create or replace a project's Linear integration.
+pub fn set_linear_integration(
+    conn: &Connection,
+    project_id: &str,
+    input: &SetLinearIntegration,
+) -> LinearIntegration {
+    let now = now();
+    conn.execute(
+        "INSERT INTO linear_integrations
+            (project_id, token, team, sync_on_complete, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+         ON CONFLICT(project_id) DO UPDATE SET
+            token = ?2, team = ?3, sync_on_complete = ?4, updated_at = ?5",
+        params![
+            project_id,
+            input.token.as_deref().filter(|t| !t.is_empty()),
+            input.team.as_deref().filter(|t| !t.is_empty()),
+            input.sync_on_complete.unwrap_or(true),
+            now
+        ],
+    )
+    .unwrap();
+    get_linear_integration(conn, project_id).unwrap()
+}
+
+pub fn delete_linear_integration(conn: &Connection, project_id: &str) -> bool {
+    conn.execute(
+        "DELETE FROM linear_integrations WHERE project_id = ?1",
+        params![project_id],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
+// ===== Phone push targets =====
+
+/// The push target of an agent or user (`subject_type` is "agent" or "user").
+pub fn get_push_target(
+    conn: &Connection,
+    subject_type: &str,
+    subject_id: &str,
+) -> Option<PushTarget> {
+    conn.query_row(
+        "SELECT provider, target, token FROM push_targets WHERE subject_type = ?1 AND subject_id = ?2",
+        params![subject_type, subject_id],
+        |row| {
+            Ok(PushTarget {
+                provider: row.get(0)?,
+                target: row.get(1)?,
+                token: row.get(2)?,
+            })
+        },
+    )
+    .ok()
+}
+
+pub fn set_push_target(
+    conn: &Connection,
+    subject_type: &str,
+    subject_id: &str,
+    input: &PushTarget,
+) -> PushTarget {
+    conn.execute(
+        "INSERT INTO push_targets (subject_type, subject_id, provider, target, token, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(subject_type, subject_id) DO UPDATE SET provider = ?3, target = ?4, token = ?5",
+        params![
+            subject_type,
+            subject_id,
+            input.provider,
+            input.target,
+            input.token,
+            now()
+        ],
+    )
+    .unwrap();
+    get_push_target(conn, subject_type, subject_id).unwrap()
+}
+
+pub fn delete_push_target(conn: &Connection, subject_type: &str, subject_id: &str) -> bool {
+    conn.execute(
+        "DELETE FROM push_targets WHERE subject_type = ?1 AND subject_id = ?2",
+        params![subject_type, subject_id],
+    )
+    .unwrap_or(0)
+        > 0
+}
+
 // ===== Agent Inbox =====
 
 pub fn get_agent_inbox(conn: &Connection, _tenant: Option<&str>, agent_id: &str) -> AgentInbox {
     // 1. Fetch agent for capacity info
     let agent = get_agent(conn, agent_id);
     let max_concurrent = agent.as_ref().map(|a| a.max_concurrent_tasks).unwrap_or(1);
+    let (tasks_today, tasks_this_week) = agent_quota_usage(conn, agent_id);
+    let over_quota = agent
+        .as_ref()
+        .is_some_and(|a| quota_exhausted(a, tasks_today, tasks_this_week));
 
     // 2. Assigned tasks (actionable statuses)
     let sql = format!(
@@ -4178,10 +8005,10 @@ pub fn get_agent_inbox(conn: &Connection, _tenant: Option<&str>, agent_id: &str)
             item_type: "question".to_string(),
             title: q.question.clone(),
             status: Some(q.status.clone()),
-            priority: None,
+            priority: Some(q.priority.clone()),
             action: "resolve_question".to_string(),
             action_hint: "Answer this question by calling resolve_question.".to_string(),
-            project_id: None,
+            project_id: Some(q.project_id.clone()),
             tags: Vec::new(),
             updated_at: None,
             metadata: Some(serde_json::json!({
@@ -4215,7 +8042,11 @@ pub fn get_agent_inbox(conn: &Connection, _tenant: Option<&str>, agent_id: &str)
     let capacity = InboxCapacity {
         max_concurrent_tasks: max_concurrent,
         current_active_tasks: active_count,
-        has_capacity: active_count < max_concurrent,
+        has_capacity: active_count < max_concurrent && !over_quota,
+        daily_task_quota: agent.as_ref().and_then(|a| a.daily_task_quota),
+        tasks_today,
+        weekly_task_quota: agent.as_ref().and_then(|a| a.weekly_task_quota),
+        tasks_this_week,
     };
 
     // Build summary
@@ -4251,7 +8082,7 @@ pub fn get_agent_inbox(conn: &Connection, _tenant: Option<&str>, agent_id: &str)
         if !notification_items.is_empty() {
             parts.push(format!("{} unread notifications", notification_items.len()));
         }
-        let capacity_note = if capacity.has_capacity {
+        let mut capacity_note = if active_count < max_concurrent {
             format!("Capacity: {}/{} slots used.", active_count, max_concurrent)
         } else {
             format!(
@@ -4259,6 +8090,12 @@ pub fn get_agent_inbox(conn: &Connection, _tenant: Option<&str>, agent_id: &str)
                 active_count, max_concurrent
             )
         };
+        if let Some(q) = capacity.daily_task_quota {
+            capacity_note.push_str(&format!(" Daily quota: {}/{}.", tasks_today, q));
+        }
+        if let Some(q) = capacity.weekly_task_quota {
+            capacity_note.push_str(&format!(" Weekly quota: {}/{}.", tasks_this_week, q));
+        }
         format!("{}. {}", parts.join(", "), capacity_note)
     };
 
@@ -4272,6 +8109,7 @@ pub fn get_agent_inbox(conn: &Connection, _tenant: Option<&str>, agent_id: &str)
         open_questions: question_items,
         unread_notifications: notification_items,
         capacity,
+        heartbeat_status: agent.and_then(|a| a.heartbeat_status),
     }
 }
 
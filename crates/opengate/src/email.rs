@@ -0,0 +1,226 @@
+//! Inbound email for the `email_task` trigger action: a mail provider's inbound webhook
+//! (Postmark's JSON, or the flat `from` / `to` / `subject` / `text` shape most others can
+//! be configured to send) becomes a task. The subject is the title, the text body the
+//! description, the sender is kept in `context.email`, and attachments become artifacts.
+
+use serde_json::Value;
+
+/// Inline attachments larger than this (in base64) are listed in the result, not stored.
+pub const MAX_INLINE_ATTACHMENT_BYTES: usize = 512 * 1024;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct InboundEmail {
+    /// Sender address, lowercased
+    pub from: String,
+    pub from_name: Option<String>,
+    /// Recipient addresses, lowercased
+    pub to: Vec<String>,
+    pub subject: String,
+    pub text: Option<String>,
+    pub message_id: Option<String>,
+    pub attachments: Vec<EmailAttachment>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct EmailAttachment {
+    pub name: String,
+    pub content_type: String,
+    /// Where the provider stored it
+    pub url: Option<String>,
+    /// Base64 body, when sent inline
+    pub content: Option<String>,
+}
+
+impl EmailAttachment {
+    /// The artifact this attachment is stored as: a `url`, or a `file` holding a
+    /// `data:` URI. `None` when there is neither, or the inline body is too large.
+    pub fn artifact(&self) -> Option<(&'static str, String)> {
+        if let Some(ref url) = self.url {
+            return Some(("url", url.clone()));
+        }
+        let content = self
+            .content
+            .as_deref()
+            .filter(|c| c.len() <= MAX_INLINE_ATTACHMENT_BYTES)?;
+        Some((
+            "file",
+            format!("data:{};base64,{}", self.content_type, content),
+        ))
+    }
+}
+
+/// `Name <user@example.com>` or `user@example.com` as (address, name).
+pub fn parse_address(value: &str) -> (String, Option<String>) {
+    let value = value.trim();
+    match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => {
+            let name = value[..start].trim().trim_matches('"').trim();
+            (
+                value[start + 1..end].trim().to_lowercase(),
+                Some(name.to_string()).filter(|n| !n.is_empty()),
+            )
+        }
+        _ => (value.to_lowercase(), None),
+    }
+}
+
+/// Comma-separated addresses, an array of them, or Postmark's `[{ "Email": ... }]`.
+fn addresses(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => s
+            .split(',')
+            .map(|a| parse_address(a).0)
+            .filter(|a| !a.is_empty())
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .flat_map(|item| match item["Email"].as_str() {
+                Some(email) => vec![email.trim().to_lowercase()],
+                None => addresses(item),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The email in an inbound webhook body, or `None` if it has no sender.
+pub fn parse(payload: &Value) -> Option<InboundEmail> {
+    let text = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| payload[*k].as_str())
+            .map(str::to_string)
+            .filter(|v| !v.trim().is_empty())
+    };
+    let (from, mut from_name) = match payload["FromFull"]["Email"].as_str() {
+        Some(email) => (email.trim().to_lowercase(), None),
+        None => parse_address(&text(&["From", "from", "sender"])?),
+    };
+    if from.is_empty() {
+        return None;
+    }
+    if let Some(name) = text(&["FromName"]).or_else(|| {
+        payload["FromFull"]["Name"]
+            .as_str()
+            .filter(|n| !n.is_empty())
+            .map(str::to_string)
+    }) {
+        from_name = Some(name);
+    }
+    let mut to = addresses(&payload["ToFull"]);
+    if to.is_empty() {
+        to = addresses(&payload["To"]);
+    }
+    if to.is_empty() {
+        to = addresses(&payload["to"]);
+    }
+    // The address the provider received it at, when forwarded or BCC'd
+    if let Some(original) = text(&["OriginalRecipient", "recipient"]) {
+        let original = parse_address(&original).0;
+        if !to.contains(&original) {
+            to.push(original);
+        }
+    }
+
+    let attachments = [&payload["Attachments"], &payload["attachments"]]
+        .iter()
+        .filter_map(|v| v.as_array())
+        .flatten()
+        .map(|a| {
+            let field =
+                |keys: &[&str]| keys.iter().find_map(|k| a[*k].as_str()).map(str::to_string);
+            EmailAttachment {
+                name: field(&["Name", "filename", "name"])
+                    .unwrap_or_else(|| "Attachment".to_string()),
+                content_type: field(&["ContentType", "content_type", "content-type"])
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                url: field(&["url", "URL"]),
+                content: field(&["Content", "content"]),
+            }
+        })
+        .collect();
+
+    Some(InboundEmail {
+        from,
+        from_name,
+        to,
+        subject: text(&["Subject", "subject"]).unwrap_or_else(|| "(no subject)".to_string()),
+        text: text(&[
+            "TextBody",
+            "text",
+            "body-plain",
+            "StrippedTextReply",
+            "HtmlBody",
+            "html",
+        ])
+        .map(|t| t.trim().to_string()),
+        message_id: text(&["MessageID", "message_id", "Message-Id"]),
+        attachments,
+    })
+}
+
+/// Whether `sender` matches an entry of `allowed`: an address, or `@domain` for a whole
+/// domain. An empty list allows everyone.
+pub fn sender_allowed(sender: &str, allowed: &[String]) -> bool {
+    allowed.is_empty()
+        || allowed.iter().any(|entry| {
+            let entry = entry.trim().to_lowercase();
+            if entry.starts_with('@') {
+                sender.ends_with(&entry)
+            } else {
+                sender == entry
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_postmark_and_flat_payloads() {
+        let postmark = serde_json::json!({
+            "FromFull": {"Email": "Ada@Example.com", "Name": "Ada"},
+            "ToFull": [{"Email": "tasks@gate.example.com"}],
+            "OriginalRecipient": "ops+tasks@gate.example.com",
+            "Subject": "Disk full on db-2",
+            "TextBody": "  See attached.\n",
+            "MessageID": "abc-123",
+            "Attachments": [{"Name": "df.txt", "ContentType": "text/plain", "Content": "L2Rldg=="}],
+        });
+        let email = parse(&postmark).unwrap();
+        assert_eq!(email.from, "ada@example.com");
+        assert_eq!(email.from_name.as_deref(), Some("Ada"));
+        assert_eq!(
+            email.to,
+            vec!["tasks@gate.example.com", "ops+tasks@gate.example.com"]
+        );
+        assert_eq!(email.text.as_deref(), Some("See attached."));
+        assert_eq!(
+            email.attachments[0].artifact(),
+            Some(("file", "data:text/plain;base64,L2Rldg==".to_string()))
+        );
+
+        let flat = serde_json::json!({
+            "from": "\"Grace H\" <grace@navy.mil>",
+            "to": "a@x.com, Tasks <tasks@x.com>",
+            "attachments": [{"filename": "log", "url": "https://files.example.com/1"}],
+        });
+        let email = parse(&flat).unwrap();
+        assert_eq!(email.from, "grace@navy.mil");
+        assert_eq!(email.from_name.as_deref(), Some("Grace H"));
+        assert_eq!(email.to, vec!["a@x.com", "tasks@x.com"]);
+        assert_eq!(email.subject, "(no subject)");
+        assert_eq!(email.attachments[0].artifact().unwrap().0, "url");
+
+        assert!(parse(&serde_json::json!({"subject": "no sender"})).is_none());
+    }
+
+    #[test]
+    fn matches_allowed_senders() {
+        let allowed = vec!["@example.com".to_string(), "ops@partner.io".to_string()];
+        assert!(sender_allowed("ada@example.com", &allowed));
+        assert!(sender_allowed("ops@partner.io", &allowed));
+        assert!(!sender_allowed("eve@partner.io", &allowed));
+        assert!(sender_allowed("anyone@anywhere", &[]));
+    }
+}
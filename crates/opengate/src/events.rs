@@ -1,35 +1,257 @@
+//! The in-process event bus.
+//!
+//! In-process sinks (the broker and Kafka forwarders) read a shared broadcast ring with
+//! [`EventBus::subscribe`]. Client connections (WS and SSE) get a bounded queue of their
+//! own with [`EventBus::subscribe_bounded`], so one stuck client can't hold events for
+//! everyone else: once its queue is full the configured [`SlowConsumerPolicy`] either
+//! drops the oldest events (and tells the client how many with a [`Delivery::Gap`]) or
+//! disconnects it. [`EventBus::stats`] reports queue depths and drops.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Notify};
+
+pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
     pub event_type: String,
     pub project_id: Option<String>,
     pub agent_id: Option<String>,
+    /// Id of the matching row in the `events` table, for events that are persisted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<i64>,
     pub data: serde_json::Value,
     pub timestamp: DateTime<Utc>,
 }
 
+/// What happens to a client whose queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SlowConsumerPolicy {
+    /// Drop the oldest queued event and report the gap
+    DropOldest,
+    /// Close the subscription; the client reconnects and resumes from its cursor
+    Disconnect,
+}
+
+impl SlowConsumerPolicy {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "drop-oldest" => Some(SlowConsumerPolicy::DropOldest),
+            "disconnect" => Some(SlowConsumerPolicy::Disconnect),
+            _ => None,
+        }
+    }
+}
+
+static QUEUE_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_QUEUE_CAPACITY);
+static DISCONNECT_SLOW: AtomicBool = AtomicBool::new(false);
+
+/// Set the queue size and slow-consumer policy for client subscriptions opened from now on.
+pub fn set_subscriber_limits(capacity: usize, policy: SlowConsumerPolicy) {
+    QUEUE_CAPACITY.store(capacity.max(1), Ordering::Relaxed);
+    DISCONNECT_SLOW.store(policy == SlowConsumerPolicy::Disconnect, Ordering::Relaxed);
+}
+
+fn subscriber_limits() -> (usize, SlowConsumerPolicy) {
+    let policy = if DISCONNECT_SLOW.load(Ordering::Relaxed) {
+        SlowConsumerPolicy::Disconnect
+    } else {
+        SlowConsumerPolicy::DropOldest
+    };
+    (QUEUE_CAPACITY.load(Ordering::Relaxed), policy)
+}
+
+/// What a bounded subscriber receives next.
+#[derive(Debug)]
+pub enum Delivery {
+    Event(Event),
+    /// This many events were dropped since the last delivery
+    Gap(u64),
+    /// The subscriber fell too far behind under [`SlowConsumerPolicy::Disconnect`]
+    Disconnected,
+}
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<Event>,
+    /// Dropped and not yet reported
+    gap: u64,
+    closed: bool,
+}
+
+struct Queue {
+    label: String,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+    state: Mutex<QueueState>,
+    ready: Notify,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+struct Registry {
+    queues: Mutex<HashMap<u64, Arc<Queue>>>,
+    next_id: AtomicU64,
+    dropped_total: AtomicU64,
+    disconnected_total: AtomicU64,
+}
+
+/// Queue depth and drops for one client subscription.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriberStats {
+    pub id: u64,
+    pub label: String,
+    pub depth: usize,
+    pub capacity: usize,
+    pub policy: SlowConsumerPolicy,
+    pub dropped: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BusStats {
+    pub subscribers: Vec<SubscriberStats>,
+    pub max_depth: usize,
+    /// Events dropped from full queues since startup
+    pub dropped_total: u64,
+    /// Subscribers disconnected for being slow since startup
+    pub disconnected_total: u64,
+}
+
 #[derive(Clone)]
 pub struct EventBus {
     sender: broadcast::Sender<Event>,
+    registry: Arc<Registry>,
 }
 
 impl EventBus {
     pub fn new(capacity: usize) -> Self {
         let (sender, _) = broadcast::channel(capacity);
-        Self { sender }
+        Self {
+            sender,
+            registry: Arc::default(),
+        }
     }
 
     pub fn emit(&self, event: Event) {
+        let queues: Vec<(u64, Arc<Queue>)> = self
+            .registry
+            .queues
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, q)| (*id, q.clone()))
+            .collect();
+        for (id, queue) in queues {
+            if !self.enqueue(&queue, event.clone()) {
+                self.registry.queues.lock().unwrap().remove(&id);
+            }
+        }
         // Ignore send errors (no active receivers)
         let _ = self.sender.send(event);
     }
 
+    /// Queue `event` for one subscriber; false once the subscriber is disconnected.
+    fn enqueue(&self, queue: &Queue, event: Event) -> bool {
+        let mut state = queue.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+        if state.events.len() >= queue.capacity {
+            match queue.policy {
+                SlowConsumerPolicy::DropOldest => {
+                    state.events.pop_front();
+                    state.gap += 1;
+                    queue.dropped.fetch_add(1, Ordering::Relaxed);
+                    self.registry.dropped_total.fetch_add(1, Ordering::Relaxed);
+                }
+                SlowConsumerPolicy::Disconnect => {
+                    state.events.clear();
+                    state.closed = true;
+                    drop(state);
+                    self.registry
+                        .disconnected_total
+                        .fetch_add(1, Ordering::Relaxed);
+                    queue.ready.notify_one();
+                    return false;
+                }
+            }
+        }
+        state.events.push_back(event);
+        drop(state);
+        queue.ready.notify_one();
+        true
+    }
+
+    /// The shared broadcast ring, for in-process consumers.
     pub fn subscribe(&self) -> broadcast::Receiver<Event> {
         self.sender.subscribe()
     }
+
+    /// A bounded queue of events for one client, with the configured capacity and policy.
+    /// `label` identifies it in [`EventBus::stats`].
+    pub fn subscribe_bounded(&self, label: &str) -> Subscriber {
+        let (capacity, policy) = subscriber_limits();
+        self.subscribe_with(label, capacity, policy)
+    }
+
+    pub fn subscribe_with(
+        &self,
+        label: &str,
+        capacity: usize,
+        policy: SlowConsumerPolicy,
+    ) -> Subscriber {
+        let queue = Arc::new(Queue {
+            label: label.to_string(),
+            capacity: capacity.max(1),
+            policy,
+            state: Mutex::default(),
+            ready: Notify::new(),
+            dropped: AtomicU64::new(0),
+        });
+        let id = self.registry.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.registry
+            .queues
+            .lock()
+            .unwrap()
+            .insert(id, queue.clone());
+        Subscriber {
+            id,
+            queue,
+            registry: self.registry.clone(),
+        }
+    }
+
+    pub fn stats(&self) -> BusStats {
+        let mut subscribers: Vec<SubscriberStats> = self
+            .registry
+            .queues
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, q)| SubscriberStats {
+                id: *id,
+                label: q.label.clone(),
+                depth: q.state.lock().unwrap().events.len(),
+                capacity: q.capacity,
+                policy: q.policy,
+                dropped: q.dropped.load(Ordering::Relaxed),
+            })
+            .collect();
+        subscribers.sort_by_key(|s| s.id);
+        BusStats {
+            max_depth: subscribers.iter().map(|s| s.depth).max().unwrap_or(0),
+            subscribers,
+            dropped_total: self.registry.dropped_total.load(Ordering::Relaxed),
+            disconnected_total: self.registry.disconnected_total.load(Ordering::Relaxed),
+        }
+    }
 }
 
 impl Default for EventBus {
@@ -38,6 +260,41 @@ impl Default for EventBus {
     }
 }
 
+/// A client's bounded event queue; unregisters itself when dropped.
+pub struct Subscriber {
+    id: u64,
+    queue: Arc<Queue>,
+    registry: Arc<Registry>,
+}
+
+impl Subscriber {
+    /// The next delivery: a `Gap` first if events were dropped, then the oldest event.
+    pub async fn recv(&mut self) -> Delivery {
+        loop {
+            let ready = self.queue.ready.notified();
+            {
+                let mut state = self.queue.state.lock().unwrap();
+                if state.gap > 0 {
+                    return Delivery::Gap(std::mem::take(&mut state.gap));
+                }
+                if let Some(event) = state.events.pop_front() {
+                    return Delivery::Event(event);
+                }
+                if state.closed {
+                    return Delivery::Disconnected;
+                }
+            }
+            ready.await;
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.registry.queues.lock().unwrap().remove(&self.id);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,6 +308,7 @@ mod tests {
             event_type: "task.created".to_string(),
             project_id: Some("proj-1".to_string()),
             agent_id: None,
+            event_id: None,
             data: serde_json::json!({"task_id": "t-1"}),
             timestamp: Utc::now(),
         };
@@ -73,6 +331,7 @@ mod tests {
             event_type: "task.updated".to_string(),
             project_id: None,
             agent_id: Some("agent-1".to_string()),
+            event_id: None,
             data: serde_json::json!({}),
             timestamp: Utc::now(),
         };
@@ -92,8 +351,59 @@ mod tests {
             event_type: "test".to_string(),
             project_id: None,
             agent_id: None,
+            event_id: None,
             data: serde_json::Value::Null,
             timestamp: Utc::now(),
         });
     }
+
+    fn event(n: i64) -> Event {
+        Event {
+            event_type: "task.updated".to_string(),
+            project_id: None,
+            agent_id: None,
+            event_id: Some(n),
+            data: serde_json::Value::Null,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_oldest_and_reports_gap() {
+        let bus = EventBus::default();
+        let mut sub = bus.subscribe_with("ws:a1", 2, SlowConsumerPolicy::DropOldest);
+        for n in 1..=5 {
+            bus.emit(event(n));
+        }
+        let stats = bus.stats();
+        assert_eq!(stats.subscribers[0].depth, 2);
+        assert_eq!(stats.dropped_total, 3);
+
+        assert!(matches!(sub.recv().await, Delivery::Gap(3)));
+        for n in 4..=5 {
+            match sub.recv().await {
+                Delivery::Event(e) => assert_eq!(e.event_id, Some(n)),
+                other => panic!("expected event {}, got {:?}", n, other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn full_queue_disconnects_slow_consumer() {
+        let bus = EventBus::default();
+        let mut slow = bus.subscribe_with("ws:slow", 1, SlowConsumerPolicy::Disconnect);
+        let mut fast = bus.subscribe_with("ws:fast", 8, SlowConsumerPolicy::Disconnect);
+        bus.emit(event(1));
+        bus.emit(event(2));
+
+        assert!(matches!(slow.recv().await, Delivery::Disconnected));
+        assert!(matches!(fast.recv().await, Delivery::Event(_)));
+        let stats = bus.stats();
+        assert_eq!(stats.disconnected_total, 1);
+        assert_eq!(stats.subscribers.len(), 1);
+        assert_eq!(stats.subscribers[0].label, "ws:fast");
+
+        drop(fast);
+        assert!(bus.stats().subscribers.is_empty());
+    }
 }
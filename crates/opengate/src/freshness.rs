@@ -0,0 +1,66 @@
+//! Knowledge freshness: an entry may carry a `review_after` deadline, after which it is
+//! `stale` until someone reviews it (sets a new deadline or clears it). Deadlines are
+//! stored as second-precision UTC (`2026-03-01T00:00:00Z`) so they compare as strings.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Normalize a `review_after` value: an RFC 3339 timestamp, or `YYYY-MM-DD` (midnight
+/// UTC). An empty string means "no deadline" and yields `None`.
+pub fn normalize_review_after(value: &str) -> Result<Option<String>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(format_stamp(at.with_timezone(&Utc))));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(Some(format_stamp(
+            date.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+        )));
+    }
+    Err(format!(
+        "Invalid review_after '{}': expected an RFC 3339 timestamp or YYYY-MM-DD",
+        value
+    ))
+}
+
+/// The current time in the stored `review_after` format.
+pub fn now_stamp() -> String {
+    format_stamp(Utc::now())
+}
+
+/// Whether an entry with this deadline is due for review.
+pub fn is_stale(review_after: Option<&str>) -> bool {
+    review_after.is_some_and(|at| at <= now_stamp().as_str())
+}
+
+fn format_stamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_timestamps_and_dates_to_utc() {
+        assert_eq!(
+            normalize_review_after("2026-03-01T09:30:00+02:00").unwrap(),
+            Some("2026-03-01T07:30:00Z".to_string())
+        );
+        assert_eq!(
+            normalize_review_after("2026-03-01").unwrap(),
+            Some("2026-03-01T00:00:00Z".to_string())
+        );
+        assert_eq!(normalize_review_after("  ").unwrap(), None);
+        assert!(normalize_review_after("next tuesday").is_err());
+    }
+
+    #[test]
+    fn stale_once_the_deadline_passes() {
+        assert!(is_stale(Some("2000-01-01T00:00:00Z")));
+        assert!(!is_stale(Some("2999-01-01T00:00:00Z")));
+        assert!(!is_stale(None));
+    }
+}
@@ -0,0 +1,232 @@
+//! GitHub integration: links the pull requests agents open to their tasks.
+//!
+//! When an agent completes a task that has a repo (a `repo_url` in its context or on its
+//! project) with an `output.pr_url`, the pull request is attached to the task as a `url`
+//! artifact. With a token set by `PUT /api/projects/:id/integrations/github`, the
+//! completion is also commented on the pull request. Inbound, a `github_pull_request`
+//! trigger moves the task a newly opened pull request belongs to into `review`.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::storage::StorageBackend;
+use opengate_models::*;
+
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Name of the artifacts pull requests are attached as.
+pub const ARTIFACT_NAME: &str = "Pull request";
+
+static API_URL: OnceLock<String> = OnceLock::new();
+
+/// Set the GitHub REST API base URL. Only the first call takes effect.
+pub fn set_api_url(url: &str) {
+    let _ = API_URL.set(url.trim_end_matches('/').to_string());
+}
+
+pub fn api_url() -> &'static str {
+    API_URL.get().map(String::as_str).unwrap_or(DEFAULT_API_URL)
+}
+
+/// A pull request as named by its web URL.
+#[derive(Debug, PartialEq)]
+pub struct PullRequest {
+    pub owner: String,
+    pub repo: String,
+    pub number: u64,
+}
+
+/// `https://github.com/<owner>/<repo>/pull/<number>`, optionally followed by a tab
+/// (`/files`), query or fragment. Enterprise hosts are accepted too.
+pub fn parse_pr_url(url: &str) -> Option<PullRequest> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let path = rest.split(['?', '#']).next()?;
+    let mut parts = path.split('/').skip(1);
+    let (owner, repo, kind, number) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    if owner.is_empty() || repo.is_empty() || kind != "pull" {
+        return None;
+    }
+    Some(PullRequest {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        number: number.parse().ok()?,
+    })
+}
+
+/// The repository a task works in: its context's `repo_url` (or `repo`), else its
+/// project's.
+pub fn task_repo(
+    storage: &dyn StorageBackend,
+    tenant: Option<&str>,
+    task: &Task,
+) -> Option<String> {
+    task.context
+        .as_ref()
+        .and_then(|c| c.get("repo_url").or_else(|| c.get("repo")))
+        .and_then(|v| v.as_str())
+        .filter(|r| !r.is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            storage
+                .get_project(tenant, &task.project_id)
+                .and_then(|p| p.repo_url)
+        })
+}
+
+/// Attach `url` to the task as a `url` artifact called `name` unless it already is.
+/// Whether it was attached now.
+pub fn attach_url(
+    storage: &dyn StorageBackend,
+    tenant: Option<&str>,
+    task_id: &str,
+    name: &str,
+    url: &str,
+    author_type: &str,
+    author_id: &str,
+) -> bool {
+    if storage
+        .list_artifacts(tenant, task_id)
+        .iter()
+        .any(|a| a.artifact_type == "url" && a.value == url)
+    {
+        return false;
+    }
+    storage.create_artifact(
+        tenant,
+        task_id,
+        &CreateArtifact {
+            name: name.to_string(),
+            artifact_type: "url".to_string(),
+            value: url.to_string(),
+        },
+        author_type,
+        author_id,
+    );
+    true
+}
+
+/// After `identity` completed `task`: attach the pull request in its output and comment
+/// on it when the project's integration has a token. The comment is posted in the
+/// background.
+pub fn link_completed_pr(
+    storage: Arc<dyn StorageBackend>,
+    identity: &Identity,
+    task: &Task,
+    summary: Option<&str>,
+) {
+    let tenant = identity.tenant_id();
+    let Some(pr_url) = task
+        .output
+        .as_ref()
+        .and_then(|o| o.get("pr_url"))
+        .and_then(|v| v.as_str())
+    else {
+        return;
+    };
+    let Some(pr) = parse_pr_url(pr_url) else {
+        return;
+    };
+    if task_repo(&*storage, tenant, task).is_none() {
+        return;
+    }
+    attach_url(
+        &*storage,
+        tenant,
+        &task.id,
+        ARTIFACT_NAME,
+        pr_url,
+        identity.author_type(),
+        identity.author_id(),
+    );
+
+    let Some(token) = storage
+        .get_github_integration(tenant, &task.project_id)
+        .filter(|g| g.comment_on_complete)
+        .and_then(|g| g.token)
+    else {
+        return;
+    };
+    let body = comment_body(&task.title, identity.display_name(), summary);
+    let task_id = task.id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = post_comment(&reqwest::Client::new(), &token, &pr, &body).await {
+            eprintln!(
+                "[github] comment on {}/{}#{} for task {} not posted: {}",
+                pr.owner, pr.repo, pr.number, task_id, e
+            );
+        }
+    });
+}
+
+/// The pull request comment for a completed task. GitLab merge requests get the same.
+pub fn comment_body(title: &str, actor: &str, summary: Option<&str>) -> String {
+    let mut body = format!("**{}** was completed by {} in OpenGate.", title, actor);
+    if let Some(summary) = summary.filter(|s| !s.trim().is_empty()) {
+        body.push_str("\n\n");
+        body.push_str(summary);
+    }
+    body
+}
+
+async fn post_comment(
+    client: &reqwest::Client,
+    token: &str,
+    pr: &PullRequest,
+    body: &str,
+) -> Result<(), String> {
+    let resp = client
+        .post(format!(
+            "{}/repos/{}/{}/issues/{}/comments",
+            api_url(),
+            pr.owner,
+            pr.repo,
+            pr.number
+        ))
+        .bearer_auth(token)
+        .header("accept", "application/vnd.github+json")
+        .header("user-agent", "opengate")
+        .json(&serde_json::json!({ "body": body }))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status().as_u16()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pull_request_urls() {
+        assert_eq!(
+            parse_pr_url("https://github.com/acme/widgets/pull/42/files?w=1"),
+            Some(PullRequest {
+                owner: "acme".to_string(),
+                repo: "widgets".to_string(),
+                number: 42,
+            })
+        );
+        assert!(parse_pr_url("https://github.enterprise.io/acme/widgets/pull/7").is_some());
+        assert!(parse_pr_url("https://github.com/acme/widgets/issues/42").is_none());
+        assert!(parse_pr_url("https://github.com/acme/widgets/pull/latest").is_none());
+        assert!(parse_pr_url("git@github.com:acme/widgets.git").is_none());
+    }
+
+    #[test]
+    fn formats_completion_comments() {
+        assert_eq!(
+            comment_body("Fix login", "builder", Some("Added a retry")),
+            "**Fix login** was completed by builder in OpenGate.\n\nAdded a retry"
+        );
+        assert_eq!(
+            comment_body("Fix login", "builder", Some("  ")),
+            "**Fix login** was completed by builder in OpenGate."
+        );
+    }
+}
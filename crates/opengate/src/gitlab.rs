@@ -0,0 +1,238 @@
+//! GitLab integration, the per-project alternative to [`crate::github`].
+//!
+//! When an agent completes a task that has a repo with an `output.mr_url` (or a GitLab
+//! `pr_url`), the merge request is attached to the task as a `url` artifact. With a token
+//! set by `PUT /api/projects/:id/integrations/gitlab`, the completion is also noted on the
+//! merge request, and the project's open issues can be imported as tasks. Inbound, a
+//! `gitlab_merge_request` trigger moves the task a newly opened merge request belongs to
+//! into `review`.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::github;
+use crate::storage::StorageBackend;
+use opengate_models::*;
+
+pub const DEFAULT_API_URL: &str = "https://gitlab.com/api/v4";
+
+/// Name of the artifacts merge requests are attached as.
+pub const ARTIFACT_NAME: &str = "Merge request";
+
+/// Pages of 100 issues fetched by one import.
+const MAX_IMPORT_PAGES: u32 = 10;
+
+static API_URL: OnceLock<String> = OnceLock::new();
+
+/// Set the GitLab REST API base URL. Only the first call takes effect.
+pub fn set_api_url(url: &str) {
+    let _ = API_URL.set(url.trim_end_matches('/').to_string());
+}
+
+pub fn api_url() -> &'static str {
+    API_URL.get().map(String::as_str).unwrap_or(DEFAULT_API_URL)
+}
+
+/// A merge request as named by its web URL.
+#[derive(Debug, PartialEq)]
+pub struct MergeRequest {
+    /// Project path, e.g. `group/subgroup/repo`
+    pub project: String,
+    pub iid: u64,
+}
+
+/// `https://gitlab.com/<project path>/-/merge_requests/<iid>`, optionally followed by a
+/// tab (`/diffs`), query or fragment. Self-managed hosts are accepted too.
+pub fn parse_mr_url(url: &str) -> Option<MergeRequest> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let path = rest.split(['?', '#']).next()?;
+    let (_host, path) = path.split_once('/')?;
+    let (project, rest) = path.split_once("/-/merge_requests/")?;
+    if project.is_empty() {
+        return None;
+    }
+    Some(MergeRequest {
+        project: project.to_string(),
+        iid: rest.split('/').next()?.parse().ok()?,
+    })
+}
+
+/// A project path or id as the API takes it in a URL path segment.
+fn encode_project(project: &str) -> String {
+    project.replace('/', "%2F")
+}
+
+/// The `context.external_ref` of the task imported from issue `iid` of `project`.
+pub fn external_ref(project: &str, iid: u64) -> String {
+    format!("gitlab:{}#{}", project, iid)
+}
+
+/// After `identity` completed `task`: attach the merge request in its output and note
+/// the completion on it when the project's integration has a token. The note is posted
+/// in the background.
+pub fn link_completed_mr(
+    storage: Arc<dyn StorageBackend>,
+    identity: &Identity,
+    task: &Task,
+    summary: Option<&str>,
+) {
+    let tenant = identity.tenant_id();
+    let Some((mr_url, mr)) = ["mr_url", "pr_url"].iter().find_map(|key| {
+        let url = task.output.as_ref()?.get(*key)?.as_str()?;
+        Some((url, parse_mr_url(url)?))
+    }) else {
+        return;
+    };
+    if github::task_repo(&*storage, tenant, task).is_none() {
+        return;
+    }
+    github::attach_url(
+        &*storage,
+        tenant,
+        &task.id,
+        ARTIFACT_NAME,
+        mr_url,
+        identity.author_type(),
+        identity.author_id(),
+    );
+
+    let Some(token) = storage
+        .get_gitlab_integration(tenant, &task.project_id)
+        .filter(|g| g.comment_on_complete)
+        .and_then(|g| g.token)
+    else {
+        return;
+    };
+    let body = github::comment_body(&task.title, identity.display_name(), summary);
+    let task_id = task.id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = post_note(&reqwest::Client::new(), &token, &mr, &body).await {
+            eprintln!(
+                "[gitlab] note on {}!{} for task {} not posted: {}",
+                mr.project, mr.iid, task_id, e
+            );
+        }
+    });
+}
+
+async fn post_note(
+    client: &reqwest::Client,
+    token: &str,
+    mr: &MergeRequest,
+    body: &str,
+) -> Result<(), String> {
+    let resp = client
+        .post(format!(
+            "{}/projects/{}/merge_requests/{}/notes",
+            api_url(),
+            encode_project(&mr.project),
+            mr.iid
+        ))
+        .header("private-token", token)
+        .json(&serde_json::json!({ "body": body }))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status().as_u16()));
+    }
+    Ok(())
+}
+
+/// An issue as the GitLab issues API returns it.
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub iid: u64,
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    pub web_url: String,
+}
+
+/// The open issues of `project`, oldest first, up to `MAX_IMPORT_PAGES` pages.
+pub async fn fetch_open_issues(
+    client: &reqwest::Client,
+    token: &str,
+    project: &str,
+) -> Result<Vec<Issue>, String> {
+    let mut issues = Vec::new();
+    for page in 1..=MAX_IMPORT_PAGES {
+        let resp = client
+            .get(format!(
+                "{}/projects/{}/issues",
+                api_url(),
+                encode_project(project)
+            ))
+            .query(&[
+                ("state", "opened"),
+                ("sort", "asc"),
+                ("per_page", "100"),
+                ("page", &page.to_string()),
+            ])
+            .header("private-token", token)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("HTTP {}", resp.status().as_u16()));
+        }
+        let batch: Vec<Issue> = resp.json().await.map_err(|e| e.to_string())?;
+        let last = batch.len() < 100;
+        issues.extend(batch);
+        if last {
+            break;
+        }
+    }
+    Ok(issues)
+}
+
+/// The task an issue of `project` is imported as: its labels become tags.
+pub fn issue_task(project: &str, issue: &Issue) -> CreateTask {
+    CreateTask {
+        title: issue.title.clone(),
+        description: issue.description.clone().filter(|d| !d.trim().is_empty()),
+        priority: None,
+        tags: Some(issue.labels.clone()).filter(|l| !l.is_empty()),
+        context: Some(serde_json::json!({
+            "external_ref": external_ref(project, issue.iid),
+            "issue_url": issue.web_url,
+        })),
+        output: None,
+        due_date: None,
+        assignee_type: None,
+        assignee_id: None,
+        scheduled_at: None,
+        recurrence_rule: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_merge_request_urls() {
+        assert_eq!(
+            parse_mr_url("https://gitlab.com/acme/tools/widgets/-/merge_requests/42/diffs?v=1"),
+            Some(MergeRequest {
+                project: "acme/tools/widgets".to_string(),
+                iid: 42,
+            })
+        );
+        assert!(parse_mr_url("https://git.example.com/acme/widgets/-/merge_requests/7").is_some());
+        assert!(parse_mr_url("https://gitlab.com/acme/widgets/-/issues/42").is_none());
+        assert!(parse_mr_url("https://github.com/acme/widgets/pull/42").is_none());
+        assert!(parse_mr_url("https://gitlab.com/-/merge_requests/1").is_none());
+        assert_eq!(
+            encode_project("acme/tools/widgets"),
+            "acme%2Ftools%2Fwidgets"
+        );
+    }
+}
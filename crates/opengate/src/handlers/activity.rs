@@ -5,6 +5,7 @@ use axum::{
 };
 
 use crate::app::AppState;
+use crate::auth::ActingIdentity;
 use crate::handlers::{events, webhooks};
 use opengate_models::*;
 
@@ -30,7 +31,10 @@ pub async fn list_activity(
 
 pub async fn create_activity(
     State(state): State<AppState>,
-    identity: Identity,
+    ActingIdentity {
+        identity,
+        delegated_by,
+    }: ActingIdentity,
     Path(task_id): Path<String>,
     Json(mut input): Json<CreateActivity>,
 ) -> Result<(StatusCode, Json<TaskActivity>), (StatusCode, Json<serde_json::Value>)> {
@@ -71,6 +75,21 @@ pub async fn create_activity(
         input.metadata = Some(meta);
     }
 
+    // Record the orchestrator behind a delegated post
+    if let Some(ref delegator) = delegated_by {
+        let mut meta = input
+            .metadata
+            .take()
+            .unwrap_or_else(|| serde_json::json!({}));
+        if let serde_json::Value::Object(ref mut map) = meta {
+            map.insert(
+                "delegation".to_string(),
+                delegator.audit_metadata(&identity),
+            );
+        }
+        input.metadata = Some(meta);
+    }
+
     let activity = state.storage.create_activity(
         identity.tenant_id(),
         &task_id,
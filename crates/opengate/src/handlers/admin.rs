@@ -0,0 +1,304 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+
+use crate::app::AppState;
+use crate::events::BusStats;
+use crate::handlers::webhooks;
+use crate::query_stats::{self, QueryStats};
+use crate::quotas;
+use opengate_models::*;
+
+/// POST /api/admin/events/replay — re-run notification routing for a range of events
+/// or a task, e.g. after an agent was down and missed its wake-ups. Open to humans and
+/// orchestrators; any other agent may only replay its own notifications.
+pub async fn replay_events(
+    State(state): State<AppState>,
+    identity: Identity,
+    Json(input): Json<EventReplayRequest>,
+) -> Result<Json<EventReplayResult>, (StatusCode, Json<serde_json::Value>)> {
+    match &identity {
+        Identity::Human { .. } => {}
+        Identity::AgentIdentity { id, .. } => {
+            let is_orchestrator = state
+                .storage
+                .get_agent(identity.tenant_id(), id)
+                .is_some_and(|a| a.role == "orchestrator");
+            if !is_orchestrator && input.agent_id.as_deref() != Some(id.as_str()) {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({
+                        "error": "Only orchestrators can replay events for other agents; pass your own agent_id"
+                    })),
+                ));
+            }
+        }
+        Identity::Anonymous => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({"error": "Authentication required"})),
+            ))
+        }
+    }
+    if input.from_event_id.is_none() && input.to_event_id.is_none() && input.task_id.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "Give from_event_id/to_event_id or task_id"
+            })),
+        ));
+    }
+
+    let (mut result, pending) = state.storage.replay_events(identity.tenant_id(), &input);
+    if input.deliver_webhooks {
+        result.webhooks_fired = !pending.is_empty();
+        webhooks::fire_notification_webhooks(state.storage.clone(), pending);
+    }
+    Ok(Json(result))
+}
+
+/// Humans and orchestrators pass; other agents and anonymous callers are refused.
+pub(crate) fn require_operator(
+    state: &AppState,
+    identity: &Identity,
+    what: &str,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match identity {
+        Identity::Human { .. } => Ok(()),
+        Identity::AgentIdentity { id, .. } => {
+            let is_orchestrator = state
+                .storage
+                .get_agent(identity.tenant_id(), id)
+                .is_some_and(|a| a.role == "orchestrator");
+            if is_orchestrator {
+                Ok(())
+            } else {
+                Err((
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({
+                        "error": format!("Only humans and orchestrators can {}", what)
+                    })),
+                ))
+            }
+        }
+        Identity::Anonymous => Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        )),
+    }
+}
+
+/// GET /api/admin/event-bus — queue depth and drops for every connected WS/SSE client.
+/// Open to humans and orchestrators.
+pub async fn event_bus_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<Json<BusStats>, (StatusCode, Json<serde_json::Value>)> {
+    require_operator(&state, &identity, "read event bus stats")?;
+    Ok(Json(state.event_bus.stats()))
+}
+
+/// GET /api/admin/queries — p50/p95 timings per SQL statement since startup (or the
+/// last reset), and the slow-query log. Open to humans and orchestrators.
+pub async fn query_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<Json<QueryStats>, (StatusCode, Json<serde_json::Value>)> {
+    require_operator(&state, &identity, "read query stats")?;
+    Ok(Json(query_stats::snapshot()))
+}
+
+/// DELETE /api/admin/queries — start the timings and slow-query log afresh.
+pub async fn reset_query_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    require_operator(&state, &identity, "reset query stats")?;
+    query_stats::reset();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/admin/settings — the runtime settings. Readable by anyone authenticated,
+/// so agents can tell whether dependencies are enforced.
+pub async fn get_settings(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<Json<RuntimeSettings>, (StatusCode, Json<serde_json::Value>)> {
+    if matches!(identity, Identity::Anonymous) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    }
+    Ok(Json(state.storage.get_settings(identity.tenant_id())))
+}
+
+/// PATCH /api/admin/settings — change settings without a restart; they apply from the
+/// next request. Open to humans and orchestrators outside any tenant, since the
+/// settings are shared by all of them.
+pub async fn update_settings(
+    State(state): State<AppState>,
+    identity: Identity,
+    Json(input): Json<UpdateSettings>,
+) -> Result<Json<RuntimeSettings>, (StatusCode, Json<serde_json::Value>)> {
+    require_operator(&state, &identity, "change settings")?;
+    if identity.tenant_id().is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(
+                serde_json::json!({"error": "Settings apply to every tenant and can only be changed by an operator outside one"}),
+            ),
+        ));
+    }
+    let updated_by = format!("{}:{}", identity.author_type(), identity.author_id());
+    state
+        .storage
+        .update_settings(identity.tenant_id(), &input, &updated_by)
+        .map(Json)
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": e.0})),
+            )
+        })
+}
+
+/// Like [`require_operator`], and only from outside any tenant: the tenant registry is
+/// above the tenants themselves.
+pub(crate) fn require_tenant_admin(
+    state: &AppState,
+    identity: &Identity,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    require_operator(state, identity, "manage tenants")?;
+    if identity.tenant_id().is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(
+                serde_json::json!({"error": "Tenants can only be managed by an operator outside one"}),
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn tenant_not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "Tenant not found"})),
+    )
+}
+
+fn summarize(state: &AppState, tenant: Tenant) -> TenantSummary {
+    let usage = quotas::usage(&*state.storage, &tenant.id);
+    TenantSummary { tenant, usage }
+}
+
+/// GET /api/admin/tenants — every registered tenant with its resource counts.
+pub async fn list_tenants(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<Json<Vec<TenantSummary>>, (StatusCode, Json<serde_json::Value>)> {
+    require_tenant_admin(&state, &identity)?;
+    let tenants = state.storage.list_tenants();
+    Ok(Json(
+        tenants.into_iter().map(|t| summarize(&state, t)).collect(),
+    ))
+}
+
+/// POST /api/admin/tenants — register a tenant. Its id is the owner_id its data carries,
+/// so a tenant already in use by agents can be registered after the fact.
+pub async fn create_tenant(
+    State(state): State<AppState>,
+    identity: Identity,
+    Json(input): Json<CreateTenant>,
+) -> Result<(StatusCode, Json<Tenant>), (StatusCode, Json<serde_json::Value>)> {
+    require_tenant_admin(&state, &identity)?;
+    input.validate().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": e})),
+        )
+    })?;
+    match state.storage.create_tenant(&input) {
+        Ok(tenant) => Ok((StatusCode::CREATED, Json(tenant))),
+        Err(e) => Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"error": e.0})),
+        )),
+    }
+}
+
+pub async fn get_tenant(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<TenantSummary>, (StatusCode, Json<serde_json::Value>)> {
+    require_tenant_admin(&state, &identity)?;
+    let tenant = state.storage.get_tenant(&id).ok_or_else(tenant_not_found)?;
+    Ok(Json(summarize(&state, tenant)))
+}
+
+/// DELETE /api/admin/tenants/:id — delete the tenant and everything it owns.
+pub async fn delete_tenant(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    require_tenant_admin(&state, &identity)?;
+    if state.storage.delete_tenant(&id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(tenant_not_found())
+    }
+}
+
+async fn set_tenant_status(
+    state: AppState,
+    identity: Identity,
+    id: String,
+    status: &str,
+) -> Result<Json<Tenant>, (StatusCode, Json<serde_json::Value>)> {
+    require_tenant_admin(&state, &identity)?;
+    state
+        .storage
+        .set_tenant_status(&id, status)
+        .map(Json)
+        .ok_or_else(tenant_not_found)
+}
+
+/// POST /api/admin/tenants/:id/suspend — refuse the tenant's keys and setup tokens
+/// until it is resumed. Nothing is deleted.
+pub async fn suspend_tenant(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<Tenant>, (StatusCode, Json<serde_json::Value>)> {
+    set_tenant_status(state, identity, id, "suspended").await
+}
+
+pub async fn resume_tenant(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<Tenant>, (StatusCode, Json<serde_json::Value>)> {
+    set_tenant_status(state, identity, id, "active").await
+}
+
+/// POST /api/admin/tenants/:id/setup-tokens — a setup token that registers agents into
+/// this tenant only. Shown once.
+pub async fn create_tenant_setup_token(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<TenantSetupToken>), (StatusCode, Json<serde_json::Value>)> {
+    require_tenant_admin(&state, &identity)?;
+    if state.storage.get_tenant(&id).is_none() {
+        return Err(tenant_not_found());
+    }
+    Ok((
+        StatusCode::CREATED,
+        Json(state.storage.create_tenant_setup_token(&id)),
+    ))
+}
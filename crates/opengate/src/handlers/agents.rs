@@ -3,8 +3,13 @@ use axum::{
     http::StatusCode,
     Json,
 };
+use chrono::Utc;
 
 use crate::app::AppState;
+use crate::auth;
+use crate::events::Event;
+use crate::handlers::{admin, webhooks};
+use crate::quotas::{self, Quota};
 use opengate_models::*;
 
 pub async fn list_agents(
@@ -82,12 +87,53 @@ pub async fn get_agent(
     }
 }
 
+fn validate_update(input: &UpdateAgent) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    for pref in input
+        .notification_preferences
+        .iter()
+        .flat_map(|p| p.values())
+    {
+        pref.validate().map_err(|e| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({"error": e})),
+            )
+        })?;
+    }
+    if let Some(ref template) = input.webhook_template {
+        webhooks::validate_template(template).map_err(|e| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({"error": e})),
+            )
+        })?;
+    }
+    Ok(())
+}
+
 pub async fn update_agent(
     State(state): State<AppState>,
     identity: Identity,
     Path(id): Path<String>,
     Json(input): Json<UpdateAgent>,
 ) -> Result<Json<Agent>, (StatusCode, Json<serde_json::Value>)> {
+    // Roles carry operator rights (acting on behalf, admin endpoints), so no agent hands one out
+    if input.role.is_some() && !matches!(identity, Identity::Human { .. }) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({"error": "Only humans can change an agent's role"})),
+        ));
+    }
+    if input.daily_task_quota.is_some() || input.weekly_task_quota.is_some() {
+        if matches!(&identity, Identity::AgentIdentity { id: caller, .. } if *caller == id) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({"error": "Agents cannot change their own task quotas"})),
+            ));
+        }
+        admin::require_operator(&state, &identity, "set task quotas")?;
+    }
+    validate_update(&input)?;
     match state
         .storage
         .update_agent(identity.tenant_id(), &id, &input)
@@ -114,6 +160,19 @@ pub async fn update_agent_self(
             ))
         }
     };
+    if input.daily_task_quota.is_some() || input.weekly_task_quota.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({"error": "Agents cannot change their own task quotas"})),
+        ));
+    }
+    if input.role.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({"error": "Agents cannot change their own role"})),
+        ));
+    }
+    validate_update(&input)?;
     match state
         .storage
         .update_agent(identity.tenant_id(), &agent_id, &input)
@@ -131,6 +190,23 @@ pub async fn create_agent(
     identity: Identity,
     Json(input): Json<CreateAgent>,
 ) -> Result<(StatusCode, Json<AgentCreated>), (StatusCode, Json<serde_json::Value>)> {
+    if input.role.as_deref().is_some_and(|r| r != "executor") {
+        admin::require_operator(&state, &identity, "create agents with a role")?;
+    }
+    // A tenant's callers always create in their own tenant; placing an agent in another
+    // takes an operator outside all of them
+    if identity.tenant_id().is_none() && input.owner_id.is_some() {
+        admin::require_tenant_admin(&state, &identity).map_err(|_| {
+            (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({
+                    "error": "Only humans and orchestrators outside a tenant can create agents in one"
+                })),
+            )
+        })?;
+    }
+    let tenant = identity.tenant_id().or(input.owner_id.as_deref());
+    quotas::check(&*state.storage, tenant, Quota::Agents)?;
     let (agent, api_key) = state.storage.create_agent(identity.tenant_id(), &input);
     Ok((StatusCode::CREATED, Json(AgentCreated { agent, api_key })))
 }
@@ -150,9 +226,391 @@ pub async fn delete_agent(
     }
 }
 
+/// Keys and webhook deliveries may be managed by a human operator or by the agent itself.
+fn authorize_self_or_human(
+    identity: &Identity,
+    agent_id: &str,
+    what: &str,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match identity {
+        Identity::Human { .. } => Ok(()),
+        Identity::AgentIdentity { id, .. } if id == agent_id => Ok(()),
+        Identity::AgentIdentity { .. } => Err((
+            StatusCode::FORBIDDEN,
+            Json(
+                serde_json::json!({"error": format!("Agents can only manage their own {}", what)}),
+            ),
+        )),
+        Identity::Anonymous => Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        )),
+    }
+}
+
+/// GET /api/agents/:id/keys — additional keys (secrets are never listed)
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ApiKey>>, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "API keys")?;
+    Ok(Json(state.storage.list_api_keys(identity.tenant_id(), &id)))
+}
+
+/// POST /api/agents/:id/keys — mint an additional, optionally scoped key
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    Json(input): Json<CreateApiKey>,
+) -> Result<(StatusCode, Json<ApiKeyCreated>), (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "API keys")?;
+    let (key, api_key) = state
+        .storage
+        .create_api_key(identity.tenant_id(), &id, &input)
+        .map_err(|e| {
+            let status = if e.0.starts_with("Agent not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::UNPROCESSABLE_ENTITY
+            };
+            (status, Json(serde_json::json!({"error": e.0})))
+        })?;
+    Ok((StatusCode::CREATED, Json(ApiKeyCreated { key, api_key })))
+}
+
+/// DELETE /api/agents/:id/keys/:key_id
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((id, key_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "API keys")?;
+    if state
+        .storage
+        .revoke_api_key(identity.tenant_id(), &id, &key_id)
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "API key not found"})),
+        ))
+    }
+}
+
+fn validate_push_target(input: &PushTarget) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    input.validate().map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({"error": e})),
+        )
+    })
+}
+
+fn push_not_configured() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "No push target configured"})),
+    )
+}
+
+/// GET /api/agents/:id/push — the agent's phone push target (tokens are never returned)
+pub async fn get_push_target(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<PushTarget>, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "push target")?;
+    state
+        .storage
+        .get_push_target(identity.tenant_id(), "agent", &id)
+        .map(Json)
+        .ok_or_else(push_not_configured)
+}
+
+/// PUT /api/agents/:id/push — set the ntfy topic or Pushover key notifications routed to
+/// the push channel go to
+pub async fn set_push_target(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    Json(input): Json<PushTarget>,
+) -> Result<Json<PushTarget>, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "push target")?;
+    validate_push_target(&input)?;
+    if state.storage.get_agent(identity.tenant_id(), &id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Agent not found"})),
+        ));
+    }
+    Ok(Json(state.storage.set_push_target(
+        identity.tenant_id(),
+        "agent",
+        &id,
+        &input,
+    )))
+}
+
+/// DELETE /api/agents/:id/push
+pub async fn delete_push_target(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "push target")?;
+    if state
+        .storage
+        .delete_push_target(identity.tenant_id(), "agent", &id)
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(push_not_configured())
+    }
+}
+
+/// The user id behind a human identity; user push targets belong to humans only.
+fn human_user_id(identity: &Identity) -> Result<&str, (StatusCode, Json<serde_json::Value>)> {
+    match identity {
+        Identity::Human { id, .. } => Ok(id),
+        Identity::AgentIdentity { .. } => Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({"error": "Use /api/agents/:id/push for agents"})),
+        )),
+        Identity::Anonymous => Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        )),
+    }
+}
+
+/// GET /api/users/me/push — the calling user's push target
+pub async fn get_my_push_target(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<Json<PushTarget>, (StatusCode, Json<serde_json::Value>)> {
+    let user_id = human_user_id(&identity)?;
+    state
+        .storage
+        .get_push_target(identity.tenant_id(), "user", user_id)
+        .map(Json)
+        .ok_or_else(push_not_configured)
+}
+
+/// PUT /api/users/me/push — push target for notifications to the agents the user owns
+pub async fn set_my_push_target(
+    State(state): State<AppState>,
+    identity: Identity,
+    Json(input): Json<PushTarget>,
+) -> Result<Json<PushTarget>, (StatusCode, Json<serde_json::Value>)> {
+    let user_id = human_user_id(&identity)?;
+    validate_push_target(&input)?;
+    Ok(Json(state.storage.set_push_target(
+        identity.tenant_id(),
+        "user",
+        user_id,
+        &input,
+    )))
+}
+
+/// DELETE /api/users/me/push
+pub async fn delete_my_push_target(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let user_id = human_user_id(&identity)?;
+    if state
+        .storage
+        .delete_push_target(identity.tenant_id(), "user", user_id)
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(push_not_configured())
+    }
+}
+
+/// POST /api/agents/:id/webhook/test — push a synthetic event to the configured
+/// webhook_url and report the receiver's status and latency
+pub async fn test_webhook(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookTestResult>, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "webhooks")?;
+    let agent = state.storage.get_agent(identity.tenant_id(), &id).ok_or((
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "Agent not found"})),
+    ))?;
+    let url = match agent.webhook_url.as_deref() {
+        Some(url) if !url.is_empty() => url.to_string(),
+        _ => {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({"error": "Agent has no webhook_url configured"})),
+            ))
+        }
+    };
+    Ok(Json(webhooks::send_test_webhook(&agent, &url).await))
+}
+
+/// Unread notifications whose webhook failed after every retry.
+fn failed_webhooks(state: &AppState, identity: &Identity, agent_id: &str) -> Vec<Notification> {
+    state
+        .storage
+        .list_notifications(identity.tenant_id(), agent_id, Some(true))
+        .into_iter()
+        .filter(|n| n.webhook_status.as_deref() == Some("failed"))
+        .collect()
+}
+
+/// GET /api/agents/:id/webhooks/failed — dead-lettered webhook deliveries
+pub async fn list_failed_webhooks(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<Notification>>, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "webhook deliveries")?;
+    if state.storage.get_agent(identity.tenant_id(), &id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Agent not found"})),
+        ));
+    }
+    Ok(Json(failed_webhooks(&state, &identity, &id)))
+}
+
+/// POST /api/agents/:id/webhooks/failed/redeliver — retry dead-lettered deliveries
+/// (all of them, or just `notification_ids`) against the agent's current webhook_url.
+pub async fn redeliver_failed_webhooks(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    input: Option<Json<RedeliverWebhooks>>,
+) -> Result<Json<WebhookRedelivery>, (StatusCode, Json<serde_json::Value>)> {
+    authorize_self_or_human(&identity, &id, "webhook deliveries")?;
+    let agent = state.storage.get_agent(identity.tenant_id(), &id).ok_or((
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "Agent not found"})),
+    ))?;
+    if agent.webhook_url.as_deref().is_none_or(str::is_empty) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({"error": "Agent has no webhook_url to redeliver to"})),
+        ));
+    }
+    let wanted = input.and_then(|Json(i)| i.notification_ids);
+    let pending: Vec<PendingNotifWebhook> = failed_webhooks(&state, &identity, &id)
+        .into_iter()
+        .filter(|n| wanted.as_ref().is_none_or(|ids| ids.contains(&n.id)))
+        .map(|n| PendingNotifWebhook {
+            agent_id: n.agent_id,
+            notification_id: n.id,
+            event_type: n.event_type,
+            title: n.title,
+            body: n.body,
+            push: false,
+        })
+        .collect();
+    let notification_ids = pending.iter().map(|p| p.notification_id).collect();
+    webhooks::fire_notification_webhooks(state.storage.clone(), pending);
+    Ok(Json(WebhookRedelivery { notification_ids }))
+}
+
+/// GET /api/agents/:id/suggested-skills
+pub async fn suggested_skills(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    Query(query): Query<SuggestedSkillsQuery>,
+) -> Result<Json<SuggestedSkills>, (StatusCode, Json<serde_json::Value>)> {
+    let min_tasks = query.min_tasks.unwrap_or(DEFAULT_SUGGESTION_MIN_TASKS);
+    state
+        .storage
+        .suggest_skills(identity.tenant_id(), &id, min_tasks)
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Agent not found"})),
+        ))
+}
+
+/// POST /api/agents/:id/suggested-skills/apply — add all current suggestions to the profile
+pub async fn apply_suggested_skills(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    Query(query): Query<SuggestedSkillsQuery>,
+) -> Result<Json<Agent>, (StatusCode, Json<serde_json::Value>)> {
+    let min_tasks = query.min_tasks.unwrap_or(DEFAULT_SUGGESTION_MIN_TASKS);
+    state
+        .storage
+        .apply_suggested_skills(identity.tenant_id(), &id, min_tasks)
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Agent not found"})),
+        ))
+}
+
+/// POST /api/agents/:id/offboard — hand off the agent's work, then delete it.
+/// Open to humans, orchestrators and the agent itself.
+pub async fn offboard_agent(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    body: Option<Json<OffboardRequest>>,
+) -> Result<Json<OffboardResult>, (StatusCode, Json<serde_json::Value>)> {
+    let is_self = matches!(&identity, Identity::AgentIdentity { id: caller, .. } if *caller == id);
+    if !is_self {
+        admin::require_operator(&state, &identity, "offboard other agents")?;
+    }
+    let input = body.map(|Json(b)| b).unwrap_or_default();
+    if let Some(ref strategy) = input.reassign_to {
+        if let Err(error) = strategy.validate() {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({ "error": error })),
+            ));
+        }
+    }
+
+    let result = state
+        .storage
+        .offboard_agent(identity.tenant_id(), &id, input.reassign_to.as_ref())
+        .map_err(|e| {
+            let status = if e.0.contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::BAD_REQUEST
+            };
+            (status, Json(serde_json::json!({"error": e.0})))
+        })?;
+
+    for reassigned in &result.reassigned_tasks {
+        if let Some(task) = state.storage.get_task(identity.tenant_id(), &reassigned.id) {
+            webhooks::fire_assignment_webhook(state.storage.clone(), &task);
+        }
+    }
+
+    state.event_bus.emit(Event {
+        event_type: "agent.offboarded".to_string(),
+        project_id: None,
+        agent_id: Some(result.agent_id.clone()),
+        event_id: None,
+        data: serde_json::to_value(&result).unwrap_or_default(),
+        timestamp: Utc::now(),
+    });
+
+    Ok(Json(result))
+}
+
+/// POST /api/agents/heartbeat — optionally with a `HeartbeatRequest` status payload
 pub async fn heartbeat(
     State(state): State<AppState>,
     identity: Identity,
+    body: Option<Json<HeartbeatRequest>>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
     let agent_id = match &identity {
         Identity::AgentIdentity { id, .. } => id.clone(),
@@ -164,9 +622,30 @@ pub async fn heartbeat(
         }
     };
 
-    state
-        .storage
-        .update_heartbeat(identity.tenant_id(), &agent_id);
+    match body {
+        Some(Json(input)) => {
+            if input
+                .status_message
+                .as_ref()
+                .is_some_and(|m| m.chars().count() > MAX_STATUS_MESSAGE_CHARS)
+            {
+                return Err((
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(serde_json::json!({
+                        "error": format!("status_message exceeds {} characters", MAX_STATUS_MESSAGE_CHARS)
+                    })),
+                ));
+            }
+            state
+                .storage
+                .update_heartbeat_status(identity.tenant_id(), &agent_id, &input);
+        }
+        None => {
+            state
+                .storage
+                .update_heartbeat(identity.tenant_id(), &agent_id);
+        }
+    }
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
 
@@ -174,21 +653,37 @@ pub async fn register_agent(
     State(state): State<AppState>,
     Json(input): Json<RegisterAgentRequest>,
 ) -> Result<(StatusCode, Json<AgentCreated>), (StatusCode, Json<serde_json::Value>)> {
-    let expected = &state.setup_token;
-    if expected.is_empty() {
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(
-                serde_json::json!({"error": "Agent self-registration is disabled (no setup token configured)"}),
-            ),
-        ));
-    }
-    if input.setup_token != *expected {
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "Invalid setup token"})),
-        ));
+    // A tenant's own setup token registers into that tenant whatever owner_id says
+    let owner_id = match state.storage.get_tenant_by_setup_token(&input.setup_token) {
+        Some(tenant) => Some(tenant.id),
+        None => {
+            let expected = &state.setup_token;
+            if expected.is_empty() {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(
+                        serde_json::json!({"error": "Agent self-registration is disabled (no setup token configured)"}),
+                    ),
+                ));
+            }
+            if input.setup_token != *expected {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({"error": "Invalid setup token"})),
+                ));
+            }
+            input.owner_id
+        }
+    };
+    if let Some(tenant) = owner_id.as_deref() {
+        if auth::tenant_suspended(&*state.storage, tenant) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({"error": format!("Tenant '{}' is suspended", tenant)})),
+            ));
+        }
     }
+    quotas::check(&*state.storage, owner_id.as_deref(), Quota::Agents)?;
 
     let (agent, api_key) = state.storage.create_agent(
         None,
@@ -201,7 +696,7 @@ pub async fn register_agent(
             capabilities: input.capabilities,
             seniority: None,
             role: None,
-            owner_id: input.owner_id,
+            owner_id,
         },
     );
     Ok((StatusCode::CREATED, Json(AgentCreated { agent, api_key })))
@@ -8,12 +8,10 @@ use crate::app::AppState;
 use crate::handlers::webhooks;
 use opengate_models::*;
 
-pub async fn create_artifact(
-    State(state): State<AppState>,
-    identity: Identity,
-    Path(task_id): Path<String>,
-    Json(input): Json<CreateArtifact>,
-) -> Result<(StatusCode, Json<TaskArtifact>), (StatusCode, Json<serde_json::Value>)> {
+/// Type and size checks shared by task artifacts and knowledge/question attachments.
+pub fn validate_artifact(
+    input: &CreateArtifact,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
     if !VALID_ARTIFACT_TYPES.contains(&input.artifact_type.as_str()) {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -36,6 +34,16 @@ pub async fn create_artifact(
             })),
         ));
     }
+    Ok(())
+}
+
+pub async fn create_artifact(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(task_id): Path<String>,
+    Json(input): Json<CreateArtifact>,
+) -> Result<(StatusCode, Json<TaskArtifact>), (StatusCode, Json<serde_json::Value>)> {
+    validate_artifact(&input)?;
 
     let task = state
         .storage
@@ -22,24 +22,26 @@ pub fn emit_task_event(
         }
     });
 
+    let (event_id, pending) = storage.record_event(
+        identity.tenant_id(),
+        event_type,
+        Some(&task.id),
+        &task.project_id,
+        identity.author_type(),
+        identity.author_id(),
+        &payload,
+    );
+
     // Emit to the broadcast EventBus for real-time WebSocket subscribers
     event_bus.emit(Event {
         event_type: event_type.to_string(),
         project_id: Some(task.project_id.clone()),
         agent_id: task.assignee_id.clone(),
+        event_id: Some(event_id),
         data: serde_json::to_value(task).unwrap_or_default(),
         timestamp: Utc::now(),
     });
-
-    storage.emit_event(
-        identity.tenant_id(),
-        event_type,
-        Some(&task.id),
-        &task.project_id,
-        identity.author_type(),
-        identity.author_id(),
-        &payload,
-    )
+    pending
 }
 
 pub fn emit_knowledge_updated(
@@ -63,10 +65,21 @@ pub fn emit_knowledge_updated(
         "action": action,
     });
 
+    let (event_id, pending) = storage.record_event(
+        identity.tenant_id(),
+        "knowledge.updated",
+        None,
+        project_id,
+        identity.author_type(),
+        identity.author_id(),
+        &payload,
+    );
+
     event_bus.emit(Event {
         event_type: "knowledge.updated".to_string(),
         project_id: Some(project_id.to_string()),
         agent_id: None,
+        event_id: Some(event_id),
         data: serde_json::json!({
             "key": key,
             "title": title,
@@ -74,14 +87,5 @@ pub fn emit_knowledge_updated(
         }),
         timestamp: Utc::now(),
     });
-
-    storage.emit_event(
-        identity.tenant_id(),
-        "knowledge.updated",
-        None,
-        project_id,
-        identity.author_type(),
-        identity.author_id(),
-        &payload,
-    )
+    pending
 }
@@ -0,0 +1,452 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+
+use crate::app::AppState;
+use crate::events::Event;
+use crate::jira;
+use crate::linear;
+use crate::quotas::{self, Quota};
+use crate::storage::StorageBackend;
+use crate::trello;
+use opengate_models::*;
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn project_not_found() -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "Project not found"})),
+    )
+}
+
+fn unprocessable(e: String) -> ApiError {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(serde_json::json!({"error": e})),
+    )
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"))
+}
+
+/// Tasks of earlier imports from `source`, by the key after `<source>:` in their
+/// `context.external_ref`.
+fn imported_tasks(
+    storage: &dyn StorageBackend,
+    tenant: Option<&str>,
+    project_id: &str,
+    source: &str,
+) -> HashMap<String, String> {
+    let filters = TaskFilters {
+        project_id: Some(project_id.to_string()),
+        status: None,
+        priority: None,
+        assignee_id: None,
+        tag: None,
+    };
+    let prefix = format!("{}:", source);
+    storage
+        .list_tasks(tenant, &filters)
+        .into_iter()
+        .filter_map(|t| {
+            let key = t
+                .context?
+                .get("external_ref")?
+                .as_str()?
+                .strip_prefix(&prefix)?
+                .to_string();
+            Some((key, t.id))
+        })
+        .collect()
+}
+
+/// The transitions that take a new, `backlog` task to `status`.
+fn status_path(status: &str) -> &'static [&'static str] {
+    match status {
+        "todo" => &["todo"],
+        "in_progress" => &["in_progress"],
+        "review" => &["in_progress", "review"],
+        "blocked" => &["todo", "blocked"],
+        "done" => &["in_progress", "done"],
+        "cancelled" => &["cancelled"],
+        _ => &[],
+    }
+}
+
+/// Create `input` and walk it from `backlog` to `status`, as far as the transitions allow,
+/// recording that it came from `key` in `source`.
+fn create_imported_task(
+    state: &AppState,
+    identity: &Identity,
+    project_id: &str,
+    input: &CreateTask,
+    status: &str,
+    source: &str,
+    key: &str,
+) -> Result<Task, ApiError> {
+    let tenant = identity.tenant_id();
+    let owner = state.storage.project_owner(project_id);
+    quotas::check(&*state.storage, owner.as_deref(), Quota::OpenTasks)?;
+    let mut task = state
+        .storage
+        .create_task(tenant, project_id, input, identity.author_id());
+    for status in status_path(status) {
+        let update = UpdateTask {
+            status: Some(status.to_string()),
+            ..Default::default()
+        };
+        match state.storage.update_task(tenant, &task.id, &update) {
+            Ok(Some(updated)) => task = updated,
+            _ => break,
+        }
+    }
+    state.storage.create_activity(
+        tenant,
+        &task.id,
+        identity.author_type(),
+        identity.author_id(),
+        &CreateActivity {
+            content: format!("Task '{}' imported from {}", task.title, key),
+            activity_type: Some("status_change".to_string()),
+            metadata: Some(serde_json::json!({"source": source, "external_ref": key})),
+            mentions: None,
+        },
+    );
+    state.event_bus.emit(Event {
+        event_type: "task.created".to_string(),
+        project_id: Some(task.project_id.clone()),
+        agent_id: None,
+        event_id: None,
+        data: serde_json::to_value(&task).unwrap_or_default(),
+        timestamp: Utc::now(),
+    });
+    Ok(task)
+}
+
+/// POST /api/projects/:id/import/jira — tasks and dependencies from a Jira JSON
+/// (`Content-Type: application/json`) or CSV export. `?dry_run=true` reports the mapping
+/// without creating anything.
+pub async fn import_jira(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<JiraImportResult>), ApiError> {
+    let tenant = identity.tenant_id();
+    if state.storage.get_project(tenant, &project_id).is_none() {
+        return Err(project_not_found());
+    }
+    let issues = if is_json(&headers) {
+        jira::parse_json(&body)
+    } else {
+        jira::parse_csv(&body)
+    }
+    .map_err(unprocessable)?;
+
+    let mut task_ids = imported_tasks(&*state.storage, tenant, &project_id, "jira");
+    let imported: HashSet<String> = task_ids.keys().cloned().collect();
+    let plan = jira::plan(issues, &imported).map_err(unprocessable)?;
+
+    let dry_run = query.dry_run.unwrap_or(false);
+    let mut result = JiraImportResult {
+        dry_run,
+        issues: Vec::with_capacity(plan.issues.len()),
+        dependencies: plan.dependencies,
+        skipped: plan.skipped,
+        warnings: plan.warnings,
+    };
+    if dry_run {
+        result.issues = plan.issues.into_iter().map(|(_, m)| m).collect();
+        return Ok((StatusCode::OK, Json(result)));
+    }
+
+    for (issue, mut mapping) in plan.issues {
+        let task = create_imported_task(
+            &state,
+            &identity,
+            &project_id,
+            &jira::issue_task(&issue, &mapping),
+            &mapping.status,
+            "jira",
+            &issue.key,
+        )?;
+        if task.status != mapping.status {
+            result.warnings.push(format!(
+                "{}: could not move to {}; left in {}",
+                issue.key, mapping.status, task.status
+            ));
+            mapping.status = task.status.clone();
+        }
+        task_ids.insert(issue.key.clone(), task.id.clone());
+        mapping.task_id = Some(task.id);
+        result.issues.push(mapping);
+    }
+
+    result.dependencies.retain(|dep| {
+        let (Some(task_id), Some(depends_on)) =
+            (task_ids.get(&dep.key), task_ids.get(&dep.depends_on))
+        else {
+            return false;
+        };
+        match state.storage.add_dependency(tenant, task_id, depends_on) {
+            Ok(()) => true,
+            Err(e) => {
+                result.warnings.push(format!(
+                    "{} depends on {} left out: {}",
+                    dep.key, dep.depends_on, e
+                ));
+                false
+            }
+        }
+    });
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+/// POST /api/projects/:id/import/linear — tasks from a Linear CSV export, or with an empty
+/// body, from the API with the project's Linear integration. `?dry_run=true` reports the
+/// mapping without creating anything.
+pub async fn import_linear(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<LinearImportResult>), ApiError> {
+    let tenant = identity.tenant_id();
+    if state.storage.get_project(tenant, &project_id).is_none() {
+        return Err(project_not_found());
+    }
+    let issues = if body.iter().all(u8::is_ascii_whitespace) {
+        let integration = state.storage.get_linear_integration(tenant, &project_id);
+        let Some((token, team)) = integration.and_then(|l| l.token.zip(l.team)) else {
+            return Err(unprocessable(
+                "Send a CSV export, or set a token and team on the project's Linear integration"
+                    .to_string(),
+            ));
+        };
+        linear::fetch_issues(&reqwest::Client::new(), &token, &team)
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    Json(serde_json::json!({"error": format!("Linear issues not fetched: {}", e)})),
+                )
+            })?
+    } else {
+        linear::parse_csv(&body).map_err(unprocessable)?
+    };
+
+    let imported = imported_tasks(&*state.storage, tenant, &project_id, "linear");
+    let dry_run = query.dry_run.unwrap_or(false);
+    let mut result = LinearImportResult {
+        dry_run,
+        issues: Vec::new(),
+        skipped: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let mut seen = HashSet::new();
+    for issue in issues {
+        if imported.contains_key(&issue.identifier) || !seen.insert(issue.identifier.clone()) {
+            result.skipped.push(issue.identifier);
+            continue;
+        }
+        let status = linear::map_status(&issue.status, issue.status_type.as_deref())
+            .unwrap_or_else(|| {
+                result.warnings.push(format!(
+                    "{}: state '{}' has no counterpart; imported as backlog",
+                    issue.identifier, issue.status
+                ));
+                "backlog"
+            });
+        let priority = match issue.priority.as_deref() {
+            None => "medium",
+            Some(p) => linear::map_priority(p).unwrap_or_else(|| {
+                result.warnings.push(format!(
+                    "{}: priority '{}' has no counterpart; imported as medium",
+                    issue.identifier, p
+                ));
+                "medium"
+            }),
+        };
+        let mut mapping = LinearIssueMapping {
+            identifier: issue.identifier.clone(),
+            title: issue.title.clone(),
+            linear_status: issue.status.clone(),
+            status: status.to_string(),
+            linear_priority: issue.priority.clone(),
+            priority: priority.to_string(),
+            tags: issue.labels.clone(),
+            task_id: None,
+        };
+        if !dry_run {
+            let task = create_imported_task(
+                &state,
+                &identity,
+                &project_id,
+                &linear::issue_task(&issue, priority),
+                status,
+                "linear",
+                &issue.identifier,
+            )?;
+            if task.status != status {
+                result.warnings.push(format!(
+                    "{}: could not move to {}; left in {}",
+                    issue.identifier, status, task.status
+                ));
+                mapping.status = task.status.clone();
+            }
+            mapping.task_id = Some(task.id);
+        }
+        result.issues.push(mapping);
+    }
+
+    let status = if dry_run {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    Ok((status, Json(result)))
+}
+
+/// POST /api/import/trello — a new project from a Trello board's JSON export, sent as is
+/// or as `{"board": <export>, "name", "status_map", "checklists"}`. `?dry_run=true`
+/// reports the mapping without creating anything.
+pub async fn import_trello(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<TrelloImportResult>), ApiError> {
+    let (board, options) = trello::parse(&body).map_err(unprocessable)?;
+    let trello::Plan {
+        lists,
+        cards,
+        warnings,
+    } = trello::plan(&board, &options).map_err(unprocessable)?;
+    let dry_run = query.dry_run.unwrap_or(false);
+    let mut result = TrelloImportResult {
+        dry_run,
+        project: None,
+        checklists: options.checklists().to_string(),
+        lists,
+        cards: Vec::with_capacity(cards.len()),
+        warnings,
+    };
+    if dry_run {
+        result.cards = cards.into_iter().map(|c| c.mapping).collect();
+        return Ok((StatusCode::OK, Json(result)));
+    }
+
+    let tenant = identity.tenant_id();
+    quotas::check(&*state.storage, tenant, Quota::Projects)?;
+    let project = state.storage.create_project(
+        tenant,
+        &CreateProject {
+            name: options.name.clone().unwrap_or_else(|| board.name.clone()),
+            description: Some(board.desc.clone()).filter(|d| !d.trim().is_empty()),
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        identity.author_id(),
+    );
+
+    let attach = |task_id: &str, name: &str, artifact_type: &str, value: String| {
+        state.storage.create_artifact(
+            tenant,
+            task_id,
+            &CreateArtifact {
+                name: name.to_string(),
+                artifact_type: artifact_type.to_string(),
+                value,
+            },
+            identity.author_type(),
+            identity.author_id(),
+        );
+    };
+    for planned in cards {
+        let card = planned.card;
+        let mut mapping = planned.mapping;
+        let source = card.url.clone().unwrap_or_else(|| card.id.clone());
+        let task = create_imported_task(
+            &state,
+            &identity,
+            &project.id,
+            &trello::card_task(card, &mapping.tags),
+            &mapping.status,
+            "trello",
+            &source,
+        )?;
+        if task.status != mapping.status {
+            result.warnings.push(format!(
+                "{}: could not move to {}; left in {}",
+                card.name, mapping.status, task.status
+            ));
+            mapping.status = task.status.clone();
+        }
+        for attachment in &card.attachments {
+            if let Some(ref url) = attachment.url {
+                let name = Some(attachment.name.as_str())
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or("Attachment");
+                attach(&task.id, name, "url", url.clone());
+            }
+        }
+        for checklist in &planned.checklists {
+            if options.checklists() == "artifacts" {
+                attach(
+                    &task.id,
+                    &checklist.name,
+                    "text",
+                    trello::checklist_text(checklist),
+                );
+                continue;
+            }
+            for item in &checklist.check_items {
+                let status = if item.complete() {
+                    "done"
+                } else if task.status == "backlog" {
+                    "backlog"
+                } else {
+                    "todo"
+                };
+                let subtask = create_imported_task(
+                    &state,
+                    &identity,
+                    &project.id,
+                    &trello::item_task(item, checklist, &task.id),
+                    status,
+                    "trello",
+                    &source,
+                )?;
+                if let Err(e) = state.storage.add_dependency(tenant, &task.id, &subtask.id) {
+                    result.warnings.push(format!(
+                        "{}: subtask '{}' not linked: {}",
+                        card.name, item.name, e
+                    ));
+                }
+                mapping.subtask_ids.push(subtask.id);
+            }
+        }
+        mapping.task_id = Some(task.id);
+        result.cards.push(mapping);
+    }
+
+    result.project = Some(project);
+    Ok((StatusCode::CREATED, Json(result)))
+}
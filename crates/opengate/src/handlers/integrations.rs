@@ -0,0 +1,402 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+
+use crate::app::AppState;
+use crate::events::Event;
+use crate::gitlab;
+use crate::quotas::{self, Quota};
+use opengate_models::*;
+
+fn project_not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "Project not found"})),
+    )
+}
+
+/// A project is hosted on GitHub or GitLab, not both: configuring one while the other is
+/// set is a 409.
+fn other_forge_configured(configured: &str) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::CONFLICT,
+        Json(serde_json::json!({
+            "error": format!(
+                "{} is configured for this project; delete that integration first",
+                configured
+            )
+        })),
+    )
+}
+
+/// GET /api/projects/:id/integrations/slack
+pub async fn get_slack(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> Result<Json<SlackIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    state
+        .storage
+        .get_slack_integration(identity.tenant_id(), &project_id)
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Slack is not configured for this project"})),
+        ))
+}
+
+/// PUT /api/projects/:id/integrations/slack — create or replace the project's Slack config
+pub async fn set_slack(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Json(input): Json<SetSlackIntegration>,
+) -> Result<Json<SlackIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    let has_webhook = input.webhook_url.as_deref().is_some_and(|u| !u.is_empty());
+    let has_bot = input.bot_token.as_deref().is_some_and(|t| !t.is_empty())
+        && input.channel.as_deref().is_some_and(|c| !c.is_empty());
+    if !has_webhook && !has_bot {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": "Give a webhook_url, or a bot_token and channel"
+            })),
+        ));
+    }
+    if input.events.as_ref().is_some_and(|e| e.is_empty()) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({"error": "events must not be empty; use [\"*\"] for all"})),
+        ));
+    }
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    Ok(Json(state.storage.set_slack_integration(
+        identity.tenant_id(),
+        &project_id,
+        &input,
+    )))
+}
+
+/// DELETE /api/projects/:id/integrations/slack
+pub async fn delete_slack(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> StatusCode {
+    if state
+        .storage
+        .delete_slack_integration(identity.tenant_id(), &project_id)
+    {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// GET /api/projects/:id/integrations/github
+pub async fn get_github(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> Result<Json<GithubIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    state
+        .storage
+        .get_github_integration(identity.tenant_id(), &project_id)
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "GitHub is not configured for this project"})),
+        ))
+}
+
+/// PUT /api/projects/:id/integrations/github — create or replace the project's GitHub config
+pub async fn set_github(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Json(input): Json<SetGithubIntegration>,
+) -> Result<Json<GithubIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    if state
+        .storage
+        .get_gitlab_integration(identity.tenant_id(), &project_id)
+        .is_some()
+    {
+        return Err(other_forge_configured("GitLab"));
+    }
+    Ok(Json(state.storage.set_github_integration(
+        identity.tenant_id(),
+        &project_id,
+        &input,
+    )))
+}
+
+/// DELETE /api/projects/:id/integrations/github
+pub async fn delete_github(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> StatusCode {
+    if state
+        .storage
+        .delete_github_integration(identity.tenant_id(), &project_id)
+    {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// GET /api/projects/:id/integrations/gitlab
+pub async fn get_gitlab(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> Result<Json<GitlabIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    state
+        .storage
+        .get_gitlab_integration(identity.tenant_id(), &project_id)
+        .map(Json)
+        .ok_or(gitlab_not_configured())
+}
+
+fn gitlab_not_configured() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "GitLab is not configured for this project"})),
+    )
+}
+
+/// PUT /api/projects/:id/integrations/gitlab — create or replace the project's GitLab config
+pub async fn set_gitlab(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Json(input): Json<SetGitlabIntegration>,
+) -> Result<Json<GitlabIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    if state
+        .storage
+        .get_github_integration(identity.tenant_id(), &project_id)
+        .is_some()
+    {
+        return Err(other_forge_configured("GitHub"));
+    }
+    Ok(Json(state.storage.set_gitlab_integration(
+        identity.tenant_id(),
+        &project_id,
+        &input,
+    )))
+}
+
+/// DELETE /api/projects/:id/integrations/gitlab
+pub async fn delete_gitlab(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> StatusCode {
+    if state
+        .storage
+        .delete_gitlab_integration(identity.tenant_id(), &project_id)
+    {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// POST /api/projects/:id/integrations/gitlab/import — a task for each open issue not
+/// imported before
+pub async fn import_gitlab_issues(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> Result<(StatusCode, Json<GitlabImport>), (StatusCode, Json<serde_json::Value>)> {
+    let tenant = identity.tenant_id();
+    if state.storage.get_project(tenant, &project_id).is_none() {
+        return Err(project_not_found());
+    }
+    let integration = state
+        .storage
+        .get_gitlab_integration(tenant, &project_id)
+        .ok_or(gitlab_not_configured())?;
+    let (Some(token), Some(gitlab_project)) = (integration.token, integration.gitlab_project)
+    else {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": "Importing issues needs the integration's token and gitlab_project"
+            })),
+        ));
+    };
+
+    let issues = gitlab::fetch_open_issues(&reqwest::Client::new(), &token, &gitlab_project)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({"error": format!("GitLab issues not fetched: {}", e)})),
+            )
+        })?;
+
+    let filters = TaskFilters {
+        project_id: Some(project_id.clone()),
+        status: None,
+        priority: None,
+        assignee_id: None,
+        tag: None,
+    };
+    let existing: std::collections::HashSet<String> = state
+        .storage
+        .list_tasks(tenant, &filters)
+        .into_iter()
+        .filter_map(|t| t.context?.get("external_ref")?.as_str().map(str::to_string))
+        .collect();
+
+    let owner = state.storage.project_owner(&project_id);
+    let mut imported = Vec::new();
+    let mut skipped = 0;
+    for issue in &issues {
+        if existing.contains(&gitlab::external_ref(&gitlab_project, issue.iid)) {
+            skipped += 1;
+            continue;
+        }
+        quotas::check(&*state.storage, owner.as_deref(), Quota::OpenTasks)?;
+        let task = state.storage.create_task(
+            tenant,
+            &project_id,
+            &gitlab::issue_task(&gitlab_project, issue),
+            identity.author_id(),
+        );
+        state.storage.create_activity(
+            tenant,
+            &task.id,
+            identity.author_type(),
+            identity.author_id(),
+            &CreateActivity {
+                content: format!("Task '{}' imported from {}", task.title, issue.web_url),
+                activity_type: Some("status_change".to_string()),
+                metadata: Some(serde_json::json!({"source": "gitlab", "issue_url": issue.web_url})),
+                mentions: None,
+            },
+        );
+        state.event_bus.emit(Event {
+            event_type: "task.created".to_string(),
+            project_id: Some(task.project_id.clone()),
+            agent_id: None,
+            event_id: None,
+            data: serde_json::to_value(&task).unwrap_or_default(),
+            timestamp: Utc::now(),
+        });
+        imported.push(task);
+    }
+
+    let status = if imported.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    Ok((status, Json(GitlabImport { imported, skipped })))
+}
+
+/// GET /api/projects/:id/integrations/linear
+pub async fn get_linear(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> Result<Json<LinearIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    state
+        .storage
+        .get_linear_integration(identity.tenant_id(), &project_id)
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Linear is not configured for this project"})),
+        ))
+}
+
+/// PUT /api/projects/:id/integrations/linear — create or replace the project's Linear config
+pub async fn set_linear(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Json(input): Json<SetLinearIntegration>,
+) -> Result<Json<LinearIntegration>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(project_not_found());
+    }
+    Ok(Json(state.storage.set_linear_integration(
+        identity.tenant_id(),
+        &project_id,
+        &input,
+    )))
+}
+
+/// DELETE /api/projects/:id/integrations/linear
+pub async fn delete_linear(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> StatusCode {
+    if state
+        .storage
+        .delete_linear_integration(identity.tenant_id(), &project_id)
+    {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
@@ -1,11 +1,14 @@
 use axum::{
+    body::Bytes,
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 
 use crate::app::AppState;
-use crate::handlers::{events, webhooks};
+use crate::handlers::{artifacts, events, webhooks};
+use crate::{freshness, kb_bundle};
 use opengate_models::*;
 
 pub async fn list_knowledge(
@@ -31,6 +34,7 @@ pub async fn list_knowledge(
     Ok(Json(entries))
 }
 
+/// GET /api/projects/:id/knowledge/search — `?scope=all` also searches the shared space.
 pub async fn search_knowledge(
     State(state): State<AppState>,
     identity: Identity,
@@ -47,7 +51,33 @@ pub async fn search_knowledge(
             Json(serde_json::json!({"error": "Project not found"})),
         ));
     }
+    let include_shared = match query.scope.as_deref() {
+        None | Some("project") => false,
+        Some("all") => true,
+        Some(other) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": format!("Invalid scope '{}'. Must be one of: project, all", other)
+                })),
+            ))
+        }
+    };
 
+    let mut entries = search_in(&state, &identity, &project_id, &query);
+    if include_shared {
+        let shared = shared_knowledge_scope(identity.tenant_id());
+        entries.extend(search_in(&state, &identity, &shared, &query));
+    }
+    Ok(Json(entries))
+}
+
+fn search_in(
+    state: &AppState,
+    identity: &Identity,
+    project_id: &str,
+    query: &KnowledgeSearchQuery,
+) -> Vec<KnowledgeEntry> {
     let tag_list: Vec<String> = query
         .tags
         .as_deref()
@@ -58,26 +88,298 @@ pub async fn search_knowledge(
         .collect();
 
     let q = query.q.as_deref().unwrap_or("");
-    let entries = state.storage.search_knowledge(
+    state.storage.search_knowledge(
         identity.tenant_id(),
-        &project_id,
+        project_id,
         q,
         &tag_list,
         query.category.as_deref(),
-    );
-    Ok(Json(entries))
+    )
+}
+
+/// GET /api/projects/:id/knowledge/export — every entry as a tar of markdown files.
+pub async fn export_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Project not found"})),
+        ));
+    }
+    let entries = state
+        .storage
+        .list_knowledge(identity.tenant_id(), &project_id, None);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"knowledge.tar\"",
+            ),
+        ],
+        kb_bundle::export_bundle(&entries),
+    ))
+}
+
+/// POST /api/projects/:id/knowledge/import — upsert entries from a JSON array of
+/// `{key, title, content, ...}` (when sent as `application/json`) or a tar bundle as
+/// produced by the export. `?on_conflict=` picks what happens to existing keys. Nothing
+/// is written if any entry fails to parse or validate.
+pub async fn import_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Query(query): Query<KnowledgeImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<KnowledgeImportResult>, (StatusCode, Json<serde_json::Value>)> {
+    let project = state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Project not found"})),
+        ))?;
+    let strategy = query.on_conflict.as_deref().unwrap_or("overwrite");
+    if !KNOWLEDGE_CONFLICT_STRATEGIES.contains(&strategy) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!(
+                    "Invalid on_conflict '{}'. Must be one of: {}",
+                    strategy,
+                    KNOWLEDGE_CONFLICT_STRATEGIES.join(", ")
+                )
+            })),
+        ));
+    }
+
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let entries: Vec<(String, UpsertKnowledge)> = if is_json {
+        parse_body::<Vec<ImportKnowledgeEntry>>(&body)?
+            .into_iter()
+            .map(|e| (e.key.trim_matches('/').to_string(), e.entry))
+            .collect()
+    } else {
+        kb_bundle::import_bundle(&body).map_err(|e| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({"error": e})),
+            )
+        })?
+    };
+
+    let mut result = KnowledgeImportResult {
+        created: Vec::new(),
+        updated: Vec::new(),
+        unchanged: Vec::new(),
+        skipped: Vec::new(),
+    };
+    let mut writes = Vec::new();
+    for (key, mut input) in entries {
+        if key.is_empty() {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({"error": "Every entry needs a non-empty key"})),
+            ));
+        }
+        normalize_review_after(&mut input)?;
+        let existing = state
+            .storage
+            .get_knowledge(identity.tenant_id(), &project_id, &key);
+        if let Some(ref e) = existing {
+            match strategy {
+                "skip" => {
+                    result.skipped.push(key);
+                    continue;
+                }
+                "merge" => input = merge_knowledge(e, input),
+                _ => {}
+            }
+            if e.title == input.title
+                && e.content == input.content
+                && e.metadata == input.metadata
+                && e.category == input.category
+                && e.tags == input.tags.clone().unwrap_or_default()
+                && input
+                    .review_after
+                    .as_ref()
+                    .is_none_or(|r| e.review_after.as_deref().unwrap_or("") == r)
+            {
+                result.unchanged.push(key);
+                continue;
+            }
+        }
+        check_template(&project, &key, &input)?;
+        writes.push((key, input, existing.is_some()));
+    }
+
+    let mut pending = Vec::new();
+    for (key, input, existed) in writes {
+        let entry = state.storage.upsert_knowledge(
+            identity.tenant_id(),
+            &project_id,
+            &key,
+            &input,
+            identity.author_type(),
+            identity.author_id(),
+        );
+        pending.extend(events::emit_knowledge_updated(
+            &*state.storage,
+            &state.event_bus,
+            &identity,
+            &project_id,
+            &entry.key,
+            &entry.title,
+            if existed { "updated" } else { "created" },
+        ));
+        if existed {
+            result.updated.push(key);
+        } else {
+            result.created.push(key);
+        }
+    }
+    webhooks::fire_notification_webhooks(state.storage.clone(), pending);
+
+    Ok(Json(result))
+}
+
+/// `on_conflict=merge`: the import's title and content, combined with what it leaves out.
+fn merge_knowledge(existing: &KnowledgeEntry, mut input: UpsertKnowledge) -> UpsertKnowledge {
+    let mut tags = existing.tags.clone();
+    for tag in input.tags.take().unwrap_or_default() {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    input.tags = Some(tags);
+    input.metadata = match (existing.metadata.clone(), input.metadata.take()) {
+        (Some(serde_json::Value::Object(mut base)), Some(serde_json::Value::Object(extra))) => {
+            base.extend(extra);
+            Some(serde_json::Value::Object(base))
+        }
+        (base, extra) => extra.or(base),
+    };
+    if input.category.is_none() {
+        input.category = existing.category.clone();
+    }
+    input
+}
+
+/// GET /api/projects/:id/knowledge/stats — most-read and never-read entries.
+pub async fn get_knowledge_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Query(query): Query<KnowledgeStatsQuery>,
+) -> Result<Json<KnowledgeUsageStats>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Project not found"})),
+        ));
+    }
+    Ok(Json(state.storage.get_knowledge_usage_stats(
+        identity.tenant_id(),
+        &project_id,
+        query.limit.unwrap_or(10).clamp(1, 100),
+        query.reader_type.as_deref(),
+    )))
+}
+
+/// GET /api/projects/:id/knowledge/graph — entries as nodes, `[[key]]` references as edges.
+pub async fn get_knowledge_graph(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+) -> Result<Json<KnowledgeGraph>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Project not found"})),
+        ));
+    }
+    Ok(Json(
+        state
+            .storage
+            .get_knowledge_graph(identity.tenant_id(), &project_id),
+    ))
 }
 
+/// GET /api/projects/:id/knowledge/*key — the entry, or its history / attachments /
+/// backlinks when the path ends in `/versions` / `/attachments` / `/backlinks` (keys may
+/// contain slashes, so these can't be separate routes).
 pub async fn get_knowledge(
     State(state): State<AppState>,
     identity: Identity,
     Path((project_id, key)): Path<(String, String)>,
-) -> Result<Json<KnowledgeEntry>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(entry_key) = key.strip_suffix("/attachments") {
+        return state
+            .storage
+            .get_knowledge(identity.tenant_id(), &project_id, entry_key)
+            .map(|entry| Json(entry.attachments).into_response())
+            .ok_or((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": "Knowledge entry not found"})),
+            ));
+    }
+    if let Some(entry_key) = key.strip_suffix("/backlinks") {
+        return state
+            .storage
+            .list_knowledge_backlinks(identity.tenant_id(), &project_id, entry_key)
+            .map(|backlinks| Json(backlinks).into_response())
+            .ok_or((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": "Knowledge entry not found"})),
+            ));
+    }
+    if let Some(entry_key) = key.strip_suffix("/versions") {
+        let versions =
+            state
+                .storage
+                .list_knowledge_versions(identity.tenant_id(), &project_id, entry_key);
+        if versions.is_empty() {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": "Knowledge entry not found"})),
+            ));
+        }
+        return Ok(Json(versions).into_response());
+    }
+
     match state
         .storage
         .get_knowledge(identity.tenant_id(), &project_id, &key)
     {
-        Some(entry) => Ok(Json(entry)),
+        Some(entry) => {
+            state.storage.record_knowledge_read(
+                identity.tenant_id(),
+                &project_id,
+                &key,
+                identity.author_type(),
+                identity.author_id(),
+            );
+            Ok(Json(entry).into_response())
+        }
         None => Err((
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({"error": "Knowledge entry not found"})),
@@ -85,32 +387,201 @@ pub async fn get_knowledge(
     }
 }
 
+/// POST /api/projects/:id/knowledge/*key — sub-resource actions, selected by suffix:
+/// `/revert` (body `RevertKnowledge`) or `/attachments` (body `CreateArtifact`).
+pub async fn post_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((project_id, key)): Path<(String, String)>,
+    body: Bytes,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(entry_key) = key.strip_suffix("/revert") {
+        let input: RevertKnowledge = parse_body(&body)?;
+        return revert_knowledge(&state, &identity, &project_id, entry_key, input)
+            .map(|entry| Json(entry).into_response());
+    }
+    if let Some(entry_key) = key.strip_suffix("/attachments") {
+        let input: CreateArtifact = parse_body(&body)?;
+        return create_attachment(&state, &identity, &project_id, entry_key, input)
+            .map(|attachment| (StatusCode::CREATED, Json(attachment)).into_response());
+    }
+    Err((
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({"error": "Not found"})),
+    ))
+}
+
+/// With `enforce_knowledge_templates`, entries must carry their category's sections.
+fn check_template(
+    project: &Project,
+    key: &str,
+    input: &UpsertKnowledge,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if !project.enforce_knowledge_templates {
+        return Ok(());
+    }
+    let missing = missing_template_sections(input.category.as_deref(), &input.content);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err((
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(serde_json::json!({
+            "error": format!(
+                "{}: missing required sections for category '{}': {}",
+                key,
+                input.category.as_deref().unwrap_or_default(),
+                missing.join(", ")
+            ),
+            "missing_sections": missing,
+        })),
+    ))
+}
+
+/// Store `review_after` in canonical form ("" still means clear).
+fn normalize_review_after(
+    input: &mut UpsertKnowledge,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if let Some(ref raw) = input.review_after {
+        let normalized = freshness::normalize_review_after(raw).map_err(|e| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({"error": e})),
+            )
+        })?;
+        input.review_after = Some(normalized.unwrap_or_default());
+    }
+    Ok(())
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(
+    body: &[u8],
+) -> Result<T, (StatusCode, Json<serde_json::Value>)> {
+    serde_json::from_slice(body).map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({"error": format!("Invalid body: {}", e)})),
+        )
+    })
+}
+
+/// Restore `input.version` as a new version.
+fn revert_knowledge(
+    state: &AppState,
+    identity: &Identity,
+    project_id: &str,
+    key: &str,
+    input: RevertKnowledge,
+) -> Result<KnowledgeEntry, (StatusCode, Json<serde_json::Value>)> {
+    let entry = state
+        .storage
+        .revert_knowledge(
+            identity.tenant_id(),
+            project_id,
+            key,
+            input.version,
+            identity.author_type(),
+            identity.author_id(),
+        )
+        .map_err(|e| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": e.0})),
+            )
+        })?;
+
+    let pending = events::emit_knowledge_updated(
+        &*state.storage,
+        &state.event_bus,
+        identity,
+        project_id,
+        &entry.key,
+        &entry.title,
+        "reverted",
+    );
+    webhooks::fire_notification_webhooks(state.storage.clone(), pending);
+
+    Ok(entry)
+}
+
+fn create_attachment(
+    state: &AppState,
+    identity: &Identity,
+    project_id: &str,
+    key: &str,
+    input: CreateArtifact,
+) -> Result<KnowledgeAttachment, (StatusCode, Json<serde_json::Value>)> {
+    artifacts::validate_artifact(&input)?;
+    let attachment = state
+        .storage
+        .create_knowledge_attachment(
+            identity.tenant_id(),
+            project_id,
+            key,
+            &input,
+            identity.author_type(),
+            identity.author_id(),
+        )
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Knowledge entry not found"})),
+        ))?;
+
+    let pending = events::emit_knowledge_updated(
+        &*state.storage,
+        &state.event_bus,
+        identity,
+        project_id,
+        key,
+        &attachment.name,
+        "attachment_added",
+    );
+    webhooks::fire_notification_webhooks(state.storage.clone(), pending);
+
+    Ok(attachment)
+}
+
 pub async fn upsert_knowledge(
     State(state): State<AppState>,
     identity: Identity,
     Path((project_id, key)): Path<(String, String)>,
-    Json(input): Json<UpsertKnowledge>,
+    Json(mut input): Json<UpsertKnowledge>,
 ) -> Result<Json<KnowledgeEntry>, (StatusCode, Json<serde_json::Value>)> {
-    if state
+    normalize_review_after(&mut input)?;
+    let project = state
         .storage
         .get_project(identity.tenant_id(), &project_id)
-        .is_none()
-    {
-        return Err((
+        .ok_or((
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({"error": "Project not found"})),
-        ));
-    }
+        ))?;
+    check_template(&project, &key, &input)?;
 
+    Ok(Json(write_knowledge(
+        &state,
+        &identity,
+        &project_id,
+        &key,
+        &input,
+    )))
+}
+
+fn write_knowledge(
+    state: &AppState,
+    identity: &Identity,
+    project_id: &str,
+    key: &str,
+    input: &UpsertKnowledge,
+) -> KnowledgeEntry {
     let existed = state
         .storage
-        .get_knowledge(identity.tenant_id(), &project_id, &key)
+        .get_knowledge(identity.tenant_id(), project_id, key)
         .is_some();
     let entry = state.storage.upsert_knowledge(
         identity.tenant_id(),
-        &project_id,
-        &key,
-        &input,
+        project_id,
+        key,
+        input,
         identity.author_type(),
         identity.author_id(),
     );
@@ -118,22 +589,39 @@ pub async fn upsert_knowledge(
     let pending = events::emit_knowledge_updated(
         &*state.storage,
         &state.event_bus,
-        &identity,
-        &project_id,
+        identity,
+        project_id,
         &entry.key,
         &entry.title,
         if existed { "updated" } else { "created" },
     );
     webhooks::fire_notification_webhooks(state.storage.clone(), pending);
 
-    Ok(Json(entry))
+    entry
 }
 
+/// DELETE /api/projects/:id/knowledge/*key — the entry, or one attachment when the path
+/// is `{key}/attachments/{attachment_id}`.
 pub async fn delete_knowledge(
     State(state): State<AppState>,
     identity: Identity,
     Path((project_id, key)): Path<(String, String)>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    if let Some((entry_key, attachment_id)) = key.rsplit_once("/attachments/") {
+        if state.storage.delete_knowledge_attachment(
+            identity.tenant_id(),
+            &project_id,
+            entry_key,
+            attachment_id,
+        ) {
+            return Ok(StatusCode::NO_CONTENT);
+        }
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Attachment not found"})),
+        ));
+    }
+
     if state
         .storage
         .delete_knowledge(identity.tenant_id(), &project_id, &key)
@@ -146,3 +634,151 @@ pub async fn delete_knowledge(
         ))
     }
 }
+
+/// GET /api/knowledge/templates — content skeletons and required sections per category.
+pub async fn list_knowledge_templates(_identity: Identity) -> Json<Vec<KnowledgeTemplate>> {
+    Json(knowledge_templates())
+}
+
+// --- Shared knowledge space ---
+//
+// Organization-wide entries (coding standards and the like) live under a pseudo project
+// per tenant; see `shared_knowledge_scope`. Entry-level routes reuse the project handlers.
+
+/// GET /api/knowledge
+pub async fn list_shared_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<KnowledgeSearchQuery>,
+) -> Json<Vec<KnowledgeEntry>> {
+    let shared = shared_knowledge_scope(identity.tenant_id());
+    Json(
+        state
+            .storage
+            .list_knowledge(identity.tenant_id(), &shared, query.prefix.as_deref()),
+    )
+}
+
+/// GET /api/knowledge/search
+pub async fn search_shared_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<KnowledgeSearchQuery>,
+) -> Json<Vec<KnowledgeEntry>> {
+    let shared = shared_knowledge_scope(identity.tenant_id());
+    Json(search_in(&state, &identity, &shared, &query))
+}
+
+/// GET /api/knowledge/*key
+pub async fn get_shared_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(key): Path<String>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let shared = shared_knowledge_scope(identity.tenant_id());
+    get_knowledge(State(state), identity, Path((shared, key))).await
+}
+
+/// PUT /api/knowledge/*key
+pub async fn upsert_shared_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(key): Path<String>,
+    Json(mut input): Json<UpsertKnowledge>,
+) -> Result<Json<KnowledgeEntry>, (StatusCode, Json<serde_json::Value>)> {
+    normalize_review_after(&mut input)?;
+    let shared = shared_knowledge_scope(identity.tenant_id());
+    Ok(Json(write_knowledge(
+        &state, &identity, &shared, &key, &input,
+    )))
+}
+
+/// POST /api/knowledge/*key — `/revert` and `/attachments`, as for project entries.
+pub async fn post_shared_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(key): Path<String>,
+    body: Bytes,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let shared = shared_knowledge_scope(identity.tenant_id());
+    post_knowledge(State(state), identity, Path((shared, key)), body).await
+}
+
+/// DELETE /api/knowledge/*key
+pub async fn delete_shared_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(key): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let shared = shared_knowledge_scope(identity.tenant_id());
+    delete_knowledge(State(state), identity, Path((shared, key))).await
+}
+
+// --- Task knowledge links ---
+
+/// GET /api/tasks/:id/knowledge
+pub async fn list_task_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(task_id): Path<String>,
+) -> Result<Json<Vec<KnowledgeSummary>>, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .get_task(identity.tenant_id(), &task_id)
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Task not found"})),
+        ));
+    }
+    Ok(Json(
+        state
+            .storage
+            .list_task_knowledge(identity.tenant_id(), &task_id),
+    ))
+}
+
+/// POST /api/tasks/:id/knowledge — link an entry from the task's project
+pub async fn link_task_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(task_id): Path<String>,
+    Json(input): Json<LinkKnowledge>,
+) -> Result<Json<Vec<KnowledgeSummary>>, (StatusCode, Json<serde_json::Value>)> {
+    state
+        .storage
+        .link_task_knowledge(
+            identity.tenant_id(),
+            &task_id,
+            &input.key,
+            identity.author_type(),
+            identity.author_id(),
+        )
+        .map(Json)
+        .map_err(|e| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": e.0})),
+            )
+        })
+}
+
+/// DELETE /api/tasks/:id/knowledge/*key
+pub async fn unlink_task_knowledge(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((task_id, key)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .storage
+        .unlink_task_knowledge(identity.tenant_id(), &task_id, &key)
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Link not found"})),
+        ))
+    }
+}
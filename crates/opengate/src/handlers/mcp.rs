@@ -0,0 +1,134 @@
+//! `POST /api/mcp` — the MCP server over Streamable HTTP, so remote agents can use the
+//! same tools and resources as `opengate mcp-server` without access to the database file.
+//!
+//! Authentication is the agent's API key as a bearer header. Each POST carries one
+//! JSON-RPC message or a batch; requests are answered in the response body as JSON, or
+//! as a single-event SSE stream when the client only accepts `text/event-stream`.
+//! Notifications get `202 Accepted`. There is no session to keep: every POST stands
+//! alone. `GET` opens the server's own SSE stream, which pushes a
+//! `notifications/message` whenever the agent gets a new notification.
+
+use std::convert::Infallible;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::stream::{self, Stream};
+use serde_json::Value;
+
+use crate::app::AppState;
+use crate::mcp;
+use opengate_models::Identity;
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({"error": "Authentication required"})),
+    )
+        .into_response()
+}
+
+pub async fn handle(
+    State(state): State<AppState>,
+    identity: Identity,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Identity::AgentIdentity {
+        id,
+        name,
+        tenant_id,
+    } = identity
+    else {
+        return unauthorized();
+    };
+
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(mcp::parse_error(&e))).into_response(),
+    };
+    let answer = |request: &Value| {
+        mcp::respond(
+            state.storage.clone(),
+            &id,
+            &name,
+            tenant_id.as_deref(),
+            request,
+        )
+    };
+    let reply = match message {
+        Value::Array(batch) => {
+            let replies: Vec<Value> = batch.iter().filter_map(answer).collect();
+            (!replies.is_empty()).then_some(Value::Array(replies))
+        }
+        request => answer(&request),
+    };
+
+    let Some(reply) = reply else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    if wants_event_stream(&headers) {
+        (
+            [
+                (header::CONTENT_TYPE, "text/event-stream"),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            format!("event: message\ndata: {}\n\n", reply),
+        )
+            .into_response()
+    } else {
+        Json(reply).into_response()
+    }
+}
+
+/// Whether the client accepts SSE but not plain JSON.
+fn wants_event_stream(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    accept.contains("text/event-stream") && !accept.contains("application/json")
+}
+
+/// The server-to-client stream: the agent's new notifications, as they arrive.
+pub async fn stream(
+    State(state): State<AppState>,
+    identity: Identity,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, Response> {
+    let Identity::AgentIdentity { id, tenant_id, .. } = identity else {
+        return Err(unauthorized());
+    };
+    let cursor = state
+        .storage
+        .get_last_notification_id(tenant_id.as_deref(), &id);
+    let mut poll = tokio::time::interval(mcp::NOTIFICATION_POLL);
+    poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let messages = stream::unfold(
+        (state, id, tenant_id, cursor, poll, Vec::<Value>::new()),
+        |(state, id, tenant_id, mut cursor, mut poll, mut queued)| async move {
+            while queued.is_empty() {
+                poll.tick().await;
+                queued = mcp::notification_messages(
+                    &*state.storage,
+                    tenant_id.as_deref(),
+                    &id,
+                    &mut cursor,
+                );
+                queued.reverse();
+            }
+            let message = queued.pop()?;
+            let event = SseEvent::default()
+                .event("message")
+                .data(message.to_string());
+            Some((Ok(event), (state, id, tenant_id, cursor, poll, queued)))
+        },
+    );
+    Ok(Sse::new(messages).keep_alive(KeepAlive::default()))
+}
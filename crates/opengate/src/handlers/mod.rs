@@ -1,14 +1,20 @@
 pub mod activity;
+pub mod admin;
 pub mod agents;
 pub mod artifacts;
 pub mod auth;
 pub mod events;
+pub mod imports;
+pub mod integrations;
 pub mod knowledge;
+pub mod mcp;
 pub mod projects;
 pub mod questions;
 pub mod schema;
+pub mod sse;
 pub mod stats;
 pub mod tasks;
 pub mod triggers;
 pub mod webhooks;
 pub mod ws;
+pub mod ws_commands;
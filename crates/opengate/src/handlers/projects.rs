@@ -1,11 +1,16 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, Method, StatusCode},
+    response::IntoResponse,
     Json,
 };
 use serde::Deserialize;
 
 use crate::app::AppState;
+use crate::auth;
+use crate::handlers::stats;
+use crate::ical;
+use crate::quotas::{self, Quota};
 use opengate_models::*;
 
 #[derive(Deserialize)]
@@ -30,6 +35,7 @@ pub async fn create_project(
     identity: Identity,
     Json(input): Json<CreateProject>,
 ) -> Result<(StatusCode, Json<Project>), (StatusCode, Json<serde_json::Value>)> {
+    quotas::check(&*state.storage, identity.tenant_id(), Quota::Projects)?;
     let project = state
         .storage
         .create_project(identity.tenant_id(), &input, identity.author_id());
@@ -97,6 +103,57 @@ pub async fn get_pulse(
     )))
 }
 
+/// GET /api/projects/:id/burndown — open work per day and completions per iteration,
+/// replayed from the tasks' status history.
+pub async fn get_burndown(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    Query(query): Query<BurndownQuery>,
+) -> Result<Json<Burndown>, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": error })),
+        )
+    };
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &id)
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Project not found"})),
+        ));
+    }
+    let by_estimate = match query.weight.as_deref().unwrap_or("count") {
+        "count" => false,
+        "estimate" => true,
+        _ => return Err(bad_request("weight must be count or estimate".to_string())),
+    };
+    let iteration_days = query.iteration_days.unwrap_or(7);
+    if !(1..=90).contains(&iteration_days) {
+        return Err(bad_request(
+            "iteration_days must be between 1 and 90".to_string(),
+        ));
+    }
+    let (from, to) = stats::date_range(&query.from, &query.to).map_err(bad_request)?;
+    if (to - from).num_days() >= stats::MAX_POINTS {
+        return Err(bad_request(format!(
+            "At most {} days per burndown; narrow from/to",
+            stats::MAX_POINTS
+        )));
+    }
+    Ok(Json(state.storage.get_burndown(
+        &id,
+        by_estimate,
+        iteration_days,
+        from,
+        to,
+    )))
+}
+
 pub async fn archive_project(
     State(state): State<AppState>,
     identity: Identity,
@@ -145,3 +202,76 @@ pub async fn get_schedule(
     );
     Ok(Json(entries))
 }
+
+#[derive(Deserialize)]
+pub struct ScheduleIcsQuery {
+    /// API key for calendar clients that cannot send an Authorization header
+    pub token: Option<String>,
+}
+
+/// GET /api/projects/:id/schedule.ics — iCalendar feed of scheduled tasks, due dates and
+/// recurring series, for subscribing from calendar apps.
+pub async fn get_schedule_ics(
+    State(state): State<AppState>,
+    // None when the request carries no usable Authorization and tenants exist; the token
+    // may still authenticate it
+    identity: Option<Identity>,
+    Path(id): Path<String>,
+    Query(query): Query<ScheduleIcsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let identity = match (identity, query.token) {
+        (Some(identity @ (Identity::AgentIdentity { .. } | Identity::Human { .. })), _) => identity,
+        (_, Some(token)) => {
+            let path = format!("/api/projects/{}/schedule.ics", id);
+            match auth::resolve_api_key(&state, &token, &Method::GET, &path) {
+                Ok(Some(identity)) => identity,
+                Ok(None) => {
+                    return Err((
+                        StatusCode::UNAUTHORIZED,
+                        Json(serde_json::json!({"error": "Invalid token"})),
+                    ))
+                }
+                Err(e) => {
+                    return Err((StatusCode::FORBIDDEN, Json(serde_json::json!({"error": e}))))
+                }
+            }
+        }
+        (Some(identity), None) => identity,
+        (None, None) => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({"error": "Authentication required"})),
+            ))
+        }
+    };
+
+    let project = state
+        .storage
+        .get_project(identity.tenant_id(), &id)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Project not found"})),
+        ))?;
+
+    let tasks = state.storage.list_tasks(
+        identity.tenant_id(),
+        &TaskFilters {
+            project_id: Some(id),
+            status: None,
+            priority: None,
+            assignee_id: None,
+            tag: None,
+        },
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "inline; filename=\"schedule.ics\"",
+            ),
+        ],
+        ical::render_schedule(&project, &tasks),
+    ))
+}
@@ -1,15 +1,105 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
     Json,
 };
 use chrono::Utc;
 
 use crate::app::AppState;
 use crate::events::Event;
-use crate::handlers::webhooks;
+use crate::handlers::{artifacts, webhooks};
 use opengate_models::*;
 
+/// Where a question lives: on a task (`/api/tasks/:id/questions/..`), or directly on a
+/// project (`/api/projects/:id/questions/..`) when no task owns it.
+struct QuestionScope {
+    project_id: String,
+    task: Option<Task>,
+    /// Task title, or the project name — used in notification titles
+    subject: String,
+}
+
+impl QuestionScope {
+    fn task_id(&self) -> Option<&str> {
+        self.task.as_ref().map(|t| t.id.as_str())
+    }
+
+    fn assignee_id(&self) -> Option<String> {
+        self.task.as_ref().and_then(|t| t.assignee_id.clone())
+    }
+
+    fn not_found(&self) -> (StatusCode, Json<serde_json::Value>) {
+        let error = if self.task.is_some() {
+            "Question not found for this task"
+        } else {
+            "Question not found for this project"
+        };
+        (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": error})),
+        )
+    }
+}
+
+fn task_scope(
+    state: &AppState,
+    identity: &Identity,
+    task_id: &str,
+) -> Result<QuestionScope, (StatusCode, Json<serde_json::Value>)> {
+    let task = state
+        .storage
+        .get_task(identity.tenant_id(), task_id)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Task not found"})),
+        ))?;
+    Ok(QuestionScope {
+        project_id: task.project_id.clone(),
+        subject: task.title.clone(),
+        task: Some(task),
+    })
+}
+
+fn project_scope(
+    state: &AppState,
+    identity: &Identity,
+    project_id: &str,
+) -> Result<QuestionScope, (StatusCode, Json<serde_json::Value>)> {
+    let project = state
+        .storage
+        .get_project(identity.tenant_id(), project_id)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Project not found"})),
+        ))?;
+    Ok(QuestionScope {
+        project_id: project.id,
+        subject: project.name,
+        task: None,
+    })
+}
+
+/// The question `question_id`, if it belongs to `scope`.
+fn scoped_question(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    question_id: &str,
+) -> Result<TaskQuestion, (StatusCode, Json<serde_json::Value>)> {
+    let question = state
+        .storage
+        .get_question(identity.tenant_id(), question_id)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Question not found"})),
+        ))?;
+    if question.task_id.as_deref() != scope.task_id() || question.project_id != scope.project_id {
+        return Err(scope.not_found());
+    }
+    Ok(question)
+}
+
 /// POST /api/tasks/:id/questions
 pub async fn create_question(
     State(state): State<AppState>,
@@ -17,18 +107,50 @@ pub async fn create_question(
     Path(task_id): Path<String>,
     Json(input): Json<CreateQuestion>,
 ) -> Result<(StatusCode, Json<TaskQuestion>), (StatusCode, Json<serde_json::Value>)> {
-    let task = state
-        .storage
-        .get_task(identity.tenant_id(), &task_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Task not found"})),
-        ))?;
+    validate_priority(&input)?;
+    let scope = task_scope(&state, &identity, &task_id)?;
+    Ok(ask_question(&state, &identity, &scope, &input))
+}
 
+/// POST /api/projects/:id/questions — a question no task owns ("which region do we
+/// deploy to?"). Same targeting and resolution flow as task questions.
+pub async fn create_project_question(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Json(input): Json<CreateQuestion>,
+) -> Result<(StatusCode, Json<TaskQuestion>), (StatusCode, Json<serde_json::Value>)> {
+    validate_priority(&input)?;
+    let scope = project_scope(&state, &identity, &project_id)?;
+    Ok(ask_question(&state, &identity, &scope, &input))
+}
+
+fn validate_priority(input: &CreateQuestion) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match input.priority.as_deref() {
+        Some(p) if Priority::from_str(p).is_none() => Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!(
+                    "Invalid priority '{}'. Must be one of: critical, high, medium, low",
+                    p
+                )
+            })),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn ask_question(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    input: &CreateQuestion,
+) -> (StatusCode, Json<TaskQuestion>) {
     let question = state.storage.create_question(
         identity.tenant_id(),
-        &task_id,
-        &input,
+        &scope.project_id,
+        scope.task_id(),
+        input,
         identity.author_type(),
         identity.author_id(),
     );
@@ -61,31 +183,33 @@ pub async fn create_question(
     // Emit to broadcast EventBus for real-time WebSocket subscribers
     state.event_bus.emit(Event {
         event_type: "task.question_asked".to_string(),
-        project_id: Some(task.project_id.clone()),
-        agent_id: task.assignee_id.clone(),
+        project_id: Some(scope.project_id.clone()),
+        agent_id: scope.assignee_id(),
+        event_id: None,
         data: serde_json::json!({
             "question_id": question.id,
             "question": question.question,
-            "task_id": task_id,
+            "task_id": scope.task_id(),
         }),
         timestamp: Utc::now(),
     });
 
     // Emit task.question_asked event
     let payload = serde_json::json!({
-        "task_title": task.title,
+        "task_title": scope.subject,
         "actor_name": identity.display_name(),
         "question_id": question.id,
         "question": question.question,
         "question_type": question.question_type,
+        "priority": question.priority,
         "target_type": question.target_type,
         "target_id": question.target_id,
     });
     let mut pending = state.storage.emit_event(
         identity.tenant_id(),
         "task.question_asked",
-        Some(&task_id),
-        &task.project_id,
+        scope.task_id(),
+        &scope.project_id,
         identity.author_type(),
         identity.author_id(),
         &payload,
@@ -95,28 +219,29 @@ pub async fn create_question(
     if let Some(targets) = auto_targets {
         let event_id = state.storage.get_last_event_id(identity.tenant_id());
         let question_preview: String = question.question.chars().take(200).collect();
-        let task_title = &task.title;
+        let subject = &scope.subject;
 
         match targets.len() {
             0 => {
                 // No matches — notify task creator if they are an agent
-                if let Some(creator_agent) = state
-                    .storage
-                    .get_agent(identity.tenant_id(), &task.created_by)
-                {
+                let creator_agent = scope
+                    .task
+                    .as_ref()
+                    .and_then(|t| state.storage.get_agent(identity.tenant_id(), &t.created_by));
+                if let Some(creator_agent) = creator_agent {
                     if creator_agent.id != identity.author_id() {
                         pending.push(state.storage.insert_question_notification(
                             identity.tenant_id(),
                             &creator_agent.id,
                             event_id,
                             "question_asked",
-                            &format!("Unrouted question on: {}", task_title),
+                            &format!("Unrouted question on: {}", subject),
                             Some(&format!(
                                 "No capability match for '{}'. Question: {}",
                                 question.required_capability.as_deref().unwrap_or(""),
                                 question_preview
                             )),
-                            Some(task_id.as_str()),
+                            scope.task_id(),
                         ));
                     }
                 }
@@ -136,9 +261,9 @@ pub async fn create_question(
                             &target.target_id,
                             event_id,
                             "question_asked",
-                            &format!("Question on: {}", task_title),
+                            &format!("Question on: {}", subject),
                             Some(&question_preview),
-                            Some(task_id.as_str()),
+                            scope.task_id(),
                         ));
                     }
                     // User notifications stored for bridge compatibility
@@ -150,7 +275,7 @@ pub async fn create_question(
 
     webhooks::fire_notification_webhooks(state.storage.clone(), pending);
 
-    Ok((StatusCode::CREATED, Json(question)))
+    (StatusCode::CREATED, Json(question))
 }
 
 /// GET /api/tasks/:id/questions
@@ -184,33 +309,28 @@ pub async fn get_question(
     identity: Identity,
     Path((task_id, question_id)): Path<(String, String)>,
 ) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
-    if state
-        .storage
-        .get_task(identity.tenant_id(), &task_id)
-        .is_none()
-    {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Task not found"})),
-        ));
-    }
-
-    let question = state
-        .storage
-        .get_question(identity.tenant_id(), &question_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found"})),
-        ))?;
-
-    if question.task_id != task_id {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found for this task"})),
-        ));
-    }
+    let scope = task_scope(&state, &identity, &task_id)?;
+    Ok(Json(scoped_question(
+        &state,
+        &identity,
+        &scope,
+        &question_id,
+    )?))
+}
 
-    Ok(Json(question))
+/// GET /api/projects/:id/questions/:qid
+pub async fn get_project_question(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((project_id, question_id)): Path<(String, String)>,
+) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
+    let scope = project_scope(&state, &identity, &project_id)?;
+    Ok(Json(scoped_question(
+        &state,
+        &identity,
+        &scope,
+        &question_id,
+    )?))
 }
 
 /// POST /api/tasks/:id/questions/:qid/resolve
@@ -220,34 +340,35 @@ pub async fn resolve_question(
     Path((task_id, question_id)): Path<(String, String)>,
     Json(input): Json<ResolveQuestion>,
 ) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
-    let task = state
-        .storage
-        .get_task(identity.tenant_id(), &task_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Task not found"})),
-        ))?;
+    let scope = task_scope(&state, &identity, &task_id)?;
+    resolve_in(&state, &identity, &scope, &question_id, &input)
+}
 
-    // Verify question belongs to this task
-    let existing = state
-        .storage
-        .get_question(identity.tenant_id(), &question_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found"})),
-        ))?;
-    if existing.task_id != task_id {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found for this task"})),
-        ));
-    }
+/// POST /api/projects/:id/questions/:qid/resolve
+pub async fn resolve_project_question(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((project_id, question_id)): Path<(String, String)>,
+    Json(input): Json<ResolveQuestion>,
+) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
+    let scope = project_scope(&state, &identity, &project_id)?;
+    resolve_in(&state, &identity, &scope, &question_id, &input)
+}
+
+fn resolve_in(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    question_id: &str,
+    input: &ResolveQuestion,
+) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
+    let existing = scoped_question(state, identity, scope, question_id)?;
 
     let question = state
         .storage
         .resolve_question(
             identity.tenant_id(),
-            &question_id,
+            question_id,
             &input.resolution,
             identity.author_type(),
             identity.author_id(),
@@ -260,19 +381,20 @@ pub async fn resolve_question(
     // Emit to broadcast EventBus for real-time WebSocket subscribers
     state.event_bus.emit(Event {
         event_type: "task.question_resolved".to_string(),
-        project_id: Some(task.project_id.clone()),
-        agent_id: task.assignee_id.clone(),
+        project_id: Some(scope.project_id.clone()),
+        agent_id: scope.assignee_id(),
+        event_id: None,
         data: serde_json::json!({
             "question_id": question.id,
             "resolution": question.resolution,
-            "task_id": task_id,
+            "task_id": scope.task_id(),
         }),
         timestamp: Utc::now(),
     });
 
     // Emit task.question_resolved event
     let payload = serde_json::json!({
-        "task_title": task.title,
+        "task_title": scope.subject,
         "actor_name": identity.display_name(),
         "question_id": question.id,
         "resolution": question.resolution,
@@ -282,8 +404,8 @@ pub async fn resolve_question(
     let mut pending = state.storage.emit_event(
         identity.tenant_id(),
         "task.question_resolved",
-        Some(&task_id),
-        &task.project_id,
+        scope.task_id(),
+        &scope.project_id,
         identity.author_type(),
         identity.author_id(),
         &payload,
@@ -304,13 +426,13 @@ pub async fn resolve_question(
             &existing.asked_by_id,
             event_id,
             "question_resolved",
-            &format!("Question resolved on: {}", task.title),
+            &format!("Question resolved on: {}", scope.subject),
             Some(&format!(
                 "{}: {}",
                 identity.display_name(),
                 resolution_preview
             )),
-            Some(task_id.as_str()),
+            scope.task_id(),
         ));
     }
 
@@ -343,7 +465,7 @@ pub async fn my_questions(
     Ok(Json(questions))
 }
 
-/// GET /api/projects/:id/questions
+/// GET /api/projects/:id/questions — task questions and project-level questions alike.
 pub async fn project_questions(
     State(state): State<AppState>,
     identity: Identity,
@@ -378,32 +500,51 @@ pub async fn create_reply(
     Path((task_id, question_id)): Path<(String, String)>,
     Json(input): Json<CreateReply>,
 ) -> Result<(StatusCode, Json<QuestionReply>), (StatusCode, Json<serde_json::Value>)> {
-    let task = state
-        .storage
-        .get_task(identity.tenant_id(), &task_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Task not found"})),
-        ))?;
-    let question = state
-        .storage
-        .get_question(identity.tenant_id(), &question_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found"})),
-        ))?;
-    if question.task_id != task_id {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found for this task"})),
-        ));
+    let scope = task_scope(&state, &identity, &task_id)?;
+    reply_in(&state, &identity, &scope, &question_id, &input)
+}
+
+/// POST /api/projects/:id/questions/:qid/replies
+pub async fn create_project_reply(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((project_id, question_id)): Path<(String, String)>,
+    Json(input): Json<CreateReply>,
+) -> Result<(StatusCode, Json<QuestionReply>), (StatusCode, Json<serde_json::Value>)> {
+    let scope = project_scope(&state, &identity, &project_id)?;
+    reply_in(&state, &identity, &scope, &question_id, &input)
+}
+
+fn reply_in(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    question_id: &str,
+    input: &CreateReply,
+) -> Result<(StatusCode, Json<QuestionReply>), (StatusCode, Json<serde_json::Value>)> {
+    let question = scoped_question(state, identity, scope, question_id)?;
+
+    if let Some(ref parent_id) = input.parent_reply_id {
+        let parent_found = state
+            .storage
+            .list_replies(identity.tenant_id(), question_id)
+            .iter()
+            .any(|r| &r.id == parent_id);
+        if !parent_found {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(
+                    serde_json::json!({"error": "parent_reply_id does not belong to this question"}),
+                ),
+            ));
+        }
     }
 
     let is_resolution = input.is_resolution.unwrap_or(false);
     let reply = state.storage.create_reply(
         identity.tenant_id(),
-        &question_id,
-        &input,
+        question_id,
+        input,
         identity.author_type(),
         identity.author_id(),
     );
@@ -416,14 +557,16 @@ pub async fn create_reply(
     };
     state.event_bus.emit(Event {
         event_type: bus_event_type.to_string(),
-        project_id: Some(task.project_id.clone()),
-        agent_id: task.assignee_id.clone(),
+        project_id: Some(scope.project_id.clone()),
+        agent_id: scope.assignee_id(),
+        event_id: None,
         data: serde_json::json!({
             "question_id": question_id,
             "reply_id": reply.id,
+            "parent_reply_id": reply.parent_reply_id,
             "body": reply.body,
             "is_resolution": is_resolution,
-            "task_id": task_id,
+            "task_id": scope.task_id(),
         }),
         timestamp: Utc::now(),
     });
@@ -435,10 +578,11 @@ pub async fn create_reply(
         "task.question_replied"
     };
     let payload = serde_json::json!({
-        "task_title": task.title,
+        "task_title": scope.subject,
         "actor_name": identity.display_name(),
         "question_id": question_id,
         "reply_id": reply.id,
+        "parent_reply_id": reply.parent_reply_id,
         "body": reply.body,
         "is_resolution": is_resolution,
         "asked_by_type": question.asked_by_type,
@@ -447,8 +591,8 @@ pub async fn create_reply(
     let mut pending = state.storage.emit_event(
         identity.tenant_id(),
         event_type,
-        Some(&task_id),
-        &task.project_id,
+        scope.task_id(),
+        &scope.project_id,
         identity.author_type(),
         identity.author_id(),
         &payload,
@@ -464,9 +608,9 @@ pub async fn create_reply(
         "question_replied"
     };
     let notif_title = if is_resolution {
-        format!("Question resolved on: {}", task.title)
+        format!("Question resolved on: {}", scope.subject)
     } else {
-        format!("Reply on: {}", task.title)
+        format!("Reply on: {}", scope.subject)
     };
 
     // Collect all agent participants to notify (asker + reply authors), excluding current actor
@@ -480,7 +624,7 @@ pub async fn create_reply(
     // Notify all previous reply authors who are agents (thread participants)
     let all_replies = state
         .storage
-        .list_replies(identity.tenant_id(), &question_id);
+        .list_replies(identity.tenant_id(), question_id);
     for r in &all_replies {
         if r.author_type == "agent" && r.author_id != identity.author_id() {
             notified_agents.insert(r.author_id.clone());
@@ -502,7 +646,7 @@ pub async fn create_reply(
             notif_type,
             &notif_title,
             Some(&format!("{}: {}", actor_name, reply_preview)),
-            Some(task_id.as_str()),
+            scope.task_id(),
         ));
     }
 
@@ -511,40 +655,135 @@ pub async fn create_reply(
     Ok((StatusCode::CREATED, Json(reply)))
 }
 
-/// GET /api/tasks/:id/questions/:qid/replies
+/// GET /api/tasks/:id/questions/:qid/replies — flat and chronological, or nested
+/// under their parents with `?threaded=true`.
 pub async fn list_replies(
     State(state): State<AppState>,
     identity: Identity,
     Path((task_id, question_id)): Path<(String, String)>,
-) -> Result<Json<Vec<QuestionReply>>, (StatusCode, Json<serde_json::Value>)> {
-    if state
+    Query(query): Query<ReplyListQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let scope = task_scope(&state, &identity, &task_id)?;
+    list_replies_in(&state, &identity, &scope, &question_id, &query)
+}
+
+/// GET /api/projects/:id/questions/:qid/replies
+pub async fn list_project_replies(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((project_id, question_id)): Path<(String, String)>,
+    Query(query): Query<ReplyListQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let scope = project_scope(&state, &identity, &project_id)?;
+    list_replies_in(&state, &identity, &scope, &question_id, &query)
+}
+
+fn list_replies_in(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    question_id: &str,
+    query: &ReplyListQuery,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    scoped_question(state, identity, scope, question_id)?;
+
+    let replies = state
         .storage
-        .get_task(identity.tenant_id(), &task_id)
-        .is_none()
-    {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Task not found"})),
-        ));
+        .list_replies(identity.tenant_id(), question_id);
+    if query.threaded.unwrap_or(false) {
+        return Ok(Json(build_reply_threads(replies)).into_response());
     }
-    let question = state
+    Ok(Json(replies).into_response())
+}
+
+fn attach_artifact(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    question_id: &str,
+    reply_id: Option<&str>,
+    input: &CreateArtifact,
+) -> Result<(StatusCode, Json<QuestionAttachment>), (StatusCode, Json<serde_json::Value>)> {
+    artifacts::validate_artifact(input)?;
+    let question = scoped_question(state, identity, scope, question_id)?;
+    let attachment = state
         .storage
-        .get_question(identity.tenant_id(), &question_id)
+        .create_question_attachment(
+            identity.tenant_id(),
+            &question.id,
+            reply_id,
+            input,
+            identity.author_type(),
+            identity.author_id(),
+        )
         .ok_or((
             StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found"})),
+            Json(serde_json::json!({"error": "Reply not found for this question"})),
         ))?;
-    if question.task_id != task_id {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found for this task"})),
-        ));
-    }
 
-    let replies = state
-        .storage
-        .list_replies(identity.tenant_id(), &question_id);
-    Ok(Json(replies))
+    let payload = serde_json::json!({
+        "task_title": scope.subject,
+        "actor_name": identity.display_name(),
+        "question_id": question.id,
+        "reply_id": attachment.reply_id,
+        "artifact_name": attachment.name,
+        "artifact_type": attachment.artifact_type,
+    });
+    let pending = state.storage.emit_event(
+        identity.tenant_id(),
+        "task.question_artifact_added",
+        scope.task_id(),
+        &scope.project_id,
+        identity.author_type(),
+        identity.author_id(),
+        &payload,
+    );
+    webhooks::fire_notification_webhooks(state.storage.clone(), pending);
+
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+/// POST /api/tasks/:id/questions/:qid/artifacts
+pub async fn create_question_artifact(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((task_id, question_id)): Path<(String, String)>,
+    Json(input): Json<CreateArtifact>,
+) -> Result<(StatusCode, Json<QuestionAttachment>), (StatusCode, Json<serde_json::Value>)> {
+    let scope = task_scope(&state, &identity, &task_id)?;
+    attach_artifact(&state, &identity, &scope, &question_id, None, &input)
+}
+
+/// POST /api/tasks/:id/questions/:qid/replies/:rid/artifacts
+pub async fn create_reply_artifact(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((task_id, question_id, reply_id)): Path<(String, String, String)>,
+    Json(input): Json<CreateArtifact>,
+) -> Result<(StatusCode, Json<QuestionAttachment>), (StatusCode, Json<serde_json::Value>)> {
+    let scope = task_scope(&state, &identity, &task_id)?;
+    attach_artifact(
+        &state,
+        &identity,
+        &scope,
+        &question_id,
+        Some(&reply_id),
+        &input,
+    )
+}
+
+/// GET /api/tasks/:id/questions/:qid/artifacts — the question's and its replies' artifacts.
+pub async fn list_question_artifacts(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((task_id, question_id)): Path<(String, String)>,
+) -> Result<Json<Vec<QuestionAttachment>>, (StatusCode, Json<serde_json::Value>)> {
+    let scope = task_scope(&state, &identity, &task_id)?;
+    scoped_question(&state, &identity, &scope, &question_id)?;
+    Ok(Json(state.storage.list_question_attachments(
+        identity.tenant_id(),
+        &question_id,
+    )))
 }
 
 /// POST /api/tasks/:id/questions/:qid/dismiss
@@ -554,30 +793,33 @@ pub async fn dismiss_question(
     Path((task_id, question_id)): Path<(String, String)>,
     Json(input): Json<DismissQuestion>,
 ) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
-    let task = state
-        .storage
-        .get_task(identity.tenant_id(), &task_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Task not found"})),
-        ))?;
-    let existing = state
-        .storage
-        .get_question(identity.tenant_id(), &question_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found"})),
-        ))?;
-    if existing.task_id != task_id {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found for this task"})),
-        ));
-    }
+    let scope = task_scope(&state, &identity, &task_id)?;
+    dismiss_in(&state, &identity, &scope, &question_id, &input)
+}
+
+/// POST /api/projects/:id/questions/:qid/dismiss
+pub async fn dismiss_project_question(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((project_id, question_id)): Path<(String, String)>,
+    Json(input): Json<DismissQuestion>,
+) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
+    let scope = project_scope(&state, &identity, &project_id)?;
+    dismiss_in(&state, &identity, &scope, &question_id, &input)
+}
+
+fn dismiss_in(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    question_id: &str,
+    input: &DismissQuestion,
+) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
+    scoped_question(state, identity, scope, question_id)?;
 
     let question = state
         .storage
-        .dismiss_question(identity.tenant_id(), &question_id, &input.reason)
+        .dismiss_question(identity.tenant_id(), question_id, &input.reason)
         .ok_or((
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({"error": "Question is not open"})),
@@ -585,18 +827,19 @@ pub async fn dismiss_question(
 
     state.event_bus.emit(Event {
         event_type: "task.question_dismissed".to_string(),
-        project_id: Some(task.project_id.clone()),
-        agent_id: task.assignee_id.clone(),
+        project_id: Some(scope.project_id.clone()),
+        agent_id: scope.assignee_id(),
+        event_id: None,
         data: serde_json::json!({
             "question_id": question_id,
             "reason": input.reason,
-            "task_id": task_id,
+            "task_id": scope.task_id(),
         }),
         timestamp: Utc::now(),
     });
 
     let payload = serde_json::json!({
-        "task_title": task.title,
+        "task_title": scope.subject,
         "actor_name": identity.display_name(),
         "question_id": question_id,
         "reason": input.reason,
@@ -604,8 +847,8 @@ pub async fn dismiss_question(
     let pending = state.storage.emit_event(
         identity.tenant_id(),
         "task.question_dismissed",
-        Some(&task_id),
-        &task.project_id,
+        scope.task_id(),
+        &scope.project_id,
         identity.author_type(),
         identity.author_id(),
         &payload,
@@ -622,32 +865,35 @@ pub async fn assign_question(
     Path((task_id, question_id)): Path<(String, String)>,
     Json(input): Json<AssignQuestion>,
 ) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
-    let task = state
-        .storage
-        .get_task(identity.tenant_id(), &task_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Task not found"})),
-        ))?;
-    let existing = state
-        .storage
-        .get_question(identity.tenant_id(), &question_id)
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found"})),
-        ))?;
-    if existing.task_id != task_id {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "Question not found for this task"})),
-        ));
-    }
+    let scope = task_scope(&state, &identity, &task_id)?;
+    assign_in(&state, &identity, &scope, &question_id, &input)
+}
+
+/// POST /api/projects/:id/questions/:qid/assign
+pub async fn assign_project_question(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path((project_id, question_id)): Path<(String, String)>,
+    Json(input): Json<AssignQuestion>,
+) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
+    let scope = project_scope(&state, &identity, &project_id)?;
+    assign_in(&state, &identity, &scope, &question_id, &input)
+}
+
+fn assign_in(
+    state: &AppState,
+    identity: &Identity,
+    scope: &QuestionScope,
+    question_id: &str,
+    input: &AssignQuestion,
+) -> Result<Json<TaskQuestion>, (StatusCode, Json<serde_json::Value>)> {
+    let existing = scoped_question(state, identity, scope, question_id)?;
 
     let question = state
         .storage
         .assign_question(
             identity.tenant_id(),
-            &question_id,
+            question_id,
             &input.target_type,
             &input.target_id,
         )
@@ -658,31 +904,33 @@ pub async fn assign_question(
 
     state.event_bus.emit(Event {
         event_type: "task.question_assigned".to_string(),
-        project_id: Some(task.project_id.clone()),
-        agent_id: task.assignee_id.clone(),
+        project_id: Some(scope.project_id.clone()),
+        agent_id: scope.assignee_id(),
+        event_id: None,
         data: serde_json::json!({
             "question_id": question_id,
             "target_type": input.target_type,
             "target_id": input.target_id,
-            "task_id": task_id,
+            "task_id": scope.task_id(),
         }),
         timestamp: Utc::now(),
     });
 
     // emit_event will auto-route notification to target via route_event_notifications
     let payload = serde_json::json!({
-        "task_title": task.title,
+        "task_title": scope.subject,
         "actor_name": identity.display_name(),
         "question_id": question_id,
         "question": existing.question,
+        "priority": existing.priority,
         "target_type": input.target_type,
         "target_id": input.target_id,
     });
     let pending = state.storage.emit_event(
         identity.tenant_id(),
         "task.question_assigned",
-        Some(&task_id),
-        &task.project_id,
+        scope.task_id(),
+        &scope.project_id,
         identity.author_type(),
         identity.author_id(),
         &payload,
@@ -0,0 +1,140 @@
+//! `GET /api/events/stream` — the WebSocket event feed as Server-Sent Events, for
+//! clients behind proxies that drop upgrades or scripts that can only hold a GET open.
+//!
+//! Authentication is the agent's API key as a bearer header. Subscription is fixed per
+//! connection by query parameters mirroring the WS `subscribe` message:
+//! `events` (comma-separated patterns, `task.*` style, default all), `agent_id`
+//! (`self` resolves to the caller) and `project_id`. `format=cloudevents` (or
+//! `Accept: application/cloudevents+json`) sends each event's data as a CloudEvent.
+
+use std::convert::Infallible;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+    Json,
+};
+use futures_util::stream::{self, Stream};
+use serde::Deserialize;
+
+use crate::app::AppState;
+use crate::cloudevents::{self, EventFormat};
+use crate::events::Delivery;
+use crate::handlers::ws::{subscription_matches, Subscription, SubscriptionFilter, TenantScope};
+use opengate_models::Identity;
+
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    pub events: Option<String>,
+    pub agent_id: Option<String>,
+    pub project_id: Option<String>,
+    pub format: Option<String>,
+}
+
+pub async fn event_stream(
+    State(state): State<AppState>,
+    identity: Identity,
+    headers: HeaderMap,
+    Query(query): Query<StreamQuery>,
+) -> Result<
+    Sse<impl Stream<Item = Result<SseEvent, Infallible>>>,
+    (StatusCode, Json<serde_json::Value>),
+> {
+    let Identity::AgentIdentity {
+        id: agent_id,
+        tenant_id,
+        ..
+    } = identity
+    else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    };
+
+    let format = match query.format.as_deref() {
+        Some(f) => EventFormat::from_str(f).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": format!("Invalid format '{}'. Must be one of: opengate, cloudevents", f)
+                })),
+            )
+        })?,
+        None if headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|accept| accept.contains(cloudevents::CONTENT_TYPE)) =>
+        {
+            EventFormat::CloudEvents
+        }
+        None => cloudevents::default_format(),
+    };
+
+    let patterns: Vec<String> = query
+        .events
+        .as_deref()
+        .unwrap_or("*")
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect();
+    let filter =
+        (query.agent_id.is_some() || query.project_id.is_some()).then_some(SubscriptionFilter {
+            agent_id: query.agent_id,
+            project_id: query.project_id,
+        });
+    let subscription = Subscription {
+        patterns,
+        filter,
+        format,
+        replayed_through: None,
+    };
+
+    let event_rx = state
+        .event_bus
+        .subscribe_bounded(&format!("sse:{}", agent_id));
+    let scope = TenantScope::new(tenant_id.as_deref());
+    let events = stream::unfold(
+        (event_rx, subscription, agent_id, scope, state.storage),
+        |(mut event_rx, subscription, agent_id, mut scope, storage)| async move {
+            loop {
+                let next = match event_rx.recv().await {
+                    Delivery::Event(event) => {
+                        if !subscription_matches(&subscription, &event, &agent_id)
+                            || !scope.allows(&*storage, &event)
+                        {
+                            continue;
+                        }
+                        let data = match subscription.format {
+                            EventFormat::Native => event.data.clone(),
+                            EventFormat::CloudEvents => cloudevents::from_bus_event(&event),
+                        };
+                        SseEvent::default()
+                            .event(event.event_type.clone())
+                            .json_data(data)
+                            .unwrap_or_default()
+                    }
+                    Delivery::Gap(n) => SseEvent::default()
+                        .event("error")
+                        .json_data(serde_json::json!({
+                            "code": "events_lagged",
+                            "message": format!("Missed {} events", n),
+                        }))
+                        .unwrap_or_default(),
+                    // Ending the stream makes EventSource clients reconnect
+                    Delivery::Disconnected => return None,
+                };
+                return Some((Ok(next), (event_rx, subscription, agent_id, scope, storage)));
+            }
+        },
+    );
+
+    Ok(Sse::new(events).keep_alive(
+        KeepAlive::new()
+            .interval(std::time::Duration::from_secs(30))
+            .text("ping"),
+    ))
+}
@@ -1,8 +1,397 @@
-use axum::{extract::State, Json};
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Days, NaiveDate, Utc};
+use serde::Deserialize;
 
 use crate::app::AppState;
+use crate::csv;
+use crate::quotas;
 use opengate_models::*;
 
-pub async fn get_stats(State(state): State<AppState>, identity: Identity) -> Json<DashboardStats> {
-    Json(state.storage.get_stats(identity.tenant_id()))
+#[derive(Deserialize)]
+pub struct TenantUsageQuery {
+    /// For callers outside any tenant; scoped callers always get their own
+    pub tenant_id: Option<String>,
+}
+
+/// Longest series one request may ask for
+pub(crate) const MAX_POINTS: i64 = 1000;
+
+/// GET /api/stats — dashboard counts, optionally for one project, one tag and tasks
+/// created between `from` and `to`.
+pub async fn get_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(filter): Query<StatsQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let as_csv = csv::wanted(format.format.as_deref())?;
+    for (name, value) in [("from", &filter.from), ("to", &filter.to)] {
+        if value
+            .as_deref()
+            .is_some_and(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").is_err())
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": format!("{} must be a YYYY-MM-DD date", name)
+                })),
+            ));
+        }
+    }
+    let stats = state.storage.get_stats(identity.tenant_id(), &filter);
+    if !as_csv {
+        return Ok(Json(stats).into_response());
+    }
+    // Flattened to one metric per row; activity isn't tabular and is left out
+    let mut rows = vec![
+        ["total_tasks".to_string(), stats.total_tasks.to_string()],
+        [
+            "total_projects".to_string(),
+            stats.total_projects.to_string(),
+        ],
+        ["active_agents".to_string(), stats.active_agents.to_string()],
+    ];
+    let by_status: BTreeMap<_, _> = stats.tasks_by_status.into_iter().collect();
+    for (status, count) in by_status {
+        rows.push([format!("tasks.{}", status), count.to_string()]);
+    }
+    Ok(csv::table("stats", &["metric", "value"], rows))
+}
+
+/// GET /api/tenant/usage — the caller's tenant usage against its `[tenant_limits]`.
+/// Callers outside any tenant name one with `?tenant_id=`.
+pub async fn tenant_usage(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<TenantUsageQuery>,
+) -> Result<Json<TenantUsage>, (StatusCode, Json<serde_json::Value>)> {
+    if matches!(identity, Identity::Anonymous) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    }
+    match identity.tenant_id().or(query.tenant_id.as_deref()) {
+        Some(tenant) => Ok(Json(quotas::usage(&*state.storage, tenant))),
+        None => Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "Not in a tenant; pass tenant_id"})),
+        )),
+    }
+}
+
+/// `from`/`to` query dates (YYYY-MM-DD): `to` defaults to today (UTC) and `from` to 29
+/// days before it.
+pub(crate) fn date_range(
+    from: &Option<String>,
+    to: &Option<String>,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let date = |name: &str, value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|v| {
+                NaiveDate::parse_from_str(v, "%Y-%m-%d")
+                    .map_err(|_| format!("{} must be a YYYY-MM-DD date", name))
+            })
+            .transpose()
+    };
+    let to = date("to", to)?.unwrap_or_else(|| Utc::now().date_naive());
+    let from = date("from", from)?.unwrap_or(to - Days::new(29));
+    if from > to {
+        return Err("from must not be after to".to_string());
+    }
+    Ok((from, to))
+}
+
+/// GET /api/stats/timeseries — task completions, creations or blocks per day or week,
+/// for trend charts. Scoped callers only see their tenant's tasks.
+pub async fn get_timeseries(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<TimeseriesQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": error })),
+        )
+    };
+    if matches!(identity, Identity::Anonymous) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    }
+    let as_csv = csv::wanted(format.format.as_deref())?;
+    let metric = query.metric.as_deref().unwrap_or("");
+    if !["tasks_completed", "tasks_created", "tasks_blocked"].contains(&metric) {
+        return Err(bad_request(
+            "metric must be tasks_completed, tasks_created or tasks_blocked".to_string(),
+        ));
+    }
+    let interval = query.interval.as_deref().unwrap_or("day");
+    if !["day", "week"].contains(&interval) {
+        return Err(bad_request("interval must be day or week".to_string()));
+    }
+    let (from, to) = date_range(&query.from, &query.to).map_err(bad_request)?;
+    let days_per_point = if interval == "week" { 7 } else { 1 };
+    if (to - from).num_days() / days_per_point >= MAX_POINTS {
+        return Err(bad_request(format!(
+            "At most {} points per series; narrow from/to or use a longer interval",
+            MAX_POINTS
+        )));
+    }
+    let series = state.storage.get_timeseries(
+        identity.tenant_id(),
+        query.project_id.as_deref(),
+        metric,
+        interval,
+        from,
+        to,
+    );
+    if !as_csv {
+        return Ok(Json(series).into_response());
+    }
+    Ok(csv::table(
+        &series.metric,
+        &["date", "count"],
+        series
+            .points
+            .into_iter()
+            .map(|p| [p.date, p.count.to_string()]),
+    ))
+}
+
+/// GET /api/stats/agents — every agent's open, in-progress and review load, completions
+/// over the last `?days=` (default 7) and time since its last heartbeat.
+pub async fn get_agent_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<AgentStatsQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    if matches!(identity, Identity::Anonymous) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    }
+    let as_csv = csv::wanted(format.format.as_deref())?;
+    let days = query.days.unwrap_or(7);
+    if !(1..=365).contains(&days) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "days must be between 1 and 365"})),
+        ));
+    }
+    let stats = state
+        .storage
+        .get_agent_workloads(identity.tenant_id(), days);
+    if !as_csv {
+        return Ok(Json(stats).into_response());
+    }
+    Ok(csv::table(
+        "agents",
+        &[
+            "agent_id",
+            "name",
+            "role",
+            "presence",
+            "open",
+            "in_progress",
+            "in_review",
+            "reviewing",
+            "completed",
+            "throughput_per_day",
+            "last_seen_at",
+            "seconds_since_heartbeat",
+        ],
+        stats.agents.into_iter().map(|a| {
+            [
+                a.agent_id,
+                a.name,
+                a.role,
+                a.presence,
+                a.open.to_string(),
+                a.in_progress.to_string(),
+                a.in_review.to_string(),
+                a.reviewing.to_string(),
+                a.completed.to_string(),
+                a.throughput_per_day.to_string(),
+                csv::opt(&a.last_seen_at),
+                csv::opt(&a.seconds_since_heartbeat),
+            ]
+        }),
+    ))
+}
+
+/// GET /api/stats/reviews — time to first review and time in review per reviewer and
+/// per project, slowest first.
+pub async fn get_review_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<ReviewStatsQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    if matches!(identity, Identity::Anonymous) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    }
+    let as_csv = csv::wanted(format.format.as_deref())?;
+    let days = query.days.unwrap_or(30);
+    if !(1..=365).contains(&days) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "days must be between 1 and 365"})),
+        ));
+    }
+    let report =
+        state
+            .storage
+            .get_review_latency(identity.tenant_id(), query.project_id.as_deref(), days);
+    if !as_csv {
+        return Ok(Json(report).into_response());
+    }
+    // Reviewers, then projects, told apart by the first column
+    let rows = report
+        .reviewers
+        .into_iter()
+        .map(|r| ("reviewer", r))
+        .chain(report.projects.into_iter().map(|p| ("project", p)))
+        .map(|(kind, r)| {
+            [
+                kind.to_string(),
+                r.id,
+                csv::opt(&r.name),
+                r.completed.to_string(),
+                r.pending.to_string(),
+                csv::opt(&r.avg_time_to_first_review_secs),
+                csv::opt(&r.avg_time_in_review_secs),
+                csv::opt(&r.oldest_pending_secs),
+            ]
+        });
+    Ok(csv::table(
+        "reviews",
+        &[
+            "kind",
+            "id",
+            "name",
+            "completed",
+            "pending",
+            "avg_time_to_first_review_secs",
+            "avg_time_in_review_secs",
+            "oldest_pending_secs",
+        ],
+        rows,
+    ))
+}
+
+/// GET /api/stats/costs — reported task spend per day or week, by agent cost tier,
+/// model, project or agent, to compare spend before and after routing changes.
+pub async fn get_cost_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<CostStatsQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": error })),
+        )
+    };
+    if matches!(identity, Identity::Anonymous) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    }
+    let as_csv = csv::wanted(format.format.as_deref())?;
+    let group_by = query.group_by.as_deref().unwrap_or("cost_tier");
+    if !["cost_tier", "model", "project", "agent"].contains(&group_by) {
+        return Err(bad_request(
+            "group_by must be cost_tier, model, project or agent".to_string(),
+        ));
+    }
+    let interval = query.interval.as_deref().unwrap_or("day");
+    if !["day", "week"].contains(&interval) {
+        return Err(bad_request("interval must be day or week".to_string()));
+    }
+    let (from, to) = date_range(&query.from, &query.to).map_err(bad_request)?;
+    let days_per_point = if interval == "week" { 7 } else { 1 };
+    if (to - from).num_days() / days_per_point >= MAX_POINTS {
+        return Err(bad_request(format!(
+            "At most {} periods per report; narrow from/to or use a longer interval",
+            MAX_POINTS
+        )));
+    }
+    let report = state.storage.get_cost_report(
+        identity.tenant_id(),
+        query.project_id.as_deref(),
+        group_by,
+        interval,
+        from,
+        to,
+    );
+    if !as_csv {
+        return Ok(Json(report).into_response());
+    }
+    Ok(csv::table(
+        "costs",
+        &[
+            "period",
+            "group",
+            "tasks",
+            "input_tokens",
+            "output_tokens",
+            "cost_usd",
+        ],
+        report.buckets.into_iter().map(|b| {
+            [
+                b.period,
+                csv::opt(&b.group),
+                b.tasks.to_string(),
+                b.input_tokens.to_string(),
+                b.output_tokens.to_string(),
+                b.cost_usd.to_string(),
+            ]
+        }),
+    ))
+}
+
+/// GET /api/stats/blocked — where work sits waiting: time in `blocked` and time held up
+/// by unfinished dependencies, per project, plus the worst blockers and blocked tasks.
+pub async fn get_blocked_stats(
+    State(state): State<AppState>,
+    identity: Identity,
+    Query(query): Query<BlockedTimeQuery>,
+) -> Result<Json<BlockedTimeReport>, (StatusCode, Json<serde_json::Value>)> {
+    if matches!(identity, Identity::Anonymous) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Authentication required"})),
+        ));
+    }
+    let limit = query.limit.unwrap_or(10);
+    if !(1..=100).contains(&limit) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "limit must be between 1 and 100"})),
+        ));
+    }
+    Ok(Json(state.storage.get_blocked_time(
+        identity.tenant_id(),
+        query.project_id.as_deref(),
+        limit as usize,
+    )))
 }
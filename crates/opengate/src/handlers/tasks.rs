@@ -1,21 +1,34 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
     Json,
 };
 use chrono::Utc;
 
 use crate::app::AppState;
+use crate::auth::ActingIdentity;
+use crate::csv;
 use crate::events::Event;
+use crate::github;
+use crate::gitlab;
 use crate::handlers::{events, webhooks};
+use crate::linear;
+use crate::quotas::{self, Quota};
+use crate::recurrence;
 use opengate_models::*;
 
 pub async fn list_tasks_global(
     State(state): State<AppState>,
     identity: Identity,
     Query(filters): Query<TaskFilters>,
-) -> Json<Vec<Task>> {
-    Json(state.storage.list_tasks(identity.tenant_id(), &filters))
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let as_csv = csv::wanted(format.format.as_deref())?;
+    Ok(task_list(
+        state.storage.list_tasks(identity.tenant_id(), &filters),
+        as_csv,
+    ))
 }
 
 pub async fn list_tasks_by_project(
@@ -23,9 +36,59 @@ pub async fn list_tasks_by_project(
     identity: Identity,
     Path(project_id): Path<String>,
     Query(mut filters): Query<TaskFilters>,
-) -> Json<Vec<Task>> {
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let as_csv = csv::wanted(format.format.as_deref())?;
     filters.project_id = Some(project_id);
-    Json(state.storage.list_tasks(identity.tenant_id(), &filters))
+    Ok(task_list(
+        state.storage.list_tasks(identity.tenant_id(), &filters),
+        as_csv,
+    ))
+}
+
+fn task_list(tasks: Vec<Task>, as_csv: bool) -> Response {
+    if !as_csv {
+        return Json(tasks).into_response();
+    }
+    csv::table(
+        "tasks",
+        &[
+            "id",
+            "project_id",
+            "title",
+            "description",
+            "status",
+            "priority",
+            "assignee_type",
+            "assignee_id",
+            "reviewer_id",
+            "tags",
+            "due_date",
+            "scheduled_at",
+            "created_by",
+            "created_at",
+            "updated_at",
+        ],
+        tasks.into_iter().map(|t| {
+            [
+                t.id,
+                t.project_id,
+                t.title,
+                csv::opt(&t.description),
+                t.status,
+                t.priority,
+                csv::opt(&t.assignee_type),
+                csv::opt(&t.assignee_id),
+                csv::opt(&t.reviewer_id),
+                t.tags.join(";"),
+                csv::opt(&t.due_date),
+                csv::opt(&t.scheduled_at),
+                t.created_by,
+                t.created_at,
+                t.updated_at,
+            ]
+        }),
+    )
 }
 
 pub async fn create_task(
@@ -45,6 +108,18 @@ pub async fn create_task(
         ));
     }
 
+    if let Some(ref rule) = input.recurrence_rule {
+        if let Err(e) = recurrence::validate_recurrence_rule(rule) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": format!("Invalid recurrence_rule: {}", e)})),
+            ));
+        }
+    }
+
+    let tenant = state.storage.project_owner(&project_id);
+    quotas::check(&*state.storage, tenant.as_deref(), Quota::OpenTasks)?;
+
     let task = state.storage.create_task(
         identity.tenant_id(),
         &project_id,
@@ -69,6 +144,7 @@ pub async fn create_task(
         event_type: "task.created".to_string(),
         project_id: Some(task.project_id.clone()),
         agent_id: task.assignee_id.clone(),
+        event_id: None,
         data: serde_json::to_value(&task).unwrap_or_default(),
         timestamp: Utc::now(),
     });
@@ -155,6 +231,7 @@ pub async fn update_task(
                         event_type: "task.status_changed".to_string(),
                         project_id: Some(task.project_id.clone()),
                         agent_id: task.assignee_id.clone(),
+                        event_id: None,
                         data: serde_json::to_value(&task).unwrap_or_default(),
                         timestamp: Utc::now(),
                     });
@@ -168,6 +245,7 @@ pub async fn update_task(
                 event_type: "task.updated".to_string(),
                 project_id: Some(task.project_id.clone()),
                 agent_id: task.assignee_id.clone(),
+                event_id: None,
                 data: serde_json::to_value(&task).unwrap_or_default(),
                 timestamp: Utc::now(),
             });
@@ -255,7 +333,10 @@ pub async fn update_context(
 
 pub async fn claim_task(
     State(state): State<AppState>,
-    identity: Identity,
+    ActingIdentity {
+        identity,
+        delegated_by,
+    }: ActingIdentity,
     Path(id): Path<String>,
 ) -> Result<Json<Task>, (StatusCode, Json<serde_json::Value>)> {
     let (agent_id, agent_name) = match &identity {
@@ -273,6 +354,26 @@ pub async fn claim_task(
         .claim_task(identity.tenant_id(), &id, &agent_id, &agent_name)
     {
         Ok(mut task) => {
+            if let Some(ref delegator) = delegated_by {
+                state.storage.create_activity(
+                    identity.tenant_id(),
+                    &task.id,
+                    identity.author_type(),
+                    identity.author_id(),
+                    &CreateActivity {
+                        content: format!(
+                            "Claimed on behalf of {} by orchestrator {}",
+                            agent_name, delegator.name
+                        ),
+                        activity_type: Some("delegation".to_string()),
+                        metadata: Some(serde_json::json!({
+                            "action": "claim",
+                            "delegation": delegator.audit_metadata(&identity),
+                        })),
+                        mentions: None,
+                    },
+                );
+            }
             task.activities = state.storage.list_activity(identity.tenant_id(), &task.id);
             let mut pending = events::emit_task_event(
                 &*state.storage,
@@ -320,6 +421,7 @@ pub async fn release_task(
                 event_type: "task.released".to_string(),
                 project_id: Some(task.project_id.clone()),
                 agent_id: Some(identity.author_id().to_string()),
+                event_id: None,
                 data: serde_json::to_value(&task).unwrap_or_default(),
                 timestamp: Utc::now(),
             });
@@ -334,7 +436,10 @@ pub async fn release_task(
 
 pub async fn complete_task(
     State(state): State<AppState>,
-    identity: Identity,
+    ActingIdentity {
+        identity,
+        delegated_by,
+    }: ActingIdentity,
     Path(id): Path<String>,
     Json(input): Json<CompleteRequest>,
 ) -> Result<Json<Task>, (StatusCode, Json<serde_json::Value>)> {
@@ -387,10 +492,30 @@ pub async fn complete_task(
                 &CreateActivity {
                     content: summary.to_string(),
                     activity_type: Some("status_change".to_string()),
-                    metadata: None,
+                    metadata: delegated_by
+                        .as_ref()
+                        .map(|d| serde_json::json!({"delegation": d.audit_metadata(&identity)})),
                     mentions: None,
                 },
             );
+            github::link_completed_pr(
+                state.storage.clone(),
+                &identity,
+                &task,
+                input.summary.as_deref(),
+            );
+            gitlab::link_completed_mr(
+                state.storage.clone(),
+                &identity,
+                &task,
+                input.summary.as_deref(),
+            );
+            linear::export_completed(
+                state.storage.clone(),
+                &identity,
+                &task,
+                input.summary.as_deref(),
+            );
             state
                 .storage
                 .inject_upstream_outputs(identity.tenant_id(), &task);
@@ -601,6 +726,7 @@ pub async fn handoff_task(
                 event_type: "task.assigned".to_string(),
                 project_id: Some(task.project_id.clone()),
                 agent_id: task.assignee_id.clone(),
+                event_id: None,
                 data: serde_json::to_value(&task).unwrap_or_default(),
                 timestamp: Utc::now(),
             });
@@ -852,6 +978,56 @@ pub async fn list_dependents(
     ))
 }
 
+// --- Usage ---
+
+/// POST /api/tasks/:id/usage — the calling agent reports tokens and spend on a task.
+pub async fn report_usage(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    Json(input): Json<ReportUsage>,
+) -> Result<(StatusCode, Json<TaskUsage>), (StatusCode, Json<serde_json::Value>)> {
+    let Identity::AgentIdentity { id: agent_id, .. } = &identity else {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({"error": "Only agents can report usage"})),
+        ));
+    };
+    input.validate().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        )
+    })?;
+    if state.storage.get_task(identity.tenant_id(), &id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Task not found"})),
+        ));
+    }
+    let usage = state
+        .storage
+        .record_task_usage(identity.tenant_id(), &id, agent_id, &input);
+    Ok((StatusCode::CREATED, Json(usage)))
+}
+
+/// GET /api/tasks/:id/usage
+pub async fn list_usage(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TaskUsage>>, (StatusCode, Json<serde_json::Value>)> {
+    if state.storage.get_task(identity.tenant_id(), &id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Task not found"})),
+        ));
+    }
+    Ok(Json(
+        state.storage.list_task_usage(identity.tenant_id(), &id),
+    ))
+}
+
 pub async fn trigger_scheduled_transition(
     State(state): State<AppState>,
     identity: Identity,
@@ -861,3 +1037,117 @@ pub async fn trigger_scheduled_transition(
         .transition_ready_scheduled_tasks(identity.tenant_id());
     Json(serde_json::json!({"transitioned": count}))
 }
+
+// --- Recurrence series controls ---
+
+/// POST /api/tasks/:id/recurrence/pause
+pub async fn pause_recurrence(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<Task>, (StatusCode, Json<serde_json::Value>)> {
+    set_recurrence_paused(state, identity, id, true).await
+}
+
+/// POST /api/tasks/:id/recurrence/resume
+pub async fn resume_recurrence(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<Task>, (StatusCode, Json<serde_json::Value>)> {
+    set_recurrence_paused(state, identity, id, false).await
+}
+
+/// POST /api/tasks/:id/recurrence/skip
+pub async fn skip_recurrence(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> Result<Json<RecurrenceSkipResult>, (StatusCode, Json<serde_json::Value>)> {
+    match state.storage.skip_next_recurrence(
+        identity.tenant_id(),
+        &id,
+        identity.author_type(),
+        identity.author_id(),
+    ) {
+        Ok((skipped, next)) => {
+            state.event_bus.emit(Event {
+                event_type: "task.recurrence_skipped".to_string(),
+                project_id: Some(skipped.project_id.clone()),
+                agent_id: skipped.assignee_id.clone(),
+                event_id: None,
+                data: serde_json::json!({
+                    "skipped": skipped,
+                    "next": next,
+                }),
+                timestamp: Utc::now(),
+            });
+
+            Ok(Json(RecurrenceSkipResult { skipped, next }))
+        }
+        Err(e) => {
+            let status = if e.0.contains("not found") {
+                StatusCode::NOT_FOUND
+            } else if e.0.contains("No upcoming occurrence") {
+                StatusCode::CONFLICT
+            } else {
+                StatusCode::BAD_REQUEST
+            };
+            Err((status, Json(serde_json::json!({"error": e.0}))))
+        }
+    }
+}
+
+async fn set_recurrence_paused(
+    state: AppState,
+    identity: Identity,
+    id: String,
+    paused: bool,
+) -> Result<Json<Task>, (StatusCode, Json<serde_json::Value>)> {
+    match state
+        .storage
+        .set_recurrence_paused(identity.tenant_id(), &id, paused)
+    {
+        Ok(task) => {
+            state.storage.create_activity(
+                identity.tenant_id(),
+                &task.id,
+                identity.author_type(),
+                identity.author_id(),
+                &CreateActivity {
+                    content: if paused {
+                        "Recurrence paused".to_string()
+                    } else {
+                        "Recurrence resumed".to_string()
+                    },
+                    activity_type: Some("status_change".to_string()),
+                    metadata: None,
+                    mentions: None,
+                },
+            );
+
+            state.event_bus.emit(Event {
+                event_type: if paused {
+                    "task.recurrence_paused".to_string()
+                } else {
+                    "task.recurrence_resumed".to_string()
+                },
+                project_id: Some(task.project_id.clone()),
+                agent_id: task.assignee_id.clone(),
+                event_id: None,
+                data: serde_json::to_value(&task).unwrap_or_default(),
+                timestamp: Utc::now(),
+            });
+
+            Ok(Json(task))
+        }
+        Err(e) => {
+            let status = if e.0.contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::BAD_REQUEST
+            };
+            Err((status, Json(serde_json::json!({"error": e.0}))))
+        }
+    }
+}
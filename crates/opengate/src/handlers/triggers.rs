@@ -5,7 +5,15 @@ use axum::{
 };
 
 use crate::app::AppState;
+use crate::email;
+use crate::github;
+use crate::gitlab;
+use crate::mapping;
+use crate::quotas::{self, Quota};
+use crate::signatures;
+use crate::slack;
 use crate::storage::StorageBackend;
+use crate::task_refs;
 use opengate_models::*;
 
 // ===== Management endpoints (require auth) =====
@@ -17,11 +25,17 @@ pub async fn create_trigger(
     Path(project_id): Path<String>,
     Json(body): Json<CreateTriggerRequest>,
 ) -> Result<(StatusCode, Json<TriggerCreatedResponse>), StatusCode> {
-    if body.action_type != "create_task" {
+    if !VALID_ACTION_TYPES.contains(&body.action_type.as_str()) {
         return Err(StatusCode::UNPROCESSABLE_ENTITY);
     }
 
-    if !validate_initial_status(&body.action_config) {
+    if !validate_action_config(&body.action_type, &body.action_config)
+        || !validate_verification(
+            body.verification.as_deref().unwrap_or("secret"),
+            body.signing_secret.as_deref(),
+        )
+        || !validate_limits(body.dedup_window_hours, body.rate_limit_per_minute)
+    {
         return Err(StatusCode::UNPROCESSABLE_ENTITY);
     }
 
@@ -33,13 +47,10 @@ pub async fn create_trigger(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    let (trigger, secret) = state.storage.create_webhook_trigger(
-        identity.tenant_id(),
-        &project_id,
-        &body.name,
-        &body.action_type,
-        &body.action_config,
-    );
+    let (trigger, secret) =
+        state
+            .storage
+            .create_webhook_trigger(identity.tenant_id(), &project_id, &body);
 
     Ok((
         StatusCode::CREATED,
@@ -98,15 +109,12 @@ pub async fn update_trigger(
     Json(body): Json<UpdateTriggerRequest>,
 ) -> Result<Json<WebhookTrigger>, StatusCode> {
     if let Some(ref at) = body.action_type {
-        if at != "create_task" {
+        if !VALID_ACTION_TYPES.contains(&at.as_str()) {
             return Err(StatusCode::UNPROCESSABLE_ENTITY);
         }
     }
-
-    if let Some(ref cfg) = body.action_config {
-        if !validate_initial_status(cfg) {
-            return Err(StatusCode::UNPROCESSABLE_ENTITY);
-        }
+    if !validate_limits(body.dedup_window_hours, body.rate_limit_per_minute) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
     }
 
     if state
@@ -117,6 +125,34 @@ pub async fn update_trigger(
         return Err(StatusCode::NOT_FOUND);
     }
 
+    // The config has to suit the action, and the verification its secret, whichever
+    // of each pair changes
+    if body.action_type.is_some()
+        || body.action_config.is_some()
+        || body.verification.is_some()
+        || body.signing_secret.is_some()
+    {
+        let (existing, _) = state
+            .storage
+            .get_webhook_trigger_for_validation(identity.tenant_id(), &trigger_id)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let action_type = body.action_type.as_ref().unwrap_or(&existing.action_type);
+        let cfg = body
+            .action_config
+            .as_ref()
+            .unwrap_or(&existing.action_config);
+        let verification = body.verification.as_ref().unwrap_or(&existing.verification);
+        let signing_secret = body
+            .signing_secret
+            .as_ref()
+            .or(existing.signing_secret.as_ref());
+        if !validate_action_config(action_type, cfg)
+            || !validate_verification(verification, signing_secret.map(String::as_str))
+        {
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+
     state
         .storage
         .update_webhook_trigger(identity.tenant_id(), &trigger_id, &body)
@@ -124,6 +160,73 @@ pub async fn update_trigger(
         .ok_or(StatusCode::NOT_FOUND)
 }
 
+/// POST /api/projects/:id/triggers/validate — render an action_config against a sample
+/// payload and report what doesn't fit, without running the action.
+pub async fn validate_trigger(
+    State(state): State<AppState>,
+    identity: Identity,
+    Path(project_id): Path<String>,
+    Json(body): Json<ValidateTriggerRequest>,
+) -> Result<Json<TriggerValidation>, StatusCode> {
+    if state
+        .storage
+        .get_project(identity.tenant_id(), &project_id)
+        .is_none()
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut errors = Vec::new();
+    if !VALID_ACTION_TYPES.contains(&body.action_type.as_str()) {
+        errors.push(format!(
+            "Unknown action_type '{}'. Must be one of: {}",
+            body.action_type,
+            VALID_ACTION_TYPES.join(", ")
+        ));
+    } else if !validate_action_config(&body.action_type, &body.action_config) {
+        errors.push(format!(
+            "action_config is missing fields {} needs",
+            body.action_type
+        ));
+    }
+
+    let payload = &body.sample_payload;
+    let runs = match mapping::for_each_items(&body.action_config, payload) {
+        Ok(Some(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| mapping::item_vars(payload, item, index))
+            .collect(),
+        Ok(None) => vec![mapping::vars(payload)],
+        Err(e) => {
+            errors.push(e);
+            vec![]
+        }
+    };
+    let mut template = body.action_config.clone();
+    if let Some(cfg) = template.as_object_mut() {
+        cfg.remove("for_each");
+    }
+    let mut missing = Vec::new();
+    let preview = runs
+        .iter()
+        .map(|vars| mapping::render_checked(&template, vars, &mut missing))
+        .collect();
+    missing.dedup();
+    for placeholder in missing {
+        let error = format!("{} is not in the sample payload", placeholder);
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+    }
+
+    Ok(Json(TriggerValidation {
+        valid: errors.is_empty(),
+        errors,
+        preview,
+    }))
+}
+
 /// GET /api/projects/:id/triggers/:tid/logs
 pub async fn list_trigger_logs(
     State(state): State<AppState>,
@@ -183,28 +286,82 @@ pub async fn receive_webhook(
         );
     }
 
-    let provided_secret = headers
-        .get("x-webhook-secret")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
-
-    let provided_hash = sha256_hex(provided_secret);
-    if provided_hash != secret_hash {
+    if let Err(reason) = verify_request(&*state.storage, &trigger, &secret_hash, &headers, &body) {
         state.storage.log_trigger_execution(
             None,
             &trigger_id,
             "rejected",
             Some(&payload),
             None,
-            Some("Invalid secret"),
+            Some(reason),
         );
         return (
             StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Invalid secret"})),
+            Json(serde_json::json!({"error": reason})),
         );
     }
 
+    if let Some(limit) = trigger.rate_limit_per_minute {
+        let since = (chrono::Utc::now() - chrono::Duration::minutes(1)).to_rfc3339();
+        if state
+            .storage
+            .count_trigger_runs_since(None, &trigger_id, &since)
+            >= limit
+        {
+            let error = format!("Rate limit exceeded: {} runs per minute", limit);
+            state.storage.log_trigger_execution(
+                None,
+                &trigger_id,
+                "rate_limited",
+                Some(&payload),
+                None,
+                Some(&error),
+            );
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({"error": error})),
+            );
+        }
+    }
+
+    // A delivery already seen (a provider retry, usually) is logged, not run again
+    let dedup_value = trigger
+        .dedup_key
+        .as_deref()
+        .and_then(|key| mapping::lookup(&mapping::vars(&payload), key))
+        .filter(|v| !v.is_null())
+        .map(|v| {
+            v.as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| v.to_string())
+        });
+    if let Some(ref value) = dedup_value {
+        if !state.storage.claim_trigger_delivery(
+            None,
+            &trigger_id,
+            value,
+            trigger.dedup_window_hours,
+        ) {
+            let result = serde_json::json!({"deduplicated": true, "dedup_value": value});
+            state.storage.log_trigger_execution(
+                None,
+                &trigger_id,
+                "deduplicated",
+                Some(&payload),
+                Some(&result),
+                None,
+            );
+            return (StatusCode::OK, Json(result));
+        }
+    }
+
     let result = execute_trigger_action(&*state.storage, &trigger, &payload);
+    if let (Err(_), Some(ref value)) = (&result, &dedup_value) {
+        // Let the provider's retry of a failed delivery run again
+        state
+            .storage
+            .release_trigger_delivery(None, &trigger_id, value);
+    }
 
     let (status, status_str, result_val, error_str) = match result {
         Ok(val) => (StatusCode::OK, "success", Some(val), None),
@@ -233,47 +390,69 @@ pub async fn receive_webhook(
     (status, Json(response))
 }
 
-fn interpolate(template: &str, payload: &serde_json::Value) -> String {
-    let mut result = template.to_string();
-    let mut i = 0;
-    while let Some(start) = result[i..].find("{{") {
-        let abs_start = i + start;
-        if let Some(end) = result[abs_start..].find("}}") {
-            let abs_end = abs_start + end + 2;
-            let placeholder = &result[abs_start + 2..abs_start + end].trim();
-            if let Some(path) = placeholder.strip_prefix("payload.") {
-                let value = resolve_path(payload, path);
-                let replacement = value.unwrap_or_default();
-                result.replace_range(abs_start..abs_end, &replacement);
-                i = abs_start + replacement.len();
-            } else {
-                i = abs_end;
+/// Check that an inbound request comes from whoever the trigger's `verification` trusts.
+fn verify_request(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    secret_hash: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), &'static str> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let signing_secret = trigger.signing_secret.as_deref().unwrap_or_default();
+    let verified = match trigger.verification.as_str() {
+        "github" => header("x-hub-signature-256")
+            .is_some_and(|sig| signatures::verify_github(signing_secret, sig, body)),
+        "gitlab" => header("x-gitlab-token")
+            .is_some_and(|token| signatures::verify_gitlab(signing_secret, token)),
+        "stripe" => header("stripe-signature").is_some_and(|sig| {
+            signatures::verify_stripe(signing_secret, sig, body, chrono::Utc::now().timestamp())
+        }),
+        _ => {
+            // Slack can't send our secret header; it signs requests with the app's
+            // signing secret instead, which lives on the project's Slack integration
+            let slack_signed = trigger.action_type == "slack_thread_reply"
+                && headers.contains_key("x-slack-signature")
+                && verify_slack_request(storage, trigger, headers, body);
+            if !slack_signed && sha256_hex(header("x-webhook-secret").unwrap_or("")) != secret_hash
+            {
+                return Err("Invalid secret");
             }
-        } else {
-            break;
+            true
         }
+    };
+    if verified {
+        Ok(())
+    } else {
+        Err("Invalid signature")
     }
-    result
 }
 
-fn resolve_path(val: &serde_json::Value, path: &str) -> Option<String> {
-    let mut current = val;
-    for key in path.split('.') {
-        current = match current {
-            serde_json::Value::Object(map) => map.get(key)?,
-            serde_json::Value::Array(arr) => {
-                let idx: usize = key.parse().ok()?;
-                arr.get(idx)?
-            }
-            _ => return None,
-        };
-    }
-    match current {
-        serde_json::Value::String(s) => Some(s.clone()),
-        serde_json::Value::Number(n) => Some(n.to_string()),
-        serde_json::Value::Bool(b) => Some(b.to_string()),
-        serde_json::Value::Null => Some("null".to_string()),
-        other => Some(other.to_string()),
+fn verify_slack_request(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let Some(secret) = storage
+        .get_slack_integration(None, &trigger.project_id)
+        .and_then(|s| s.signing_secret)
+    else {
+        return false;
+    };
+    match (
+        header("x-slack-request-timestamp"),
+        header("x-slack-signature"),
+    ) {
+        (Some(timestamp), Some(signature)) => slack::verify_signature(
+            &secret,
+            timestamp,
+            signature,
+            body,
+            chrono::Utc::now().timestamp(),
+        ),
+        _ => false,
     }
 }
 
@@ -284,6 +463,58 @@ fn sha256_hex(input: &str) -> String {
     hex::encode(hasher.finalize())
 }
 
+const VALID_ACTION_TYPES: &[&str] = &[
+    "create_task",
+    "update_task",
+    "add_activity",
+    "resolve_question",
+    "slack_thread_reply",
+    "github_pull_request",
+    "gitlab_merge_request",
+    "email_task",
+    "commit_references",
+];
+
+/// A dedup window of at least an hour; a rate limit of 0 (off) or more.
+fn validate_limits(dedup_window_hours: Option<i64>, rate_limit_per_minute: Option<i64>) -> bool {
+    dedup_window_hours.is_none_or(|h| h >= 1) && rate_limit_per_minute.is_none_or(|n| n >= 0)
+}
+
+/// Provider verifications check against a signing secret, so they need one.
+fn validate_verification(verification: &str, signing_secret: Option<&str>) -> bool {
+    TRIGGER_VERIFICATIONS.contains(&verification)
+        && (verification == "secret" || signing_secret.is_some_and(|s| !s.is_empty()))
+}
+
+/// Whether `cfg` has what `action_type` needs.
+fn validate_action_config(action_type: &str, cfg: &serde_json::Value) -> bool {
+    let has = |key: &str| cfg.get(key).is_some_and(|v| v.is_string());
+    let targets_task = has("task_id") || has("external_ref");
+    validate_initial_status(cfg)
+        && validate_assign_to(cfg)
+        && cfg
+            .get("for_each")
+            .is_none_or(|v| v.is_null() || v.is_string())
+        && match action_type {
+            "update_task" => targets_task,
+            "add_activity" => targets_task && has("content"),
+            "resolve_question" => has("question_id") && has("resolution"),
+            "email_task" => {
+                cfg.get("address")
+                    .is_none_or(|v| v.is_null() || v.is_string())
+                    && cfg.get("allowed_senders").is_none_or(|v| {
+                        v.is_null()
+                            || v.as_array()
+                                .is_some_and(|a| a.iter().all(|s| s.is_string()))
+                    })
+            }
+            "commit_references" => cfg
+                .get("review_on_merge")
+                .is_none_or(|v| v.is_null() || v.is_boolean()),
+            _ => true,
+        }
+}
+
 const ALLOWED_INITIAL_STATUSES: &[&str] = &["backlog", "todo", "in_progress"];
 
 fn validate_initial_status(cfg: &serde_json::Value) -> bool {
@@ -294,13 +525,59 @@ fn validate_initial_status(cfg: &serde_json::Value) -> bool {
     }
 }
 
+fn validate_assign_to(cfg: &serde_json::Value) -> bool {
+    match cfg.get("assign_to") {
+        None | Some(serde_json::Value::Null) => true,
+        Some(v) => {
+            serde_json::from_value::<AssignStrategy>(v.clone()).is_ok_and(|s| s.validate().is_ok())
+        }
+    }
+}
+
 fn execute_trigger_action(
     storage: &dyn StorageBackend,
     trigger: &WebhookTrigger,
     payload: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    if trigger.action_type == "slack_thread_reply" {
+        return execute_slack_thread_reply(storage, trigger, payload);
+    }
+    if trigger.action_type == "github_pull_request" {
+        return execute_github_pull_request(storage, trigger, payload);
+    }
+    if trigger.action_type == "gitlab_merge_request" {
+        return execute_gitlab_merge_request(storage, trigger, payload);
+    }
+    if trigger.action_type == "email_task" {
+        return execute_email_task(storage, trigger, payload);
+    }
+    if trigger.action_type == "commit_references" {
+        return execute_commit_references(storage, trigger, payload);
+    }
+    let Some(items) = mapping::for_each_items(&trigger.action_config, payload)? else {
+        return execute_mapped_action(storage, trigger, &mapping::vars(payload));
+    };
+    let mut results = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        let vars = mapping::item_vars(payload, item, index);
+        let result = execute_mapped_action(storage, trigger, &vars)
+            .map_err(|e| format!("for_each item {}: {}", index, e))?;
+        results.push(result);
+    }
+    Ok(serde_json::json!({"count": results.len(), "results": results}))
+}
+
+/// Run the action once, with its templates filled from `vars`.
+fn execute_mapped_action(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    vars: &serde_json::Value,
 ) -> Result<serde_json::Value, String> {
     match trigger.action_type.as_str() {
-        "create_task" => execute_create_task(storage, trigger, payload),
+        "create_task" => execute_create_task(storage, trigger, vars),
+        "update_task" => execute_update_task(storage, trigger, vars),
+        "add_activity" => execute_add_activity(storage, trigger, vars),
+        "resolve_question" => execute_resolve_question(storage, trigger, vars),
         other => Err(format!("Unknown action_type: {}", other)),
     }
 }
@@ -308,46 +585,45 @@ fn execute_trigger_action(
 fn execute_create_task(
     storage: &dyn StorageBackend,
     trigger: &WebhookTrigger,
-    payload: &serde_json::Value,
+    vars: &serde_json::Value,
 ) -> Result<serde_json::Value, String> {
     let cfg = &trigger.action_config;
 
     let title_tpl = cfg["title"]
         .as_str()
         .ok_or("Missing title in action_config")?;
-    let title = interpolate(title_tpl, payload);
+    let title = mapping::interpolate(title_tpl, vars);
 
-    let description = cfg["description"].as_str().map(|d| interpolate(d, payload));
+    let description = cfg["description"]
+        .as_str()
+        .map(|d| mapping::interpolate(d, vars));
 
     let priority = cfg["priority"].as_str().map(|p| p.to_string());
 
     let tags: Option<Vec<String>> = cfg["tags"].as_array().map(|arr| {
         arr.iter()
-            .filter_map(|v| v.as_str().map(|s| interpolate(s, payload)))
+            .filter_map(|v| v.as_str().map(|s| mapping::interpolate(s, vars)))
             .collect()
     });
 
-    let (assignee_type, assignee_id) = match cfg.get("assign_to") {
-        Some(strategy) if !strategy.is_null() => {
-            let strategy_str = strategy.to_string();
-            let parsed: Option<AssignStrategy> = serde_json::from_str(&strategy_str).ok();
-            match parsed {
-                Some(s) => match storage.find_best_agent(None, &s) {
-                    Some(agent_id) => (Some("agent".to_string()), Some(agent_id)),
-                    None => (None, None),
-                },
-                None => (None, None),
-            }
-        }
-        _ => (None, None),
-    };
+    let (assignee_type, assignee_id) = config_assignee(storage, cfg);
+
+    let mut context = cfg
+        .get("context")
+        .filter(|c| c.is_object())
+        .map(|c| mapping::render(c, vars));
+    // Lets later update_task / add_activity triggers find the task by the same ref
+    if let Some(r) = cfg["external_ref"].as_str() {
+        context.get_or_insert_with(|| serde_json::json!({}))["external_ref"] =
+            serde_json::json!(mapping::interpolate(r, vars));
+    }
 
     let create_input = CreateTask {
         title,
         description,
         priority,
         tags,
-        context: None,
+        context,
         output: None,
         due_date: None,
         assignee_type,
@@ -356,10 +632,48 @@ fn execute_create_task(
         recurrence_rule: None,
     };
 
+    let tenant = storage.project_owner(&trigger.project_id);
+    quotas::check(storage, tenant.as_deref(), Quota::OpenTasks).map_err(|e| e.to_string())?;
+
     let task = storage.create_task(None, &trigger.project_id, &create_input, "system");
+    let final_status = apply_initial_status(storage, cfg, &task);
 
+    Ok(serde_json::json!({
+        "task_id": task.id,
+        "task_title": task.title,
+        "status": final_status
+    }))
+}
+
+/// The agent `cfg.assign_to` picks, if any.
+fn config_assignee(
+    storage: &dyn StorageBackend,
+    cfg: &serde_json::Value,
+) -> (Option<String>, Option<String>) {
+    match cfg.get("assign_to") {
+        Some(strategy) if !strategy.is_null() => {
+            let strategy_str = strategy.to_string();
+            let parsed: Option<AssignStrategy> = serde_json::from_str(&strategy_str).ok();
+            match parsed {
+                Some(s) => match storage.find_best_agent(None, &s) {
+                    Some(agent_id) => (Some("agent".to_string()), Some(agent_id)),
+                    None => (None, None),
+                },
+                None => (None, None),
+            }
+        }
+        _ => (None, None),
+    }
+}
+
+/// Move a newly created `task` to `cfg.initial_status`, returning its status.
+fn apply_initial_status(
+    storage: &dyn StorageBackend,
+    cfg: &serde_json::Value,
+    task: &Task,
+) -> String {
     // Apply initial_status if specified and different from the default "backlog"
-    let final_status = match cfg.get("initial_status").and_then(|v| v.as_str()) {
+    match cfg.get("initial_status").and_then(|v| v.as_str()) {
         Some(s) if s != "backlog" && ALLOWED_INITIAL_STATUSES.contains(&s) => {
             let update = UpdateTask {
                 status: Some(s.to_string()),
@@ -381,11 +695,678 @@ fn execute_create_task(
             s.to_string()
         }
         _ => task.status.clone(),
+    }
+}
+
+/// The task an action targets: `task_id`, or the project task whose
+/// `context.external_ref` matches `external_ref` (both interpolated).
+fn find_target_task(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    vars: &serde_json::Value,
+) -> Result<Task, String> {
+    let cfg = &trigger.action_config;
+    if let Some(tpl) = cfg["task_id"].as_str() {
+        let task_id = mapping::interpolate(tpl, vars);
+        return storage
+            .get_task(None, &task_id)
+            .filter(|t| t.project_id == trigger.project_id)
+            .ok_or_else(|| format!("Task not found: {}", task_id));
+    }
+    let tpl = cfg["external_ref"]
+        .as_str()
+        .ok_or("Missing task_id or external_ref in action_config")?;
+    let external_ref = mapping::interpolate(tpl, vars);
+    let filters = TaskFilters {
+        project_id: Some(trigger.project_id.clone()),
+        status: None,
+        priority: None,
+        assignee_id: None,
+        tag: None,
+    };
+    storage
+        .list_tasks(None, &filters)
+        .into_iter()
+        .find(|t| {
+            t.context
+                .as_ref()
+                .and_then(|c| c.get("external_ref"))
+                .and_then(|v| v.as_str())
+                == Some(external_ref.as_str())
+        })
+        .ok_or_else(|| format!("No task with external_ref {}", external_ref))
+}
+
+fn execute_update_task(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    vars: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let cfg = &trigger.action_config;
+    let task = find_target_task(storage, trigger, vars)?;
+    let field = |key: &str| cfg[key].as_str().map(|v| mapping::interpolate(v, vars));
+
+    let update = UpdateTask {
+        status: field("status"),
+        title: field("title"),
+        description: field("description"),
+        priority: field("priority"),
+        tags: cfg["tags"].as_array().map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(|s| mapping::interpolate(s, vars)))
+                .collect()
+        }),
+        ..Default::default()
+    };
+    let mut updated = storage
+        .update_task(None, &task.id, &update)
+        .map_err(|e| e.to_string())?
+        .ok_or("Task not found")?;
+    if let Some(patch) = cfg.get("context").filter(|c| c.is_object()) {
+        updated = storage
+            .merge_context(None, &task.id, &mapping::render(patch, vars))
+            .map_err(|e| e.to_string())?
+            .ok_or("Task not found")?;
+    }
+
+    Ok(serde_json::json!({
+        "task_id": updated.id,
+        "task_title": updated.title,
+        "status": updated.status
+    }))
+}
+
+fn execute_add_activity(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    vars: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let cfg = &trigger.action_config;
+    let task = find_target_task(storage, trigger, vars)?;
+    let content = mapping::interpolate(
+        cfg["content"]
+            .as_str()
+            .ok_or("Missing content in action_config")?,
+        vars,
+    );
+
+    let activity = storage.create_activity(
+        None,
+        &task.id,
+        "system",
+        "system",
+        &CreateActivity {
+            content,
+            activity_type: Some(
+                cfg["activity_type"]
+                    .as_str()
+                    .unwrap_or("comment")
+                    .to_string(),
+            ),
+            metadata: Some(serde_json::json!({
+                "source": "trigger",
+                "trigger_id": trigger.id,
+            })),
+            mentions: None,
+        },
+    );
+    Ok(serde_json::json!({"task_id": task.id, "activity_id": activity.id}))
+}
+
+fn execute_resolve_question(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    vars: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let cfg = &trigger.action_config;
+    let question_id = mapping::interpolate(
+        cfg["question_id"]
+            .as_str()
+            .ok_or("Missing question_id in action_config")?,
+        vars,
+    );
+    let resolution = mapping::interpolate(
+        cfg["resolution"]
+            .as_str()
+            .ok_or("Missing resolution in action_config")?,
+        vars,
+    );
+    storage
+        .get_question(None, &question_id)
+        .filter(|q| q.project_id == trigger.project_id)
+        .ok_or_else(|| format!("Question not found: {}", question_id))?;
+
+    let question = storage
+        .resolve_question(None, &question_id, &resolution, "system", "system")
+        .ok_or("Question is not open")?;
+    Ok(serde_json::json!({
+        "question_id": question.id,
+        "task_id": question.task_id,
+        "status": question.status
+    }))
+}
+
+/// Ingest a Slack Events API callback: a reply in the thread of a task posted by the
+/// Slack integration becomes a comment on that task. Anything else is acknowledged and
+/// ignored, so Slack doesn't retry it.
+fn execute_slack_thread_reply(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    payload: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    // Slack checks the request URL once by asking us to echo a challenge
+    if payload["type"] == "url_verification" {
+        return Ok(serde_json::json!({"challenge": payload["challenge"]}));
+    }
+    let ignored = |reason: &str| Ok(serde_json::json!({"ignored": true, "reason": reason}));
+    let event = &payload["event"];
+    if payload["type"] != "event_callback" || event["type"] != "message" {
+        return ignored("not a message event");
+    }
+    // Edits, joins and our own posts carry a subtype or bot_id
+    if event.get("subtype").is_some() || event.get("bot_id").is_some() {
+        return ignored("not a user message");
+    }
+    let (Some(channel), Some(thread_ts), Some(ts)) = (
+        event["channel"].as_str(),
+        event["thread_ts"].as_str(),
+        event["ts"].as_str(),
+    ) else {
+        return ignored("not a thread reply");
+    };
+    if thread_ts == ts {
+        return ignored("not a thread reply");
+    }
+    let Some(task) = storage
+        .find_slack_thread_task(None, channel, thread_ts)
+        .and_then(|id| storage.get_task(None, &id))
+        .filter(|t| t.project_id == trigger.project_id)
+    else {
+        return ignored("thread is not linked to a task");
+    };
+    let user = event["user"].as_str().unwrap_or("unknown");
+    let text = event["text"].as_str().unwrap_or_default();
+    if text.trim().is_empty() {
+        return ignored("empty message");
+    }
+
+    let activity = storage.create_activity(
+        None,
+        &task.id,
+        "human",
+        &format!("slack:{}", user),
+        &CreateActivity {
+            content: text.to_string(),
+            activity_type: Some("comment".to_string()),
+            metadata: Some(serde_json::json!({
+                "source": "slack",
+                "slack_user": user,
+                "slack_channel": channel,
+                "slack_ts": ts,
+            })),
+            mentions: None,
+        },
+    );
+    Ok(serde_json::json!({"task_id": task.id, "activity_id": activity.id}))
+}
+
+/// Ingest a GitHub `pull_request` webhook: when a pull request is opened (or reopened,
+/// or marked ready) for a task, attach it and move the task from `in_progress` into
+/// `review`. The task is the one whose `pr_url` is the pull request, or whose id
+/// appears in its branch, title or description. Other deliveries are acknowledged and
+/// ignored.
+fn execute_github_pull_request(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    payload: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let ignored = |reason: &str| Ok(serde_json::json!({"ignored": true, "reason": reason}));
+    let pr = &payload["pull_request"];
+    let Some(pr_url) = pr["html_url"].as_str() else {
+        return ignored("not a pull_request event");
+    };
+    let action = payload["action"].as_str().unwrap_or_default();
+    if !["opened", "reopened", "ready_for_review"].contains(&action) {
+        return ignored("pull request was not opened");
+    }
+    if pr["draft"] == true {
+        return ignored("draft pull request");
+    }
+
+    let mentions = [&pr["head"]["ref"], &pr["title"], &pr["body"]]
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let review = OpenedReview {
+        source: "github",
+        artifact_name: github::ARTIFACT_NAME,
+        url: pr_url,
+        number: &pr["number"],
+        mentions: &mentions,
+    };
+    link_opened_review(storage, trigger, &review)
+}
+
+/// A GitLab `merge_request` hook, handled like `github_pull_request`: opened, reopened
+/// and marked-ready merge requests move their task into `review`.
+fn execute_gitlab_merge_request(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    payload: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let ignored = |reason: &str| Ok(serde_json::json!({"ignored": true, "reason": reason}));
+    let mr = &payload["object_attributes"];
+    let Some(mr_url) = mr["url"]
+        .as_str()
+        .filter(|_| payload["object_kind"] == "merge_request")
+    else {
+        return ignored("not a merge_request event");
+    };
+    let action = mr["action"].as_str().unwrap_or_default();
+    let marked_ready = action == "update"
+        && payload["changes"]["draft"]["previous"] == true
+        && payload["changes"]["draft"]["current"] == false;
+    if !["open", "reopen"].contains(&action) && !marked_ready {
+        return ignored("merge request was not opened");
+    }
+    if mr["draft"] == true || mr["work_in_progress"] == true {
+        return ignored("draft merge request");
+    }
+
+    let mentions = [&mr["source_branch"], &mr["title"], &mr["description"]]
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let review = OpenedReview {
+        source: "gitlab",
+        artifact_name: gitlab::ARTIFACT_NAME,
+        url: mr_url,
+        number: &mr["iid"],
+        mentions: &mentions,
+    };
+    link_opened_review(storage, trigger, &review)
+}
+
+/// Turn an inbound email into a task: subject as title, text body as description, the
+/// sender in `context.email` and attachments as artifacts. Mail to another address than
+/// `address`, or from a sender `allowed_senders` doesn't list, is acknowledged and ignored.
+fn execute_email_task(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    payload: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let ignored = |reason: &str| Ok(serde_json::json!({"ignored": true, "reason": reason}));
+    let cfg = &trigger.action_config;
+    let Some(email) = email::parse(payload) else {
+        return ignored("not an inbound email");
     };
+    if let Some(address) = cfg["address"].as_str() {
+        if !email.to.contains(&address.trim().to_lowercase()) {
+            return ignored("not sent to the configured address");
+        }
+    }
+    let allowed: Vec<String> = cfg["allowed_senders"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect();
+    if !email::sender_allowed(&email.from, &allowed) {
+        return ignored("sender is not allowed");
+    }
+
+    let mut context = serde_json::json!({
+        "email": {
+            "from": email.from,
+            "from_name": email.from_name,
+            "to": email.to,
+            "message_id": email.message_id,
+        }
+    });
+    if let Some(ref id) = email.message_id {
+        context["external_ref"] = serde_json::json!(format!("email:{}", id));
+    }
+    let (assignee_type, assignee_id) = config_assignee(storage, cfg);
+    let create_input = CreateTask {
+        title: email.subject.clone(),
+        description: email.text.clone(),
+        priority: cfg["priority"].as_str().map(str::to_string),
+        tags: cfg["tags"].as_array().map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect()
+        }),
+        context: Some(context),
+        output: None,
+        due_date: None,
+        assignee_type,
+        assignee_id,
+        scheduled_at: None,
+        recurrence_rule: None,
+    };
+
+    let tenant = storage.project_owner(&trigger.project_id);
+    quotas::check(storage, tenant.as_deref(), Quota::OpenTasks).map_err(|e| e.to_string())?;
+    let task = storage.create_task(None, &trigger.project_id, &create_input, "system");
+    let status = apply_initial_status(storage, cfg, &task);
+
+    let mut attached = 0;
+    let mut skipped = Vec::new();
+    for attachment in &email.attachments {
+        let Some((artifact_type, value)) = attachment.artifact() else {
+            skipped.push(attachment.name.clone());
+            continue;
+        };
+        storage.create_artifact(
+            None,
+            &task.id,
+            &CreateArtifact {
+                name: attachment.name.clone(),
+                artifact_type: artifact_type.to_string(),
+                value,
+            },
+            "system",
+            "email",
+        );
+        attached += 1;
+    }
 
     Ok(serde_json::json!({
         "task_id": task.id,
         "task_title": task.title,
-        "status": final_status
+        "status": status,
+        "from": email.from,
+        "attachments": attached,
+        "skipped_attachments": skipped,
+    }))
+}
+
+/// A commit or pull request whose text may reference tasks.
+struct Reference<'a> {
+    source: &'static str,
+    artifact_name: String,
+    url: &'a str,
+    /// Commit message, or branch, title and description
+    text: String,
+    /// The activity posted on each task it references
+    summary: String,
+    merged: bool,
+}
+
+/// The commits of a GitHub or GitLab push, or a GitHub pull request / GitLab merge
+/// request that was opened, edited or merged.
+fn payload_references(payload: &serde_json::Value) -> Vec<Reference<'_>> {
+    let join = |values: &[&serde_json::Value]| {
+        values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    if let Some(commits) = payload["commits"].as_array() {
+        let source = if payload["object_kind"] == "push" {
+            "gitlab"
+        } else {
+            "github"
+        };
+        return commits
+            .iter()
+            .filter_map(|c| {
+                let url = c["url"].as_str()?;
+                let message = c["message"].as_str()?;
+                let sha: String = c["id"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .chars()
+                    .take(7)
+                    .collect();
+                Some(Reference {
+                    source,
+                    artifact_name: format!("Commit {}", sha),
+                    url,
+                    text: message.to_string(),
+                    summary: format!(
+                        "Referenced in commit {}: {}",
+                        sha,
+                        message.lines().next().unwrap_or_default()
+                    ),
+                    merged: false,
+                })
+            })
+            .collect();
+    }
+    let (source, artifact_name, url, text, merged, linkable) =
+        if let Some(url) = payload["pull_request"]["html_url"].as_str() {
+            let pr = &payload["pull_request"];
+            let action = payload["action"].as_str().unwrap_or_default();
+            let merged = action == "closed" && pr["merged"] == true;
+            (
+                "github",
+                github::ARTIFACT_NAME,
+                url,
+                join(&[&pr["head"]["ref"], &pr["title"], &pr["body"]]),
+                merged,
+                merged || ["opened", "reopened", "edited", "ready_for_review"].contains(&action),
+            )
+        } else if let Some(url) = payload["object_attributes"]["url"]
+            .as_str()
+            .filter(|_| payload["object_kind"] == "merge_request")
+        {
+            let mr = &payload["object_attributes"];
+            let action = mr["action"].as_str().unwrap_or_default();
+            (
+                "gitlab",
+                gitlab::ARTIFACT_NAME,
+                url,
+                join(&[&mr["source_branch"], &mr["title"], &mr["description"]]),
+                action == "merge",
+                ["open", "reopen", "update", "merge"].contains(&action),
+            )
+        } else {
+            return Vec::new();
+        };
+    if !linkable {
+        return Vec::new();
+    }
+    vec![Reference {
+        source,
+        artifact_name: artifact_name.to_string(),
+        url,
+        text,
+        summary: format!(
+            "{} {}: {}",
+            artifact_name,
+            if merged {
+                "merged"
+            } else {
+                "references this task"
+            },
+            url
+        ),
+        merged,
+    }]
+}
+
+/// Link the tasks that commits and pull requests reference (by id, or `OG-<id prefix>`)
+/// in their message, branch, title or description: each gets the commit or pull request
+/// as a `url` artifact and an activity. With `review_on_merge`, merging moves the task
+/// from `in_progress` to `review`.
+fn execute_commit_references(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    payload: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let references = payload_references(payload);
+    let mentions: Vec<_> = references
+        .iter()
+        .map(|r| (r, task_refs::find(&r.text)))
+        .filter(|(_, refs)| !refs.is_empty())
+        .collect();
+    if mentions.is_empty() {
+        return Ok(serde_json::json!({"ignored": true, "reason": "no task references"}));
+    }
+    let review_on_merge = trigger.action_config["review_on_merge"] == true;
+    let filters = TaskFilters {
+        project_id: Some(trigger.project_id.clone()),
+        status: None,
+        priority: None,
+        assignee_id: None,
+        tag: None,
+    };
+    let tasks = storage.list_tasks(None, &filters);
+
+    let mut linked = Vec::new();
+    let mut moved = Vec::new();
+    let mut unmatched = Vec::new();
+    for (reference, refs) in mentions {
+        for task_ref in refs {
+            let Some(task) = task_refs::resolve(&task_ref, &tasks) else {
+                let label = task_ref.label();
+                if !unmatched.contains(&label) {
+                    unmatched.push(label);
+                }
+                continue;
+            };
+            let attached = github::attach_url(
+                storage,
+                None,
+                &task.id,
+                &reference.artifact_name,
+                reference.url,
+                "system",
+                reference.source,
+            );
+            // Redeliveries and edits don't repeat the activity; a merge always posts one
+            if attached || reference.merged {
+                storage.create_activity(
+                    None,
+                    &task.id,
+                    "system",
+                    "system",
+                    &CreateActivity {
+                        content: reference.summary.clone(),
+                        activity_type: Some("comment".to_string()),
+                        metadata: Some(serde_json::json!({
+                            "source": reference.source,
+                            "trigger_id": trigger.id,
+                            "url": reference.url,
+                            "reference": task_ref.label(),
+                        })),
+                        mentions: None,
+                    },
+                );
+            }
+            linked.push(serde_json::json!({"task_id": task.id, "url": reference.url}));
+            if reference.merged && review_on_merge && task.status == "in_progress" {
+                let update = UpdateTask {
+                    status: Some("review".to_string()),
+                    ..Default::default()
+                };
+                storage
+                    .update_task(None, &task.id, &update)
+                    .map_err(|e| e.to_string())?;
+                moved.push(task.id.clone());
+            }
+        }
+    }
+    Ok(serde_json::json!({
+        "linked": linked,
+        "moved_to_review": moved,
+        "unmatched": unmatched,
+    }))
+}
+
+/// A pull or merge request that was opened for review.
+struct OpenedReview<'a> {
+    source: &'a str,
+    artifact_name: &'a str,
+    url: &'a str,
+    number: &'a serde_json::Value,
+    /// Branch, title and description, searched for a task id.
+    mentions: &'a str,
+}
+
+/// Find the task `review` belongs to (its `pr_url`, else the task id in its branch, title
+/// or description), attach it and move the task from `in_progress` to `review`.
+fn link_opened_review(
+    storage: &dyn StorageBackend,
+    trigger: &WebhookTrigger,
+    review: &OpenedReview,
+) -> Result<serde_json::Value, String> {
+    let filters = TaskFilters {
+        project_id: Some(trigger.project_id.clone()),
+        status: None,
+        priority: None,
+        assignee_id: None,
+        tag: None,
+    };
+    let tasks = storage.list_tasks(None, &filters);
+    let links_pr = |t: &Task| {
+        [&t.context, &t.output].iter().any(|v| {
+            ["pr_url", "mr_url"].iter().any(|key| {
+                v.as_ref()
+                    .and_then(|v| v.get(*key))
+                    .and_then(|u| u.as_str())
+                    == Some(review.url)
+            })
+        })
+    };
+    let Some(task) = tasks
+        .iter()
+        .find(|t| links_pr(t))
+        .or_else(|| tasks.iter().find(|t| review.mentions.contains(&t.id)))
+    else {
+        return Ok(serde_json::json!({
+            "ignored": true,
+            "reason": format!("{} is not linked to a task", review.artifact_name.to_lowercase())
+        }));
+    };
+
+    github::attach_url(
+        storage,
+        None,
+        &task.id,
+        review.artifact_name,
+        review.url,
+        "system",
+        review.source,
+    );
+    storage.create_activity(
+        None,
+        &task.id,
+        "system",
+        "system",
+        &CreateActivity {
+            content: format!("{} opened: {}", review.artifact_name, review.url),
+            activity_type: Some("comment".to_string()),
+            metadata: Some(serde_json::json!({
+                "source": review.source,
+                "trigger_id": trigger.id,
+                "pr_url": review.url,
+                "pr_number": review.number,
+            })),
+            mentions: None,
+        },
+    );
+    let status = if task.status == "in_progress" {
+        let update = UpdateTask {
+            status: Some("review".to_string()),
+            ..Default::default()
+        };
+        storage
+            .update_task(None, &task.id, &update)
+            .map_err(|e| e.to_string())?
+            .ok_or("Task not found")?
+            .status
+    } else {
+        task.status.clone()
+    };
+    Ok(serde_json::json!({
+        "task_id": task.id,
+        "task_title": task.title,
+        "pr_url": review.url,
+        "status": status
     }))
 }
@@ -1,6 +1,129 @@
+use crate::cloudevents::{self, EventFormat};
+use crate::config::WebhookPolicy;
+use crate::push;
+use crate::quotas;
 use crate::storage::StorageBackend;
+use crate::telemetry;
 use opengate_models::*;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+
+static POLICY: OnceLock<WebhookPolicy> = OnceLock::new();
+
+/// Set the attempts, timeout and backoff of agent webhook deliveries.
+pub fn set_policy(policy: WebhookPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+fn policy() -> WebhookPolicy {
+    POLICY.get().cloned().unwrap_or_default()
+}
+
+/// Body and content type of an outbound webhook. An agent's `webhook_template` wins;
+/// otherwise the server's event format applies. `cloud_event` builds the CloudEvents
+/// envelope and is only called when that format is configured.
+fn outbound_body(
+    agent: &Agent,
+    native: serde_json::Value,
+    cloud_event: impl FnOnce() -> serde_json::Value,
+) -> (serde_json::Value, &'static str) {
+    if let Some(ref template) = agent.webhook_template {
+        let mut context = native;
+        context["agent"] = serde_json::json!({"id": agent.id, "name": agent.name});
+        return (render_template(template, &context), "application/json");
+    }
+    match cloudevents::default_format() {
+        EventFormat::Native => (native, "application/json"),
+        EventFormat::CloudEvents => (cloud_event(), cloudevents::CONTENT_TYPE),
+    }
+}
+
+/// Check a `webhook_template` before it is stored: it must be a JSON object or array and
+/// every `{{` must be closed around a field path.
+pub fn validate_template(template: &serde_json::Value) -> Result<(), String> {
+    if !template.is_object() && !template.is_array() {
+        return Err("webhook_template must be a JSON object or array".to_string());
+    }
+    fn check(value: &serde_json::Value) -> Result<(), String> {
+        match value {
+            serde_json::Value::String(s) => {
+                let mut rest = s.as_str();
+                while let Some(start) = rest.find("{{") {
+                    let after = &rest[start + 2..];
+                    let end = after
+                        .find("}}")
+                        .ok_or_else(|| format!("Unclosed placeholder in '{}'", s))?;
+                    if after[..end].trim().is_empty() {
+                        return Err(format!("Empty placeholder in '{}'", s));
+                    }
+                    rest = &after[end + 2..];
+                }
+                Ok(())
+            }
+            serde_json::Value::Array(items) => items.iter().try_for_each(check),
+            serde_json::Value::Object(map) => map.values().try_for_each(check),
+            _ => Ok(()),
+        }
+    }
+    check(template)
+}
+
+/// Render a `webhook_template` over an event payload. `{{field.path}}` placeholders in
+/// string values are replaced with the payload field (missing fields render empty). A
+/// string that is exactly one placeholder takes the field's JSON value as-is, so
+/// `"task": "{{task}}"` embeds the whole object and numbers stay numbers.
+pub fn render_template(
+    template: &serde_json::Value,
+    context: &serde_json::Value,
+) -> serde_json::Value {
+    match template {
+        serde_json::Value::String(s) => {
+            let trimmed = s.trim();
+            if let Some(path) = trimmed
+                .strip_prefix("{{")
+                .and_then(|t| t.strip_suffix("}}"))
+                .filter(|p| !p.contains("{{") && !p.contains("}}"))
+            {
+                return lookup(context, path.trim())
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null);
+            }
+            let mut out = String::new();
+            let mut rest = s.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(end) = rest[start + 2..].find("}}") else {
+                    break;
+                };
+                out.push_str(&rest[..start]);
+                match lookup(context, rest[start + 2..start + 2 + end].trim()) {
+                    Some(serde_json::Value::String(v)) => out.push_str(v),
+                    Some(serde_json::Value::Null) | None => {}
+                    Some(other) => out.push_str(&other.to_string()),
+                }
+                rest = &rest[start + 2 + end + 2..];
+            }
+            out.push_str(rest);
+            serde_json::Value::String(out)
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(|v| render_template(v, context)).collect())
+        }
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), render_template(v, context)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn lookup<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .try_fold(value, |current, key| match current {
+            serde_json::Value::Object(map) => map.get(key),
+            serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
 
 /// Fire webhook notifications for a list of pending notification webhooks.
 pub fn fire_notification_webhooks(
@@ -12,6 +135,10 @@ pub fn fire_notification_webhooks(
             Some(a) => a,
             None => continue,
         };
+        // Phone push is its own channel: it doesn't depend on the agent having a webhook
+        if notif.push {
+            push::send_notification(&*storage, &agent, &notif);
+        }
         let webhook_url = agent.webhook_url.clone();
         let webhook_events = agent.webhook_events.clone();
 
@@ -25,31 +152,59 @@ pub fn fire_notification_webhooks(
                 continue;
             }
         }
+        if !NotificationPreference::resolve(&agent.notification_preferences, &notif.event_type)
+            .webhook
+        {
+            continue;
+        }
+        if !quotas::take_webhook_delivery(agent.owner_id.as_deref()) {
+            continue;
+        }
 
-        let payload = serde_json::json!({
-            "event": "notification",
-            "notification_id": notif.notification_id,
-            "event_type": notif.event_type,
-            "title": notif.title,
-            "body": notif.body,
-            "timestamp": chrono::Utc::now().to_rfc3339()
-        });
+        let (payload, content_type) = outbound_body(
+            &agent,
+            serde_json::json!({
+                "event": "notification",
+                "notification_id": notif.notification_id,
+                "event_type": notif.event_type,
+                "title": notif.title,
+                "body": notif.body,
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            }),
+            || {
+                cloudevents::envelope(
+                    &format!("notification-{}", notif.notification_id),
+                    &notif.event_type,
+                    None,
+                    None,
+                    chrono::Utc::now(),
+                    &serde_json::json!({
+                        "notification_id": notif.notification_id,
+                        "title": notif.title,
+                        "body": notif.body,
+                    }),
+                )
+            },
+        );
 
         let storage_clone = storage.clone();
         let notification_id = notif.notification_id;
         let agent_id = notif.agent_id.clone();
+        let trace = telemetry::current();
 
         tokio::spawn(async move {
             let client = reqwest::Client::new();
-            let max_attempts: i64 = 3;
+            let policy = policy();
+            let max_attempts = policy.max_attempts as i64;
 
             for attempt in 1..=max_attempts {
-                let result = client
+                let request = client
                     .post(&url)
-                    .json(&payload)
-                    .timeout(std::time::Duration::from_secs(10))
-                    .send()
-                    .await;
+                    .header(reqwest::header::CONTENT_TYPE, content_type)
+                    .body(payload.to_string())
+                    .timeout(std::time::Duration::from_secs(policy.timeout_secs));
+                let result =
+                    telemetry::send_traced(request, "notification webhook", trace.as_ref()).await;
 
                 match result {
                     Ok(resp) => {
@@ -67,6 +222,7 @@ pub fn fire_notification_webhooks(
                                 "[webhook] notif {} delivered to agent {}, auto-acked",
                                 notification_id, agent_id
                             );
+                            crate::metrics::record_webhook("notification", true);
                             return;
                         }
                         if attempt == max_attempts {
@@ -76,6 +232,7 @@ pub fn fire_notification_webhooks(
                                 "failed",
                             );
                             eprintln!("[webhook] notif {} failed for agent {} (HTTP {}); left unread for polling", notification_id, agent_id, status_code);
+                            crate::metrics::record_webhook("notification", false);
                         }
                     }
                     Err(e) => {
@@ -86,13 +243,14 @@ pub fn fire_notification_webhooks(
                                 "failed",
                             );
                             eprintln!("[webhook] notif {} failed for agent {} ({}); left unread for polling", notification_id, agent_id, e);
+                            crate::metrics::record_webhook("notification", false);
                         }
                     }
                 }
 
                 if attempt < max_attempts {
                     tokio::time::sleep(std::time::Duration::from_secs(
-                        attempt as u64 * attempt as u64,
+                        policy.retry_delay_secs * attempt as u64 * attempt as u64,
                     ))
                     .await;
                 }
@@ -101,6 +259,55 @@ pub fn fire_notification_webhooks(
     }
 }
 
+/// Push a synthetic `webhook.test` event to `url` and wait for the receiver's answer.
+/// Nothing is logged or acknowledged.
+pub async fn send_test_webhook(agent: &Agent, url: &str) -> WebhookTestResult {
+    let now = chrono::Utc::now();
+    let data = serde_json::json!({
+        "agent_id": agent.id,
+        "agent_name": agent.name,
+        "message": "Test push from OpenGate — no action needed",
+    });
+    let mut native = data.clone();
+    native["event"] = serde_json::json!("webhook.test");
+    native["timestamp"] = serde_json::json!(now.to_rfc3339());
+    let (payload, content_type) = outbound_body(agent, native, || {
+        cloudevents::envelope(
+            &uuid::Uuid::new_v4().to_string(),
+            "webhook.test",
+            None,
+            None,
+            now,
+            &data,
+        )
+    });
+
+    let started = std::time::Instant::now();
+    let result = reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(payload.to_string())
+        .timeout(std::time::Duration::from_secs(policy().timeout_secs))
+        .send()
+        .await;
+    let (status_code, response_body, error) = match result {
+        Ok(resp) => {
+            let status = resp.status().as_u16();
+            let body: String = resp.text().await.unwrap_or_default();
+            (Some(status), Some(body.chars().take(500).collect()), None)
+        }
+        Err(e) => (None, None, Some(e.to_string())),
+    };
+    WebhookTestResult {
+        url: url.to_string(),
+        delivered: status_code.is_some_and(|s| (200..300).contains(&s)),
+        status_code,
+        latency_ms: started.elapsed().as_millis() as i64,
+        response_body,
+        error,
+    }
+}
+
 /// Fire a webhook event to an agent's webhook_url
 pub fn fire_webhook(
     storage: Arc<dyn StorageBackend>,
@@ -116,13 +323,32 @@ pub fn fire_webhook(
         Some(url) if !url.is_empty() => url.clone(),
         _ => return,
     };
+    if !NotificationPreference::resolve(&agent.notification_preferences, event_type).webhook {
+        return;
+    }
+    if !quotas::take_webhook_delivery(agent.owner_id.as_deref()) {
+        return;
+    }
 
-    let payload = serde_json::json!({
-        "event": event_type,
-        "task_id": task.id,
-        "task": task,
-        "timestamp": chrono::Utc::now().to_rfc3339()
-    });
+    let (payload, content_type) = outbound_body(
+        &agent,
+        serde_json::json!({
+            "event": event_type,
+            "task_id": task.id,
+            "task": task,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }),
+        || {
+            cloudevents::envelope(
+                &uuid::Uuid::new_v4().to_string(),
+                event_type,
+                Some(&task.project_id),
+                Some(&task.id),
+                chrono::Utc::now(),
+                &serde_json::to_value(task).unwrap_or_default(),
+            )
+        },
+    );
 
     let log_id = storage.create_webhook_log(None, agent_id, event_type, &payload);
 
@@ -130,18 +356,21 @@ pub fn fire_webhook(
     let log_id_clone = log_id;
     let url = webhook_url;
     let payload_clone = payload;
+    let trace = telemetry::current();
 
     tokio::spawn(async move {
         let client = reqwest::Client::new();
-        let max_attempts: i64 = 3;
+        let policy = policy();
+        let max_attempts = policy.max_attempts as i64;
 
         for attempt in 1..=max_attempts {
-            let result: Result<reqwest::Response, reqwest::Error> = client
+            let request = client
                 .post(&url)
-                .json(&payload_clone)
-                .timeout(std::time::Duration::from_secs(10))
-                .send()
-                .await;
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(payload_clone.to_string())
+                .timeout(std::time::Duration::from_secs(policy.timeout_secs));
+            let result: Result<reqwest::Response, reqwest::Error> =
+                telemetry::send_traced(request, "task webhook", trace.as_ref()).await;
 
             match result {
                 Ok(resp) => {
@@ -161,12 +390,17 @@ pub fn fire_webhook(
                         Some(&body),
                     );
                     if delivery_status == "delivered" {
+                        crate::metrics::record_webhook("task", true);
                         return;
                     }
+                    if attempt == max_attempts {
+                        crate::metrics::record_webhook("task", false);
+                    }
                 }
                 Err(e) => {
                     let err_str: String = e.to_string();
                     let delivery_status = if attempt == max_attempts {
+                        crate::metrics::record_webhook("task", false);
                         "failed"
                     } else {
                         "pending"
@@ -184,7 +418,7 @@ pub fn fire_webhook(
 
             if attempt < max_attempts {
                 tokio::time::sleep(std::time::Duration::from_secs(
-                    attempt as u64 * attempt as u64,
+                    policy.retry_delay_secs * attempt as u64 * attempt as u64,
                 ))
                 .await;
             }
@@ -7,11 +7,24 @@ use axum::{
     },
     response::IntoResponse,
 };
+use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
 
 use crate::app::AppState;
-use crate::events::Event;
+use crate::auth;
+use crate::cloudevents::{self, EventFormat};
+use crate::events::{Delivery, Event, Subscriber};
+use crate::handlers::ws_commands;
+use crate::storage::StorageBackend;
+use opengate_models::{Agent, Identity, Notification, StoredEvent};
+
+/// Most events one `since_event_id` subscribe replays; the client resubscribes from the
+/// returned cursor for the rest.
+const REPLAY_LIMIT: usize = 1000;
+const REPLAY_BATCH: i64 = 500;
+/// How often a connection with notification subscriptions checks for new ones
+const NOTIFICATION_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+const NOTIFICATION_BATCH: i64 = 200;
 
 // ---------------------------------------------------------------------------
 // Protocol types
@@ -24,19 +37,42 @@ enum ClientMessage {
     Auth { token: String },
     #[serde(rename = "subscribe")]
     Subscribe {
+        /// `events` (default): bus events. `notifications`: the agent's own
+        /// notifications, with `events` filtering on their event type
+        target: Option<String>,
+        #[serde(default)]
         events: Vec<String>,
         filter: Option<SubscriptionFilter>,
+        /// `opengate` (default) or `cloudevents`
+        format: Option<String>,
+        /// Replay matching persisted events after this id before going live
+        since_event_id: Option<i64>,
     },
     #[serde(rename = "unsubscribe")]
     Unsubscribe { id: String },
+    /// Acknowledge one notification, or all of them with `all: true`
+    #[serde(rename = "ack")]
+    Ack {
+        notification_id: Option<i64>,
+        all: Option<bool>,
+    },
+    /// Run a REST handler over the socket (see `ws_commands`)
+    #[serde(rename = "call")]
+    Call {
+        /// Echoed on the result so the client can match it up
+        id: Option<serde_json::Value>,
+        method: String,
+        #[serde(default)]
+        params: serde_json::Value,
+    },
     #[serde(rename = "ping")]
     Ping,
 }
 
 #[derive(Debug, Deserialize, Clone)]
-struct SubscriptionFilter {
-    agent_id: Option<String>,
-    project_id: Option<String>,
+pub(crate) struct SubscriptionFilter {
+    pub(crate) agent_id: Option<String>,
+    pub(crate) project_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -52,8 +88,43 @@ enum ServerMessage {
     Event {
         sub: String,
         event: String,
+        /// Cursor to resume from with `since_event_id` (persisted events only)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        event_id: Option<i64>,
         data: serde_json::Value,
     },
+    /// End of a `since_event_id` replay. `complete` is false when the replay stopped at
+    /// `REPLAY_LIMIT`; subscribe again from `cursor` for the rest.
+    #[serde(rename = "replayed")]
+    Replayed {
+        sub: String,
+        count: usize,
+        cursor: i64,
+        complete: bool,
+    },
+    /// One of the agent's notifications, for a `notifications` subscription
+    #[serde(rename = "notification")]
+    Notification {
+        sub: String,
+        notification: Notification,
+    },
+    /// Answer to an `ack`: the notification acked, or how many with `all`
+    #[serde(rename = "acked")]
+    Acked {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        notification_id: Option<i64>,
+        count: i64,
+    },
+    /// Answer to a `call`: `result` on a 2xx status, `error` otherwise
+    #[serde(rename = "result")]
+    Result {
+        id: Option<serde_json::Value>,
+        status: u16,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<serde_json::Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
     #[serde(rename = "ping")]
     Ping,
     #[serde(rename = "pong")]
@@ -66,13 +137,26 @@ enum ServerMessage {
 // Subscription bookkeeping
 // ---------------------------------------------------------------------------
 
-struct Subscription {
-    patterns: Vec<String>,
-    filter: Option<SubscriptionFilter>,
+pub(crate) struct Subscription {
+    pub(crate) patterns: Vec<String>,
+    pub(crate) filter: Option<SubscriptionFilter>,
+    pub(crate) format: EventFormat,
+    /// Persisted events up to this id were already replayed; skip them when live
+    pub(crate) replayed_through: Option<i64>,
 }
 
-fn pattern_matches(pattern: &str, event_type: &str) -> bool {
-    if let Some(prefix) = pattern.strip_suffix(".*") {
+/// Whether a `notifications` subscription wants this notification; no patterns means all.
+fn notification_matches(patterns: &[String], notification: &Notification) -> bool {
+    patterns.is_empty()
+        || patterns
+            .iter()
+            .any(|p| pattern_matches(p, &notification.event_type))
+}
+
+pub(crate) fn pattern_matches(pattern: &str, event_type: &str) -> bool {
+    if pattern == "*" {
+        true
+    } else if let Some(prefix) = pattern.strip_suffix(".*") {
         event_type.starts_with(prefix)
             && event_type.len() > prefix.len()
             && event_type.as_bytes()[prefix.len()] == b'.'
@@ -81,7 +165,7 @@ fn pattern_matches(pattern: &str, event_type: &str) -> bool {
     }
 }
 
-fn subscription_matches(sub: &Subscription, event: &Event, self_agent_id: &str) -> bool {
+pub(crate) fn subscription_matches(sub: &Subscription, event: &Event, self_agent_id: &str) -> bool {
     // Check at least one pattern matches
     let pattern_ok = sub
         .patterns
@@ -115,6 +199,39 @@ fn subscription_matches(sub: &Subscription, event: &Event, self_agent_id: &str)
     true
 }
 
+/// Which bus events a tenant's connection may receive: those of projects the tenant
+/// can see (remembered per project) and, for events without a project, of its own
+/// agents. Unscoped connections receive everything.
+pub(crate) struct TenantScope {
+    tenant: Option<String>,
+    projects: HashMap<String, bool>,
+}
+
+impl TenantScope {
+    pub(crate) fn new(tenant: Option<&str>) -> Self {
+        Self {
+            tenant: tenant.map(str::to_string),
+            projects: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn allows(&mut self, storage: &dyn StorageBackend, event: &Event) -> bool {
+        let Some(tenant) = self.tenant.as_deref() else {
+            return true;
+        };
+        if let Some(project_id) = &event.project_id {
+            return *self
+                .projects
+                .entry(project_id.clone())
+                .or_insert_with(|| storage.project_in_tenant(Some(tenant), project_id));
+        }
+        match &event.agent_id {
+            Some(agent_id) => storage.get_agent(Some(tenant), agent_id).is_some(),
+            None => true,
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Handler
 // ---------------------------------------------------------------------------
@@ -127,16 +244,17 @@ async fn handle_socket(mut socket: WebSocket, state: AppState) {
     // -----------------------------------------------------------------------
     // Phase 1: Auth — first message must be auth
     // -----------------------------------------------------------------------
-    let (agent_id, agent_name) = match wait_for_auth(&mut socket, &state).await {
-        Some(id) => id,
+    let agent = match wait_for_auth(&mut socket, &state).await {
+        Some(agent) => agent,
         None => return, // connection already closed with error
     };
+    let agent_id = agent.id.clone();
 
     // Send auth_ok
     let identity_json = serde_json::json!({
         "type": "agent",
-        "id": agent_id,
-        "name": agent_name,
+        "id": agent.id,
+        "name": agent.name,
     });
     if send_msg(
         &mut socket,
@@ -157,13 +275,20 @@ async fn handle_socket(mut socket: WebSocket, state: AppState) {
     // -----------------------------------------------------------------------
     // Phase 2: Authenticated session
     // -----------------------------------------------------------------------
-    let event_rx = state.event_bus.subscribe();
-    run_session(socket, event_rx, agent_id).await;
+    let event_rx = state
+        .event_bus
+        .subscribe_bounded(&format!("ws:{}", agent_id));
+    let identity = Identity::AgentIdentity {
+        id: agent.id,
+        name: agent.name,
+        tenant_id: agent.owner_id,
+    };
+    run_session(socket, event_rx, state, identity).await;
 }
 
 /// Wait for the first message which must be an Auth message.
-/// Returns (agent_id, agent_name) on success, or None if auth fails.
-async fn wait_for_auth(socket: &mut WebSocket, state: &AppState) -> Option<(String, String)> {
+/// Returns the agent on success, or None if auth fails.
+async fn wait_for_auth(socket: &mut WebSocket, state: &AppState) -> Option<Agent> {
     // Give client 10 seconds to authenticate
     let deadline = tokio::time::sleep(std::time::Duration::from_secs(10));
     tokio::pin!(deadline);
@@ -184,8 +309,11 @@ async fn wait_for_auth(socket: &mut WebSocket, state: &AppState) -> Option<(Stri
                         match serde_json::from_str::<ClientMessage>(&text) {
                             Ok(ClientMessage::Auth { token }) => {
                                 let hash = state.storage.hash_api_key(&token);
-                                if let Some(agent) = state.storage.get_agent_by_key_hash(None, &hash) {
-                                    return Some((agent.id, agent.name));
+                                let agent = state.storage.get_agent_by_key_hash(None, &hash).filter(|a| {
+                                    a.owner_id.as_deref().is_none_or(|t| !auth::tenant_suspended(&*state.storage, t))
+                                });
+                                if let Some(agent) = agent {
+                                    return Some(agent);
                                 } else {
                                     let _ = send_msg(socket, &ServerMessage::Error {
                                         code: "auth_failed".to_string(),
@@ -226,10 +354,17 @@ async fn wait_for_auth(socket: &mut WebSocket, state: &AppState) -> Option<(Stri
 /// Run the authenticated WebSocket session.
 async fn run_session(
     mut socket: WebSocket,
-    mut event_rx: broadcast::Receiver<Event>,
-    agent_id: String,
+    mut event_rx: Subscriber,
+    state: AppState,
+    identity: Identity,
 ) {
+    let agent_id = identity.author_id().to_string();
+    let mut scope = TenantScope::new(identity.tenant_id());
     let mut subscriptions: HashMap<String, Subscription> = HashMap::new();
+    // `notifications` subscriptions (their patterns), fed by polling from one cursor
+    let mut notification_subs: HashMap<String, Vec<String>> = HashMap::new();
+    let mut notification_cursor = state.storage.get_last_notification_id(None, &agent_id);
+    let mut notification_poll = tokio::time::interval(NOTIFICATION_POLL);
     let mut sub_counter: u64 = 0;
     let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(30));
     // First tick completes immediately; skip it.
@@ -244,30 +379,61 @@ async fn run_session(
                 }
             }
 
+            // New notifications, while something is subscribed to them
+            _ = notification_poll.tick(), if !notification_subs.is_empty() => {
+                loop {
+                    let batch = state.storage.list_notifications_after(
+                        None, &agent_id, notification_cursor, NOTIFICATION_BATCH,
+                    );
+                    let Some(last) = batch.last() else { break };
+                    notification_cursor = last.id;
+                    for notification in batch {
+                        for (sub_id, patterns) in &notification_subs {
+                            if notification_matches(patterns, &notification)
+                                && send_notification(&mut socket, sub_id, &notification).await.is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+
             // Events from the bus
-            event_result = event_rx.recv() => {
-                match event_result {
-                    Ok(event) => {
+            delivery = event_rx.recv() => {
+                match delivery {
+                    Delivery::Event(event) if !scope.allows(&*state.storage, &event) => {}
+                    Delivery::Event(event) => {
                         for (sub_id, sub) in &subscriptions {
-                            if subscription_matches(sub, &event, &agent_id) {
-                                let msg = ServerMessage::Event {
-                                    sub: sub_id.clone(),
-                                    event: event.event_type.clone(),
-                                    data: event.data.clone(),
-                                };
-                                if send_msg(&mut socket, &msg).await.is_err() {
-                                    return;
+                            if !subscription_matches(sub, &event, &agent_id) {
+                                continue;
+                            }
+                            if let (Some(through), Some(id)) = (sub.replayed_through, event.event_id) {
+                                if id <= through {
+                                    continue;
                                 }
                             }
+                            if send_event(&mut socket, sub_id, sub, &event).await.is_err() {
+                                return;
+                            }
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                    // The client fell behind and its oldest events were dropped;
+                    // persisted ones can be fetched again with `since_event_id`
+                    Delivery::Gap(n) => {
                         let _ = send_msg(&mut socket, &ServerMessage::Error {
                             code: "events_lagged".to_string(),
                             message: format!("Missed {} events", n),
                         }).await;
                     }
-                    Err(broadcast::error::RecvError::Closed) => break,
+                    Delivery::Disconnected => {
+                        let _ = send_msg(&mut socket, &ServerMessage::Error {
+                            code: "slow_consumer".to_string(),
+                            message: "Too far behind on events; reconnect and resume with since_event_id".to_string(),
+                        }).await;
+                        let _ = send_close(&mut socket).await;
+                        break;
+                    }
                 }
             }
 
@@ -281,23 +447,120 @@ async fn run_session(
                                     break;
                                 }
                             }
-                            Ok(ClientMessage::Subscribe { events, filter }) => {
+                            Ok(ClientMessage::Subscribe { target: Some(target), events, .. })
+                                if target == "notifications" =>
+                            {
+                                sub_counter += 1;
+                                let id = format!("sub-{}", sub_counter);
+                                if send_msg(&mut socket, &ServerMessage::Subscribed { id: id.clone() }).await.is_err() {
+                                    break;
+                                }
+                                // Unread backlog first, oldest first; the poll picks up the rest
+                                let mut unread = state.storage.list_notifications(None, &agent_id, Some(true));
+                                unread.retain(|n| n.id <= notification_cursor);
+                                unread.reverse();
+                                for notification in &unread {
+                                    if notification_matches(&events, notification)
+                                        && send_notification(&mut socket, &id, notification).await.is_err()
+                                    {
+                                        return;
+                                    }
+                                }
+                                notification_subs.insert(id, events);
+                            }
+                            Ok(ClientMessage::Subscribe { target: Some(target), .. }) if target != "events" => {
+                                let _ = send_msg(&mut socket, &ServerMessage::Error {
+                                    code: "invalid_target".to_string(),
+                                    message: format!("Unknown target '{}': use events or notifications", target),
+                                }).await;
+                            }
+                            Ok(ClientMessage::Subscribe { events, filter, format, since_event_id, .. }) => {
+                                let format = match format.as_deref() {
+                                    None => cloudevents::default_format(),
+                                    Some(f) => match EventFormat::from_str(f) {
+                                        Some(format) => format,
+                                        None => {
+                                            let _ = send_msg(&mut socket, &ServerMessage::Error {
+                                                code: "invalid_format".to_string(),
+                                                message: format!("Unknown format '{}': use opengate or cloudevents", f),
+                                            }).await;
+                                            continue;
+                                        }
+                                    },
+                                };
                                 sub_counter += 1;
                                 let id = format!("sub-{}", sub_counter);
-                                subscriptions.insert(id.clone(), Subscription {
+                                let mut sub = Subscription {
                                     patterns: events,
                                     filter,
-                                });
-                                if send_msg(&mut socket, &ServerMessage::Subscribed { id }).await.is_err() {
+                                    format,
+                                    replayed_through: None,
+                                };
+                                if send_msg(&mut socket, &ServerMessage::Subscribed { id: id.clone() }).await.is_err() {
                                     break;
                                 }
+                                if let Some(since) = since_event_id {
+                                    let (missed, cursor, complete) =
+                                        missed_events(&*state.storage, identity.tenant_id(), &sub, since, &agent_id);
+                                    for event in &missed {
+                                        if send_event(&mut socket, &id, &sub, event).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                    let replayed = ServerMessage::Replayed {
+                                        sub: id.clone(),
+                                        count: missed.len(),
+                                        cursor,
+                                        complete,
+                                    };
+                                    if send_msg(&mut socket, &replayed).await.is_err() {
+                                        break;
+                                    }
+                                    sub.replayed_through = Some(cursor);
+                                }
+                                subscriptions.insert(id, sub);
                             }
                             Ok(ClientMessage::Unsubscribe { id }) => {
                                 subscriptions.remove(&id);
+                                notification_subs.remove(&id);
                                 if send_msg(&mut socket, &ServerMessage::Unsubscribed { id }).await.is_err() {
                                     break;
                                 }
                             }
+                            Ok(ClientMessage::Ack { notification_id, all }) => {
+                                let reply = match (notification_id, all) {
+                                    (_, Some(true)) => ServerMessage::Acked {
+                                        notification_id: None,
+                                        count: state.storage.ack_all_notifications(None, &agent_id),
+                                    },
+                                    (Some(nid), _) if state.storage.ack_notification(None, &agent_id, nid) => {
+                                        ServerMessage::Acked { notification_id: Some(nid), count: 1 }
+                                    }
+                                    (Some(nid), _) => ServerMessage::Error {
+                                        code: "not_found".to_string(),
+                                        message: format!("Notification {} not found", nid),
+                                    },
+                                    (None, _) => ServerMessage::Error {
+                                        code: "invalid_message".to_string(),
+                                        message: "ack needs notification_id or all: true".to_string(),
+                                    },
+                                };
+                                if send_msg(&mut socket, &reply).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(ClientMessage::Call { id, method, params }) => {
+                                let (status, outcome) =
+                                    ws_commands::call(&state, &identity, &method, params).await;
+                                let (result, error) = match outcome {
+                                    Ok(result) => (Some(result), None),
+                                    Err(error) => (None, Some(error)),
+                                };
+                                let reply = ServerMessage::Result { id, status, result, error };
+                                if send_msg(&mut socket, &reply).await.is_err() {
+                                    break;
+                                }
+                            }
                             Ok(ClientMessage::Auth { .. }) => {
                                 let _ = send_msg(&mut socket, &ServerMessage::Error {
                                     code: "already_authenticated".to_string(),
@@ -321,12 +584,116 @@ async fn run_session(
     }
 }
 
+/// Persisted events after `since` that `tenant` may see and match `sub`, oldest first, with the cursor the
+/// scan reached and whether it got to the end of the log.
+fn missed_events(
+    storage: &dyn StorageBackend,
+    tenant: Option<&str>,
+    sub: &Subscription,
+    since: i64,
+    self_agent_id: &str,
+) -> (Vec<Event>, i64, bool) {
+    let mut missed = Vec::new();
+    let mut cursor = since;
+    loop {
+        let batch = storage.list_events_after(tenant, cursor, REPLAY_BATCH);
+        if batch.is_empty() {
+            return (missed, cursor, true);
+        }
+        for stored in batch {
+            cursor = stored.id;
+            let event = bus_event(storage, stored);
+            if subscription_matches(sub, &event, self_agent_id) {
+                missed.push(event);
+                if missed.len() == REPLAY_LIMIT {
+                    return (missed, cursor, false);
+                }
+            }
+        }
+    }
+}
+
+/// A stored event in the shape it had on the bus. Task events carry the task (in its
+/// current state) as data, like live ones; others carry the stored payload.
+fn bus_event(storage: &dyn StorageBackend, stored: StoredEvent) -> Event {
+    let task = stored
+        .task_id
+        .as_deref()
+        .and_then(|id| storage.get_task(None, id));
+    let timestamp = NaiveDateTime::parse_from_str(&stored.created_at, "%Y-%m-%d %H:%M:%S")
+        .map(|t| t.and_utc())
+        .unwrap_or_else(|_| Utc::now());
+    Event {
+        event_type: stored.event_type,
+        project_id: Some(stored.project_id),
+        agent_id: task.as_ref().and_then(|t| t.assignee_id.clone()),
+        event_id: Some(stored.id),
+        data: match task {
+            Some(task) => serde_json::to_value(task).unwrap_or_default(),
+            None => stored.payload,
+        },
+        timestamp,
+    }
+}
+
+/// Deliver one event to one subscription in its format.
+async fn send_event(
+    socket: &mut WebSocket,
+    sub_id: &str,
+    sub: &Subscription,
+    event: &Event,
+) -> Result<(), ()> {
+    match sub.format {
+        EventFormat::Native => {
+            send_msg(
+                socket,
+                &ServerMessage::Event {
+                    sub: sub_id.to_string(),
+                    event: event.event_type.clone(),
+                    event_id: event.event_id,
+                    data: event.data.clone(),
+                },
+            )
+            .await
+        }
+        EventFormat::CloudEvents => {
+            // The CloudEvent itself, with the subscription as an extension attribute
+            let mut ce = cloudevents::from_bus_event(event);
+            ce["subscription"] = serde_json::json!(sub_id);
+            send_json(socket, &ce).await
+        }
+    }
+}
+
+async fn send_notification(
+    socket: &mut WebSocket,
+    sub_id: &str,
+    notification: &Notification,
+) -> Result<(), ()> {
+    send_msg(
+        socket,
+        &ServerMessage::Notification {
+            sub: sub_id.to_string(),
+            notification: notification.clone(),
+        },
+    )
+    .await
+}
+
 /// Serialize and send a ServerMessage as text.
 async fn send_msg(socket: &mut WebSocket, msg: &ServerMessage) -> Result<(), ()> {
     let json = serde_json::to_string(msg).map_err(|_| ())?;
     socket.send(Message::Text(json)).await.map_err(|_| ())
 }
 
+/// Send a raw JSON value as text.
+async fn send_json(socket: &mut WebSocket, value: &serde_json::Value) -> Result<(), ()> {
+    socket
+        .send(Message::Text(value.to_string()))
+        .await
+        .map_err(|_| ())
+}
+
 /// Send a close frame without consuming the socket.
 async fn send_close(socket: &mut WebSocket) -> Result<(), ()> {
     socket.send(Message::Close(None)).await.map_err(|_| ())
@@ -356,16 +723,25 @@ mod tests {
         assert!(!pattern_matches("task.*", "taskfoo"));
     }
 
+    #[test]
+    fn test_pattern_match_all() {
+        assert!(pattern_matches("*", "task.created"));
+        assert!(pattern_matches("*", "knowledge.updated"));
+    }
+
     #[test]
     fn test_subscription_matches_no_filter() {
         let sub = Subscription {
             patterns: vec!["task.*".to_string()],
             filter: None,
+            format: EventFormat::Native,
+            replayed_through: None,
         };
         let event = Event {
             event_type: "task.created".to_string(),
             project_id: Some("p1".to_string()),
             agent_id: Some("a1".to_string()),
+            event_id: None,
             data: serde_json::Value::Null,
             timestamp: Utc::now(),
         };
@@ -380,11 +756,14 @@ mod tests {
                 agent_id: Some("self".to_string()),
                 project_id: None,
             }),
+            format: EventFormat::Native,
+            replayed_through: None,
         };
         let event_match = Event {
             event_type: "task.assigned".to_string(),
             project_id: None,
             agent_id: Some("agent-42".to_string()),
+            event_id: None,
             data: serde_json::Value::Null,
             timestamp: Utc::now(),
         };
@@ -392,6 +771,7 @@ mod tests {
             event_type: "task.assigned".to_string(),
             project_id: None,
             agent_id: Some("other-agent".to_string()),
+            event_id: None,
             data: serde_json::Value::Null,
             timestamp: Utc::now(),
         };
@@ -407,11 +787,14 @@ mod tests {
                 agent_id: None,
                 project_id: Some("proj-1".to_string()),
             }),
+            format: EventFormat::Native,
+            replayed_through: None,
         };
         let event_match = Event {
             event_type: "task.created".to_string(),
             project_id: Some("proj-1".to_string()),
             agent_id: None,
+            event_id: None,
             data: serde_json::Value::Null,
             timestamp: Utc::now(),
         };
@@ -419,6 +802,7 @@ mod tests {
             event_type: "task.created".to_string(),
             project_id: Some("proj-2".to_string()),
             agent_id: None,
+            event_id: None,
             data: serde_json::Value::Null,
             timestamp: Utc::now(),
         };
@@ -431,11 +815,14 @@ mod tests {
         let sub = Subscription {
             patterns: vec!["project.*".to_string()],
             filter: None,
+            format: EventFormat::Native,
+            replayed_through: None,
         };
         let event = Event {
             event_type: "task.created".to_string(),
             project_id: None,
             agent_id: None,
+            event_id: None,
             data: serde_json::Value::Null,
             timestamp: Utc::now(),
         };
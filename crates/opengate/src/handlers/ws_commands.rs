@@ -0,0 +1,190 @@
+//! Request/response calls over the WebSocket, so an interactive agent can work entirely
+//! over one socket.
+//!
+//! The client sends `{"type": "call", "id": "r1", "method": "claim_task", "params":
+//! {"task_id": "..."}}`; the server answers `{"type": "result", "id": "r1", "status": 200,
+//! "result": {...}}`, or `"error": "..."` instead of `"result"` when the status isn't 2xx.
+//! Each method runs the same handler as its REST endpoint, as the authenticated agent, so
+//! validation, events and webhooks behave exactly as over HTTP. Method names match the
+//! MCP tools.
+
+use axum::{
+    body::to_bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::app::AppState;
+use crate::auth::ActingIdentity;
+use crate::handlers::{activity, agents, tasks};
+use opengate_models::*;
+
+/// Methods a `call` may name.
+pub const METHODS: &[&str] = &[
+    "get_task",
+    "list_tasks",
+    "my_tasks",
+    "next_task",
+    "claim_task",
+    "release_task",
+    "complete_task",
+    "block_task",
+    "update_task",
+    "update_context",
+    "post_comment",
+    "heartbeat",
+    "check_inbox",
+    "get_notifications",
+    "ack_notification",
+];
+
+/// Largest response body a call returns.
+const MAX_RESULT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Run `method` as `identity` and return the HTTP status with the `result` (2xx) or
+/// `error` message.
+pub(crate) async fn call(
+    state: &AppState,
+    identity: &Identity,
+    method: &str,
+    params: serde_json::Value,
+) -> (u16, Result<serde_json::Value, String>) {
+    let response = match dispatch(state, identity, method, params).await {
+        Ok(response) => response,
+        Err(invalid) => return (StatusCode::UNPROCESSABLE_ENTITY.as_u16(), Err(invalid)),
+    };
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), MAX_RESULT_BYTES)
+        .await
+        .unwrap_or_default();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+    if status.is_success() {
+        return (status.as_u16(), Ok(body));
+    }
+    let message = match body.get("error").and_then(|e| e.as_str()) {
+        Some(error) => error.to_string(),
+        None if body.is_null() => status
+            .canonical_reason()
+            .unwrap_or("Request failed")
+            .to_string(),
+        None => body.to_string(),
+    };
+    (status.as_u16(), Err(message))
+}
+
+/// The handler's response, or why the params don't fit the method.
+async fn dispatch(
+    state: &AppState,
+    identity: &Identity,
+    method: &str,
+    mut params: serde_json::Value,
+) -> Result<Response, String> {
+    if params.is_null() {
+        params = serde_json::json!({});
+    }
+    let state = || State(state.clone());
+    let acting = || ActingIdentity {
+        identity: identity.clone(),
+        delegated_by: None,
+    };
+    let identity = identity.clone();
+
+    let response = match method {
+        "get_task" => tasks::get_task(state(), identity, task_path(&mut params)?)
+            .await
+            .into_response(),
+        "list_tasks" => tasks::list_tasks_global(
+            state(),
+            identity,
+            Query(parse(params)?),
+            Query(FormatQuery { format: None }),
+        )
+        .await
+        .into_response(),
+        "my_tasks" => tasks::my_tasks(state(), identity).await.into_response(),
+        "next_task" => tasks::next_task(state(), identity, Query(parse(params)?))
+            .await
+            .into_response(),
+        "claim_task" => tasks::claim_task(state(), acting(), task_path(&mut params)?)
+            .await
+            .into_response(),
+        "release_task" => tasks::release_task(state(), identity, task_path(&mut params)?)
+            .await
+            .into_response(),
+        "complete_task" => {
+            let path = task_path(&mut params)?;
+            tasks::complete_task(state(), acting(), path, Json(parse(params)?))
+                .await
+                .into_response()
+        }
+        "block_task" => {
+            let path = task_path(&mut params)?;
+            tasks::block_task(state(), identity, path, Json(parse(params)?))
+                .await
+                .into_response()
+        }
+        "update_task" => {
+            let path = task_path(&mut params)?;
+            tasks::update_task(state(), identity, path, Json(parse(params)?))
+                .await
+                .into_response()
+        }
+        "update_context" => {
+            let path = task_path(&mut params)?;
+            let patch = params
+                .get_mut("context")
+                .map(serde_json::Value::take)
+                .ok_or("context is required")?;
+            tasks::update_context(state(), identity, path, Json(patch))
+                .await
+                .into_response()
+        }
+        "post_comment" => {
+            let path = task_path(&mut params)?;
+            activity::create_activity(state(), acting(), path, Json(parse(params)?))
+                .await
+                .into_response()
+        }
+        "heartbeat" => agents::heartbeat(state(), identity, Some(Json(parse(params)?)))
+            .await
+            .into_response(),
+        "check_inbox" => agents::inbox(state(), identity).await.into_response(),
+        "get_notifications" => agents::my_notifications(state(), identity, Query(parse(params)?))
+            .await
+            .into_response(),
+        "ack_notification" => {
+            let id = params
+                .get("notification_id")
+                .and_then(|v| v.as_i64())
+                .ok_or("notification_id is required")?;
+            agents::ack_notification(state(), identity, Path(id))
+                .await
+                .into_response()
+        }
+        other => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": format!("Unknown method '{}'. Available: {}", other, METHODS.join(", "))
+            })),
+        )
+            .into_response(),
+    };
+    Ok(response)
+}
+
+/// Take `task_id` out of the params, leaving the rest as the request body.
+fn task_path(params: &mut serde_json::Value) -> Result<Path<String>, String> {
+    params
+        .as_object_mut()
+        .and_then(|p| p.remove("task_id"))
+        .and_then(|v| v.as_str().map(str::to_string))
+        .map(Path)
+        .ok_or_else(|| "task_id is required".to_string())
+}
+
+fn parse<T: DeserializeOwned>(params: serde_json::Value) -> Result<T, String> {
+    serde_json::from_value(params).map_err(|e| format!("Invalid params: {}", e))
+}
@@ -0,0 +1,178 @@
+//! iCalendar (RFC 5545) rendering of a project's schedule.
+//!
+//! Each task contributes up to two VEVENTs: one at `scheduled_at` (carrying an RRULE
+//! when it is the root of an active recurring series) and one on its `due_date`.
+//! Occurrences spawned from an active series are folded into the root's RRULE, with
+//! skipped (cancelled) occurrences emitted as EXDATEs.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::recurrence;
+use opengate_models::{Project, Task};
+
+/// Render a VCALENDAR for `project` from its tasks.
+pub fn render_schedule(project: &Project, tasks: &[Task]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//OpenGate//Schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(&project.name)),
+    ];
+
+    let is_active_series_root = |t: &Task| {
+        t.recurrence_parent_id.is_none()
+            && !t.recurrence_paused
+            && t.scheduled_at.is_some()
+            && t.recurrence_rule
+                .as_ref()
+                .and_then(recurrence::to_rrule_string)
+                .is_some()
+    };
+
+    for task in tasks {
+        // Occurrences of an active series are represented by the root's RRULE
+        let folded_into_root = task.recurrence_parent_id.as_deref().is_some_and(|pid| {
+            tasks
+                .iter()
+                .any(|root| root.id == pid && is_active_series_root(root))
+        });
+
+        if let Some(start) = task.scheduled_at.as_deref().and_then(format_datetime) {
+            if !folded_into_root {
+                lines.push("BEGIN:VEVENT".to_string());
+                lines.push(format!("UID:{}-scheduled@opengate", task.id));
+                lines.push(format!("DTSTAMP:{}", stamp));
+                lines.push(format!("DTSTART:{}", start));
+                lines.push(format!("SUMMARY:{}", escape_text(&task.title)));
+                lines.push(format!("DESCRIPTION:{}", escape_text(&describe(task))));
+                if is_active_series_root(task) {
+                    if let Some(rrule) = task
+                        .recurrence_rule
+                        .as_ref()
+                        .and_then(recurrence::to_rrule_string)
+                    {
+                        lines.push(format!("RRULE:{}", rrule));
+                    }
+                    for skipped in tasks.iter().filter(|t| {
+                        (t.id == task.id
+                            || t.recurrence_parent_id.as_deref() == Some(task.id.as_str()))
+                            && t.status == "cancelled"
+                    }) {
+                        if let Some(at) = skipped.scheduled_at.as_deref().and_then(format_datetime)
+                        {
+                            lines.push(format!("EXDATE:{}", at));
+                        }
+                    }
+                } else if task.status == "cancelled" {
+                    lines.push("STATUS:CANCELLED".to_string());
+                }
+                push_categories(&mut lines, task);
+                lines.push("END:VEVENT".to_string());
+            }
+        }
+
+        if let Some(ref due) = task.due_date {
+            let dtstart = if let Some(date) = format_date(due) {
+                format!("DTSTART;VALUE=DATE:{}", date)
+            } else if let Some(at) = format_datetime(due) {
+                format!("DTSTART:{}", at)
+            } else {
+                continue;
+            };
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:{}-due@opengate", task.id));
+            lines.push(format!("DTSTAMP:{}", stamp));
+            lines.push(dtstart);
+            lines.push(format!(
+                "SUMMARY:{}",
+                escape_text(&format!("Due: {}", task.title))
+            ));
+            lines.push(format!("DESCRIPTION:{}", escape_text(&describe(task))));
+            if task.status == "cancelled" {
+                lines.push("STATUS:CANCELLED".to_string());
+            }
+            push_categories(&mut lines, task);
+            lines.push("END:VEVENT".to_string());
+        }
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&fold_line(&line));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn describe(task: &Task) -> String {
+    let mut desc = format!("Status: {}\nPriority: {}", task.status, task.priority);
+    if let Some(ref assignee) = task.assignee_id {
+        desc.push_str(&format!("\nAssignee: {}", assignee));
+    }
+    if task.recurrence_paused {
+        desc.push_str("\nRecurrence paused");
+    }
+    if let Some(ref d) = task.description {
+        if !d.is_empty() {
+            desc.push_str("\n\n");
+            desc.push_str(d);
+        }
+    }
+    desc
+}
+
+fn push_categories(lines: &mut Vec<String>, task: &Task) {
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|t| escape_text(t)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+}
+
+/// RFC 3339 → UTC basic format (`20260223T090000Z`).
+fn format_datetime(s: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+/// Plain `YYYY-MM-DD` → `YYYYMMDD` (all-day event).
+fn format_date(s: &str) -> Option<String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.format("%Y%m%d").to_string())
+}
+
+/// Escape a TEXT value (RFC 5545 §3.3.11).
+fn escape_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold content lines longer than 75 octets (RFC 5545 §3.1), never splitting a UTF-8 char.
+fn fold_line(line: &str) -> String {
+    if line.len() <= 75 {
+        return line.to_string();
+    }
+    let mut out = String::new();
+    let mut current = 0;
+    let mut limit = 75;
+    for ch in line.chars() {
+        if current + ch.len_utf8() > limit {
+            out.push_str("\r\n ");
+            current = 0;
+            // continuation lines start with a space, which counts toward the limit
+            limit = 74;
+        }
+        out.push(ch);
+        current += ch.len_utf8();
+    }
+    out
+}
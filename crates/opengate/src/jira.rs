@@ -0,0 +1,448 @@
+//! Jira import for `POST /api/projects/:id/import/jira`: issues from a JSON export (the
+//! REST search response, or a bare array of its issues) or a CSV export become tasks, and
+//! their blocking links become dependencies.
+//!
+//! Statuses map by name, falling back to Jira's status category; priorities map by name.
+//! Imported tasks carry `context.external_ref = "jira:<key>"`, so importing the same
+//! export again skips the issues it already created.
+
+use std::collections::{BTreeSet, HashSet};
+
+use crate::csv;
+use opengate_models::*;
+
+/// A Jira issue as read from an export.
+#[derive(Debug, Default, PartialEq)]
+pub struct JiraIssue {
+    pub key: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub status: String,
+    /// `new`, `indeterminate` or `done`, when the export has it
+    pub status_category: Option<String>,
+    pub priority: Option<String>,
+    pub labels: Vec<String>,
+    pub links: Vec<JiraLink>,
+}
+
+/// A link from an issue to `key`.
+#[derive(Debug, PartialEq)]
+pub struct JiraLink {
+    /// Link type name, e.g. `Blocks` or `Relates`
+    pub link_type: String,
+    /// Whether the issue is on the link's outward side (`blocks`, `depends on`), rather
+    /// than the inward one (`is blocked by`, `is depended on by`)
+    pub outward: bool,
+    pub key: String,
+}
+
+pub fn external_ref(key: &str) -> String {
+    format!("jira:{}", key)
+}
+
+/// The OpenGate status for a Jira status, if there is an obvious one.
+pub fn map_status(name: &str, category: Option<&str>) -> Option<&'static str> {
+    let status = match name.trim().to_lowercase().as_str() {
+        "backlog" => "backlog",
+        "to do" | "todo" | "open" | "new" | "reopened" | "selected for development" => "todo",
+        "in progress" | "in development" | "doing" => "in_progress",
+        "in review" | "review" | "code review" | "in qa" | "qa" | "testing" => "review",
+        "blocked" | "on hold" => "blocked",
+        "done" | "closed" | "resolved" | "complete" | "completed" => "done",
+        "won't do" | "won't fix" | "cancelled" | "canceled" | "declined" | "rejected" => {
+            "cancelled"
+        }
+        _ => match category?.trim().to_lowercase().as_str() {
+            "new" | "to do" => "todo",
+            "indeterminate" | "in progress" => "in_progress",
+            "done" => "done",
+            _ => return None,
+        },
+    };
+    Some(status)
+}
+
+/// The OpenGate priority for a Jira priority, if there is an obvious one.
+pub fn map_priority(name: &str) -> Option<&'static str> {
+    let priority = match name.trim().to_lowercase().as_str() {
+        "highest" | "blocker" | "critical" | "urgent" => "critical",
+        "high" | "major" => "high",
+        "medium" | "normal" => "medium",
+        "low" | "lowest" | "minor" | "trivial" => "low",
+        _ => return None,
+    };
+    Some(priority)
+}
+
+// --- Parsing ---
+
+/// Issues from a JSON export: `{"issues": [...]}` or `[...]`, each with a `key` and
+/// `fields`.
+pub fn parse_json(body: &[u8]) -> Result<Vec<JiraIssue>, String> {
+    let value: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let issues = value
+        .get("issues")
+        .unwrap_or(&value)
+        .as_array()
+        .ok_or("Expected an array of issues, or an object with an \"issues\" array")?;
+    issues
+        .iter()
+        .enumerate()
+        .map(|(i, issue)| {
+            let fields = &issue["fields"];
+            let text = |v: &serde_json::Value| v.as_str().map(str::to_string);
+            let key = text(&issue["key"]).ok_or(format!("Issue {} has no key", i))?;
+            let summary =
+                text(&fields["summary"]).ok_or(format!("Issue {} has no summary", key))?;
+            let links = fields["issuelinks"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|link| {
+                    let link_type = text(&link["type"]["name"]).unwrap_or_default();
+                    let (outward, other) = match link.get("outwardIssue") {
+                        Some(other) => (true, other),
+                        None => (false, link.get("inwardIssue")?),
+                    };
+                    Some(JiraLink {
+                        link_type,
+                        outward,
+                        key: text(&other["key"])?,
+                    })
+                })
+                .collect();
+            Ok(JiraIssue {
+                key,
+                summary,
+                description: description_text(&fields["description"]),
+                status: text(&fields["status"]["name"]).unwrap_or_default(),
+                status_category: text(&fields["status"]["statusCategory"]["key"]),
+                priority: text(&fields["priority"]["name"]),
+                labels: fields["labels"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(text)
+                    .collect(),
+                links,
+            })
+        })
+        .collect()
+}
+
+/// A description as plain text: Jira Cloud's v3 API returns an Atlassian Document Format
+/// tree rather than a string.
+fn description_text(value: &serde_json::Value) -> Option<String> {
+    fn walk(node: &serde_json::Value, out: &mut String) {
+        if let Some(text) = node["text"].as_str() {
+            out.push_str(text);
+        }
+        for child in node["content"].as_array().into_iter().flatten() {
+            walk(child, out);
+        }
+        if matches!(
+            node["type"].as_str(),
+            Some("paragraph" | "heading" | "listItem" | "codeBlock" | "hardBreak")
+        ) {
+            out.push('\n');
+        }
+    }
+    let text = match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Object(_) => {
+            let mut out = String::new();
+            walk(value, &mut out);
+            out
+        }
+        _ => return None,
+    };
+    Some(text.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// Issues from a CSV export. Jira repeats a column for each value of a multi-valued field
+/// (`Labels`, `Outward issue link (Blocks)`, ...), so every column of a name is read.
+pub fn parse_csv(body: &[u8]) -> Result<Vec<JiraIssue>, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "CSV export is not UTF-8")?;
+    let mut records = csv::parse(text)?.into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or("CSV export is empty")?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let key_col = column("Issue key").ok_or("CSV export has no \"Issue key\" column")?;
+    let summary_col = column("Summary").ok_or("CSV export has no \"Summary\" column")?;
+    let (description_col, status_col, category_col, priority_col) = (
+        column("Description"),
+        column("Status"),
+        column("Status Category"),
+        column("Priority"),
+    );
+    // `Inward issue link (Blocks)` → (false, "Blocks")
+    let link_cols: Vec<(usize, bool, String)> = header
+        .iter()
+        .enumerate()
+        .filter_map(|(i, h)| {
+            let (side, rest) = h.split_once(" issue link (")?;
+            let outward = match side.to_lowercase().as_str() {
+                "outward" => true,
+                "inward" => false,
+                _ => return None,
+            };
+            Some((i, outward, rest.strip_suffix(')')?.to_string()))
+        })
+        .collect();
+
+    records
+        .enumerate()
+        .map(|(row, record)| {
+            let get = |col: Option<usize>| {
+                col.and_then(|c| record.get(c))
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+            };
+            let key = get(Some(key_col)).ok_or(format!("Row {} has no issue key", row + 2))?;
+            let summary = get(Some(summary_col)).ok_or(format!("Issue {} has no summary", key))?;
+            let labels = header
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| h.eq_ignore_ascii_case("Labels"))
+                .filter_map(|(i, _)| get(Some(i)))
+                .collect();
+            let links = link_cols
+                .iter()
+                .filter_map(|(i, outward, link_type)| {
+                    Some(JiraLink {
+                        link_type: link_type.clone(),
+                        outward: *outward,
+                        key: get(Some(*i))?,
+                    })
+                })
+                .collect();
+            Ok(JiraIssue {
+                key,
+                summary,
+                description: get(description_col),
+                status: get(status_col).unwrap_or_default(),
+                status_category: get(category_col),
+                priority: get(priority_col),
+                labels,
+                links,
+            })
+        })
+        .collect()
+}
+
+// --- Mapping ---
+
+/// What an import does: the tasks to create, dependencies to add, issues to skip.
+pub struct Plan {
+    /// New issues, each with its mapping (its `task_id` still unset)
+    pub issues: Vec<(JiraIssue, JiraIssueMapping)>,
+    pub dependencies: Vec<JiraDependencyMapping>,
+    pub skipped: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Map `issues` onto tasks. Keys in `imported` were created by an earlier import: they are
+/// skipped, but links to them still become dependencies.
+pub fn plan(issues: Vec<JiraIssue>, imported: &HashSet<String>) -> Result<Plan, String> {
+    let mut keys = HashSet::new();
+    for issue in &issues {
+        if !keys.insert(issue.key.clone()) {
+            return Err(format!("Issue {} appears more than once", issue.key));
+        }
+    }
+    let known = |key: &str| keys.contains(key) || imported.contains(key);
+
+    let mut warnings = Vec::new();
+    // (dependent, dependency); each link is usually exported from both of its ends
+    let mut edges = BTreeSet::new();
+    for issue in &issues {
+        for link in &issue.links {
+            let kind = link.link_type.to_lowercase();
+            let blocks = if kind.contains("block") {
+                link.outward
+            } else if kind.contains("depend") {
+                !link.outward
+            } else {
+                continue;
+            };
+            if !known(&link.key) {
+                warnings.push(format!(
+                    "{}: link to {} left out; it is not in this export or an earlier one",
+                    issue.key, link.key
+                ));
+                continue;
+            }
+            if link.key == issue.key {
+                continue;
+            }
+            let edge = if blocks {
+                (link.key.clone(), issue.key.clone())
+            } else {
+                (issue.key.clone(), link.key.clone())
+            };
+            edges.insert(edge);
+        }
+    }
+
+    let mut planned = Vec::new();
+    let mut skipped = Vec::new();
+    for issue in issues {
+        if imported.contains(&issue.key) {
+            skipped.push(issue.key);
+            continue;
+        }
+        let status =
+            map_status(&issue.status, issue.status_category.as_deref()).unwrap_or_else(|| {
+                warnings.push(format!(
+                    "{}: status '{}' has no counterpart; imported as backlog",
+                    issue.key, issue.status
+                ));
+                "backlog"
+            });
+        let priority = match issue.priority.as_deref() {
+            None => "medium",
+            Some(p) => map_priority(p).unwrap_or_else(|| {
+                warnings.push(format!(
+                    "{}: priority '{}' has no counterpart; imported as medium",
+                    issue.key, p
+                ));
+                "medium"
+            }),
+        };
+        let mapping = JiraIssueMapping {
+            key: issue.key.clone(),
+            title: issue.summary.clone(),
+            jira_status: issue.status.clone(),
+            status: status.to_string(),
+            jira_priority: issue.priority.clone(),
+            priority: priority.to_string(),
+            tags: issue.labels.clone(),
+            task_id: None,
+        };
+        planned.push((issue, mapping));
+    }
+
+    Ok(Plan {
+        issues: planned,
+        dependencies: edges
+            .into_iter()
+            .map(|(key, depends_on)| JiraDependencyMapping { key, depends_on })
+            .collect(),
+        skipped,
+        warnings,
+    })
+}
+
+/// The task `issue` is created as, in `backlog`.
+pub fn issue_task(issue: &JiraIssue, mapping: &JiraIssueMapping) -> CreateTask {
+    CreateTask {
+        title: issue.summary.clone(),
+        description: issue.description.clone(),
+        priority: Some(mapping.priority.clone()),
+        tags: Some(issue.labels.clone()).filter(|l| !l.is_empty()),
+        context: Some(serde_json::json!({ "external_ref": external_ref(&issue.key) })),
+        output: None,
+        due_date: None,
+        assignee_type: None,
+        assignee_id: None,
+        scheduled_at: None,
+        recurrence_rule: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_statuses_and_priorities() {
+        assert_eq!(map_status("In Progress", None), Some("in_progress"));
+        assert_eq!(map_status("Won't Do", Some("done")), Some("cancelled"));
+        assert_eq!(
+            map_status("Ready for QA", Some("indeterminate")),
+            Some("in_progress")
+        );
+        assert_eq!(map_status("Triage", None), None);
+        assert_eq!(map_priority("Blocker"), Some("critical"));
+        assert_eq!(map_priority("Lowest"), Some("low"));
+        assert_eq!(map_priority("P7"), None);
+    }
+
+    #[test]
+    fn reads_csv_exports() {
+        let csv = "Summary,Issue key,Status,Priority,Labels,Labels,Outward issue link (Blocks),Inward issue link (Relates)\n\
+                   Login,WEB-1,To Do,High,auth,ui,WEB-2,WEB-3\n\
+                   Signup,WEB-2,Done,,,,,\n";
+        let issues = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].key, "WEB-1");
+        assert_eq!(issues[0].labels, vec!["auth", "ui"]);
+        assert_eq!(
+            issues[0].links,
+            vec![
+                JiraLink {
+                    link_type: "Blocks".to_string(),
+                    outward: true,
+                    key: "WEB-2".to_string(),
+                },
+                JiraLink {
+                    link_type: "Relates".to_string(),
+                    outward: false,
+                    key: "WEB-3".to_string(),
+                },
+            ]
+        );
+        assert_eq!(issues[1].priority, None);
+    }
+
+    #[test]
+    fn plans_dependencies_from_blocking_links() {
+        let issue = |key: &str, links: Vec<JiraLink>| JiraIssue {
+            key: key.to_string(),
+            summary: key.to_string(),
+            status: "To Do".to_string(),
+            links,
+            ..Default::default()
+        };
+        let link = |link_type: &str, outward: bool, key: &str| JiraLink {
+            link_type: link_type.to_string(),
+            outward,
+            key: key.to_string(),
+        };
+        let issues = vec![
+            // A blocks B, seen from both ends
+            issue("A", vec![link("Blocks", true, "B")]),
+            issue(
+                "B",
+                vec![link("Blocks", false, "A"), link("Relates", true, "A")],
+            ),
+            // C depends on OLD, from an earlier import, and links to a missing issue
+            issue(
+                "C",
+                vec![
+                    link("Dependency", true, "OLD"),
+                    link("Blocks", true, "GONE"),
+                ],
+            ),
+        ];
+        let imported = HashSet::from(["OLD".to_string()]);
+        let plan = plan(issues, &imported).unwrap();
+        let deps: Vec<(&str, &str)> = plan
+            .dependencies
+            .iter()
+            .map(|d| (d.key.as_str(), d.depends_on.as_str()))
+            .collect();
+        assert_eq!(deps, vec![("B", "A"), ("C", "OLD")]);
+        assert_eq!(plan.issues.len(), 3);
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("GONE"));
+
+        let twice = vec![issue("A", vec![]), issue("A", vec![])];
+        assert!(super::plan(twice, &HashSet::new()).is_err());
+    }
+}
@@ -0,0 +1,668 @@
+//! Kafka event sink with at-least-once delivery.
+//!
+//! Stored events whose type matches a route (`task.*=opengate.tasks`, same patterns as
+//! WS subscriptions, first match wins) get an `event_outbox` row in the same write as
+//! their `events` row, in the shape they have on the bus; events that only live on the
+//! bus are not sent. A background flusher produces the outbox to Kafka oldest-first and
+//! deletes rows only once the partition leader acknowledges them (`acks=all`), so
+//! events queue up while the broker is down and are delivered when it comes back —
+//! possibly more than once if an acknowledgement is lost.
+//!
+//! Records are keyed by project id, keeping each project's events on one partition in
+//! order. The client speaks just enough of the wire protocol for this: Metadata v4 to
+//! find partition leaders and Produce v3 with v2 record batches.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::handlers::ws::pattern_matches;
+use crate::storage::StorageBackend;
+use opengate_models::OutboxEvent;
+
+pub const DEFAULT_ROUTES: &str = "*=opengate.events";
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const BATCH_SIZE: i64 = 500;
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+const API_PRODUCE: i16 = 0;
+const API_METADATA: i16 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KafkaRoute {
+    /// Event type pattern: exact, `prefix.*` or `*`
+    pub pattern: String,
+    pub topic: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KafkaConfig {
+    /// Bootstrap brokers, `host:port`
+    pub brokers: Vec<String>,
+    pub routes: Vec<KafkaRoute>,
+}
+
+impl KafkaConfig {
+    /// Topic for an event type, or `None` if no route selects it.
+    pub fn topic_for(&self, event_type: &str) -> Option<&str> {
+        self.routes
+            .iter()
+            .find(|r| pattern_matches(&r.pattern, event_type))
+            .map(|r| r.topic.as_str())
+    }
+}
+
+static KAFKA: OnceLock<KafkaConfig> = OnceLock::new();
+
+/// Parse comma-separated `host:port` brokers and `pattern=topic` routes.
+pub fn parse_config(brokers: &str, routes: &str) -> Result<KafkaConfig, String> {
+    let brokers: Vec<String> = brokers
+        .split(',')
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(|b| {
+            if b.contains(':') {
+                b.to_string()
+            } else {
+                format!("{}:9092", b)
+            }
+        })
+        .collect();
+    if brokers.is_empty() {
+        return Err("No Kafka brokers given".to_string());
+    }
+    let routes = routes
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(|r| match r.split_once('=') {
+            Some((pattern, topic)) if !pattern.trim().is_empty() && !topic.trim().is_empty() => {
+                Ok(KafkaRoute {
+                    pattern: pattern.trim().to_string(),
+                    topic: topic.trim().to_string(),
+                })
+            }
+            _ => Err(format!(
+                "Invalid Kafka route '{}': expected <event pattern>=<topic>",
+                r
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if routes.is_empty() {
+        return Err("No Kafka routes given".to_string());
+    }
+    Ok(KafkaConfig { brokers, routes })
+}
+
+/// Set the server-wide Kafka sink. Only the first call takes effect.
+pub fn set_kafka(config: KafkaConfig) {
+    let _ = KAFKA.set(config);
+}
+
+pub fn configured_kafka() -> Option<&'static KafkaConfig> {
+    KAFKA.get()
+}
+
+/// Start flushing the outbox to Kafka.
+pub fn spawn(config: KafkaConfig, storage: Arc<dyn StorageBackend>) {
+    tokio::spawn(async move {
+        let mut wait = FLUSH_INTERVAL;
+        loop {
+            tokio::time::sleep(wait).await;
+            let batch = storage.list_outbox_events(None, BATCH_SIZE);
+            if batch.is_empty() {
+                wait = FLUSH_INTERVAL;
+                continue;
+            }
+            let (acked, failed) = match deliver(&config.brokers, &batch).await {
+                Ok(outcome) => outcome,
+                Err(e) => (
+                    Vec::new(),
+                    HashMap::from([(e.to_string(), batch.iter().map(|e| e.id).collect())]),
+                ),
+            };
+            storage.ack_outbox_events(None, &acked);
+            for (error, ids) in &failed {
+                eprintln!("[kafka] {} event(s) not delivered: {}", ids.len(), error);
+                storage.fail_outbox_events(None, ids, error);
+            }
+            wait = if !failed.is_empty() {
+                (wait * 2).clamp(FLUSH_INTERVAL, MAX_BACKOFF)
+            } else if batch.len() as i64 == BATCH_SIZE {
+                // More waiting behind this batch
+                Duration::ZERO
+            } else {
+                FLUSH_INTERVAL
+            };
+        }
+    });
+}
+
+/// Acknowledged outbox ids, and failed ids grouped by error.
+type Delivery = (Vec<i64>, HashMap<String, Vec<i64>>);
+
+async fn deliver(bootstrap: &[String], batch: &[OutboxEvent]) -> io::Result<Delivery> {
+    let mut topics: Vec<&str> = batch.iter().map(|e| e.topic.as_str()).collect();
+    topics.sort_unstable();
+    topics.dedup();
+    let metadata = fetch_metadata(bootstrap, &topics).await?;
+
+    let mut acked = Vec::new();
+    let mut failed: HashMap<String, Vec<i64>> = HashMap::new();
+    // leader -> (topic, partition) -> events
+    let mut by_leader: HashMap<i32, BTreeMap<(String, i32), Vec<&OutboxEvent>>> = HashMap::new();
+    for event in batch {
+        match metadata.route(event) {
+            Ok((leader, partition)) => by_leader
+                .entry(leader)
+                .or_default()
+                .entry((event.topic.clone(), partition))
+                .or_default()
+                .push(event),
+            Err(e) => failed.entry(e).or_default().push(event.id),
+        }
+    }
+
+    for (leader, partitions) in by_leader {
+        let ids = |p: &BTreeMap<(String, i32), Vec<&OutboxEvent>>| -> Vec<i64> {
+            p.values().flatten().map(|e| e.id).collect()
+        };
+        let Some(addr) = metadata.brokers.get(&leader) else {
+            failed
+                .entry(format!("unknown leader broker {}", leader))
+                .or_default()
+                .extend(ids(&partitions));
+            continue;
+        };
+        let result = async {
+            let mut broker = Broker::connect(addr).await?;
+            let response = broker
+                .call(API_PRODUCE, 3, &encode_produce(&partitions))
+                .await?;
+            decode_produce(&response)
+        }
+        .await;
+        match result {
+            Ok(errors) => {
+                for (key, events) in &partitions {
+                    match errors.get(key).copied().unwrap_or(-1) {
+                        0 => acked.extend(events.iter().map(|e| e.id)),
+                        code => failed
+                            .entry(format!("{}[{}]: error code {}", key.0, key.1, code))
+                            .or_default()
+                            .extend(events.iter().map(|e| e.id)),
+                    }
+                }
+            }
+            Err(e) => failed
+                .entry(format!("{}: {}", addr, e))
+                .or_default()
+                .extend(ids(&partitions)),
+        }
+    }
+    Ok((acked, failed))
+}
+
+// ---------------------------------------------------------------------------
+// Metadata
+// ---------------------------------------------------------------------------
+
+struct ClusterMetadata {
+    brokers: HashMap<i32, String>,
+    /// topic -> (error code, partitions as (index, leader) sorted by index)
+    topics: HashMap<String, (i16, Vec<(i32, i32)>)>,
+}
+
+impl ClusterMetadata {
+    /// (leader, partition) for an event: keyed events hash onto a partition, unkeyed
+    /// ones spread by outbox id.
+    fn route(&self, event: &OutboxEvent) -> Result<(i32, i32), String> {
+        let (error_code, partitions) = self
+            .topics
+            .get(&event.topic)
+            .ok_or_else(|| format!("topic {} missing from metadata", event.topic))?;
+        if *error_code != 0 || partitions.is_empty() {
+            return Err(format!(
+                "topic {} unavailable (error code {})",
+                event.topic, error_code
+            ));
+        }
+        let slot = match event.event_key.as_deref() {
+            Some(key) => crc32c(key.as_bytes()) as usize,
+            None => event.id as usize,
+        } % partitions.len();
+        let (partition, leader) = partitions[slot];
+        if leader < 0 {
+            return Err(format!("{}[{}] has no leader", event.topic, partition));
+        }
+        Ok((leader, partition))
+    }
+}
+
+async fn fetch_metadata(bootstrap: &[String], topics: &[&str]) -> io::Result<ClusterMetadata> {
+    let mut body = Encoder::default();
+    body.i32(topics.len() as i32);
+    for topic in topics {
+        body.string(topic);
+    }
+    body.i8(1); // allow_auto_topic_creation
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no bootstrap brokers");
+    for addr in bootstrap {
+        let result = async {
+            let mut broker = Broker::connect(addr).await?;
+            let response = broker.call(API_METADATA, 4, &body.0).await?;
+            decode_metadata(&response)
+        }
+        .await;
+        match result {
+            Ok(metadata) => return Ok(metadata),
+            Err(e) => last_error = io::Error::new(e.kind(), format!("{}: {}", addr, e)),
+        }
+    }
+    Err(last_error)
+}
+
+fn decode_metadata(buf: &[u8]) -> io::Result<ClusterMetadata> {
+    let mut d = Decoder { buf, pos: 0 };
+    d.i32()?; // throttle_time_ms
+    let mut brokers = HashMap::new();
+    for _ in 0..d.i32()? {
+        let node_id = d.i32()?;
+        let host = d.string()?.unwrap_or_default();
+        let port = d.i32()?;
+        d.string()?; // rack
+        brokers.insert(node_id, format!("{}:{}", host, port));
+    }
+    d.string()?; // cluster_id
+    d.i32()?; // controller_id
+    let mut topics = HashMap::new();
+    for _ in 0..d.i32()? {
+        let error_code = d.i16()?;
+        let name = d.string()?.unwrap_or_default();
+        d.i8()?; // is_internal
+        let mut partitions = Vec::new();
+        for _ in 0..d.i32()? {
+            d.i16()?; // partition error_code — a missing leader shows up as -1 below
+            let index = d.i32()?;
+            let leader = d.i32()?;
+            for _ in 0..d.i32()? {
+                d.i32()?; // replica_nodes
+            }
+            for _ in 0..d.i32()? {
+                d.i32()?; // isr_nodes
+            }
+            partitions.push((index, leader));
+        }
+        partitions.sort_unstable();
+        topics.insert(name, (error_code, partitions));
+    }
+    Ok(ClusterMetadata { brokers, topics })
+}
+
+// ---------------------------------------------------------------------------
+// Produce
+// ---------------------------------------------------------------------------
+
+fn encode_produce(partitions: &BTreeMap<(String, i32), Vec<&OutboxEvent>>) -> Vec<u8> {
+    let mut by_topic: BTreeMap<&str, Vec<(i32, &Vec<&OutboxEvent>)>> = BTreeMap::new();
+    for ((topic, partition), events) in partitions {
+        by_topic
+            .entry(topic)
+            .or_default()
+            .push((*partition, events));
+    }
+    let mut body = Encoder::default();
+    body.i16(-1); // transactional_id: null
+    body.i16(-1); // acks: all in-sync replicas
+    body.i32(IO_TIMEOUT.as_millis() as i32);
+    body.i32(by_topic.len() as i32);
+    for (topic, partitions) in by_topic {
+        body.string(topic);
+        body.i32(partitions.len() as i32);
+        for (partition, events) in partitions {
+            body.i32(partition);
+            let records: Vec<Record> = events
+                .iter()
+                .map(|e| Record {
+                    key: e.event_key.as_deref().map(str::as_bytes),
+                    value: e.payload.as_bytes(),
+                    timestamp_ms: timestamp_ms(&e.created_at),
+                })
+                .collect();
+            body.bytes(&record_batch(&records));
+        }
+    }
+    body.0
+}
+
+/// (topic, partition) -> error code
+fn decode_produce(buf: &[u8]) -> io::Result<HashMap<(String, i32), i16>> {
+    let mut d = Decoder { buf, pos: 0 };
+    let mut errors = HashMap::new();
+    for _ in 0..d.i32()? {
+        let topic = d.string()?.unwrap_or_default();
+        for _ in 0..d.i32()? {
+            let partition = d.i32()?;
+            let error_code = d.i16()?;
+            d.i64()?; // base_offset
+            d.i64()?; // log_append_time_ms
+            errors.insert((topic.clone(), partition), error_code);
+        }
+    }
+    Ok(errors)
+}
+
+struct Record<'a> {
+    key: Option<&'a [u8]>,
+    value: &'a [u8],
+    timestamp_ms: i64,
+}
+
+fn timestamp_ms(rfc3339: &str) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(rfc3339)
+        .map(|at| at.timestamp_millis())
+        .unwrap_or_else(|_| chrono::Utc::now().timestamp_millis())
+}
+
+/// A v2 (magic 2) record batch, uncompressed and non-transactional.
+fn record_batch(records: &[Record]) -> Vec<u8> {
+    let first_ts = records.iter().map(|r| r.timestamp_ms).min().unwrap_or(0);
+    let max_ts = records.iter().map(|r| r.timestamp_ms).max().unwrap_or(0);
+
+    let mut encoded = Encoder::default();
+    for (offset_delta, record) in records.iter().enumerate() {
+        let mut r = Encoder::default();
+        r.i8(0); // attributes
+        r.varint(record.timestamp_ms - first_ts);
+        r.varint(offset_delta as i64);
+        match record.key {
+            Some(key) => {
+                r.varint(key.len() as i64);
+                r.0.extend_from_slice(key);
+            }
+            None => r.varint(-1),
+        }
+        r.varint(record.value.len() as i64);
+        r.0.extend_from_slice(record.value);
+        r.varint(0); // headers
+        encoded.varint(r.0.len() as i64);
+        encoded.0.extend_from_slice(&r.0);
+    }
+
+    // Everything from `attributes` on is covered by the CRC
+    let mut tail = Encoder::default();
+    tail.i16(0); // attributes: no compression, CreateTime
+    tail.i32(records.len() as i32 - 1); // last_offset_delta
+    tail.i64(first_ts);
+    tail.i64(max_ts);
+    tail.i64(-1); // producer_id
+    tail.i16(-1); // producer_epoch
+    tail.i32(-1); // base_sequence
+    tail.i32(records.len() as i32);
+    tail.0.extend_from_slice(&encoded.0);
+
+    let mut batch = Encoder::default();
+    batch.i64(0); // base_offset, assigned by the broker
+    batch.i32((4 + 1 + 4 + tail.0.len()) as i32); // batch_length
+    batch.i32(-1); // partition_leader_epoch
+    batch.i8(2); // magic
+    batch.0.extend_from_slice(&crc32c(&tail.0).to_be_bytes());
+    batch.0.extend_from_slice(&tail.0);
+    batch.0
+}
+
+// ---------------------------------------------------------------------------
+// Wire protocol
+// ---------------------------------------------------------------------------
+
+struct Broker {
+    stream: TcpStream,
+    correlation_id: i32,
+}
+
+impl Broker {
+    async fn connect(addr: &str) -> io::Result<Self> {
+        let stream = tokio::time::timeout(IO_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))??;
+        Ok(Self {
+            stream,
+            correlation_id: 0,
+        })
+    }
+
+    /// Send one request and return the response body (after the correlation id).
+    async fn call(&mut self, api_key: i16, api_version: i16, body: &[u8]) -> io::Result<Vec<u8>> {
+        self.correlation_id += 1;
+        let mut request = Encoder::default();
+        request.i16(api_key);
+        request.i16(api_version);
+        request.i32(self.correlation_id);
+        request.string("opengate");
+        request.0.extend_from_slice(body);
+
+        let exchange = async {
+            self.stream
+                .write_all(&(request.0.len() as i32).to_be_bytes())
+                .await?;
+            self.stream.write_all(&request.0).await?;
+            let size = self.stream.read_i32().await?;
+            if size < 4 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "short response"));
+            }
+            let mut response = vec![0u8; size as usize];
+            self.stream.read_exact(&mut response).await?;
+            Ok(response)
+        };
+        let response = tokio::time::timeout(IO_TIMEOUT, exchange)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "request timed out"))??;
+        if response[..4] != self.correlation_id.to_be_bytes() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "mismatched correlation id",
+            ));
+        }
+        Ok(response[4..].to_vec())
+    }
+}
+
+#[derive(Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn i8(&mut self, v: i8) {
+        self.0.push(v as u8);
+    }
+    fn i16(&mut self, v: i16) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+    fn i32(&mut self, v: i32) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+    fn i64(&mut self, v: i64) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+    fn string(&mut self, s: &str) {
+        self.i16(s.len() as i16);
+        self.0.extend_from_slice(s.as_bytes());
+    }
+    fn bytes(&mut self, b: &[u8]) {
+        self.i32(b.len() as i32);
+        self.0.extend_from_slice(b);
+    }
+    /// Zigzag varint, as used inside record batches.
+    fn varint(&mut self, v: i64) {
+        let mut z = ((v << 1) ^ (v >> 63)) as u64;
+        while z >= 0x80 {
+            self.0.push((z as u8 & 0x7f) | 0x80);
+            z >>= 7;
+        }
+        self.0.push(z as u8);
+    }
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, n: usize) -> io::Result<&[u8]> {
+        let end = self.pos + n;
+        let slice = self
+            .buf
+            .get(self.pos..end)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated response"))?;
+        self.pos = end;
+        Ok(slice)
+    }
+    fn i8(&mut self) -> io::Result<i8> {
+        Ok(self.take(1)?[0] as i8)
+    }
+    fn i16(&mut self) -> io::Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    /// A nullable string (length -1 is `None`).
+    fn string(&mut self) -> io::Result<Option<String>> {
+        let len = self.i16()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        Ok(Some(
+            String::from_utf8_lossy(self.take(len as usize)?).into_owned(),
+        ))
+    }
+}
+
+/// CRC-32C (Castagnoli), the checksum of v2 record batches.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_brokers_and_routes() {
+        let config = parse_config(
+            "kafka-1, kafka-2:9093",
+            "task.*=opengate.tasks, knowledge.*=opengate.kb",
+        )
+        .unwrap();
+        assert_eq!(config.brokers, vec!["kafka-1:9092", "kafka-2:9093"]);
+        assert_eq!(config.topic_for("task.created"), Some("opengate.tasks"));
+        assert_eq!(config.topic_for("knowledge.updated"), Some("opengate.kb"));
+        assert_eq!(config.topic_for("agent.offline"), None);
+
+        let catch_all = parse_config("k:9092", DEFAULT_ROUTES).unwrap();
+        assert_eq!(
+            catch_all.topic_for("agent.offline"),
+            Some("opengate.events")
+        );
+
+        assert!(parse_config("", DEFAULT_ROUTES).is_err());
+        assert!(parse_config("k", "task.*").is_err());
+    }
+
+    #[test]
+    fn crc32c_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+    }
+
+    #[test]
+    fn zigzag_varints() {
+        let encode = |v: i64| {
+            let mut e = Encoder::default();
+            e.varint(v);
+            e.0
+        };
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(-1), vec![0x01]);
+        assert_eq!(encode(1), vec![0x02]);
+        assert_eq!(encode(64), vec![0x80, 0x01]);
+    }
+
+    #[test]
+    fn record_batch_layout() {
+        let records = [
+            Record {
+                key: Some(b"p1"),
+                value: b"{}",
+                timestamp_ms: 1_000,
+            },
+            Record {
+                key: None,
+                value: b"[]",
+                timestamp_ms: 1_005,
+            },
+        ];
+        let batch = record_batch(&records);
+        let mut d = Decoder {
+            buf: &batch,
+            pos: 0,
+        };
+        assert_eq!(d.i64().unwrap(), 0);
+        assert_eq!(d.i32().unwrap() as usize, batch.len() - 12);
+        assert_eq!(d.i32().unwrap(), -1);
+        assert_eq!(d.i8().unwrap(), 2);
+        let crc = d.i32().unwrap() as u32;
+        assert_eq!(crc, crc32c(&batch[21..]));
+        assert_eq!(d.i16().unwrap(), 0);
+        assert_eq!(d.i32().unwrap(), 1); // last_offset_delta
+        assert_eq!(d.i64().unwrap(), 1_000);
+        assert_eq!(d.i64().unwrap(), 1_005);
+    }
+
+    #[test]
+    fn keyed_events_stick_to_a_partition() {
+        let metadata = ClusterMetadata {
+            brokers: HashMap::from([(1, "k1:9092".to_string()), (2, "k2:9092".to_string())]),
+            topics: HashMap::from([("t".to_string(), (0, vec![(0, 1), (1, 2), (2, 1)]))]),
+        };
+        let event = |id: i64, key: Option<&str>| OutboxEvent {
+            id,
+            topic: "t".to_string(),
+            event_key: key.map(str::to_string),
+            payload: "{}".to_string(),
+            attempts: 0,
+            last_error: None,
+            created_at: String::new(),
+        };
+        assert_eq!(
+            metadata.route(&event(1, Some("proj"))),
+            metadata.route(&event(2, Some("proj")))
+        );
+        assert_eq!(metadata.route(&event(4, None)), Ok((2, 1)));
+        assert!(metadata
+            .route(&OutboxEvent {
+                topic: "missing".to_string(),
+                ..event(5, None)
+            })
+            .is_err());
+    }
+}
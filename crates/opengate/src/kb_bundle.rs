@@ -0,0 +1,373 @@
+//! Knowledge base bundles: a project's entries as a tar of markdown files.
+//!
+//! Each entry becomes `{key}.md` (keys may contain slashes, which become directories)
+//! with a front-matter block holding the title, category, tags, metadata and review
+//! deadline. Front-matter
+//! values are written as JSON, which is also valid YAML, so the files stay readable and
+//! diffable when mirrored in a git repo. On import, plain unquoted strings are accepted
+//! too, so hand-edited files work.
+//!
+//! Archives are POSIX ustar. Paths longer than the header allows are written with a pax
+//! `path` record; reading also understands GNU long-name entries.
+
+use opengate_models::{KnowledgeEntry, UpsertKnowledge};
+
+const BLOCK: usize = 512;
+
+/// Markdown file for an entry: front-matter followed by the content.
+pub fn render_markdown(entry: &KnowledgeEntry) -> String {
+    let mut out = String::from("---\n");
+    out.push_str(&format!("title: {}\n", json(&entry.title)));
+    if let Some(ref category) = entry.category {
+        out.push_str(&format!("category: {}\n", json(category)));
+    }
+    if !entry.tags.is_empty() {
+        out.push_str(&format!("tags: {}\n", json(&entry.tags)));
+    }
+    if let Some(ref metadata) = entry.metadata {
+        out.push_str(&format!("metadata: {}\n", json(metadata)));
+    }
+    if let Some(ref review_after) = entry.review_after {
+        out.push_str(&format!("review_after: {}\n", json(review_after)));
+    }
+    out.push_str("---\n");
+    out.push_str(&entry.content);
+    // Always terminate the file; parsing strips exactly one trailing newline
+    out.push('\n');
+    out
+}
+
+/// Parse a markdown file written by [`render_markdown`] (or by hand). Files without
+/// front-matter are taken as content only, titled after the last key segment.
+pub fn parse_markdown(key: &str, text: &str) -> Result<UpsertKnowledge, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let default_title = key.rsplit('/').next().unwrap_or(key).to_string();
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return Ok(UpsertKnowledge {
+            title: default_title,
+            content: text.to_string(),
+            metadata: None,
+            tags: None,
+            category: None,
+            review_after: None,
+        });
+    };
+    let (front, content) = match rest.find("\n---\n") {
+        Some(i) => (&rest[..i], &rest[i + 5..]),
+        None => match rest.strip_suffix("\n---") {
+            Some(front) => (front, ""),
+            None => return Err(format!("{}: unterminated front-matter", key)),
+        },
+    };
+
+    let mut input = UpsertKnowledge {
+        title: default_title,
+        content: content.strip_suffix('\n').unwrap_or(content).to_string(),
+        metadata: None,
+        tags: None,
+        category: None,
+        review_after: None,
+    };
+    for line in front.lines().filter(|l| !l.trim().is_empty()) {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(format!("{}: invalid front-matter line '{}'", key, line));
+        };
+        let value = value.trim();
+        match name.trim() {
+            "title" => input.title = scalar(value),
+            "category" => input.category = Some(scalar(value)).filter(|c| !c.is_empty()),
+            "tags" => input.tags = Some(tag_list(value)),
+            "review_after" => input.review_after = Some(scalar(value)),
+            "metadata" => {
+                input.metadata = Some(
+                    serde_json::from_str(value)
+                        .map_err(|e| format!("{}: invalid metadata: {}", key, e))?,
+                )
+            }
+            _ => {}
+        }
+    }
+    Ok(input)
+}
+
+/// Tar archive of every entry as `{key}.md`.
+pub fn export_bundle(entries: &[KnowledgeEntry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for entry in entries {
+        append_file(
+            &mut out,
+            &format!("{}.md", entry.key),
+            render_markdown(entry).as_bytes(),
+        );
+    }
+    // End of archive: two zero blocks
+    out.resize(out.len() + 2 * BLOCK, 0);
+    out
+}
+
+/// Entries from a tar archive, as `(key, input)` in archive order. Files not ending in
+/// `.md` (READMEs, directories, etc.) are ignored.
+pub fn import_bundle(data: &[u8]) -> Result<Vec<(String, UpsertKnowledge)>, String> {
+    let mut entries = Vec::new();
+    for (path, body) in read_files(data)? {
+        let path = path.trim_start_matches("./");
+        let Some(key) = path.strip_suffix(".md") else {
+            continue;
+        };
+        let base = key.rsplit('/').next().unwrap_or(key);
+        if key.is_empty() || base.starts_with('.') {
+            continue;
+        }
+        let text = String::from_utf8(body).map_err(|_| format!("{}: not valid UTF-8", path))?;
+        entries.push((key.to_string(), parse_markdown(key, &text)?));
+    }
+    Ok(entries)
+}
+
+fn json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+/// A front-matter string: JSON-quoted, or the raw (optionally single-quoted) text.
+fn scalar(value: &str) -> String {
+    if let Ok(s) = serde_json::from_str::<String>(value) {
+        return s;
+    }
+    value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// A front-matter tag list: a JSON array, or `[a, b]` / `a, b`.
+fn tag_list(value: &str) -> Vec<String> {
+    if let Ok(tags) = serde_json::from_str::<Vec<String>>(value) {
+        return tags;
+    }
+    let inner = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or(value);
+    inner
+        .split(',')
+        .map(|t| scalar(t.trim()))
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn append_file(out: &mut Vec<u8>, path: &str, body: &[u8]) {
+    let (name, prefix) = match split_ustar_path(path) {
+        Some(parts) => parts,
+        None => {
+            // pax record: "<len> path=<path>\n", where <len> counts its own digits
+            let rest = format!(" path={}\n", path);
+            let mut len = rest.len() + 1;
+            while format!("{}{}", len, rest).len() != len {
+                len += 1;
+            }
+            let record = format!("{}{}", len, rest);
+            append_entry(out, "PaxHeader", "", b'x', record.as_bytes());
+            // Readers without pax support still get the tail of the path
+            let mut start = path.len() - 100;
+            while !path.is_char_boundary(start) {
+                start += 1;
+            }
+            append_entry(out, &path[start..], "", b'0', body);
+            return;
+        }
+    };
+    append_entry(out, name, prefix, b'0', body);
+}
+
+/// Split a path into ustar `(name, prefix)` fields (100 and 155 bytes), if it fits.
+fn split_ustar_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some((path, ""));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[i + 1..], &path[..i]))
+        .find(|(name, prefix)| name.len() <= 100 && prefix.len() <= 155 && !name.is_empty())
+}
+
+fn append_entry(out: &mut Vec<u8>, name: &str, prefix: &str, typeflag: u8, body: &[u8]) {
+    let mut header = [0u8; BLOCK];
+    write_field(&mut header[0..100], name.as_bytes());
+    write_field(&mut header[100..108], b"0000644");
+    write_field(&mut header[108..116], b"0000000");
+    write_field(&mut header[116..124], b"0000000");
+    write_field(
+        &mut header[124..136],
+        format!("{:011o}", body.len()).as_bytes(),
+    );
+    write_field(&mut header[136..148], b"00000000000");
+    header[156] = typeflag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    write_field(&mut header[345..500], prefix.as_bytes());
+
+    // Checksum is computed with the checksum field itself set to spaces
+    header[148..156].fill(b' ');
+    let sum: u32 = header.iter().map(|&b| b as u32).sum();
+    write_field(&mut header[148..156], format!("{:06o}\0 ", sum).as_bytes());
+
+    out.extend_from_slice(&header);
+    out.extend_from_slice(body);
+    let padding = (BLOCK - body.len() % BLOCK) % BLOCK;
+    out.resize(out.len() + padding, 0);
+}
+
+fn write_field(field: &mut [u8], value: &[u8]) {
+    let n = value.len().min(field.len());
+    field[..n].copy_from_slice(&value[..n]);
+}
+
+/// Regular files in a tar archive as `(path, body)`.
+fn read_files(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = Vec::new();
+    let mut offset = 0;
+    let mut long_name: Option<String> = None;
+    while offset + BLOCK <= data.len() {
+        let header = &data[offset..offset + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if &header[257..262] != b"ustar" {
+            return Err("Not a tar archive".to_string());
+        }
+        let expected = parse_octal(&header[148..156])?;
+        let actual: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+            .sum();
+        if expected != actual {
+            return Err("Corrupt tar header (bad checksum)".to_string());
+        }
+
+        let size = parse_octal(&header[124..136])? as usize;
+        let start = offset + BLOCK;
+        let end = start
+            .checked_add(size)
+            .filter(|&end| end <= data.len())
+            .ok_or("Truncated tar archive")?;
+        let body = &data[start..end];
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+
+        match header[156] {
+            b'x' => long_name = pax_path(body).or(long_name),
+            b'L' => long_name = Some(field_str(body)),
+            b'0' | 0 => {
+                let path = long_name.take().unwrap_or_else(|| {
+                    let name = field_str(&header[0..100]);
+                    let prefix = field_str(&header[345..500]);
+                    if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    }
+                });
+                files.push((path, body.to_vec()));
+            }
+            _ => long_name = None,
+        }
+    }
+    Ok(files)
+}
+
+fn parse_octal(field: &[u8]) -> Result<u64, String> {
+    let s = field_str(field);
+    let s = s.trim_matches(|c: char| c == ' ' || c == '\0');
+    if s.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(s, 8).map_err(|_| "Corrupt tar header".to_string())
+}
+
+fn field_str(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn pax_path(body: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(body).lines().find_map(|record| {
+        let (_, kv) = record.split_once(' ')?;
+        kv.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, content: &str) -> KnowledgeEntry {
+        KnowledgeEntry {
+            id: "id".to_string(),
+            project_id: "p".to_string(),
+            key: key.to_string(),
+            title: "Title: with \"quotes\"".to_string(),
+            content: content.to_string(),
+            metadata: Some(serde_json::json!({"owner": "payments"})),
+            tags: vec!["rust".to_string(), "db".to_string()],
+            category: Some("gotcha".to_string()),
+            created_by_type: "human".to_string(),
+            created_by_id: "h".to_string(),
+            version: 1,
+            review_after: Some("2026-06-01T00:00:00Z".to_string()),
+            stale: false,
+            updated_at: String::new(),
+            created_at: String::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn bundle_round_trips() {
+        // Too long for the ustar name field with no usable split: needs a pax record
+        let long_key = format!("notes/{}", "x".repeat(120));
+        let entries = vec![
+            entry("arch/payments", "# Payments\n\n---\nnot front-matter"),
+            entry(&long_key, "x"),
+        ];
+        let imported = import_bundle(&export_bundle(&entries)).unwrap();
+        assert_eq!(imported.len(), 2);
+        let (key, input) = &imported[0];
+        assert_eq!(key, "arch/payments");
+        assert_eq!(input.title, "Title: with \"quotes\"");
+        assert_eq!(input.content, "# Payments\n\n---\nnot front-matter");
+        assert_eq!(
+            input.tags.as_deref(),
+            Some(&["rust".to_string(), "db".to_string()][..])
+        );
+        assert_eq!(input.category.as_deref(), Some("gotcha"));
+        assert_eq!(
+            input.metadata,
+            Some(serde_json::json!({"owner": "payments"}))
+        );
+        assert_eq!(input.review_after.as_deref(), Some("2026-06-01T00:00:00Z"));
+        assert_eq!(imported[1].0, long_key);
+    }
+
+    #[test]
+    fn hand_written_front_matter_is_accepted() {
+        let input = parse_markdown(
+            "notes/deploy",
+            "---\ntitle: Deploy steps\ntags: [ops, 'ci']\n---\nRun it\n",
+        )
+        .unwrap();
+        assert_eq!(input.title, "Deploy steps");
+        assert_eq!(input.tags.unwrap(), vec!["ops", "ci"]);
+        assert_eq!(input.content, "Run it");
+
+        let bare = parse_markdown("notes/plain", "just text").unwrap();
+        assert_eq!(bare.title, "plain");
+        assert_eq!(bare.content, "just text");
+        assert!(parse_markdown("k", "---\ntitle: x\n").is_err());
+    }
+
+    #[test]
+    fn rejects_non_tar_input() {
+        assert!(import_bundle(&[1u8; 1024]).is_err());
+        assert!(import_bundle(&[]).unwrap().is_empty());
+    }
+}
@@ -2,11 +2,47 @@
 
 pub mod app;
 pub mod auth;
+pub mod backlinks;
+pub mod client;
+pub mod cloudevents;
+pub mod compression;
+pub mod config;
+pub mod csv;
 pub mod db;
 pub mod db_ops;
+pub mod email;
+pub mod event_sink;
 pub mod events;
+pub mod freshness;
+pub mod github;
+pub mod gitlab;
 pub mod handlers;
+pub mod ical;
+pub mod jira;
+pub mod kafka;
+pub mod kb_bundle;
+pub mod linear;
+pub mod listen;
+pub mod load_shed;
+pub mod mapping;
 pub mod mcp;
+pub mod metrics;
+pub mod openapi;
+pub mod presence;
+pub mod push;
+pub mod query_stats;
+pub mod question_routing;
+pub mod quotas;
+pub mod rate_limit;
+pub mod recurrence;
+pub mod signatures;
+pub mod slack;
 pub mod storage;
+pub mod task_refs;
+pub mod telemetry;
+pub mod tls;
+pub mod trello;
+pub mod tui;
+pub mod ui;
 
 pub use opengate_models as models;
@@ -0,0 +1,350 @@
+//! Linear import and export, for teams moving work from Linear to agents gradually.
+//!
+//! `POST /api/projects/:id/import/linear` creates tasks from a Linear CSV export, or from
+//! the API with the token and team set by `PUT /api/projects/:id/integrations/linear`.
+//! Imported tasks carry `context.external_ref = "linear:<identifier>"`; when one is
+//! completed, its Linear issue is moved to the team's completed state and the summary is
+//! posted as a comment.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::csv;
+use crate::storage::StorageBackend;
+use opengate_models::*;
+
+pub const DEFAULT_API_URL: &str = "https://api.linear.app/graphql";
+
+/// Pages of 100 issues fetched by one import.
+const MAX_IMPORT_PAGES: u32 = 10;
+
+static API_URL: OnceLock<String> = OnceLock::new();
+
+/// Set the Linear GraphQL endpoint. Only the first call takes effect.
+pub fn set_api_url(url: &str) {
+    let _ = API_URL.set(url.trim_end_matches('/').to_string());
+}
+
+pub fn api_url() -> &'static str {
+    API_URL.get().map(String::as_str).unwrap_or(DEFAULT_API_URL)
+}
+
+/// A Linear issue as read from an export or the API.
+#[derive(Debug, Default, PartialEq)]
+pub struct LinearIssue {
+    /// Team key and number, e.g. `ENG-123`
+    pub identifier: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub status: String,
+    /// The workflow state type (`triage`, `backlog`, `unstarted`, `started`,
+    /// `completed`, `canceled`); the API has it, exports don't
+    pub status_type: Option<String>,
+    pub priority: Option<String>,
+    pub labels: Vec<String>,
+    pub url: Option<String>,
+}
+
+pub fn external_ref(identifier: &str) -> String {
+    format!("linear:{}", identifier)
+}
+
+/// The OpenGate status for a Linear workflow state, if there is an obvious one.
+pub fn map_status(name: &str, state_type: Option<&str>) -> Option<&'static str> {
+    let status = match name.trim().to_lowercase().as_str() {
+        "triage" | "backlog" => "backlog",
+        "todo" | "to do" | "unstarted" => "todo",
+        "in progress" | "started" => "in_progress",
+        "in review" | "review" => "review",
+        "blocked" => "blocked",
+        "done" | "completed" => "done",
+        "canceled" | "cancelled" | "duplicate" => "cancelled",
+        _ => match state_type? {
+            "triage" | "backlog" => "backlog",
+            "unstarted" => "todo",
+            "started" => "in_progress",
+            "completed" => "done",
+            "canceled" => "cancelled",
+            _ => return None,
+        },
+    };
+    Some(status)
+}
+
+/// The OpenGate priority for a Linear priority label. "No priority" is `medium`.
+pub fn map_priority(name: &str) -> Option<&'static str> {
+    let priority = match name.trim().to_lowercase().as_str() {
+        "urgent" => "critical",
+        "high" => "high",
+        "medium" | "no priority" | "" => "medium",
+        "low" => "low",
+        _ => return None,
+    };
+    Some(priority)
+}
+
+/// Issues from a Linear CSV export (`ID`, `Title`, `Description`, `Status`, `Priority`,
+/// `Labels` as one comma-separated field).
+pub fn parse_csv(body: &[u8]) -> Result<Vec<LinearIssue>, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "CSV export is not UTF-8")?;
+    let mut records = csv::parse(text)?.into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or("CSV export is empty")?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let id_col = column("ID").ok_or("CSV export has no \"ID\" column")?;
+    let title_col = column("Title").ok_or("CSV export has no \"Title\" column")?;
+    let (description_col, status_col, priority_col, labels_col) = (
+        column("Description"),
+        column("Status"),
+        column("Priority"),
+        column("Labels"),
+    );
+    records
+        .enumerate()
+        .map(|(row, record)| {
+            let get = |col: Option<usize>| {
+                col.and_then(|c| record.get(c))
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+            };
+            let identifier = get(Some(id_col)).ok_or(format!("Row {} has no ID", row + 2))?;
+            let title = get(Some(title_col)).ok_or(format!("Issue {} has no title", identifier))?;
+            Ok(LinearIssue {
+                identifier,
+                title,
+                description: get(description_col),
+                status: get(status_col).unwrap_or_default(),
+                status_type: None,
+                priority: get(priority_col),
+                labels: get(labels_col)
+                    .map(|l| {
+                        l.split(',')
+                            .map(|l| l.trim().to_string())
+                            .filter(|l| !l.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                url: None,
+            })
+        })
+        .collect()
+}
+
+/// The task `issue` is created as, in `backlog`.
+pub fn issue_task(issue: &LinearIssue, priority: &str) -> CreateTask {
+    let mut context = serde_json::json!({ "external_ref": external_ref(&issue.identifier) });
+    if let Some(ref url) = issue.url {
+        context["issue_url"] = serde_json::json!(url);
+    }
+    CreateTask {
+        title: issue.title.clone(),
+        description: issue.description.clone(),
+        priority: Some(priority.to_string()),
+        tags: Some(issue.labels.clone()).filter(|l| !l.is_empty()),
+        context: Some(context),
+        output: None,
+        due_date: None,
+        assignee_type: None,
+        assignee_id: None,
+        scheduled_at: None,
+        recurrence_rule: None,
+    }
+}
+
+// --- API ---
+
+async fn graphql(
+    client: &reqwest::Client,
+    token: &str,
+    query: &str,
+    variables: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let resp = client
+        .post(api_url())
+        .header("authorization", token)
+        .json(&serde_json::json!({ "query": query, "variables": variables }))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status().as_u16()));
+    }
+    let body: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    if let Some(message) = body["errors"][0]["message"].as_str() {
+        return Err(message.to_string());
+    }
+    Ok(body["data"].clone())
+}
+
+const ISSUES_QUERY: &str = "query Issues($team: String!, $after: String) {
+  issues(first: 100, after: $after, filter: { team: { key: { eq: $team } } }) {
+    nodes { identifier title description url priorityLabel state { name type } labels { nodes { name } } }
+    pageInfo { hasNextPage endCursor }
+  }
+}";
+
+/// The issues of the team with key `team`, up to `MAX_IMPORT_PAGES` pages.
+pub async fn fetch_issues(
+    client: &reqwest::Client,
+    token: &str,
+    team: &str,
+) -> Result<Vec<LinearIssue>, String> {
+    let mut issues = Vec::new();
+    let mut after = serde_json::Value::Null;
+    for _ in 0..MAX_IMPORT_PAGES {
+        let data = graphql(
+            client,
+            token,
+            ISSUES_QUERY,
+            serde_json::json!({ "team": team, "after": after }),
+        )
+        .await?;
+        let page = &data["issues"];
+        let text = |v: &serde_json::Value| v.as_str().map(str::to_string);
+        for node in page["nodes"].as_array().into_iter().flatten() {
+            let (Some(identifier), Some(title)) = (text(&node["identifier"]), text(&node["title"]))
+            else {
+                continue;
+            };
+            issues.push(LinearIssue {
+                identifier,
+                title,
+                description: text(&node["description"]).filter(|d| !d.trim().is_empty()),
+                status: text(&node["state"]["name"]).unwrap_or_default(),
+                status_type: text(&node["state"]["type"]),
+                priority: text(&node["priorityLabel"]),
+                labels: node["labels"]["nodes"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|l| text(&l["name"]))
+                    .collect(),
+                url: text(&node["url"]),
+            });
+        }
+        if page["pageInfo"]["hasNextPage"] != true {
+            break;
+        }
+        after = page["pageInfo"]["endCursor"].clone();
+    }
+    Ok(issues)
+}
+
+const COMPLETED_STATE_QUERY: &str = "query CompletedState($id: String!) {
+  issue(id: $id) { id team { states(filter: { type: { eq: \"completed\" } }) { nodes { id } } } }
+}";
+
+const COMPLETE_MUTATION: &str =
+    "mutation Complete($id: String!, $stateId: String!, $body: String!) {
+  issueUpdate(id: $id, input: { stateId: $stateId }) { success }
+  commentCreate(input: { issueId: $id, body: $body }) { success }
+}";
+
+/// Move `identifier` to its team's first completed state and comment `body` on it.
+async fn push_completion(
+    client: &reqwest::Client,
+    token: &str,
+    identifier: &str,
+    body: &str,
+) -> Result<(), String> {
+    let data = graphql(
+        client,
+        token,
+        COMPLETED_STATE_QUERY,
+        serde_json::json!({ "id": identifier }),
+    )
+    .await?;
+    let issue_id = data["issue"]["id"].as_str().ok_or("issue not found")?;
+    let state_id = data["issue"]["team"]["states"]["nodes"][0]["id"]
+        .as_str()
+        .ok_or("team has no completed state")?;
+    graphql(
+        client,
+        token,
+        COMPLETE_MUTATION,
+        serde_json::json!({ "id": issue_id, "stateId": state_id, "body": body }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// After `identity` completed `task`: when it was imported from Linear and the project's
+/// integration syncs completions, complete its issue in the background.
+pub fn export_completed(
+    storage: Arc<dyn StorageBackend>,
+    identity: &Identity,
+    task: &Task,
+    summary: Option<&str>,
+) {
+    let Some(identifier) = task
+        .context
+        .as_ref()
+        .and_then(|c| c.get("external_ref"))
+        .and_then(|v| v.as_str())
+        .and_then(|r| r.strip_prefix("linear:"))
+        .map(str::to_string)
+    else {
+        return;
+    };
+    let Some(token) = storage
+        .get_linear_integration(identity.tenant_id(), &task.project_id)
+        .filter(|l| l.sync_on_complete)
+        .and_then(|l| l.token)
+    else {
+        return;
+    };
+    let mut body = format!("Completed in OpenGate by {}.", identity.display_name());
+    if let Some(summary) = summary.filter(|s| !s.trim().is_empty()) {
+        body.push_str("\n\n");
+        body.push_str(summary);
+    }
+    let task_id = task.id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = push_completion(&reqwest::Client::new(), &token, &identifier, &body).await {
+            eprintln!(
+                "[linear] {} for task {} not completed: {}",
+                identifier, task_id, e
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_states_and_priorities() {
+        assert_eq!(map_status("In Review", Some("started")), Some("review"));
+        assert_eq!(map_status("Shipped", Some("completed")), Some("done"));
+        assert_eq!(map_status("Duplicate", None), Some("cancelled"));
+        assert_eq!(map_status("Parked", None), None);
+        assert_eq!(map_priority("Urgent"), Some("critical"));
+        assert_eq!(map_priority("No priority"), Some("medium"));
+        assert_eq!(map_priority("P0"), None);
+    }
+
+    #[test]
+    fn reads_csv_exports() {
+        let csv = "ID,Team,Title,Description,Status,Priority,Labels\n\
+                   ENG-1,Engineering,Fix login,,In Progress,High,\"Bug, Auth\"\n";
+        let issues = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(
+            issues,
+            vec![LinearIssue {
+                identifier: "ENG-1".to_string(),
+                title: "Fix login".to_string(),
+                status: "In Progress".to_string(),
+                priority: Some("High".to_string()),
+                labels: vec!["Bug".to_string(), "Auth".to_string()],
+                ..Default::default()
+            }]
+        );
+        assert!(parse_csv(b"Title\nNo id\n").is_err());
+    }
+}
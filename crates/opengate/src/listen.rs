@@ -0,0 +1,110 @@
+//! Where `serve` accepts connections (`--listen`, `listen = [...]`): TCP addresses, and
+//! Unix domain sockets for single-host setups that shouldn't open a port at all.
+
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+
+use axum::{http::Request, Router};
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use tokio::net::UnixListener;
+use tower::Service;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Listen {
+    /// host:port
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl Listen {
+    /// `unix:/path/to.sock`, or a TCP `host:port` (optionally written `tcp:host:port`).
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        if let Some(path) = spec.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("unix: needs a socket path, e.g. unix:/run/opengate.sock".to_string());
+            }
+            return Ok(Listen::Unix(PathBuf::from(path)));
+        }
+        let addr = spec.strip_prefix("tcp:").unwrap_or(spec);
+        match addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(Listen::Tcp(addr.to_string()))
+            }
+            _ => Err(format!(
+                "Invalid listen address '{}': expected host:port or unix:/path",
+                spec
+            )),
+        }
+    }
+}
+
+/// Bind a Unix socket at `path`, replacing a stale one left by an earlier run. Any
+/// other file there is left alone, and the bind fails.
+pub fn bind_unix(path: &Path) -> std::io::Result<UnixListener> {
+    if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    UnixListener::bind(path)
+}
+
+/// Accept connections on a Unix socket until `shutdown` resolves, then remove the
+/// socket file. Connections already open are dropped with the runtime.
+pub async fn serve_unix(
+    listener: UnixListener,
+    router: Router,
+    shutdown: impl std::future::Future<Output = ()>,
+) {
+    tokio::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("[listen] unix accept error: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let router = router.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request: Request<Incoming>| {
+                router.clone().call(request)
+            });
+            let _ = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await;
+        });
+    }
+    if let Ok(addr) = listener.local_addr() {
+        if let Some(path) = addr.as_pathname() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_listen_addresses() {
+        assert_eq!(
+            Listen::parse("unix:/run/og.sock").unwrap(),
+            Listen::Unix(PathBuf::from("/run/og.sock"))
+        );
+        assert_eq!(
+            Listen::parse("127.0.0.1:8080").unwrap(),
+            Listen::Tcp("127.0.0.1:8080".to_string())
+        );
+        assert_eq!(
+            Listen::parse("tcp:[::1]:9000").unwrap(),
+            Listen::Tcp("[::1]:9000".to_string())
+        );
+        assert!(Listen::parse("unix:").is_err());
+        assert!(Listen::parse("localhost").is_err());
+        assert!(Listen::parse(":8080").is_err());
+    }
+}
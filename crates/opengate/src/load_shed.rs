@@ -0,0 +1,137 @@
+//! Instance-wide cap on requests in flight (`[load_shedding] max_in_flight`). Past it,
+//! requests get 503 with `Retry-After` instead of queueing behind one busy client.
+//! Health checks, heartbeats and notification acks are always let through, and heavy
+//! list/search/export reads are shed first: they only get three quarters of the cap.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use axum::{
+    extract::Request,
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+static MAX_IN_FLIGHT: AtomicU32 = AtomicU32::new(0);
+static IN_FLIGHT: InFlight = InFlight::new();
+
+/// Admit at most `limit` requests at once (0 turns the limit off).
+pub fn set_max_in_flight(limit: u32) {
+    MAX_IN_FLIGHT.store(limit, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Priority {
+    /// Never shed: losing these makes agents look dead or replay work
+    Critical,
+    Normal,
+    /// Shed before anything else
+    Heavy,
+}
+
+fn priority(method: &Method, path: &str) -> Priority {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["health"] | ["metrics"] => Priority::Critical,
+        ["api", "agents", "heartbeat"] => Priority::Critical,
+        ["api", "agents", "me", "notifications", _, "ack"]
+        | ["api", "agents", "me", "notifications", "ack-all"] => Priority::Critical,
+        _ if *method != Method::GET => Priority::Normal,
+        ["api", "tasks"]
+        | ["api", "projects"]
+        | ["api", "agents"]
+        | ["api", "projects", _, "tasks"]
+        | ["api", "projects", _, "knowledge"]
+        | ["api", "projects", _, "knowledge", "search" | "graph" | "export"]
+        | ["api", "projects", _, "pulse" | "burndown" | "schedule" | "schedule.ics"]
+        | ["api", "knowledge"]
+        | ["api", "knowledge", "search"]
+        | ["api", "stats"]
+        | ["api", "stats", "timeseries" | "agents" | "reviews" | "costs" | "blocked"] => {
+            Priority::Heavy
+        }
+        _ => Priority::Normal,
+    }
+}
+
+struct InFlight(AtomicU32);
+
+impl InFlight {
+    const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    /// Count a request in, unless that would pass its priority's share of `limit`.
+    fn enter(&self, priority: Priority, limit: u32) -> Option<Admitted<'_>> {
+        let cap = match priority {
+            Priority::Critical => u32::MAX,
+            Priority::Normal => limit,
+            Priority::Heavy => (limit * 3 / 4).max(1),
+        };
+        self.0
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < cap).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Admitted(self))
+    }
+}
+
+/// Counts its request out when dropped, however the request ends.
+struct Admitted<'a>(&'a InFlight);
+
+impl Drop for Admitted<'_> {
+    fn drop(&mut self) {
+        self.0 .0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Middleware answering 503 with `Retry-After` once too many requests are in flight.
+pub async fn shed_load(request: Request, next: Next) -> Response {
+    let limit = MAX_IN_FLIGHT.load(Ordering::Relaxed);
+    if limit == 0 {
+        return next.run(request).await;
+    }
+    match IN_FLIGHT.enter(priority(request.method(), request.uri().path()), limit) {
+        Some(_admitted) => next.run(request).await,
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "1")],
+            Json(serde_json::json!({
+                "error": format!("Server busy: {} requests already in flight", limit)
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heavy_reads_shed_first() {
+        let in_flight = InFlight::new();
+        let heavy: Vec<_> = (0..4)
+            .filter_map(|_| in_flight.enter(Priority::Heavy, 4))
+            .collect();
+        assert_eq!(heavy.len(), 3);
+        let normal = in_flight.enter(Priority::Normal, 4);
+        assert!(normal.is_some());
+        assert!(in_flight.enter(Priority::Normal, 4).is_none());
+        assert!(in_flight.enter(Priority::Critical, 4).is_some());
+        drop(normal);
+        assert!(in_flight.enter(Priority::Normal, 4).is_some());
+
+        assert_eq!(priority(&Method::GET, "/api/tasks"), Priority::Heavy);
+        assert_eq!(
+            priority(&Method::POST, "/api/tasks/t1/claim"),
+            Priority::Normal
+        );
+        assert_eq!(
+            priority(&Method::POST, "/api/agents/me/notifications/7/ack"),
+            Priority::Critical
+        );
+    }
+}
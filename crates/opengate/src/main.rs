@@ -1,6 +1,7 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
 use opengate::app;
+use opengate::client::{self, Client, ClientConfig};
 use opengate::mcp;
 
 #[derive(Parser)]
@@ -14,15 +15,89 @@ struct Cli {
 }
 
 #[derive(Subcommand)]
+// Parsed once at startup; boxing `Serve` wouldn't buy anything
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Start the OpenGate engine server
     Serve {
-        #[arg(long, default_value = "8080")]
-        port: u16,
-        #[arg(long, default_value = "opengate.db")]
-        db: String,
-        #[arg(long, env = "OPENGATE_SETUP_TOKEN", default_value = "")]
-        setup_token: String,
+        /// Server settings file (TOML); OPENGATE_* variables and the flags below override it
+        #[arg(long, env = "OPENGATE_CONFIG")]
+        config: Option<String>,
+        /// [default: 8080]
+        #[arg(long)]
+        port: Option<u16>,
+        /// Accept connections here instead of host:port; repeatable. host:port or unix:/path/opengate.sock
+        #[arg(long)]
+        listen: Vec<String>,
+        /// [default: opengate.db]
+        #[arg(long)]
+        db: Option<String>,
+        /// Token agents self-register with (also OPENGATE_SETUP_TOKEN)
+        #[arg(long)]
+        setup_token: Option<String>,
+        /// Directory holding a built web dashboard to serve alongside the API
+        #[arg(long)]
+        ui_dir: Option<String>,
+        /// Maximum occurrences in one recurring series (root included) [default: 1000]
+        #[arg(long)]
+        max_recurrence_occurrences: Option<i64>,
+        /// Minutes without a heartbeat before an agent shows as idle
+        #[arg(long, env = "OPENGATE_IDLE_AFTER_MINUTES", default_value_t = opengate::presence::DEFAULT_IDLE_AFTER_MINUTES)]
+        idle_after_minutes: i64,
+        /// Minutes without a heartbeat before an agent shows as stale (offline follows each agent's stale_timeout)
+        #[arg(long, env = "OPENGATE_STALE_AFTER_MINUTES", default_value_t = opengate::presence::DEFAULT_STALE_AFTER_MINUTES)]
+        stale_after_minutes: i64,
+        /// Minutes a question may sit with a stale/offline target before it is re-routed (0 disables)
+        #[arg(long, env = "OPENGATE_QUESTION_REROUTE_MINUTES", default_value_t = opengate::question_routing::DEFAULT_REROUTE_AFTER_MINUTES)]
+        question_reroute_minutes: i64,
+        /// Republish every event to a broker: nats://host[:port] or redis://host[:port]
+        #[arg(long, env = "OPENGATE_EVENT_SINK")]
+        event_sink: Option<String>,
+        /// Subject/channel prefix for the event sink (events go to <prefix>.<event_type>)
+        #[arg(long, env = "OPENGATE_EVENT_SINK_PREFIX", default_value = opengate::event_sink::DEFAULT_PREFIX)]
+        event_sink_prefix: String,
+        /// Kafka bootstrap brokers (host:port, comma-separated); enables the Kafka outbox sink
+        #[arg(long, env = "OPENGATE_KAFKA_BROKERS")]
+        kafka_brokers: Option<String>,
+        /// Which events go to which topic: comma-separated <event pattern>=<topic>, first match wins
+        #[arg(long, env = "OPENGATE_KAFKA_ROUTES", default_value = opengate::kafka::DEFAULT_ROUTES)]
+        kafka_routes: String,
+        /// Outbound event format for webhooks and WS/SSE connections that don't choose one: opengate | cloudevents
+        #[arg(long, env = "OPENGATE_EVENT_FORMAT", default_value = "opengate")]
+        event_format: String,
+        /// Slack Web API base URL used by bot-token Slack integrations
+        #[arg(long, env = "OPENGATE_SLACK_API_URL", default_value = opengate::slack::DEFAULT_API_URL)]
+        slack_api_url: String,
+        /// GitHub REST API base URL used to comment on pull requests (GitHub Enterprise: https://<host>/api/v3)
+        #[arg(long, env = "OPENGATE_GITHUB_API_URL", default_value = opengate::github::DEFAULT_API_URL)]
+        github_api_url: String,
+        /// GitLab REST API base URL used for merge request notes and issue import (self-managed: https://<host>/api/v4)
+        #[arg(long, env = "OPENGATE_GITLAB_API_URL", default_value = opengate::gitlab::DEFAULT_API_URL)]
+        gitlab_api_url: String,
+        /// Linear GraphQL endpoint used for issue import and completion sync
+        #[arg(long, env = "OPENGATE_LINEAR_API_URL", default_value = opengate::linear::DEFAULT_API_URL)]
+        linear_api_url: String,
+        /// Events queued per WS/SSE connection before the slow-consumer policy applies
+        #[arg(long, env = "OPENGATE_CLIENT_QUEUE_CAPACITY", default_value_t = opengate::events::DEFAULT_QUEUE_CAPACITY)]
+        client_queue_capacity: usize,
+        /// What happens to a WS/SSE client whose queue is full: drop-oldest | disconnect
+        #[arg(
+            long,
+            env = "OPENGATE_SLOW_CONSUMER_POLICY",
+            default_value = "drop-oldest"
+        )]
+        slow_consumer_policy: String,
+        /// Bearer token scrapers must send to GET /metrics; open when unset
+        #[arg(long, env = "OPENGATE_METRICS_TOKEN")]
+        metrics_token: Option<String>,
+        /// Statements taking at least this many milliseconds go to the slow-query log (0 disables)
+        #[arg(long, env = "OPENGATE_SLOW_QUERY_MS", default_value_t = opengate::query_stats::DEFAULT_SLOW_QUERY_MS)]
+        slow_query_ms: u64,
+        /// OTLP/HTTP collector to export traces to, e.g. http://localhost:4318
+        #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+        otlp_endpoint: Option<String>,
+        #[arg(long, env = "OTEL_SERVICE_NAME", default_value = opengate::telemetry::DEFAULT_SERVICE_NAME)]
+        otel_service_name: String,
     },
     /// Initialize the database
     Init {
@@ -33,22 +108,299 @@ enum Commands {
     McpServer {
         #[arg(long, default_value = "opengate.db")]
         db: String,
+        /// Agent every request acts as; omit to pass `_meta.agent_key` per session or call
+        #[arg(long)]
+        agent_key: Option<String>,
+    },
+    /// List, claim and complete tasks on a running server
+    Task {
+        #[command(flatten)]
+        server: ServerArgs,
+        #[command(subcommand)]
+        command: TaskCommand,
+    },
+    /// Register agents on a running server
+    Agent {
+        #[command(flatten)]
+        server: ServerArgs,
+        #[command(subcommand)]
+        command: AgentCommand,
+    },
+    /// Terminal dashboard for a project: Pulse, the board, agents and live events
+    Tui {
+        #[command(flatten)]
+        server: ServerArgs,
+        /// Project id, id prefix or name [default: the first project]
+        #[arg(long)]
+        project: Option<String>,
+        /// Seconds between refreshes when no events arrive
+        #[arg(long, default_value_t = 5)]
+        refresh: u64,
+    },
+}
+
+/// The server client commands talk to; both fall back to the client config file
+#[derive(Args)]
+struct ServerArgs {
+    /// Server base URL [default: http://localhost:8080]
+    #[arg(long, env = "OPENGATE_URL", global = true)]
+    url: Option<String>,
+    /// API key to authenticate with
+    #[arg(long, env = "OPENGATE_API_KEY", hide_env_values = true, global = true)]
+    api_key: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum TaskCommand {
+    /// List tasks across projects
+    List {
+        #[arg(long)]
+        status: Option<String>,
+        #[arg(long)]
+        project: Option<String>,
+        #[arg(long)]
+        priority: Option<String>,
+        /// Agent or user id
+        #[arg(long)]
+        assignee: Option<String>,
+        #[arg(long)]
+        tag: Option<String>,
+        /// Print the tasks as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show one task; ids may be abbreviated to a unique prefix
+    Show {
+        id: String,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Claim a task for the key's agent
+    Claim { id: String },
+    /// Complete a task
+    Complete {
+        id: String,
         #[arg(long)]
-        agent_key: String,
+        summary: Option<String>,
     },
 }
 
+#[derive(Subcommand)]
+enum AgentCommand {
+    /// Self-register an agent with the server's setup token and print its API key
+    Register {
+        #[arg(long)]
+        name: String,
+        /// Comma-separated skills
+        #[arg(long, value_delimiter = ',')]
+        skills: Vec<String>,
+        #[arg(long, env = "OPENGATE_SETUP_TOKEN", hide_env_values = true)]
+        setup_token: String,
+        /// Save the server URL and new key to the client config file
+        #[arg(long)]
+        save: bool,
+    },
+}
+
+/// Print `result`'s output, or its error and exit non-zero.
+fn finish(result: Result<String, String>) {
+    match result {
+        Ok(output) => println!("{}", output),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn load_client_config() -> (Option<std::path::PathBuf>, ClientConfig) {
+    let path = ClientConfig::path();
+    let config = match path.as_deref().map(ClientConfig::load).transpose() {
+        Ok(config) => config.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    (path, config)
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+async fn run_task_command(client: &Client, command: TaskCommand) -> Result<String, String> {
+    match command {
+        TaskCommand::List {
+            status,
+            project,
+            priority,
+            assignee,
+            tag,
+            json,
+        } => {
+            let filters = opengate_models::TaskFilters {
+                project_id: project,
+                status,
+                priority,
+                assignee_id: assignee,
+                tag,
+            };
+            let tasks = client.list_tasks(&filters).await?;
+            Ok(if json {
+                to_json(&tasks)
+            } else if tasks.is_empty() {
+                "No tasks".to_string()
+            } else {
+                client::task_table(&tasks)
+            })
+        }
+        TaskCommand::Show { id, json } => {
+            let task = client.get_task(&id).await?;
+            Ok(if json {
+                to_json(&task)
+            } else {
+                client::task_details(&task)
+            })
+        }
+        TaskCommand::Claim { id } => {
+            let task = client.claim_task(&id).await?;
+            Ok(format!("Claimed {}: {}", task.id, task.title))
+        }
+        TaskCommand::Complete { id, summary } => {
+            let task = client.complete_task(&id, summary.as_deref()).await?;
+            Ok(format!(
+                "Completed {}: {} ({})",
+                task.id, task.title, task.status
+            ))
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
     match cli.command {
         Commands::Serve {
+            config,
             port,
+            listen,
             db,
             setup_token,
+            ui_dir,
+            max_recurrence_occurrences,
+            idle_after_minutes,
+            stale_after_minutes,
+            question_reroute_minutes,
+            event_sink,
+            event_sink_prefix,
+            kafka_brokers,
+            kafka_routes,
+            event_format,
+            slack_api_url,
+            github_api_url,
+            gitlab_api_url,
+            linear_api_url,
+            client_queue_capacity,
+            slow_consumer_policy,
+            metrics_token,
+            slow_query_ms,
+            otlp_endpoint,
+            otel_service_name,
         } => {
-            app::run_server(port, &db, &setup_token).await;
+            let mut config = match opengate::config::ServerConfig::load(config.as_deref()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            };
+            if let Some(port) = port {
+                config.port = port;
+            }
+            if !listen.is_empty() {
+                config.listen = listen;
+            }
+            if let Some(db) = db {
+                config.db = db;
+            }
+            if let Some(token) = setup_token {
+                config.setup_token = token;
+            }
+            if let Some(max) = max_recurrence_occurrences {
+                config.max_recurrence_occurrences = max;
+            }
+            if let Some(dir) = ui_dir {
+                config.ui_dir = dir;
+            }
+            if let Err(e) = config.validate() {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            app::set_cors_origins(config.cors.allowed_origins.clone());
+            opengate::handlers::webhooks::set_policy(config.webhooks.clone());
+            opengate::rate_limit::set_requests_per_minute(config.rate_limit.requests_per_minute);
+            opengate::load_shed::set_max_in_flight(config.load_shedding.max_in_flight);
+            opengate::compression::set_enabled(config.compression.enabled);
+            if !config.ui_dir.is_empty() {
+                opengate::ui::set_dir(config.ui_dir.clone().into());
+            }
+            opengate::quotas::set_limits(config.tenant_limits.clone());
+            opengate::presence::set_thresholds(idle_after_minutes, stale_after_minutes);
+            opengate::question_routing::set_reroute_after_minutes(question_reroute_minutes);
+            opengate::query_stats::set_slow_threshold_ms(slow_query_ms);
+            if let Some(url) = event_sink {
+                match opengate::event_sink::parse_sink_url(&url, &event_sink_prefix) {
+                    Ok(config) => opengate::event_sink::set_sink(config),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(2);
+                    }
+                }
+            }
+            match opengate::cloudevents::EventFormat::from_str(&event_format) {
+                Some(format) => opengate::cloudevents::set_default_format(format),
+                None => {
+                    eprintln!(
+                        "Unknown event format '{}': use opengate or cloudevents",
+                        event_format
+                    );
+                    std::process::exit(2);
+                }
+            }
+            if let Some(brokers) = kafka_brokers {
+                match opengate::kafka::parse_config(&brokers, &kafka_routes) {
+                    Ok(config) => opengate::kafka::set_kafka(config),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(2);
+                    }
+                }
+            }
+            opengate::slack::set_api_url(&slack_api_url);
+            opengate::github::set_api_url(&github_api_url);
+            opengate::gitlab::set_api_url(&gitlab_api_url);
+            opengate::linear::set_api_url(&linear_api_url);
+            match opengate::events::SlowConsumerPolicy::from_str(&slow_consumer_policy) {
+                Some(policy) => {
+                    opengate::events::set_subscriber_limits(client_queue_capacity, policy)
+                }
+                None => {
+                    eprintln!(
+                        "Unknown slow consumer policy '{}': use drop-oldest or disconnect",
+                        slow_consumer_policy
+                    );
+                    std::process::exit(2);
+                }
+            }
+            if let Some(token) = metrics_token.filter(|t| !t.is_empty()) {
+                opengate::metrics::set_token(token);
+            }
+            if let Some(endpoint) = otlp_endpoint.filter(|e| !e.is_empty()) {
+                opengate::telemetry::set_exporter(&endpoint, &otel_service_name);
+            }
+            app::run_server(&config).await;
         }
         Commands::Init { db } => {
             let conn = opengate::db::init_db(&db);
@@ -56,7 +408,54 @@ async fn main() {
             drop(conn);
         }
         Commands::McpServer { db, agent_key } => {
-            mcp::run_mcp_server(&db, &agent_key).await;
+            mcp::run_mcp_server(&db, agent_key.as_deref()).await;
+        }
+        Commands::Task { server, command } => {
+            let (_, config) = load_client_config();
+            let client = Client::resolve(server.url, server.api_key, &config);
+            finish(run_task_command(&client, command).await);
+        }
+        Commands::Tui {
+            server,
+            project,
+            refresh,
+        } => {
+            let (_, config) = load_client_config();
+            let client = Client::resolve(server.url, server.api_key, &config);
+            let refresh = std::time::Duration::from_secs(refresh.max(1));
+            if let Err(e) = opengate::tui::run(&client, project.as_deref(), refresh).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Agent {
+            server,
+            command:
+                AgentCommand::Register {
+                    name,
+                    skills,
+                    setup_token,
+                    save,
+                },
+        } => {
+            let (path, mut config) = load_client_config();
+            let client = Client::resolve(server.url, None, &config);
+            finish(
+                async {
+                    let (agent_id, api_key) =
+                        client.register_agent(&name, &skills, &setup_token).await?;
+                    let mut output = format!("Registered {} as {}\n{}", name, agent_id, api_key);
+                    if save {
+                        let path = path.ok_or("No config directory: set OPENGATE_CLIENT_CONFIG")?;
+                        config.url = Some(client.url().to_string());
+                        config.api_key = Some(api_key);
+                        config.save(&path)?;
+                        output.push_str(&format!("\nSaved to {}", path.display()));
+                    }
+                    Ok(output)
+                }
+                .await,
+            );
         }
     }
 }
@@ -0,0 +1,288 @@
+//! Payload mapping for inbound trigger `action_config`s.
+//!
+//! Strings in a config are templates: `{{payload.field}}` is replaced with a field of the
+//! webhook body. Paths take JSONPath-style steps — `{{payload.commits[0].author.name}}`,
+//! `{{$.commits[0].id}}` (`$` is the payload) — and `[*]` collects every element of an
+//! array. A config value that is a single placeholder and nothing else keeps the JSON
+//! type it resolves to, so objects and numbers can be mapped into task context as-is.
+//!
+//! `"for_each": "payload.commits"` runs the action once per array element, with that
+//! element as `{{item}}` and its position as `{{index}}`.
+
+use serde_json::Value;
+
+/// Most elements one `for_each` may fan out to.
+pub const MAX_ITEMS: usize = 100;
+
+/// Names a placeholder may start with; anything else is left as written.
+const ROOTS: &[&str] = &["payload", "item", "index"];
+
+/// Template variables for a payload.
+pub fn vars(payload: &Value) -> Value {
+    serde_json::json!({ "payload": payload })
+}
+
+/// Template variables for one `for_each` element.
+pub fn item_vars(payload: &Value, item: &Value, index: usize) -> Value {
+    serde_json::json!({ "payload": payload, "item": item, "index": index })
+}
+
+/// The elements `cfg.for_each` points at, or `None` when the config doesn't iterate.
+pub fn for_each_items(cfg: &Value, payload: &Value) -> Result<Option<Vec<Value>>, String> {
+    let Some(expr) = cfg.get("for_each").filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let expr = expr
+        .as_str()
+        .map(|e| strip_braces(e).unwrap_or(e))
+        .ok_or("for_each must be a path like payload.items")?;
+    match lookup(&vars(payload), expr) {
+        Some(Value::Array(items)) if items.len() > MAX_ITEMS => Err(format!(
+            "for_each: {} has {} elements (at most {})",
+            expr,
+            items.len(),
+            MAX_ITEMS
+        )),
+        Some(Value::Array(items)) => Ok(Some(items)),
+        Some(_) => Err(format!("for_each: {} is not an array", expr)),
+        None => Err(format!("for_each: {} is not in the payload", expr)),
+    }
+}
+
+/// Resolve `expr` (`payload.a.b[0]`, `$.a[*].b`, `item.x`, `index`) against `vars`.
+/// `None` when a step doesn't exist or the root isn't a variable.
+pub fn lookup(vars: &Value, expr: &str) -> Option<Value> {
+    let expr = expr.trim();
+    let path = match expr.strip_prefix('$') {
+        Some(rest) => format!("payload{}", rest),
+        None => expr.to_string(),
+    };
+    let steps = parse_path(&path)?;
+    let Some(Step::Key(root)) = steps.first() else {
+        return None;
+    };
+    if !ROOTS.contains(&root.as_str()) {
+        return None;
+    }
+
+    let fans_out = steps.iter().any(|s| matches!(s, Step::All));
+    let mut current = vec![vars];
+    for step in &steps {
+        current = current
+            .into_iter()
+            .flat_map(|value| -> Vec<&Value> {
+                match (step, value) {
+                    (Step::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+                    // `commits.0.id` works like `commits[0].id`
+                    (Step::Key(key), Value::Array(arr)) => key
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| arr.get(i))
+                        .into_iter()
+                        .collect(),
+                    (Step::Index(i), Value::Array(arr)) => arr.get(*i).into_iter().collect(),
+                    (Step::All, Value::Array(arr)) => arr.iter().collect(),
+                    _ => vec![],
+                }
+            })
+            .collect();
+    }
+    if fans_out {
+        Some(Value::Array(current.into_iter().cloned().collect()))
+    } else {
+        current.first().map(|v| (*v).clone())
+    }
+}
+
+enum Step {
+    Key(String),
+    Index(usize),
+    All,
+}
+
+/// `a.b[0][*].c` → Key(a) Key(b) Index(0) All Key(c)
+fn parse_path(path: &str) -> Option<Vec<Step>> {
+    let mut steps = Vec::new();
+    for segment in path.split('.') {
+        let (key, mut rest) = match segment.find('[') {
+            Some(i) => segment.split_at(i),
+            None => (segment, ""),
+        };
+        if !key.is_empty() {
+            steps.push(Step::Key(key.to_string()));
+        } else if steps.is_empty() || rest.is_empty() {
+            return None;
+        }
+        while let Some(inner) = rest.strip_prefix('[') {
+            let end = inner.find(']')?;
+            steps.push(match &inner[..end] {
+                "*" => Step::All,
+                index => Step::Index(index.parse().ok()?),
+            });
+            rest = &inner[end + 1..];
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+    }
+    Some(steps)
+}
+
+/// Replace every `{{...}}` in `template`; values that don't resolve become empty.
+pub fn interpolate(template: &str, vars: &Value) -> String {
+    render_str(template, vars, &mut Vec::new())
+}
+
+/// `value` with every string rendered (see the module docs for typed placeholders).
+pub fn render(value: &Value, vars: &Value) -> Value {
+    render_checked(value, vars, &mut Vec::new())
+}
+
+/// Like [`render`], collecting the placeholders that resolved to nothing into `missing`.
+pub fn render_checked(value: &Value, vars: &Value, missing: &mut Vec<String>) -> Value {
+    match value {
+        Value::String(s) => match strip_braces(s) {
+            Some(expr) if is_variable(expr) => lookup(vars, expr).unwrap_or_else(|| {
+                missing.push(s.clone());
+                Value::Null
+            }),
+            _ => Value::String(render_str(s, vars, missing)),
+        },
+        Value::Array(items) => items
+            .iter()
+            .map(|v| render_checked(v, vars, missing))
+            .collect(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| (k.clone(), render_checked(v, vars, missing)))
+            .collect(),
+        other => other.clone(),
+    }
+}
+
+fn render_str(template: &str, vars: &Value, missing: &mut Vec<String>) -> String {
+    let mut result = template.to_string();
+    let mut i = 0;
+    while let Some(start) = result[i..].find("{{") {
+        let abs_start = i + start;
+        let Some(end) = result[abs_start..].find("}}") else {
+            break;
+        };
+        let abs_end = abs_start + end + 2;
+        let expr = result[abs_start + 2..abs_start + end].trim().to_string();
+        if is_variable(&expr) {
+            let replacement = match lookup(vars, &expr) {
+                Some(value) => to_text(&value),
+                None => {
+                    missing.push(format!("{{{{{}}}}}", expr));
+                    String::new()
+                }
+            };
+            result.replace_range(abs_start..abs_end, &replacement);
+            i = abs_start + replacement.len();
+        } else {
+            i = abs_end;
+        }
+    }
+    result
+}
+
+/// `{{expr}}` → `expr`, when the whole string is one placeholder.
+fn strip_braces(s: &str) -> Option<&str> {
+    let inner = s.trim().strip_prefix("{{")?.strip_suffix("}}")?;
+    (!inner.contains("{{") && !inner.contains("}}")).then_some(inner.trim())
+}
+
+fn is_variable(expr: &str) -> bool {
+    expr.starts_with('$')
+        || ROOTS.iter().any(|root| {
+            expr.strip_prefix(root)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+        })
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+        // `[*]` results read as a list
+        Value::Array(items) if items.iter().all(|v| !v.is_object() && !v.is_array()) => {
+            items.iter().map(to_text).collect::<Vec<_>>().join(", ")
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn push() -> Value {
+        json!({
+            "ref": "refs/heads/main",
+            "commits": [
+                {"id": "a1", "message": "Fix login", "author": {"name": "ada"}},
+                {"id": "b2", "message": "Add tests", "author": {"name": "bob"}}
+            ]
+        })
+    }
+
+    #[test]
+    fn resolves_paths() {
+        let vars = vars(&push());
+        assert_eq!(lookup(&vars, "payload.commits[1].id"), Some(json!("b2")));
+        assert_eq!(lookup(&vars, "payload.commits.0.id"), Some(json!("a1")));
+        assert_eq!(
+            lookup(&vars, "$.commits[0].author.name"),
+            Some(json!("ada"))
+        );
+        assert_eq!(
+            lookup(&vars, "$.commits[*].author.name"),
+            Some(json!(["ada", "bob"]))
+        );
+        assert_eq!(lookup(&vars, "payload.nope"), None);
+        assert_eq!(lookup(&vars, "secrets.token"), None);
+    }
+
+    #[test]
+    fn interpolates_templates() {
+        let vars = vars(&push());
+        assert_eq!(
+            interpolate("{{ payload.ref }}: {{$.commits[*].id}}", &vars),
+            "refs/heads/main: a1, b2"
+        );
+        // Unknown placeholders are left alone; missing fields go empty
+        assert_eq!(
+            interpolate("{{other}} [{{payload.missing}}]", &vars),
+            "{{other}} []"
+        );
+    }
+
+    #[test]
+    fn renders_typed_values_and_reports_missing() {
+        let payload = push();
+        let items = for_each_items(&json!({"for_each": "payload.commits"}), &payload)
+            .unwrap()
+            .unwrap();
+        assert_eq!(items.len(), 2);
+        let vars = item_vars(&payload, &items[1], 1);
+        let mut missing = Vec::new();
+        let rendered = render_checked(
+            &json!({
+                "title": "#{{index}} {{item.message}}",
+                "context": {"author": "{{item.author}}", "sha": "{{item.sha}}"}
+            }),
+            &vars,
+            &mut missing,
+        );
+        assert_eq!(rendered["title"], "#1 Add tests");
+        assert_eq!(rendered["context"]["author"], json!({"name": "bob"}));
+        assert_eq!(missing, vec!["{{item.sha}}".to_string()]);
+
+        assert!(for_each_items(&json!({"for_each": "payload.ref"}), &payload).is_err());
+        assert!(for_each_items(&json!({}), &payload).unwrap().is_none());
+    }
+}
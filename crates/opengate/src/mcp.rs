@@ -1,38 +1,74 @@
-use rusqlite::Connection;
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use crate::auth;
 use crate::db;
-use crate::db_ops;
+use crate::freshness;
+use crate::quotas::{self, Quota};
+use crate::recurrence;
+use crate::storage::sqlite::SqliteBackend;
+use crate::storage::StorageBackend;
 use opengate_models::*;
 
 struct McpContext {
-    conn: Connection,
+    storage: Arc<dyn StorageBackend>,
     agent_id: String,
     agent_name: String,
     tenant_id: Option<String>,
 }
 
-pub async fn run_mcp_server(db_path: &str, agent_key: &str) {
+impl McpContext {
+    /// The context for the agent holding `agent_key`, or `None` if no agent has it.
+    fn authenticate(storage: Arc<dyn StorageBackend>, agent_key: &str) -> Option<Self> {
+        let hash = storage.hash_api_key(agent_key);
+        let agent = storage.get_agent_by_key_hash(None, &hash)?;
+        if let Some(tenant) = agent.owner_id.as_deref() {
+            if auth::tenant_suspended(&*storage, tenant) {
+                return None;
+            }
+        }
+        storage.update_heartbeat(None, &agent.id);
+        Some(McpContext {
+            agent_id: agent.id,
+            agent_name: agent.name,
+            tenant_id: agent.owner_id,
+            storage,
+        })
+    }
+}
+
+/// Serve MCP over stdio. With `agent_key` every request acts as that agent; without it,
+/// or to act as someone else, a request carries a key in `params._meta.agent_key` — on
+/// `initialize` it becomes the session's agent, on any other request it applies to that
+/// request only. One process can so serve many agents, each attributed correctly.
+pub async fn run_mcp_server(db_path: &str, agent_key: Option<&str>) {
     let conn = db::init_db(db_path);
+    let storage =
+        Arc::new(SqliteBackend::new(Arc::new(Mutex::new(conn)))) as Arc<dyn StorageBackend>;
 
     // Validate agent key
-    let hash = db_ops::hash_api_key(agent_key);
-    let agent =
-        db_ops::get_agent_by_key_hash(&conn, &hash).expect("Invalid agent key — agent not found");
-
-    eprintln!(
-        "[mcp] Authenticated as agent '{}' ({})",
-        agent.name, agent.id
-    );
-    db_ops::update_heartbeat(&conn, &agent.id);
-
-    let ctx = McpContext {
-        conn,
-        agent_id: agent.id,
-        agent_name: agent.name,
-        tenant_id: agent.owner_id,
+    let agent = agent_key.map(|key| {
+        McpContext::authenticate(storage.clone(), key).expect("Invalid agent key — agent not found")
+    });
+    match &agent {
+        Some(ctx) => eprintln!(
+            "[mcp] Authenticated as agent '{}' ({})",
+            ctx.agent_name, ctx.agent_id
+        ),
+        None => eprintln!("[mcp] No --agent-key: requests must carry _meta.agent_key"),
+    }
+    let mut session = Session {
+        storage: storage.clone(),
+        agent: None,
+        watch: Arc::new(Mutex::new(None)),
     };
+    if let Some(ctx) = agent {
+        session.set_agent(ctx);
+    }
+    spawn_notification_pusher(storage, session.watch.clone());
 
     let stdin = io::stdin();
     let stdout = io::stdout();
@@ -50,47 +86,16 @@ pub async fn run_mcp_server(db_path: &str, agent_key: &str) {
         let request: Value = match serde_json::from_str(&line) {
             Ok(v) => v,
             Err(e) => {
-                let err_resp = json!({
-                    "jsonrpc": "2.0",
-                    "id": null,
-                    "error": {"code": -32700, "message": format!("Parse error: {}", e)}
-                });
                 let mut out = stdout.lock();
-                let _ = writeln!(out, "{}", err_resp);
+                let _ = writeln!(out, "{}", parse_error(&e));
                 let _ = out.flush();
                 continue;
             }
         };
 
-        let id = request.get("id").cloned();
-        let method = request.get("method").and_then(|v| v.as_str()).unwrap_or("");
-        let params = request.get("params").cloned().unwrap_or(json!({}));
-
         // Notifications (no id) don't get responses
-        if id.is_none() {
-            // Handle notifications like initialized
+        let Some(resp) = session.handle(&request) else {
             continue;
-        }
-
-        let response = match method {
-            "initialize" => handle_initialize(&params),
-            "tools/list" => handle_tools_list(),
-            "tools/call" => handle_tools_call(&ctx, &params),
-            "ping" => Ok(json!({})),
-            _ => Err(json!({"code": -32601, "message": format!("Method not found: {}", method)})),
-        };
-
-        let resp = match response {
-            Ok(result) => json!({
-                "jsonrpc": "2.0",
-                "id": id,
-                "result": result
-            }),
-            Err(error) => json!({
-                "jsonrpc": "2.0",
-                "id": id,
-                "error": error
-            }),
         };
 
         let mut out = stdout.lock();
@@ -99,11 +104,220 @@ pub async fn run_mcp_server(db_path: &str, agent_key: &str) {
     }
 }
 
-fn handle_initialize(_params: &Value) -> Result<Value, Value> {
+/// One stdio connection and the agent it acts as, if it has one yet.
+struct Session {
+    storage: Arc<dyn StorageBackend>,
+    agent: Option<McpContext>,
+    /// Whose notifications get pushed, shared with the pusher thread
+    watch: Arc<Mutex<Option<Watch>>>,
+}
+
+struct Watch {
+    agent_id: String,
+    tenant_id: Option<String>,
+    cursor: i64,
+}
+
+impl Session {
+    fn handle(&mut self, request: &Value) -> Option<Value> {
+        let per_call = match request_agent_key(request) {
+            Some(key) => match McpContext::authenticate(self.storage.clone(), key) {
+                Some(ctx) => Some(ctx),
+                None => return request.get("id").map(invalid_agent_key),
+            },
+            None => None,
+        };
+        // A key given at initialize holds for the rest of the session
+        if request.get("method").and_then(|v| v.as_str()) == Some("initialize") {
+            if let Some(ctx) = per_call {
+                eprintln!(
+                    "[mcp] Session is agent '{}' ({})",
+                    ctx.agent_name, ctx.agent_id
+                );
+                self.set_agent(ctx);
+                return handle_message(self.agent.as_ref(), request);
+            }
+        }
+        handle_message(per_call.as_ref().or(self.agent.as_ref()), request)
+    }
+
+    /// Act as `ctx` from now on, and push its notifications from here on.
+    fn set_agent(&mut self, ctx: McpContext) {
+        let cursor = self
+            .storage
+            .get_last_notification_id(ctx.tenant_id.as_deref(), &ctx.agent_id);
+        *self.watch.lock().unwrap() = Some(Watch {
+            agent_id: ctx.agent_id.clone(),
+            tenant_id: ctx.tenant_id.clone(),
+            cursor,
+        });
+        self.agent = Some(ctx);
+    }
+}
+
+/// How often the session agent's notifications are checked for pushing.
+pub(crate) const NOTIFICATION_POLL: Duration = Duration::from_secs(1);
+
+const NOTIFICATION_BATCH: i64 = 100;
+
+/// Write the session agent's new notifications to stdout as they arrive, so the client
+/// hears about them without polling.
+fn spawn_notification_pusher(storage: Arc<dyn StorageBackend>, watch: Arc<Mutex<Option<Watch>>>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(NOTIFICATION_POLL);
+        let messages = match watch.lock().unwrap().as_mut() {
+            Some(w) => notification_messages(
+                &*storage,
+                w.tenant_id.as_deref(),
+                &w.agent_id,
+                &mut w.cursor,
+            ),
+            None => continue,
+        };
+        if messages.is_empty() {
+            continue;
+        }
+        let mut out = io::stdout().lock();
+        for message in messages {
+            let _ = writeln!(out, "{}", message);
+        }
+        let _ = out.flush();
+    });
+}
+
+/// A `notifications/message` for each of `agent_id`'s notifications after `cursor`,
+/// which moves past them. The notification itself is the message's `data`.
+pub(crate) fn notification_messages(
+    storage: &dyn StorageBackend,
+    tenant: Option<&str>,
+    agent_id: &str,
+    cursor: &mut i64,
+) -> Vec<Value> {
+    let mut messages = Vec::new();
+    loop {
+        let batch = storage.list_notifications_after(tenant, agent_id, *cursor, NOTIFICATION_BATCH);
+        let Some(last) = batch.last() else { break };
+        *cursor = last.id;
+        messages.extend(batch.iter().map(|notification| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": {
+                    "level": "info",
+                    "logger": "opengate",
+                    "data": {"type": "notification", "notification": notification}
+                }
+            })
+        }));
+    }
+    messages
+}
+
+/// The agent key a request carries in `params._meta.agent_key`.
+fn request_agent_key(request: &Value) -> Option<&str> {
+    request
+        .get("params")?
+        .get("_meta")?
+        .get("agent_key")?
+        .as_str()
+}
+
+fn invalid_agent_key(id: &Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": -32001, "message": "Invalid agent key — agent not found"}
+    })
+}
+
+/// Answer one JSON-RPC message from `agent` (the HTTP transport's entry point); `None`
+/// for notifications.
+pub(crate) fn respond(
+    storage: Arc<dyn StorageBackend>,
+    agent_id: &str,
+    agent_name: &str,
+    tenant_id: Option<&str>,
+    request: &Value,
+) -> Option<Value> {
+    let ctx = McpContext {
+        storage,
+        agent_id: agent_id.to_string(),
+        agent_name: agent_name.to_string(),
+        tenant_id: tenant_id.map(str::to_string),
+    };
+    handle_message(Some(&ctx), request)
+}
+
+/// A JSON-RPC parse error response.
+pub(crate) fn parse_error(e: &serde_json::Error) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {"code": -32700, "message": format!("Parse error: {}", e)}
+    })
+}
+
+/// Answer one JSON-RPC message; `None` for notifications, which get no response.
+/// Tools and resources need an agent; the handshake and listings don't.
+fn handle_message(ctx: Option<&McpContext>, request: &Value) -> Option<Value> {
+    let id = request.get("id").cloned()?;
+    let method = request.get("method").and_then(|v| v.as_str()).unwrap_or("");
+    let params = request.get("params").cloned().unwrap_or(json!({}));
+    let agent = || {
+        ctx.ok_or_else(|| {
+            json!({
+                "code": -32001,
+                "message": "No agent: start with --agent-key, or pass _meta.agent_key at initialize or on this request"
+            })
+        })
+    };
+
+    let response = match method {
+        "initialize" => handle_initialize(&params),
+        "tools/list" => handle_tools_list(),
+        "tools/call" => agent().and_then(|ctx| handle_tools_call(ctx, &params)),
+        "resources/list" => agent().and_then(handle_resources_list),
+        "resources/templates/list" => handle_resource_templates_list(),
+        "resources/read" => agent().and_then(|ctx| handle_resources_read(ctx, &params)),
+        "prompts/list" => handle_prompts_list(),
+        "prompts/get" => agent().and_then(|ctx| handle_prompts_get(ctx, &params)),
+        // Notifications are pushed at `info` whatever the level; nothing else is logged
+        "logging/setLevel" => Ok(json!({})),
+        "ping" => Ok(json!({})),
+        _ => Err(json!({"code": -32601, "message": format!("Method not found: {}", method)})),
+    };
+
+    Some(match response {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": error
+        }),
+    })
+}
+
+/// Protocol revisions this server speaks, newest first. Streamable HTTP arrived in
+/// 2025-03-26; stdio clients on 2024-11-05 see no difference.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
+fn handle_initialize(params: &Value) -> Result<Value, Value> {
+    // Answer with the client's revision when we know it, else our newest
+    let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+    let version = requested
+        .filter(|v| PROTOCOL_VERSIONS.contains(v))
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
     Ok(json!({
-        "protocolVersion": "2024-11-05",
+        "protocolVersion": version,
         "capabilities": {
-            "tools": {}
+            "tools": {},
+            "resources": {},
+            "prompts": {},
+            "logging": {}
         },
         "serverInfo": {
             "name": "opengate",
@@ -142,14 +356,16 @@ fn handle_tools_list() -> Result<Value, Value> {
                 },
                 "required": ["name"]
             })),
-            tool_def("list_tasks", "List tasks with optional filters", json!({
+            tool_def("list_tasks", "List tasks with optional filters, a page at a time. When the result says truncated, pass its next_cursor to get more.", json!({
                 "type": "object",
                 "properties": {
                     "project_id": {"type": "string", "description": "Filter by project ID"},
                     "status": {"type": "string", "description": "Filter by status"},
                     "priority": {"type": "string", "description": "Filter by priority"},
                     "assignee_id": {"type": "string", "description": "Filter by assignee ID"},
-                    "tag": {"type": "string", "description": "Filter by tag"}
+                    "tag": {"type": "string", "description": "Filter by tag"},
+                    "limit": {"type": "integer", "description": "Page size (default 50, max 200)"},
+                    "cursor": {"type": "string", "description": "next_cursor from the previous page"}
                 }
             })),
             tool_def("get_task", "Get task with full context and output", json!({
@@ -224,9 +440,13 @@ fn handle_tools_list() -> Result<Value, Value> {
                     "skills": {"type": "array", "items": {"type": "string"}, "description": "Skills to match against task tags"}
                 }
             })),
-            tool_def("my_tasks", "List ALL tasks assigned to you (including completed). For actionable work only, use check_inbox instead.", json!({
+            tool_def("my_tasks", "List tasks assigned to you (including completed unless filtered by status), a page at a time. For actionable work only, use check_inbox instead.", json!({
                 "type": "object",
-                "properties": {}
+                "properties": {
+                    "status": {"type": "string", "description": "Filter by status; comma-separate several (e.g. todo,in_progress)"},
+                    "limit": {"type": "integer", "description": "Page size (default 50, max 200)"},
+                    "cursor": {"type": "string", "description": "next_cursor from the previous page"}
+                }
             })),
             tool_def("update_context", "Merge-patch task context (append/update fields)", json!({
                 "type": "object",
@@ -245,9 +465,13 @@ fn handle_tools_list() -> Result<Value, Value> {
                 },
                 "required": ["task_id", "content"]
             })),
-            tool_def("heartbeat", "Report agent liveness", json!({
+            tool_def("heartbeat", "Report agent liveness, optionally with a status shown to operators", json!({
                 "type": "object",
-                "properties": {}
+                "properties": {
+                    "load": {"type": "number", "description": "Current load (e.g. active jobs, or 0.0-1.0 utilisation)"},
+                    "version": {"type": "string", "description": "Agent software version"},
+                    "status_message": {"type": "string", "description": "Free-form status (max 500 chars)"}
+                }
             })),
             tool_def("list_agents", "List all registered agents", json!({
                 "type": "object",
@@ -269,15 +493,16 @@ fn handle_tools_list() -> Result<Value, Value> {
                     "max_concurrent_tasks": {"type": "integer", "description": "Max concurrent tasks"},
                     "webhook_url": {"type": "string", "description": "Webhook URL for notifications"},
                     "webhook_events": {"type": "array", "items": {"type": "string"}, "description": "Event types to subscribe to (null = all)"},
+                    "webhook_template": {"type": "object", "description": "JSON body for webhook pushes with {{field.path}} placeholders (e.g. {\"text\": \"{{title}}\"}); {} removes it"},
                     "config": {"type": "object", "description": "Arbitrary agent config JSON"},
                     "model": {"type": "string", "description": "LLM model identifier"},
                     "provider": {"type": "string", "description": "LLM provider"},
                     "cost_tier": {"type": "string", "description": "Cost tier (free|standard|premium)"},
                     "capabilities": {"type": "array", "items": {"type": "string"}, "description": "Capability strings (e.g. code-review:rust)"},
                     "seniority": {"type": "string", "description": "Agent seniority: junior | mid | senior"},
-                    "role": {"type": "string", "description": "Agent role: executor | orchestrator"},
                     "stale_timeout": {"type": "integer", "description": "Minutes before considered stale (default: 240)"},
-                    "tags": {"type": "array", "items": {"type": "string"}, "description": "Category tags (e.g. [\"rust\", \"frontend\", \"devops\"])"}
+                    "tags": {"type": "array", "items": {"type": "string"}, "description": "Category tags (e.g. [\"rust\", \"frontend\", \"devops\"])"},
+                    "notification_preferences": {"type": "object", "description": "Per event type (or \"*\"): {in_app?: bool, webhook?: bool, wake?: bool, email?: bool, push?: bool, min_priority?: critical|high|medium|low, push_min_priority?: critical|high|medium|low, ack_after_hours?: int (auto-ack unread after N hours), archive_after_hours?: int (archive unread after N hours)}. Replaces the whole map."}
                 }
             })),
             tool_def("assign_task", "Assign a task to a specific agent", json!({
@@ -313,6 +538,30 @@ fn handle_tools_list() -> Result<Value, Value> {
                 },
                 "required": ["task_id", "comment"]
             })),
+            // dependency tools
+            tool_def("add_dependencies", "Make a task depend on other tasks: it can't be claimed until they're done. Cycles are refused.", json!({
+                "type": "object",
+                "properties": {
+                    "task_id": {"type": "string", "description": "Task that waits"},
+                    "depends_on": {"type": "array", "items": {"type": "string"}, "description": "Task IDs it waits on"}
+                },
+                "required": ["task_id", "depends_on"]
+            })),
+            tool_def("remove_dependency", "Remove one dependency from a task", json!({
+                "type": "object",
+                "properties": {
+                    "task_id": {"type": "string", "description": "Task that waits"},
+                    "depends_on_id": {"type": "string", "description": "Task it should no longer wait on"}
+                },
+                "required": ["task_id", "depends_on_id"]
+            })),
+            tool_def("get_dependency_graph", "Get a project's task dependency graph: nodes, edges (from waits on to), the tasks ready to start and the topological layers, for planning.", json!({
+                "type": "object",
+                "properties": {
+                    "project_id": {"type": "string", "description": "Project ID"}
+                },
+                "required": ["project_id"]
+            })),
             tool_def("get_knowledge", "Get a knowledge base entry by key", json!({
                 "type": "object",
                 "properties": {
@@ -335,9 +584,10 @@ fn handle_tools_list() -> Result<Value, Value> {
                     },
                     "category": {
                         "type": "string",
-                        "enum": ["architecture", "pattern", "gotcha", "decision", "reference"],
+                        "enum": ["architecture", "pattern", "gotcha", "decision", "reference", "runbook"],
                         "description": "Entry category"
-                    }
+                    },
+                    "review_after": {"type": "string", "description": "Review deadline (RFC 3339 or YYYY-MM-DD); the entry is flagged stale once it passes. Empty string clears it."}
                 },
                 "required": ["project_id", "key", "title", "content"]
             })),
@@ -353,8 +603,13 @@ fn handle_tools_list() -> Result<Value, Value> {
                     },
                     "category": {
                         "type": "string",
-                        "enum": ["architecture", "pattern", "gotcha", "decision", "reference"],
+                        "enum": ["architecture", "pattern", "gotcha", "decision", "reference", "runbook"],
                         "description": "Filter by category"
+                    },
+                    "scope": {
+                        "type": "string",
+                        "enum": ["project", "all"],
+                        "description": "'all' also searches the organization-wide shared knowledge (default: project)"
                     }
                 },
                 "required": ["project_id"]
@@ -370,7 +625,9 @@ fn handle_tools_list() -> Result<Value, Value> {
             tool_def("get_notifications", "Get your notifications. Use unread_only=true (default) for new notifications.", json!({
                 "type": "object",
                 "properties": {
-                    "unread_only": {"type": "boolean", "description": "Only return unread notifications (default: true)"}
+                    "unread_only": {"type": "boolean", "description": "Only return unread notifications (default: true)"},
+                    "limit": {"type": "integer", "description": "Page size (default 50, max 200)"},
+                    "cursor": {"type": "string", "description": "next_cursor from the previous page"}
                 }
             })),
             tool_def("ack_notification", "Mark notification(s) as read. Pass id to ack one, omit to ack all.", json!({
@@ -380,22 +637,21 @@ fn handle_tools_list() -> Result<Value, Value> {
                 }
             })),
             // artifact tools
-            tool_def("create_artifact", "Attach an artifact to a task. Multiple artifacts per task are supported — call this repeatedly to add more (e.g. draft + final, post + image URL). Types: url, text, json, file. Text/json max 65536 chars.", json!({
+            tool_def("create_artifact", "Attach an artifact to a task — by default your current task (the one you have in progress). Multiple artifacts per task are supported — call this repeatedly to add more (e.g. draft + final, post + image URL). Types: url, text, json, file. Text/json max 65536 chars.", json!({
                 "type": "object",
                 "properties": {
-                    "task_id": {"type": "string", "description": "Task ID to attach the artifact to"},
+                    "task_id": {"type": "string", "description": "Task ID to attach the artifact to (default: your in-progress task)"},
                     "name": {"type": "string", "description": "Human-readable artifact name (e.g. 'LinkedIn Post Draft', 'Final Copy', 'Image URL')"},
                     "artifact_type": {"type": "string", "enum": ["url", "text", "json", "file"], "description": "Artifact type"},
-                    "value": {"type": "string", "description": "Artifact content (text/json up to 65536 chars; url/file store a URL or path)"}
+                    "value": {"description": "Artifact content (text/json up to 65536 chars; url/file store a URL or path). For json, an object or array is accepted as-is"}
                 },
-                "required": ["task_id", "name", "artifact_type", "value"]
+                "required": ["name", "artifact_type", "value"]
             })),
-            tool_def("list_artifacts", "List all artifacts attached to a task. Use this to see previously produced outputs (drafts, final versions, URLs, etc.).", json!({
+            tool_def("list_artifacts", "List all artifacts attached to a task (default: your in-progress task). Use this to see previously produced outputs (drafts, final versions, URLs, etc.).", json!({
                 "type": "object",
                 "properties": {
-                    "task_id": {"type": "string", "description": "Task ID"}
-                },
-                "required": ["task_id"]
+                    "task_id": {"type": "string", "description": "Task ID (default: your in-progress task)"}
+                }
             })),
             tool_def("delete_artifact", "Delete an artifact by ID. Only the creator can delete their own artifacts.", json!({
                 "type": "object",
@@ -435,10 +691,10 @@ fn handle_tools_call(ctx: &McpContext, params: &Value) -> Result<Value, Value> {
         "complete_task" => call_complete_task(ctx, &args),
         "block_task" => call_block_task(ctx, &args),
         "next_task" => call_next_task(ctx, &args),
-        "my_tasks" => call_my_tasks(ctx),
+        "my_tasks" => call_my_tasks(ctx, &args),
         "update_context" => call_update_context(ctx, &args),
         "post_comment" => call_post_comment(ctx, &args),
-        "heartbeat" => call_heartbeat(ctx),
+        "heartbeat" => call_heartbeat(ctx, &args),
         "list_agents" => call_list_agents(ctx),
         // v2 tools
         "get_agent_profile" => call_get_agent_profile(ctx, &args),
@@ -456,6 +712,9 @@ fn handle_tools_call(ctx: &McpContext, params: &Value) -> Result<Value, Value> {
         "create_artifact" => call_create_artifact(ctx, &args),
         "list_artifacts" => call_list_artifacts(ctx, &args),
         "delete_artifact" => call_delete_artifact(ctx, &args),
+        "add_dependencies" => call_add_dependencies(ctx, &args),
+        "remove_dependency" => call_remove_dependency(ctx, &args),
+        "get_dependency_graph" => call_get_dependency_graph(ctx, &args),
         _ => Err(format!("Unknown tool: {}", tool_name)),
     };
 
@@ -476,11 +735,457 @@ fn handle_tools_call(ctx: &McpContext, params: &Value) -> Result<Value, Value> {
     }
 }
 
+// --- Resources ---
+//
+// Read-only views an MCP client can attach to a prompt without a tool call:
+// `opengate://inbox`, `opengate://tasks/mine`, `opengate://task/{id}` and
+// `opengate://pulse/{project_id}`.
+
+const RESOURCE_SCHEME: &str = "opengate://";
+
+fn resource_def(uri: &str, name: &str, description: &str) -> Value {
+    json!({
+        "uri": uri,
+        "name": name,
+        "description": description,
+        "mimeType": "application/json"
+    })
+}
+
+fn handle_resources_list(ctx: &McpContext) -> Result<Value, Value> {
+    let mut resources = vec![
+        resource_def(
+            "opengate://inbox",
+            "Inbox",
+            "Your tasks, open questions and unread notifications",
+        ),
+        resource_def(
+            "opengate://tasks/mine",
+            "My tasks",
+            "Every task assigned to you",
+        ),
+    ];
+    for project in ctx
+        .storage
+        .list_projects(ctx.tenant_id.as_deref(), Some("active"))
+    {
+        resources.push(resource_def(
+            &format!("opengate://pulse/{}", project.id),
+            &format!("Pulse: {}", project.name),
+            "Active, blocked and in-review tasks with recent activity",
+        ));
+    }
+    Ok(json!({ "resources": resources }))
+}
+
+fn handle_resource_templates_list() -> Result<Value, Value> {
+    Ok(json!({
+        "resourceTemplates": [
+            {
+                "uriTemplate": "opengate://task/{id}",
+                "name": "Task",
+                "description": "A task with its activity, artifacts and dependencies",
+                "mimeType": "application/json"
+            },
+            {
+                "uriTemplate": "opengate://pulse/{project_id}",
+                "name": "Project pulse",
+                "description": "Active, blocked and in-review tasks with recent activity",
+                "mimeType": "application/json"
+            }
+        ]
+    }))
+}
+
+fn handle_resources_read(ctx: &McpContext, params: &Value) -> Result<Value, Value> {
+    let uri = params.get("uri").and_then(|v| v.as_str()).unwrap_or("");
+    match read_resource(ctx, uri) {
+        Ok(value) => Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": serde_json::to_string_pretty(&value).unwrap()
+            }]
+        })),
+        // -32002: resource not found
+        Err(e) => Err(json!({"code": -32002, "message": e, "data": {"uri": uri}})),
+    }
+}
+
+fn read_resource(ctx: &McpContext, uri: &str) -> Result<Value, String> {
+    let tenant = ctx.tenant_id.as_deref();
+    match parse_resource_uri(uri) {
+        Some(Resource::Inbox) => call_check_inbox(ctx),
+        Some(Resource::MyTasks) => {
+            let tasks = ctx.storage.get_tasks_for_assignee(tenant, &ctx.agent_id);
+            Ok(serde_json::to_value(&tasks).unwrap())
+        }
+        Some(Resource::Task(id)) => ctx
+            .storage
+            .get_task_full(tenant, id)
+            .map(|task| serde_json::to_value(&task).unwrap())
+            .ok_or_else(|| format!("Task not found: {}", id)),
+        Some(Resource::Pulse(project_id)) => {
+            if ctx.storage.get_project(tenant, project_id).is_none() {
+                return Err(format!("Project not found: {}", project_id));
+            }
+            let pulse = ctx
+                .storage
+                .get_pulse(tenant, project_id, Some(&ctx.agent_id));
+            Ok(serde_json::to_value(&pulse).unwrap())
+        }
+        None => Err(format!("Unknown resource: {}", uri)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Resource<'a> {
+    Inbox,
+    MyTasks,
+    Task(&'a str),
+    Pulse(&'a str),
+}
+
+fn parse_resource_uri(uri: &str) -> Option<Resource<'_>> {
+    let path = uri.strip_prefix(RESOURCE_SCHEME)?;
+    match path.split_once('/') {
+        None if path == "inbox" => Some(Resource::Inbox),
+        Some(("tasks", "mine")) => Some(Resource::MyTasks),
+        Some(("task", id)) if !id.is_empty() && !id.contains('/') => Some(Resource::Task(id)),
+        Some(("pulse", project_id)) if !project_id.is_empty() && !project_id.contains('/') => {
+            Some(Resource::Pulse(project_id))
+        }
+        _ => None,
+    }
+}
+
+// --- Prompts ---
+//
+// Ready-made prompts filled in from storage: `standup` (the caller's inbox),
+// `review_task` (a task's activity and artifacts) and `plan_subtasks` (a task and what
+// its project already has in flight).
+
+fn prompt_def(name: &str, description: &str, arguments: Value) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "arguments": arguments
+    })
+}
+
+fn handle_prompts_list() -> Result<Value, Value> {
+    let task_arg = json!([{"name": "task_id", "description": "Task ID", "required": true}]);
+    Ok(json!({
+        "prompts": [
+            prompt_def(
+                "standup",
+                "Summarize your inbox: what's in progress, blocked, waiting on review and asked of you",
+                json!([]),
+            ),
+            prompt_def(
+                "review_task",
+                "Review a task's activity and artifacts against what it set out to do",
+                task_arg.clone(),
+            ),
+            prompt_def(
+                "plan_subtasks",
+                "Break a task into subtasks, given what its project already has in flight",
+                task_arg,
+            ),
+        ]
+    }))
+}
+
+fn handle_prompts_get(ctx: &McpContext, params: &Value) -> Result<Value, Value> {
+    let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let args = params.get("arguments").cloned().unwrap_or(json!({}));
+    let (description, text) = match name {
+        "standup" => prompt_standup(ctx),
+        "review_task" => prompt_review_task(ctx, &args),
+        "plan_subtasks" => prompt_plan_subtasks(ctx, &args),
+        _ => Err(format!("Unknown prompt: {}", name)),
+    }
+    // -32602: invalid params
+    .map_err(|e| json!({"code": -32602, "message": e}))?;
+    Ok(json!({
+        "description": description,
+        "messages": [{
+            "role": "user",
+            "content": {"type": "text", "text": text}
+        }]
+    }))
+}
+
+fn prompt_task(ctx: &McpContext, args: &Value) -> Result<Task, String> {
+    let task_id = args
+        .get("task_id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'task_id'")?;
+    ctx.storage
+        .get_task_full(ctx.tenant_id.as_deref(), task_id)
+        .ok_or_else(|| format!("Task not found: {}", task_id))
+}
+
+fn pretty(value: &impl serde::Serialize) -> String {
+    serde_json::to_string_pretty(value).unwrap()
+}
+
+fn prompt_standup(ctx: &McpContext) -> Result<(String, String), String> {
+    let inbox = ctx
+        .storage
+        .get_agent_inbox(ctx.tenant_id.as_deref(), &ctx.agent_id);
+    let text = format!(
+        "You are {name}. Write a short standup from your OpenGate inbox below: what you're \
+         working on, what's blocked and why, what's waiting on review, and which questions \
+         or notifications need a reply. End with the one thing you'll do next.\n\n\
+         Inbox:\n```json\n{inbox}\n```",
+        name = ctx.agent_name,
+        inbox = pretty(&inbox),
+    );
+    Ok((format!("Standup for {}", ctx.agent_name), text))
+}
+
+fn prompt_review_task(ctx: &McpContext, args: &Value) -> Result<(String, String), String> {
+    let task = prompt_task(ctx, args)?;
+    let artifacts = ctx
+        .storage
+        .list_artifacts(ctx.tenant_id.as_deref(), &task.id);
+    let text = format!(
+        "Review the task \"{title}\" ({status}). Compare its description and context with \
+         what the activity log and artifacts show was done. Point out anything missing, \
+         unverified or inconsistent, then recommend approve or request changes with \
+         concrete reasons.\n\n\
+         Description:\n{description}\n\n\
+         Context:\n```json\n{context}\n```\n\n\
+         Output:\n```json\n{output}\n```\n\n\
+         Activity (oldest first):\n```json\n{activities}\n```\n\n\
+         Artifacts:\n```json\n{artifacts}\n```",
+        title = task.title,
+        status = task.status,
+        description = task.description.as_deref().unwrap_or("(none)"),
+        context = pretty(&task.context),
+        output = pretty(&task.output),
+        activities = pretty(&task.activities),
+        artifacts = pretty(&artifacts),
+    );
+    Ok((format!("Review: {}", task.title), text))
+}
+
+fn prompt_plan_subtasks(ctx: &McpContext, args: &Value) -> Result<(String, String), String> {
+    let task = prompt_task(ctx, args)?;
+    let pulse = ctx.storage.get_pulse(
+        ctx.tenant_id.as_deref(),
+        &task.project_id,
+        Some(&ctx.agent_id),
+    );
+    let text = format!(
+        "Plan subtasks for \"{title}\". Propose 3-8 subtasks, each small enough for one \
+         agent to finish, with a title, a one-line description, a priority \
+         (critical/high/medium/low) and which other subtasks it depends on. Don't duplicate \
+         work the project already has in flight.\n\n\
+         Task description:\n{description}\n\n\
+         Task context:\n```json\n{context}\n```\n\n\
+         Project pulse:\n```json\n{pulse}\n```",
+        title = task.title,
+        description = task.description.as_deref().unwrap_or("(none)"),
+        context = pretty(&task.context),
+        pulse = pretty(&pulse),
+    );
+    Ok((format!("Plan: {}", task.title), text))
+}
+
+// --- Dependency tools ---
+
+fn call_add_dependencies(ctx: &McpContext, args: &Value) -> Result<Value, String> {
+    let task_id = args
+        .get("task_id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'task_id'")?;
+    let depends_on: Vec<&str> = args
+        .get("depends_on")
+        .and_then(|v| v.as_array())
+        .ok_or("Missing 'depends_on'")?
+        .iter()
+        .map(|v| v.as_str().ok_or("'depends_on' must be task IDs"))
+        .collect::<Result<_, _>>()?;
+
+    let tenant = ctx.tenant_id.as_deref();
+    ctx.storage
+        .get_task(tenant, task_id)
+        .ok_or_else(|| "Task not found".to_string())?;
+    for dep_id in depends_on {
+        ctx.storage
+            .add_dependency(tenant, task_id, dep_id)
+            .map_err(|e| e.0)?;
+    }
+    let task = ctx.storage.get_task(tenant, task_id).unwrap();
+    Ok(serde_json::to_value(&task).unwrap())
+}
+
+fn call_remove_dependency(ctx: &McpContext, args: &Value) -> Result<Value, String> {
+    let task_id = args
+        .get("task_id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'task_id'")?;
+    let depends_on_id = args
+        .get("depends_on_id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'depends_on_id'")?;
+    if ctx
+        .storage
+        .remove_dependency(ctx.tenant_id.as_deref(), task_id, depends_on_id)
+    {
+        Ok(json!({"ok": true}))
+    } else {
+        Err("Dependency not found".to_string())
+    }
+}
+
+fn call_get_dependency_graph(ctx: &McpContext, args: &Value) -> Result<Value, String> {
+    let project_id = args
+        .get("project_id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'project_id'")?;
+    let tenant = ctx.tenant_id.as_deref();
+    ctx.storage
+        .get_project(tenant, project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+    let filters = TaskFilters {
+        project_id: Some(project_id.to_string()),
+        status: None,
+        priority: None,
+        assignee_id: None,
+        tag: None,
+    };
+    let tasks = ctx.storage.list_tasks(tenant, &filters);
+    // Ready means claimable as far as dependencies go: every one of them done
+    let ready: Vec<&str> = tasks
+        .iter()
+        .filter(|t| !matches!(t.status.as_str(), "done" | "cancelled"))
+        .filter(|t| ctx.storage.check_dependencies(tenant, t).is_ok())
+        .map(|t| t.id.as_str())
+        .collect();
+    Ok(dependency_graph(&tasks, &ready))
+}
+
+/// Nodes and edges for `tasks`, the `ready` ones, and the topological layers — each
+/// layer only waits on earlier ones. Edges to tasks outside `tasks` (other projects)
+/// are listed but don't hold back a layer.
+fn dependency_graph(tasks: &[Task], ready: &[&str]) -> Value {
+    let by_id: BTreeMap<&str, &Task> = tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+
+    let nodes: Vec<Value> = tasks
+        .iter()
+        .map(|t| {
+            json!({
+                "id": t.id,
+                "title": t.title,
+                "status": t.status,
+                "priority": t.priority,
+                "assignee_id": t.assignee_id,
+            })
+        })
+        .collect();
+    let edges: Vec<Value> = tasks
+        .iter()
+        .flat_map(|t| {
+            t.dependencies
+                .iter()
+                .map(move |dep| json!({"from": t.id, "to": dep}))
+        })
+        .collect();
+    // Kahn's algorithm over in-project edges
+    let mut waiting_on: BTreeMap<&str, usize> = tasks
+        .iter()
+        .map(|t| {
+            let n = t
+                .dependencies
+                .iter()
+                .filter(|d| by_id.contains_key(d.as_str()))
+                .count();
+            (t.id.as_str(), n)
+        })
+        .collect();
+    let mut layers: Vec<Vec<&str>> = Vec::new();
+    loop {
+        let layer: Vec<&str> = waiting_on
+            .iter()
+            .filter(|(_, n)| **n == 0)
+            .map(|(id, _)| *id)
+            .collect();
+        if layer.is_empty() {
+            break;
+        }
+        for id in &layer {
+            waiting_on.remove(id);
+        }
+        for t in tasks {
+            if let Some(n) = waiting_on.get_mut(t.id.as_str()) {
+                *n -= t
+                    .dependencies
+                    .iter()
+                    .filter(|d| layer.contains(&d.as_str()))
+                    .count();
+            }
+        }
+        layers.push(layer);
+    }
+
+    json!({
+        "nodes": nodes,
+        "edges": edges,
+        "ready": ready,
+        "layers": layers,
+    })
+}
+
 // --- Tool implementations ---
 
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+
+/// The page of `items` that `args.limit` and `args.cursor` ask for, under `key`, with
+/// the total and — when there's more — a `next_cursor` and a hint to use it, so a model
+/// knows it hasn't seen everything.
+fn paginate<T: serde::Serialize>(items: Vec<T>, args: &Value, key: &str) -> Result<Value, String> {
+    let limit = match args.get("limit").filter(|v| !v.is_null()) {
+        Some(v) => match v.as_u64() {
+            Some(n) if n > 0 => (n as usize).min(MAX_PAGE_SIZE),
+            _ => return Err("'limit' must be a positive integer".to_string()),
+        },
+        None => DEFAULT_PAGE_SIZE,
+    };
+    let offset = match args.get("cursor").and_then(|v| v.as_str()) {
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid cursor '{}'", cursor))?,
+        None => 0,
+    };
+    let total = items.len();
+    let page: Vec<T> = items.into_iter().skip(offset).take(limit).collect();
+    let end = offset + page.len();
+    let mut result = json!({
+        key: page,
+        "total": total,
+        "truncated": end < total,
+        "next_cursor": null,
+    });
+    if end < total {
+        result["next_cursor"] = json!(end.to_string());
+        result["hint"] = json!(format!(
+            "Showing {}-{} of {}; call again with cursor \"{}\" for more",
+            offset + 1,
+            end,
+            total,
+            end
+        ));
+    }
+    Ok(result)
+}
+
 fn call_list_projects(ctx: &McpContext, args: &Value) -> Result<Value, String> {
     let status = args.get("status").and_then(|v| v.as_str());
-    let projects = db_ops::list_projects(&ctx.conn, ctx.tenant_id.as_deref(), status);
+    let projects = ctx.storage.list_projects(ctx.tenant_id.as_deref(), status);
     Ok(serde_json::to_value(&projects).unwrap())
 }
 
@@ -489,7 +1194,9 @@ fn call_get_project(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .get("id")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'id'")?;
-    let project = db_ops::get_project_with_stats(&ctx.conn, ctx.tenant_id.as_deref(), id)
+    let project = ctx
+        .storage
+        .get_project_with_stats(ctx.tenant_id.as_deref(), id)
         .ok_or_else(|| "Project not found".to_string())?;
     Ok(serde_json::to_value(&project).unwrap())
 }
@@ -520,8 +1227,11 @@ fn call_create_project(ctx: &McpContext, args: &Value) -> Result<Value, String>
         cta_enabled: None,
         is_public: None,
     };
-    let project =
-        db_ops::create_project(&ctx.conn, ctx.tenant_id.as_deref(), &input, &ctx.agent_id);
+    quotas::check(&*ctx.storage, ctx.tenant_id.as_deref(), Quota::Projects)
+        .map_err(|e| e.to_string())?;
+    let project = ctx
+        .storage
+        .create_project(ctx.tenant_id.as_deref(), &input, &ctx.agent_id);
     Ok(serde_json::to_value(&project).unwrap())
 }
 
@@ -548,8 +1258,8 @@ fn call_list_tasks(ctx: &McpContext, args: &Value) -> Result<Value, String> {
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
     };
-    let tasks = db_ops::list_tasks(&ctx.conn, ctx.tenant_id.as_deref(), &filters);
-    Ok(serde_json::to_value(&tasks).unwrap())
+    let tasks = ctx.storage.list_tasks(ctx.tenant_id.as_deref(), &filters);
+    paginate(tasks, args, "tasks")
 }
 
 fn call_get_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
@@ -557,7 +1267,9 @@ fn call_get_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .get("id")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'id'")?;
-    let task = db_ops::get_task_full(&ctx.conn, ctx.tenant_id.as_deref(), id)
+    let task = ctx
+        .storage
+        .get_task_full(ctx.tenant_id.as_deref(), id)
         .ok_or_else(|| "Task not found".to_string())?;
     Ok(serde_json::to_value(&task).unwrap())
 }
@@ -572,7 +1284,11 @@ fn call_create_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .and_then(|v| v.as_str())
         .ok_or("Missing 'title'")?;
 
-    if db_ops::get_project(&ctx.conn, ctx.tenant_id.as_deref(), project_id).is_none() {
+    if ctx
+        .storage
+        .get_project(ctx.tenant_id.as_deref(), project_id)
+        .is_none()
+    {
         return Err("Project not found".to_string());
     }
 
@@ -608,16 +1324,20 @@ fn call_create_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         recurrence_rule: args.get("recurrence_rule").cloned(),
     };
 
-    let task = db_ops::create_task(
-        &ctx.conn,
-        ctx.tenant_id.as_deref(),
-        project_id,
-        &input,
-        &ctx.agent_id,
-    );
+    if let Some(ref rule) = input.recurrence_rule {
+        recurrence::validate_recurrence_rule(rule)
+            .map_err(|e| format!("Invalid recurrence_rule: {}", e))?;
+    }
 
-    db_ops::create_activity(
-        &ctx.conn,
+    let tenant = ctx.storage.project_owner(project_id);
+    quotas::check(&*ctx.storage, tenant.as_deref(), Quota::OpenTasks).map_err(|e| e.to_string())?;
+
+    let task = ctx
+        .storage
+        .create_task(ctx.tenant_id.as_deref(), project_id, &input, &ctx.agent_id);
+
+    ctx.storage.create_activity(
+        ctx.tenant_id.as_deref(),
         &task.id,
         "agent",
         &ctx.agent_id,
@@ -679,10 +1399,13 @@ fn call_update_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         recurrence_rule: args.get("recurrence_rule").cloned(),
     };
 
-    match db_ops::update_task(&ctx.conn, ctx.tenant_id.as_deref(), id, &input) {
+    match ctx
+        .storage
+        .update_task(ctx.tenant_id.as_deref(), id, &input)
+    {
         Ok(Some(task)) => Ok(serde_json::to_value(&task).unwrap()),
         Ok(None) => Err("Task not found".to_string()),
-        Err(e) => Err(e),
+        Err(e) => Err(e.0),
     }
 }
 
@@ -691,14 +1414,13 @@ fn call_claim_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .get("id")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'id'")?;
-    let mut task = db_ops::claim_task(
-        &ctx.conn,
-        ctx.tenant_id.as_deref(),
-        id,
-        &ctx.agent_id,
-        &ctx.agent_name,
-    )?;
-    task.activities = db_ops::list_activity(&ctx.conn, &task.id);
+    let mut task = ctx
+        .storage
+        .claim_task(ctx.tenant_id.as_deref(), id, &ctx.agent_id, &ctx.agent_name)
+        .map_err(|e| e.0)?;
+    task.activities = ctx
+        .storage
+        .list_activity(ctx.tenant_id.as_deref(), &task.id);
     Ok(serde_json::to_value(&task).unwrap())
 }
 
@@ -707,7 +1429,10 @@ fn call_release_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .get("id")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'id'")?;
-    let task = db_ops::release_task(&ctx.conn, ctx.tenant_id.as_deref(), id, &ctx.agent_id)?;
+    let task = ctx
+        .storage
+        .release_task(ctx.tenant_id.as_deref(), id, &ctx.agent_id)
+        .map_err(|e| e.0)?;
     Ok(serde_json::to_value(&task).unwrap())
 }
 
@@ -717,7 +1442,10 @@ fn call_complete_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .and_then(|v| v.as_str())
         .ok_or("Missing 'id'")?;
 
-    let task = db_ops::get_task(&ctx.conn, ctx.tenant_id.as_deref(), id).ok_or("Task not found")?;
+    let task = ctx
+        .storage
+        .get_task(ctx.tenant_id.as_deref(), id)
+        .ok_or("Task not found")?;
     let current_status = TaskStatus::from_str(&task.status).ok_or("Invalid task status")?;
 
     if current_status != TaskStatus::InProgress && current_status != TaskStatus::Review {
@@ -741,14 +1469,17 @@ fn call_complete_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         recurrence_rule: None,
     };
 
-    match db_ops::update_task(&ctx.conn, ctx.tenant_id.as_deref(), id, &input) {
+    match ctx
+        .storage
+        .update_task(ctx.tenant_id.as_deref(), id, &input)
+    {
         Ok(Some(task)) => {
             let summary = args
                 .get("summary")
                 .and_then(|v| v.as_str())
                 .unwrap_or("Task completed");
-            db_ops::create_activity(
-                &ctx.conn,
+            ctx.storage.create_activity(
+                ctx.tenant_id.as_deref(),
                 id,
                 "agent",
                 &ctx.agent_id,
@@ -760,11 +1491,12 @@ fn call_complete_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
                 },
             );
             // v2: inject output into downstream dependent tasks
-            db_ops::inject_upstream_outputs(&ctx.conn, ctx.tenant_id.as_deref(), &task);
+            ctx.storage
+                .inject_upstream_outputs(ctx.tenant_id.as_deref(), &task);
             Ok(serde_json::to_value(&task).unwrap())
         }
         Ok(None) => Err("Task not found".to_string()),
-        Err(e) => Err(e),
+        Err(e) => Err(e.0),
     }
 }
 
@@ -791,14 +1523,17 @@ fn call_block_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         recurrence_rule: None,
     };
 
-    match db_ops::update_task(&ctx.conn, ctx.tenant_id.as_deref(), id, &input) {
+    match ctx
+        .storage
+        .update_task(ctx.tenant_id.as_deref(), id, &input)
+    {
         Ok(Some(task)) => {
             let reason = args
                 .get("reason")
                 .and_then(|v| v.as_str())
                 .unwrap_or("Blocked");
-            db_ops::create_activity(
-                &ctx.conn,
+            ctx.storage.create_activity(
+                ctx.tenant_id.as_deref(),
                 id,
                 "agent",
                 &ctx.agent_id,
@@ -812,7 +1547,7 @@ fn call_block_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
             Ok(serde_json::to_value(&task).unwrap())
         }
         Ok(None) => Err("Task not found".to_string()),
-        Err(e) => Err(e),
+        Err(e) => Err(e.0),
     }
 }
 
@@ -827,18 +1562,33 @@ fn call_next_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         })
         .unwrap_or_default();
 
-    match db_ops::get_next_task(&ctx.conn, ctx.tenant_id.as_deref(), &skills) {
+    match ctx.storage.get_next_task(ctx.tenant_id.as_deref(), &skills) {
         Some(mut task) => {
-            task.activities = db_ops::list_activity(&ctx.conn, &task.id);
+            task.activities = ctx
+                .storage
+                .list_activity(ctx.tenant_id.as_deref(), &task.id);
             Ok(serde_json::to_value(&task).unwrap())
         }
         None => Err("No matching tasks available".to_string()),
     }
 }
 
-fn call_my_tasks(ctx: &McpContext) -> Result<Value, String> {
-    let tasks = db_ops::get_tasks_for_assignee(&ctx.conn, ctx.tenant_id.as_deref(), &ctx.agent_id);
-    Ok(serde_json::to_value(&tasks).unwrap())
+fn call_my_tasks(ctx: &McpContext, args: &Value) -> Result<Value, String> {
+    let statuses: Option<Vec<&str>> = args
+        .get("status")
+        .and_then(|v| v.as_str())
+        .map(|s| s.split(',').map(str::trim).collect());
+    let tasks: Vec<Task> = ctx
+        .storage
+        .get_tasks_for_assignee(ctx.tenant_id.as_deref(), &ctx.agent_id)
+        .into_iter()
+        .filter(|t| {
+            statuses
+                .as_ref()
+                .is_none_or(|s| s.contains(&t.status.as_str()))
+        })
+        .collect();
+    paginate(tasks, args, "tasks")
 }
 
 fn call_update_context(ctx: &McpContext, args: &Value) -> Result<Value, String> {
@@ -848,10 +1598,13 @@ fn call_update_context(ctx: &McpContext, args: &Value) -> Result<Value, String>
         .ok_or("Missing 'id'")?;
     let patch = args.get("context_patch").ok_or("Missing 'context_patch'")?;
 
-    match db_ops::merge_context(&ctx.conn, ctx.tenant_id.as_deref(), id, patch) {
+    match ctx
+        .storage
+        .merge_context(ctx.tenant_id.as_deref(), id, patch)
+    {
         Ok(Some(task)) => {
-            db_ops::create_activity(
-                &ctx.conn,
+            ctx.storage.create_activity(
+                ctx.tenant_id.as_deref(),
                 id,
                 "agent",
                 &ctx.agent_id,
@@ -865,7 +1618,7 @@ fn call_update_context(ctx: &McpContext, args: &Value) -> Result<Value, String>
             Ok(serde_json::to_value(&task).unwrap())
         }
         Ok(None) => Err("Task not found".to_string()),
-        Err(e) => Err(e),
+        Err(e) => Err(e.0),
     }
 }
 
@@ -879,7 +1632,9 @@ fn call_post_comment(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .and_then(|v| v.as_str())
         .ok_or("Missing 'content'")?;
 
-    let task = db_ops::get_task(&ctx.conn, ctx.tenant_id.as_deref(), task_id)
+    let task = ctx
+        .storage
+        .get_task(ctx.tenant_id.as_deref(), task_id)
         .ok_or_else(|| "Task not found".to_string())?;
 
     // Parse optional mentions
@@ -895,7 +1650,8 @@ fn call_post_comment(ctx: &McpContext, args: &Value) -> Result<Value, String> {
 
     // Validate mentions
     if !mentions.is_empty() {
-        db_ops::validate_mentions(&ctx.conn, ctx.tenant_id.as_deref(), &task, &mentions)
+        ctx.storage
+            .validate_mentions(ctx.tenant_id.as_deref(), &task, &mentions)
             .map_err(|bad_id| format!("Cannot mention agent: {}", bad_id))?;
     }
 
@@ -906,8 +1662,8 @@ fn call_post_comment(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         Some(serde_json::json!({ "mentions": mentions }))
     };
 
-    let activity = db_ops::create_activity(
-        &ctx.conn,
+    let activity = ctx.storage.create_activity(
+        ctx.tenant_id.as_deref(),
         task_id,
         "agent",
         &ctx.agent_id,
@@ -921,8 +1677,8 @@ fn call_post_comment(ctx: &McpContext, args: &Value) -> Result<Value, String> {
 
     // Emit mention events
     if !mentions.is_empty() {
-        db_ops::emit_mention_events(
-            &ctx.conn,
+        ctx.storage.emit_mention_events(
+            ctx.tenant_id.as_deref(),
             &task,
             &mentions,
             content,
@@ -935,13 +1691,40 @@ fn call_post_comment(ctx: &McpContext, args: &Value) -> Result<Value, String> {
     Ok(serde_json::to_value(&activity).unwrap())
 }
 
-fn call_heartbeat(ctx: &McpContext) -> Result<Value, String> {
-    db_ops::update_heartbeat(&ctx.conn, &ctx.agent_id);
+fn call_heartbeat(ctx: &McpContext, args: &Value) -> Result<Value, String> {
+    let input = HeartbeatRequest {
+        load: args.get("load").and_then(|v| v.as_f64()),
+        version: args
+            .get("version")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        status_message: args
+            .get("status_message")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+    };
+    if input.load.is_none() && input.version.is_none() && input.status_message.is_none() {
+        ctx.storage
+            .update_heartbeat(ctx.tenant_id.as_deref(), &ctx.agent_id);
+        return Ok(json!({"status": "ok"}));
+    }
+    if input
+        .status_message
+        .as_ref()
+        .is_some_and(|m| m.chars().count() > MAX_STATUS_MESSAGE_CHARS)
+    {
+        return Err(format!(
+            "status_message exceeds {} characters",
+            MAX_STATUS_MESSAGE_CHARS
+        ));
+    }
+    ctx.storage
+        .update_heartbeat_status(ctx.tenant_id.as_deref(), &ctx.agent_id, &input);
     Ok(json!({"status": "ok"}))
 }
 
 fn call_list_agents(ctx: &McpContext) -> Result<Value, String> {
-    let agents = db_ops::list_agents(&ctx.conn, ctx.tenant_id.as_deref());
+    let agents = ctx.storage.list_agents(ctx.tenant_id.as_deref());
     Ok(serde_json::to_value(&agents).unwrap())
 }
 
@@ -952,7 +1735,10 @@ fn call_get_agent_profile(ctx: &McpContext, args: &Value) -> Result<Value, Strin
         .get("agent_id")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'agent_id'")?;
-    let agent = db_ops::get_agent(&ctx.conn, agent_id).ok_or("Agent not found")?;
+    let agent = ctx
+        .storage
+        .get_agent(ctx.tenant_id.as_deref(), agent_id)
+        .ok_or("Agent not found")?;
     Ok(serde_json::to_value(&agent).unwrap())
 }
 
@@ -983,6 +1769,24 @@ fn call_update_agent_profile(ctx: &McpContext, args: &Value) -> Result<Value, St
             .filter_map(|v| v.as_str().map(|s| s.to_string()))
             .collect()
     });
+    let notification_preferences: Option<BTreeMap<String, NotificationPreference>> =
+        match args.get("notification_preferences") {
+            Some(v) => Some(
+                serde_json::from_value(v.clone())
+                    .map_err(|e| format!("Invalid notification_preferences: {}", e))?,
+            ),
+            None => None,
+        };
+    for pref in notification_preferences.iter().flat_map(|p| p.values()) {
+        pref.validate()?;
+    }
+    if args.get("role").is_some() {
+        return Err("Agents cannot change their own role".to_string());
+    }
+    let webhook_template = args.get("webhook_template").cloned();
+    if let Some(ref template) = webhook_template {
+        crate::handlers::webhooks::validate_template(template)?;
+    }
     let input = UpdateAgent {
         description: args
             .get("description")
@@ -995,6 +1799,7 @@ fn call_update_agent_profile(ctx: &McpContext, args: &Value) -> Result<Value, St
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
         webhook_events,
+        webhook_template,
         config: args.get("config").cloned(),
         model: args
             .get("model")
@@ -1013,14 +1818,18 @@ fn call_update_agent_profile(ctx: &McpContext, args: &Value) -> Result<Value, St
             .get("seniority")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
-        role: args
-            .get("role")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
+        role: None,
         stale_timeout: args.get("stale_timeout").and_then(|v| v.as_i64()),
         tags,
+        // Quotas are set by operators, not by the agent itself
+        daily_task_quota: None,
+        weekly_task_quota: None,
+        notification_preferences,
     };
-    let agent = db_ops::update_agent(&ctx.conn, &ctx.agent_id, &input).ok_or("Agent not found")?;
+    let agent = ctx
+        .storage
+        .update_agent(ctx.tenant_id.as_deref(), &ctx.agent_id, &input)
+        .ok_or("Agent not found")?;
     Ok(serde_json::to_value(&agent).unwrap())
 }
 
@@ -1033,7 +1842,10 @@ fn call_assign_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .get("agent_id")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'agent_id'")?;
-    let task = db_ops::assign_task(&ctx.conn, ctx.tenant_id.as_deref(), task_id, agent_id)?;
+    let task = ctx
+        .storage
+        .assign_task(ctx.tenant_id.as_deref(), task_id, agent_id)
+        .map_err(|e| e.0)?;
     Ok(serde_json::to_value(&task).unwrap())
 }
 
@@ -1047,14 +1859,16 @@ fn call_handoff_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .and_then(|v| v.as_str())
         .ok_or("Missing 'to_agent_id'")?;
     let summary = args.get("summary").and_then(|v| v.as_str());
-    let task = db_ops::handoff_task(
-        &ctx.conn,
-        ctx.tenant_id.as_deref(),
-        task_id,
-        &ctx.agent_id,
-        to_agent_id,
-        summary,
-    )?;
+    let task = ctx
+        .storage
+        .handoff_task(
+            ctx.tenant_id.as_deref(),
+            task_id,
+            &ctx.agent_id,
+            to_agent_id,
+            summary,
+        )
+        .map_err(|e| e.0)?;
     Ok(serde_json::to_value(&task).unwrap())
 }
 
@@ -1064,14 +1878,12 @@ fn call_approve_task(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .and_then(|v| v.as_str())
         .ok_or("Missing 'task_id'")?;
     let comment = args.get("comment").and_then(|v| v.as_str());
-    let task = db_ops::approve_task(
-        &ctx.conn,
-        ctx.tenant_id.as_deref(),
-        task_id,
-        &ctx.agent_id,
-        comment,
-    )?;
-    db_ops::inject_upstream_outputs(&ctx.conn, ctx.tenant_id.as_deref(), &task);
+    let task = ctx
+        .storage
+        .approve_task(ctx.tenant_id.as_deref(), task_id, &ctx.agent_id, comment)
+        .map_err(|e| e.0)?;
+    ctx.storage
+        .inject_upstream_outputs(ctx.tenant_id.as_deref(), &task);
     Ok(serde_json::to_value(&task).unwrap())
 }
 
@@ -1084,13 +1896,10 @@ fn call_request_changes(ctx: &McpContext, args: &Value) -> Result<Value, String>
         .get("comment")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'comment'")?;
-    let task = db_ops::request_changes(
-        &ctx.conn,
-        ctx.tenant_id.as_deref(),
-        task_id,
-        &ctx.agent_id,
-        comment,
-    )?;
+    let task = ctx
+        .storage
+        .request_changes(ctx.tenant_id.as_deref(), task_id, &ctx.agent_id, comment)
+        .map_err(|e| e.0)?;
     Ok(serde_json::to_value(&task).unwrap())
 }
 
@@ -1103,8 +1912,17 @@ fn call_get_knowledge(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .get("key")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'key'")?;
-    let entry =
-        db_ops::get_knowledge(&ctx.conn, project_id, key).ok_or("Knowledge entry not found")?;
+    let entry = ctx
+        .storage
+        .get_knowledge(ctx.tenant_id.as_deref(), project_id, key)
+        .ok_or("Knowledge entry not found")?;
+    ctx.storage.record_knowledge_read(
+        ctx.tenant_id.as_deref(),
+        project_id,
+        key,
+        "agent",
+        &ctx.agent_id,
+    );
     Ok(serde_json::to_value(&entry).unwrap())
 }
 
@@ -1126,9 +1944,10 @@ fn call_set_knowledge(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         .and_then(|v| v.as_str())
         .ok_or("Missing 'content'")?;
 
-    if db_ops::get_project(&ctx.conn, ctx.tenant_id.as_deref(), project_id).is_none() {
-        return Err("Project not found".to_string());
-    }
+    let project = ctx
+        .storage
+        .get_project(ctx.tenant_id.as_deref(), project_id)
+        .ok_or("Project not found")?;
 
     let tags: Option<Vec<String>> = args
         .get("tags")
@@ -1144,9 +1963,29 @@ fn call_set_knowledge(ctx: &McpContext, args: &Value) -> Result<Value, String> {
         metadata: args.get("metadata").cloned(),
         tags,
         category,
+        review_after: args
+            .get("review_after")
+            .and_then(|v| v.as_str())
+            .map(|r| freshness::normalize_review_after(r).map(Option::unwrap_or_default))
+            .transpose()?,
     };
-    let entry =
-        db_ops::upsert_knowledge(&ctx.conn, project_id, key, &input, "agent", &ctx.agent_id);
+    if project.enforce_knowledge_templates {
+        let missing = missing_template_sections(input.category.as_deref(), &input.content);
+        if !missing.is_empty() {
+            return Err(format!(
+                "Missing required sections for this category: {}",
+                missing.join(", ")
+            ));
+        }
+    }
+    let entry = ctx.storage.upsert_knowledge(
+        ctx.tenant_id.as_deref(),
+        project_id,
+        key,
+        &input,
+        "agent",
+        &ctx.agent_id,
+    );
     Ok(serde_json::to_value(&entry).unwrap())
 }
 
@@ -1164,7 +2003,23 @@ fn call_search_knowledge(ctx: &McpContext, args: &Value) -> Result<Value, String
 
     let category = args.get("category").and_then(|v| v.as_str());
 
-    let entries = db_ops::search_knowledge(&ctx.conn, project_id, query, &tag_list, category);
+    let mut entries = ctx.storage.search_knowledge(
+        ctx.tenant_id.as_deref(),
+        project_id,
+        query,
+        &tag_list,
+        category,
+    );
+    if args.get("scope").and_then(|v| v.as_str()) == Some("all") {
+        let shared = shared_knowledge_scope(ctx.tenant_id.as_deref());
+        entries.extend(ctx.storage.search_knowledge(
+            ctx.tenant_id.as_deref(),
+            &shared,
+            query,
+            &tag_list,
+            category,
+        ));
+    }
     Ok(serde_json::to_value(&entries).unwrap())
 }
 
@@ -1174,12 +2029,16 @@ fn call_list_knowledge(ctx: &McpContext, args: &Value) -> Result<Value, String>
         .and_then(|v| v.as_str())
         .ok_or("Missing 'project_id'")?;
     let prefix = args.get("prefix").and_then(|v| v.as_str());
-    let entries = db_ops::list_knowledge(&ctx.conn, project_id, prefix);
+    let entries = ctx
+        .storage
+        .list_knowledge(ctx.tenant_id.as_deref(), project_id, prefix);
     Ok(serde_json::to_value(&entries).unwrap())
 }
 
 fn call_check_inbox(ctx: &McpContext) -> Result<Value, String> {
-    let inbox = db_ops::get_agent_inbox(&ctx.conn, ctx.tenant_id.as_deref(), &ctx.agent_id);
+    let inbox = ctx
+        .storage
+        .get_agent_inbox(ctx.tenant_id.as_deref(), &ctx.agent_id);
     Ok(serde_json::to_value(&inbox).unwrap())
 }
 
@@ -1188,28 +2047,52 @@ fn call_get_notifications(ctx: &McpContext, args: &Value) -> Result<Value, Strin
         .get("unread_only")
         .and_then(|v| v.as_bool())
         .unwrap_or(true);
-    let notifications = db_ops::list_notifications(&ctx.conn, &ctx.agent_id, Some(unread_only));
-    Ok(serde_json::to_value(&notifications).unwrap())
+    let notifications =
+        ctx.storage
+            .list_notifications(ctx.tenant_id.as_deref(), &ctx.agent_id, Some(unread_only));
+    paginate(notifications, args, "notifications")
 }
 
 fn call_ack_notification(ctx: &McpContext, args: &Value) -> Result<Value, String> {
     if let Some(id) = args.get("id").and_then(|v| v.as_i64()) {
-        if db_ops::ack_notification(&ctx.conn, &ctx.agent_id, id) {
+        if ctx
+            .storage
+            .ack_notification(ctx.tenant_id.as_deref(), &ctx.agent_id, id)
+        {
             Ok(json!({"ok": true, "acknowledged": 1}))
         } else {
             Err("Notification not found".to_string())
         }
     } else {
-        let count = db_ops::ack_all_notifications(&ctx.conn, &ctx.agent_id);
+        let count = ctx
+            .storage
+            .ack_all_notifications(ctx.tenant_id.as_deref(), &ctx.agent_id);
         Ok(json!({"ok": true, "acknowledged": count}))
     }
 }
 
+/// `args.task_id`, or the one task the caller has in progress.
+fn artifact_task(ctx: &McpContext, args: &Value) -> Result<Task, String> {
+    if let Some(task_id) = args.get("task_id").and_then(|v| v.as_str()) {
+        return ctx
+            .storage
+            .get_task(ctx.tenant_id.as_deref(), task_id)
+            .ok_or_else(|| "Task not found".to_string());
+    }
+    let mut in_progress: Vec<Task> = ctx
+        .storage
+        .get_tasks_for_assignee(ctx.tenant_id.as_deref(), &ctx.agent_id)
+        .into_iter()
+        .filter(|t| t.status == "in_progress")
+        .collect();
+    match in_progress.len() {
+        1 => Ok(in_progress.remove(0)),
+        0 => Err("No task in progress — pass 'task_id'".to_string()),
+        n => Err(format!("{} tasks in progress — pass 'task_id'", n)),
+    }
+}
+
 fn call_create_artifact(ctx: &McpContext, args: &Value) -> Result<Value, String> {
-    let task_id = args
-        .get("task_id")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing 'task_id'")?;
     let name = args
         .get("name")
         .and_then(|v| v.as_str())
@@ -1218,10 +2101,11 @@ fn call_create_artifact(ctx: &McpContext, args: &Value) -> Result<Value, String>
         .get("artifact_type")
         .and_then(|v| v.as_str())
         .ok_or("Missing 'artifact_type'")?;
-    let value = args
-        .get("value")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing 'value'")?;
+    let value = match args.get("value") {
+        Some(Value::String(s)) => s.clone(),
+        Some(v @ (Value::Object(_) | Value::Array(_))) if artifact_type == "json" => v.to_string(),
+        _ => return Err("Missing 'value'".to_string()),
+    };
 
     if !VALID_ARTIFACT_TYPES.contains(&artifact_type) {
         return Err(format!(
@@ -1237,29 +2121,45 @@ fn call_create_artifact(ctx: &McpContext, args: &Value) -> Result<Value, String>
         );
     }
 
-    db_ops::get_task(&ctx.conn, ctx.tenant_id.as_deref(), task_id)
-        .ok_or_else(|| "Task not found".to_string())?;
+    let task = artifact_task(ctx, args)?;
 
     let input = CreateArtifact {
         name: name.to_string(),
         artifact_type: artifact_type.to_string(),
-        value: value.to_string(),
+        value,
     };
 
-    let artifact = db_ops::create_artifact(&ctx.conn, task_id, &input, "agent", &ctx.agent_id);
+    let artifact = ctx.storage.create_artifact(
+        ctx.tenant_id.as_deref(),
+        &task.id,
+        &input,
+        "agent",
+        &ctx.agent_id,
+    );
+    // Same event as the REST endpoint, so reviewers hear about it
+    let pending = ctx.storage.emit_event(
+        ctx.tenant_id.as_deref(),
+        "task.artifact_created",
+        Some(&task.id),
+        &task.project_id,
+        "agent",
+        &ctx.agent_id,
+        &json!({
+            "task_title": task.title,
+            "actor_name": ctx.agent_name,
+            "artifact_name": artifact.name,
+            "artifact_type": artifact.artifact_type,
+        }),
+    );
+    crate::handlers::webhooks::fire_notification_webhooks(ctx.storage.clone(), pending);
     Ok(serde_json::to_value(&artifact).unwrap())
 }
 
 fn call_list_artifacts(ctx: &McpContext, args: &Value) -> Result<Value, String> {
-    let task_id = args
-        .get("task_id")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing 'task_id'")?;
-
-    db_ops::get_task(&ctx.conn, ctx.tenant_id.as_deref(), task_id)
-        .ok_or_else(|| "Task not found".to_string())?;
-
-    let artifacts = db_ops::list_artifacts(&ctx.conn, task_id);
+    let task = artifact_task(ctx, args)?;
+    let artifacts = ctx
+        .storage
+        .list_artifacts(ctx.tenant_id.as_deref(), &task.id);
     Ok(serde_json::to_value(&artifacts).unwrap())
 }
 
@@ -1273,10 +2173,13 @@ fn call_delete_artifact(ctx: &McpContext, args: &Value) -> Result<Value, String>
         .and_then(|v| v.as_str())
         .ok_or("Missing 'artifact_id'")?;
 
-    db_ops::get_task(&ctx.conn, ctx.tenant_id.as_deref(), task_id)
+    ctx.storage
+        .get_task(ctx.tenant_id.as_deref(), task_id)
         .ok_or_else(|| "Task not found".to_string())?;
 
-    let artifact = db_ops::get_artifact(&ctx.conn, artifact_id)
+    let artifact = ctx
+        .storage
+        .get_artifact(ctx.tenant_id.as_deref(), artifact_id)
         .ok_or_else(|| "Artifact not found".to_string())?;
 
     if artifact.task_id != task_id {
@@ -1287,6 +2190,255 @@ fn call_delete_artifact(ctx: &McpContext, args: &Value) -> Result<Value, String>
         return Err("Only the artifact creator can delete artifacts".to_string());
     }
 
-    db_ops::delete_artifact(&ctx.conn, artifact_id);
+    ctx.storage
+        .delete_artifact(ctx.tenant_id.as_deref(), artifact_id);
     Ok(json!({"ok": true, "deleted": artifact_id}))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_session() -> (Session, String, String) {
+        let conn = db::init_db(":memory:");
+        let (_, alice) = crate::db_ops::create_agent(&conn, &CreateAgent::new("alice"));
+        let (_, bob) = crate::db_ops::create_agent(&conn, &CreateAgent::new("bob"));
+        let session = Session {
+            storage: Arc::new(SqliteBackend::new(Arc::new(Mutex::new(conn)))),
+            agent: None,
+            watch: Arc::new(Mutex::new(None)),
+        };
+        (session, alice, bob)
+    }
+
+    fn call(session: &mut Session, tool: &str, arguments: Value, meta: Value) -> Value {
+        let resp = session
+            .handle(&json!({
+                "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+                "params": {"name": tool, "arguments": arguments, "_meta": meta}
+            }))
+            .unwrap();
+        match resp["result"]["content"][0]["text"].as_str() {
+            Some(text) => serde_json::from_str(text).unwrap_or(json!(text)),
+            None => resp,
+        }
+    }
+
+    #[test]
+    fn attributes_calls_to_session_and_per_call_keys() {
+        let (mut session, alice, bob) = test_session();
+
+        // No agent yet: listing works, tools don't
+        let tools = session
+            .handle(&json!({"jsonrpc": "2.0", "id": 0, "method": "tools/list"}))
+            .unwrap();
+        assert!(tools["result"]["tools"].is_array());
+        let anonymous = call(&mut session, "list_projects", json!({}), json!({}));
+        assert_eq!(anonymous["error"]["code"], -32001);
+
+        session.handle(&json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": {"_meta": {"agent_key": alice}}
+        }));
+        let project = call(
+            &mut session,
+            "create_project",
+            json!({"name": "P"}),
+            json!({}),
+        );
+        let task = call(
+            &mut session,
+            "create_task",
+            json!({"project_id": project["id"], "title": "T"}),
+            json!({}),
+        );
+        let mut comment_author = |meta: Value| {
+            let args = json!({"task_id": task["id"], "content": "hi"});
+            call(&mut session, "post_comment", args, meta)["author_id"].clone()
+        };
+
+        let session_author = comment_author(json!({}));
+        assert!(session_author.is_string());
+        // A per-call key acts once, then the session agent is back
+        let bob_author = comment_author(json!({"agent_key": bob}));
+        assert_ne!(bob_author, session_author);
+        assert_eq!(comment_author(json!({})), session_author);
+
+        let bad = call(
+            &mut session,
+            "list_projects",
+            json!({}),
+            json!({"agent_key": "nope"}),
+        );
+        assert_eq!(bad["error"]["code"], -32001);
+    }
+
+    #[test]
+    fn fills_prompts_from_storage() {
+        let (mut session, alice, _) = test_session();
+        let meta = json!({"agent_key": alice});
+        let project = call(
+            &mut session,
+            "create_project",
+            json!({"name": "P"}),
+            meta.clone(),
+        );
+        let task = call(
+            &mut session,
+            "create_task",
+            json!({"project_id": project["id"], "title": "Ship prompts"}),
+            meta.clone(),
+        );
+        let get = |session: &mut Session, name: &str, arguments: Value| {
+            session
+                .handle(&json!({
+                    "jsonrpc": "2.0", "id": 1, "method": "prompts/get",
+                    "params": {"name": name, "arguments": arguments, "_meta": meta}
+                }))
+                .unwrap()
+        };
+
+        let review = get(&mut session, "review_task", json!({"task_id": task["id"]}));
+        assert_eq!(review["result"]["description"], "Review: Ship prompts");
+        let text = review["result"]["messages"][0]["content"]["text"]
+            .as_str()
+            .unwrap();
+        assert!(text.contains("Ship prompts"));
+        let standup = get(&mut session, "standup", json!({}));
+        assert_eq!(standup["result"]["description"], "Standup for alice");
+        let missing = get(&mut session, "plan_subtasks", json!({}));
+        assert_eq!(missing["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn attaches_artifacts_to_the_current_task() {
+        let (mut session, alice, _) = test_session();
+        let meta = json!({"agent_key": alice});
+        let attach = |session: &mut Session, args: Value| {
+            call(session, "create_artifact", args, meta.clone())
+        };
+        let draft = json!({"name": "Draft", "artifact_type": "json", "value": {"ok": true}});
+        assert_eq!(
+            attach(&mut session, draft.clone()),
+            json!("No task in progress — pass 'task_id'")
+        );
+
+        let project = call(
+            &mut session,
+            "create_project",
+            json!({"name": "P"}),
+            meta.clone(),
+        );
+        let task = call(
+            &mut session,
+            "create_task",
+            json!({"project_id": project["id"], "title": "T"}),
+            meta.clone(),
+        );
+        let todo = json!({"id": task["id"], "status": "todo"});
+        call(&mut session, "update_task", todo, meta.clone());
+        call(
+            &mut session,
+            "claim_task",
+            json!({"id": task["id"]}),
+            meta.clone(),
+        );
+
+        let artifact = attach(&mut session, draft);
+        assert_eq!(artifact["task_id"], task["id"]);
+        assert_eq!(artifact["value"], r#"{"ok":true}"#);
+        let listed = call(&mut session, "list_artifacts", json!({}), meta.clone());
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn paginates_with_cursor_and_hint() {
+        let items: Vec<u32> = (0..5).collect();
+        let first = paginate(items.clone(), &json!({"limit": 2}), "tasks").unwrap();
+        assert_eq!(first["tasks"], json!([0, 1]));
+        assert_eq!(first["total"], 5);
+        assert_eq!(first["truncated"], true);
+        assert_eq!(first["next_cursor"], "2");
+        assert!(first["hint"].as_str().unwrap().contains("cursor \"2\""));
+
+        let last = paginate(items.clone(), &json!({"limit": 2, "cursor": "4"}), "tasks").unwrap();
+        assert_eq!(last["tasks"], json!([4]));
+        assert_eq!(last["truncated"], false);
+        assert!(last["next_cursor"].is_null());
+        assert!(last.get("hint").is_none());
+
+        assert!(paginate(items.clone(), &json!({"cursor": "x"}), "tasks").is_err());
+        assert!(paginate(items, &json!({"limit": 0}), "tasks").is_err());
+    }
+
+    #[test]
+    fn builds_dependency_graph() {
+        let (mut session, alice, _) = test_session();
+        let meta = json!({"agent_key": alice});
+        let project = call(
+            &mut session,
+            "create_project",
+            json!({"name": "P"}),
+            meta.clone(),
+        );
+        let mut ids = Vec::new();
+        for title in ["design", "build", "ship"] {
+            let args = json!({"project_id": project["id"], "title": title});
+            ids.push(call(&mut session, "create_task", args, meta.clone())["id"].clone());
+        }
+        let depend = |session: &mut Session, task: usize, on: usize| {
+            let args = json!({"task_id": ids[task], "depends_on": [ids[on]]});
+            call(session, "add_dependencies", args, meta.clone())
+        };
+        depend(&mut session, 1, 0);
+        depend(&mut session, 2, 1);
+        // ship → build → design: design can't wait on ship
+        let cycle = depend(&mut session, 0, 2);
+        assert!(cycle.as_str().unwrap().contains("cycle"));
+
+        let graph_args = json!({"project_id": project["id"]});
+        let graph = call(
+            &mut session,
+            "get_dependency_graph",
+            graph_args.clone(),
+            meta.clone(),
+        );
+        assert_eq!(graph["nodes"].as_array().unwrap().len(), 3);
+        assert_eq!(graph["edges"].as_array().unwrap().len(), 2);
+        assert_eq!(graph["ready"], json!([ids[0]]));
+        assert_eq!(graph["layers"], json!([[ids[0]], [ids[1]], [ids[2]]]));
+
+        let args = json!({"task_id": ids[2], "depends_on_id": ids[1]});
+        call(&mut session, "remove_dependency", args, meta.clone());
+        let graph = call(
+            &mut session,
+            "get_dependency_graph",
+            graph_args,
+            meta.clone(),
+        );
+        assert_eq!(graph["edges"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parses_resource_uris() {
+        assert_eq!(
+            parse_resource_uri("opengate://inbox"),
+            Some(Resource::Inbox)
+        );
+        assert_eq!(
+            parse_resource_uri("opengate://tasks/mine"),
+            Some(Resource::MyTasks)
+        );
+        assert_eq!(
+            parse_resource_uri("opengate://task/abc"),
+            Some(Resource::Task("abc"))
+        );
+        assert_eq!(
+            parse_resource_uri("opengate://pulse/p1"),
+            Some(Resource::Pulse("p1"))
+        );
+        assert_eq!(parse_resource_uri("opengate://task/"), None);
+        assert_eq!(parse_resource_uri("opengate://pulse/p1/x"), None);
+        assert_eq!(parse_resource_uri("file:///etc/passwd"), None);
+    }
+}
@@ -0,0 +1,237 @@
+//! Prometheus metrics, served at `GET /metrics`.
+//!
+//! Request counts and latencies are recorded per route by [`track_requests`], and
+//! webhook outcomes by [`record_webhook`]. Task counts and event subscribers are read
+//! when the endpoint is scraped. The endpoint is open unless a token is set with
+//! [`set_token`], in which case scrapers send it as a bearer token.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::app::AppState;
+
+/// Upper bounds of the request latency histogram, in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static TOKEN: OnceLock<String> = OnceLock::new();
+
+/// Require `token` as a bearer token to scrape `/metrics`.
+pub fn set_token(token: String) {
+    let _ = TOKEN.set(token);
+}
+
+#[derive(Default)]
+struct RouteStats {
+    count: u64,
+    sum_seconds: f64,
+    /// Requests at or under each of [`LATENCY_BUCKETS`]
+    buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+/// (method, route, status) → stats
+type Requests = BTreeMap<(String, String, u16), RouteStats>;
+
+fn requests() -> &'static Mutex<Requests> {
+    static REQUESTS: OnceLock<Mutex<Requests>> = OnceLock::new();
+    REQUESTS.get_or_init(Default::default)
+}
+
+/// Webhook deliveries by kind (`task`, `notification`) and whether they got through.
+static WEBHOOKS: [[AtomicU64; 2]; 2] = [
+    [AtomicU64::new(0), AtomicU64::new(0)],
+    [AtomicU64::new(0), AtomicU64::new(0)],
+];
+const WEBHOOK_KINDS: [&str; 2] = ["task", "notification"];
+
+/// Count a finished webhook delivery, after its retries.
+pub fn record_webhook(kind: &str, delivered: bool) {
+    if let Some(k) = WEBHOOK_KINDS.iter().position(|known| *known == kind) {
+        WEBHOOKS[k][delivered as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Middleware timing every request under its route pattern (`/api/tasks/:id`), so ids
+/// don't multiply the series. Unmatched paths are counted as `unmatched`.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let seconds = started.elapsed().as_secs_f64();
+
+    let mut requests = requests().lock().unwrap();
+    let stats = requests
+        .entry((method, route, response.status().as_u16()))
+        .or_default();
+    stats.count += 1;
+    stats.sum_seconds += seconds;
+    for (bucket, bound) in stats.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+    response
+}
+
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(token) = TOKEN.get() {
+        let given = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+        if given != Some(token.as_str()) {
+            return (StatusCode::UNAUTHORIZED, "Metrics token required\n").into_response();
+        }
+    }
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&state),
+    )
+        .into_response()
+}
+
+fn render(state: &AppState) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP opengate_http_requests_total HTTP requests by route and status\n");
+    out.push_str("# TYPE opengate_http_requests_total counter\n");
+    let requests = requests().lock().unwrap();
+    for ((method, route, status), stats) in requests.iter() {
+        let _ = writeln!(
+            out,
+            "opengate_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+            method,
+            escape(route),
+            status,
+            stats.count
+        );
+    }
+
+    // Latency per route, summed over statuses
+    let mut by_route: BTreeMap<(String, String), RouteStats> = BTreeMap::new();
+    for ((method, route, _), stats) in requests.iter() {
+        let total = by_route.entry((method.clone(), route.clone())).or_default();
+        total.count += stats.count;
+        total.sum_seconds += stats.sum_seconds;
+        for (sum, n) in total.buckets.iter_mut().zip(stats.buckets) {
+            *sum += n;
+        }
+    }
+    drop(requests);
+    out.push_str("# HELP opengate_http_request_duration_seconds HTTP request latency by route\n");
+    out.push_str("# TYPE opengate_http_request_duration_seconds histogram\n");
+    for ((method, route), stats) in &by_route {
+        let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+        for (bound, n) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+            let _ = writeln!(
+                out,
+                "opengate_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, n
+            );
+        }
+        let _ = writeln!(
+            out,
+            "opengate_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, stats.count
+        );
+        let _ = writeln!(
+            out,
+            "opengate_http_request_duration_seconds_sum{{{}}} {}",
+            labels, stats.sum_seconds
+        );
+        let _ = writeln!(
+            out,
+            "opengate_http_request_duration_seconds_count{{{}}} {}",
+            labels, stats.count
+        );
+    }
+
+    // Queue depths: todo is the claimable queue, backlog the unplanned one, review the
+    // one waiting on reviewers
+    let stats = state
+        .storage
+        .get_stats(None, &opengate_models::StatsQuery::default());
+    out.push_str("# HELP opengate_tasks Tasks by status\n");
+    out.push_str("# TYPE opengate_tasks gauge\n");
+    let statuses: BTreeMap<_, _> = stats.tasks_by_status.iter().collect();
+    for (status, count) in statuses {
+        let _ = writeln!(
+            out,
+            "opengate_tasks{{status=\"{}\"}} {}",
+            escape(status),
+            count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP opengate_active_agents Agents seen recently\n# TYPE opengate_active_agents gauge\nopengate_active_agents {}",
+        stats.active_agents
+    );
+
+    out.push_str(
+        "# HELP opengate_webhook_deliveries_total Outbound webhook deliveries after retries\n",
+    );
+    out.push_str("# TYPE opengate_webhook_deliveries_total counter\n");
+    for (k, kind) in WEBHOOK_KINDS.iter().enumerate() {
+        for (delivered, result) in ["failed", "delivered"].iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "opengate_webhook_deliveries_total{{kind=\"{}\",result=\"{}\"}} {}",
+                kind,
+                result,
+                WEBHOOKS[k][delivered].load(Ordering::Relaxed)
+            );
+        }
+    }
+
+    let bus = state.event_bus.stats();
+    let mut transports: BTreeMap<&str, usize> = BTreeMap::from([("sse", 0), ("ws", 0)]);
+    for subscriber in &bus.subscribers {
+        let transport = subscriber.label.split(':').next().unwrap_or("");
+        *transports.entry(transport).or_default() += 1;
+    }
+    out.push_str("# HELP opengate_event_subscribers Connected WS and SSE clients\n");
+    out.push_str("# TYPE opengate_event_subscribers gauge\n");
+    for (transport, count) in transports {
+        let _ = writeln!(
+            out,
+            "opengate_event_subscribers{{transport=\"{}\"}} {}",
+            escape(transport),
+            count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP opengate_event_queue_max_depth Deepest client event queue\n# TYPE opengate_event_queue_max_depth gauge\nopengate_event_queue_max_depth {}",
+        bus.max_depth
+    );
+    let _ = writeln!(
+        out,
+        "# HELP opengate_events_dropped_total Events dropped from full client queues\n# TYPE opengate_events_dropped_total counter\nopengate_events_dropped_total {}",
+        bus.dropped_total
+    );
+    out
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,2779 @@
+//! The OpenAPI 3.1 document served at `GET /api/openapi.json`, for client generation.
+//! `GET /api/schema` is a compact view of the same document for agent discovery.
+//!
+//! The operations are the ones `app::ROUTES` lists, the table the router itself is built
+//! from; the endpoint catalog at the bottom of this file describes each. Catalog entries
+//! use a shorthand: each param, header and body field maps to a type descriptor such as
+//! `"string"`, `"int?"`, `"string[]?"` or `"agent|human"`. A trailing `?` (or "optional")
+//! marks the field optional, and the whole descriptor becomes its description. A
+//! capitalised type such as `"Task"` or `"Agent[]"` refers to one of the component
+//! schemas above the catalog. `response` describes the success body, or names its media
+//! type (`"text/calendar"`), and `status` overrides the default 200. Tests check that
+//! every route has a catalog entry and every entry a route.
+
+use std::sync::OnceLock;
+
+use serde_json::{json, Map, Value};
+
+pub const OPENAPI_VERSION: &str = "3.1.0";
+
+static DOCUMENT: OnceLock<Value> = OnceLock::new();
+
+/// The OpenAPI document, built on first use.
+pub fn document() -> &'static Value {
+    DOCUMENT.get_or_init(build)
+}
+
+fn build() -> Value {
+    let catalog = catalog();
+    let mut paths = Map::new();
+    for &(method, route) in crate::app::ROUTES {
+        let undocumented = json!({
+            "method": method.to_uppercase(),
+            "path": route_path(route),
+            "description": "Undocumented",
+            "auth": true
+        });
+        let mut entries: Vec<&Value> = catalog
+            .iter()
+            .filter(|entry| serves(method, route, entry))
+            .collect();
+        if entries.is_empty() {
+            entries.push(&undocumented);
+        }
+        for entry in entries {
+            let path = entry["path"].as_str().unwrap_or_default();
+            paths.entry(path).or_insert_with(|| json!({}))[method] = operation(entry);
+        }
+    }
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "OpenGate API",
+            "version": "2.0.0",
+            "description": "Agent-first task management system"
+        },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearerAuth": {"type": "http", "scheme": "bearer", "description": "An agent or human API key"}
+            },
+            "responses": {
+                "Error": {
+                    "description": "The request failed",
+                    "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}}
+                }
+            },
+            "schemas": schemas()
+        },
+        "x-opengate": {
+            "status_flow": {
+                "backlog": ["todo", "cancelled"],
+                "todo": ["in_progress", "blocked", "cancelled"],
+                "in_progress": ["review", "handoff", "done", "blocked", "cancelled"],
+                "handoff": ["in_progress"],
+                "review": ["done", "in_progress"],
+                "blocked": ["todo", "in_progress", "cancelled"],
+                "done": [],
+                "cancelled": []
+            },
+            "priorities": ["critical", "high", "medium", "low"],
+            "context_fields": {
+                "repo_url": "string — git repository URL",
+                "branch": "string — working branch",
+                "files": "string[] — relevant file paths",
+                "acceptance_criteria": "string[] — checkable items",
+                "dependencies": "string[] — task IDs this depends on",
+                "environment": "object — key-value pairs",
+                "notes": "string — freeform markdown",
+                "references": "string[] — URLs or doc links",
+                "upstream_outputs": "object — outputs from completed dependency tasks (auto-injected)"
+            },
+            "output_field": "JSON object for agent deliverables: PR URLs, file paths, build logs, artifacts"
+        }
+    })
+}
+
+/// `GET /api/schema`: the document's operations as a flat list in the catalog shorthand,
+/// with the status flow and context conventions agents need.
+pub fn schema_view() -> Value {
+    let doc = document();
+    let mut endpoints = Vec::new();
+    for (path, item) in doc["paths"].as_object().into_iter().flatten() {
+        for (method, op) in item.as_object().into_iter().flatten() {
+            let mut entry = json!({
+                "method": method.to_uppercase(),
+                "path": path,
+                "description": op["description"],
+            });
+            for (location, key) in [("query", "params"), ("header", "headers")] {
+                let fields: Map<String, Value> = op["parameters"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|p| p["in"] == location)
+                    .map(|p| {
+                        (
+                            p["name"].as_str().unwrap_or_default().to_string(),
+                            p["description"].clone(),
+                        )
+                    })
+                    .collect();
+                if !fields.is_empty() {
+                    entry[key] = Value::Object(fields);
+                }
+            }
+            let body = &op["requestBody"];
+            if !body.is_null() {
+                entry["body"] = match body["description"].as_str() {
+                    Some(descriptor) => json!(descriptor),
+                    None => shorthand(&body["content"]["application/json"]["schema"]),
+                };
+            }
+            let (status, success) = op["responses"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .find(|(status, _)| *status != "4XX")
+                .expect("every operation has a success response");
+            let content = &success["content"];
+            if !content["application/json"].is_null() {
+                entry["response"] = shorthand(&content["application/json"]["schema"]);
+            } else if let Some(media_type) = content.as_object().and_then(|c| c.keys().next()) {
+                entry["response"] = json!(media_type);
+            }
+            if status != "200" {
+                entry["status"] = json!(status.parse::<u64>().unwrap_or_default());
+            }
+            entry["auth"] = json!(op["security"].as_array().is_some_and(|s| !s.is_empty()));
+            endpoints.push(entry);
+        }
+    }
+    let conventions = &doc["x-opengate"];
+    json!({
+        "openapi": doc["openapi"],
+        "info": doc["info"],
+        "openapi_url": "/api/openapi.json",
+        "endpoints": endpoints,
+        "status_flow": conventions["status_flow"],
+        "priorities": conventions["priorities"],
+        "context_fields": conventions["context_fields"],
+        "output_field": conventions["output_field"],
+    })
+}
+
+/// One catalog entry as an OpenAPI operation.
+fn operation(entry: &Value) -> Value {
+    let method = entry["method"].as_str().unwrap_or_default();
+    let path = entry["path"].as_str().unwrap_or_default();
+    let mut parameters: Vec<Value> = path
+        .split('/')
+        .filter_map(|s| s.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            let mut param =
+                json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}});
+            if name == "key" {
+                param["description"] = json!("Knowledge key; may contain slashes");
+            }
+            param
+        })
+        .collect();
+    for (location, key) in [("query", "params"), ("header", "headers")] {
+        for (name, descriptor) in entry[key].as_object().into_iter().flatten() {
+            let descriptor = descriptor.as_str().unwrap_or_default();
+            let (mut schema, required) = descriptor_schema(descriptor);
+            if let Some(schema) = schema.as_object_mut() {
+                schema.remove("description");
+            }
+            parameters.push(json!({
+                "name": name,
+                "in": location,
+                "required": required,
+                "description": descriptor,
+                "schema": schema,
+            }));
+        }
+    }
+
+    let status = entry["status"].as_u64().unwrap_or(200);
+    let mut success = json!({"description": match status {
+        101 => "Switching to the WebSocket protocol",
+        201 => "Created",
+        204 => "No content",
+        _ => "Success",
+    }});
+    match &entry["response"] {
+        Value::Null => {}
+        // Non-JSON responses are named by their media type
+        Value::String(media_type) if media_type.contains('/') => {
+            success["content"] = json!({media_type.as_str(): {"schema": {"type": "string"}}});
+        }
+        response => {
+            success["content"] = json!({"application/json": {"schema": value_schema(response).0}});
+            if entry["params"]["format"]
+                .as_str()
+                .is_some_and(|f| f.contains("csv"))
+            {
+                success["content"]["text/csv"] = json!({"schema": {"type": "string"}});
+            }
+        }
+    }
+    let mut op = json!({
+        "operationId": operation_id(method, path),
+        "description": entry["description"],
+        "tags": [tag(path)],
+        "responses": {
+            status.to_string(): success,
+            "4XX": {"$ref": "#/components/responses/Error"}
+        },
+        "security": if entry["auth"] == true { json!([{"bearerAuth": []}]) } else { json!([]) },
+    });
+    if !parameters.is_empty() {
+        op["parameters"] = json!(parameters);
+    }
+    match &entry["body"] {
+        Value::Null => {}
+        // Free-form bodies (uploads, provider payloads) are described, not typed
+        Value::String(descriptor) => {
+            let mut content = json!({"application/json": {"schema": {}}});
+            if descriptor.contains("csv")
+                || entry["description"]
+                    .as_str()
+                    .is_some_and(|d| d.contains("CSV"))
+            {
+                content["text/csv"] = json!({"schema": {"type": "string"}});
+            }
+            op["requestBody"] = json!({
+                "description": descriptor,
+                "required": !descriptor.split(" (").next().unwrap_or_default().ends_with('?'),
+                "content": content,
+            });
+        }
+        body => {
+            let schema = value_schema(body).0;
+            op["requestBody"] = json!({
+                "required": schema.get("required").is_some(),
+                "content": {"application/json": {"schema": schema}},
+            });
+        }
+    }
+    op
+}
+
+/// `get_tasks_id_claim` for `GET /api/tasks/{id}/claim`.
+/// An axum route path (`/api/tasks/:id`, `/api/knowledge/*key`) in OpenAPI's syntax.
+fn route_path(route: &str) -> String {
+    route
+        .split('/')
+        .map(|segment| match segment.strip_prefix([':', '*']) {
+            Some(param) => format!("{{{}}}", param),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether the route `method route` serves the catalog `entry`. A wildcard route also
+/// serves the suffixed paths under it (`{key}/revert`).
+fn serves(method: &str, route: &str, entry: &Value) -> bool {
+    let path = route_path(route);
+    let entry_path = entry["path"].as_str().unwrap_or_default();
+    entry["method"]
+        .as_str()
+        .is_some_and(|m| m.eq_ignore_ascii_case(method))
+        && (entry_path == path
+            || (route.contains('*') && entry_path.starts_with(&format!("{}/", path))))
+}
+
+fn operation_id(method: &str, path: &str) -> String {
+    let mut id = method.to_lowercase();
+    for segment in path
+        .trim_start_matches("/api")
+        .split('/')
+        .filter(|s| !s.is_empty())
+    {
+        id.push('_');
+        id.extend(segment.trim_matches(['{', '}']).chars().map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c
+            } else {
+                '_'
+            }
+        }));
+    }
+    id
+}
+
+/// The first segment after `/api`, which groups operations in generated clients.
+fn tag(path: &str) -> &str {
+    path.trim_start_matches("/api")
+        .split('/')
+        .find(|s| !s.is_empty())
+        .unwrap_or("api")
+}
+
+/// A descriptor as a JSON Schema, and whether the field is required.
+fn descriptor_schema(descriptor: &str) -> (Value, bool) {
+    let head = descriptor.split(" (").next().unwrap_or_default().trim();
+    let required = !head.ends_with('?') && !descriptor.contains("optional");
+    let word = head
+        .trim_end_matches('?')
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    let (base, array) = match word.strip_suffix("[]") {
+        Some(base) => (base, true),
+        None => (word, false),
+    };
+    let mut schema = if base.contains('|') {
+        json!({"type": "string", "enum": base.split('|').collect::<Vec<_>>()})
+    } else if base.starts_with(|c: char| c.is_ascii_uppercase()) {
+        json!({"$ref": format!("#/components/schemas/{}", base)})
+    } else {
+        let json_type = match base {
+            "bool" | "boolean" => "boolean",
+            "int" | "integer" => "integer",
+            "float" | "number" => "number",
+            "object" => "object",
+            _ => "string",
+        };
+        json!({"type": json_type})
+    };
+    if array {
+        schema = json!({"type": "array", "items": schema});
+    }
+    schema["description"] = json!(descriptor);
+    (schema, required)
+}
+
+/// A catalog value (a descriptor, an object of them, or an array holding one) as a JSON
+/// Schema, and whether it is required.
+fn value_schema(value: &Value) -> (Value, bool) {
+    match value {
+        Value::String(descriptor) => descriptor_schema(descriptor),
+        Value::Array(items) => {
+            let items = items
+                .first()
+                .map(|i| value_schema(i).0)
+                .unwrap_or_else(|| json!({}));
+            (json!({"type": "array", "items": items}), true)
+        }
+        Value::Object(fields) => {
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            for (name, field) in fields {
+                let (schema, is_required) = value_schema(field);
+                if is_required {
+                    required.push(name.clone());
+                }
+                properties.insert(name.clone(), schema);
+            }
+            let mut schema = json!({"type": "object", "properties": properties});
+            if !required.is_empty() {
+                schema["required"] = json!(required);
+            }
+            (schema, true)
+        }
+        _ => (json!({}), false),
+    }
+}
+
+/// The inverse of `value_schema`: a JSON Schema back in catalog shorthand.
+fn shorthand(schema: &Value) -> Value {
+    if let Some(descriptor) = schema["description"].as_str() {
+        return json!(descriptor);
+    }
+    match schema["type"].as_str() {
+        Some("array") => json!([shorthand(&schema["items"])]),
+        Some("object") => Value::Object(
+            schema["properties"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, field)| (name.clone(), shorthand(field)))
+                .collect(),
+        ),
+        _ => json!({}),
+    }
+}
+
+// ===== Component schemas =====
+
+/// The response types as `components.schemas`, in catalog shorthand. Descriptors that
+/// name a schema (`"Task"`, `"Agent[]"`, `"HeartbeatStatus?"`) become `$ref`s to it.
+fn schemas() -> Map<String, Value> {
+    let mut schemas = Map::new();
+    schemas.insert(
+        "Error".to_string(),
+        json!({"type": "object", "properties": {"error": {"type": "string"}}, "required": ["error"]}),
+    );
+    for group in [
+        project_schemas(),
+        integration_schemas(),
+        task_schemas(),
+        question_schemas(),
+        agent_schemas(),
+        knowledge_schemas(),
+        system_schemas(),
+    ] {
+        for (name, fields) in group.as_object().into_iter().flatten() {
+            schemas.insert(name.clone(), value_schema(fields).0);
+        }
+    }
+    schemas
+}
+
+/// Projects, their pulse, burndown and schedule.
+fn project_schemas() -> Value {
+    json!({
+        "Project": {
+            "id": "string",
+            "name": "string",
+            "description": "string?",
+            "status": "string",
+            "repo_url": "string?",
+            "default_branch": "string?",
+            "join_mode": "string?",
+            "cta_enabled": "bool?",
+            "is_public": "bool?",
+            "enforce_knowledge_templates": "bool (Reject knowledge writes missing their category template's required sections)",
+            "created_at": "string",
+            "updated_at": "string"
+        },
+        "ProjectWithStats": {
+            "project": "Project",
+            "task_count": "int",
+            "tasks_by_status": "object"
+        },
+        "PulseResponse": {
+            "active_tasks": "PulseTask[]",
+            "blocked_tasks": "PulseTask[]",
+            "pending_review": "PulseTask[]",
+            "recently_completed": "PulseTask[]",
+            "unread_events": "int",
+            "agents": "PulseAgent[]",
+            "recent_knowledge_updates": "PulseKnowledge[]",
+            "knowledge_needing_review": "PulseKnowledge[] (Entries whose `review_after` deadline has passed, oldest deadline first)",
+            "most_read_knowledge": "PulseKnowledge[] (The project's most-read entries (top 5))",
+            "blocked_by_deps": "int (Number of tasks currently blocked by unmet dependencies)",
+            "slowest_review_queues": "ReviewLatency[] (Reviewers with pending reviews in this project, longest wait first (top 3))"
+        },
+        "PulseTask": {
+            "id": "string",
+            "title": "string",
+            "status": "string",
+            "priority": "string",
+            "assignee_name": "string?",
+            "reviewer_name": "string?",
+            "tags": "string[]",
+            "updated_at": "string"
+        },
+        "PulseAgent": {
+            "id": "string",
+            "name": "string",
+            "status": "string (online | idle | stale | offline)",
+            "seniority": "string",
+            "role": "string",
+            "current_task": "string?",
+            "last_seen_at": "string?",
+            "heartbeat_status": "HeartbeatStatus?"
+        },
+        "PulseKnowledge": {
+            "key": "string",
+            "title": "string",
+            "category": "string?",
+            "updated_at": "string",
+            "review_after": "string?",
+            "reads": "int?"
+        },
+        "Burndown": {
+            "project_id": "string",
+            "weight": "string",
+            "from": "string",
+            "to": "string",
+            "points": "BurndownPoint[]",
+            "iterations": "IterationVelocity[]",
+            "average_velocity": "float (Mean `completed` over full-length iterations (all of them when none is full))"
+        },
+        "BurndownPoint": {
+            "date": "string",
+            "remaining": "float (Open work at the end of the day; cancelled tasks leave the scope)"
+        },
+        "IterationVelocity": {
+            "start": "string",
+            "end": "string (Last day, inclusive; the final iteration may be cut short by `to`)",
+            "completed": "float (Work that was open at the start and done by the end)"
+        },
+        "ScheduledTaskEntry": {
+            "id": "string",
+            "title": "string",
+            "status": "string",
+            "priority": "string",
+            "scheduled_at": "string",
+            "assignee_id": "string?",
+            "recurrence_paused": "bool (True if the task belongs to a paused recurring series)"
+        }
+    })
+}
+
+/// Integrations, imports and inbound webhook triggers.
+fn integration_schemas() -> Value {
+    json!({
+        "SlackIntegration": {
+            "project_id": "string",
+            "webhook_url": "string?",
+            "channel": "string?",
+            "events": "string[]",
+            "dashboard_url": "string? (Base URL of the dashboard; task titles link to `<dashboard_url>/tasks/<id>`)",
+            "threaded": "bool (Posting with the bot token: one thread per task, replies can be ingested)",
+            "created_at": "string",
+            "updated_at": "string"
+        },
+        "GithubIntegration": {
+            "project_id": "string",
+            "has_token": "bool",
+            "comment_on_complete": "bool (Comment on a task's pull request when the task is completed)",
+            "created_at": "string",
+            "updated_at": "string"
+        },
+        "GitlabIntegration": {
+            "project_id": "string",
+            "has_token": "bool",
+            "gitlab_project": "string? (GitLab project path (`group/repo`) or numeric id issues are imported from)",
+            "comment_on_complete": "bool (Comment on a task's merge request when the task is completed)",
+            "created_at": "string",
+            "updated_at": "string"
+        },
+        "GitlabImport": {
+            "imported": "Task[] (Tasks created, one per newly imported issue)",
+            "skipped": "int (Open issues already imported earlier)"
+        },
+        "LinearIntegration": {
+            "project_id": "string",
+            "has_token": "bool",
+            "team": "string? (Key of the Linear team issues are imported from, e.g)",
+            "sync_on_complete": "bool (Complete a task's Linear issue when the task is completed)",
+            "created_at": "string",
+            "updated_at": "string"
+        },
+        "LinearImportResult": {
+            "dry_run": "bool",
+            "issues": "LinearIssueMapping[] (Issues that become tasks)",
+            "skipped": "string[] (Identifiers of issues imported before, left as they are)",
+            "warnings": "string[] (States and priorities with no obvious counterpart)"
+        },
+        "LinearIssueMapping": {
+            "identifier": "string",
+            "title": "string",
+            "linear_status": "string",
+            "status": "string",
+            "linear_priority": "string?",
+            "priority": "string",
+            "tags": "string[]",
+            "task_id": "string? (The created task; unset on a dry run)"
+        },
+        "JiraImportResult": {
+            "dry_run": "bool",
+            "issues": "JiraIssueMapping[] (Issues that become tasks)",
+            "dependencies": "JiraDependencyMapping[] (Dependencies between tasks of this or earlier imports, by issue key)",
+            "skipped": "string[] (Keys of issues imported before, left as they are)",
+            "warnings": "string[] (Statuses and priorities with no obvious counterpart, links left out)"
+        },
+        "JiraIssueMapping": {
+            "key": "string",
+            "title": "string",
+            "jira_status": "string",
+            "status": "string",
+            "jira_priority": "string?",
+            "priority": "string",
+            "tags": "string[]",
+            "task_id": "string? (The created task; unset on a dry run)"
+        },
+        "JiraDependencyMapping": {
+            "key": "string",
+            "depends_on": "string"
+        },
+        "TrelloImportResult": {
+            "dry_run": "bool",
+            "project": "Project? (The created project; unset on a dry run)",
+            "checklists": "string",
+            "lists": "TrelloListMapping[]",
+            "cards": "TrelloCardMapping[]",
+            "warnings": "string[]"
+        },
+        "TrelloListMapping": {
+            "name": "string",
+            "status": "string",
+            "cards": "int (Open cards on the list)"
+        },
+        "TrelloCardMapping": {
+            "card_id": "string",
+            "name": "string",
+            "list": "string",
+            "status": "string",
+            "tags": "string[]",
+            "checklist_items": "int",
+            "attachments": "int (Link attachments, each becoming a `url` artifact)",
+            "task_id": "string? (The created task; unset on a dry run)",
+            "subtask_ids": "string[] (Tasks created for checklist items with `checklists: subtasks`)"
+        },
+        "WebhookTrigger": {
+            "id": "string",
+            "project_id": "string",
+            "name": "string",
+            "action_type": "string",
+            "action_config": "object",
+            "enabled": "bool",
+            "ip_allowlist": "string[]",
+            "verification": "secret|github|gitlab|stripe (how inbound requests prove they're genuine)",
+            "dedup_key": "string?",
+            "dedup_window_hours": "int",
+            "rate_limit_per_minute": "int? (Most runs per minute; deliveries past it get 429 and are logged `rate_limited`)",
+            "created_at": "string",
+            "updated_at": "string"
+        },
+        "TriggerCreatedResponse": {
+            "trigger": "WebhookTrigger",
+            "secret": "string"
+        },
+        "TriggerValidation": {
+            "valid": "bool",
+            "errors": "string[]",
+            "preview": "object[] (The action_config as each run would see it: one entry, or one per `for_each` item)"
+        },
+        "WebhookTriggerLog": {
+            "id": "string",
+            "trigger_id": "string",
+            "received_at": "string",
+            "status": "string",
+            "payload": "object?",
+            "result": "object?",
+            "error": "string?"
+        }
+    })
+}
+
+/// Tasks and what hangs off them.
+fn task_schemas() -> Value {
+    json!({
+        "Task": {
+            "id": "string",
+            "project_id": "string",
+            "title": "string",
+            "description": "string?",
+            "status": "string",
+            "priority": "string",
+            "assignee_type": "string?",
+            "assignee_id": "string?",
+            "context": "object?",
+            "output": "object?",
+            "tags": "string[]",
+            "due_date": "string?",
+            "reviewer_type": "string?",
+            "reviewer_id": "string?",
+            "status_history": "StatusHistoryEntry[]",
+            "artifacts": "TaskArtifact[]",
+            "scheduled_at": "string? (ISO8601 datetime: task stays in backlog until this passes)",
+            "recurrence_rule": "object? (JSON recurrence rule: {frequency, interval, cron, ...} or {rrule: \"FREQ=...;BYDAY=...\"})",
+            "recurrence_parent_id": "string? (Points to the original recurring task (parent))",
+            "recurrence_paused": "bool (True if this is a recurrence parent whose series is paused (no new occurrences))",
+            "dependencies": "string[] (IDs of tasks this task depends on (loaded from task_dependencies))",
+            "has_open_questions": "bool (True if this task has blocking open questions)",
+            "started_review_at": "string? (ISO8601 timestamp: when the reviewer started reviewing this task)",
+            "created_by": "string",
+            "created_at": "string",
+            "updated_at": "string",
+            "activities": "TaskActivity[]?",
+            "knowledge": "KnowledgeSummary[]? (Knowledge entries linked to this task (loaded with the full task view))"
+        },
+        "AssignStrategy": {
+            "strategy": "string (capability | round_robin | least_loaded | highest_seniority | random | seniority | explicit)",
+            "capabilities": "string[]?",
+            "seniority": "string?",
+            "role": "string?",
+            "agent_id": "string?"
+        },
+        "StatusHistoryEntry": {
+            "status": "string",
+            "agent_id": "string?",
+            "agent_type": "string?",
+            "timestamp": "string"
+        },
+        "TaskActivity": {
+            "id": "string",
+            "task_id": "string",
+            "author_type": "string",
+            "author_id": "string",
+            "author_name": "string?",
+            "content": "string",
+            "activity_type": "string",
+            "metadata": "object?",
+            "created_at": "string"
+        },
+        "TaskArtifact": {
+            "id": "string",
+            "task_id": "string",
+            "name": "string",
+            "artifact_type": "string",
+            "value": "string",
+            "created_by_type": "string",
+            "created_by_id": "string",
+            "created_at": "string"
+        },
+        "TaskUsage": {
+            "id": "string",
+            "task_id": "string",
+            "agent_id": "string",
+            "input_tokens": "int",
+            "output_tokens": "int",
+            "cost_usd": "float?",
+            "cost_tier": "string? (The agent's `cost_tier` and `model` when it reported)",
+            "model": "string?",
+            "reported_at": "string"
+        },
+        "BatchResult": {
+            "succeeded": "string[]",
+            "failed": "BatchError[]"
+        },
+        "BatchError": {
+            "task_id": "string",
+            "error": "string"
+        },
+        "RecurrenceSkipResult": {
+            "skipped": "Task (The occurrence that was skipped (now cancelled))",
+            "next": "Task? (The occurrence scheduled in its place, if the series continues)"
+        }
+    })
+}
+
+/// Questions and replies.
+fn question_schemas() -> Value {
+    json!({
+        "TaskQuestion": {
+            "id": "string",
+            "task_id": "string? (null for project-level questions that no task owns)",
+            "project_id": "string",
+            "question": "string",
+            "question_type": "string",
+            "context": "string?",
+            "asked_by_type": "string",
+            "asked_by_id": "string",
+            "target_type": "string?",
+            "target_id": "string?",
+            "required_capability": "string?",
+            "status": "string",
+            "blocking": "bool",
+            "priority": "string",
+            "resolved_by_type": "string?",
+            "resolved_by_id": "string?",
+            "resolution": "string?",
+            "created_at": "string",
+            "resolved_at": "string?",
+            "targeted_at": "string? (When the current target was set)",
+            "routing_history": "QuestionRoute[] (Every target change, oldest first)",
+            "attachments": "QuestionAttachment[]? (Artifacts attached to the question itself (loaded when fetching a single question))"
+        },
+        "QuestionRoute": {
+            "target_type": "string?",
+            "target_id": "string?",
+            "reason": "string (asked | capability_match | assigned | target_offboarded | target_inactive | unrouted)",
+            "routed_at": "string"
+        },
+        "QuestionAttachment": {
+            "id": "string",
+            "question_id": "string",
+            "reply_id": "string? (Set when attached to a reply rather than the question)",
+            "name": "string",
+            "artifact_type": "string",
+            "value": "string",
+            "created_by_type": "string",
+            "created_by_id": "string",
+            "created_at": "string"
+        },
+        "QuestionReply": {
+            "id": "string",
+            "question_id": "string",
+            "author_type": "string",
+            "author_id": "string",
+            "author_name": "string?",
+            "body": "string",
+            "is_resolution": "bool",
+            "parent_reply_id": "string? (The reply this one answers; null for top-level replies)",
+            "created_at": "string",
+            "attachments": "QuestionAttachment[]?"
+        },
+        "ReplyThread": {
+            "id": "string",
+            "question_id": "string",
+            "author_type": "string",
+            "author_id": "string",
+            "author_name": "string?",
+            "body": "string",
+            "is_resolution": "bool",
+            "parent_reply_id": "string? (The reply this one answers; null for top-level replies)",
+            "created_at": "string",
+            "attachments": "QuestionAttachment[]?",
+            "replies": "ReplyThread[]"
+        }
+    })
+}
+
+/// Agents, their keys, inbox, notifications and push targets.
+fn agent_schemas() -> Value {
+    json!({
+        "Agent": {
+            "id": "string",
+            "name": "string",
+            "skills": "string[]",
+            "description": "string?",
+            "status": "string (available | busy | offline — routing status)",
+            "presence": "string (online | idle | stale | offline — graduated heartbeat freshness)",
+            "max_concurrent_tasks": "int",
+            "current_task_count": "int",
+            "review_task_count": "int (Count of tasks where this agent is reviewer and status = review)",
+            "webhook_url": "string?",
+            "webhook_events": "string[]? (Optional JSON array of event types to push via webhook)",
+            "webhook_template": "object?",
+            "config": "object?",
+            "model": "string?",
+            "provider": "string?",
+            "cost_tier": "string?",
+            "capabilities": "string[]",
+            "seniority": "string (senior | mid | junior)",
+            "role": "string (orchestrator | executor)",
+            "stale_timeout": "int (Minutes before agent is considered stale/offline (default: 30))",
+            "last_seen_at": "string?",
+            "created_at": "string",
+            "owner_id": "string? (Optional owner)",
+            "tags": "string[] (Free-form category tags)",
+            "daily_task_quota": "int? (Max tasks the agent may take on (claim or assignment) per UTC day)",
+            "weekly_task_quota": "int? (Max tasks the agent may take on per ISO week (Monday 00:00 UTC))",
+            "notification_preferences": "object (Notification preferences keyed by event type, with \"*\" as the fallback)",
+            "heartbeat_status": "HeartbeatStatus? (Last status reported with a heartbeat, if the agent sends one)"
+        },
+        "AgentCreated": {
+            "agent": "Agent",
+            "api_key": "string"
+        },
+        "HeartbeatStatus": {
+            "load": "float?",
+            "version": "string?",
+            "status_message": "string?",
+            "reported_at": "string"
+        },
+        "ApiKey": {
+            "id": "string",
+            "agent_id": "string",
+            "name": "string?",
+            "read_only": "bool (Only GET requests are allowed)",
+            "project_id": "string? (Only routes under this project (and its tasks) are allowed)",
+            "no_admin": "bool (Agent, key, trigger, integration, import and project management routes are refused)",
+            "created_at": "string",
+            "last_used_at": "string?"
+        },
+        "ApiKeyCreated": {
+            "id": "string",
+            "agent_id": "string",
+            "name": "string?",
+            "read_only": "bool (Only GET requests are allowed)",
+            "project_id": "string? (Only routes under this project (and its tasks) are allowed)",
+            "no_admin": "bool (Agent, key, trigger, integration, import and project management routes are refused)",
+            "created_at": "string",
+            "last_used_at": "string?",
+            "api_key": "string"
+        },
+        "AgentInbox": {
+            "summary": "string",
+            "todo_tasks": "InboxItem[]",
+            "in_progress_tasks": "InboxItem[]",
+            "review_tasks": "InboxItem[]",
+            "blocked_tasks": "InboxItem[]",
+            "handoff_tasks": "InboxItem[]",
+            "open_questions": "InboxItem[]",
+            "unread_notifications": "InboxItem[]",
+            "capacity": "InboxCapacity",
+            "heartbeat_status": "HeartbeatStatus?"
+        },
+        "InboxItem": {
+            "id": "string",
+            "item_type": "string",
+            "title": "string",
+            "status": "string?",
+            "priority": "string?",
+            "action": "string",
+            "action_hint": "string",
+            "project_id": "string?",
+            "tags": "string[]",
+            "updated_at": "string?",
+            "metadata": "object?"
+        },
+        "InboxCapacity": {
+            "max_concurrent_tasks": "int",
+            "current_active_tasks": "int",
+            "has_capacity": "bool (False when either the concurrency limit or a task quota is exhausted)",
+            "daily_task_quota": "int?",
+            "tasks_today": "int",
+            "weekly_task_quota": "int?",
+            "tasks_this_week": "int"
+        },
+        "Notification": {
+            "id": "int",
+            "agent_id": "string",
+            "event_id": "int?",
+            "event_type": "string",
+            "title": "string",
+            "body": "string?",
+            "read": "bool",
+            "webhook_status": "string? (Webhook delivery status: \"delivered\" | \"failed\" | null (not attempted))",
+            "task_id": "string?",
+            "wake": "bool (Whether the bridge should wake the agent for this notification)",
+            "email": "bool (Whether an email relay should forward this notification)",
+            "archived": "bool (Expired unread by the agent's `archive_after_hours` policy)",
+            "created_at": "string"
+        },
+        "PushTarget": {
+            "provider": "string (ntfy | pushover)",
+            "target": "string (ntfy topic URL, or Pushover user key)"
+        },
+        "WebhookTestResult": {
+            "url": "string",
+            "delivered": "bool (Whether the receiver answered 2xx)",
+            "status_code": "int?",
+            "latency_ms": "int",
+            "response_body": "string? (Start of the receiver's response body)",
+            "error": "string? (Transport error (connection refused, timeout, ...))"
+        },
+        "WebhookRedelivery": {
+            "notification_ids": "int[] (Notifications queued for another delivery attempt)"
+        },
+        "OffboardResult": {
+            "agent_id": "string",
+            "reassigned_tasks": "OffboardReassignment[]",
+            "released_tasks": "string[]",
+            "reassigned_reviews": "OffboardReassignment[]",
+            "cleared_reviews": "string[]",
+            "rerouted_questions": "OffboardReassignment[]",
+            "unrouted_questions": "string[]"
+        },
+        "OffboardReassignment": {
+            "id": "string (Task or question id)",
+            "agent_id": "string"
+        },
+        "SuggestedSkills": {
+            "agent_id": "string",
+            "completed_tasks": "int",
+            "skills": "SkillSuggestion[]",
+            "capabilities": "SkillSuggestion[]"
+        },
+        "SkillSuggestion": {
+            "name": "string",
+            "completed_tasks": "int (Completed tasks tagged with this skill)"
+        }
+    })
+}
+
+/// Knowledge entries, their history and usage.
+fn knowledge_schemas() -> Value {
+    json!({
+        "KnowledgeEntry": {
+            "id": "string",
+            "project_id": "string",
+            "key": "string",
+            "title": "string",
+            "content": "string",
+            "metadata": "object?",
+            "tags": "string[] (Tag list stored as JSON array in SQLite)",
+            "category": "string? (Optional category: architecture | pattern | gotcha | decision | reference | runbook)",
+            "created_by_type": "string",
+            "created_by_id": "string",
+            "version": "int (Starts at 1 and increases with every write (including reverts))",
+            "review_after": "string? (Review deadline (UTC); once it passes the entry is `stale` until reviewed)",
+            "stale": "bool (True when `review_after` has passed — treat the content as possibly outdated)",
+            "updated_at": "string",
+            "created_at": "string",
+            "attachments": "KnowledgeAttachment[]? (Files/URLs attached to the entry (loaded when fetching a single entry))"
+        },
+        "KnowledgeSummary": {
+            "key": "string",
+            "title": "string",
+            "category": "string?",
+            "tags": "string[]",
+            "updated_at": "string"
+        },
+        "KnowledgeVersion": {
+            "version": "int",
+            "title": "string",
+            "content": "string",
+            "metadata": "object?",
+            "tags": "string[]",
+            "category": "string?",
+            "author_type": "string",
+            "author_id": "string",
+            "created_at": "string"
+        },
+        "KnowledgeAttachment": {
+            "id": "string",
+            "knowledge_id": "string",
+            "name": "string",
+            "artifact_type": "string",
+            "value": "string",
+            "created_by_type": "string",
+            "created_by_id": "string",
+            "created_at": "string"
+        },
+        "KnowledgeTemplate": {
+            "category": "string",
+            "description": "string",
+            "required_sections": "string[]",
+            "content": "string (Markdown skeleton to start an entry from)"
+        },
+        "KnowledgeGraph": {
+            "nodes": "KnowledgeSummary[]",
+            "edges": "KnowledgeEdge[]",
+            "unresolved": "KnowledgeEdge[]"
+        },
+        "KnowledgeEdge": {
+            "from": "string",
+            "to": "string"
+        },
+        "KnowledgeUsageStats": {
+            "total_entries": "int",
+            "most_read": "KnowledgeUsage[]",
+            "never_read": "KnowledgeSummary[]"
+        },
+        "KnowledgeUsage": {
+            "key": "string",
+            "title": "string",
+            "category": "string?",
+            "reads": "int",
+            "readers": "int (Distinct agents/users that fetched the entry)",
+            "last_read_at": "string"
+        },
+        "KnowledgeImportResult": {
+            "created": "string[]",
+            "updated": "string[]",
+            "unchanged": "string[]",
+            "skipped": "string[] (Existing entries left untouched by `on_conflict=skip`)"
+        }
+    })
+}
+
+/// Stats, administration and tenants.
+fn system_schemas() -> Value {
+    json!({
+        "DashboardStats": {
+            "tasks_by_status": "object",
+            "total_tasks": "int",
+            "active_agents": "int",
+            "total_projects": "int",
+            "recent_activity": "TaskActivity[]"
+        },
+        "Timeseries": {
+            "metric": "string",
+            "interval": "string",
+            "from": "string",
+            "to": "string",
+            "points": "TimeseriesPoint[]"
+        },
+        "TimeseriesPoint": {
+            "date": "string (First day of the bucket)",
+            "count": "int"
+        },
+        "AgentWorkloadStats": {
+            "days": "int",
+            "agents": "AgentWorkload[]"
+        },
+        "AgentWorkload": {
+            "agent_id": "string",
+            "name": "string",
+            "role": "string",
+            "presence": "string",
+            "open": "int (Assigned but not started: backlog, todo, blocked or handoff)",
+            "in_progress": "int",
+            "in_review": "int (Assigned tasks waiting on a reviewer)",
+            "reviewing": "int (Tasks in review with this agent as the reviewer)",
+            "completed": "int (Assigned tasks that reached done in the window)",
+            "throughput_per_day": "float",
+            "last_seen_at": "string?",
+            "seconds_since_heartbeat": "int? (None when the agent has never been seen)"
+        },
+        "ReviewLatencyReport": {
+            "days": "int",
+            "reviewers": "ReviewLatency[]",
+            "projects": "ReviewLatency[]"
+        },
+        "ReviewLatency": {
+            "id": "string (Reviewer agent id, or project id)",
+            "name": "string?",
+            "completed": "int (Reviews that have left `review`)",
+            "pending": "int",
+            "avg_time_to_first_review_secs": "int?",
+            "avg_time_in_review_secs": "int? (Over completed reviews)",
+            "oldest_pending_secs": "int? (How long the longest-waiting pending review has been in `review`)"
+        },
+        "CostReport": {
+            "group_by": "string",
+            "interval": "string",
+            "from": "string",
+            "to": "string",
+            "total_cost_usd": "float",
+            "buckets": "CostBucket[] (By period, then by cost, highest first)"
+        },
+        "CostBucket": {
+            "period": "string (First day of the period)",
+            "group": "string?",
+            "tasks": "int (Distinct tasks the usage was reported against)",
+            "input_tokens": "int",
+            "output_tokens": "int",
+            "cost_usd": "float"
+        },
+        "BlockedTimeReport": {
+            "projects": "ProjectBlockedTime[]",
+            "top_blockers": "Blocker[]",
+            "most_blocked": "TaskBlockedTime[]"
+        },
+        "ProjectBlockedTime": {
+            "project_id": "string",
+            "name": "string",
+            "tasks": "int (Tasks with any blocked or dependency-wait time)",
+            "blocked_secs": "int",
+            "blocked_by_deps_secs": "int"
+        },
+        "TaskBlockedTime": {
+            "task_id": "string",
+            "title": "string",
+            "project_id": "string",
+            "status": "string",
+            "blocked_secs": "int",
+            "blocked_by_deps_secs": "int"
+        },
+        "Blocker": {
+            "task_id": "string",
+            "title": "string",
+            "project_id": "string",
+            "status": "string",
+            "dependents": "int (Tasks that waited on it)",
+            "dependent_wait_secs": "int"
+        },
+        "EventReplayResult": {
+            "events_replayed": "int",
+            "notifications_created": "int (Notifications routing produces now that didn't exist before)",
+            "notifications_redelivered": "int (Existing unread notifications whose wake-up is sent again)",
+            "webhooks_fired": "bool"
+        },
+        "RuntimeSettings": {
+            "enforce_dependencies": "bool",
+            "auto_assign_reviewers": "bool",
+            "stale_release_minutes": "int (Minutes without a heartbeat before an agent's tasks are released)"
+        },
+        "BusStats": {
+            "subscribers": "SubscriberStats[]",
+            "max_depth": "int",
+            "dropped_total": "int (Events dropped from full queues since startup)",
+            "disconnected_total": "int (Subscribers disconnected for being slow since startup)"
+        },
+        "SubscriberStats": {
+            "id": "int",
+            "label": "string",
+            "depth": "int",
+            "capacity": "int",
+            "policy": "drop-oldest|disconnect",
+            "dropped": "int"
+        },
+        "QueryStats": {
+            "slow_query_threshold_ms": "int",
+            "statements": "StatementStats[] (Most total time first)",
+            "slow_queries": "SlowQuery[] (Newest first)"
+        },
+        "StatementStats": {
+            "statement": "string",
+            "count": "int",
+            "total_ms": "float",
+            "p50_ms": "float (Over the most recent runs)",
+            "p95_ms": "float",
+            "max_ms": "float"
+        },
+        "SlowQuery": {
+            "statement": "string",
+            "duration_ms": "float",
+            "at": "string"
+        },
+        "Tenant": {
+            "id": "string",
+            "name": "string",
+            "status": "string (active | suspended — a suspended tenant's keys and setup tokens are refused)",
+            "created_at": "string",
+            "updated_at": "string"
+        },
+        "TenantSummary": {
+            "id": "string",
+            "name": "string",
+            "status": "string (active | suspended — a suspended tenant's keys and setup tokens are refused)",
+            "created_at": "string",
+            "updated_at": "string",
+            "usage": "TenantUsage"
+        },
+        "TenantUsage": {
+            "tenant_id": "string",
+            "projects": "QuotaUsage (Projects that aren't archived)",
+            "open_tasks": "QuotaUsage (Tasks that aren't done or cancelled)",
+            "agents": "QuotaUsage",
+            "events_today": "QuotaUsage",
+            "webhook_deliveries_today": "QuotaUsage"
+        },
+        "QuotaUsage": {
+            "used": "int",
+            "limit": "int? (None = unlimited)"
+        },
+        "TenantSetupToken": {
+            "tenant_id": "string",
+            "setup_token": "string"
+        }
+    })
+}
+
+// ===== Endpoint catalog =====
+
+fn catalog() -> Vec<Value> {
+    [
+        projects(),
+        integrations(),
+        tasks(),
+        questions(),
+        agents(),
+        knowledge(),
+        system(),
+    ]
+    .into_iter()
+    .flat_map(|group| match group {
+        Value::Array(entries) => entries,
+        _ => Vec::new(),
+    })
+    .collect()
+}
+
+/// Identity and projects.
+fn projects() -> Value {
+    json!([
+        {
+            "method": "GET",
+            "path": "/api/auth/me",
+            "description": "Get current identity info",
+            "response": {"type": "agent|human|anonymous", "id": "string?", "name": "string? (agents only)"},
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects",
+            "description": "List all projects",
+            "params": {"status": "string (optional, 'active' or 'archived')"},
+            "response": "Project[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects",
+            "description": "Create a new project",
+            "body": {"name": "string", "description": "string?", "repo_url": "string?", "default_branch": "string?"},
+            "response": "Project",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}",
+            "description": "Get project details with task stats (includes repo_url and default_branch)",
+            "response": "ProjectWithStats",
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/projects/{id}",
+            "description": "Update project",
+            "body": {"name": "string?", "description": "string?", "status": "string?", "repo_url": "string?", "default_branch": "string?", "enforce_knowledge_templates": "bool? (require category template sections on knowledge writes)"},
+            "response": "Project",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}",
+            "description": "Archive project",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/schedule.ics",
+            "description": "iCalendar feed of scheduled tasks, due dates and recurring series (calendar apps may pass ?token=<api_key>)",
+            "params": {"token": "string? (API key, alternative to the Authorization header)"},
+            "response": "text/calendar",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/burndown",
+            "description": "Open tasks at the end of each day and completions per fixed-length iteration, replayed from status history. weight=estimate sums each task's numeric context.estimate (1 when unset).",
+            "params": {"from": "string? (YYYY-MM-DD, default 29 days before to)", "to": "string? (YYYY-MM-DD, default today UTC)", "weight": "string? (count or estimate, default count)", "iteration_days": "int? (default 7)"},
+            "response": "Burndown",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/tasks",
+            "description": "List tasks in a project",
+            "params": {"status": "string?", "priority": "string?", "assignee_id": "string?", "tag": "string?", "format": "string? (json or csv)"},
+            "response": "Task[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/tasks",
+            "description": "Create a task in a project",
+            "body": {"title": "string", "description": "string?", "priority": "string?", "tags": "string[]?", "context": "object?", "output": "object?", "due_date": "string?"},
+            "response": "Task",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/pulse",
+            "description": "Project pulse: active, blocked, in-review and recently completed tasks, agents, unread events for the calling agent, recent, overdue-for-review and most-read knowledge, tasks blocked by dependencies and the slowest review queues",
+            "response": "PulseResponse",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/schedule",
+            "description": "Scheduled tasks in a window, soonest first (recurring occurrences included; paused series are flagged)",
+            "params": {"from": "string? (ISO 8601)", "to": "string? (ISO 8601)"},
+            "response": "ScheduledTaskEntry[]",
+            "auth": true
+        }
+    ])
+}
+
+/// Integrations, imports and inbound webhook triggers.
+fn integrations() -> Value {
+    json!([
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/integrations/slack",
+            "description": "Project's Slack integration (bot_token and signing_secret are never returned)",
+            "response": "SlackIntegration",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/projects/{id}/integrations/slack",
+            "description": "Post selected project events to Slack. With bot_token + channel each task gets its own thread, and replies can be ingested by a slack_thread_reply trigger.",
+            "body": {"webhook_url": "string?", "bot_token": "string?", "channel": "string?", "signing_secret": "string? (verifies inbound Slack events)", "events": "string[]? (default: task.assigned, task.blocked, task.review_requested, task.completed; \"*\" = all)", "dashboard_url": "string? (task titles link to <dashboard_url>/tasks/<id>)"},
+            "response": "SlackIntegration",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}/integrations/slack",
+            "description": "Stop posting project events to Slack",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/integrations/github",
+            "description": "Project's GitHub integration (the token is never returned; has_token says whether one is set)",
+            "response": "GithubIntegration",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/projects/{id}/integrations/github",
+            "description": "Comment on pull requests when their tasks complete. Pull requests in output.pr_url are attached to tasks with a repo as url artifacts with or without this. 409 while GitLab is configured.",
+            "body": {"token": "string? (write access to the repo's pull requests)", "comment_on_complete": "bool? (default true)"},
+            "response": "GithubIntegration",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}/integrations/github",
+            "description": "Stop commenting on the project's pull requests",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/integrations/gitlab",
+            "description": "Project's GitLab integration (the token is never returned; has_token says whether one is set)",
+            "response": "GitlabIntegration",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/projects/{id}/integrations/gitlab",
+            "description": "Use GitLab instead of GitHub for the project: note on merge requests when their tasks complete and import issues. Merge requests in output.mr_url are attached to tasks with a repo as url artifacts with or without this. 409 while GitHub is configured.",
+            "body": {"token": "string? (api scope on the GitLab project)", "gitlab_project": "string? (path like group/repo, or numeric id; needed for import)", "comment_on_complete": "bool? (default true)"},
+            "response": "GitlabIntegration",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}/integrations/gitlab",
+            "description": "Stop noting on the project's merge requests",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/integrations/gitlab/import",
+            "description": "Create a task for each open issue of the GitLab project (labels become tags, context.external_ref is gitlab:<project>#<iid>). Issues imported before are skipped. Needs a token and gitlab_project.",
+            "response": "GitlabImport (200 when nothing new was imported)",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/integrations/linear",
+            "description": "Project's Linear integration (the token is never returned; has_token says whether one is set)",
+            "response": "LinearIntegration",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/projects/{id}/integrations/linear",
+            "description": "Linear API access for importing a team's issues and, with sync_on_complete, completing the Linear issue of each imported task when the task is completed (the summary is posted as a comment)",
+            "body": {"token": "string? (Linear API key)", "team": "string? (team key, e.g. ENG; needed for API import)", "sync_on_complete": "bool? (default true)"},
+            "response": "LinearIntegration",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}/integrations/linear",
+            "description": "Stop syncing completions to Linear",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/import/jira",
+            "description": "Import a Jira export (JSON search response with Content-Type: application/json, else CSV): issues become tasks with mapped status, priority and labels as tags; Blocks and Dependency links become dependencies. Issues imported before (context.external_ref jira:<key>) are skipped. Returns the mapping, warnings for anything left out, and 201 once created",
+            "params": {"dry_run": "bool? (report the mapping without creating anything)"},
+            "body": "{\"issues\": [{\"key\": \"string\", \"fields\": {\"summary\": \"string\", \"status\": {\"name\": \"string\"}, \"priority\": {\"name\": \"string\"}, \"labels\": \"string[]\", \"issuelinks\": \"array\"}}]} | csv",
+            "response": "JiraImportResult (200 on a dry run)",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/import/linear",
+            "description": "Import Linear issues from a CSV export, or with an empty body from the API using the project's Linear integration (token and team). States and priorities are mapped, labels become tags, context.external_ref is linear:<identifier>. Issues imported before are skipped. Returns the mapping and warnings, and 201 once created",
+            "params": {"dry_run": "bool? (report the mapping without creating anything)"},
+            "body": "csv?",
+            "response": "LinearImportResult (200 on a dry run)",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/triggers",
+            "description": "Inbound webhook triggers of a project (secrets are never returned)",
+            "response": "WebhookTrigger[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/triggers",
+            "description": "Create an inbound webhook trigger; the secret is returned once",
+            "body": {"name": "string", "action_type": "create_task|update_task|add_activity|resolve_question|slack_thread_reply|github_pull_request|gitlab_merge_request|email_task|commit_references", "action_config": "object (templates like {{payload.field}})", "verification": "string? (secret | github | gitlab | stripe, default secret)", "signing_secret": "string? (the provider's webhook secret)", "dedup_key": "string? (payload path identifying a delivery)", "dedup_window_hours": "integer? (default 24)", "rate_limit_per_minute": "integer?"},
+            "response": "TriggerCreatedResponse",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/triggers/validate",
+            "description": "Render an action_config against a sample payload and list what doesn't fit, without running it",
+            "body": {"action_type": "string", "action_config": "object", "sample_payload": "object?"},
+            "response": "TriggerValidation",
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/projects/{id}/triggers/{tid}",
+            "description": "Update a trigger in place",
+            "body": {"name": "string?", "action_type": "string?", "action_config": "object?", "enabled": "bool?", "ip_allowlist": "string[]?", "verification": "string?", "signing_secret": "string?", "dedup_key": "string? (empty turns dedup off)", "dedup_window_hours": "integer?", "rate_limit_per_minute": "integer? (0 turns the limit off)"},
+            "response": "WebhookTrigger",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}/triggers/{tid}",
+            "description": "Delete a trigger",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/triggers/{tid}/logs",
+            "description": "Recent executions of a trigger with payload, result and error",
+            "response": "WebhookTriggerLog[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/import/trello",
+            "description": "Create a project from a Trello board JSON export: open lists map to statuses (guessed from list names unless status_map says otherwise), cards to tasks with labels as tags, link attachments to url artifacts, checklists to text artifacts or subtasks. Returns the mapping, and 201 with the project once created",
+            "params": {"dry_run": "bool? (report the mapping without creating anything)"},
+            "body": "<board export> | {\"board\": \"<board export>\", \"name\": \"string?\", \"status_map\": \"object? (list name -> status)\", \"checklists\": \"string? (artifacts | subtasks, default artifacts)\"}",
+            "response": "TrelloImportResult (200 on a dry run)",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/webhooks/trigger/{trigger_id}",
+            "description": "Fire a trigger. Verified by x-webhook-secret, or the provider signature its verification names",
+            "headers": {"X-Webhook-Secret": "string? (for verification secret)"},
+            "body": "object (the provider's webhook payload)",
+            "response": "object (the action's result; {deduplicated, dedup_value} for a repeated delivery)",
+            "auth": false
+        }
+    ])
+}
+
+/// Tasks: lifecycle, dependencies, activity and artifacts.
+fn tasks() -> Value {
+    json!([
+        {
+            "method": "GET",
+            "path": "/api/tasks",
+            "description": "List all tasks globally",
+            "params": {"project_id": "string?", "status": "string?", "priority": "string?", "assignee_id": "string?", "tag": "string?", "format": "string? (json or csv)"},
+            "response": "Task[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/mine",
+            "description": "List all tasks assigned to the authenticated agent/user",
+            "response": "Task[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/next",
+            "description": "Get highest-priority unclaimed task matching skills",
+            "params": {"skills": "string? (comma-separated)"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}",
+            "description": "Get task with full context and output",
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/tasks/{id}",
+            "description": "Update task fields (validates status transitions and dependencies)",
+            "body": {"title": "string?", "description": "string?", "status": "string?", "priority": "string?", "tags": "string[]?", "context": "object?", "output": "object?", "due_date": "string?"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/tasks/{id}",
+            "description": "Delete task",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/tasks/{id}/context",
+            "description": "Merge-patch task context (append/update fields without replacing)",
+            "body": "object (fields to merge into context)",
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/claim",
+            "description": "Claim an unassigned task (idempotent, checks dependencies)",
+            "headers": {"X-On-Behalf-Of": "agent id? (orchestrator agents only — act as this agent; both identities are recorded in activity)"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/release",
+            "description": "Release a claimed task back to pool",
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/complete",
+            "description": "Mark task done (from in_progress or review). Optionally attach output. Injects output into downstream tasks. An output.pr_url on a task with a repo is attached as a url artifact.",
+            "body": {"summary": "string?", "output": "object?"},
+            "headers": {"X-On-Behalf-Of": "agent id? (orchestrator agents only — act as this agent; both identities are recorded in activity)"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/block",
+            "description": "Mark task as blocked with reason",
+            "body": {"reason": "string?"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/assign",
+            "description": "Assign task to an agent (validates agent exists and is not offline)",
+            "body": {"agent_id": "string"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/handoff",
+            "description": "Hand off task from current agent to another agent",
+            "body": {"target_agent_id": "string", "reason": "string?"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/approve",
+            "description": "Approve a task in review status (moves to done)",
+            "body": {"comment": "string?"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/request-changes",
+            "description": "Request changes on a task in review (moves back to in_progress)",
+            "body": {"comment": "string"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/recurrence/pause",
+            "description": "Pause a recurring series (no new occurrences are created until resumed)",
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/recurrence/resume",
+            "description": "Resume a paused recurring series",
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/recurrence/skip",
+            "description": "Skip the upcoming occurrence of a recurring series and schedule the one after it",
+            "response": "RecurrenceSkipResult",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/batch/status",
+            "description": "Bulk status update for multiple tasks",
+            "body": {"updates": [{"task_id": "string", "status": "string"}]},
+            "response": "BatchResult",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/activity",
+            "description": "Get task activity log",
+            "response": "TaskActivity[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/activity",
+            "description": "Post a comment/update to task activity",
+            "body": {"content": "string", "activity_type": "string?", "metadata": "object?"},
+            "headers": {"X-On-Behalf-Of": "agent id? (orchestrator agents only — act as this agent; both identities are recorded in activity)"},
+            "response": "TaskActivity",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/usage",
+            "description": "Report tokens and spend on a task (agents only). Each report is a separate row carrying the agent's current cost_tier and model.",
+            "body": {"input_tokens": "int?", "output_tokens": "int?", "cost_usd": "float?"},
+            "response": "TaskUsage",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/usage",
+            "description": "Usage reported on a task, oldest first",
+            "response": "TaskUsage[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/submit-review",
+            "description": "Move an in_progress task to review, assigning a reviewer (auto-selected unless reviewer_id is given)",
+            "body": {"summary": "string? (recorded as activity)", "reviewer_id": "string?"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/start-review",
+            "description": "The assigned reviewer picks up a task in review",
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/dependencies",
+            "description": "Tasks this task depends on",
+            "response": "Task[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/dependencies",
+            "description": "Add dependencies (rejects cycles)",
+            "body": {"depends_on": "string[]"},
+            "response": "Task",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/tasks/{id}/dependencies/{dep_id}",
+            "description": "Remove a dependency",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/dependents",
+            "description": "Tasks that depend on this task",
+            "response": "Task[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/artifacts",
+            "description": "Artifacts attached to a task",
+            "response": "TaskArtifact[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/artifacts",
+            "description": "Attach an artifact to a task",
+            "body": {"name": "string", "artifact_type": "url|file|text|json", "value": "string"},
+            "response": "TaskArtifact",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/tasks/{id}/artifacts/{artifact_id}",
+            "description": "Rename an artifact or replace its value (the type is fixed)",
+            "body": {"name": "string?", "value": "string?"},
+            "response": "TaskArtifact",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/tasks/{id}/artifacts/{artifact_id}",
+            "description": "Remove an artifact",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/scheduled/transition",
+            "description": "Move scheduled backlog tasks whose scheduled_at has passed to todo now, instead of waiting for the background sweep; returns how many moved",
+            "response": {"transitioned": "int"},
+            "auth": true
+        }
+    ])
+}
+
+/// Questions on tasks and projects, and their replies.
+fn questions() -> Value {
+    json!([
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/questions",
+            "description": "Questions on a task",
+            "params": {"status": "string? (open | resolved | dismissed)", "unrouted": "bool? (only questions without a target)"},
+            "response": "TaskQuestion[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/questions",
+            "description": "Ask a question about a task, routed to a target, a capability or the project's humans",
+            "body": {"question": "string", "question_type": "string? (clarification, decision, ...)", "context": "string?", "target_type": "string? (agent | human)", "target_id": "string?", "required_capability": "string? (route to an agent with this capability)", "blocking": "bool? (block the task until resolved)", "priority": "string? (defaults to the task's priority)"},
+            "response": "TaskQuestion",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/questions/{qid}",
+            "description": "Get a question",
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/questions/{qid}/resolve",
+            "description": "Resolve a question (unblocks the task when it was blocking)",
+            "body": {"resolution": "string"},
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/questions/{qid}/replies",
+            "description": "Replies to a question, flat and chronological or nested",
+            "params": {"threaded": "bool? (nest replies under their parents)"},
+            "response": "QuestionReply[] (ReplyThread[] with threaded=true)",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/questions/{qid}/replies",
+            "description": "Reply to a question; is_resolution resolves it with this reply",
+            "body": {"body": "string", "is_resolution": "bool?", "parent_reply_id": "string?"},
+            "response": "QuestionReply",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/questions/{qid}/replies/{rid}/artifacts",
+            "description": "Attach an artifact to a reply",
+            "body": {"name": "string", "artifact_type": "url|file|text|json", "value": "string"},
+            "response": "QuestionAttachment",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/questions/{qid}/artifacts",
+            "description": "Artifacts on the question and its replies",
+            "response": "QuestionAttachment[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/questions/{qid}/artifacts",
+            "description": "Attach an artifact to a question",
+            "body": {"name": "string", "artifact_type": "url|file|text|json", "value": "string"},
+            "response": "QuestionAttachment",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/questions/{qid}/dismiss",
+            "description": "Dismiss a question without answering it",
+            "body": {"reason": "string"},
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/questions/{qid}/assign",
+            "description": "Route a question to an agent or human",
+            "body": {"target_type": "string (agent | human)", "target_id": "string"},
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/questions",
+            "description": "Questions in a project: task questions and project-level ones",
+            "params": {"status": "string? (open | resolved | dismissed)", "unrouted": "bool? (only questions without a target)"},
+            "response": "TaskQuestion[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/questions",
+            "description": "Ask a project-level question no task owns; same targeting and resolution flow as task questions",
+            "body": {"question": "string", "question_type": "string? (clarification, decision, ...)", "context": "string?", "target_type": "string? (agent | human)", "target_id": "string?", "required_capability": "string? (route to an agent with this capability)", "blocking": "bool? (block the task until resolved)", "priority": "string? (defaults to the task's priority)"},
+            "response": "TaskQuestion",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/questions/{qid}",
+            "description": "Get a project question",
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/questions/{qid}/resolve",
+            "description": "Resolve a project question",
+            "body": {"resolution": "string"},
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/questions/{qid}/replies",
+            "description": "Replies to a project question",
+            "params": {"threaded": "bool? (nest replies under their parents)"},
+            "response": "QuestionReply[] (ReplyThread[] with threaded=true)",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/questions/{qid}/replies",
+            "description": "Reply to a project question",
+            "body": {"body": "string", "is_resolution": "bool?", "parent_reply_id": "string?"},
+            "response": "QuestionReply",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/questions/{qid}/dismiss",
+            "description": "Dismiss a project question",
+            "body": {"reason": "string"},
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/questions/{qid}/assign",
+            "description": "Route a project question",
+            "body": {"target_type": "string (agent | human)", "target_id": "string"},
+            "response": "TaskQuestion",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/me/questions",
+            "description": "Questions routed to the calling agent",
+            "params": {"status": "string? (open | resolved | dismissed)"},
+            "response": "TaskQuestion[]",
+            "auth": true
+        }
+    ])
+}
+
+/// Agents, their keys, webhooks, notifications and push targets.
+fn agents() -> Value {
+    json!([
+        {
+            "method": "GET",
+            "path": "/api/agents",
+            "description": "List all registered agents with computed status",
+            "response": "Agent[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents",
+            "description": "Register new agent",
+            "body": {"name": "string", "skills": "string[]?"},
+            "response": "AgentCreated",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/{id}",
+            "description": "Get agent profile with computed status, description, webhook_url, and task counts",
+            "response": "Agent",
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/agents/{id}",
+            "description": "Update agent profile",
+            "body": {"description": "string?", "max_concurrent_tasks": "integer?", "webhook_url": "string?", "webhook_template": "object? (JSON body with {{field.path}} placeholders; {} removes it)", "config": "object?", "daily_task_quota": "integer? (0 = unlimited)", "weekly_task_quota": "integer? (0 = unlimited)", "notification_preferences": "object? ({event_type | \"*\": {in_app?: bool, webhook?: bool, wake?: bool, email?: bool, push?: bool, min_priority?: critical|high|medium|low, push_min_priority?: critical|high|medium|low, ack_after_hours?: integer, archive_after_hours?: integer}}) — replaces the whole map"},
+            "response": "Agent",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/agents/{id}",
+            "description": "Revoke agent",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/{id}/keys",
+            "description": "List the agent's additional API keys and their scopes (secrets are not returned)",
+            "response": "ApiKey[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/{id}/keys",
+            "description": "Mint an additional API key. read_only allows only GET; project_id limits it to one project's routes and tasks; no_admin refuses agent, key, trigger, integration, import and project management. The secret is returned once.",
+            "body": {"name": "string?", "read_only": "bool?", "project_id": "string?", "no_admin": "bool?"},
+            "response": "ApiKeyCreated",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/agents/{id}/keys/{key_id}",
+            "description": "Revoke an additional API key",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/{id}/webhook/test",
+            "description": "Send a synthetic webhook.test event to the agent's webhook_url; returns delivered, status_code, latency_ms",
+            "response": "WebhookTestResult",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/{id}/webhooks/failed",
+            "description": "Dead-letter queue: unread notifications whose webhook failed after all retries (self or human)",
+            "response": "Notification[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/{id}/push",
+            "description": "Agent's phone push target (tokens are never returned; self or human)",
+            "response": "PushTarget",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/agents/{id}/push",
+            "description": "Buzz a phone for notifications routed to the push channel (by default: blocked tasks, critical unrouted questions)",
+            "body": {"provider": "ntfy|pushover", "target": "string (ntfy topic URL, or Pushover user key)", "token": "string? (Pushover app token, required; ntfy access token)"},
+            "response": "PushTarget",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/agents/{id}/push",
+            "description": "Remove the agent's push target",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/users/me/push",
+            "description": "Calling user's push target (humans only)",
+            "response": "PushTarget",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/users/me/push",
+            "description": "Push target for push-channel notifications to any agent the user owns",
+            "body": {"provider": "ntfy|pushover", "target": "string", "token": "string?"},
+            "response": "PushTarget",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/users/me/push",
+            "description": "Remove the calling user's push target",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/{id}/webhooks/failed/redeliver",
+            "description": "Retry dead-lettered webhook deliveries against the current webhook_url",
+            "body": {"notification_ids": "int[]? (default: all)"},
+            "response": "WebhookRedelivery",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/{id}/offboard",
+            "description": "Offboard an agent: reassign (via strategy) or release its tasks and reviews, reroute its open questions, then delete it",
+            "body": {"reassign_to": "AssignStrategy? ({strategy, capabilities?, seniority?, role?, agent_id?}) — omit to release work"},
+            "response": "OffboardResult",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/{id}/suggested-skills",
+            "description": "Skills and capabilities inferred from tags of the agent's completed tasks that are not yet on its profile",
+            "params": {"min_tasks": "integer? (default 3) — completed tasks required per tag"},
+            "response": "SuggestedSkills",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/{id}/suggested-skills/apply",
+            "description": "Add all current skill/capability suggestions to the agent's profile",
+            "params": {"min_tasks": "integer? (default 3)"},
+            "response": "Agent",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/register",
+            "description": "Agent self-registration with setup token (no admin login needed)",
+            "body": {"name": "string", "skills": "string[]?", "setup_token": "string"},
+            "response": "AgentCreated",
+            "status": 201,
+            "auth": false
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/heartbeat",
+            "description": "Agent reports liveness. An optional body replaces the agent's heartbeat_status, shown on the agent profile, project pulse and inbox",
+            "body": {"load": "number?", "version": "string?", "status_message": "string? (max 500 chars)"},
+            "response": {"status": "string"},
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/match",
+            "description": "The best available agent for a capability, seniority and role (404 when none fits)",
+            "params": {"capability": "string? (comma-separated)", "seniority": "string?", "role": "string?"},
+            "response": "Agent",
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/agents/me",
+            "description": "Update the calling agent's own profile (same body as PATCH /api/agents/{id})",
+            "body": {"description": "string?", "skills": "string[]?", "max_concurrent_tasks": "integer?", "webhook_url": "string?", "webhook_events": "string[]?", "capabilities": "string[]?", "seniority": "string?", "role": "string?", "tags": "string[]?"},
+            "response": "Agent",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/me/inbox",
+            "description": "Everything waiting on the calling agent: tasks by status, handoffs, open questions, unread notifications, capacity and heartbeat status",
+            "response": "AgentInbox",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/agents/me/notifications",
+            "description": "The calling agent's notifications, newest first",
+            "params": {"unread": "bool?"},
+            "response": "Notification[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/me/notifications/{id}/ack",
+            "description": "Mark a notification read",
+            "response": {"ok": "bool"},
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/agents/me/notifications/ack-all",
+            "description": "Mark all of the calling agent's notifications read",
+            "response": {"ok": "bool", "acknowledged": "int"},
+            "auth": true
+        }
+    ])
+}
+
+/// Project and shared knowledge.
+fn knowledge() -> Value {
+    json!([
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge",
+            "description": "List knowledge entries for a project",
+            "params": {"prefix": "string? (filter by key prefix)"},
+            "response": "KnowledgeEntry[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/search",
+            "description": "Full-text search project knowledge base",
+            "params": {"q": "string (search query)", "tags": "string? (comma-separated)", "category": "string?", "scope": "string? (project | all — `all` includes the shared knowledge space)"},
+            "response": "KnowledgeEntry[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/stats",
+            "description": "Knowledge usage: most-read entries (reads and distinct readers, counted on every entry fetch) and never-read entries",
+            "params": {"limit": "integer? (most_read size, default 10)", "reader_type": "string? (agent | human)"},
+            "response": "KnowledgeUsageStats",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/graph",
+            "description": "Knowledge graph: entries as nodes and `[[key]]` references in their content as edges (references to missing keys under `unresolved`)",
+            "response": "KnowledgeGraph",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/export",
+            "description": "Download the knowledge base as a tar of `{key}.md` files with front-matter (title, category, tags, metadata)",
+            "response": "application/x-tar",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/knowledge/import",
+            "description": "Bulk upsert: a JSON array of entries (Content-Type: application/json) or a tar bundle in the export format. Unchanged entries are left alone",
+            "params": {"on_conflict": "string? (overwrite | skip | merge, default overwrite)"},
+            "body": "[{\"key\": \"string\", \"title\": \"string\", \"content\": \"string\", \"tags\": \"string[]?\", \"category\": \"string?\", \"metadata\": \"object?\"}] | tar",
+            "response": "KnowledgeImportResult",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/{key}",
+            "description": "Get a specific knowledge entry by key",
+            "response": "KnowledgeEntry",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/projects/{id}/knowledge/{key}",
+            "description": "Create or update a knowledge entry (upsert). Entries past `review_after` are flagged `stale`, listed in the project pulse and announced once via a `knowledge.stale` event",
+            "body": {"title": "string", "content": "string", "metadata": "object?", "tags": "string[]?", "category": "string?", "review_after": "string? (RFC 3339 or YYYY-MM-DD; empty string clears)"},
+            "response": "KnowledgeEntry",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/{key}/backlinks",
+            "description": "Entries whose content references this one as `[[key]]`",
+            "response": "KnowledgeSummary[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/{key}/versions",
+            "description": "Version history of a knowledge entry, newest first",
+            "response": "KnowledgeVersion[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/knowledge/{key}/revert",
+            "description": "Restore an earlier version's content (recorded as a new version)",
+            "body": {"version": "integer"},
+            "response": "KnowledgeEntry",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}/knowledge/{key}",
+            "description": "Delete a knowledge entry",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/projects/{id}/knowledge/{key}/attachments",
+            "description": "Attachments on a knowledge entry (also included as `attachments` in the entry)",
+            "response": "KnowledgeAttachment[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/projects/{id}/knowledge/{key}/attachments",
+            "description": "Attach a file, URL or snippet to a knowledge entry",
+            "body": {"name": "string", "artifact_type": "url|file|text|json", "value": "string"},
+            "response": "KnowledgeAttachment",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/projects/{id}/knowledge/{key}/attachments/{attachment_id}",
+            "description": "Remove an attachment from a knowledge entry",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/knowledge",
+            "description": "List organization-wide shared knowledge (one space per tenant; entries report project_id `shared`)",
+            "params": {"prefix": "string? (filter by key prefix)"},
+            "response": "KnowledgeEntry[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/knowledge/templates",
+            "description": "Content templates per knowledge category (markdown skeleton and required section headings)",
+            "response": "KnowledgeTemplate[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/knowledge/search",
+            "description": "Search shared knowledge",
+            "params": {"q": "string?", "tags": "string? (comma-separated)", "category": "string?"},
+            "response": "KnowledgeEntry[]",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/knowledge/{key}",
+            "description": "Get a shared entry; `/versions` and `/attachments` suffixes work as for project entries",
+            "response": "KnowledgeEntry",
+            "auth": true
+        },
+        {
+            "method": "PUT",
+            "path": "/api/knowledge/{key}",
+            "description": "Create or update a shared entry (same body as project entries)",
+            "response": "KnowledgeEntry",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/knowledge/{key}/revert",
+            "description": "Revert a shared entry; POST `/attachments` adds an attachment",
+            "body": {"version": "integer"},
+            "response": "KnowledgeEntry",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/knowledge/{key}",
+            "description": "Delete a shared entry (or `/attachments/{attachment_id}`)",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tasks/{id}/knowledge",
+            "description": "Knowledge entries linked to a task (also included as `knowledge` in GET /api/tasks/{id})",
+            "response": "KnowledgeSummary[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/tasks/{id}/knowledge",
+            "description": "Link a knowledge entry from the task's project to the task",
+            "body": {"key": "string"},
+            "response": "KnowledgeSummary[]",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/tasks/{id}/knowledge/{key}",
+            "description": "Unlink a knowledge entry from a task",
+            "status": 204,
+            "auth": true
+        }
+    ])
+}
+
+/// Stats, administration, event streams and service endpoints.
+fn system() -> Value {
+    json!([
+        {
+            "method": "GET",
+            "path": "/api/stats",
+            "description": "Dashboard statistics. Filters narrow task counts and recent activity; active_agents is never filtered.",
+            "params": {"project_id": "string?", "tag": "string?", "from": "string? (YYYY-MM-DD, tasks created and activity on or after)", "to": "string? (YYYY-MM-DD, on or before)", "format": "string? (json or csv)"},
+            "response": "DashboardStats",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/stats/timeseries",
+            "description": "Task completions, creations or blocks per day or week (weeks start Monday), every bucket present. At most 1000 points.",
+            "params": {"metric": "string (tasks_completed, tasks_created or tasks_blocked)", "interval": "string? (day or week, default day)", "from": "string? (YYYY-MM-DD, default 29 days before to)", "to": "string? (YYYY-MM-DD, default today UTC)", "project_id": "string?", "format": "string? (json or csv)"},
+            "response": "Timeseries",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/stats/agents",
+            "description": "Per-agent workload: open, in-progress and in-review assigned tasks, reviews waiting on the agent, completions and throughput over the window, seconds since last heartbeat",
+            "params": {"days": "int? (throughput window, default 7, at most 365)", "format": "string? (json or csv)"},
+            "response": "AgentWorkloadStats",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/stats/reviews",
+            "description": "Review latency per reviewer and per project, slowest first: time to first review (reviewer starting it, or its verdict), time in review, pending count and oldest wait",
+            "params": {"days": "int? (reviews submitted in the window, default 30, at most 365; pending ones always count)", "project_id": "string?", "format": "string? (json or csv)"},
+            "response": "ReviewLatencyReport",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/stats/costs",
+            "description": "Reported task spend and tokens per day or week, grouped by the reporting agent's cost tier or model, by project or by agent, with distinct task counts",
+            "params": {"group_by": "string? (cost_tier, model, project or agent, default cost_tier)", "interval": "string? (day or week, default day)", "from": "string? (YYYY-MM-DD, default 29 days before to)", "to": "string? (YYYY-MM-DD, default today UTC)", "project_id": "string?", "format": "string? (json or csv)"},
+            "response": "CostReport",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/stats/blocked",
+            "description": "Blocked-time accounting from status history: cumulative time in blocked and time open tasks waited on unfinished dependencies, per project, with the dependencies holding up the most work and the most-blocked tasks",
+            "params": {"project_id": "string?", "limit": "int? (length of top_blockers and most_blocked, default 10, at most 100)"},
+            "response": "BlockedTimeReport",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/admin/events/replay",
+            "description": "Re-run notification routing for an event id range or a task (e.g. after an agent missed its wake-ups). Existing unread notifications are redelivered, not duplicated. Orchestrators and humans only, unless agent_id is the caller.",
+            "body": {"from_event_id": "int?", "to_event_id": "int?", "task_id": "string?", "agent_id": "string?", "deliver_webhooks": "bool?"},
+            "response": "EventReplayResult",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/admin/settings",
+            "description": "Runtime settings (readable by anyone authenticated)",
+            "response": "RuntimeSettings",
+            "auth": true
+        },
+        {
+            "method": "PATCH",
+            "path": "/api/admin/settings",
+            "description": "Change runtime settings without a restart (humans and orchestrators outside any tenant)",
+            "body": {"enforce_dependencies": "bool?", "auto_assign_reviewers": "bool?", "stale_release_minutes": "integer?"},
+            "response": "RuntimeSettings",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/admin/event-bus",
+            "description": "Queue depth and drops for every connected WS/SSE client (humans and orchestrators)",
+            "response": "BusStats",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/admin/queries",
+            "description": "p50/p95 timings per SQL statement since startup or the last reset, and the slow-query log (humans and orchestrators)",
+            "response": "QueryStats",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/admin/queries",
+            "description": "Reset the query timings and slow-query log",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/admin/tenants",
+            "description": "Registered tenants with their resource counts",
+            "response": "TenantSummary[]",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/admin/tenants",
+            "description": "Register a tenant; its id is the owner_id its data carries",
+            "body": {"id": "string", "name": "string? (defaults to the id)"},
+            "response": "Tenant",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/admin/tenants/{id}",
+            "description": "A tenant with its resource counts",
+            "response": "TenantSummary",
+            "auth": true
+        },
+        {
+            "method": "DELETE",
+            "path": "/api/admin/tenants/{id}",
+            "description": "Delete a tenant and everything it owns",
+            "status": 204,
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/admin/tenants/{id}/suspend",
+            "description": "Refuse the tenant's keys and setup tokens until resumed (nothing is deleted)",
+            "response": "Tenant",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/admin/tenants/{id}/resume",
+            "description": "Lift a tenant's suspension",
+            "response": "Tenant",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/admin/tenants/{id}/setup-tokens",
+            "description": "Mint a setup token that registers agents into this tenant only (shown once)",
+            "response": "TenantSetupToken",
+            "status": 201,
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/tenant/usage",
+            "description": "The tenant's usage against its limits (callers outside any tenant name one)",
+            "params": {"tenant_id": "string?"},
+            "response": "TenantUsage",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/ws",
+            "description": "WebSocket event feed. The first message authenticates ({\"type\": \"auth\", \"token\": \"<api_key>\"}), then subscribe messages pick event patterns, agents and projects",
+            "status": 101,
+            "auth": false
+        },
+        {
+            "method": "GET",
+            "path": "/api/events/stream",
+            "description": "The WebSocket event feed as Server-Sent Events",
+            "params": {"events": "string? (comma-separated patterns like task.*, default all)", "agent_id": "string? (self for the caller)", "project_id": "string?", "format": "string? (opengate | cloudevents)"},
+            "response": "text/event-stream",
+            "auth": true
+        },
+        {
+            "method": "POST",
+            "path": "/api/mcp",
+            "description": "MCP over Streamable HTTP: JSON-RPC requests for the same tools as the stdio server",
+            "body": "object (a JSON-RPC request)",
+            "response": "object (a JSON-RPC response; 202 with no body for notifications)",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/mcp",
+            "description": "MCP server-to-client stream: the agent's new notifications as they arrive (Accept: text/event-stream)",
+            "response": "text/event-stream",
+            "auth": true
+        },
+        {
+            "method": "GET",
+            "path": "/api/openapi.json",
+            "description": "OpenAPI 3.1 document of this API, for client generation",
+            "response": "object (this document)",
+            "auth": false
+        },
+        {
+            "method": "GET",
+            "path": "/metrics",
+            "description": "Prometheus metrics (a bearer token is required when --metrics-token is set)",
+            "response": "text/plain",
+            "auth": false
+        },
+        {
+            "method": "GET",
+            "path": "/health",
+            "description": "Liveness check",
+            "response": {"status": "string"},
+            "auth": false
+        },
+        {
+            "method": "GET",
+            "path": "/api/schema",
+            "description": "This endpoint — API schema for agent discovery",
+            "response": "object (the operations in catalog shorthand, with the status flow and context conventions)",
+            "auth": false
+        }
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_every_route() {
+        let catalog = catalog();
+        assert!(crate::app::ROUTES.len() > 150);
+        let undocumented: Vec<_> = crate::app::ROUTES
+            .iter()
+            .filter(|(method, route)| !catalog.iter().any(|e| serves(method, route, e)))
+            .collect();
+        assert!(
+            undocumented.is_empty(),
+            "not in the OpenAPI catalog: {:?}",
+            undocumented
+        );
+        let unserved: Vec<_> = catalog
+            .iter()
+            .filter(|e| {
+                crate::app::ROUTES
+                    .iter()
+                    .filter(|(method, route)| serves(method, route, e))
+                    .count()
+                    != 1
+            })
+            .map(|e| format!("{} {}", e["method"], e["path"]))
+            .collect();
+        assert!(
+            unserved.is_empty(),
+            "not served by exactly one route: {:?}",
+            unserved
+        );
+
+        let paths = &document()["paths"];
+        let mut ids: Vec<&str> = paths
+            .as_object()
+            .unwrap()
+            .values()
+            .flat_map(|item| item.as_object().unwrap().values())
+            .map(|op| op["operationId"].as_str().unwrap())
+            .collect();
+        let count = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), count, "operationIds are not unique");
+    }
+
+    #[test]
+    fn schema_view_round_trips_the_catalog() {
+        let view = schema_view();
+        let endpoints = view["endpoints"].as_array().unwrap();
+        let catalog = catalog();
+        assert_eq!(endpoints.len(), catalog.len());
+        for entry in &catalog {
+            let shown = endpoints
+                .iter()
+                .find(|e| e["method"] == entry["method"] && e["path"] == entry["path"])
+                .unwrap();
+            assert_eq!(shown, entry);
+        }
+    }
+
+    #[test]
+    fn types_every_response() {
+        let doc = document();
+        let mut refs = Vec::new();
+        collect_refs(doc, &mut refs);
+        assert!(refs.len() > 100);
+        for reference in refs {
+            assert!(
+                doc.pointer(reference.trim_start_matches('#')).is_some(),
+                "unresolved {}",
+                reference
+            );
+        }
+
+        for (path, item) in doc["paths"].as_object().unwrap() {
+            for (method, op) in item.as_object().unwrap() {
+                let responses = op["responses"].as_object().unwrap();
+                assert_eq!(responses.len(), 2, "{} {}", method, path);
+                let (status, success) = responses.iter().find(|(s, _)| *s != "4XX").unwrap();
+                let bodiless = status == "204" || status == "101";
+                assert_eq!(
+                    success["content"].is_null(),
+                    bodiless,
+                    "{} {} -> {}",
+                    method,
+                    path,
+                    status
+                );
+            }
+        }
+    }
+
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(fields) => {
+                if let Some(reference) = fields.get("$ref").and_then(Value::as_str) {
+                    refs.push(reference);
+                }
+                fields.values().for_each(|v| collect_refs(v, refs));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    /// The Task, Project and Agent schemas against rows as the API serializes them.
+    #[test]
+    fn schemas_match_stored_rows() {
+        let conn = crate::db::init_db(":memory:");
+        let project = crate::db_ops::create_project(
+            &conn,
+            None,
+            &serde_json::from_value(json!({"name": "Platform"})).unwrap(),
+            "tester",
+        );
+        let task = crate::db_ops::create_task(
+            &conn,
+            None,
+            &project.id,
+            &serde_json::from_value(json!({"title": "Ship it"})).unwrap(),
+            "tester",
+        );
+        let (agent, _) =
+            crate::db_ops::create_agent(&conn, &opengate_models::CreateAgent::new("worker"));
+
+        let schemas = &document()["components"]["schemas"];
+        for (name, row) in [
+            ("Task", json!(task)),
+            ("Project", json!(project)),
+            ("Agent", json!(agent)),
+        ] {
+            let schema = &schemas[name];
+            let properties = schema["properties"].as_object().unwrap();
+            for key in row.as_object().unwrap().keys() {
+                assert!(
+                    properties.contains_key(key),
+                    "{}.{} is undocumented",
+                    name,
+                    key
+                );
+            }
+            for key in schema["required"].as_array().unwrap() {
+                let key = key.as_str().unwrap();
+                assert!(row.get(key).is_some(), "{}.{} is not serialized", name, key);
+            }
+        }
+    }
+
+    #[test]
+    fn types_descriptors() {
+        let (schema, required) = descriptor_schema("int[]? (event ids)");
+        assert_eq!(schema["items"]["type"], "integer");
+        assert!(!required);
+        let (schema, required) = descriptor_schema("agent|human");
+        assert_eq!(schema["enum"], json!(["agent", "human"]));
+        assert!(required);
+        assert!(!descriptor_schema("string (optional, 'active' or 'archived')").1);
+        let (schema, required) = descriptor_schema("Task[]");
+        assert_eq!(schema["items"]["$ref"], "#/components/schemas/Task");
+        assert!(required);
+        assert_eq!(
+            route_path("/api/projects/:id/knowledge/*key"),
+            "/api/projects/{id}/knowledge/{key}"
+        );
+        assert_eq!(
+            operation_id("GET", "/api/tasks/{id}/claim"),
+            "get_tasks_id_claim"
+        );
+    }
+}
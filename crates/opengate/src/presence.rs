@@ -0,0 +1,98 @@
+//! Graduated agent presence derived from heartbeat age.
+//!
+//! An agent is `online` until its last heartbeat is older than the server-wide
+//! [`idle_after_minutes`], then `idle`, then `stale` after [`stale_after_minutes`],
+//! and `offline` once it passes its own `stale_timeout`. The offline cutoff always
+//! wins, so an agent with a short `stale_timeout` may skip the middle states.
+
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+pub const ONLINE: &str = "online";
+pub const IDLE: &str = "idle";
+pub const STALE: &str = "stale";
+pub const OFFLINE: &str = "offline";
+
+pub const DEFAULT_IDLE_AFTER_MINUTES: i64 = 5;
+pub const DEFAULT_STALE_AFTER_MINUTES: i64 = 15;
+
+static IDLE_AFTER_MINUTES: AtomicI64 = AtomicI64::new(DEFAULT_IDLE_AFTER_MINUTES);
+static STALE_AFTER_MINUTES: AtomicI64 = AtomicI64::new(DEFAULT_STALE_AFTER_MINUTES);
+
+/// Set the server-wide idle and stale thresholds (minutes since last heartbeat).
+pub fn set_thresholds(idle_after_minutes: i64, stale_after_minutes: i64) {
+    IDLE_AFTER_MINUTES.store(idle_after_minutes, Ordering::Relaxed);
+    STALE_AFTER_MINUTES.store(stale_after_minutes, Ordering::Relaxed);
+}
+
+pub fn idle_after_minutes() -> i64 {
+    IDLE_AFTER_MINUTES.load(Ordering::Relaxed)
+}
+
+pub fn stale_after_minutes() -> i64 {
+    STALE_AFTER_MINUTES.load(Ordering::Relaxed)
+}
+
+/// Presence for an agent last seen at `last_seen` (RFC 3339) with the given offline cutoff.
+/// Never-seen agents are offline.
+pub fn presence(last_seen: Option<&str>, offline_after_minutes: i64) -> &'static str {
+    let Some(seen) = last_seen.and_then(|ts| DateTime::parse_from_rfc3339(ts).ok()) else {
+        return OFFLINE;
+    };
+    let age = (Utc::now() - seen.with_timezone(&Utc)).num_seconds();
+    if age > offline_after_minutes * 60 {
+        OFFLINE
+    } else if age > stale_after_minutes() * 60 {
+        STALE
+    } else if age > idle_after_minutes() * 60 {
+        IDLE
+    } else {
+        ONLINE
+    }
+}
+
+/// Event emitted for a presence transition, if any: dropping offline, or coming back.
+pub fn transition_event(from: &str, to: &str) -> Option<&'static str> {
+    match (from == OFFLINE, to == OFFLINE) {
+        (false, true) => Some("agent.went_offline"),
+        (true, false) => Some("agent.back_online"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes_ago(m: i64) -> String {
+        (Utc::now() - chrono::Duration::minutes(m)).to_rfc3339()
+    }
+
+    #[test]
+    fn presence_graduates_with_heartbeat_age() {
+        assert_eq!(presence(Some(&minutes_ago(1)), 30), ONLINE);
+        assert_eq!(presence(Some(&minutes_ago(10)), 30), IDLE);
+        assert_eq!(presence(Some(&minutes_ago(20)), 30), STALE);
+        assert_eq!(presence(Some(&minutes_ago(31)), 30), OFFLINE);
+        assert_eq!(presence(None, 30), OFFLINE);
+        assert_eq!(presence(Some("not a timestamp"), 30), OFFLINE);
+    }
+
+    #[test]
+    fn offline_cutoff_wins_over_server_thresholds() {
+        assert_eq!(presence(Some(&minutes_ago(3)), 2), OFFLINE);
+        assert_eq!(presence(Some(&minutes_ago(10)), 12), IDLE);
+    }
+
+    #[test]
+    fn only_offline_boundaries_emit_events() {
+        assert_eq!(transition_event(STALE, OFFLINE), Some("agent.went_offline"));
+        assert_eq!(
+            transition_event(ONLINE, OFFLINE),
+            Some("agent.went_offline")
+        );
+        assert_eq!(transition_event(OFFLINE, IDLE), Some("agent.back_online"));
+        assert_eq!(transition_event(ONLINE, IDLE), None);
+        assert_eq!(transition_event(IDLE, ONLINE), None);
+    }
+}
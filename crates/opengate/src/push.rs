@@ -0,0 +1,101 @@
+//! Phone push for humans: ntfy topics and Pushover.
+//!
+//! Webhooks wake agents; this buzzes a person. A push target is set per agent
+//! (`PUT /api/agents/:id/push`) or per user (`PUT /api/users/me/push`), and a
+//! notification routed to the `push` channel goes to the recipient agent's target and to
+//! its owner's. Only a few event types push by default — blocked tasks and critical
+//! questions nobody picked up — so a target can stay on without becoming noise.
+
+use std::time::Duration;
+
+use crate::storage::StorageBackend;
+use opengate_models::*;
+
+pub const PUSHOVER_API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// The push targets a notification for `agent` goes to: the agent's own, then its
+/// owner's.
+pub fn targets_for(storage: &dyn StorageBackend, agent: &Agent) -> Vec<PushTarget> {
+    let mut targets: Vec<PushTarget> = storage
+        .get_push_target(None, "agent", &agent.id)
+        .into_iter()
+        .collect();
+    if let Some(ref owner_id) = agent.owner_id {
+        if let Some(target) = storage.get_push_target(None, "user", owner_id) {
+            if !targets
+                .iter()
+                .any(|t| t.provider == target.provider && t.target == target.target)
+            {
+                targets.push(target);
+            }
+        }
+    }
+    targets
+}
+
+/// Push a notification to every target of its recipient, in the background.
+pub fn send_notification(storage: &dyn StorageBackend, agent: &Agent, notif: &PendingNotifWebhook) {
+    let targets = targets_for(storage, agent);
+    if targets.is_empty() {
+        return;
+    }
+    let title = format!("[{}] {}", agent.name, notif.title);
+    let message = notif.body.clone().unwrap_or_else(|| notif.title.clone());
+    let tag = notif
+        .event_type
+        .strip_prefix("task.")
+        .unwrap_or(&notif.event_type)
+        .to_string();
+    let notification_id = notif.notification_id;
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        for target in targets {
+            if let Err(e) = send(&client, &target, &title, &message, &tag).await {
+                eprintln!(
+                    "[push] notif {} not pushed via {}: {}",
+                    notification_id, target.provider, e
+                );
+            }
+        }
+    });
+}
+
+async fn send(
+    client: &reqwest::Client,
+    target: &PushTarget,
+    title: &str,
+    message: &str,
+    tag: &str,
+) -> Result<(), String> {
+    let request = match target.provider.as_str() {
+        // https://docs.ntfy.sh/publish/ — the body is the message; the rest go in the query
+        // string rather than headers, which can't carry the emoji in titles
+        "ntfy" => {
+            let mut request = client
+                .post(&target.target)
+                .query(&[("title", title), ("priority", "high"), ("tags", tag)])
+                .body(message.to_string());
+            if let Some(ref token) = target.token {
+                request = request.bearer_auth(token);
+            }
+            request
+        }
+        "pushover" => client.post(PUSHOVER_API_URL).json(&serde_json::json!({
+            "token": target.token,
+            "user": target.target,
+            "title": title,
+            "message": message,
+            "priority": 1,
+        })),
+        other => return Err(format!("unknown provider '{}'", other)),
+    };
+    let resp = request
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status().as_u16()));
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! Storage timings for `GET /api/admin/queries`.
+//!
+//! Every SQL statement is timed by the SQLite profile hook and grouped by its text
+//! (statements are parameterised, so one group is one query shape). Each group keeps
+//! its recent durations for p50/p95. Statements slower than the threshold set with
+//! [`set_slow_threshold_ms`] are logged to stderr and kept in a short slow-query log.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::Serialize;
+
+pub const DEFAULT_SLOW_QUERY_MS: u64 = 100;
+
+/// Durations kept per statement for the percentiles
+const SAMPLES_PER_STATEMENT: usize = 512;
+/// Distinct statements tracked; later ones are counted under [`OTHER`]
+const MAX_STATEMENTS: usize = 500;
+const OTHER: &str = "(other statements)";
+const SLOW_LOG_LEN: usize = 100;
+
+static SLOW_THRESHOLD_MS: AtomicU64 = AtomicU64::new(DEFAULT_SLOW_QUERY_MS);
+
+/// Log statements taking at least `ms` milliseconds (0 turns the slow-query log off).
+pub fn set_slow_threshold_ms(ms: u64) {
+    SLOW_THRESHOLD_MS.store(ms, Ordering::Relaxed);
+}
+
+#[derive(Default)]
+struct Timings {
+    count: u64,
+    total: Duration,
+    max: Duration,
+    recent: VecDeque<Duration>,
+}
+
+#[derive(Default)]
+struct Registry {
+    statements: HashMap<String, Timings>,
+    slow: VecDeque<SlowQuery>,
+}
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementStats {
+    pub statement: String,
+    pub count: u64,
+    pub total_ms: f64,
+    /// Over the most recent runs
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlowQuery {
+    pub statement: String,
+    pub duration_ms: f64,
+    pub at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryStats {
+    pub slow_query_threshold_ms: u64,
+    /// Most total time first
+    pub statements: Vec<StatementStats>,
+    /// Newest first
+    pub slow_queries: Vec<SlowQuery>,
+}
+
+/// Collapse whitespace so the same statement written across lines groups together.
+fn normalize(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn millis(d: Duration) -> f64 {
+    (d.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Record one statement run.
+pub fn record(sql: &str, took: Duration) {
+    let statement = normalize(sql);
+    let threshold = SLOW_THRESHOLD_MS.load(Ordering::Relaxed);
+    let slow = threshold > 0 && took >= Duration::from_millis(threshold);
+    if slow {
+        eprintln!("[slow-query] {}ms: {}", millis(took), statement);
+    }
+
+    let mut registry = registry().lock().unwrap();
+    if slow {
+        if registry.slow.len() == SLOW_LOG_LEN {
+            registry.slow.pop_back();
+        }
+        registry.slow.push_front(SlowQuery {
+            statement: statement.clone(),
+            duration_ms: millis(took),
+            at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+    let key = if registry.statements.len() < MAX_STATEMENTS
+        || registry.statements.contains_key(&statement)
+    {
+        statement
+    } else {
+        OTHER.to_string()
+    };
+    let timings = registry.statements.entry(key).or_default();
+    timings.count += 1;
+    timings.total += took;
+    timings.max = timings.max.max(took);
+    if timings.recent.len() == SAMPLES_PER_STATEMENT {
+        timings.recent.pop_front();
+    }
+    timings.recent.push_back(took);
+}
+
+/// Nearest-rank percentile of sorted durations.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn snapshot() -> QueryStats {
+    let registry = registry().lock().unwrap();
+    let mut statements: Vec<StatementStats> = registry
+        .statements
+        .iter()
+        .map(|(statement, t)| {
+            let mut sorted: Vec<Duration> = t.recent.iter().copied().collect();
+            sorted.sort();
+            StatementStats {
+                statement: statement.clone(),
+                count: t.count,
+                total_ms: millis(t.total),
+                p50_ms: millis(percentile(&sorted, 50.0)),
+                p95_ms: millis(percentile(&sorted, 95.0)),
+                max_ms: millis(t.max),
+            }
+        })
+        .collect();
+    statements.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    QueryStats {
+        slow_query_threshold_ms: SLOW_THRESHOLD_MS.load(Ordering::Relaxed),
+        statements,
+        slow_queries: registry.slow.iter().cloned().collect(),
+    }
+}
+
+/// Forget everything recorded so far, e.g. to measure again after adding an index.
+pub fn reset() {
+    let mut registry = registry().lock().unwrap();
+    registry.statements.clear();
+    registry.slow.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let sorted: Vec<Duration> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 95.0), Duration::from_millis(19));
+        assert_eq!(percentile(&sorted[..1], 95.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+        assert_eq!(
+            normalize("SELECT *\n    FROM tasks\tWHERE id = ?1 "),
+            "SELECT * FROM tasks WHERE id = ?1"
+        );
+    }
+}
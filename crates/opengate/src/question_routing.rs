@@ -0,0 +1,43 @@
+//! Question re-routing when the targeted agent goes quiet.
+//!
+//! A question targeted at an agent that has gone `stale` or `offline` and hasn't
+//! replied within the server-wide [`reroute_after_minutes`] window is handed to the
+//! next capability match, or left unrouted for an orchestrator to pick up. Every
+//! target change is appended to the question's `routing_history`.
+
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+pub const DEFAULT_REROUTE_AFTER_MINUTES: i64 = 30;
+
+static REROUTE_AFTER_MINUTES: AtomicI64 = AtomicI64::new(DEFAULT_REROUTE_AFTER_MINUTES);
+
+/// Set the server-wide re-routing window. Values below 1 disable re-routing.
+pub fn set_reroute_after_minutes(minutes: i64) {
+    REROUTE_AFTER_MINUTES.store(minutes, Ordering::Relaxed);
+}
+
+pub fn reroute_after_minutes() -> i64 {
+    REROUTE_AFTER_MINUTES.load(Ordering::Relaxed)
+}
+
+/// Whether a question targeted at `targeted_at` (RFC 3339) has waited longer than
+/// `window_minutes`. Unparseable timestamps never count as overdue.
+pub fn is_overdue(targeted_at: &str, window_minutes: i64) -> bool {
+    DateTime::parse_from_rfc3339(targeted_at)
+        .is_ok_and(|at| (Utc::now() - at.with_timezone(&Utc)).num_seconds() >= window_minutes * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overdue_once_the_window_passes() {
+        let minutes_ago = |m: i64| (Utc::now() - chrono::Duration::minutes(m)).to_rfc3339();
+        assert!(is_overdue(&minutes_ago(31), 30));
+        assert!(!is_overdue(&minutes_ago(5), 30));
+        assert!(is_overdue(&minutes_ago(0), 0));
+        assert!(!is_overdue("yesterday", 30));
+    }
+}
@@ -0,0 +1,225 @@
+//! Per-tenant caps (`[tenant_limits]`). Projects, open tasks and agents are checked when
+//! one is created and refused with 402; events and webhook deliveries are counted per
+//! UTC day, with writes refused with 429 once a tenant's events run out. Callers
+//! outside any tenant, and unowned projects, are never limited.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDate, Utc};
+
+use crate::app::AppState;
+use crate::config::{TenantLimits, TenantLimitsConfig};
+use crate::storage::StorageBackend;
+use opengate_models::{Identity, QuotaUsage, TenantUsage};
+
+static LIMITS: RwLock<Option<TenantLimitsConfig>> = RwLock::new(None);
+
+/// Apply `limits` from the next request on.
+pub fn set_limits(limits: TenantLimitsConfig) {
+    *LIMITS.write().unwrap() = Some(limits);
+}
+
+fn limits_for(tenant: &str) -> TenantLimits {
+    LIMITS
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|limits| limits.for_tenant(tenant))
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quota {
+    Projects,
+    OpenTasks,
+    Agents,
+}
+
+impl Quota {
+    fn name(self) -> &'static str {
+        match self {
+            Quota::Projects => "projects",
+            Quota::OpenTasks => "open_tasks",
+            Quota::Agents => "agents",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub tenant: String,
+    pub quota: Quota,
+    pub limit: u64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Tenant '{}' has reached its limit of {} {}",
+            self.tenant,
+            self.limit,
+            self.quota.name().replace('_', " ")
+        )
+    }
+}
+
+impl From<QuotaExceeded> for (StatusCode, Json<serde_json::Value>) {
+    fn from(e: QuotaExceeded) -> Self {
+        (
+            StatusCode::PAYMENT_REQUIRED,
+            Json(serde_json::json!({
+                "error": e.to_string(),
+                "quota": e.quota.name(),
+                "limit": e.limit,
+            })),
+        )
+    }
+}
+
+/// Whether `tenant` may create one more of `quota`.
+pub fn check(
+    storage: &dyn StorageBackend,
+    tenant: Option<&str>,
+    quota: Quota,
+) -> Result<(), QuotaExceeded> {
+    let Some(tenant) = tenant else {
+        return Ok(());
+    };
+    let limits = limits_for(tenant);
+    let limit = match quota {
+        Quota::Projects => limits.projects,
+        Quota::OpenTasks => limits.open_tasks,
+        Quota::Agents => limits.agents,
+    };
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    let usage = storage.get_tenant_usage(tenant);
+    let used = match quota {
+        Quota::Projects => usage.projects.used,
+        Quota::OpenTasks => usage.open_tasks.used,
+        Quota::Agents => usage.agents.used,
+    };
+    if used as u64 >= limit {
+        return Err(QuotaExceeded {
+            tenant: tenant.to_string(),
+            quota,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// tenant → (UTC day, webhook deliveries that day)
+fn deliveries() -> &'static Mutex<HashMap<String, (NaiveDate, u64)>> {
+    static DELIVERIES: OnceLock<Mutex<HashMap<String, (NaiveDate, u64)>>> = OnceLock::new();
+    DELIVERIES.get_or_init(Default::default)
+}
+
+fn deliveries_today(tenant: &str) -> u64 {
+    let today = Utc::now().date_naive();
+    match deliveries().lock().unwrap().get(tenant) {
+        Some((day, count)) if *day == today => *count,
+        _ => 0,
+    }
+}
+
+/// Count one outbound webhook for `tenant`; false when its deliveries for the day are
+/// used up and the webhook should be dropped.
+pub fn take_webhook_delivery(tenant: Option<&str>) -> bool {
+    let Some(tenant) = tenant else {
+        return true;
+    };
+    let limit = limits_for(tenant).webhook_deliveries_per_day;
+    let today = Utc::now().date_naive();
+    let mut deliveries = deliveries().lock().unwrap();
+    let (day, count) = deliveries.entry(tenant.to_string()).or_insert((today, 0));
+    if *day != today {
+        *day = today;
+        *count = 0;
+    }
+    if limit.is_some_and(|limit| *count >= limit) {
+        return false;
+    }
+    *count += 1;
+    true
+}
+
+/// `tenant`'s usage against its limits.
+pub fn usage(storage: &dyn StorageBackend, tenant: &str) -> TenantUsage {
+    let limits = limits_for(tenant);
+    let mut usage = storage.get_tenant_usage(tenant);
+    usage.projects.limit = limits.projects;
+    usage.open_tasks.limit = limits.open_tasks;
+    usage.agents.limit = limits.agents;
+    usage.events_today.limit = limits.events_per_day;
+    usage.webhook_deliveries_today = QuotaUsage {
+        used: deliveries_today(tenant) as i64,
+        limit: limits.webhook_deliveries_per_day,
+    };
+    usage
+}
+
+fn any_event_limit() -> bool {
+    LIMITS.read().unwrap().as_ref().is_some_and(|limits| {
+        limits.events_per_day > 0
+            || limits
+                .tenants
+                .values()
+                .any(|t| t.events_per_day.is_some_and(|n| n > 0))
+    })
+}
+
+/// Middleware answering writes from a tenant that has used up its events for the day
+/// with 429 and a `Retry-After` of the next UTC midnight. Reads still go through.
+pub async fn limit_events(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let read = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if read || !any_event_limit() {
+        return next.run(request).await;
+    }
+    let (mut parts, body) = request.into_parts();
+    let identity = Identity::from_request_parts(&mut parts, &state)
+        .await
+        .unwrap_or(Identity::Anonymous);
+    let request = Request::from_parts(parts, body);
+    let Some(tenant) = identity.tenant_id() else {
+        return next.run(request).await;
+    };
+    let Some(limit) = limits_for(tenant).events_per_day else {
+        return next.run(request).await;
+    };
+    if (state.storage.get_tenant_usage(tenant).events_today.used as u64) < limit {
+        return next.run(request).await;
+    }
+    let now = Utc::now();
+    let midnight = (now.date_naive() + chrono::Days::new(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(
+            header::RETRY_AFTER,
+            (midnight - now).num_seconds().max(1).to_string(),
+        )],
+        Json(serde_json::json!({
+            "error": format!("Tenant '{}' has used its {} events for today", tenant, limit),
+            "quota": "events_per_day",
+            "limit": limit,
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,115 @@
+//! Per-client request limit (`[rate_limit] requests_per_minute`), counted in fixed
+//! one-minute windows. Clients are told apart by their `Authorization` header, or by
+//! address when they send none. `/metrics` and `/health` are never limited, so scrapes
+//! and probes don't eat into anyone's budget.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sha2::{Digest, Sha256};
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Clients tracked before expired windows are swept out
+const SWEEP_AT: usize = 10_000;
+
+static REQUESTS_PER_MINUTE: AtomicU32 = AtomicU32::new(0);
+
+/// Allow each client `limit` requests a minute (0 turns the limit off).
+pub fn set_requests_per_minute(limit: u32) {
+    REQUESTS_PER_MINUTE.store(limit, Ordering::Relaxed);
+}
+
+#[derive(Default)]
+struct Windows {
+    /// client → (window start, requests in it)
+    clients: HashMap<String, (Instant, u32)>,
+}
+
+impl Windows {
+    /// Count a request; `Err` holds how long until the client's window resets.
+    fn hit(&mut self, client: &str, limit: u32, now: Instant) -> Result<(), Duration> {
+        if self.clients.len() >= SWEEP_AT {
+            self.clients
+                .retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+        }
+        let (start, count) = self.clients.entry(client.to_string()).or_insert((now, 0));
+        if now.duration_since(*start) >= WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= limit {
+            return Err(WINDOW - now.duration_since(*start));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+fn windows() -> &'static Mutex<Windows> {
+    static WINDOWS: OnceLock<Mutex<Windows>> = OnceLock::new();
+    WINDOWS.get_or_init(Default::default)
+}
+
+fn client_key(request: &Request) -> String {
+    if let Some(auth) = request.headers().get(header::AUTHORIZATION) {
+        // Hashed so keys aren't held in the table
+        return format!("key:{}", hex::encode(Sha256::digest(auth.as_bytes())));
+    }
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "anonymous".to_string(),
+    }
+}
+
+/// Middleware answering 429 with `Retry-After` once a client is over its limit.
+pub async fn limit_requests(request: Request, next: Next) -> Response {
+    let limit = REQUESTS_PER_MINUTE.load(Ordering::Relaxed);
+    if limit == 0 || matches!(request.uri().path(), "/metrics" | "/health") {
+        return next.run(request).await;
+    }
+    let client = client_key(&request);
+    let hit = windows()
+        .lock()
+        .unwrap()
+        .hit(&client, limit, Instant::now());
+    match hit {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, (retry_after.as_secs() + 1).to_string())],
+            Json(serde_json::json!({
+                "error": format!("Rate limit of {} requests per minute exceeded", limit)
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_window_per_client() {
+        let mut windows = Windows::default();
+        let start = Instant::now();
+        assert!(windows.hit("a", 2, start).is_ok());
+        assert!(windows.hit("a", 2, start).is_ok());
+        let wait = windows
+            .hit("a", 2, start + Duration::from_secs(15))
+            .unwrap_err();
+        assert_eq!(wait, Duration::from_secs(45));
+        assert!(windows.hit("b", 2, start).is_ok());
+        assert!(windows.hit("a", 2, start + WINDOW).is_ok());
+    }
+}
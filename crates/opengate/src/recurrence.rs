@@ -0,0 +1,302 @@
+//! Recurrence rule evaluation.
+//!
+//! A task's `recurrence_rule` is either the `frequency`/`interval` shorthand
+//! (`{"frequency": "weekly", "interval": 2}`) or a full RFC 5545 RRULE string
+//! (`{"rrule": "FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10"}`). Both forms honour `end_date`,
+//! `end_after`, and the server's `max_recurrence_occurrences` cap on series length.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+
+/// Default server-wide cap on the number of occurrences in one recurring series.
+pub const DEFAULT_MAX_OCCURRENCES: i64 = 1000;
+
+/// Parse `end_date` as RFC 3339, or a plain `YYYY-MM-DD` meaning the end of that day (UTC).
+pub fn parse_end_date(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(23, 59, 59))
+        .map(|dt| dt.and_utc())
+}
+
+/// Parse an RFC 5545 RRULE string (with or without the `RRULE:` prefix) anchored at `dtstart`.
+fn parse_rrule(rrule: &str, dtstart: DateTime<Utc>) -> Result<rrule::RRuleSet, String> {
+    let body = rrule.trim();
+    let body = body
+        .strip_prefix("RRULE:")
+        .or_else(|| body.strip_prefix("rrule:"))
+        .unwrap_or(body);
+    let parsed: rrule::RRule<rrule::Unvalidated> = body.parse().map_err(|e| format!("{}", e))?;
+    parsed
+        .build(dtstart.with_timezone(&rrule::Tz::UTC))
+        .map_err(|e| format!("{}", e))
+}
+
+/// Validate a recurrence rule: either an `rrule` string or the `frequency`/`interval` shorthand.
+pub fn validate_recurrence_rule(rule: &serde_json::Value) -> Result<(), String> {
+    if rule.is_null() {
+        return Ok(());
+    }
+    match rule.get("end_date") {
+        None | Some(serde_json::Value::Null) => {}
+        Some(serde_json::Value::String(s)) if s.is_empty() => {}
+        Some(serde_json::Value::String(s)) => {
+            parse_end_date(s).ok_or_else(|| {
+                format!(
+                    "'end_date' must be an RFC 3339 datetime or YYYY-MM-DD, got '{}'",
+                    s
+                )
+            })?;
+        }
+        Some(_) => return Err("'end_date' must be a string".to_string()),
+    }
+    match rule.get("end_after") {
+        None | Some(serde_json::Value::Null) => {}
+        Some(v) if v.as_i64().is_some_and(|n| n >= 0) => {}
+        Some(_) => return Err("'end_after' must be a non-negative integer".to_string()),
+    }
+    match rule.get("interval") {
+        None | Some(serde_json::Value::Null) => {}
+        Some(v) if v.as_i64().is_some_and(|n| n >= 1) => {}
+        Some(_) => return Err("'interval' must be a positive integer".to_string()),
+    }
+    if let Some(rrule) = rule.get("rrule") {
+        let rrule = rrule.as_str().ok_or("'rrule' must be a string")?;
+        // Anchor far enough back that any UNTIL is still after DTSTART
+        parse_rrule(rrule, DateTime::<Utc>::UNIX_EPOCH)?;
+        return Ok(());
+    }
+    match rule.get("frequency").and_then(|v| v.as_str()) {
+        Some("daily" | "weekly" | "monthly" | "cron") => Ok(()),
+        Some(other) => Err(format!(
+            "Unknown frequency '{}'. Must be one of: daily, weekly, monthly, cron",
+            other
+        )),
+        None => Err("Recurrence rule requires either 'rrule' or 'frequency'".to_string()),
+    }
+}
+
+/// Express a recurrence rule as an RFC 5545 RRULE value (without the `RRULE:` prefix),
+/// folding in `end_date` as UNTIL and `end_after` as COUNT when the rule has no bound of
+/// its own. Returns None for rules that cannot be expressed (unknown frequency).
+pub fn to_rrule_string(rule: &serde_json::Value) -> Option<String> {
+    let mut parts: Vec<String> = if let Some(rrule) = rule.get("rrule").and_then(|v| v.as_str()) {
+        let body = rrule.trim();
+        let body = body
+            .strip_prefix("RRULE:")
+            .or_else(|| body.strip_prefix("rrule:"))
+            .unwrap_or(body);
+        body.split(';')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.to_string())
+            .collect()
+    } else {
+        let freq = match rule.get("frequency")?.as_str()? {
+            "daily" | "cron" => "DAILY",
+            "weekly" => "WEEKLY",
+            "monthly" => "MONTHLY",
+            _ => return None,
+        };
+        let interval = rule
+            .get("interval")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(1)
+            .max(1);
+        let mut parts = vec![format!("FREQ={}", freq)];
+        if interval > 1 {
+            parts.push(format!("INTERVAL={}", interval));
+        }
+        // end_after counts follow-ups; COUNT includes the first occurrence
+        if let Some(end_after) = rule.get("end_after").and_then(|v| v.as_i64()) {
+            parts.push(format!("COUNT={}", end_after + 1));
+        }
+        parts
+    };
+
+    let bounded = parts
+        .iter()
+        .any(|p| p.to_uppercase().starts_with("UNTIL=") || p.to_uppercase().starts_with("COUNT="));
+    if !bounded {
+        if let Some(end) = rule
+            .get("end_date")
+            .and_then(|v| v.as_str())
+            .and_then(parse_end_date)
+        {
+            parts.push(format!("UNTIL={}", end.format("%Y%m%dT%H%M%SZ")));
+        }
+    }
+
+    Some(parts.join(";"))
+}
+
+/// Calculate next scheduled_at from a recurrence rule and previous scheduled_at / now.
+/// `anchor` is the series start (DTSTART) used when the rule is an RRULE string, so that
+/// COUNT/BYDAY/BYMONTHDAY are evaluated relative to the first occurrence.
+pub fn next_recurrence_time(rule: &serde_json::Value, anchor: &str, from: &str) -> Option<String> {
+    // Parse from as UTC datetime, fall back to now
+    let base: DateTime<Utc> = DateTime::parse_from_rfc3339(from)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+
+    let next = if let Some(rrule) = rule.get("rrule").and_then(|v| v.as_str()) {
+        let dtstart: DateTime<Utc> = DateTime::parse_from_rfc3339(anchor)
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or(base);
+        let set = parse_rrule(rrule, dtstart).ok()?;
+        // `after` is inclusive, so step past the occurrence we are advancing from.
+        // An exhausted COUNT/UNTIL yields no dates and ends the series.
+        let after = (base + Duration::seconds(1)).with_timezone(&rrule::Tz::UTC);
+        set.after(after)
+            .all(1)
+            .dates
+            .into_iter()
+            .next()?
+            .with_timezone(&Utc)
+    } else {
+        next_shorthand_time(rule, base)?
+    };
+
+    // Check end conditions
+    if let Some(end) = rule
+        .get("end_date")
+        .and_then(|v| v.as_str())
+        .and_then(parse_end_date)
+    {
+        if next > end {
+            return None; // Past end date
+        }
+    }
+
+    Some(next.to_rfc3339())
+}
+
+/// Advance `base` by the `frequency`/`interval` shorthand.
+fn next_shorthand_time(rule: &serde_json::Value, base: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let frequency = rule.get("frequency")?.as_str()?;
+    let interval = rule
+        .get("interval")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(1)
+        .max(1);
+
+    let next = match frequency {
+        "daily" => base + Duration::days(interval),
+        "weekly" => base + Duration::weeks(interval),
+        "monthly" => {
+            // Add months by manipulating year/month
+            let mut month = base.month() as i64 + interval;
+            let mut year = base.year() as i64;
+            while month > 12 {
+                month -= 12;
+                year += 1;
+            }
+            base.with_month(month as u32)
+                .and_then(|d| d.with_year(year as i32))
+                .unwrap_or(base + Duration::days(30 * interval))
+        }
+        "cron" => {
+            // Cron support: try to parse using simple next-occurrence logic
+            // For now, fall back to daily if we can't compute cron
+            base + Duration::days(1)
+        }
+        _ => return None,
+    };
+
+    Some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db, db_ops};
+    use opengate_models::{CreateProject, CreateTask};
+
+    #[test]
+    fn end_date_accepts_plain_date_as_end_of_day() {
+        let end = parse_end_date("2026-03-01").unwrap();
+        assert_eq!(end.to_rfc3339(), "2026-03-01T23:59:59+00:00");
+        assert!(parse_end_date("2026-03-01T10:00:00Z").is_some());
+        assert!(parse_end_date("March 1st").is_none());
+    }
+
+    #[test]
+    fn validate_rejects_malformed_bounds() {
+        let ok = serde_json::json!({"frequency": "daily", "end_date": "2026-03-01"});
+        assert!(validate_recurrence_rule(&ok).is_ok());
+        for bad in [
+            serde_json::json!({"frequency": "daily", "end_date": "2026-13-45"}),
+            serde_json::json!({"frequency": "daily", "end_date": 20260301}),
+            serde_json::json!({"frequency": "daily", "end_after": -1}),
+            serde_json::json!({"frequency": "daily", "interval": 0}),
+        ] {
+            assert!(validate_recurrence_rule(&bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn next_time_stops_at_end_date() {
+        let rule = serde_json::json!({"frequency": "daily", "end_date": "2026-03-02"});
+        let from = "2026-03-01T09:00:00+00:00";
+        assert_eq!(
+            next_recurrence_time(&rule, from, from).as_deref(),
+            Some("2026-03-02T09:00:00+00:00")
+        );
+        let from = "2026-03-02T09:00:00+00:00";
+        assert_eq!(next_recurrence_time(&rule, from, from), None);
+    }
+
+    #[test]
+    fn server_cap_limits_chain_length() {
+        let conn = db::init_db(":memory:");
+        let project = db_ops::create_project(
+            &conn,
+            None,
+            &CreateProject {
+                name: "cap".to_string(),
+                description: None,
+                repo_url: None,
+                default_branch: None,
+                join_mode: None,
+                cta_enabled: None,
+                is_public: None,
+            },
+            "tester",
+        );
+        let root = db_ops::create_task(
+            &conn,
+            None,
+            &project.id,
+            &CreateTask {
+                title: "Forever".to_string(),
+                description: None,
+                priority: None,
+                tags: None,
+                context: None,
+                output: None,
+                due_date: None,
+                assignee_type: None,
+                assignee_id: None,
+                scheduled_at: Some("2026-01-01T00:00:00Z".to_string()),
+                recurrence_rule: Some(serde_json::json!({"frequency": "daily"})),
+            },
+            "tester",
+        );
+
+        let mut current = root;
+        let mut created = 0;
+        while let Some(next_id) = db_ops::create_next_recurrence(&conn, &current, 3) {
+            created += 1;
+            current = db_ops::get_task(&conn, None, &next_id).unwrap();
+            assert!(created < 10, "cap was not enforced");
+        }
+
+        // Root plus two spawned occurrences
+        assert_eq!(created, 2);
+        let activity = db_ops::list_activity(&conn, &current.id);
+        assert!(activity
+            .iter()
+            .any(|a| a.content.contains("server limit of 3")));
+    }
+}
@@ -0,0 +1,318 @@
+//! Slack integration: posts a project's task events to a Slack channel.
+//!
+//! Configured per project with `PUT /api/projects/:id/integrations/slack`. The forwarder
+//! follows the persisted event log (so messages carry the actor and survive a busy bus)
+//! and posts each selected event as Block Kit blocks: the task title (linked to the
+//! dashboard when `dashboard_url` is set), its status and who acted.
+//!
+//! With an incoming `webhook_url` messages are only posted. With a `bot_token` and
+//! `channel` they go through `chat.postMessage`: the first message about a task opens a
+//! thread and later ones reply in it, and replies people write in that thread can be
+//! ingested back as task activity by a `slack_thread_reply` trigger.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::signatures::{constant_time_eq, hmac_sha256};
+use crate::storage::StorageBackend;
+use opengate_models::*;
+
+pub const DEFAULT_API_URL: &str = "https://slack.com/api";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const BATCH_SIZE: i64 = 200;
+/// Slack rejects signed requests older than five minutes; so do we.
+const MAX_SIGNATURE_AGE_SECS: i64 = 300;
+
+static API_URL: OnceLock<String> = OnceLock::new();
+
+/// Set the Slack Web API base URL. Only the first call takes effect.
+pub fn set_api_url(url: &str) {
+    let _ = API_URL.set(url.trim_end_matches('/').to_string());
+}
+
+pub fn api_url() -> &'static str {
+    API_URL.get().map(String::as_str).unwrap_or(DEFAULT_API_URL)
+}
+
+/// Whether `integration` posts events of this type.
+pub fn wants(integration: &SlackIntegration, event_type: &str) -> bool {
+    integration
+        .events
+        .iter()
+        .any(|e| e == "*" || e == event_type)
+}
+
+/// Follow the event log and post new events to their project's Slack integration.
+pub fn spawn(storage: Arc<dyn StorageBackend>) {
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut cursor = storage.get_last_event_id(None);
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            for event in storage.list_events_after(None, cursor, BATCH_SIZE) {
+                cursor = event.id;
+                let Some(integration) = storage.get_slack_integration(None, &event.project_id)
+                else {
+                    continue;
+                };
+                if !wants(&integration, &event.event_type) {
+                    continue;
+                }
+                let task = event
+                    .task_id
+                    .as_deref()
+                    .and_then(|id| storage.get_task(None, id));
+                let msg = message(&integration, &event, task.as_ref());
+                if let Err(e) = post(&client, &*storage, &integration, &event, msg).await {
+                    eprintln!(
+                        "[slack] event {} for project {} not posted: {}",
+                        event.id, event.project_id, e
+                    );
+                }
+            }
+        }
+    });
+}
+
+/// The Slack message for a stored event: fallback `text` plus Block Kit `blocks`.
+pub fn message(
+    integration: &SlackIntegration,
+    event: &StoredEvent,
+    task: Option<&Task>,
+) -> serde_json::Value {
+    let title = task
+        .map(|t| t.title.as_str())
+        .or_else(|| event.payload.get("task_title").and_then(|v| v.as_str()))
+        .unwrap_or("Untitled task");
+    let status = event
+        .payload
+        .pointer("/status_change/to")
+        .and_then(|v| v.as_str())
+        .or(task.map(|t| t.status.as_str()))
+        .unwrap_or("unknown");
+    let actor = event
+        .payload
+        .get("actor_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(&event.actor_id);
+    let label = event_label(&event.event_type);
+
+    let heading = match (&integration.dashboard_url, &event.task_id) {
+        (Some(base), Some(task_id)) => format!("<{}/tasks/{}|{}>", base, task_id, escape(title)),
+        _ => escape(title),
+    };
+    let mut context = format!("Status: *{}*  ·  By: {}", escape(status), escape(actor));
+    if let Some(ref task_id) = event.task_id {
+        context.push_str(&format!("  ·  `{}`", task_id));
+    }
+    serde_json::json!({
+        "text": format!("{}: {} ({})", label, title, status),
+        "blocks": [
+            {
+                "type": "section",
+                "text": {"type": "mrkdwn", "text": format!("*{}*  {}", label, heading)},
+            },
+            {
+                "type": "context",
+                "elements": [{"type": "mrkdwn", "text": context}],
+            },
+        ],
+    })
+}
+
+/// `task.review_requested` → `Review requested`
+fn event_label(event_type: &str) -> String {
+    let name = event_type.strip_prefix("task.").unwrap_or(event_type);
+    let mut label = name.replace(['_', '.'], " ");
+    if let Some(first) = label.get(..1) {
+        label.replace_range(..1, &first.to_uppercase());
+    }
+    label
+}
+
+/// Escape the characters Slack's mrkdwn treats as control sequences.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+async fn post(
+    client: &reqwest::Client,
+    storage: &dyn StorageBackend,
+    integration: &SlackIntegration,
+    event: &StoredEvent,
+    mut msg: serde_json::Value,
+) -> Result<(), String> {
+    let (Some(token), Some(channel)) = (&integration.bot_token, &integration.channel) else {
+        let url = integration
+            .webhook_url
+            .as_deref()
+            .ok_or("no webhook_url or bot_token configured")?;
+        let resp = client
+            .post(url)
+            .json(&msg)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("HTTP {}", resp.status().as_u16()));
+        }
+        return Ok(());
+    };
+
+    let thread = event
+        .task_id
+        .as_deref()
+        .and_then(|id| storage.get_slack_thread(None, id));
+    match thread {
+        Some((thread_channel, ts)) => {
+            msg["channel"] = serde_json::json!(thread_channel);
+            msg["thread_ts"] = serde_json::json!(ts);
+        }
+        None => msg["channel"] = serde_json::json!(channel),
+    }
+    let resp: serde_json::Value = client
+        .post(format!("{}/chat.postMessage", api_url()))
+        .bearer_auth(token)
+        .json(&msg)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    if resp["ok"] != true {
+        return Err(resp["error"]
+            .as_str()
+            .unwrap_or("unknown error")
+            .to_string());
+    }
+    // The first message about a task opens its thread
+    if msg.get("thread_ts").is_none() {
+        if let (Some(task_id), Some(channel), Some(ts)) = (
+            event.task_id.as_deref(),
+            resp["channel"].as_str(),
+            resp["ts"].as_str(),
+        ) {
+            storage.record_slack_thread(None, task_id, channel, ts);
+        }
+    }
+    Ok(())
+}
+
+/// Check Slack's `X-Slack-Signature` (`v0=` + HMAC-SHA256 of `v0:<timestamp>:<body>`)
+/// and that `X-Slack-Request-Timestamp` is recent.
+pub fn verify_signature(
+    signing_secret: &str,
+    timestamp: &str,
+    signature: &str,
+    body: &[u8],
+    now: i64,
+) -> bool {
+    let Ok(ts) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now - ts).abs() > MAX_SIGNATURE_AGE_SECS {
+        return false;
+    }
+    constant_time_eq(&sign(signing_secret, timestamp, body), signature)
+}
+
+/// The `X-Slack-Signature` Slack would send for `body` at `timestamp`.
+pub fn sign(signing_secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut base = format!("v0:{}:", timestamp).into_bytes();
+    base.extend_from_slice(body);
+    format!(
+        "v0={}",
+        hex::encode(hmac_sha256(signing_secret.as_bytes(), &base))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integration() -> SlackIntegration {
+        SlackIntegration {
+            project_id: "p1".to_string(),
+            webhook_url: Some("https://hooks.slack.com/services/x".to_string()),
+            bot_token: None,
+            channel: None,
+            signing_secret: None,
+            events: vec!["task.completed".to_string()],
+            dashboard_url: Some("https://gate.example.com".to_string()),
+            threaded: false,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn formats_task_events() {
+        let event = StoredEvent {
+            id: 7,
+            event_type: "task.review_requested".to_string(),
+            task_id: Some("t1".to_string()),
+            project_id: "p1".to_string(),
+            actor_type: "agent".to_string(),
+            actor_id: "a1".to_string(),
+            payload: serde_json::json!({
+                "task_title": "Fix <login>",
+                "actor_name": "builder",
+                "status_change": {"from": "in_progress", "to": "review"},
+            }),
+            created_at: String::new(),
+        };
+        let msg = message(&integration(), &event, None);
+        assert_eq!(
+            msg["blocks"][0]["text"]["text"],
+            "*Review requested*  <https://gate.example.com/tasks/t1|Fix &lt;login&gt;>"
+        );
+        assert_eq!(
+            msg["blocks"][1]["elements"][0]["text"],
+            "Status: *review*  ·  By: builder  ·  `t1`"
+        );
+        assert_eq!(msg["text"], "Review requested: Fix <login> (review)");
+    }
+
+    #[test]
+    fn filters_event_types() {
+        let mut slack = integration();
+        assert!(wants(&slack, "task.completed"));
+        assert!(!wants(&slack, "task.claimed"));
+        slack.events = vec!["*".to_string()];
+        assert!(wants(&slack, "task.claimed"));
+    }
+
+    #[test]
+    fn verifies_slack_signatures() {
+        let body = br#"{"type":"event_callback"}"#;
+        let signature = sign("secret", "1700000000", body);
+        assert!(verify_signature(
+            "secret",
+            "1700000000",
+            &signature,
+            body,
+            1700000010
+        ));
+        assert!(!verify_signature(
+            "other",
+            "1700000000",
+            &signature,
+            body,
+            1700000010
+        ));
+        // Replayed too late
+        assert!(!verify_signature(
+            "secret",
+            "1700000000",
+            &signature,
+            body,
+            1700001000
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod sqlite;
 
+use chrono::NaiveDate;
 use opengate_models::*;
 
 /// Error type for storage operations.
@@ -22,8 +23,8 @@ impl From<String> for StorageError {
 
 // --- Storage Traits ---
 // Each trait covers a domain. All methods take `tenant: Option<&str>`:
-// - `None` for single-tenant (OSS standalone)
-// - `Some(id)` for multi-tenant (product)
+// - `None` for unscoped callers (single-tenant installs, background jobs)
+// - `Some(id)` for a tenant: the owner of the calling agent, or the product's org
 
 pub trait ProjectStore: Send + Sync {
     fn create_project(
@@ -41,6 +42,11 @@ pub trait ProjectStore: Send + Sync {
         input: &UpdateProject,
     ) -> Option<Project>;
     fn archive_project(&self, tenant: Option<&str>, id: &str) -> bool;
+    /// Whether `tenant` may see the project; unlike `get_project` this includes the
+    /// hidden shared-knowledge scopes.
+    fn project_in_tenant(&self, tenant: Option<&str>, project_id: &str) -> bool;
+    /// The tenant a project (and so its tasks and events) belongs to; None when unowned.
+    fn project_owner(&self, project_id: &str) -> Option<String>;
     fn get_project_with_stats(&self, tenant: Option<&str>, id: &str) -> Option<ProjectWithStats>;
     fn get_schedule(
         &self,
@@ -104,6 +110,19 @@ pub trait TaskStore: Send + Sync {
     fn transition_ready_scheduled_tasks(&self, tenant: Option<&str>) -> usize;
     fn create_next_recurrence(&self, tenant: Option<&str>, completed_task: &Task)
         -> Option<String>;
+    fn set_recurrence_paused(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        paused: bool,
+    ) -> Result<Task, StorageError>;
+    fn skip_next_recurrence(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        actor_type: &str,
+        actor_id: &str,
+    ) -> Result<(Task, Option<Task>), StorageError>;
     fn append_status_history(
         &self,
         tenant: Option<&str>,
@@ -176,6 +195,16 @@ pub trait TaskStore: Send + Sync {
         caller_id: &str,
         caller_type: &str,
     ) -> Result<Task, StorageError>;
+    /// Record tokens and spend `agent_id` reports against a task, snapshotting the
+    /// agent's cost tier and model.
+    fn record_task_usage(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        agent_id: &str,
+        input: &ReportUsage,
+    ) -> TaskUsage;
+    fn list_task_usage(&self, tenant: Option<&str>, task_id: &str) -> Vec<TaskUsage>;
 }
 
 pub trait AgentStore: Send + Sync {
@@ -186,10 +215,45 @@ pub trait AgentStore: Send + Sync {
     fn list_agents_by_owner(&self, tenant: Option<&str>, owner_id: &str) -> Vec<Agent>;
     fn update_agent(&self, tenant: Option<&str>, id: &str, input: &UpdateAgent) -> Option<Agent>;
     fn delete_agent(&self, tenant: Option<&str>, id: &str) -> bool;
+    fn offboard_agent(
+        &self,
+        tenant: Option<&str>,
+        id: &str,
+        strategy: Option<&AssignStrategy>,
+    ) -> Result<OffboardResult, StorageError>;
+    fn create_api_key(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        input: &CreateApiKey,
+    ) -> Result<(ApiKey, String), StorageError>;
+    fn list_api_keys(&self, tenant: Option<&str>, agent_id: &str) -> Vec<ApiKey>;
+    fn revoke_api_key(&self, tenant: Option<&str>, agent_id: &str, key_id: &str) -> bool;
+    /// Resolve an additional (scoped) key. Primary keys go through `get_agent_by_key_hash`.
+    fn get_api_key_by_hash(&self, tenant: Option<&str>, hash: &str) -> Option<ApiKey>;
     fn update_heartbeat(&self, tenant: Option<&str>, agent_id: &str) -> bool;
+    fn update_heartbeat_status(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        input: &HeartbeatRequest,
+    ) -> bool;
+    fn sweep_agent_presence(&self, tenant: Option<&str>) -> Vec<PresenceChange>;
     fn find_best_agent(&self, tenant: Option<&str>, strategy: &AssignStrategy) -> Option<String>;
     fn get_agent_name(&self, tenant: Option<&str>, agent_id: &str) -> Option<String>;
     fn get_agent_inbox(&self, tenant: Option<&str>, agent_id: &str) -> AgentInbox;
+    fn suggest_skills(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        min_tasks: i64,
+    ) -> Option<SuggestedSkills>;
+    fn apply_suggested_skills(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        min_tasks: i64,
+    ) -> Option<Agent>;
 }
 
 pub trait ActivityStore: Send + Sync {
@@ -235,6 +299,74 @@ pub trait KnowledgeStore: Send + Sync {
         category: Option<&str>,
     ) -> Vec<KnowledgeEntry>;
     fn delete_knowledge(&self, tenant: Option<&str>, project_id: &str, key: &str) -> bool;
+    fn list_knowledge_versions(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+    ) -> Vec<KnowledgeVersion>;
+    fn revert_knowledge(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        version: i64,
+        author_type: &str,
+        author_id: &str,
+    ) -> Result<KnowledgeEntry, StorageError>;
+    fn list_knowledge_backlinks(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+    ) -> Option<Vec<KnowledgeSummary>>;
+    fn get_knowledge_graph(&self, tenant: Option<&str>, project_id: &str) -> KnowledgeGraph;
+    fn record_knowledge_read(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        reader_type: &str,
+        reader_id: &str,
+    );
+    fn get_knowledge_usage_stats(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        limit: i64,
+        reader_type: Option<&str>,
+    ) -> KnowledgeUsageStats;
+    /// Mark entries past their review deadline and emit `knowledge.stale` for each.
+    fn sweep_stale_knowledge(
+        &self,
+        tenant: Option<&str>,
+    ) -> (Vec<KnowledgeEntry>, Vec<PendingNotifWebhook>);
+    fn create_knowledge_attachment(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        input: &CreateArtifact,
+        author_type: &str,
+        author_id: &str,
+    ) -> Option<KnowledgeAttachment>;
+    fn delete_knowledge_attachment(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        attachment_id: &str,
+    ) -> bool;
+    fn list_task_knowledge(&self, tenant: Option<&str>, task_id: &str) -> Vec<KnowledgeSummary>;
+    fn link_task_knowledge(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        key: &str,
+        linked_by_type: &str,
+        linked_by_id: &str,
+    ) -> Result<Vec<KnowledgeSummary>, StorageError>;
+    fn unlink_task_knowledge(&self, tenant: Option<&str>, task_id: &str, key: &str) -> bool;
 }
 
 pub trait ArtifactStore: Send + Sync {
@@ -258,10 +390,12 @@ pub trait ArtifactStore: Send + Sync {
 }
 
 pub trait QuestionStore: Send + Sync {
+    /// `task_id: None` asks a project-level question
     fn create_question(
         &self,
         tenant: Option<&str>,
-        task_id: &str,
+        project_id: &str,
+        task_id: Option<&str>,
         input: &CreateQuestion,
         asked_by_type: &str,
         asked_by_id: &str,
@@ -304,6 +438,21 @@ pub trait QuestionStore: Send + Sync {
         author_id: &str,
     ) -> QuestionReply;
     fn list_replies(&self, tenant: Option<&str>, question_id: &str) -> Vec<QuestionReply>;
+    fn list_question_attachments(
+        &self,
+        tenant: Option<&str>,
+        question_id: &str,
+    ) -> Vec<QuestionAttachment>;
+    /// Attach to the question, or to `reply_id`. `None` if the reply isn't on the question.
+    fn create_question_attachment(
+        &self,
+        tenant: Option<&str>,
+        question_id: &str,
+        reply_id: Option<&str>,
+        input: &CreateArtifact,
+        author_type: &str,
+        author_id: &str,
+    ) -> Option<QuestionAttachment>;
     fn dismiss_question(
         &self,
         tenant: Option<&str>,
@@ -328,6 +477,12 @@ pub trait QuestionStore: Send + Sync {
         question_id: &str,
         required_capability: &str,
     ) -> Vec<CapabilityTarget>;
+    /// Move questions off targets that went quiet for `window_minutes`
+    fn reroute_inactive_questions(
+        &self,
+        tenant: Option<&str>,
+        window_minutes: i64,
+    ) -> (Vec<QuestionReroute>, Vec<PendingNotifWebhook>);
 }
 
 pub trait EventStore: Send + Sync {
@@ -342,7 +497,25 @@ pub trait EventStore: Send + Sync {
         actor_id: &str,
         payload: &serde_json::Value,
     ) -> Vec<PendingNotifWebhook>;
+    /// `emit_event`, also returning the stored event's id
+    #[allow(clippy::too_many_arguments)]
+    fn record_event(
+        &self,
+        tenant: Option<&str>,
+        event_type: &str,
+        task_id: Option<&str>,
+        project_id: &str,
+        actor_type: &str,
+        actor_id: &str,
+        payload: &serde_json::Value,
+    ) -> (i64, Vec<PendingNotifWebhook>);
     fn get_last_event_id(&self, tenant: Option<&str>) -> i64;
+    /// Re-run notification routing over stored events (see `EventReplayRequest`)
+    fn replay_events(
+        &self,
+        tenant: Option<&str>,
+        input: &EventReplayRequest,
+    ) -> (EventReplayResult, Vec<PendingNotifWebhook>);
     #[allow(clippy::too_many_arguments)]
     fn insert_question_notification(
         &self,
@@ -360,15 +533,45 @@ pub trait EventStore: Send + Sync {
         agent_id: &str,
         unread: Option<bool>,
     ) -> Vec<Notification>;
+    /// The agent's notifications with an id above `after_id`, oldest first
+    fn list_notifications_after(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        after_id: i64,
+        limit: i64,
+    ) -> Vec<Notification>;
+    fn get_last_notification_id(&self, tenant: Option<&str>, agent_id: &str) -> i64;
     fn ack_notification(&self, tenant: Option<&str>, agent_id: &str, notification_id: i64) -> bool;
     fn ack_all_notifications(&self, tenant: Option<&str>, agent_id: &str) -> i64;
     fn ack_notification_system(&self, tenant: Option<&str>, notification_id: i64);
+    fn sweep_notification_policies(&self, tenant: Option<&str>) -> NotificationSweep;
+    /// Delete history older than the policy allows.
+    fn prune_history(&self, tenant: Option<&str>, policy: &RetentionPolicy) -> RetentionSweep;
     fn update_notification_webhook_status(
         &self,
         tenant: Option<&str>,
         notification_id: i64,
         status: &str,
     );
+    /// Queue an event for the Kafka sink; it stays until acknowledged.
+    fn enqueue_outbox_event(
+        &self,
+        tenant: Option<&str>,
+        topic: &str,
+        event_key: Option<&str>,
+        payload: &str,
+    ) -> i64;
+    fn list_outbox_events(&self, tenant: Option<&str>, limit: i64) -> Vec<OutboxEvent>;
+    fn ack_outbox_events(&self, tenant: Option<&str>, ids: &[i64]);
+    fn fail_outbox_events(&self, tenant: Option<&str>, ids: &[i64], error: &str);
+    /// Persisted events after `after_id`, oldest first
+    fn list_events_after(
+        &self,
+        tenant: Option<&str>,
+        after_id: i64,
+        limit: i64,
+    ) -> Vec<StoredEvent>;
 }
 
 pub trait WebhookStore: Send + Sync {
@@ -376,9 +579,7 @@ pub trait WebhookStore: Send + Sync {
         &self,
         tenant: Option<&str>,
         project_id: &str,
-        name: &str,
-        action_type: &str,
-        action_config: &serde_json::Value,
+        input: &CreateTriggerRequest,
     ) -> (WebhookTrigger, String);
     fn list_webhook_triggers(&self, tenant: Option<&str>, project_id: &str) -> Vec<WebhookTrigger>;
     fn get_webhook_trigger_for_validation(
@@ -413,6 +614,16 @@ pub trait WebhookStore: Send + Sync {
         trigger_id: &str,
         limit: i64,
     ) -> Vec<WebhookTriggerLog>;
+    /// Record a delivery's dedup value; false if it was already seen within the window
+    fn claim_trigger_delivery(
+        &self,
+        tenant: Option<&str>,
+        trigger_id: &str,
+        dedup_value: &str,
+        window_hours: i64,
+    ) -> bool;
+    fn release_trigger_delivery(&self, tenant: Option<&str>, trigger_id: &str, dedup_value: &str);
+    fn count_trigger_runs_since(&self, tenant: Option<&str>, trigger_id: &str, since: &str) -> i64;
     fn create_webhook_log(
         &self,
         tenant: Option<&str>,
@@ -429,10 +640,169 @@ pub trait WebhookStore: Send + Sync {
         response_status: Option<i64>,
         response_body: Option<&str>,
     );
+    fn get_slack_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<SlackIntegration>;
+    fn set_slack_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        input: &SetSlackIntegration,
+    ) -> SlackIntegration;
+    fn delete_slack_integration(&self, tenant: Option<&str>, project_id: &str) -> bool;
+    fn get_github_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<GithubIntegration>;
+    fn set_github_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        input: &SetGithubIntegration,
+    ) -> GithubIntegration;
+    fn delete_github_integration(&self, tenant: Option<&str>, project_id: &str) -> bool;
+    fn get_gitlab_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<GitlabIntegration>;
+    fn set_gitlab_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        input: &SetGitlabIntegration,
+    ) -> GitlabIntegration;
+    fn delete_gitlab_integration(&self, tenant: Option<&str>, project_id: &str) -> bool;
+    fn get_linear_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<LinearIntegration>;
+    fn set_linear_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        input: &SetLinearIntegration,
+    ) -> LinearIntegration;
+    fn delete_linear_integration(&self, tenant: Option<&str>, project_id: &str) -> bool;
+    fn record_slack_thread(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        channel: &str,
+        thread_ts: &str,
+    );
+    /// The `(channel, thread_ts)` a task's Slack messages go to
+    fn get_slack_thread(&self, tenant: Option<&str>, task_id: &str) -> Option<(String, String)>;
+    /// Phone push target of an agent or user (`subject_type` is "agent" or "user")
+    fn get_push_target(
+        &self,
+        tenant: Option<&str>,
+        subject_type: &str,
+        subject_id: &str,
+    ) -> Option<PushTarget>;
+    fn set_push_target(
+        &self,
+        tenant: Option<&str>,
+        subject_type: &str,
+        subject_id: &str,
+        input: &PushTarget,
+    ) -> PushTarget;
+    fn delete_push_target(
+        &self,
+        tenant: Option<&str>,
+        subject_type: &str,
+        subject_id: &str,
+    ) -> bool;
+    fn find_slack_thread_task(
+        &self,
+        tenant: Option<&str>,
+        channel: &str,
+        thread_ts: &str,
+    ) -> Option<String>;
 }
 
 pub trait StatsStore: Send + Sync {
-    fn get_stats(&self, tenant: Option<&str>) -> DashboardStats;
+    fn get_stats(&self, tenant: Option<&str>, filter: &StatsQuery) -> DashboardStats;
+    /// Counts for `[tenant_limits]`, without the limits or webhook deliveries filled in.
+    fn get_tenant_usage(&self, tenant: &str) -> TenantUsage;
+    /// `metric` per `interval` bucket from `from` through `to` (UTC days). Callers pass
+    /// a known metric and interval.
+    fn get_timeseries(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        metric: &str,
+        interval: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Timeseries;
+    /// Every agent's task counts and completions over the last `days` days.
+    fn get_agent_workloads(&self, tenant: Option<&str>, days: i64) -> AgentWorkloadStats;
+    /// Daily open work and per-iteration completions for one project, replayed from
+    /// its tasks' status history.
+    fn get_burndown(
+        &self,
+        project_id: &str,
+        by_estimate: bool,
+        iteration_days: i64,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Burndown;
+    /// Review turnaround per reviewer and per project over the last `days` days.
+    fn get_review_latency(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        days: i64,
+    ) -> ReviewLatencyReport;
+    /// Reported usage per `interval` from `from` through `to`, grouped by `group_by`
+    /// (cost_tier, model, project or agent). Callers pass a known grouping and interval.
+    fn get_cost_report(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        group_by: &str,
+        interval: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> CostReport;
+    /// Cumulative blocked and dependency-wait time per task and per project, with the
+    /// dependencies holding the most work up; lists of tasks cut to `limit`.
+    fn get_blocked_time(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        limit: usize,
+    ) -> BlockedTimeReport;
+}
+
+pub trait SettingsStore: Send + Sync {
+    /// The current runtime settings. Cheap: backends are expected to cache them.
+    fn get_settings(&self, tenant: Option<&str>) -> RuntimeSettings;
+    fn update_settings(
+        &self,
+        tenant: Option<&str>,
+        input: &UpdateSettings,
+        updated_by: &str,
+    ) -> Result<RuntimeSettings, StorageError>;
+}
+
+/// The tenant registry. Managed by operators outside any tenant, so unscoped.
+pub trait TenantStore: Send + Sync {
+    fn create_tenant(&self, input: &CreateTenant) -> Result<Tenant, StorageError>;
+    fn get_tenant(&self, id: &str) -> Option<Tenant>;
+    fn list_tenants(&self) -> Vec<Tenant>;
+    /// Whether any tenant exists, registered or as the owner of an agent or project.
+    fn has_tenants(&self) -> bool;
+    fn set_tenant_status(&self, id: &str, status: &str) -> Option<Tenant>;
+    /// Delete the tenant and everything it owns.
+    fn delete_tenant(&self, id: &str) -> bool;
+    fn create_tenant_setup_token(&self, tenant_id: &str) -> TenantSetupToken;
+    fn get_tenant_by_setup_token(&self, token: &str) -> Option<Tenant>;
 }
 
 /// Super-trait combining all domain stores.
@@ -447,6 +817,8 @@ pub trait StorageBackend:
     + EventStore
     + WebhookStore
     + StatsStore
+    + SettingsStore
+    + TenantStore
 {
     /// Hash an API key (utility, doesn't need &self but lives here for convenience).
     fn hash_api_key(&self, key: &str) -> String;
@@ -456,6 +828,7 @@ pub trait StorageBackend:
     fn get_task_full(&self, tenant: Option<&str>, id: &str) -> Option<Task> {
         let mut task = self.get_task(tenant, id)?;
         task.activities = self.list_activity(tenant, &task.id);
+        task.knowledge = self.list_task_knowledge(tenant, &task.id);
 
         // Enrich context with project repo info if not already set
         if let Some(project) = self.get_project(tenant, &task.project_id) {
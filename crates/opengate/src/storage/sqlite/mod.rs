@@ -1,140 +1,199 @@
+use chrono::NaiveDate;
 use opengate_models::*;
 use rusqlite::Connection;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
 use crate::db_ops;
 use crate::storage::*;
 
 /// SQLite-backed storage implementation.
 /// Wraps a `Mutex<Connection>` and delegates to existing `db_ops` functions.
-/// The `tenant` parameter is ignored in single-tenant (OSS) mode.
+/// Lookups by id are checked against `tenant` through the project, task or agent the
+/// row belongs to (see `db_ops::project_in_tenant` and friends); `None` is unscoped.
 pub struct SqliteBackend {
     pub conn: Arc<Mutex<Connection>>,
+    /// Runtime settings, read once and replaced on every update
+    settings: RwLock<RuntimeSettings>,
+    /// Most occurrences one recurring series may reach, root included
+    max_recurrence_occurrences: i64,
 }
 
 impl SqliteBackend {
     pub fn new(conn: Arc<Mutex<Connection>>) -> Self {
-        Self { conn }
+        let settings = {
+            let mut conn = conn.lock().unwrap();
+            conn.profile(Some(profile));
+            db_ops::get_settings(&conn)
+        };
+        Self {
+            conn,
+            settings: RwLock::new(settings),
+            max_recurrence_occurrences: crate::recurrence::DEFAULT_MAX_OCCURRENCES,
+        }
+    }
+
+    /// Cap recurring series at `max` occurrences; values below 1 stop auto-creation.
+    pub fn with_max_recurrence_occurrences(mut self, max: i64) -> Self {
+        self.max_recurrence_occurrences = max;
+        self
+    }
+
+    fn settings(&self) -> RuntimeSettings {
+        self.settings.read().unwrap().clone()
     }
 
     fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
         self.conn.lock().unwrap()
     }
+
+    /// The connection, if `visible` lets `tenant` see the row with this id.
+    fn scoped(
+        &self,
+        visible: fn(&Connection, Option<&str>, &str) -> bool,
+        tenant: Option<&str>,
+        id: &str,
+    ) -> Option<std::sync::MutexGuard<'_, Connection>> {
+        let conn = self.lock();
+        visible(&conn, tenant, id).then_some(conn)
+    }
+}
+
+/// Time every statement for the query diagnostics; those run inside a traced request
+/// also become spans of it.
+fn profile(sql: &str, took: std::time::Duration) {
+    crate::query_stats::record(sql, took);
+    crate::telemetry::record_sql(sql, took);
 }
 
 impl ProjectStore for SqliteBackend {
     fn create_project(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         input: &CreateProject,
         created_by: &str,
     ) -> Project {
-        db_ops::create_project(&self.lock(), _tenant, input, created_by)
+        db_ops::create_project(&self.lock(), tenant, input, created_by)
     }
-    fn get_project(&self, _tenant: Option<&str>, id: &str) -> Option<Project> {
-        db_ops::get_project(&self.lock(), _tenant, id)
+    fn get_project(&self, tenant: Option<&str>, id: &str) -> Option<Project> {
+        db_ops::get_project(&self.lock(), tenant, id)
     }
-    fn list_projects(&self, _tenant: Option<&str>, status_filter: Option<&str>) -> Vec<Project> {
-        db_ops::list_projects(&self.lock(), _tenant, status_filter)
+    fn list_projects(&self, tenant: Option<&str>, status_filter: Option<&str>) -> Vec<Project> {
+        db_ops::list_projects(&self.lock(), tenant, status_filter)
     }
     fn update_project(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         id: &str,
         input: &UpdateProject,
     ) -> Option<Project> {
-        db_ops::update_project(&self.lock(), _tenant, id, input)
+        db_ops::update_project(&self.lock(), tenant, id, input)
+    }
+    fn archive_project(&self, tenant: Option<&str>, id: &str) -> bool {
+        db_ops::archive_project(&self.lock(), tenant, id)
     }
-    fn archive_project(&self, _tenant: Option<&str>, id: &str) -> bool {
-        db_ops::archive_project(&self.lock(), _tenant, id)
+    fn project_in_tenant(&self, tenant: Option<&str>, project_id: &str) -> bool {
+        db_ops::project_in_tenant(&self.lock(), tenant, project_id)
     }
-    fn get_project_with_stats(&self, _tenant: Option<&str>, id: &str) -> Option<ProjectWithStats> {
-        db_ops::get_project_with_stats(&self.lock(), _tenant, id)
+    fn project_owner(&self, project_id: &str) -> Option<String> {
+        db_ops::project_owner(&self.lock(), project_id)
+    }
+    fn get_project_with_stats(&self, tenant: Option<&str>, id: &str) -> Option<ProjectWithStats> {
+        db_ops::get_project_with_stats(&self.lock(), tenant, id)
     }
     fn get_schedule(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         project_id: &str,
         from: Option<&str>,
         to: Option<&str>,
     ) -> Vec<ScheduledTaskEntry> {
-        db_ops::get_schedule(&self.lock(), _tenant, project_id, from, to)
+        db_ops::get_schedule(&self.lock(), tenant, project_id, from, to)
     }
     fn get_pulse(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         project_id: &str,
         caller_agent_id: Option<&str>,
     ) -> PulseResponse {
-        db_ops::get_pulse(&self.lock(), _tenant, project_id, caller_agent_id)
+        db_ops::get_pulse(&self.lock(), tenant, project_id, caller_agent_id)
     }
 }
 
 impl TaskStore for SqliteBackend {
     fn create_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         project_id: &str,
         input: &CreateTask,
         created_by: &str,
     ) -> Task {
-        db_ops::create_task(&self.lock(), _tenant, project_id, input, created_by)
+        db_ops::create_task(&self.lock(), tenant, project_id, input, created_by)
     }
-    fn get_task(&self, _tenant: Option<&str>, id: &str) -> Option<Task> {
-        db_ops::get_task(&self.lock(), _tenant, id)
+    fn get_task(&self, tenant: Option<&str>, id: &str) -> Option<Task> {
+        db_ops::get_task(&self.lock(), tenant, id)
     }
-    fn list_tasks(&self, _tenant: Option<&str>, filters: &TaskFilters) -> Vec<Task> {
-        db_ops::list_tasks(&self.lock(), _tenant, filters)
+    fn list_tasks(&self, tenant: Option<&str>, filters: &TaskFilters) -> Vec<Task> {
+        db_ops::list_tasks(&self.lock(), tenant, filters)
     }
     fn update_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         id: &str,
         input: &UpdateTask,
     ) -> Result<Option<Task>, StorageError> {
-        db_ops::update_task(&self.lock(), _tenant, id, input).map_err(StorageError)
+        let settings = self.settings();
+        db_ops::update_task(&self.lock(), tenant, id, input, &settings).map_err(StorageError)
     }
-    fn delete_task(&self, _tenant: Option<&str>, id: &str) -> bool {
-        db_ops::delete_task(&self.lock(), _tenant, id)
+    fn delete_task(&self, tenant: Option<&str>, id: &str) -> bool {
+        db_ops::delete_task(&self.lock(), tenant, id)
     }
     fn claim_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         agent_id: &str,
         agent_name: &str,
     ) -> Result<Task, StorageError> {
-        db_ops::claim_task(&self.lock(), _tenant, task_id, agent_id, agent_name)
-            .map_err(StorageError)
+        let settings = self.settings();
+        db_ops::claim_task(
+            &self.lock(),
+            tenant,
+            task_id,
+            agent_id,
+            agent_name,
+            &settings,
+        )
+        .map_err(StorageError)
     }
     fn release_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         agent_id: &str,
     ) -> Result<Task, StorageError> {
-        db_ops::release_task(&self.lock(), _tenant, task_id, agent_id).map_err(StorageError)
+        db_ops::release_task(&self.lock(), tenant, task_id, agent_id).map_err(StorageError)
     }
-    fn get_next_task(&self, _tenant: Option<&str>, skills: &[String]) -> Option<Task> {
-        db_ops::get_next_task(&self.lock(), _tenant, skills)
+    fn get_next_task(&self, tenant: Option<&str>, skills: &[String]) -> Option<Task> {
+        db_ops::get_next_task(&self.lock(), tenant, skills)
     }
-    fn get_tasks_for_assignee(&self, _tenant: Option<&str>, assignee_id: &str) -> Vec<Task> {
-        db_ops::get_tasks_for_assignee(&self.lock(), _tenant, assignee_id)
+    fn get_tasks_for_assignee(&self, tenant: Option<&str>, assignee_id: &str) -> Vec<Task> {
+        db_ops::get_tasks_for_assignee(&self.lock(), tenant, assignee_id)
     }
     fn merge_context(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         patch: &serde_json::Value,
     ) -> Result<Option<Task>, StorageError> {
-        db_ops::merge_context(&self.lock(), _tenant, task_id, patch).map_err(StorageError)
+        db_ops::merge_context(&self.lock(), tenant, task_id, patch).map_err(StorageError)
     }
     fn batch_update_status(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         updates: &[(String, String)],
     ) -> BatchResult {
-        db_ops::batch_update_status(&self.lock(), _tenant, updates)
+        let settings = self.settings();
+        db_ops::batch_update_status(&self.lock(), tenant, updates, &settings)
     }
     fn release_stale_tasks(
         &self,
@@ -151,7 +210,43 @@ impl TaskStore for SqliteBackend {
         _tenant: Option<&str>,
         completed_task: &Task,
     ) -> Option<String> {
-        db_ops::create_next_recurrence(&self.lock(), completed_task)
+        db_ops::create_next_recurrence(
+            &self.lock(),
+            completed_task,
+            self.max_recurrence_occurrences,
+        )
+    }
+    fn set_recurrence_paused(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        paused: bool,
+    ) -> Result<Task, StorageError> {
+        db_ops::set_recurrence_paused(
+            &self.lock(),
+            tenant,
+            task_id,
+            paused,
+            self.max_recurrence_occurrences,
+        )
+        .map_err(StorageError)
+    }
+    fn skip_next_recurrence(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        actor_type: &str,
+        actor_id: &str,
+    ) -> Result<(Task, Option<Task>), StorageError> {
+        db_ops::skip_next_recurrence(
+            &self.lock(),
+            tenant,
+            task_id,
+            actor_type,
+            actor_id,
+            self.max_recurrence_occurrences,
+        )
+        .map_err(StorageError)
     }
     fn append_status_history(
         &self,
@@ -163,50 +258,50 @@ impl TaskStore for SqliteBackend {
     ) {
         db_ops::append_status_history(&self.lock(), task_id, new_status, agent_type, agent_id)
     }
-    fn check_dependencies(&self, _tenant: Option<&str>, task: &Task) -> Result<(), Vec<String>> {
-        db_ops::check_dependencies(&self.lock(), _tenant, task)
+    fn check_dependencies(&self, tenant: Option<&str>, task: &Task) -> Result<(), Vec<String>> {
+        db_ops::check_dependencies(&self.lock(), tenant, task)
     }
     fn add_dependency(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         depends_on_id: &str,
     ) -> Result<(), StorageError> {
-        db_ops::add_dependency(&self.lock(), _tenant, task_id, depends_on_id).map_err(StorageError)
+        db_ops::add_dependency(&self.lock(), tenant, task_id, depends_on_id).map_err(StorageError)
     }
-    fn remove_dependency(&self, _tenant: Option<&str>, task_id: &str, depends_on_id: &str) -> bool {
-        db_ops::remove_dependency(&self.lock(), _tenant, task_id, depends_on_id)
+    fn remove_dependency(&self, tenant: Option<&str>, task_id: &str, depends_on_id: &str) -> bool {
+        db_ops::remove_dependency(&self.lock(), tenant, task_id, depends_on_id)
     }
-    fn get_task_dependencies(&self, _tenant: Option<&str>, task_id: &str) -> Vec<Task> {
-        db_ops::get_task_dependencies(&self.lock(), _tenant, task_id)
+    fn get_task_dependencies(&self, tenant: Option<&str>, task_id: &str) -> Vec<Task> {
+        db_ops::get_task_dependencies(&self.lock(), tenant, task_id)
     }
-    fn get_task_dependents(&self, _tenant: Option<&str>, task_id: &str) -> Vec<Task> {
-        db_ops::get_task_dependents(&self.lock(), _tenant, task_id)
+    fn get_task_dependents(&self, tenant: Option<&str>, task_id: &str) -> Vec<Task> {
+        db_ops::get_task_dependents(&self.lock(), tenant, task_id)
     }
     fn unblock_dependents_on_complete(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         completed_task_id: &str,
     ) -> Vec<PendingNotifWebhook> {
-        db_ops::unblock_dependents_on_complete(&self.lock(), _tenant, completed_task_id)
+        db_ops::unblock_dependents_on_complete(&self.lock(), tenant, completed_task_id)
     }
-    fn all_dependencies_done(&self, _tenant: Option<&str>, task: &Task) -> bool {
-        db_ops::all_dependencies_done(&self.lock(), _tenant, task)
+    fn all_dependencies_done(&self, tenant: Option<&str>, task: &Task) -> bool {
+        db_ops::all_dependencies_done(&self.lock(), tenant, task)
     }
-    fn inject_upstream_outputs(&self, _tenant: Option<&str>, completed_task: &Task) {
-        db_ops::inject_upstream_outputs(&self.lock(), _tenant, completed_task)
+    fn inject_upstream_outputs(&self, tenant: Option<&str>, completed_task: &Task) {
+        db_ops::inject_upstream_outputs(&self.lock(), tenant, completed_task)
     }
     fn assign_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         agent_id: &str,
     ) -> Result<Task, StorageError> {
-        db_ops::assign_task(&self.lock(), _tenant, task_id, agent_id).map_err(StorageError)
+        db_ops::assign_task(&self.lock(), tenant, task_id, agent_id).map_err(StorageError)
     }
     fn handoff_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         from_agent_id: &str,
         to_agent_id: &str,
@@ -214,7 +309,7 @@ impl TaskStore for SqliteBackend {
     ) -> Result<Task, StorageError> {
         db_ops::handoff_task(
             &self.lock(),
-            _tenant,
+            tenant,
             task_id,
             from_agent_id,
             to_agent_id,
@@ -224,87 +319,184 @@ impl TaskStore for SqliteBackend {
     }
     fn approve_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         reviewer_id: &str,
         comment: Option<&str>,
     ) -> Result<Task, StorageError> {
-        db_ops::approve_task(&self.lock(), _tenant, task_id, reviewer_id, comment)
+        db_ops::approve_task(&self.lock(), tenant, task_id, reviewer_id, comment)
             .map_err(StorageError)
     }
     fn request_changes(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         reviewer_id: &str,
         comment: &str,
     ) -> Result<Task, StorageError> {
-        db_ops::request_changes(&self.lock(), _tenant, task_id, reviewer_id, comment)
+        db_ops::request_changes(&self.lock(), tenant, task_id, reviewer_id, comment)
             .map_err(StorageError)
     }
     fn submit_review_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         submitter_id: &str,
         summary: Option<&str>,
         explicit_reviewer_id: Option<&str>,
     ) -> Result<Task, StorageError> {
+        let settings = self.settings();
         db_ops::submit_review_task(
             &self.lock(),
-            _tenant,
+            tenant,
             task_id,
             submitter_id,
             summary,
             explicit_reviewer_id,
+            &settings,
         )
         .map_err(StorageError)
     }
     fn start_review_task(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         caller_id: &str,
         caller_type: &str,
     ) -> Result<Task, StorageError> {
-        db_ops::start_review_task(&self.lock(), _tenant, task_id, caller_id, caller_type)
+        db_ops::start_review_task(&self.lock(), tenant, task_id, caller_id, caller_type)
             .map_err(StorageError)
     }
+    fn record_task_usage(
+        &self,
+        _tenant: Option<&str>,
+        task_id: &str,
+        agent_id: &str,
+        input: &ReportUsage,
+    ) -> TaskUsage {
+        db_ops::record_task_usage(&self.lock(), task_id, agent_id, input)
+    }
+    fn list_task_usage(&self, tenant: Option<&str>, task_id: &str) -> Vec<TaskUsage> {
+        self.scoped(db_ops::task_in_tenant, tenant, task_id)
+            .map(|conn| db_ops::list_task_usage(&conn, task_id))
+            .unwrap_or_default()
+    }
 }
 
 impl AgentStore for SqliteBackend {
-    fn create_agent(&self, _tenant: Option<&str>, input: &CreateAgent) -> (Agent, String) {
-        db_ops::create_agent(&self.lock(), input)
-    }
-    fn get_agent(&self, _tenant: Option<&str>, id: &str) -> Option<Agent> {
-        db_ops::get_agent(&self.lock(), id)
-    }
-    fn get_agent_by_key_hash(&self, _tenant: Option<&str>, hash: &str) -> Option<Agent> {
+    fn create_agent(&self, tenant: Option<&str>, input: &CreateAgent) -> (Agent, String) {
+        // A scoped caller can only add agents to its own tenant
+        let input = CreateAgent {
+            owner_id: tenant
+                .map(str::to_string)
+                .or_else(|| input.owner_id.clone()),
+            ..input.clone()
+        };
+        db_ops::create_agent(&self.lock(), &input)
+    }
+    fn get_agent(&self, tenant: Option<&str>, id: &str) -> Option<Agent> {
+        db_ops::get_tenant_agent(&self.lock(), tenant, id)
+    }
+    fn get_agent_by_key_hash(&self, tenant: Option<&str>, hash: &str) -> Option<Agent> {
         db_ops::get_agent_by_key_hash(&self.lock(), hash)
+            .filter(|a| tenant.is_none() || a.owner_id.as_deref() == tenant)
     }
-    fn list_agents(&self, _tenant: Option<&str>) -> Vec<Agent> {
-        db_ops::list_agents(&self.lock(), _tenant)
+    fn list_agents(&self, tenant: Option<&str>) -> Vec<Agent> {
+        db_ops::list_agents(&self.lock(), tenant)
     }
-    fn list_agents_by_owner(&self, _tenant: Option<&str>, owner_id: &str) -> Vec<Agent> {
+    fn list_agents_by_owner(&self, tenant: Option<&str>, owner_id: &str) -> Vec<Agent> {
+        if tenant.is_some_and(|t| t != owner_id) {
+            return Vec::new();
+        }
         db_ops::list_agents_by_owner(&self.lock(), owner_id)
     }
-    fn update_agent(&self, _tenant: Option<&str>, id: &str, input: &UpdateAgent) -> Option<Agent> {
-        db_ops::update_agent(&self.lock(), id, input)
+    fn update_agent(&self, tenant: Option<&str>, id: &str, input: &UpdateAgent) -> Option<Agent> {
+        self.scoped(db_ops::agent_in_tenant, tenant, id)
+            .and_then(|conn| db_ops::update_agent(&conn, id, input))
     }
-    fn delete_agent(&self, _tenant: Option<&str>, id: &str) -> bool {
-        db_ops::delete_agent(&self.lock(), id)
+    fn delete_agent(&self, tenant: Option<&str>, id: &str) -> bool {
+        self.scoped(db_ops::agent_in_tenant, tenant, id)
+            .is_some_and(|conn| db_ops::delete_agent(&conn, id))
     }
-    fn update_heartbeat(&self, _tenant: Option<&str>, agent_id: &str) -> bool {
-        db_ops::update_heartbeat(&self.lock(), agent_id)
+    fn offboard_agent(
+        &self,
+        tenant: Option<&str>,
+        id: &str,
+        strategy: Option<&AssignStrategy>,
+    ) -> Result<OffboardResult, StorageError> {
+        let conn = self
+            .scoped(db_ops::agent_in_tenant, tenant, id)
+            .ok_or_else(|| StorageError("Agent not found".to_string()))?;
+        db_ops::offboard_agent(&conn, tenant, id, strategy).map_err(StorageError)
+    }
+    fn create_api_key(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        input: &CreateApiKey,
+    ) -> Result<(ApiKey, String), StorageError> {
+        let conn = self
+            .scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .ok_or_else(|| StorageError("Agent not found".to_string()))?;
+        db_ops::create_api_key(&conn, agent_id, input).map_err(StorageError)
+    }
+    fn list_api_keys(&self, tenant: Option<&str>, agent_id: &str) -> Vec<ApiKey> {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .map(|conn| db_ops::list_api_keys(&conn, agent_id))
+            .unwrap_or_default()
+    }
+    fn revoke_api_key(&self, tenant: Option<&str>, agent_id: &str, key_id: &str) -> bool {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .is_some_and(|conn| db_ops::revoke_api_key(&conn, agent_id, key_id))
+    }
+    fn get_api_key_by_hash(&self, tenant: Option<&str>, hash: &str) -> Option<ApiKey> {
+        let conn = self.lock();
+        db_ops::get_api_key_by_hash(&conn, hash)
+            .filter(|key| db_ops::agent_in_tenant(&conn, tenant, &key.agent_id))
+    }
+    fn update_heartbeat(&self, tenant: Option<&str>, agent_id: &str) -> bool {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .is_some_and(|conn| db_ops::update_heartbeat(&conn, agent_id))
+    }
+    fn update_heartbeat_status(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        input: &HeartbeatRequest,
+    ) -> bool {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .is_some_and(|conn| db_ops::update_heartbeat_status(&conn, agent_id, input))
+    }
+    fn sweep_agent_presence(&self, _tenant: Option<&str>) -> Vec<PresenceChange> {
+        db_ops::sweep_agent_presence(&self.lock())
     }
-    fn find_best_agent(&self, _tenant: Option<&str>, strategy: &AssignStrategy) -> Option<String> {
-        db_ops::find_best_agent(&self.lock(), _tenant, strategy)
+    fn find_best_agent(&self, tenant: Option<&str>, strategy: &AssignStrategy) -> Option<String> {
+        db_ops::find_best_agent(&self.lock(), tenant, strategy)
     }
-    fn get_agent_name(&self, _tenant: Option<&str>, agent_id: &str) -> Option<String> {
-        db_ops::get_agent_name(&self.lock(), agent_id)
+    fn get_agent_name(&self, tenant: Option<&str>, agent_id: &str) -> Option<String> {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .and_then(|conn| db_ops::get_agent_name(&conn, agent_id))
     }
-    fn get_agent_inbox(&self, _tenant: Option<&str>, agent_id: &str) -> AgentInbox {
-        db_ops::get_agent_inbox(&self.lock(), _tenant, agent_id)
+    fn get_agent_inbox(&self, tenant: Option<&str>, agent_id: &str) -> AgentInbox {
+        db_ops::get_agent_inbox(&self.lock(), tenant, agent_id)
+    }
+    fn suggest_skills(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        min_tasks: i64,
+    ) -> Option<SuggestedSkills> {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .and_then(|conn| db_ops::suggest_skills(&conn, agent_id, min_tasks))
+    }
+    fn apply_suggested_skills(
+        &self,
+        tenant: Option<&str>,
+        agent_id: &str,
+        min_tasks: i64,
+    ) -> Option<Agent> {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .and_then(|conn| db_ops::apply_suggested_skills(&conn, agent_id, min_tasks))
     }
 }
 
@@ -319,8 +511,10 @@ impl ActivityStore for SqliteBackend {
     ) -> TaskActivity {
         db_ops::create_activity(&self.lock(), task_id, author_type, author_id, input)
     }
-    fn list_activity(&self, _tenant: Option<&str>, task_id: &str) -> Vec<TaskActivity> {
-        db_ops::list_activity(&self.lock(), task_id)
+    fn list_activity(&self, tenant: Option<&str>, task_id: &str) -> Vec<TaskActivity> {
+        self.scoped(db_ops::task_in_tenant, tenant, task_id)
+            .map(|conn| db_ops::list_activity(&conn, task_id))
+            .unwrap_or_default()
     }
 }
 
@@ -338,32 +532,165 @@ impl KnowledgeStore for SqliteBackend {
     }
     fn get_knowledge(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         project_id: &str,
         key: &str,
     ) -> Option<KnowledgeEntry> {
-        db_ops::get_knowledge(&self.lock(), project_id, key)
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .and_then(|conn| db_ops::get_knowledge(&conn, project_id, key))
     }
     fn list_knowledge(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         project_id: &str,
         prefix: Option<&str>,
     ) -> Vec<KnowledgeEntry> {
-        db_ops::list_knowledge(&self.lock(), project_id, prefix)
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .map(|conn| db_ops::list_knowledge(&conn, project_id, prefix))
+            .unwrap_or_default()
     }
     fn search_knowledge(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         project_id: &str,
         query: &str,
         tag_list: &[String],
         category: Option<&str>,
     ) -> Vec<KnowledgeEntry> {
-        db_ops::search_knowledge(&self.lock(), project_id, query, tag_list, category)
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .map(|conn| db_ops::search_knowledge(&conn, project_id, query, tag_list, category))
+            .unwrap_or_default()
+    }
+    fn delete_knowledge(&self, tenant: Option<&str>, project_id: &str, key: &str) -> bool {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .is_some_and(|conn| db_ops::delete_knowledge(&conn, project_id, key))
+    }
+    fn list_knowledge_versions(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+    ) -> Vec<KnowledgeVersion> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .map(|conn| db_ops::list_knowledge_versions(&conn, project_id, key))
+            .unwrap_or_default()
+    }
+    fn revert_knowledge(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        version: i64,
+        author_type: &str,
+        author_id: &str,
+    ) -> Result<KnowledgeEntry, StorageError> {
+        let conn = self
+            .scoped(db_ops::project_in_tenant, tenant, project_id)
+            .ok_or_else(|| StorageError("Knowledge entry not found".to_string()))?;
+        db_ops::revert_knowledge(&conn, project_id, key, version, author_type, author_id)
+            .map_err(StorageError)
+    }
+    fn list_knowledge_backlinks(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+    ) -> Option<Vec<KnowledgeSummary>> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .and_then(|conn| db_ops::list_knowledge_backlinks(&conn, project_id, key))
+    }
+    fn get_knowledge_graph(&self, tenant: Option<&str>, project_id: &str) -> KnowledgeGraph {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .map(|conn| db_ops::get_knowledge_graph(&conn, project_id))
+            .unwrap_or_default()
+    }
+    fn record_knowledge_read(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        reader_type: &str,
+        reader_id: &str,
+    ) {
+        if let Some(conn) = self.scoped(db_ops::project_in_tenant, tenant, project_id) {
+            db_ops::record_knowledge_read(&conn, project_id, key, reader_type, reader_id)
+        }
+    }
+    fn get_knowledge_usage_stats(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        limit: i64,
+        reader_type: Option<&str>,
+    ) -> KnowledgeUsageStats {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .map(|conn| db_ops::get_knowledge_usage_stats(&conn, project_id, limit, reader_type))
+            .unwrap_or_default()
+    }
+    fn sweep_stale_knowledge(
+        &self,
+        _tenant: Option<&str>,
+    ) -> (Vec<KnowledgeEntry>, Vec<PendingNotifWebhook>) {
+        db_ops::sweep_stale_knowledge(&self.lock())
+    }
+    fn create_knowledge_attachment(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        input: &CreateArtifact,
+        author_type: &str,
+        author_id: &str,
+    ) -> Option<KnowledgeAttachment> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .and_then(|conn| {
+                db_ops::create_knowledge_attachment(
+                    &conn,
+                    project_id,
+                    key,
+                    input,
+                    author_type,
+                    author_id,
+                )
+            })
+    }
+    fn delete_knowledge_attachment(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+        key: &str,
+        attachment_id: &str,
+    ) -> bool {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .is_some_and(|conn| {
+                db_ops::delete_knowledge_attachment(&conn, project_id, key, attachment_id)
+            })
     }
-    fn delete_knowledge(&self, _tenant: Option<&str>, project_id: &str, key: &str) -> bool {
-        db_ops::delete_knowledge(&self.lock(), project_id, key)
+    fn list_task_knowledge(&self, tenant: Option<&str>, task_id: &str) -> Vec<KnowledgeSummary> {
+        self.scoped(db_ops::task_in_tenant, tenant, task_id)
+            .map(|conn| db_ops::list_task_knowledge(&conn, task_id))
+            .unwrap_or_default()
+    }
+    fn link_task_knowledge(
+        &self,
+        tenant: Option<&str>,
+        task_id: &str,
+        key: &str,
+        linked_by_type: &str,
+        linked_by_id: &str,
+    ) -> Result<Vec<KnowledgeSummary>, StorageError> {
+        db_ops::link_task_knowledge(
+            &self.lock(),
+            tenant,
+            task_id,
+            key,
+            linked_by_type,
+            linked_by_id,
+        )
+        .map_err(StorageError)
+    }
+    fn unlink_task_knowledge(&self, tenant: Option<&str>, task_id: &str, key: &str) -> bool {
+        db_ops::unlink_task_knowledge(&self.lock(), tenant, task_id, key)
     }
 }
 
@@ -378,22 +705,27 @@ impl ArtifactStore for SqliteBackend {
     ) -> TaskArtifact {
         db_ops::create_artifact(&self.lock(), task_id, input, author_type, author_id)
     }
-    fn list_artifacts(&self, _tenant: Option<&str>, task_id: &str) -> Vec<TaskArtifact> {
-        db_ops::list_artifacts(&self.lock(), task_id)
+    fn list_artifacts(&self, tenant: Option<&str>, task_id: &str) -> Vec<TaskArtifact> {
+        self.scoped(db_ops::task_in_tenant, tenant, task_id)
+            .map(|conn| db_ops::list_artifacts(&conn, task_id))
+            .unwrap_or_default()
     }
-    fn get_artifact(&self, _tenant: Option<&str>, artifact_id: &str) -> Option<TaskArtifact> {
-        db_ops::get_artifact(&self.lock(), artifact_id)
+    fn get_artifact(&self, tenant: Option<&str>, artifact_id: &str) -> Option<TaskArtifact> {
+        self.scoped(db_ops::artifact_in_tenant, tenant, artifact_id)
+            .and_then(|conn| db_ops::get_artifact(&conn, artifact_id))
     }
-    fn delete_artifact(&self, _tenant: Option<&str>, artifact_id: &str) -> bool {
-        db_ops::delete_artifact(&self.lock(), artifact_id)
+    fn delete_artifact(&self, tenant: Option<&str>, artifact_id: &str) -> bool {
+        self.scoped(db_ops::artifact_in_tenant, tenant, artifact_id)
+            .is_some_and(|conn| db_ops::delete_artifact(&conn, artifact_id))
     }
     fn update_artifact(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         artifact_id: &str,
         input: &UpdateArtifact,
     ) -> Option<TaskArtifact> {
-        db_ops::update_artifact(&self.lock(), artifact_id, input)
+        self.scoped(db_ops::artifact_in_tenant, tenant, artifact_id)
+            .and_then(|conn| db_ops::update_artifact(&conn, artifact_id, input))
     }
 }
 
@@ -401,56 +733,74 @@ impl QuestionStore for SqliteBackend {
     fn create_question(
         &self,
         _tenant: Option<&str>,
-        task_id: &str,
+        project_id: &str,
+        task_id: Option<&str>,
         input: &CreateQuestion,
         asked_by_type: &str,
         asked_by_id: &str,
     ) -> TaskQuestion {
-        db_ops::create_question(&self.lock(), task_id, input, asked_by_type, asked_by_id)
+        db_ops::create_question(
+            &self.lock(),
+            project_id,
+            task_id,
+            input,
+            asked_by_type,
+            asked_by_id,
+        )
     }
-    fn get_question(&self, _tenant: Option<&str>, id: &str) -> Option<TaskQuestion> {
-        db_ops::get_question(&self.lock(), id)
+    fn get_question(&self, tenant: Option<&str>, id: &str) -> Option<TaskQuestion> {
+        self.scoped(db_ops::question_in_tenant, tenant, id)
+            .and_then(|conn| db_ops::get_question(&conn, id))
     }
     fn list_questions(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         task_id: &str,
         status: Option<&str>,
     ) -> Vec<TaskQuestion> {
-        db_ops::list_questions(&self.lock(), task_id, status)
+        self.scoped(db_ops::task_in_tenant, tenant, task_id)
+            .map(|conn| db_ops::list_questions(&conn, task_id, status))
+            .unwrap_or_default()
     }
     fn list_questions_for_agent(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         agent_id: &str,
         status: Option<&str>,
     ) -> Vec<TaskQuestion> {
-        db_ops::list_questions_for_agent(&self.lock(), agent_id, status)
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .map(|conn| db_ops::list_questions_for_agent(&conn, agent_id, status))
+            .unwrap_or_default()
     }
     fn list_questions_for_project(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         project_id: &str,
         status: Option<&str>,
         unrouted: bool,
     ) -> Vec<TaskQuestion> {
-        db_ops::list_questions_for_project(&self.lock(), project_id, status, unrouted)
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .map(|conn| db_ops::list_questions_for_project(&conn, project_id, status, unrouted))
+            .unwrap_or_default()
     }
     fn resolve_question(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         question_id: &str,
         resolution: &str,
         resolved_by_type: &str,
         resolved_by_id: &str,
     ) -> Option<TaskQuestion> {
-        db_ops::resolve_question(
-            &self.lock(),
-            question_id,
-            resolution,
-            resolved_by_type,
-            resolved_by_id,
-        )
+        self.scoped(db_ops::question_in_tenant, tenant, question_id)
+            .and_then(|conn| {
+                db_ops::resolve_question(
+                    &conn,
+                    question_id,
+                    resolution,
+                    resolved_by_type,
+                    resolved_by_id,
+                )
+            })
     }
     fn recalculate_has_open_questions(&self, _tenant: Option<&str>, task_id: &str) {
         db_ops::recalculate_has_open_questions(&self.lock(), task_id)
@@ -465,40 +815,86 @@ impl QuestionStore for SqliteBackend {
     ) -> QuestionReply {
         db_ops::create_reply(&self.lock(), question_id, input, author_type, author_id)
     }
-    fn list_replies(&self, _tenant: Option<&str>, question_id: &str) -> Vec<QuestionReply> {
-        db_ops::list_replies(&self.lock(), question_id)
+    fn list_replies(&self, tenant: Option<&str>, question_id: &str) -> Vec<QuestionReply> {
+        self.scoped(db_ops::question_in_tenant, tenant, question_id)
+            .map(|conn| db_ops::list_replies(&conn, question_id))
+            .unwrap_or_default()
+    }
+    fn list_question_attachments(
+        &self,
+        tenant: Option<&str>,
+        question_id: &str,
+    ) -> Vec<QuestionAttachment> {
+        self.scoped(db_ops::question_in_tenant, tenant, question_id)
+            .map(|conn| db_ops::list_question_attachments(&conn, question_id))
+            .unwrap_or_default()
+    }
+    fn create_question_attachment(
+        &self,
+        tenant: Option<&str>,
+        question_id: &str,
+        reply_id: Option<&str>,
+        input: &CreateArtifact,
+        author_type: &str,
+        author_id: &str,
+    ) -> Option<QuestionAttachment> {
+        self.scoped(db_ops::question_in_tenant, tenant, question_id)
+            .and_then(|conn| {
+                db_ops::create_question_attachment(
+                    &conn,
+                    question_id,
+                    reply_id,
+                    input,
+                    author_type,
+                    author_id,
+                )
+            })
     }
     fn dismiss_question(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         question_id: &str,
         reason: &str,
     ) -> Option<TaskQuestion> {
-        db_ops::dismiss_question(&self.lock(), question_id, reason)
+        self.scoped(db_ops::question_in_tenant, tenant, question_id)
+            .and_then(|conn| db_ops::dismiss_question(&conn, question_id, reason))
     }
     fn assign_question(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         question_id: &str,
         target_type: &str,
         target_id: &str,
     ) -> Option<TaskQuestion> {
-        db_ops::assign_question(&self.lock(), question_id, target_type, target_id)
+        let conn = self.scoped(db_ops::question_in_tenant, tenant, question_id)?;
+        if target_type == "agent" && !db_ops::agent_in_tenant(&conn, tenant, target_id) {
+            return None;
+        }
+        db_ops::assign_question(&conn, question_id, target_type, target_id)
     }
     fn find_capability_targets(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         required_capability: &str,
     ) -> Vec<CapabilityTarget> {
-        db_ops::find_capability_targets(&self.lock(), required_capability)
+        db_ops::find_capability_targets(&self.lock(), tenant, required_capability)
     }
     fn auto_target_question(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         question_id: &str,
         required_capability: &str,
     ) -> Vec<CapabilityTarget> {
-        db_ops::auto_target_question(&self.lock(), question_id, required_capability)
+        self.scoped(db_ops::question_in_tenant, tenant, question_id)
+            .map(|conn| db_ops::auto_target_question(&conn, question_id, required_capability))
+            .unwrap_or_default()
+    }
+    fn reroute_inactive_questions(
+        &self,
+        _tenant: Option<&str>,
+        window_minutes: i64,
+    ) -> (Vec<QuestionReroute>, Vec<PendingNotifWebhook>) {
+        db_ops::reroute_inactive_questions(&self.lock(), window_minutes)
     }
 }
 
@@ -523,11 +919,38 @@ impl EventStore for SqliteBackend {
             payload,
         )
     }
+    fn record_event(
+        &self,
+        _tenant: Option<&str>,
+        event_type: &str,
+        task_id: Option<&str>,
+        project_id: &str,
+        actor_type: &str,
+        actor_id: &str,
+        payload: &serde_json::Value,
+    ) -> (i64, Vec<PendingNotifWebhook>) {
+        db_ops::record_event(
+            &self.lock(),
+            event_type,
+            task_id,
+            project_id,
+            actor_type,
+            actor_id,
+            payload,
+        )
+    }
     fn get_last_event_id(&self, _tenant: Option<&str>) -> i64 {
         self.lock()
             .query_row("SELECT MAX(id) FROM events", [], |row| row.get::<_, i64>(0))
             .unwrap_or(0)
     }
+    fn replay_events(
+        &self,
+        tenant: Option<&str>,
+        input: &EventReplayRequest,
+    ) -> (EventReplayResult, Vec<PendingNotifWebhook>) {
+        db_ops::replay_events(&self.lock(), tenant, input)
+    }
     fn insert_question_notification(
         &self,
         _tenant: Option<&str>,
@@ -550,26 +973,48 @@ impl EventStore for SqliteBackend {
     }
     fn list_notifications(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         agent_id: &str,
         unread: Option<bool>,
     ) -> Vec<Notification> {
-        db_ops::list_notifications(&self.lock(), agent_id, unread)
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .map(|conn| db_ops::list_notifications(&conn, agent_id, unread))
+            .unwrap_or_default()
     }
-    fn ack_notification(
+    fn list_notifications_after(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         agent_id: &str,
-        notification_id: i64,
-    ) -> bool {
-        db_ops::ack_notification(&self.lock(), agent_id, notification_id)
+        after_id: i64,
+        limit: i64,
+    ) -> Vec<Notification> {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .map(|conn| db_ops::list_notifications_after(&conn, agent_id, after_id, limit))
+            .unwrap_or_default()
     }
-    fn ack_all_notifications(&self, _tenant: Option<&str>, agent_id: &str) -> i64 {
-        db_ops::ack_all_notifications(&self.lock(), agent_id)
+    fn get_last_notification_id(&self, tenant: Option<&str>, agent_id: &str) -> i64 {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .map(|conn| db_ops::get_last_notification_id(&conn, agent_id))
+            .unwrap_or(0)
+    }
+    fn ack_notification(&self, tenant: Option<&str>, agent_id: &str, notification_id: i64) -> bool {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .is_some_and(|conn| db_ops::ack_notification(&conn, agent_id, notification_id))
+    }
+    fn ack_all_notifications(&self, tenant: Option<&str>, agent_id: &str) -> i64 {
+        self.scoped(db_ops::agent_in_tenant, tenant, agent_id)
+            .map(|conn| db_ops::ack_all_notifications(&conn, agent_id))
+            .unwrap_or(0)
     }
     fn ack_notification_system(&self, _tenant: Option<&str>, notification_id: i64) {
         db_ops::ack_notification_system(&self.lock(), notification_id)
     }
+    fn sweep_notification_policies(&self, _tenant: Option<&str>) -> NotificationSweep {
+        db_ops::sweep_notification_policies(&self.lock())
+    }
+    fn prune_history(&self, _tenant: Option<&str>, policy: &RetentionPolicy) -> RetentionSweep {
+        db_ops::prune_history(&self.lock(), policy)
+    }
     fn update_notification_webhook_status(
         &self,
         _tenant: Option<&str>,
@@ -578,6 +1023,32 @@ impl EventStore for SqliteBackend {
     ) {
         db_ops::update_notification_webhook_status(&self.lock(), notification_id, status)
     }
+    fn enqueue_outbox_event(
+        &self,
+        _tenant: Option<&str>,
+        topic: &str,
+        event_key: Option<&str>,
+        payload: &str,
+    ) -> i64 {
+        db_ops::enqueue_outbox_event(&self.lock(), topic, event_key, payload)
+    }
+    fn list_outbox_events(&self, _tenant: Option<&str>, limit: i64) -> Vec<OutboxEvent> {
+        db_ops::list_outbox_events(&self.lock(), limit)
+    }
+    fn ack_outbox_events(&self, _tenant: Option<&str>, ids: &[i64]) {
+        db_ops::ack_outbox_events(&self.lock(), ids)
+    }
+    fn fail_outbox_events(&self, _tenant: Option<&str>, ids: &[i64], error: &str) {
+        db_ops::fail_outbox_events(&self.lock(), ids, error)
+    }
+    fn list_events_after(
+        &self,
+        tenant: Option<&str>,
+        after_id: i64,
+        limit: i64,
+    ) -> Vec<StoredEvent> {
+        db_ops::list_events_after(&self.lock(), tenant, after_id, limit)
+    }
 }
 
 impl WebhookStore for SqliteBackend {
@@ -585,36 +1056,35 @@ impl WebhookStore for SqliteBackend {
         &self,
         _tenant: Option<&str>,
         project_id: &str,
-        name: &str,
-        action_type: &str,
-        action_config: &serde_json::Value,
+        input: &CreateTriggerRequest,
     ) -> (WebhookTrigger, String) {
-        db_ops::create_webhook_trigger(&self.lock(), project_id, name, action_type, action_config)
+        db_ops::create_webhook_trigger(&self.lock(), project_id, input)
     }
-    fn list_webhook_triggers(
-        &self,
-        _tenant: Option<&str>,
-        project_id: &str,
-    ) -> Vec<WebhookTrigger> {
-        db_ops::list_webhook_triggers(&self.lock(), project_id)
+    fn list_webhook_triggers(&self, tenant: Option<&str>, project_id: &str) -> Vec<WebhookTrigger> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .map(|conn| db_ops::list_webhook_triggers(&conn, project_id))
+            .unwrap_or_default()
     }
     fn get_webhook_trigger_for_validation(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         trigger_id: &str,
     ) -> Option<(WebhookTrigger, String)> {
-        db_ops::get_webhook_trigger_for_validation(&self.lock(), trigger_id)
+        self.scoped(db_ops::trigger_in_tenant, tenant, trigger_id)
+            .and_then(|conn| db_ops::get_webhook_trigger_for_validation(&conn, trigger_id))
     }
     fn update_webhook_trigger(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         trigger_id: &str,
         input: &UpdateTriggerRequest,
     ) -> Option<WebhookTrigger> {
-        db_ops::update_webhook_trigger(&self.lock(), trigger_id, input)
+        self.scoped(db_ops::trigger_in_tenant, tenant, trigger_id)
+            .and_then(|conn| db_ops::update_webhook_trigger(&conn, trigger_id, input))
     }
-    fn delete_webhook_trigger(&self, _tenant: Option<&str>, trigger_id: &str) -> bool {
-        db_ops::delete_webhook_trigger(&self.lock(), trigger_id)
+    fn delete_webhook_trigger(&self, tenant: Option<&str>, trigger_id: &str) -> bool {
+        self.scoped(db_ops::trigger_in_tenant, tenant, trigger_id)
+            .is_some_and(|conn| db_ops::delete_webhook_trigger(&conn, trigger_id))
     }
     fn rotate_webhook_trigger_secret(
         &self,
@@ -636,11 +1106,33 @@ impl WebhookStore for SqliteBackend {
     }
     fn list_trigger_logs(
         &self,
-        _tenant: Option<&str>,
+        tenant: Option<&str>,
         trigger_id: &str,
         limit: i64,
     ) -> Vec<WebhookTriggerLog> {
-        db_ops::list_trigger_logs(&self.lock(), trigger_id, limit)
+        self.scoped(db_ops::trigger_in_tenant, tenant, trigger_id)
+            .map(|conn| db_ops::list_trigger_logs(&conn, trigger_id, limit))
+            .unwrap_or_default()
+    }
+    fn claim_trigger_delivery(
+        &self,
+        _tenant: Option<&str>,
+        trigger_id: &str,
+        dedup_value: &str,
+        window_hours: i64,
+    ) -> bool {
+        db_ops::claim_trigger_delivery(&self.lock(), trigger_id, dedup_value, window_hours)
+    }
+    fn release_trigger_delivery(&self, _tenant: Option<&str>, trigger_id: &str, dedup_value: &str) {
+        db_ops::release_trigger_delivery(&self.lock(), trigger_id, dedup_value)
+    }
+    fn count_trigger_runs_since(
+        &self,
+        _tenant: Option<&str>,
+        trigger_id: &str,
+        since: &str,
+    ) -> i64 {
+        db_ops::count_trigger_runs_since(&self.lock(), trigger_id, since)
     }
     fn create_webhook_log(
         &self,
@@ -669,11 +1161,263 @@ impl WebhookStore for SqliteBackend {
             response_body,
         )
     }
+    fn get_slack_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<SlackIntegration> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .and_then(|conn| db_ops::get_slack_integration(&conn, project_id))
+    }
+    fn set_slack_integration(
+        &self,
+        _tenant: Option<&str>,
+        project_id: &str,
+        input: &SetSlackIntegration,
+    ) -> SlackIntegration {
+        db_ops::set_slack_integration(&self.lock(), project_id, input)
+    }
+    fn delete_slack_integration(&self, tenant: Option<&str>, project_id: &str) -> bool {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .is_some_and(|conn| db_ops::delete_slack_integration(&conn, project_id))
+    }
+    fn get_github_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<GithubIntegration> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .and_then(|conn| db_ops::get_github_integration(&conn, project_id))
+    }
+    fn set_github_integration(
+        &self,
+        _tenant: Option<&str>,
+        project_id: &str,
+        input: &SetGithubIntegration,
+    ) -> GithubIntegration {
+        db_ops::set_github_integration(&self.lock(), project_id, input)
+    }
+    fn delete_github_integration(&self, tenant: Option<&str>, project_id: &str) -> bool {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .is_some_and(|conn| db_ops::delete_github_integration(&conn, project_id))
+    }
+    fn get_gitlab_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<GitlabIntegration> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .and_then(|conn| db_ops::get_gitlab_integration(&conn, project_id))
+    }
+    fn set_gitlab_integration(
+        &self,
+        _tenant: Option<&str>,
+        project_id: &str,
+        input: &SetGitlabIntegration,
+    ) -> GitlabIntegration {
+        db_ops::set_gitlab_integration(&self.lock(), project_id, input)
+    }
+    fn delete_gitlab_integration(&self, tenant: Option<&str>, project_id: &str) -> bool {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .is_some_and(|conn| db_ops::delete_gitlab_integration(&conn, project_id))
+    }
+    fn get_linear_integration(
+        &self,
+        tenant: Option<&str>,
+        project_id: &str,
+    ) -> Option<LinearIntegration> {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .and_then(|conn| db_ops::get_linear_integration(&conn, project_id))
+    }
+    fn set_linear_integration(
+        &self,
+        _tenant: Option<&str>,
+        project_id: &str,
+        input: &SetLinearIntegration,
+    ) -> LinearIntegration {
+        db_ops::set_linear_integration(&self.lock(), project_id, input)
+    }
+    fn delete_linear_integration(&self, tenant: Option<&str>, project_id: &str) -> bool {
+        self.scoped(db_ops::project_in_tenant, tenant, project_id)
+            .is_some_and(|conn| db_ops::delete_linear_integration(&conn, project_id))
+    }
+    fn record_slack_thread(
+        &self,
+        _tenant: Option<&str>,
+        task_id: &str,
+        channel: &str,
+        thread_ts: &str,
+    ) {
+        db_ops::record_slack_thread(&self.lock(), task_id, channel, thread_ts)
+    }
+    fn get_slack_thread(&self, _tenant: Option<&str>, task_id: &str) -> Option<(String, String)> {
+        db_ops::get_slack_thread(&self.lock(), task_id)
+    }
+    fn find_slack_thread_task(
+        &self,
+        _tenant: Option<&str>,
+        channel: &str,
+        thread_ts: &str,
+    ) -> Option<String> {
+        db_ops::find_slack_thread_task(&self.lock(), channel, thread_ts)
+    }
+    fn get_push_target(
+        &self,
+        tenant: Option<&str>,
+        subject_type: &str,
+        subject_id: &str,
+    ) -> Option<PushTarget> {
+        let conn = self.lock();
+        if subject_type == "agent" && !db_ops::agent_in_tenant(&conn, tenant, subject_id) {
+            return None;
+        }
+        db_ops::get_push_target(&conn, subject_type, subject_id)
+    }
+    fn set_push_target(
+        &self,
+        _tenant: Option<&str>,
+        subject_type: &str,
+        subject_id: &str,
+        input: &PushTarget,
+    ) -> PushTarget {
+        db_ops::set_push_target(&self.lock(), subject_type, subject_id, input)
+    }
+    fn delete_push_target(
+        &self,
+        tenant: Option<&str>,
+        subject_type: &str,
+        subject_id: &str,
+    ) -> bool {
+        let conn = self.lock();
+        if subject_type == "agent" && !db_ops::agent_in_tenant(&conn, tenant, subject_id) {
+            return false;
+        }
+        db_ops::delete_push_target(&conn, subject_type, subject_id)
+    }
 }
 
 impl StatsStore for SqliteBackend {
-    fn get_stats(&self, tenant: Option<&str>) -> DashboardStats {
-        db_ops::get_stats(&self.lock(), tenant)
+    fn get_stats(&self, tenant: Option<&str>, filter: &StatsQuery) -> DashboardStats {
+        db_ops::get_stats(&self.lock(), tenant, filter)
+    }
+    fn get_tenant_usage(&self, tenant: &str) -> TenantUsage {
+        db_ops::get_tenant_usage(&self.lock(), tenant)
+    }
+
+    fn get_timeseries(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        metric: &str,
+        interval: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Timeseries {
+        db_ops::get_timeseries(&self.lock(), tenant, project_id, metric, interval, from, to)
+    }
+
+    fn get_agent_workloads(&self, tenant: Option<&str>, days: i64) -> AgentWorkloadStats {
+        db_ops::get_agent_workloads(&self.lock(), tenant, days)
+    }
+
+    fn get_burndown(
+        &self,
+        project_id: &str,
+        by_estimate: bool,
+        iteration_days: i64,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Burndown {
+        db_ops::get_burndown(
+            &self.lock(),
+            project_id,
+            by_estimate,
+            iteration_days,
+            from,
+            to,
+        )
+    }
+
+    fn get_review_latency(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        days: i64,
+    ) -> ReviewLatencyReport {
+        db_ops::get_review_latency(&self.lock(), tenant, project_id, days)
+    }
+    fn get_cost_report(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        group_by: &str,
+        interval: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> CostReport {
+        db_ops::get_cost_report(
+            &self.lock(),
+            tenant,
+            project_id,
+            group_by,
+            interval,
+            from,
+            to,
+        )
+    }
+    fn get_blocked_time(
+        &self,
+        tenant: Option<&str>,
+        project_id: Option<&str>,
+        limit: usize,
+    ) -> BlockedTimeReport {
+        db_ops::get_blocked_time(&self.lock(), tenant, project_id, limit)
+    }
+}
+
+impl TenantStore for SqliteBackend {
+    fn create_tenant(&self, input: &CreateTenant) -> Result<Tenant, StorageError> {
+        db_ops::create_tenant(&self.lock(), input).map_err(StorageError)
+    }
+    fn get_tenant(&self, id: &str) -> Option<Tenant> {
+        db_ops::get_tenant(&self.lock(), id)
+    }
+    fn list_tenants(&self) -> Vec<Tenant> {
+        db_ops::list_tenants(&self.lock())
+    }
+    fn has_tenants(&self) -> bool {
+        db_ops::has_tenants(&self.lock())
+    }
+    fn set_tenant_status(&self, id: &str, status: &str) -> Option<Tenant> {
+        db_ops::set_tenant_status(&self.lock(), id, status)
+    }
+    fn delete_tenant(&self, id: &str) -> bool {
+        db_ops::delete_tenant(&self.lock(), id)
+    }
+    fn create_tenant_setup_token(&self, tenant_id: &str) -> TenantSetupToken {
+        db_ops::create_tenant_setup_token(&self.lock(), tenant_id)
+    }
+    fn get_tenant_by_setup_token(&self, token: &str) -> Option<Tenant> {
+        db_ops::get_tenant_by_setup_token(&self.lock(), token)
+    }
+}
+
+impl SettingsStore for SqliteBackend {
+    fn get_settings(&self, _tenant: Option<&str>) -> RuntimeSettings {
+        self.settings()
+    }
+    fn update_settings(
+        &self,
+        _tenant: Option<&str>,
+        input: &UpdateSettings,
+        updated_by: &str,
+    ) -> Result<RuntimeSettings, StorageError> {
+        // Hold the cache while writing so concurrent updates land in order
+        let mut cached = self.settings.write().unwrap();
+        let settings =
+            db_ops::update_settings(&self.lock(), input, updated_by).map_err(StorageError)?;
+        *cached = settings.clone();
+        Ok(settings)
     }
 }
 
@@ -0,0 +1,119 @@
+//! Task references in commit messages and pull request text, for the `commit_references`
+//! trigger action: a full task id, or `OG-` followed by the first characters of one
+//! (`OG-3f2a9c1b`), the way branch names and commit subjects usually abbreviate it.
+
+use opengate_models::Task;
+
+/// Shortest `OG-` prefix taken as a reference; anything shorter matches too much.
+pub const MIN_PREFIX_LEN: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TaskRef {
+    /// A full task id
+    Id(String),
+    /// The start of a task id, from `OG-<prefix>`
+    Prefix(String),
+}
+
+impl TaskRef {
+    /// As it appeared in the text, more or less.
+    pub fn label(&self) -> String {
+        match self {
+            TaskRef::Id(id) => id.clone(),
+            TaskRef::Prefix(prefix) => format!("OG-{}", prefix),
+        }
+    }
+}
+
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// The references in `text`, in order of first appearance, without repeats.
+pub fn find(text: &str) -> Vec<TaskRef> {
+    let lower = text.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    let boundary = |i: usize| i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+    let mut refs = Vec::new();
+    let mut push = |r: TaskRef| {
+        if !refs.contains(&r) {
+            refs.push(r);
+        }
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"og-") && boundary(i) {
+            let rest = &lower[i + 3..];
+            let len = rest
+                .find(|c: char| !(c.is_ascii_hexdigit() || c == '-'))
+                .unwrap_or(rest.len())
+                .min(36);
+            let prefix = rest[..len].trim_end_matches('-');
+            if is_uuid(prefix) {
+                push(TaskRef::Id(prefix.to_string()));
+            } else if prefix.len() >= MIN_PREFIX_LEN {
+                push(TaskRef::Prefix(prefix.to_string()));
+            }
+            i += 3 + len;
+            continue;
+        }
+        if boundary(i) {
+            if let Some(candidate) = lower.get(i..i + 36) {
+                let end_ok = bytes.get(i + 36).is_none_or(|b| !b.is_ascii_alphanumeric());
+                if end_ok && is_uuid(candidate) {
+                    push(TaskRef::Id(candidate.to_string()));
+                    i += 36;
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+    refs
+}
+
+/// The one task in `tasks` that `r` names, or `None` when it names none or, for a prefix,
+/// more than one.
+pub fn resolve<'a>(r: &TaskRef, tasks: &'a [Task]) -> Option<&'a Task> {
+    match r {
+        TaskRef::Id(id) => tasks.iter().find(|t| t.id.eq_ignore_ascii_case(id)),
+        TaskRef::Prefix(prefix) => {
+            let mut matches = tasks
+                .iter()
+                .filter(|t| t.id.to_ascii_lowercase().starts_with(prefix));
+            let task = matches.next()?;
+            matches.next().is_none().then_some(task)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_ids_and_prefixes() {
+        let id = "3f2a9c1b-4d5e-4f60-8a7b-9c0d1e2f3a4b";
+        let text = format!(
+            "Fix login (OG-3F2A9C1B)\n\nRefs {id}, og-77aa01-, dog-123456, OG-abc and OG-3f2a9c1b again"
+        );
+        assert_eq!(
+            find(&text),
+            vec![
+                TaskRef::Prefix("3f2a9c1b".to_string()),
+                TaskRef::Id(id.to_string()),
+                TaskRef::Prefix("77aa01".to_string()),
+            ]
+        );
+        assert_eq!(
+            find(&format!("feature/OG-{id}-search")),
+            vec![TaskRef::Id(id.to_string())]
+        );
+        assert!(find(&format!("x{id}")).is_empty());
+        assert!(find("Ünïcode — OG-").is_empty());
+    }
+}
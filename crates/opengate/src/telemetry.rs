@@ -0,0 +1,346 @@
+//! Distributed tracing, exported as OTLP/HTTP JSON when `--otlp-endpoint` is set.
+//!
+//! Every request gets a server span named after its route, joined to the caller's trace
+//! when it sends a W3C `traceparent` header. Each SQL statement run while handling it
+//! becomes a child span carrying the statement, and outbound webhooks become client
+//! spans that pass `traceparent` on to the receiver. Finished spans are batched and
+//! posted to `<endpoint>/v1/traces`. With no endpoint nothing is recorded.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+
+pub const DEFAULT_SERVICE_NAME: &str = "opengate";
+
+/// Finished spans held for export; beyond this the newest are dropped
+const MAX_BUFFERED: usize = 10_000;
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// OTLP span kinds
+pub const KIND_SERVER: u8 = 2;
+pub const KIND_CLIENT: u8 = 3;
+const KIND_INTERNAL: u8 = 1;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+struct Exporter {
+    endpoint: String,
+    service_name: String,
+}
+
+fn buffer() -> &'static Mutex<Vec<Value>> {
+    static BUFFER: OnceLock<Mutex<Vec<Value>>> = OnceLock::new();
+    BUFFER.get_or_init(Default::default)
+}
+
+/// Export spans to the collector at `endpoint` (e.g. `http://localhost:4318`).
+pub fn set_exporter(endpoint: &str, service_name: &str) {
+    let _ = EXPORTER.set(Exporter {
+        endpoint: endpoint.trim_end_matches('/').to_string(),
+        service_name: service_name.to_string(),
+    });
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Where a span sits in its trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanContext {
+    pub trace_id: String,
+    pub span_id: String,
+}
+
+impl SpanContext {
+    /// Parse a W3C `traceparent` (`00-<trace id>-<parent id>-<flags>`).
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let (version, trace_id, span_id, _flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        let hex = |s: &str, len| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+        let valid = version.len() == 2
+            && version != "ff"
+            && hex(trace_id, 32)
+            && hex(span_id, 16)
+            && trace_id.bytes().any(|b| b != b'0')
+            && span_id.bytes().any(|b| b != b'0');
+        valid.then(|| Self {
+            trace_id: trace_id.to_ascii_lowercase(),
+            span_id: span_id.to_ascii_lowercase(),
+        })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", self.trace_id, self.span_id)
+    }
+}
+
+tokio::task_local! {
+    static CURRENT: SpanContext;
+}
+
+/// The span the running request is in, to parent work it hands to other tasks.
+pub fn current() -> Option<SpanContext> {
+    CURRENT.try_with(|c| c.clone()).ok()
+}
+
+fn random_hex(bytes: usize) -> String {
+    hex::encode(&uuid::Uuid::new_v4().as_bytes()[..bytes])
+}
+
+fn unix_nanos(t: SystemTime) -> String {
+    t.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// A span in progress; recorded when [`Span::end`] is called.
+pub struct Span {
+    pub context: SpanContext,
+    parent_span_id: Option<String>,
+    name: String,
+    kind: u8,
+    start: SystemTime,
+    attributes: Vec<(&'static str, Value)>,
+    error: bool,
+}
+
+impl Span {
+    /// Start a span under `parent`, or a new trace without one. `None` while tracing is
+    /// off.
+    pub fn start(name: &str, kind: u8, parent: Option<&SpanContext>) -> Option<Self> {
+        if !enabled() {
+            return None;
+        }
+        Some(Self {
+            context: SpanContext {
+                trace_id: parent
+                    .map(|p| p.trace_id.clone())
+                    .unwrap_or_else(|| random_hex(16)),
+                span_id: random_hex(8),
+            },
+            parent_span_id: parent.map(|p| p.span_id.clone()),
+            name: name.to_string(),
+            kind,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: false,
+        })
+    }
+
+    pub fn set(&mut self, key: &'static str, value: impl Into<Value>) {
+        self.attributes.push((key, value.into()));
+    }
+
+    pub fn set_error(&mut self) {
+        self.error = true;
+    }
+
+    pub fn end(self) {
+        self.end_at(SystemTime::now());
+    }
+
+    fn end_at(self, end: SystemTime) {
+        let attributes: Vec<Value> = self
+            .attributes
+            .iter()
+            .map(|(key, value)| json!({"key": key, "value": otlp_value(value)}))
+            .collect();
+        let mut span = json!({
+            "traceId": self.context.trace_id,
+            "spanId": self.context.span_id,
+            "name": self.name,
+            "kind": self.kind,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(end),
+            "attributes": attributes,
+            // STATUS_CODE_ERROR, or UNSET
+            "status": {"code": if self.error { 2 } else { 0 }},
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = parent.into();
+        }
+        let mut buffer = buffer().lock().unwrap();
+        if buffer.len() < MAX_BUFFERED {
+            buffer.push(span);
+        }
+    }
+}
+
+fn otlp_value(value: &Value) -> Value {
+    match value {
+        Value::Bool(b) => json!({"boolValue": b}),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({"intValue": n.to_string()}),
+        Value::Number(n) => json!({"doubleValue": n.as_f64()}),
+        Value::String(s) => json!({"stringValue": s}),
+        other => json!({"stringValue": other.to_string()}),
+    }
+}
+
+/// Middleware wrapping each request in a server span, continuing the caller's trace
+/// from `traceparent`.
+pub async fn trace_requests(request: Request, next: Next) -> Response {
+    if !enabled() {
+        return next.run(request).await;
+    }
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let parent = request
+        .headers()
+        .get("traceparent")
+        .and_then(|v| v.to_str().ok())
+        .and_then(SpanContext::from_traceparent);
+    let method = request.method().to_string();
+    let Some(mut span) = Span::start(
+        &format!("{} {}", method, route),
+        KIND_SERVER,
+        parent.as_ref(),
+    ) else {
+        return next.run(request).await;
+    };
+    span.set("http.request.method", method);
+    span.set("http.route", route);
+    span.set("url.path", request.uri().path());
+
+    let mut response = CURRENT.scope(span.context.clone(), next.run(request)).await;
+    let status = response.status();
+    span.set("http.response.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.set_error();
+    }
+    if let Ok(value) = HeaderValue::from_str(&span.context.traceparent()) {
+        response.headers_mut().insert("traceparent", value);
+    }
+    span.end();
+    response
+}
+
+/// SQLite profile hook: record each statement that ran inside a traced request as a
+/// child span of it.
+pub fn record_sql(sql: &str, took: Duration) {
+    if !enabled() {
+        return;
+    }
+    let Some(parent) = current() else {
+        return;
+    };
+    let end = SystemTime::now();
+    if let Some(mut span) = Span::start("sqlite", KIND_INTERNAL, Some(&parent)) {
+        span.start = end - took;
+        span.set("db.system", "sqlite");
+        span.set("db.statement", sql.trim());
+        span.end_at(end);
+    }
+}
+
+/// Send an outbound request as a client span under `parent`, passing the trace on in
+/// its `traceparent` header.
+pub async fn send_traced(
+    request: reqwest::RequestBuilder,
+    name: &str,
+    parent: Option<&SpanContext>,
+) -> reqwest::Result<reqwest::Response> {
+    let Some(mut span) = Span::start(name, KIND_CLIENT, parent) else {
+        return request.send().await;
+    };
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    span.set("http.request.method", request.method().to_string());
+    span.set("url.full", request.url().to_string());
+    if let Ok(value) = HeaderValue::from_str(&span.context.traceparent()) {
+        request.headers_mut().insert("traceparent", value);
+    }
+    let result = client.execute(request).await;
+    match &result {
+        Ok(resp) => {
+            span.set("http.response.status_code", resp.status().as_u16());
+            if !resp.status().is_success() {
+                span.set_error();
+            }
+        }
+        Err(e) => {
+            span.set("error.type", e.to_string());
+            span.set_error();
+        }
+    }
+    span.end();
+    result
+}
+
+/// Post buffered spans to the collector until the process exits.
+pub fn spawn_exporter() {
+    let Some(exporter) = EXPORTER.get() else {
+        return;
+    };
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let url = format!("{}/v1/traces", exporter.endpoint);
+        let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let spans = std::mem::take(&mut *buffer().lock().unwrap());
+            if spans.is_empty() {
+                continue;
+            }
+            let body = json!({
+                "resourceSpans": [{
+                    "resource": {"attributes": [
+                        {"key": "service.name", "value": {"stringValue": exporter.service_name}}
+                    ]},
+                    "scopeSpans": [{"scope": {"name": "opengate"}, "spans": spans}]
+                }]
+            });
+            match client
+                .post(&url)
+                .json(&body)
+                .timeout(Duration::from_secs(10))
+                .send()
+                .await
+            {
+                Ok(resp) if resp.status().is_success() => {}
+                Ok(resp) => eprintln!("[otlp] export failed (HTTP {})", resp.status()),
+                Err(e) => eprintln!("[otlp] export error: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_traceparent() {
+        let ctx = SpanContext::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .unwrap();
+        assert_eq!(ctx.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ctx.span_id, "00f067aa0ba902b7");
+        assert_eq!(
+            ctx.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert!(SpanContext::from_traceparent(
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+        )
+        .is_none());
+        assert!(SpanContext::from_traceparent("00-4bf92f35-00f067aa0ba902b7-01").is_none());
+        assert!(SpanContext::from_traceparent("garbage").is_none());
+    }
+}
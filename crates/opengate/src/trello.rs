@@ -0,0 +1,393 @@
+//! Trello import for `POST /api/import/trello`: a board's JSON export (Menu → Print,
+//! export and share → Export as JSON) becomes a new project. Lists map to statuses,
+//! cards to tasks, checklists to `text` artifacts or to subtasks the card's task depends
+//! on, and link attachments to `url` artifacts. Archived lists and cards are left out.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use opengate_models::*;
+
+#[derive(Debug, Deserialize)]
+pub struct Board {
+    pub name: String,
+    #[serde(default)]
+    pub desc: String,
+    #[serde(default)]
+    pub lists: Vec<List>,
+    #[serde(default)]
+    pub cards: Vec<Card>,
+    #[serde(default)]
+    pub checklists: Vec<Checklist>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct List {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub closed: bool,
+    #[serde(default)]
+    pub pos: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Card {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub desc: String,
+    pub id_list: String,
+    #[serde(default)]
+    pub closed: bool,
+    #[serde(default)]
+    pub pos: f64,
+    pub due: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Label {
+    #[serde(default)]
+    pub name: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    #[serde(default)]
+    pub name: String,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checklist {
+    pub name: String,
+    pub id_card: String,
+    #[serde(default)]
+    pub pos: f64,
+    #[serde(default)]
+    pub check_items: Vec<CheckItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckItem {
+    pub name: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub pos: f64,
+}
+
+impl CheckItem {
+    pub fn complete(&self) -> bool {
+        self.state == "complete"
+    }
+}
+
+/// The importable form of a body: a wrapper with options, or the bare export.
+pub fn parse(body: &[u8]) -> Result<(Board, TrelloImportOptions), String> {
+    let value: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let (board, options) = match value.get("board") {
+        Some(board) => (
+            board.clone(),
+            serde_json::from_value(value.clone()).map_err(|e| format!("Invalid options: {}", e))?,
+        ),
+        None => (value, TrelloImportOptions::default()),
+    };
+    let board =
+        serde_json::from_value(board).map_err(|e| format!("Not a Trello board export: {}", e))?;
+    Ok((board, options))
+}
+
+/// The status for a list with no `status_map` entry, by its name.
+pub fn default_status(list_name: &str) -> &'static str {
+    let name = list_name.trim().to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| name.contains(w));
+    if has(&["done", "complete", "closed", "shipped", "finished"]) {
+        "done"
+    } else if has(&["review", "qa", "testing", "verify"]) {
+        "review"
+    } else if has(&["doing", "progress", "working", "started"]) {
+        "in_progress"
+    } else if has(&["blocked", "on hold", "waiting"]) {
+        "blocked"
+    } else if has(&["cancel", "won't", "wont", "rejected"]) {
+        "cancelled"
+    } else if has(&["to do", "todo", "ready", "next", "up next"]) {
+        "todo"
+    } else {
+        "backlog"
+    }
+}
+
+/// A checklist as a `text` artifact body: one `- [x] item` line per item.
+pub fn checklist_text(checklist: &Checklist) -> String {
+    checklist
+        .check_items
+        .iter()
+        .map(|item| {
+            format!(
+                "- [{}] {}",
+                if item.complete() { "x" } else { " " },
+                item.name
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A card, ready to become a task.
+pub struct PlannedCard<'a> {
+    pub card: &'a Card,
+    pub checklists: Vec<&'a Checklist>,
+    pub mapping: TrelloCardMapping,
+}
+
+/// How a board maps onto a project.
+pub struct Plan<'a> {
+    /// The open lists with their statuses
+    pub lists: Vec<TrelloListMapping>,
+    /// The open cards, in board order
+    pub cards: Vec<PlannedCard<'a>>,
+    pub warnings: Vec<String>,
+}
+
+/// Map `board` onto a project. Errors on an unknown `checklists` mode, or a `status_map`
+/// entry that names no list or no valid status.
+pub fn plan<'a>(board: &'a Board, options: &TrelloImportOptions) -> Result<Plan<'a>, String> {
+    let mut warnings = Vec::new();
+    if !TRELLO_CHECKLIST_MODES.contains(&options.checklists()) {
+        return Err(format!(
+            "checklists must be one of: {}",
+            TRELLO_CHECKLIST_MODES.join(", ")
+        ));
+    }
+    for (list, status) in &options.status_map {
+        if TaskStatus::from_str(status).is_none() {
+            return Err(format!("status_map: '{}' is not a task status", status));
+        }
+        if !board.lists.iter().any(|l| &l.name == list) {
+            return Err(format!("status_map: the board has no list '{}'", list));
+        }
+    }
+
+    let mut lists: Vec<&List> = board.lists.iter().filter(|l| !l.closed).collect();
+    lists.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+    let list_status: HashMap<&str, &str> = lists
+        .iter()
+        .map(|l| {
+            let status = options
+                .status_map
+                .get(&l.name)
+                .map(String::as_str)
+                .unwrap_or_else(|| default_status(&l.name));
+            (l.id.as_str(), status)
+        })
+        .collect();
+    let list_index: HashMap<&str, usize> = lists
+        .iter()
+        .enumerate()
+        .map(|(i, l)| (l.id.as_str(), i))
+        .collect();
+
+    let mut cards: Vec<&Card> = board
+        .cards
+        .iter()
+        .filter(|c| !c.closed && list_index.contains_key(c.id_list.as_str()))
+        .collect();
+    let archived = board.cards.len() - cards.len();
+    if archived > 0 {
+        warnings.push(format!(
+            "{} archived card(s), or cards on archived lists, left out",
+            archived
+        ));
+    }
+    cards.sort_by(|a, b| {
+        list_index[a.id_list.as_str()]
+            .cmp(&list_index[b.id_list.as_str()])
+            .then(a.pos.total_cmp(&b.pos))
+    });
+
+    let planned = cards
+        .into_iter()
+        .map(|card| {
+            let mut checklists: Vec<&Checklist> = board
+                .checklists
+                .iter()
+                .filter(|c| c.id_card == card.id)
+                .collect();
+            checklists.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+            let list = lists[list_index[card.id_list.as_str()]];
+            let attachments = card.attachments.iter().filter(|a| a.url.is_some()).count();
+            PlannedCard {
+                mapping: TrelloCardMapping {
+                    card_id: card.id.clone(),
+                    name: card.name.clone(),
+                    list: list.name.clone(),
+                    status: list_status[list.id.as_str()].to_string(),
+                    tags: card_tags(card),
+                    checklist_items: checklists.iter().map(|c| c.check_items.len()).sum(),
+                    attachments,
+                    task_id: None,
+                    subtask_ids: Vec::new(),
+                },
+                card,
+                checklists,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let list_mappings = lists
+        .iter()
+        .map(|l| TrelloListMapping {
+            name: l.name.clone(),
+            status: list_status[l.id.as_str()].to_string(),
+            cards: planned.iter().filter(|p| p.card.id_list == l.id).count(),
+        })
+        .collect();
+    Ok(Plan {
+        lists: list_mappings,
+        cards: planned,
+        warnings,
+    })
+}
+
+/// Label names, or colors for unnamed labels.
+fn card_tags(card: &Card) -> Vec<String> {
+    card.labels
+        .iter()
+        .filter_map(|l| {
+            Some(l.name.trim())
+                .filter(|n| !n.is_empty())
+                .map(str::to_string)
+                .or_else(|| l.color.clone())
+        })
+        .collect()
+}
+
+/// The task `card` is created as, in `backlog`.
+pub fn card_task(card: &Card, tags: &[String]) -> CreateTask {
+    let mut context = serde_json::json!({ "external_ref": format!("trello:{}", card.id) });
+    if let Some(ref url) = card.url {
+        context["card_url"] = serde_json::json!(url);
+    }
+    CreateTask {
+        title: card.name.clone(),
+        description: Some(card.desc.clone()).filter(|d| !d.trim().is_empty()),
+        priority: None,
+        tags: Some(tags.to_vec()).filter(|t| !t.is_empty()),
+        context: Some(context),
+        output: None,
+        due_date: card.due.clone(),
+        assignee_type: None,
+        assignee_id: None,
+        scheduled_at: None,
+        recurrence_rule: None,
+    }
+}
+
+/// The subtask a checklist item of the card task `parent_id` is created as.
+pub fn item_task(item: &CheckItem, checklist: &Checklist, parent_id: &str) -> CreateTask {
+    CreateTask {
+        title: item.name.clone(),
+        description: None,
+        priority: None,
+        tags: None,
+        context: Some(serde_json::json!({
+            "parent_task_id": parent_id,
+            "checklist": checklist.name,
+        })),
+        output: None,
+        due_date: None,
+        assignee_type: None,
+        assignee_id: None,
+        scheduled_at: None,
+        recurrence_rule: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board() -> Board {
+        serde_json::from_value(serde_json::json!({
+            "name": "Roadmap",
+            "lists": [
+                {"id": "l2", "name": "Doing", "pos": 2.0},
+                {"id": "l1", "name": "Ideas", "pos": 1.0},
+                {"id": "l3", "name": "Old", "closed": true, "pos": 3.0},
+            ],
+            "cards": [
+                {"id": "c1", "name": "Search", "idList": "l2", "pos": 1.0,
+                 "labels": [{"name": "", "color": "red"}, {"name": "api"}]},
+                {"id": "c2", "name": "Themes", "idList": "l1", "pos": 5.0},
+                {"id": "c3", "name": "Gone", "idList": "l1", "closed": true},
+                {"id": "c4", "name": "Ancient", "idList": "l3"},
+            ],
+            "checklists": [
+                {"name": "Steps", "idCard": "c1", "checkItems": [
+                    {"name": "Index", "state": "complete"},
+                    {"name": "Query", "state": "incomplete"},
+                ]},
+            ],
+        }))
+        .unwrap()
+    }
+
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn maps_lists_and_cards_in_board_order() {
+        let board = board();
+        let options = TrelloImportOptions {
+            status_map: BTreeMap::from([("Ideas".to_string(), "todo".to_string())]),
+            ..Default::default()
+        };
+        let Plan {
+            lists,
+            cards,
+            warnings,
+        } = plan(&board, &options).unwrap();
+        let lists: Vec<(&str, &str, usize)> = lists
+            .iter()
+            .map(|l| (l.name.as_str(), l.status.as_str(), l.cards))
+            .collect();
+        assert_eq!(
+            lists,
+            vec![("Ideas", "todo", 1), ("Doing", "in_progress", 1)]
+        );
+        assert_eq!(cards[0].mapping.name, "Themes");
+        assert_eq!(cards[1].mapping.tags, vec!["red", "api"]);
+        assert_eq!(cards[1].mapping.checklist_items, 2);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            checklist_text(cards[1].checklists[0]),
+            "- [x] Index\n- [ ] Query"
+        );
+
+        let bad = TrelloImportOptions {
+            status_map: BTreeMap::from([("Ideas".to_string(), "someday".to_string())]),
+            ..Default::default()
+        };
+        assert!(plan(&board, &bad).is_err());
+    }
+
+    #[test]
+    fn guesses_statuses_from_list_names() {
+        assert_eq!(default_status("To Do"), "todo");
+        assert_eq!(default_status("Code review"), "review");
+        assert_eq!(default_status("Done 🎉"), "done");
+        assert_eq!(default_status("Icebox"), "backlog");
+    }
+}
@@ -0,0 +1,688 @@
+//! `opengate tui`: a terminal dashboard for one project (its Pulse, the board, agent
+//! statuses and the live event stream) for operators who live in tmux rather than a
+//! browser. It is drawn with plain ANSI escapes and reads keys through `stty`: `q` quits,
+//! `r` refreshes.
+//!
+//! Pulse and the board are refetched every `--refresh` seconds, and within a second of
+//! any event. Events arrive over `/api/ws`. This build has no TLS for WebSockets, so
+//! against an `https://` server the dashboard only polls.
+//!
+//! The terminal is put back the way it was however the dashboard ends: on `q`, Ctrl-C,
+//! SIGTERM or SIGHUP, and on a panic. It needs `stty` and `/dev/tty`, so it is Unix-only;
+//! elsewhere `opengate tui` exits with an error.
+//!
+//! The dashboard was meant to be built on ratatui and crossterm, but neither crate is
+//! vendored into this workspace, so the frame is drawn by hand instead. The renderer is
+//! kept to what the dashboard needs: a full redraw into one buffer per frame, column
+//! fitting by character count, and colour by status. Moving to ratatui later only
+//! replaces `Dashboard::render` and `Screen`; fetching and the event stream stay as they
+//! are.
+
+use std::collections::VecDeque;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::client::Client;
+use opengate_models::*;
+
+/// Board columns, left to right
+pub const BOARD_STATUSES: [&str; 6] = [
+    "backlog",
+    "todo",
+    "in_progress",
+    "review",
+    "blocked",
+    "done",
+];
+/// Event lines kept for the stream pane
+const MAX_EVENTS: usize = 200;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+pub struct Dashboard {
+    pub project_id: String,
+    pub project_name: String,
+    /// `GET /api/projects/:id/pulse`
+    pub pulse: Value,
+    pub tasks: Vec<Task>,
+    /// Newest last
+    pub events: VecDeque<String>,
+    /// Shown in the header: the last refresh, or what went wrong
+    pub status: String,
+}
+
+impl Dashboard {
+    pub fn new(project_id: &str, project_name: &str) -> Self {
+        Dashboard {
+            project_id: project_id.to_string(),
+            project_name: project_name.to_string(),
+            pulse: Value::Null,
+            tasks: Vec::new(),
+            events: VecDeque::new(),
+            status: String::new(),
+        }
+    }
+
+    pub fn push_event(&mut self, line: String) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(line);
+    }
+
+    /// Refetch Pulse and the project's tasks, keeping the old ones on failure.
+    pub async fn refresh(&mut self, client: &Client) {
+        let pulse = client
+            .get(&format!("/api/projects/{}/pulse", self.project_id))
+            .await;
+        let tasks = client
+            .list_tasks(&TaskFilters {
+                project_id: Some(self.project_id.clone()),
+                ..Default::default()
+            })
+            .await;
+        match (pulse, tasks) {
+            (Ok(pulse), Ok(tasks)) => {
+                self.pulse = pulse;
+                self.tasks = tasks;
+                self.status = format!("updated {}", chrono::Local::now().format("%H:%M:%S"));
+            }
+            (Err(e), _) | (_, Err(e)) => self.status = e,
+        }
+    }
+
+    /// The whole screen as `height` lines of at most `width` visible characters.
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let width = width.max(20);
+        let count = |key: &str| self.pulse[key].as_array().map_or(0, Vec::len);
+        let mut lines = vec![
+            format!(
+                "{BOLD}{}{RESET}{}",
+                fit(&format!("OpenGate · {}", self.project_name), width / 2),
+                fit_right(&self.status, width - width / 2)
+            ),
+            fit(
+                &format!(
+                    "active {} · blocked {} · in review {} · recently done {} · waiting on deps {} · unread events {}",
+                    count("active_tasks"),
+                    count("blocked_tasks"),
+                    count("pending_review"),
+                    count("recently_completed"),
+                    self.pulse["blocked_by_deps"].as_i64().unwrap_or(0),
+                    self.pulse["unread_events"].as_i64().unwrap_or(0),
+                ),
+                width,
+            ),
+            String::new(),
+        ];
+
+        let agents = self.pulse["agents"].as_array().cloned().unwrap_or_default();
+        let columns: Vec<Vec<&Task>> = BOARD_STATUSES
+            .iter()
+            .map(|status| self.tasks.iter().filter(|t| t.status == *status).collect())
+            .collect();
+        // Header, pulse, blank, board header, blank, agents header, blank, events header, footer
+        let rest = height.saturating_sub(9);
+        let agent_rows = agents.len().min((rest / 4).max(1));
+        let tallest = columns.iter().map(Vec::len).max().unwrap_or(0);
+        let board_rows = tallest.min((rest - agent_rows.min(rest)) / 2).max(1);
+        let event_rows = rest.saturating_sub(agent_rows + board_rows);
+
+        let col_width = (width + 1) / BOARD_STATUSES.len() - 1;
+        let board_line = |cells: Vec<String>| cells.join(" ");
+        lines.push(format!(
+            "{BOLD}{}{RESET}",
+            board_line(
+                BOARD_STATUSES
+                    .iter()
+                    .zip(&columns)
+                    .map(|(status, tasks)| fit(
+                        &format!("{} ({})", status.to_uppercase(), tasks.len()),
+                        col_width
+                    ))
+                    .collect()
+            )
+        ));
+        for row in 0..board_rows {
+            lines.push(board_line(
+                columns
+                    .iter()
+                    .map(|tasks| {
+                        let overflow = tasks.len() > board_rows && row == board_rows - 1;
+                        match tasks.get(row) {
+                            _ if overflow => fit(
+                                &format!("+{} more", tasks.len() - board_rows + 1),
+                                col_width,
+                            ),
+                            Some(task) => fit(
+                                &format!("{} {}", priority_mark(&task.priority), task.title),
+                                col_width,
+                            ),
+                            None => fit("", col_width),
+                        }
+                    })
+                    .collect(),
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!("{BOLD}AGENTS ({}){RESET}", agents.len()));
+        for agent in agents.iter().take(agent_rows) {
+            let status = agent["status"].as_str().unwrap_or("offline");
+            let task = agent["current_task"]
+                .as_str()
+                .map(|task| format!(" · {}", task))
+                .unwrap_or_default();
+            lines.push(format!(
+                "{}●{RESET} {}",
+                status_color(status),
+                fit(
+                    &format!(
+                        "{:<20} {:<8}{}",
+                        agent["name"].as_str().unwrap_or("?"),
+                        status,
+                        task
+                    ),
+                    width - 2
+                )
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!("{BOLD}EVENTS{RESET}"));
+        let skip = self.events.len().saturating_sub(event_rows);
+        let mut shown = 0;
+        for event in self.events.iter().skip(skip) {
+            lines.push(fit(event, width));
+            shown += 1;
+        }
+        for _ in shown..event_rows {
+            lines.push(String::new());
+        }
+
+        lines.truncate(height.saturating_sub(1));
+        while lines.len() < height.saturating_sub(1) {
+            lines.push(String::new());
+        }
+        lines.push(format!("{DIM}{}{RESET}", fit("q quit · r refresh", width)));
+        lines
+    }
+}
+
+fn priority_mark(priority: &str) -> &'static str {
+    match priority {
+        "critical" => "!!",
+        "high" => "! ",
+        _ => "  ",
+    }
+}
+
+fn status_color(status: &str) -> &'static str {
+    match status {
+        "online" => "\x1b[32m",
+        "idle" => "\x1b[33m",
+        "stale" => "\x1b[31m",
+        _ => DIM,
+    }
+}
+
+/// `text` cut or padded to exactly `width` characters.
+fn fit(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width {
+        format!("{}{}", text, " ".repeat(width - len))
+    } else if width == 0 {
+        String::new()
+    } else {
+        let mut cut: String = text.chars().take(width - 1).collect();
+        cut.push('…');
+        cut
+    }
+}
+
+fn fit_right(text: &str, width: usize) -> String {
+    let text = fit(text, width);
+    let pad = text.len() - text.trim_end().len();
+    format!("{}{}", " ".repeat(pad), text.trim_end())
+}
+
+/// A `/api/ws` `event` message as one stream line: time, event type and what it's about.
+pub fn event_line(message: &Value) -> Option<String> {
+    if message["type"] != "event" {
+        return None;
+    }
+    let data = &message["data"];
+    let subject = ["title", "key", "name", "task_title"]
+        .iter()
+        .find_map(|k| data[*k].as_str())
+        .unwrap_or("");
+    Some(format!(
+        "{} {:<24} {}",
+        chrono::Local::now().format("%H:%M:%S"),
+        message["event"].as_str().unwrap_or("?"),
+        subject
+    ))
+}
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// A project's events over `/api/ws`, authenticated with the client's key.
+pub struct EventStream {
+    socket: Socket,
+}
+
+impl EventStream {
+    pub async fn connect(client: &Client, project_id: &str) -> Result<Self, String> {
+        let url = format!(
+            "{}/api/ws",
+            client
+                .url()
+                .replacen("https://", "wss://", 1)
+                .replacen("http://", "ws://", 1)
+        );
+        let (socket, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .map_err(|e| format!("event stream: {}", e))?;
+        let mut stream = EventStream { socket };
+        stream
+            .send(serde_json::json!({"type": "auth", "token": client.api_key().unwrap_or("")}))
+            .await?;
+        match stream.next_message().await {
+            Some(reply) if reply["type"] == "auth_ok" => {}
+            Some(reply) => {
+                return Err(format!(
+                    "event stream: {}",
+                    reply["message"].as_str().unwrap_or("authentication failed")
+                ))
+            }
+            None => return Err("event stream: closed during authentication".to_string()),
+        }
+        stream
+            .send(serde_json::json!({
+                "type": "subscribe",
+                "events": ["*"],
+                "filter": {"project_id": project_id},
+            }))
+            .await?;
+        match stream.next_message().await {
+            Some(reply) if reply["type"] == "subscribed" => Ok(stream),
+            Some(reply) => Err(format!(
+                "event stream: {}",
+                reply["message"].as_str().unwrap_or("subscription refused")
+            )),
+            None => Err("event stream: closed while subscribing".to_string()),
+        }
+    }
+
+    async fn send(&mut self, message: Value) -> Result<(), String> {
+        self.socket
+            .send(Message::Text(message.to_string().into()))
+            .await
+            .map_err(|e| format!("event stream: {}", e))
+    }
+
+    async fn next_message(&mut self) -> Option<Value> {
+        loop {
+            match self.socket.next().await? {
+                Ok(Message::Text(text)) => {
+                    if let Ok(value) = serde_json::from_str(&text) {
+                        return Some(value);
+                    }
+                }
+                Ok(Message::Close(_)) | Err(_) => return None,
+                Ok(_) => {}
+            }
+        }
+    }
+
+    /// The next event as a stream line; `None` once the connection is gone.
+    pub async fn next_line(&mut self) -> Option<String> {
+        loop {
+            if let Some(line) = event_line(&self.next_message().await?) {
+                return Some(line);
+            }
+        }
+    }
+}
+
+/// Runs `stty` on the controlling terminal.
+fn stty(args: &[&str]) -> Option<String> {
+    let tty = std::fs::File::open("/dev/tty").ok()?;
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty))
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// (columns, rows) of the terminal.
+fn terminal_size() -> (usize, usize) {
+    stty(&["size"])
+        .and_then(|size| {
+            let (rows, cols) = size.split_once(' ')?;
+            Some((cols.parse().ok()?, rows.parse().ok()?))
+        })
+        .unwrap_or((80, 24))
+}
+
+/// The `stty -g` state to return to while the dashboard has the terminal.
+static SAVED_TERMINAL: Mutex<Option<String>> = Mutex::new(None);
+
+/// Leave the dashboard's mode, once: later calls find nothing saved.
+fn restore_terminal() {
+    let saved = SAVED_TERMINAL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    if let Some(saved) = saved {
+        print!("\x1b[?25h\x1b[?1049l");
+        stty(&[&saved]);
+    }
+}
+
+/// The terminal in the dashboard's mode (alternate screen, no echo, unbuffered keys)
+/// until dropped.
+struct Screen;
+
+impl Screen {
+    #[cfg(unix)]
+    fn enter() -> Result<Self, String> {
+        static PANIC_HOOK: std::sync::Once = std::sync::Once::new();
+
+        let saved = stty(&["-g"]).ok_or("opengate tui needs a terminal")?;
+        // Restore before the panic message is printed, or it lands on the alternate
+        // screen and is wiped with it
+        PANIC_HOOK.call_once(|| {
+            let hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                hook(info);
+            }));
+        });
+        *SAVED_TERMINAL.lock().unwrap_or_else(|e| e.into_inner()) = Some(saved);
+        if stty(&["-icanon", "-echo", "min", "1"]).is_none() {
+            restore_terminal();
+            return Err("opengate tui needs a terminal".to_string());
+        }
+        print!("\x1b[?1049h\x1b[?25l");
+        Ok(Screen)
+    }
+
+    #[cfg(not(unix))]
+    fn enter() -> Result<Self, String> {
+        Err("opengate tui needs a Unix terminal (stty and /dev/tty)".to_string())
+    }
+
+    fn draw(&self, lines: &[String]) {
+        use std::io::Write;
+
+        let mut frame = String::from("\x1b[H");
+        for line in lines {
+            frame.push_str(line);
+            frame.push_str("\x1b[K\r\n");
+        }
+        frame.truncate(frame.len() - 2);
+        frame.push_str("\x1b[J");
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Resolves on SIGTERM or SIGHUP (a `kill`, or the terminal closing), which would
+/// otherwise end the process without dropping the `Screen`.
+#[cfg(unix)]
+async fn terminated() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::hangup()),
+    ) {
+        (Ok(mut term), Ok(mut hup)) => {
+            tokio::select! {
+                _ = term.recv() => {}
+                _ = hup.recv() => {}
+            }
+        }
+        _ => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminated() {
+    std::future::pending().await
+}
+
+/// The project `selector` names (an id, an id prefix or a name), or the first project.
+async fn find_project(client: &Client, selector: Option<&str>) -> Result<Project, String> {
+    let projects: Vec<Project> = serde_json::from_value(client.get("/api/projects").await?)
+        .map_err(|e| format!("Unexpected response: {}", e))?;
+    let Some(selector) = selector else {
+        return projects
+            .into_iter()
+            .next()
+            .ok_or_else(|| "No projects yet".to_string());
+    };
+    let mut matches: Vec<Project> = projects
+        .into_iter()
+        .filter(|p| p.id.starts_with(selector) || p.name.eq_ignore_ascii_case(selector))
+        .collect();
+    match matches.len() {
+        1 => Ok(matches.remove(0)),
+        0 => Err(format!("No project matches '{}'", selector)),
+        _ => Err(format!("'{}' matches more than one project", selector)),
+    }
+}
+
+/// Show the dashboard for `project` (or the first project) until `q` or Ctrl-C.
+pub async fn run(client: &Client, project: Option<&str>, refresh: Duration) -> Result<(), String> {
+    let project = find_project(client, project).await?;
+    let mut dashboard = Dashboard::new(&project.id, &project.name);
+    dashboard.refresh(client).await;
+    let screen = Screen::enter()?;
+
+    // std rather than tokio: a blocking read would keep the runtime from shutting down
+    let (keys_tx, mut keys) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        use std::io::Read;
+        let mut stdin = std::io::stdin();
+        let mut byte = [0u8; 1];
+        while stdin.read_exact(&mut byte).is_ok() && keys_tx.send(byte[0]).is_ok() {}
+    });
+
+    let mut events: Option<EventStream> = None;
+    let mut last_connect: Option<Instant> = None;
+    let mut last_refresh = Instant::now();
+    let mut stale = false;
+    let mut size = terminal_size();
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    let terminated = terminated();
+    tokio::pin!(terminated);
+    loop {
+        if events.is_none() && last_connect.is_none_or(|t| t.elapsed() >= refresh) {
+            last_connect = Some(Instant::now());
+            match EventStream::connect(client, &dashboard.project_id).await {
+                Ok(stream) => {
+                    dashboard.push_event("— connected to the event stream".to_string());
+                    events = Some(stream);
+                }
+                Err(e) => dashboard.push_event(format!("— {}", e)),
+            }
+        }
+        screen.draw(&dashboard.render(size.0, size.1));
+
+        let next_event = async {
+            match events.as_mut() {
+                Some(stream) => stream.next_line().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = &mut terminated => break,
+            key = keys.recv() => match key {
+                Some(b'q') | None => break,
+                Some(b'r') => {
+                    dashboard.refresh(client).await;
+                    last_refresh = Instant::now();
+                }
+                Some(_) => {}
+            },
+            line = next_event => match line {
+                Some(line) => {
+                    dashboard.push_event(line);
+                    stale = true;
+                }
+                None => {
+                    dashboard.push_event("— event stream closed".to_string());
+                    events = None;
+                }
+            },
+            _ = tick.tick() => {
+                size = terminal_size();
+                if stale || last_refresh.elapsed() >= refresh {
+                    dashboard.refresh(client).await;
+                    last_refresh = Instant::now();
+                    stale = false;
+                }
+            }
+        }
+    }
+    drop(screen);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Copies of one stored task with `title`, `status` and `priority` replaced.
+    fn tasks(specs: &[(&str, &str, &str)]) -> Vec<Task> {
+        let conn = crate::db::init_db(":memory:");
+        let project = crate::db_ops::create_project(
+            &conn,
+            None,
+            &CreateProject {
+                name: "Platform".to_string(),
+                description: None,
+                repo_url: None,
+                default_branch: None,
+                join_mode: None,
+                cta_enabled: None,
+                is_public: None,
+            },
+            "tester",
+        );
+        let task = crate::db_ops::create_task(
+            &conn,
+            None,
+            &project.id,
+            &CreateTask {
+                title: "template".to_string(),
+                description: None,
+                priority: None,
+                tags: None,
+                context: None,
+                output: None,
+                due_date: None,
+                assignee_type: None,
+                assignee_id: None,
+                scheduled_at: None,
+                recurrence_rule: None,
+            },
+            "tester",
+        );
+        specs
+            .iter()
+            .map(|(title, status, priority)| Task {
+                title: title.to_string(),
+                status: status.to_string(),
+                priority: priority.to_string(),
+                ..task.clone()
+            })
+            .collect()
+    }
+
+    fn visible(line: &str) -> String {
+        let mut out = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn renders_a_screen_that_fits() {
+        let mut dashboard = Dashboard::new("p1", "Platform");
+        dashboard.pulse = serde_json::json!({
+            "active_tasks": [{}], "blocked_tasks": [], "pending_review": [], "recently_completed": [],
+            "unread_events": 4, "blocked_by_deps": 0,
+            "agents": [{"name": "builder", "status": "online", "current_task": "Fix the login page"}],
+        });
+        let titles: Vec<String> = (0..12).map(|i| format!("Task {}", i)).collect();
+        let mut specs: Vec<(&str, &str, &str)> = titles
+            .iter()
+            .map(|t| (t.as_str(), "todo", "medium"))
+            .collect();
+        specs.push((
+            "A very long title that cannot possibly fit in one column",
+            "in_progress",
+            "critical",
+        ));
+        dashboard.tasks = tasks(&specs);
+        for i in 0..30 {
+            dashboard.push_event(format!("12:00:{:02} task.updated Task {}", i, i));
+        }
+
+        let lines = dashboard.render(100, 30);
+        assert_eq!(lines.len(), 30);
+        assert!(lines.iter().all(|l| visible(l).chars().count() <= 100));
+        let screen: String = lines.iter().map(|l| visible(l) + "\n").collect();
+        assert!(screen.contains("OpenGate · Platform"));
+        assert!(screen.contains("active 1 "));
+        assert!(screen.contains("TODO (12)"));
+        assert!(screen.contains("more"));
+        assert!(screen.contains("!! A very long"));
+        assert!(screen.contains("builder"));
+        assert!(screen.contains("Task 29"), "newest events are shown");
+        assert!(!screen.contains("12:00:00"), "oldest events scroll off");
+
+        // A tiny terminal still gets exactly its rows
+        assert_eq!(dashboard.render(10, 5).len(), 5);
+    }
+
+    #[test]
+    fn formats_event_messages() {
+        let line = event_line(&serde_json::json!({
+            "type": "event", "sub": "1", "event": "task.claimed", "data": {"title": "Fix login"},
+        }))
+        .unwrap();
+        assert!(line.contains("task.claimed") && line.ends_with("Fix login"));
+        assert!(event_line(&serde_json::json!({"type": "ping"})).is_none());
+    }
+}
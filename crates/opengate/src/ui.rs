@@ -0,0 +1,90 @@
+//! `--ui-dir`: serve a built web dashboard from the API's own port. Paths that aren't
+//! files fall back to `index.html` so client-side routes survive a reload; `/api` paths
+//! never do, and stay 404.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use axum::{
+    http::{header, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+
+static UI_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Serve the dashboard in `dir` for every path no route claims.
+pub fn set_dir(dir: PathBuf) {
+    let _ = UI_DIR.set(dir);
+}
+
+/// Router fallback: the dashboard when one is configured, 404 otherwise.
+pub async fn fallback(method: Method, uri: Uri) -> Response {
+    match UI_DIR.get() {
+        Some(dir) => serve_from(dir, &method, uri.path()).await,
+        None => not_found(),
+    }
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "Not found").into_response()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "html" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "txt" => "text/plain; charset=utf-8",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The file under `dir` that `path` names, `index.html` for client-side routes.
+pub async fn serve_from(dir: &Path, method: &Method, path: &str) -> Response {
+    if !matches!(*method, Method::GET | Method::HEAD) || path == "/api" || path.starts_with("/api/")
+    {
+        return not_found();
+    }
+    let relative = Path::new(path.trim_start_matches('/'));
+    // Only plain names: no `..`, roots or prefixes that could leave `dir`
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return not_found();
+    }
+    let mut file = dir.join(relative);
+    if tokio::fs::metadata(&file).await.is_ok_and(|m| m.is_dir()) {
+        file = file.join("index.html");
+    } else if relative.extension().is_none() && !file.exists() {
+        file = dir.join("index.html");
+    }
+    let Ok(body) = tokio::fs::read(&file).await else {
+        return not_found();
+    };
+    // Bundled assets carry content hashes; the page that names them must be re-checked
+    let cache = if file.file_name().is_some_and(|n| n == "index.html") {
+        "no-cache"
+    } else {
+        "public, max-age=3600"
+    };
+    (
+        [
+            (header::CONTENT_TYPE, content_type(&file)),
+            (header::CACHE_CONTROL, cache),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -11,13 +11,16 @@ use opengate::app::{build_router, AppState};
 use opengate::db;
 use opengate::db_ops;
 use opengate::storage::sqlite::SqliteBackend;
-use opengate_models::CreateAgent;
+use opengate::storage::AgentStore;
+use opengate_models::{Agent, AssignStrategy, CreateAgent};
+use std::collections::HashMap;
 
 /// A self-contained test server with its own temp DB, agent, and random port.
 struct TestServer {
     base_url: String,
     api_key: String,
     agent_id: String,
+    storage: Arc<SqliteBackend>,
     _tmp: TempDir, // dropped (and cleaned up) when TestServer is dropped
 }
 
@@ -37,9 +40,11 @@ impl TestServer {
         );
         let agent_id = agent.id.clone();
 
-        let storage = SqliteBackend::new(Arc::new(Mutex::new(conn)));
+        let storage = Arc::new(SqliteBackend::new(Arc::new(Mutex::new(conn))));
+        // Background forwarders that follow the event log, as in run_server
+        opengate::slack::spawn(storage.clone());
         let state = AppState {
-            storage: Arc::new(storage),
+            storage: storage.clone(),
             setup_token: "test-setup-token".to_string(),
             event_bus: opengate::events::EventBus::default(),
         };
@@ -58,6 +63,7 @@ impl TestServer {
             base_url: format!("http://{addr}"),
             api_key,
             agent_id,
+            storage,
             _tmp: tmp,
         }
     }
@@ -70,6 +76,17 @@ impl TestServer {
         &self.agent_id
     }
 
+    /// An orchestrator straight from the database, since agents cannot hand out roles.
+    /// Returns the body `POST /api/agents` would.
+    fn create_orchestrator(&self, name: &str, owner_id: Option<&str>) -> Value {
+        let input = CreateAgent {
+            owner_id: owner_id.map(str::to_string),
+            ..CreateAgent::new(name).with_role("orchestrator")
+        };
+        let (agent, api_key) = self.storage.create_agent(None, &input);
+        json!({ "agent": agent, "api_key": api_key })
+    }
+
     fn auth_header(&self) -> String {
         format!("Bearer {}", self.api_key)
     }
@@ -127,6 +144,95 @@ impl TestServer {
         assert_eq!(resp.status(), 200);
         resp.json::<Value>().await.unwrap()
     }
+
+    /// Create a recurring task with the given schedule and rule.
+    async fn create_recurring_task(
+        &self,
+        project_id: &str,
+        title: &str,
+        scheduled_at: &str,
+        rule: Value,
+    ) -> Value {
+        let resp = self
+            .client()
+            .post(format!(
+                "{}/api/projects/{}/tasks",
+                self.base_url, project_id
+            ))
+            .header("Authorization", self.auth_header())
+            .json(&json!({
+                "title": title,
+                "scheduled_at": scheduled_at,
+                "recurrence_rule": rule,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+        resp.json::<Value>().await.unwrap()
+    }
+
+    /// Drive a task through todo → in_progress → done.
+    async fn finish_task(&self, task_id: &str) {
+        self.client()
+            .patch(format!("{}/api/tasks/{}", self.base_url, task_id))
+            .header("Authorization", self.auth_header())
+            .json(&json!({ "status": "todo" }))
+            .send()
+            .await
+            .unwrap();
+        self.client()
+            .post(format!("{}/api/tasks/{}/claim", self.base_url, task_id))
+            .header("Authorization", self.auth_header())
+            .send()
+            .await
+            .unwrap();
+        let resp = self
+            .client()
+            .post(format!("{}/api/tasks/{}/complete", self.base_url, task_id))
+            .header("Authorization", self.auth_header())
+            .json(&json!({ "summary": "done" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    /// All tasks in a project whose recurrence_parent_id is `parent_id`.
+    async fn recurrence_children(&self, project_id: &str, parent_id: &str) -> Vec<Value> {
+        let tasks: Value = self
+            .client()
+            .get(format!(
+                "{}/api/projects/{}/tasks",
+                self.base_url, project_id
+            ))
+            .header("Authorization", self.auth_header())
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        tasks
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|t| t["recurrence_parent_id"].as_str() == Some(parent_id))
+            .cloned()
+            .collect()
+    }
+
+    async fn get_task(&self, task_id: &str) -> Value {
+        self.client()
+            .get(format!("{}/api/tasks/{}", self.base_url, task_id))
+            .header("Authorization", self.auth_header())
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
 }
 
 type WsSink = futures_util::stream::SplitSink<
@@ -457,6 +563,84 @@ async fn test_agent_heartbeat() {
     assert_eq!(body["status"], "ok");
 }
 
+#[tokio::test]
+async fn test_heartbeat_status_payload() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Heartbeat Status").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Keep busy").await;
+    s.client()
+        .post(format!(
+            "{}/api/tasks/{}/claim",
+            s.base_url,
+            task["id"].as_str().unwrap()
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    let beat = |body: Value| {
+        s.client()
+            .post(format!("{}/api/agents/heartbeat", s.base_url))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
+    let resp = beat(json!({"status_message": "x".repeat(501)}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
+    let resp = beat(json!({"load": 0.75, "version": "1.4.2", "status_message": "indexing repo"}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let get = |path: String| {
+        s.client()
+            .get(format!("{}{}", s.base_url, path))
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let agent: Value = get(format!("/api/agents/{}", s.agent_id()))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(agent["heartbeat_status"]["load"], 0.75);
+    assert_eq!(agent["heartbeat_status"]["version"], "1.4.2");
+    assert!(agent["heartbeat_status"]["reported_at"].is_string());
+
+    let pulse: Value = get(format!("/api/projects/{}/pulse", pid))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        pulse["agents"][0]["heartbeat_status"]["status_message"],
+        "indexing repo"
+    );
+
+    // A bare heartbeat keeps the last reported status
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/heartbeat", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let inbox: Value = get("/api/agents/me/inbox".to_string())
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(inbox["heartbeat_status"]["version"], "1.4.2");
+}
+
 // 12. My tasks: lists tasks assigned to the authenticated agent
 #[tokio::test]
 async fn test_my_tasks() {
@@ -842,2019 +1026,2406 @@ async fn test_knowledge_base_crud() {
     assert_eq!(resp.status(), 404);
 }
 
-// 23. Review flow: in_progress -> review -> approve -> done
 #[tokio::test]
-async fn test_review_approve_flow() {
+async fn test_knowledge_versioning_and_revert() {
     let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Review").await;
+    let project = s.create_project("KB Versions").await;
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_ready_task(pid, "Test Review Flow").await;
-    let task_id = task["id"].as_str().unwrap();
+    let url = |suffix: &str| {
+        format!(
+            "{}/api/projects/{}/knowledge/deploy/runbook{}",
+            s.base_url, pid, suffix
+        )
+    };
 
-    // Claim (backlog -> in_progress)
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+    for content in ["v1: ssh in", "v2: use the script", "v3: oops"] {
+        let resp = s
+            .client()
+            .put(url(""))
+            .header("Authorization", s.auth_header())
+            .json(&json!({"title": "Runbook", "content": content, "tags": [content]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    let entry: Value = s
+        .client()
+        .get(url(""))
         .header("Authorization", s.auth_header())
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
+    assert_eq!(entry["version"], 3);
 
-    // Move to review
-    let resp = s
+    let versions: Vec<Value> = s
         .client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .get(url("/versions"))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "review" }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "review");
+    let numbers: Vec<i64> = versions
+        .iter()
+        .map(|v| v["version"].as_i64().unwrap())
+        .collect();
+    assert_eq!(numbers, vec![3, 2, 1]);
+    assert_eq!(versions[1]["content"], "v2: use the script");
+    assert_eq!(versions[1]["author_id"], s.agent_id());
 
-    // Approve
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/approve", s.base_url, task_id))
+        .post(url("/revert"))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "comment": "Looks good!" }))
+        .json(&json!({"version": 2}))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "done");
-}
-
-// 24. Review flow: request changes sends task back to in_progress
-#[tokio::test]
-async fn test_review_request_changes() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Changes").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_ready_task(pid, "Test Request Changes").await;
-    let task_id = task["id"].as_str().unwrap();
+    let reverted: Value = resp.json().await.unwrap();
+    assert_eq!(reverted["version"], 4);
+    assert_eq!(reverted["content"], "v2: use the script");
+    assert_eq!(reverted["tags"], json!(["v2: use the script"]));
 
-    // Claim + move to review
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+    let resp = s
+        .client()
+        .post(url("/revert"))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "review" }))
+        .json(&json!({"version": 9}))
         .send()
         .await
         .unwrap();
-
-    // Request changes
+    assert_eq!(resp.status(), 404);
     let resp = s
         .client()
-        .post(format!(
-            "{}/api/tasks/{}/request-changes",
-            s.base_url, task_id
+        .get(format!(
+            "{}/api/projects/{}/knowledge/missing/versions",
+            s.base_url, pid
         ))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "comment": "Please fix the error handling" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "in_progress"); // handoff back to executor — they fix and re-submit for review
+    assert_eq!(resp.status(), 404);
 }
 
-// 25. Handoff status transition: in_progress -> handoff -> in_progress
 #[tokio::test]
-async fn test_handoff_status() {
+async fn test_task_knowledge_links() {
     let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Handoff").await;
+    let project = s.create_project("KB Links").await;
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_ready_task(pid, "Test Handoff").await;
-    let task_id = task["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Touch the payments service").await;
+    let tid = task["id"].as_str().unwrap();
+    for (key, category) in [
+        ("arch/payments", "architecture"),
+        ("gotchas/idempotency", "gotcha"),
+    ] {
+        s.client()
+            .put(format!(
+                "{}/api/projects/{}/knowledge/{}",
+                s.base_url, pid, key
+            ))
+            .header("Authorization", s.auth_header())
+            .json(&json!({"title": key, "content": "...", "category": category}))
+            .send()
+            .await
+            .unwrap();
+    }
 
-    // Claim
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
+    let link = |key: &str| {
+        s.client()
+            .post(format!("{}/api/tasks/{}/knowledge", s.base_url, tid))
+            .header("Authorization", s.auth_header())
+            .json(&json!({ "key": key }))
+            .send()
+    };
+    assert_eq!(link("arch/payments").await.unwrap().status(), 200);
+    assert_eq!(link("arch/payments").await.unwrap().status(), 200);
+    let resp = link("gotchas/idempotency").await.unwrap();
+    let linked: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(linked.len(), 2);
+    assert_eq!(link("nope").await.unwrap().status(), 404);
+
+    // The full task view carries the summaries
+    let full = s.get_task(tid).await;
+    let keys: Vec<&str> = full["knowledge"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|k| k["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(keys, vec!["arch/payments", "gotchas/idempotency"]);
+    assert_eq!(full["knowledge"][1]["category"], "gotcha");
 
-    // Move to handoff
     let resp = s
         .client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .delete(format!(
+            "{}/api/tasks/{}/knowledge/arch/payments",
+            s.base_url, tid
+        ))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "handoff" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "handoff");
+    assert_eq!(resp.status(), 204);
 
-    // Move back to in_progress
-    let resp = s
-        .client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+    // Deleting the entry drops its link too
+    s.client()
+        .delete(format!(
+            "{}/api/projects/{}/knowledge/gotchas/idempotency",
+            s.base_url, pid
+        ))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "in_progress" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "in_progress");
+    let full = s.get_task(tid).await;
+    assert!(full.get("knowledge").is_none());
 }
 
-// 26. Downstream output injection: completing parent injects output into child context
 #[tokio::test]
-async fn test_downstream_output_injection() {
+async fn test_knowledge_attachments() {
     let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Downstream").await;
+    let project = s.create_project("KB Attachments").await;
     let pid = project["id"].as_str().unwrap();
-
-    // Create parent task (move to todo so it can be claimed)
-    let parent = s.create_ready_task(pid, "Parent Task").await;
-    let parent_id = parent["id"].as_str().unwrap();
-
-    // Create child task with dependency on parent
-    let resp = s
-        .client()
-        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+    let entry_url = format!(
+        "{}/api/projects/{}/knowledge/runbooks/deploy",
+        s.base_url, pid
+    );
+    s.client()
+        .put(&entry_url)
         .header("Authorization", s.auth_header())
-        .json(&json!({
-            "title": "Child Task",
-            "priority": "medium",
-            "context": { "dependencies": [parent_id] }
-        }))
+        .json(&json!({"title": "Deploy", "content": "Run the pipeline"}))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let child: Value = resp.json().await.unwrap();
-    let child_id = child["id"].as_str().unwrap();
 
-    // Claim and complete parent with output
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, parent_id))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
-    s.client()
-        .post(format!("{}/api/tasks/{}/complete", s.base_url, parent_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({
-            "summary": "Parent done",
-            "output": { "pr_url": "https://github.com/test/pr/1" }
-        }))
-        .send()
+    let attach = |body: Value| {
+        s.client()
+            .post(format!("{}/attachments", entry_url))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
+    let resp = attach(
+        json!({"name": "Dashboard", "artifact_type": "url", "value": "https://ci.example.com"}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 201);
+    let url_att: Value = resp.json().await.unwrap();
+    assert!(url_att["created_by_id"].is_string());
+    let resp =
+        attach(json!({"name": "Checklist", "artifact_type": "text", "value": "1. tag\n2. push"}))
+            .await
+            .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = attach(json!({"name": "Bad", "artifact_type": "video", "value": "x"}))
         .await
         .unwrap();
+    assert_eq!(resp.status(), 400);
 
-    // Check child's context for upstream_outputs
-    let resp = s
+    let entry: Value = s
         .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, child_id))
+        .get(&entry_url)
         .header("Authorization", s.auth_header())
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let child_task: Value = resp.json().await.unwrap();
-    assert!(child_task["context"]["upstream_outputs"][parent_id].is_object());
+    let names: Vec<&str> = entry["attachments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["Dashboard", "Checklist"]);
     assert_eq!(
-        child_task["context"]["upstream_outputs"][parent_id]["output"]["pr_url"],
-        "https://github.com/test/pr/1"
+        entry["attachments"][0]["created_by_type"],
+        url_att["created_by_type"]
     );
-}
 
-// 27. Agent self-registration with setup token
-#[tokio::test]
-async fn test_agent_self_registration() {
-    let s = TestServer::start().await;
     let resp = s
         .client()
-        .post(format!("{}/api/agents/register", s.base_url))
-        .json(&json!({
-            "name": "self-registered-agent",
-            "skills": ["python", "ml"],
-            "setup_token": "test-setup-token"
-        }))
+        .delete(format!(
+            "{}/attachments/{}",
+            entry_url,
+            url_att["id"].as_str().unwrap()
+        ))
+        .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let body: Value = resp.json().await.unwrap();
-    assert!(body["api_key"].is_string());
-    assert!(body["agent"]["id"].is_string());
-    assert_eq!(body["agent"]["name"], "self-registered-agent");
-}
-
-// 28. Schema endpoint includes v2 endpoints
-#[tokio::test]
-async fn test_schema_includes_v2() {
-    let s = TestServer::start().await;
-    let resp = s
+    assert_eq!(resp.status(), 204);
+    let listed: Vec<Value> = s
         .client()
-        .get(format!("{}/api/schema", s.base_url))
+        .get(format!("{}/attachments", entry_url))
+        .header("Authorization", s.auth_header())
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let schema: Value = resp.json().await.unwrap();
-    let endpoints = schema["endpoints"].as_array().unwrap();
-
-    // Check v2 endpoints exist
-    let paths: Vec<&str> = endpoints
-        .iter()
-        .map(|e| e["path"].as_str().unwrap())
-        .collect();
-    assert!(paths.contains(&"/api/tasks/{id}/assign"));
-    assert!(paths.contains(&"/api/tasks/{id}/handoff"));
-    assert!(paths.contains(&"/api/tasks/{id}/approve"));
-    assert!(paths.contains(&"/api/tasks/{id}/request-changes"));
-    assert!(paths.contains(&"/api/agents/{id}"));
-    assert!(paths.contains(&"/api/projects/{id}/knowledge"));
-    assert!(paths.contains(&"/api/projects/{id}/knowledge/search"));
-    assert!(paths.contains(&"/api/projects/{id}/knowledge/{key}"));
-
-    // Check handoff in status_flow
-    assert!(schema["status_flow"]["handoff"].is_array());
-}
-
-// Offline agent assignment — should succeed with warning, not block
-#[tokio::test]
-async fn test_assign_to_offline_agent() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Offline Assign").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Test Offline Assignment").await;
-    let task_id = task["id"].as_str().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["name"], "Checklist");
 
-    // Agent has never sent heartbeat → offline status
-    // Assign should still work
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
+        .post(format!(
+            "{}/api/projects/{}/knowledge/missing/attachments",
+            s.base_url, pid
+        ))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "agent_id": s.agent_id() }))
+        .json(&json!({"name": "x", "artifact_type": "url", "value": "https://x"}))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "todo");
-    assert!(body["assignee_id"].as_str().is_some());
+    assert_eq!(resp.status(), 404);
 }
 
-// Stale release should NOT auto-release tasks in review status
 #[tokio::test]
-async fn test_stale_release_preserves_review() {
+async fn test_knowledge_export_import_bundle() {
     let s = TestServer::start().await;
-
-    // Register a second agent to serve as reviewer (via setup-token registration)
-    let resp = s.client()
-        .post(format!("{}/api/agents/register", s.base_url))
-        .json(&json!({"name": "reviewer-agent", "setup_token": "test-setup-token", "skills": ["rust"]}))
-        .send()
-        .await
-        .unwrap();
-    assert!(
-        resp.status().is_success(),
-        "Failed to register reviewer agent: {}",
-        resp.status()
-    );
-    let reviewer: Value = resp.json().await.unwrap();
-    let reviewer_id = reviewer["agent"]["id"].as_str().unwrap().to_string();
-
-    let project = s.create_project("Test Project - Stale Release").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_ready_task(pid, "Test Review Preservation").await;
-    let task_id = task["id"].as_str().unwrap();
-
-    // Claim
+    let source = s.create_project("KB Source").await;
+    let src = source["id"].as_str().unwrap();
     s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .put(format!(
+            "{}/api/projects/{}/knowledge/arch/payments",
+            s.base_url, src
+        ))
         .header("Authorization", s.auth_header())
+        .json(&json!({
+            "title": "Payments",
+            "content": "# Payments\n\nIdempotency keys everywhere.",
+            "tags": ["payments", "api"],
+            "category": "architecture",
+            "metadata": {"owner": "team-billing"}
+        }))
         .send()
         .await
         .unwrap();
 
-    // Submit for review (explicitly nominate the reviewer agent)
     let resp = s
         .client()
-        .post(format!(
-            "{}/api/tasks/{}/submit-review",
-            s.base_url, task_id
+        .get(format!(
+            "{}/api/projects/{}/knowledge/export",
+            s.base_url, src
         ))
         .header("Authorization", s.auth_header())
-        .json(&json!({"reviewer_id": reviewer_id}))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "review");
+    assert_eq!(resp.headers()["content-type"], "application/x-tar");
+    let bundle = resp.bytes().await.unwrap();
+    let text = String::from_utf8_lossy(&bundle);
+    assert!(text.contains("arch/payments.md"));
+    assert!(text.contains("category: \"architecture\""));
+
+    let target = s.create_project("KB Mirror").await;
+    let dst = target["id"].as_str().unwrap();
+    let import = |body: Vec<u8>| {
+        s.client()
+            .post(format!(
+                "{}/api/projects/{}/knowledge/import",
+                s.base_url, dst
+            ))
+            .header("Authorization", s.auth_header())
+            .body(body)
+            .send()
+    };
+    let result: Value = import(bundle.to_vec()).await.unwrap().json().await.unwrap();
+    assert_eq!(result["created"], json!(["arch/payments"]));
 
-    // Verify task stays in review even without heartbeat
-    // (release_stale_tasks excludes review status)
-    let resp = s
+    let entry: Value = s
         .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .get(format!(
+            "{}/api/projects/{}/knowledge/arch/payments",
+            s.base_url, dst
+        ))
         .header("Authorization", s.auth_header())
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "review");
-    assert!(body["assignee_id"].as_str().is_some());
-}
-
-// ============================================================
-// Webhook notification tests
-// ============================================================
-
-/// Spawn a tiny axum server that records incoming POST payloads and
-/// responds with `response_status`. Returns (url, Arc to recorded bodies).
-async fn start_mock_webhook(response_status: u16) -> (String, Arc<tokio::sync::Mutex<Vec<Value>>>) {
-    use axum::extract::State;
-    use axum::{http::StatusCode, routing::post, Router};
-
-    let received: Arc<tokio::sync::Mutex<Vec<Value>>> =
-        Arc::new(tokio::sync::Mutex::new(Vec::new()));
-    let received_clone = received.clone();
-
-    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-    let addr: SocketAddr = listener.local_addr().unwrap();
-
-    let app = Router::new()
-        .route(
-            "/hook",
-            post(
-                move |State(state): State<(Arc<tokio::sync::Mutex<Vec<Value>>>, StatusCode)>,
-                      body: axum::body::Bytes| async move {
-                    let parsed: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
-                    state.0.lock().await.push(parsed);
-                    state.1
-                },
-            ),
-        )
-        .with_state((
-            received_clone,
-            StatusCode::from_u16(response_status).unwrap(),
-        ));
-
-    tokio::spawn(async move {
-        axum::serve(listener, app).await.unwrap();
-    });
+    assert_eq!(entry["title"], "Payments");
+    assert_eq!(
+        entry["content"],
+        "# Payments\n\nIdempotency keys everywhere."
+    );
+    assert_eq!(entry["tags"], json!(["payments", "api"]));
+    assert_eq!(entry["metadata"]["owner"], "team-billing");
 
-    (format!("http://{}/hook", addr), received)
-}
+    // Re-importing the same bundle is a no-op
+    let result: Value = import(bundle.to_vec()).await.unwrap().json().await.unwrap();
+    assert_eq!(result["unchanged"], json!(["arch/payments"]));
+    assert!(result["updated"].as_array().unwrap().is_empty());
 
-/// Helper: set webhook_url (and optional webhook_events) on the test agent.
-async fn set_agent_webhook(s: &TestServer, url: &str, events: Option<Vec<&str>>) {
-    let mut body = json!({ "webhook_url": url });
-    if let Some(ev) = events {
-        body["webhook_events"] = json!(ev);
-    }
-    let resp = s
-        .client()
-        .patch(format!("{}/api/agents/{}", s.base_url, s.agent_id()))
-        .header("Authorization", s.auth_header())
-        .json(&body)
-        .send()
+    let resp = import(b"definitely not a tarball".repeat(50))
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200, "Failed to update agent webhook config");
+    assert_eq!(resp.status(), 422);
 }
 
-/// Helper: create a project + task and assign it to the test agent.
-/// Returns task_id.
-async fn assign_task_to_self(s: &TestServer, project_suffix: &str) -> String {
-    let project = s
-        .create_project(&format!("Webhook Test Project {}", project_suffix))
-        .await;
+#[tokio::test]
+async fn test_knowledge_review_deadline() {
+    let s = TestServer::start().await;
+    let project = s.create_project("KB Freshness").await;
     let pid = project["id"].as_str().unwrap();
-    let task = s
-        .create_task(pid, &format!("Webhook Test Task {}", project_suffix))
-        .await;
-    let task_id = task["id"].as_str().unwrap().to_string();
+    let put = |key: &str, body: Value| {
+        s.client()
+            .put(format!(
+                "{}/api/projects/{}/knowledge/{}",
+                s.base_url, pid, key
+            ))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
 
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "agent_id": s.agent_id() }))
-        .send()
-        .await
-        .unwrap();
-    assert_eq!(
-        resp.status(),
-        200,
-        "Failed to assign task: {}",
-        resp.text().await.unwrap()
-    );
-    task_id
-}
+    let resp = put(
+        "decisions/queue",
+        json!({"title": "Use SQS", "content": "...", "review_after": "2020-01-01"}),
+    )
+    .await
+    .unwrap();
+    let entry: Value = resp.json().await.unwrap();
+    assert_eq!(entry["review_after"], "2020-01-01T00:00:00Z");
+    assert_eq!(entry["stale"], true);
+    let entry: Value = put(
+        "decisions/db",
+        json!({"title": "Use SQLite", "content": "...", "review_after": "2999-01-01T00:00:00+01:00"}),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(entry["review_after"], "2998-12-31T23:00:00Z");
+    assert_eq!(entry["stale"], false);
+    let resp = put(
+        "decisions/bad",
+        json!({"title": "x", "content": "x", "review_after": "soon"}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 422);
 
-/// Helper: fetch unread notifications for the test agent.
-async fn unread_notifications(s: &TestServer) -> Vec<Value> {
-    let resp = s
-        .client()
-        .get(format!(
-            "{}/api/agents/me/notifications?unread=true",
-            s.base_url
-        ))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
-    assert_eq!(resp.status(), 200);
-    resp.json::<Value>()
-        .await
-        .unwrap()
-        .as_array()
-        .unwrap()
-        .clone()
-}
+    let pulse = |s: &TestServer| {
+        s.client()
+            .get(format!("{}/api/projects/{}/pulse", s.base_url, pid))
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let body: Value = pulse(&s).await.unwrap().json().await.unwrap();
+    let review = body["knowledge_needing_review"].as_array().unwrap();
+    assert_eq!(review.len(), 1);
+    assert_eq!(review[0]["key"], "decisions/queue");
+
+    // An edit that doesn't mention review_after keeps the deadline
+    let entry: Value = put(
+        "decisions/queue",
+        json!({"title": "Use SQS", "content": "edited"}),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(entry["stale"], true);
 
-/// Helper: fetch all notifications for the test agent.
-async fn all_notifications(s: &TestServer) -> Vec<Value> {
-    let resp = s
-        .client()
-        .get(format!("{}/api/agents/me/notifications", s.base_url))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
-    assert_eq!(resp.status(), 200);
-    resp.json::<Value>()
-        .await
-        .unwrap()
+    // Reviewing means pushing the deadline out (or clearing it)
+    put(
+        "decisions/queue",
+        json!({"title": "Use SQS", "content": "edited", "review_after": ""}),
+    )
+    .await
+    .unwrap();
+    let body: Value = pulse(&s).await.unwrap().json().await.unwrap();
+    assert!(body["knowledge_needing_review"]
         .as_array()
         .unwrap()
-        .clone()
+        .is_empty());
 }
 
-// ------ Test 1: successful delivery → webhook fired + notification auto-acked ------
-
 #[tokio::test]
-async fn test_webhook_delivery_auto_ack() {
-    let s = TestServer::start().await;
-    let (hook_url, received) = start_mock_webhook(200).await;
-
-    set_agent_webhook(&s, &hook_url, None).await;
-    assign_task_to_self(&s, "auto-ack").await;
-
-    // Give the async webhook task time to fire and process
-    tokio::time::sleep(tokio::time::Duration::from_millis(800)).await;
-
-    // Notification should be auto-acked after successful delivery
-    let unread = unread_notifications(&s).await;
-    assert!(
-        unread.is_empty(),
-        "Expected no unread notifications after auto-ack, found: {}",
-        unread.len()
+async fn test_stale_knowledge_sweep_announces_once() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("freshness.db").to_str().unwrap());
+    let agents = strategy_pool(&conn, &[("author", "mid")]);
+    let author = &agents[0];
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "KB".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &author.id,
     );
-
-    // Webhook should have been called
-    let reqs = received.lock().await;
-    assert!(!reqs.is_empty(), "Mock webhook server received no requests");
-    // Verify event_type in payload
-    let notification_reqs: Vec<_> = reqs
-        .iter()
-        .filter(|r| r.get("event").and_then(|e| e.as_str()) == Some("notification"))
-        .collect();
-    assert!(
-        !notification_reqs.is_empty(),
-        "Expected at least one notification webhook payload"
+    let input = |review_after: &str| opengate_models::UpsertKnowledge {
+        title: "Runbook".to_string(),
+        content: "...".to_string(),
+        metadata: None,
+        tags: None,
+        category: None,
+        review_after: Some(review_after.to_string()),
+    };
+    db_ops::upsert_knowledge(
+        &conn,
+        &project.id,
+        "ops/runbook",
+        &input("2020-01-01T00:00:00Z"),
+        "agent",
+        &author.id,
     );
-    let ev_type = notification_reqs[0]["event_type"].as_str().unwrap_or("");
-    assert_eq!(ev_type, "task.assigned");
-
-    // webhook_status in DB should be "delivered"
-    let all = all_notifications(&s).await;
-    let notif = all
-        .iter()
-        .find(|n| n["event_type"].as_str() == Some("task.assigned"))
-        .expect("task.assigned notification missing");
-    assert_eq!(
-        notif["webhook_status"].as_str(),
-        Some("delivered"),
-        "webhook_status should be 'delivered'"
+    db_ops::upsert_knowledge(
+        &conn,
+        &project.id,
+        "ops/fresh",
+        &input("2999-01-01T00:00:00Z"),
+        "agent",
+        &author.id,
     );
-}
-
-// ------ Test 2: event filter — subscribed only to review_requested, not assigned ------
-
-#[tokio::test]
-async fn test_webhook_event_filter_blocks_unsubscribed() {
-    let s = TestServer::start().await;
-    let (hook_url, received) = start_mock_webhook(200).await;
-
-    // Subscribe only to task.review_requested — task.assigned should be filtered
-    set_agent_webhook(&s, &hook_url, Some(vec!["task.review_requested"])).await;
-    assign_task_to_self(&s, "event-filter").await;
-
-    tokio::time::sleep(tokio::time::Duration::from_millis(800)).await;
 
-    // Webhook should NOT have fired for task.assigned
-    let reqs = received.lock().await;
-    let notification_reqs: Vec<_> = reqs
+    let (stale, _) = db_ops::sweep_stale_knowledge(&conn);
+    let keys: Vec<&str> = stale.iter().map(|e| e.key.as_str()).collect();
+    assert_eq!(keys, vec!["ops/runbook"]);
+    let notifications = db_ops::list_notifications(&conn, &author.id, None);
+    assert!(notifications
         .iter()
-        .filter(|r| {
-            r.get("event").and_then(|e| e.as_str()) == Some("notification")
-                && r.get("event_type").and_then(|e| e.as_str()) == Some("task.assigned")
-        })
-        .collect();
-    assert!(
-        notification_reqs.is_empty(),
-        "Notification webhook should have been filtered out for task.assigned"
-    );
-    drop(reqs);
+        .any(|n| n.event_type == "knowledge.stale" && n.title.contains("Runbook")));
+    assert!(db_ops::sweep_stale_knowledge(&conn).0.is_empty());
 
-    // Notification should still be unread (not auto-acked because webhook was never sent)
-    let unread = unread_notifications(&s).await;
-    assert!(
-        !unread.is_empty(),
-        "Notification should remain unread when webhook was filtered out"
-    );
-    // webhook_status should be null (delivery never attempted)
-    let notif = unread
-        .iter()
-        .find(|n| n["event_type"].as_str() == Some("task.assigned"))
-        .expect("task.assigned notification missing");
-    assert!(
-        notif["webhook_status"].is_null(),
-        "webhook_status should be null when delivery was not attempted (filtered)"
+    // A new (still past) deadline counts as a fresh review cycle
+    db_ops::upsert_knowledge(
+        &conn,
+        &project.id,
+        "ops/runbook",
+        &input("2021-01-01T00:00:00Z"),
+        "agent",
+        &author.id,
     );
+    assert_eq!(db_ops::sweep_stale_knowledge(&conn).0.len(), 1);
 }
 
-// ------ Test 3: failed delivery → notification stays unread, status = "failed" ------
-
-/// NOTE: This test waits ~6 s for the 3-retry backoff to complete (1 s + 4 s).
 #[tokio::test]
-async fn test_webhook_failed_delivery_leaves_unread() {
-    let s = TestServer::start().await;
-
-    // Use a port that is (almost certainly) not listening → immediate connection refused
-    let hook_url = "http://127.0.0.1:39876";
-
-    set_agent_webhook(&s, hook_url, None).await;
-    assign_task_to_self(&s, "failed-delivery").await;
-
-    // Backoff: 1 s after attempt 1, 4 s after attempt 2, then done → wait ≥6 s
-    tokio::time::sleep(tokio::time::Duration::from_secs(7)).await;
-
-    // Notification should still be unread
-    let unread = unread_notifications(&s).await;
-    assert!(
-        !unread.is_empty(),
-        "Notification should remain unread after failed webhook delivery"
-    );
+async fn test_notification_expiry_policies() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("expiry.db").to_str().unwrap());
+    let agents = strategy_pool(&conn, &[("sleeper", "mid")]);
+    let agent = &agents[0];
+    let update: opengate_models::UpdateAgent = serde_json::from_value(json!({
+        "notification_preferences": {"task.assigned": {"archive_after_hours": 72}}
+    }))
+    .unwrap();
+    db_ops::update_agent(&conn, &agent.id, &update).unwrap();
+
+    for (event_type, age_hours) in [
+        ("task.progress", 30),
+        ("task.progress", 2),
+        ("task.assigned", 30),
+        ("task.assigned", 100),
+    ] {
+        conn.execute(
+            "INSERT INTO events (event_type, project_id, actor_type, actor_id) VALUES ('task.progress', 'p', 'system', 'system')",
+            [],
+        )
+        .unwrap();
+        let n = db_ops::insert_question_notification(
+            &conn,
+            &agent.id,
+            conn.last_insert_rowid(),
+            event_type,
+            "Update",
+            None,
+            None,
+        );
+        conn.execute(
+            &format!(
+                "UPDATE notifications SET created_at = datetime('now', '-{} hours') WHERE id = {}",
+                age_hours, n.notification_id
+            ),
+            [],
+        )
+        .unwrap();
+    }
 
-    // webhook_status should be "failed"
-    let all = all_notifications(&s).await;
-    let notif = all
+    // Progress is auto-acked after 24h by default; assignments past 72h are archived
+    let sweep = db_ops::sweep_notification_policies(&conn);
+    assert_eq!(sweep.acknowledged, 1);
+    assert_eq!(sweep.archived, 1);
+    let notes = db_ops::list_notifications(&conn, &agent.id, None);
+    let unread: Vec<(&str, bool)> = notes
         .iter()
-        .find(|n| n["event_type"].as_str() == Some("task.assigned"))
-        .expect("task.assigned notification missing");
+        .filter(|n| !n.read)
+        .map(|n| (n.event_type.as_str(), n.archived))
+        .collect();
     assert_eq!(
-        notif["webhook_status"].as_str(),
-        Some("failed"),
-        "webhook_status should be 'failed' after exhausting all retries"
+        unread,
+        vec![("task.progress", false), ("task.assigned", false)]
     );
+    assert_eq!(notes.iter().filter(|n| n.archived).count(), 1);
+    assert_eq!(db_ops::sweep_notification_policies(&conn).archived, 0);
 }
 
-// ═══════════════════════════════════════════════════════
-// v4: Task Dependencies — schema + API + cycle detection
-// ═══════════════════════════════════════════════════════
-
 #[tokio::test]
-async fn test_task_dependencies_crud_and_auto_unblock() {
+async fn test_shared_knowledge_scope() {
     let s = TestServer::start().await;
-    let proj = s.create_project("deps-test").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    // Create two tasks: A depends on B
-    let a = s.create_task(pid, "Task A").await;
-    let b = s.create_task(pid, "Task B").await;
-    let a_id = a["id"].as_str().unwrap();
-    let b_id = b["id"].as_str().unwrap();
-
-    // POST /api/tasks/A/dependencies { depends_on: [B] }
+    let project = s.create_project("KB Shared").await;
+    let pid = project["id"].as_str().unwrap();
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+        .put(format!("{}/api/knowledge/standards/rust", s.base_url))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "depends_on": [b_id] }))
+        .json(&json!({"title": "Rust standards", "content": "Run clippy with -D warnings"}))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200, "add dependency should succeed");
-    let task_a: Value = resp.json().await.unwrap();
-    assert!(
-        task_a["dependencies"]
+    assert_eq!(resp.status(), 200);
+    let shared: Value = resp.json().await.unwrap();
+    assert_eq!(shared["project_id"], "shared");
+    s.client()
+        .put(format!(
+            "{}/api/projects/{}/knowledge/ci/setup",
+            s.base_url, pid
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"title": "CI", "content": "We run clippy in CI"}))
+        .send()
+        .await
+        .unwrap();
+
+    let search = |scope: &str| {
+        s.client()
+            .get(format!(
+                "{}/api/projects/{}/knowledge/search?q=clippy{}",
+                s.base_url, pid, scope
+            ))
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let keys = |entries: &Value| -> Vec<String> {
+        entries
             .as_array()
             .unwrap()
             .iter()
-            .any(|d| d.as_str() == Some(b_id)),
-        "Task A should list B in its dependencies"
-    );
+            .map(|e| e["key"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let project_only: Value = search("").await.unwrap().json().await.unwrap();
+    assert_eq!(keys(&project_only), vec!["ci/setup"]);
+    let all: Value = search("&scope=all").await.unwrap().json().await.unwrap();
+    assert_eq!(keys(&all), vec!["ci/setup", "standards/rust"]);
+    assert_eq!(search("&scope=galaxy").await.unwrap().status(), 400);
 
-    // GET /api/tasks/A/dependencies
+    // The shared space isn't visible as a regular project
+    let listed: Value = s
+        .client()
+        .get(format!("{}/api/knowledge", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(keys(&listed), vec!["standards/rust"]);
     let resp = s
         .client()
-        .get(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+        .get(format!("{}/api/projects/shared/knowledge", s.base_url))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let deps: Value = resp.json().await.unwrap();
-    assert_eq!(deps.as_array().unwrap().len(), 1);
-    assert_eq!(deps[0]["id"].as_str(), Some(b_id));
+    assert_eq!(resp.status(), 404);
 
-    // GET /api/tasks/B/dependents
     let resp = s
         .client()
-        .get(format!("{}/api/tasks/{}/dependents", s.base_url, b_id))
+        .delete(format!("{}/api/knowledge/standards/rust", s.base_url))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let dependents: Value = resp.json().await.unwrap();
-    assert_eq!(dependents.as_array().unwrap().len(), 1);
-    assert_eq!(dependents[0]["id"].as_str(), Some(a_id));
-
-    // Attempt to move A to in_progress — should fail (B is not done)
-    // First move A to todo (valid from backlog)
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
-        .send()
+    assert_eq!(resp.status(), 204);
+}
+
+#[tokio::test]
+async fn test_knowledge_backlinks_and_graph() {
+    let s = TestServer::start().await;
+    let project = s.create_project("KB Graph").await;
+    let pid = project["id"].as_str().unwrap();
+    let put = |key: &str, content: &str| {
+        s.client()
+            .put(format!(
+                "{}/api/projects/{}/knowledge/{}",
+                s.base_url, pid, key
+            ))
+            .header("Authorization", s.auth_header())
+            .json(&json!({"title": key, "content": content}))
+            .send()
+    };
+    put("arch/payments", "Core flow. Relies on [[decisions/queue]].")
         .await
         .unwrap();
-    // Now try in_progress — should fail with 409 Conflict due to unmet dep
-    let resp = s
+    put(
+        "decisions/queue",
+        "Chosen for [[arch/payments|payments]]; see also [[decisions/retired]].",
+    )
+    .await
+    .unwrap();
+    put("runbooks/replay", "Replay via the [[decisions/queue]] DLQ.")
+        .await
+        .unwrap();
+
+    let get = |path: String| {
+        s.client()
+            .get(path)
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let backlinks: Vec<Value> = get(format!(
+        "{}/api/projects/{}/knowledge/decisions/queue/backlinks",
+        s.base_url, pid
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let keys: Vec<&str> = backlinks
+        .iter()
+        .map(|b| b["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(keys, vec!["arch/payments", "runbooks/replay"]);
+
+    let graph: Value = get(format!(
+        "{}/api/projects/{}/knowledge/graph",
+        s.base_url, pid
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), 3);
+    assert_eq!(graph["edges"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        graph["unresolved"],
+        json!([{"from": "decisions/queue", "to": "decisions/retired"}])
+    );
+
+    // Rewriting an entry replaces its outgoing references
+    put("runbooks/replay", "Replay manually.").await.unwrap();
+    let backlinks: Vec<Value> = get(format!(
+        "{}/api/projects/{}/knowledge/decisions/queue/backlinks",
+        s.base_url, pid
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(backlinks.len(), 1);
+    let resp = get(format!(
+        "{}/api/projects/{}/knowledge/missing/backlinks",
+        s.base_url, pid
+    ))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_knowledge_templates_enforcement() {
+    let s = TestServer::start().await;
+    let templates: Vec<Value> = s
         .client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
+        .get(format!("{}/api/knowledge/templates", s.base_url))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "in_progress" }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let decision = templates
+        .iter()
+        .find(|t| t["category"] == "decision")
         .unwrap();
+    assert!(decision["content"]
+        .as_str()
+        .unwrap()
+        .contains("## Consequences"));
+    assert!(templates.iter().any(|t| t["category"] == "runbook"));
+
+    let project = s.create_project("KB Templates").await;
+    let pid = project["id"].as_str().unwrap();
+    let put = |content: &str, category: Option<&str>| {
+        s.client()
+            .put(format!(
+                "{}/api/projects/{}/knowledge/decisions/db",
+                s.base_url, pid
+            ))
+            .header("Authorization", s.auth_header())
+            .json(&json!({"title": "DB", "content": content, "category": category}))
+            .send()
+    };
+    // Not enforced by default
     assert_eq!(
-        resp.status(),
-        409,
-        "should block in_progress with 409 when dep not met"
+        put("Use SQLite", Some("decision")).await.unwrap().status(),
+        200
     );
 
-    // Move B to todo, then claim → in_progress, then complete it → A should auto-transition to todo
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, b_id))
+    let resp = s
+        .client()
+        .patch(format!("{}/api/projects/{}", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
+        .json(&json!({"enforce_knowledge_templates": true}))
         .send()
         .await
         .unwrap();
+    let updated: Value = resp.json().await.unwrap();
+    assert_eq!(updated["enforce_knowledge_templates"], true);
+
+    let resp = put(
+        "## Context\nSmall deploys\n## Decision\nSQLite",
+        Some("decision"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 422);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["missing_sections"],
+        json!(["Alternatives considered", "Consequences"])
+    );
+    let complete = decision["content"].as_str().unwrap().to_string();
+    assert_eq!(
+        put(&complete, Some("decision")).await.unwrap().status(),
+        200
+    );
+    // Entries without a category aren't held to any template
+    assert_eq!(put("free form", None).await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn test_bulk_knowledge_import_conflict_strategies() {
+    let s = TestServer::start().await;
+    let project = s.create_project("KB Bulk").await;
+    let pid = project["id"].as_str().unwrap();
     s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, b_id))
+        .put(format!(
+            "{}/api/projects/{}/knowledge/docs/setup",
+            s.base_url, pid
+        ))
         .header("Authorization", s.auth_header())
+        .json(&json!({
+            "title": "Setup",
+            "content": "old",
+            "tags": ["onboarding"],
+            "category": "reference",
+            "metadata": {"owner": "platform"}
+        }))
         .send()
         .await
         .unwrap();
-    s.client()
-        .post(format!("{}/api/tasks/{}/complete", s.base_url, b_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "summary": "B done" }))
-        .send()
+
+    let import = |strategy: &str, body: Value| {
+        s.client()
+            .post(format!(
+                "{}/api/projects/{}/knowledge/import?on_conflict={}",
+                s.base_url, pid, strategy
+            ))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
+    let get_setup = || async {
+        s.client()
+            .get(format!(
+                "{}/api/projects/{}/knowledge/docs/setup",
+                s.base_url, pid
+            ))
+            .header("Authorization", s.auth_header())
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap()
+    };
+    let incoming = json!([
+        {"key": "docs/setup", "title": "Setup v2", "content": "new", "tags": ["dev"], "metadata": {"reviewed": true}},
+        {"key": "docs/deploy", "title": "Deploy", "content": "ship it"}
+    ]);
+
+    let result: Value = import("skip", incoming.clone())
+        .await
+        .unwrap()
+        .json()
         .await
         .unwrap();
+    assert_eq!(result["created"], json!(["docs/deploy"]));
+    assert_eq!(result["skipped"], json!(["docs/setup"]));
+    assert_eq!(get_setup().await["content"], "old");
 
-    // Check A is now todo
-    let resp = s
-        .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, a_id))
-        .header("Authorization", s.auth_header())
-        .send()
+    let result: Value = import("merge", incoming.clone())
+        .await
+        .unwrap()
+        .json()
         .await
         .unwrap();
-    let task_a_after: Value = resp.json().await.unwrap();
+    assert_eq!(result["updated"], json!(["docs/setup"]));
+    assert_eq!(result["unchanged"], json!(["docs/deploy"]));
+    let merged = get_setup().await;
+    assert_eq!(merged["content"], "new");
+    assert_eq!(merged["tags"], json!(["onboarding", "dev"]));
+    assert_eq!(merged["category"], "reference");
     assert_eq!(
-        task_a_after["status"].as_str(),
-        Some("todo"),
-        "A should be auto-unblocked to todo after B completes"
+        merged["metadata"],
+        json!({"owner": "platform", "reviewed": true})
     );
 
-    // DELETE dependency
-    let resp = s
-        .client()
-        .delete(format!(
-            "{}/api/tasks/{}/dependencies/{}",
-            s.base_url, a_id, b_id
-        ))
-        .header("Authorization", s.auth_header())
-        .send()
+    let result: Value = import("overwrite", incoming.clone())
+        .await
+        .unwrap()
+        .json()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 204, "delete dependency should return 204");
+    assert_eq!(result["updated"], json!(["docs/setup"]));
+    let replaced = get_setup().await;
+    assert_eq!(replaced["tags"], json!(["dev"]));
+    assert!(replaced["category"].is_null());
+
+    assert_eq!(import("yolo", incoming).await.unwrap().status(), 400);
+    let resp = import("skip", json!([{"key": "", "title": "x", "content": "x"}]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
 }
 
 #[tokio::test]
-async fn test_task_dependency_cycle_detection() {
+async fn test_knowledge_usage_stats() {
     let s = TestServer::start().await;
-    let proj = s.create_project("cycle-test").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    let a = s.create_task(pid, "Cycle A").await;
-    let b = s.create_task(pid, "Cycle B").await;
-    let a_id = a["id"].as_str().unwrap();
-    let b_id = b["id"].as_str().unwrap();
+    let project = s.create_project("KB Usage").await;
+    let pid = project["id"].as_str().unwrap();
+    for key in ["hot", "warm", "cold"] {
+        s.client()
+            .put(format!(
+                "{}/api/projects/{}/knowledge/{}",
+                s.base_url, pid, key
+            ))
+            .header("Authorization", s.auth_header())
+            .json(&json!({"title": key, "content": "body"}))
+            .send()
+            .await
+            .unwrap();
+    }
+    for key in ["hot", "hot", "hot", "warm"] {
+        let resp = s
+            .client()
+            .get(format!(
+                "{}/api/projects/{}/knowledge/{}",
+                s.base_url, pid, key
+            ))
+            .header("Authorization", s.auth_header())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
 
-    // A depends on B
-    s.client()
-        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+    let stats: Value = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/knowledge/stats",
+            s.base_url, pid
+        ))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "depends_on": [b_id] }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
+    assert_eq!(stats["total_entries"], 3);
+    let most_read = stats["most_read"].as_array().unwrap();
+    assert_eq!(most_read.len(), 2);
+    assert_eq!(most_read[0]["key"], "hot");
+    assert_eq!(most_read[0]["reads"], 3);
+    assert_eq!(most_read[0]["readers"], 1);
+    assert_eq!(most_read[1]["key"], "warm");
+    let never_read: Vec<&str> = stats["never_read"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(never_read, vec!["cold"]);
 
-    // B depends on A → cycle! Should be rejected.
-    let resp = s
+    let pulse: Value = s
         .client()
-        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, b_id))
+        .get(format!("{}/api/projects/{}/pulse", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "depends_on": [a_id] }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 400, "cycle should be rejected");
-    let body: Value = resp.json().await.unwrap();
-    assert!(
-        body["error"].as_str().unwrap().contains("cycle"),
-        "error message should mention cycle"
-    );
+    assert_eq!(pulse["most_read_knowledge"][0]["key"], "hot");
+    assert_eq!(pulse["most_read_knowledge"][0]["reads"], 3);
 
-    // Self-dependency should also be rejected
-    let resp = s
+    // Deleting an entry drops its read history
+    s.client()
+        .delete(format!("{}/api/projects/{}/knowledge/hot", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let stats: Value = s
         .client()
-        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+        .get(format!(
+            "{}/api/projects/{}/knowledge/stats?limit=1",
+            s.base_url, pid
+        ))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "depends_on": [a_id] }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 400, "self-dependency should be rejected");
+    assert_eq!(stats["most_read"][0]["key"], "warm");
+    assert_eq!(stats["most_read"].as_array().unwrap().len(), 1);
 }
 
-// ═══════════════════════════════════════════════════════
-// v4: Task Scheduling — scheduled_at + auto-transition
-// ═══════════════════════════════════════════════════════
-
+// 23. Review flow: in_progress -> review -> approve -> done
 #[tokio::test]
-async fn test_task_scheduling_create_and_enforce() {
+async fn test_review_approve_flow() {
     let s = TestServer::start().await;
-    let proj = s.create_project("sched-test").await;
-    let pid = proj["id"].as_str().unwrap();
+    let project = s.create_project("Test Project - Review").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Test Review Flow").await;
+    let task_id = task["id"].as_str().unwrap();
 
-    // Create a task scheduled in the future
-    let future_time = "2099-01-01T00:00:00Z";
-    let resp = s
-        .client()
-        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+    // Claim (backlog -> in_progress)
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({
-            "title": "Future Task",
-            "priority": "high",
-            "scheduled_at": future_time
-        }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let task: Value = resp.json().await.unwrap();
-    let task_id = task["id"].as_str().unwrap();
-    assert_eq!(
-        task["scheduled_at"].as_str(),
-        Some(future_time),
-        "scheduled_at should be persisted"
-    );
 
-    // Cannot manually advance a future-scheduled task (to todo or in_progress)
+    // Move to review
     let resp = s
         .client()
         .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
+        .json(&json!({ "status": "review" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(
-        resp.status(),
-        400,
-        "future-scheduled task should block manual advance to todo"
-    );
+    assert_eq!(resp.status(), 200);
     let body: Value = resp.json().await.unwrap();
-    assert!(
-        body["error"].as_str().unwrap_or("").contains("scheduled"),
-        "error should mention scheduling"
-    );
+    assert_eq!(body["status"], "review");
 
-    // Schedule listing: GET /api/projects/:id/schedule
+    // Approve
     let resp = s
         .client()
-        .get(format!("{}/api/projects/{}/schedule", s.base_url, pid))
+        .post(format!("{}/api/tasks/{}/approve", s.base_url, task_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "comment": "Looks good!" }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let schedule: Value = resp.json().await.unwrap();
-    let entries = schedule.as_array().unwrap();
-    assert!(
-        !entries.is_empty(),
-        "schedule should include the future task"
-    );
-    let entry = entries
-        .iter()
-        .find(|e| e["id"].as_str() == Some(task_id))
-        .expect("task should be in schedule");
-    assert_eq!(entry["scheduled_at"].as_str(), Some(future_time));
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "done");
 }
 
+// 24. Review flow: request changes sends task back to in_progress
 #[tokio::test]
-async fn test_scheduled_task_auto_transition() {
+async fn test_review_request_changes() {
     let s = TestServer::start().await;
-    let proj = s.create_project("auto-sched-test").await;
-    let pid = proj["id"].as_str().unwrap();
+    let project = s.create_project("Test Project - Changes").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Test Request Changes").await;
+    let task_id = task["id"].as_str().unwrap();
 
-    // Create a task scheduled in the past (should be ready to transition)
-    let past_time = "2020-01-01T00:00:00Z";
-    let resp = s
-        .client()
-        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+    // Claim + move to review
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({
-            "title": "Past Scheduled Task",
-            "priority": "medium",
-            "scheduled_at": past_time
-        }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let task: Value = resp.json().await.unwrap();
-    let task_id = task["id"].as_str().unwrap();
-
-    // Trigger auto-transition
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/scheduled/transition", s.base_url))
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "review" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let result: Value = resp.json().await.unwrap();
-    assert!(
-        result["transitioned"].as_i64().unwrap_or(0) >= 1,
-        "should have transitioned at least one task"
-    );
 
-    // Task should now be todo
+    // Request changes
     let resp = s
         .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .post(format!(
+            "{}/api/tasks/{}/request-changes",
+            s.base_url, task_id
+        ))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "comment": "Please fix the error handling" }))
         .send()
         .await
         .unwrap();
-    let task_after: Value = resp.json().await.unwrap();
-    assert_eq!(
-        task_after["status"].as_str(),
-        Some("todo"),
-        "past-scheduled task should auto-transition to todo"
-    );
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "in_progress"); // handoff back to executor — they fix and re-submit for review
 }
 
-// ═══════════════════════════════════════════════════════
-// v4: Task Recurrence — rules + auto-creation on complete
-// ═══════════════════════════════════════════════════════
-
+// 25. Handoff status transition: in_progress -> handoff -> in_progress
 #[tokio::test]
-async fn test_task_recurrence_creates_next_on_complete() {
+async fn test_handoff_status() {
     let s = TestServer::start().await;
-    let proj = s.create_project("recurrence-test").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    // Create a daily recurring task
-    let resp = s
-        .client()
-        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
-        .header("Authorization", s.auth_header())
-        .json(&json!({
-            "title": "Daily Standup",
-            "priority": "medium",
-            "scheduled_at": "2026-02-23T09:00:00Z",
-            "recurrence_rule": {
-                "frequency": "daily",
-                "interval": 1
-            }
-        }))
-        .send()
-        .await
-        .unwrap();
-    assert_eq!(resp.status(), 201);
-    let task: Value = resp.json().await.unwrap();
+    let project = s.create_project("Test Project - Handoff").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Test Handoff").await;
     let task_id = task["id"].as_str().unwrap();
-    assert!(
-        task["recurrence_rule"].is_object(),
-        "recurrence_rule should be persisted"
-    );
-
-    // Move to todo first (scheduled_at in the past auto-transitions, but be explicit)
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
-        .send()
-        .await
-        .unwrap();
 
-    // Claim task → in_progress, then complete it
+    // Claim
     s.client()
         .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
+
+    // Move to handoff
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/complete", s.base_url, task_id))
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "summary": "Standup done" }))
+        .json(&json!({ "status": "handoff" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200, "complete should succeed");
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "handoff");
 
-    // Check that a new recurrence was created in the project
+    // Move back to in_progress
     let resp = s
         .client()
-        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "in_progress" }))
         .send()
         .await
         .unwrap();
-    let tasks: Value = resp.json().await.unwrap();
-    let task_list = tasks.as_array().unwrap();
-
-    let next_recurrence = task_list.iter().find(|t| {
-        t["recurrence_parent_id"].as_str() == Some(task_id)
-            || (t["id"].as_str() != Some(task_id)
-                && t["title"].as_str() == Some("Daily Standup")
-                && t["status"].as_str() != Some("done"))
-    });
-    assert!(
-        next_recurrence.is_some(),
-        "completing a recurring task should create the next occurrence"
-    );
-    let next = next_recurrence.unwrap();
-    assert_eq!(
-        next["status"].as_str(),
-        Some("backlog"),
-        "next recurrence should start in backlog"
-    );
-    assert!(
-        next["scheduled_at"].is_string(),
-        "next recurrence should have a scheduled_at"
-    );
-    // scheduled_at should be ~1 day after the original
-    assert_ne!(
-        next["scheduled_at"].as_str(),
-        Some("2026-02-23T09:00:00Z"),
-        "next occurrence should have a different scheduled_at"
-    );
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "in_progress");
 }
 
+// 26. Downstream output injection: completing parent injects output into child context
 #[tokio::test]
-async fn test_task_recurrence_respects_end_after() {
+async fn test_downstream_output_injection() {
     let s = TestServer::start().await;
-    let proj = s.create_project("recurrence-end-test").await;
-    let pid = proj["id"].as_str().unwrap();
+    let project = s.create_project("Test Project - Downstream").await;
+    let pid = project["id"].as_str().unwrap();
 
-    // Create a task that recurs only 1 more time after first completion
+    // Create parent task (move to todo so it can be claimed)
+    let parent = s.create_ready_task(pid, "Parent Task").await;
+    let parent_id = parent["id"].as_str().unwrap();
+
+    // Create child task with dependency on parent
     let resp = s
         .client()
         .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
         .json(&json!({
-            "title": "Limited Recurrence",
-            "priority": "low",
-            "scheduled_at": "2026-02-23T09:00:00Z",
-            "recurrence_rule": {
-                "frequency": "daily",
-                "interval": 1,
-                "end_after": 1
-            }
+            "title": "Child Task",
+            "priority": "medium",
+            "context": { "dependencies": [parent_id] }
         }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 201);
-    let task: Value = resp.json().await.unwrap();
-    let task_id = task["id"].as_str().unwrap();
+    let child: Value = resp.json().await.unwrap();
+    let child_id = child["id"].as_str().unwrap();
 
-    // Move to todo then claim original → complete → should create 1 recurrence
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
-        .send()
-        .await
-        .unwrap();
+    // Claim and complete parent with output
     s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, parent_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
     s.client()
-        .post(format!("{}/api/tasks/{}/complete", s.base_url, task_id))
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, parent_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "summary": "done" }))
+        .json(&json!({
+            "summary": "Parent done",
+            "output": { "pr_url": "https://github.com/test/pr/1" }
+        }))
         .send()
         .await
         .unwrap();
 
+    // Check child's context for upstream_outputs
     let resp = s
         .client()
-        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .get(format!("{}/api/tasks/{}", s.base_url, child_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    let tasks: Value = resp.json().await.unwrap();
-    let children: Vec<_> = tasks
-        .as_array()
-        .unwrap()
-        .iter()
-        .filter(|t| t["recurrence_parent_id"].as_str() == Some(task_id))
-        .collect();
+    assert_eq!(resp.status(), 200);
+    let child_task: Value = resp.json().await.unwrap();
+    assert!(child_task["context"]["upstream_outputs"][parent_id].is_object());
     assert_eq!(
-        children.len(),
-        1,
-        "should have exactly 1 recurrence after first completion"
+        child_task["context"]["upstream_outputs"][parent_id]["output"]["pr_url"],
+        "https://github.com/test/pr/1"
     );
-    let child_id = children[0]["id"].as_str().unwrap().to_string();
+}
 
-    // Move recurrence to todo, then claim + complete → end_after=1 means no more
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, child_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
-        .send()
-        .await
-        .unwrap();
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, child_id))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
-    s.client()
-        .post(format!("{}/api/tasks/{}/complete", s.base_url, child_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "summary": "done again" }))
+// 27. Agent self-registration with setup token
+#[tokio::test]
+async fn test_agent_self_registration() {
+    let s = TestServer::start().await;
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({
+            "name": "self-registered-agent",
+            "skills": ["python", "ml"],
+            "setup_token": "test-setup-token"
+        }))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["api_key"].is_string());
+    assert!(body["agent"]["id"].is_string());
+    assert_eq!(body["agent"]["name"], "self-registered-agent");
+}
 
+// 28. Schema endpoint includes v2 endpoints
+#[tokio::test]
+async fn test_schema_includes_v2() {
+    let s = TestServer::start().await;
     let resp = s
         .client()
-        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
-        .header("Authorization", s.auth_header())
+        .get(format!("{}/api/schema", s.base_url))
         .send()
         .await
         .unwrap();
-    let tasks_after: Value = resp.json().await.unwrap();
-    let children_after: Vec<_> = tasks_after
-        .as_array()
-        .unwrap()
+    assert_eq!(resp.status(), 200);
+    let schema: Value = resp.json().await.unwrap();
+    let endpoints = schema["endpoints"].as_array().unwrap();
+
+    // Check v2 endpoints exist
+    let paths: Vec<&str> = endpoints
         .iter()
-        .filter(|t| t["recurrence_parent_id"].as_str() == Some(task_id))
+        .map(|e| e["path"].as_str().unwrap())
         .collect();
-    assert_eq!(
-        children_after.len(),
-        1,
-        "end_after=1 means no additional recurrences after the first child completes"
-    );
-}
+    assert!(paths.contains(&"/api/tasks/{id}/assign"));
+    assert!(paths.contains(&"/api/tasks/{id}/handoff"));
+    assert!(paths.contains(&"/api/tasks/{id}/approve"));
+    assert!(paths.contains(&"/api/tasks/{id}/request-changes"));
+    assert!(paths.contains(&"/api/agents/{id}"));
+    assert!(paths.contains(&"/api/projects/{id}/knowledge"));
+    assert!(paths.contains(&"/api/projects/{id}/knowledge/search"));
+    assert!(paths.contains(&"/api/projects/{id}/knowledge/{key}"));
 
-// ===== Inbound Webhook Triggers =====
+    // Check handoff in status_flow
+    assert!(schema["status_flow"]["handoff"].is_array());
+}
 
 #[tokio::test]
-async fn test_webhook_trigger_create_and_list() {
+async fn test_openapi_document() {
     let s = TestServer::start().await;
-    let proj = s.create_project("trigger-test").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    // Create a trigger
-    let resp: Value = s
+    let resp = s
         .client()
-        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
-        .header("Authorization", s.auth_header())
-        .json(&json!({
-            "name": "CI Failure",
-            "action_type": "create_task",
-            "action_config": {
-                "title": "CI failed on {{payload.repo}}",
-                "description": "Branch: {{payload.branch}}",
-                "priority": "high",
-                "tags": ["ci", "{{payload.repo}}"]
-            }
-        }))
+        .get(format!("{}/api/openapi.json", s.base_url))
         .send()
         .await
-        .unwrap()
-        .json()
-        .await
         .unwrap();
+    assert_eq!(resp.status(), 200);
+    let doc: Value = resp.json().await.unwrap();
+    assert_eq!(doc["openapi"], "3.1.0");
+    assert_eq!(
+        doc["components"]["securitySchemes"]["bearerAuth"]["scheme"],
+        "bearer"
+    );
 
-    assert_eq!(resp["trigger"]["name"].as_str(), Some("CI Failure"));
-    assert_eq!(resp["trigger"]["action_type"].as_str(), Some("create_task"));
-    let secret = resp["secret"].as_str().unwrap();
-    assert!(!secret.is_empty(), "should return raw secret on creation");
-
-    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+    let claim = &doc["paths"]["/api/tasks/{id}/claim"]["post"];
+    assert_eq!(claim["operationId"], "post_tasks_id_claim");
+    assert_eq!(claim["security"], json!([{"bearerAuth": []}]));
+    assert_eq!(claim["parameters"][0]["in"], "path");
+    assert_eq!(claim["parameters"][1]["name"], "X-On-Behalf-Of");
+    let create = &doc["paths"]["/api/projects/{id}/tasks"]["post"]["requestBody"]["content"]
+        ["application/json"]["schema"];
+    assert_eq!(create["required"], json!(["title"]));
+    assert_eq!(create["properties"]["tags"]["type"], "array");
+    assert_eq!(doc["paths"]["/health"]["get"]["security"], json!([]));
 
-    // List triggers
-    let list: Value = s
+    // /api/schema lists the same operations
+    let schema: Value = s
         .client()
-        .get(format!("{}/api/projects/{}/triggers", s.base_url, pid))
-        .header("Authorization", s.auth_header())
+        .get(format!("{}/api/schema", s.base_url))
         .send()
         .await
         .unwrap()
         .json()
         .await
         .unwrap();
+    let operations: usize = doc["paths"]
+        .as_object()
+        .unwrap()
+        .values()
+        .map(|item| item.as_object().unwrap().len())
+        .sum();
+    assert_eq!(schema["endpoints"].as_array().unwrap().len(), operations);
+}
 
-    let arr = list.as_array().unwrap();
-    assert_eq!(arr.len(), 1);
-    assert_eq!(arr[0]["id"].as_str(), Some(trigger_id.as_str()));
-
-    // Secret is not exposed in list
-    assert!(arr[0].get("secret").is_none() || arr[0]["secret"].is_null());
+/// Check a live response against the OpenAPI operation `method path`: the status is the
+/// documented success status, and the body has every required field of its schema.
+fn assert_documented(doc: &Value, method: &str, path: &str, status: u16, body: &Value) {
+    let op = &doc["paths"][path][method];
+    assert!(op.is_object(), "{method} {path} is undocumented");
+    let success = &op["responses"][status.to_string()];
+    assert!(
+        success.is_object(),
+        "{method} {path} answered {status}, documented {:?}",
+        op["responses"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>()
+    );
+    let mut schema = &success["content"]["application/json"]["schema"];
+    let mut body = body;
+    if schema["type"] == "array" {
+        schema = &schema["items"];
+        let Some(first) = body.as_array().and_then(|items| items.first()) else {
+            return;
+        };
+        body = first;
+    }
+    if let Some(reference) = schema["$ref"].as_str() {
+        schema = doc.pointer(reference.trim_start_matches('#')).unwrap();
+    }
+    for key in schema["required"].as_array().into_iter().flatten() {
+        let key = key.as_str().unwrap();
+        assert!(
+            body.get(key).is_some(),
+            "{method} {path}: no {key} in {body}"
+        );
+    }
 }
 
+// The document's statuses and schemas are the ones the handlers actually produce
 #[tokio::test]
-async fn test_webhook_trigger_receive_creates_task() {
+async fn test_openapi_matches_live_responses() {
+    use reqwest::Method;
+
     let s = TestServer::start().await;
-    let proj = s.create_project("trigger-recv").await;
-    let pid = proj["id"].as_str().unwrap();
+    let client = s.client();
+    let doc = opengate::openapi::document();
+    let send = |method: reqwest::Method, path: String, body: Option<Value>| {
+        let mut request = client
+            .request(method, format!("{}{}", s.base_url, path))
+            .header("Authorization", s.auth_header());
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        async move {
+            let resp = request.send().await.unwrap();
+            let status = resp.status().as_u16();
+            (status, resp.json::<Value>().await.unwrap_or_default())
+        }
+    };
 
-    let resp: Value = s
-        .client()
-        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
-        .header("Authorization", s.auth_header())
-        .json(&json!({
-            "name": "Create Task Trigger",
-            "action_type": "create_task",
-            "action_config": {
-                "title": "Alert: {{payload.service}} is down",
-                "description": "Region: {{payload.region}}",
-                "priority": "high"
-            }
-        }))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
+    let (status, project) = send(
+        Method::POST,
+        "/api/projects".to_string(),
+        Some(json!({"name": "Documented"})),
+    )
+    .await;
+    assert_documented(doc, "post", "/api/projects", status, &project);
+    let pid = project["id"].as_str().unwrap().to_string();
+    let (status, task) = send(
+        Method::POST,
+        format!("/api/projects/{pid}/tasks"),
+        Some(json!({"title": "Write the docs"})),
+    )
+    .await;
+    assert_documented(doc, "post", "/api/projects/{id}/tasks", status, &task);
+    let tid = task["id"].as_str().unwrap().to_string();
+
+    let requests = [
+        (
+            Method::GET,
+            "/api/auth/me".to_string(),
+            "/api/auth/me",
+            None,
+        ),
+        (
+            Method::GET,
+            "/api/projects".to_string(),
+            "/api/projects",
+            None,
+        ),
+        (
+            Method::GET,
+            format!("/api/projects/{pid}"),
+            "/api/projects/{id}",
+            None,
+        ),
+        (
+            Method::GET,
+            format!("/api/projects/{pid}/pulse"),
+            "/api/projects/{id}/pulse",
+            None,
+        ),
+        (Method::GET, "/api/tasks".to_string(), "/api/tasks", None),
+        (
+            Method::PATCH,
+            format!("/api/tasks/{tid}"),
+            "/api/tasks/{id}",
+            Some(json!({"status": "todo"})),
+        ),
+        (
+            Method::POST,
+            format!("/api/tasks/{tid}/claim"),
+            "/api/tasks/{id}/claim",
+            None,
+        ),
+        (
+            Method::POST,
+            format!("/api/tasks/{tid}/activity"),
+            "/api/tasks/{id}/activity",
+            Some(json!({"content": "Halfway"})),
+        ),
+        (
+            Method::GET,
+            format!("/api/tasks/{tid}/activity"),
+            "/api/tasks/{id}/activity",
+            None,
+        ),
+        (
+            Method::PUT,
+            format!("/api/projects/{pid}/knowledge/style"),
+            "/api/projects/{id}/knowledge/{key}",
+            Some(json!({"title": "Style", "content": "Short"})),
+        ),
+        (Method::GET, "/api/agents".to_string(), "/api/agents", None),
+        (
+            Method::GET,
+            "/api/agents/me/inbox".to_string(),
+            "/api/agents/me/inbox",
+            None,
+        ),
+        (Method::GET, "/api/stats".to_string(), "/api/stats", None),
+        (Method::GET, "/health".to_string(), "/health", None),
+        (
+            Method::DELETE,
+            format!("/api/tasks/{tid}"),
+            "/api/tasks/{id}",
+            None,
+        ),
+        (
+            Method::DELETE,
+            format!("/api/projects/{pid}"),
+            "/api/projects/{id}",
+            None,
+        ),
+    ];
+    for (method, path, documented, body) in requests {
+        let name = method.as_str().to_lowercase();
+        let (status, body) = send(method, path, body).await;
+        assert_documented(doc, &name, documented, status, &body);
+    }
+}
 
-    let secret = resp["secret"].as_str().unwrap().to_string();
-    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+// Offline agent assignment — should succeed with warning, not block
+#[tokio::test]
+async fn test_assign_to_offline_agent() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Test Project - Offline Assign").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Test Offline Assignment").await;
+    let task_id = task["id"].as_str().unwrap();
 
-    // Fire the webhook
-    let fire_resp = s
+    // Agent has never sent heartbeat → offline status
+    // Assign should still work
+    let resp = s
         .client()
-        .post(format!(
-            "{}/api/webhooks/trigger/{}",
-            s.base_url, trigger_id
-        ))
-        .header("X-Webhook-Secret", &secret)
-        .json(&json!({ "service": "payments-api", "region": "eu-west-1" }))
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": s.agent_id() }))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "todo");
+    assert!(body["assignee_id"].as_str().is_some());
+}
 
-    assert_eq!(fire_resp.status(), 200);
-    let result: Value = fire_resp.json().await.unwrap();
-    assert!(result["task_id"].is_string(), "should return task_id");
+// Stale release should NOT auto-release tasks in review status
+#[tokio::test]
+async fn test_stale_release_preserves_review() {
+    let s = TestServer::start().await;
 
-    // Verify task was created with interpolated title
-    let task_id = result["task_id"].as_str().unwrap();
-    let task: Value = s
-        .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
+    // Register a second agent to serve as reviewer (via setup-token registration)
+    let resp = s.client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({"name": "reviewer-agent", "setup_token": "test-setup-token", "skills": ["rust"]}))
         .send()
         .await
-        .unwrap()
-        .json()
-        .await
         .unwrap();
+    assert!(
+        resp.status().is_success(),
+        "Failed to register reviewer agent: {}",
+        resp.status()
+    );
+    let reviewer: Value = resp.json().await.unwrap();
+    let reviewer_id = reviewer["agent"]["id"].as_str().unwrap().to_string();
 
-    assert_eq!(task["title"].as_str(), Some("Alert: payments-api is down"));
-}
-
-#[tokio::test]
-async fn test_webhook_trigger_invalid_secret_rejected() {
-    let s = TestServer::start().await;
-    let proj = s.create_project("trigger-auth").await;
-    let pid = proj["id"].as_str().unwrap();
+    let project = s.create_project("Test Project - Stale Release").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Test Review Preservation").await;
+    let task_id = task["id"].as_str().unwrap();
 
-    let resp: Value = s
-        .client()
-        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+    // Claim
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({
-            "name": "Auth Test",
-            "action_type": "create_task",
-            "action_config": { "title": "Test", "priority": "low" }
-        }))
         .send()
         .await
-        .unwrap()
-        .json()
-        .await
         .unwrap();
 
-    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
-
-    // Wrong secret → 401
-    let fire_resp = s
+    // Submit for review (explicitly nominate the reviewer agent)
+    let resp = s
         .client()
         .post(format!(
-            "{}/api/webhooks/trigger/{}",
-            s.base_url, trigger_id
+            "{}/api/tasks/{}/submit-review",
+            s.base_url, task_id
         ))
-        .header("X-Webhook-Secret", "wrong-secret")
-        .json(&json!({ "foo": "bar" }))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"reviewer_id": reviewer_id}))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "review");
 
-    assert_eq!(fire_resp.status(), 401);
-
-    // Missing secret → 401
-    let fire_resp2 = s
+    // Verify task stays in review even without heartbeat
+    // (release_stale_tasks excludes review status)
+    let resp = s
         .client()
-        .post(format!(
-            "{}/api/webhooks/trigger/{}",
-            s.base_url, trigger_id
-        ))
-        .json(&json!({ "foo": "bar" }))
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-
-    assert_eq!(fire_resp2.status(), 401);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "review");
+    assert!(body["assignee_id"].as_str().is_some());
 }
 
-#[tokio::test]
-async fn test_webhook_trigger_delete() {
-    let s = TestServer::start().await;
-    let proj = s.create_project("trigger-del").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    let resp: Value = s
-        .client()
-        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
-        .header("Authorization", s.auth_header())
-        .json(&json!({
-            "name": "Delete Me",
-            "action_type": "create_task",
-            "action_config": { "title": "Deleted", "priority": "low" }
-        }))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-
-    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
-
-    // Delete
-    let del_resp = s
-        .client()
-        .delete(format!(
-            "{}/api/projects/{}/triggers/{}",
-            s.base_url, pid, trigger_id
-        ))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
-    assert_eq!(del_resp.status(), 204);
-
-    // List should be empty
-    let list: Value = s
-        .client()
-        .get(format!("{}/api/projects/{}/triggers", s.base_url, pid))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-    assert_eq!(list.as_array().unwrap().len(), 0);
-}
-
-#[tokio::test]
-async fn test_webhook_trigger_logs_recorded() {
-    let s = TestServer::start().await;
-    let proj = s.create_project("trigger-logs").await;
-    let pid = proj["id"].as_str().unwrap();
+// ============================================================
+// Webhook notification tests
+// ============================================================
 
-    let resp: Value = s
-        .client()
-        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
-        .header("Authorization", s.auth_header())
-        .json(&json!({
-            "name": "Log Test",
-            "action_type": "create_task",
-            "action_config": { "title": "Logged Task", "priority": "medium" }
-        }))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
+/// Spawn a tiny axum server that records incoming POST payloads and
+/// responds with `response_status`. Returns (url, Arc to recorded bodies).
+async fn start_mock_webhook(response_status: u16) -> (String, Arc<tokio::sync::Mutex<Vec<Value>>>) {
+    use axum::extract::State;
+    use axum::{http::StatusCode, routing::post, Router};
 
-    let secret = resp["secret"].as_str().unwrap().to_string();
-    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+    let received: Arc<tokio::sync::Mutex<Vec<Value>>> =
+        Arc::new(tokio::sync::Mutex::new(Vec::new()));
+    let received_clone = received.clone();
 
-    // Fire with correct secret
-    s.client()
-        .post(format!(
-            "{}/api/webhooks/trigger/{}",
-            s.base_url, trigger_id
-        ))
-        .header("X-Webhook-Secret", &secret)
-        .json(&json!({ "x": 1 }))
-        .send()
-        .await
-        .unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
 
-    // Fire with wrong secret
-    s.client()
-        .post(format!(
-            "{}/api/webhooks/trigger/{}",
-            s.base_url, trigger_id
-        ))
-        .header("X-Webhook-Secret", "bad")
-        .json(&json!({ "x": 2 }))
-        .send()
-        .await
-        .unwrap();
+    let app = Router::new()
+        .route(
+            "/hook",
+            post(
+                move |State(state): State<(Arc<tokio::sync::Mutex<Vec<Value>>>, StatusCode)>,
+                      body: axum::body::Bytes| async move {
+                    let parsed: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                    state.0.lock().await.push(parsed);
+                    state.1
+                },
+            ),
+        )
+        .with_state((
+            received_clone,
+            StatusCode::from_u16(response_status).unwrap(),
+        ));
 
-    // Check logs
-    let logs: Value = s
-        .client()
-        .get(format!(
-            "{}/api/projects/{}/triggers/{}/logs",
-            s.base_url, pid, trigger_id
-        ))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
 
-    let arr = logs.as_array().unwrap();
-    assert_eq!(arr.len(), 2, "should have 2 log entries");
-    // Most recent first
-    assert_eq!(
-        arr[0]["status"].as_str(),
-        Some("rejected"),
-        "last entry is rejected"
-    );
-    assert_eq!(
-        arr[1]["status"].as_str(),
-        Some("success"),
-        "first entry is success"
-    );
+    (format!("http://{}/hook", addr), received)
 }
 
-// ===== User Enrichment Tests =====
-
-/// Helper: register a human user and return (user_id, jwt_token)
-#[allow(dead_code)]
-async fn register_user(s: &TestServer, username: &str, password: &str) -> (String, String) {
+/// Helper: set webhook_url (and optional webhook_events) on the test agent.
+async fn set_agent_webhook(s: &TestServer, url: &str, events: Option<Vec<&str>>) {
+    let mut body = json!({ "webhook_url": url });
+    if let Some(ev) = events {
+        body["webhook_events"] = json!(ev);
+    }
     let resp = s
         .client()
-        .post(format!("{}/api/auth/register", s.base_url))
-        .json(&json!({ "username": username, "password": password }))
+        .patch(format!("{}/api/agents/{}", s.base_url, s.agent_id()))
+        .header("Authorization", s.auth_header())
+        .json(&body)
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    let id = body["user"]["id"].as_str().unwrap().to_string();
-    let token = body["token"].as_str().unwrap().to_string();
-    (id, token)
+    assert_eq!(resp.status(), 200, "Failed to update agent webhook config");
 }
 
-#[allow(dead_code)]
-async fn test_list_questions_with_status_filter() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Q - Filter").await;
+/// Helper: create a project + task and assign it to the test agent.
+/// Returns task_id.
+async fn assign_task_to_self(s: &TestServer, project_suffix: &str) -> String {
+    let project = s
+        .create_project(&format!("Webhook Test Project {}", project_suffix))
+        .await;
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task question filter").await;
-    let task_id = task["id"].as_str().unwrap();
-
-    // Create two questions
-    for q in &["Question 1?", "Question 2?"] {
-        let resp = s
-            .client()
-            .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
-            .header("Authorization", s.auth_header())
-            .json(&json!({ "question": q }))
-            .send()
-            .await
-            .unwrap();
-        assert_eq!(resp.status(), 201);
-    }
-
-    // List all — should be 2
-    let resp = s
-        .client()
-        .get(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .send()
-        .await
-        .unwrap();
-    assert_eq!(resp.status(), 200);
-    let all: Vec<Value> = resp.json().await.unwrap();
-    assert_eq!(all.len(), 2);
+    let task = s
+        .create_task(pid, &format!("Webhook Test Task {}", project_suffix))
+        .await;
+    let task_id = task["id"].as_str().unwrap().to_string();
 
-    // Resolve first question
-    let q_id = all[0]["id"].as_str().unwrap();
     let resp = s
         .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/resolve",
-            s.base_url, task_id, q_id
-        ))
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "resolution": "Use JSON" }))
+        .json(&json!({ "agent_id": s.agent_id() }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let resolved: Value = resp.json().await.unwrap();
-    assert_eq!(resolved["status"], "resolved");
-    assert_eq!(resolved["resolution"], "Use JSON");
+    assert_eq!(
+        resp.status(),
+        200,
+        "Failed to assign task: {}",
+        resp.text().await.unwrap()
+    );
+    task_id
+}
 
-    // Filter open — should be 1
+/// Helper: fetch unread notifications for the test agent.
+async fn unread_notifications(s: &TestServer) -> Vec<Value> {
     let resp = s
         .client()
         .get(format!(
-            "{}/api/tasks/{}/questions?status=open",
-            s.base_url, task_id
+            "{}/api/agents/me/notifications?unread=true",
+            s.base_url
         ))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let open: Vec<Value> = resp.json().await.unwrap();
-    assert_eq!(open.len(), 1);
+    resp.json::<Value>()
+        .await
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone()
+}
 
-    // Filter resolved — should be 1
+/// Helper: fetch all notifications for the test agent.
+async fn all_notifications(s: &TestServer) -> Vec<Value> {
     let resp = s
         .client()
-        .get(format!(
-            "{}/api/tasks/{}/questions?status=resolved",
-            s.base_url, task_id
-        ))
+        .get(format!("{}/api/agents/me/notifications", s.base_url))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let resolved_list: Vec<Value> = resp.json().await.unwrap();
-    assert_eq!(resolved_list.len(), 1);
+    resp.json::<Value>()
+        .await
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone()
 }
 
+// ------ Test 1: successful delivery → webhook fired + notification auto-acked ------
+
 #[tokio::test]
-async fn test_has_open_questions_flag() {
+async fn test_webhook_delivery_auto_ack() {
     let s = TestServer::start().await;
-    let project = s.create_project("Q - Flag").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task flag test").await;
-    let task_id = task["id"].as_str().unwrap();
+    let (hook_url, received) = start_mock_webhook(200).await;
+
+    set_agent_webhook(&s, &hook_url, None).await;
+    assign_task_to_self(&s, "auto-ack").await;
+
+    // Give the async webhook task time to fire and process
+    tokio::time::sleep(tokio::time::Duration::from_millis(800)).await;
+
+    // Notification should be auto-acked after successful delivery
+    let unread = unread_notifications(&s).await;
+    assert!(
+        unread.is_empty(),
+        "Expected no unread notifications after auto-ack, found: {}",
+        unread.len()
+    );
+
+    // Webhook should have been called
+    let reqs = received.lock().await;
+    assert!(!reqs.is_empty(), "Mock webhook server received no requests");
+    // Verify event_type in payload
+    let notification_reqs: Vec<_> = reqs
+        .iter()
+        .filter(|r| r.get("event").and_then(|e| e.as_str()) == Some("notification"))
+        .collect();
+    assert!(
+        !notification_reqs.is_empty(),
+        "Expected at least one notification webhook payload"
+    );
+    let ev_type = notification_reqs[0]["event_type"].as_str().unwrap_or("");
+    assert_eq!(ev_type, "task.assigned");
+
+    // webhook_status in DB should be "delivered"
+    let all = all_notifications(&s).await;
+    let notif = all
+        .iter()
+        .find(|n| n["event_type"].as_str() == Some("task.assigned"))
+        .expect("task.assigned notification missing");
+    assert_eq!(
+        notif["webhook_status"].as_str(),
+        Some("delivered"),
+        "webhook_status should be 'delivered'"
+    );
+}
+
+// ------ Test 2: event filter — subscribed only to review_requested, not assigned ------
+
+#[tokio::test]
+async fn test_webhook_event_filter_blocks_unsubscribed() {
+    let s = TestServer::start().await;
+    let (hook_url, received) = start_mock_webhook(200).await;
+
+    // Subscribe only to task.review_requested — task.assigned should be filtered
+    set_agent_webhook(&s, &hook_url, Some(vec!["task.review_requested"])).await;
+    assign_task_to_self(&s, "event-filter").await;
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(800)).await;
+
+    // Webhook should NOT have fired for task.assigned
+    let reqs = received.lock().await;
+    let notification_reqs: Vec<_> = reqs
+        .iter()
+        .filter(|r| {
+            r.get("event").and_then(|e| e.as_str()) == Some("notification")
+                && r.get("event_type").and_then(|e| e.as_str()) == Some("task.assigned")
+        })
+        .collect();
+    assert!(
+        notification_reqs.is_empty(),
+        "Notification webhook should have been filtered out for task.assigned"
+    );
+    drop(reqs);
+
+    // Notification should still be unread (not auto-acked because webhook was never sent)
+    let unread = unread_notifications(&s).await;
+    assert!(
+        !unread.is_empty(),
+        "Notification should remain unread when webhook was filtered out"
+    );
+    // webhook_status should be null (delivery never attempted)
+    let notif = unread
+        .iter()
+        .find(|n| n["event_type"].as_str() == Some("task.assigned"))
+        .expect("task.assigned notification missing");
+    assert!(
+        notif["webhook_status"].is_null(),
+        "webhook_status should be null when delivery was not attempted (filtered)"
+    );
+}
+
+// ------ Test 3: failed delivery → notification stays unread, status = "failed" ------
+
+/// NOTE: This test waits ~6 s for the 3-retry backoff to complete (1 s + 4 s).
+#[tokio::test]
+async fn test_webhook_failed_delivery_leaves_unread() {
+    let s = TestServer::start().await;
+
+    // Use a port that is (almost certainly) not listening → immediate connection refused
+    let hook_url = "http://127.0.0.1:39876";
+
+    set_agent_webhook(&s, hook_url, None).await;
+    assign_task_to_self(&s, "failed-delivery").await;
+
+    // Backoff: 1 s after attempt 1, 4 s after attempt 2, then done → wait ≥6 s
+    tokio::time::sleep(tokio::time::Duration::from_secs(7)).await;
+
+    // Notification should still be unread
+    let unread = unread_notifications(&s).await;
+    assert!(
+        !unread.is_empty(),
+        "Notification should remain unread after failed webhook delivery"
+    );
+
+    // webhook_status should be "failed"
+    let all = all_notifications(&s).await;
+    let notif = all
+        .iter()
+        .find(|n| n["event_type"].as_str() == Some("task.assigned"))
+        .expect("task.assigned notification missing");
+    assert_eq!(
+        notif["webhook_status"].as_str(),
+        Some("failed"),
+        "webhook_status should be 'failed' after exhausting all retries"
+    );
+}
+
+// ------ Webhook test-fire: synthetic push reports status and latency ------
+
+#[tokio::test]
+async fn test_webhook_test_fire() {
+    let s = TestServer::start().await;
+    let test_url = format!("{}/api/agents/{}/webhook/test", s.base_url, s.agent_id());
 
-    // Initially, has_open_questions should be false
     let resp = s
         .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .post(&test_url)
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    let t: Value = resp.json().await.unwrap();
-    assert_eq!(t["has_open_questions"], false);
+    assert_eq!(resp.status(), 422, "no webhook_url configured yet");
 
-    // Create a blocking question
+    let (hook_url, received) = start_mock_webhook(200).await;
+    set_agent_webhook(&s, &hook_url, None).await;
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .post(&test_url)
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "Blocking Q?", "blocking": true }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
+    assert_eq!(resp.status(), 200);
+    let result: Value = resp.json().await.unwrap();
+    assert_eq!(result["delivered"], true);
+    assert_eq!(result["status_code"], 200);
+    assert!(result["latency_ms"].as_i64().unwrap() >= 0);
+    let reqs = received.lock().await;
+    assert_eq!(reqs.len(), 1);
+    assert_eq!(reqs[0]["event"], "webhook.test");
+    drop(reqs);
+
+    // A dead receiver is reported, not an error
+    set_agent_webhook(&s, "http://127.0.0.1:39878", None).await;
+    let result: Value = s
+        .client()
+        .post(&test_url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(result["delivered"], false);
+    assert!(result["status_code"].is_null());
+    assert!(result["error"].is_string());
+}
+
+// ------ Templated webhook payloads ------
+
+#[tokio::test]
+async fn test_webhook_template_shapes_payload() {
+    let s = TestServer::start().await;
+    let (hook_url, received) = start_mock_webhook(200).await;
+    set_agent_webhook(&s, &hook_url, None).await;
+    let me_url = format!("{}/api/agents/me", s.base_url);
 
-    // Now has_open_questions should be true
     let resp = s
         .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .patch(&me_url)
         .header("Authorization", s.auth_header())
+        .json(&json!({"webhook_template": {"text": "{{unclosed"}}))
         .send()
         .await
         .unwrap();
-    let t: Value = resp.json().await.unwrap();
-    assert_eq!(t["has_open_questions"], true);
+    assert_eq!(resp.status(), 422);
 
-    // Resolve the question
+    let template = json!({
+        "text": "{{agent.name}}: {{event}} for {{missing}}",
+        "blocks": [{"type": "section", "text": {"type": "mrkdwn", "text": "*{{event}}*"}}],
+        "agent": "{{agent}}",
+    });
     let resp = s
         .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/resolve",
-            s.base_url, task_id, q_id
-        ))
+        .patch(&me_url)
         .header("Authorization", s.auth_header())
-        .json(&json!({ "resolution": "Answered" }))
+        .json(&json!({"webhook_template": template}))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
+    let agent: Value = resp.json().await.unwrap();
+    assert_eq!(agent["webhook_template"], template);
 
-    // Flag should be false again
+    let test_url = format!("{}/api/agents/{}/webhook/test", s.base_url, s.agent_id());
     let resp = s
         .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .post(&test_url)
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    let t: Value = resp.json().await.unwrap();
-    assert_eq!(t["has_open_questions"], false);
+    assert_eq!(resp.status(), 200);
+    let reqs = received.lock().await;
+    assert_eq!(reqs.len(), 1);
+    assert_eq!(reqs[0]["text"], "test-agent: webhook.test for ");
+    assert_eq!(reqs[0]["blocks"][0]["text"]["text"], "*webhook.test*");
+    // A lone placeholder embeds the field's JSON value
+    assert_eq!(reqs[0]["agent"]["id"], s.agent_id());
+    drop(reqs);
+
+    // An empty object restores the default payload
+    s.client()
+        .patch(&me_url)
+        .header("Authorization", s.auth_header())
+        .json(&json!({"webhook_template": {}}))
+        .send()
+        .await
+        .unwrap();
+    s.client()
+        .post(&test_url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let reqs = received.lock().await;
+    assert_eq!(reqs[1]["event"], "webhook.test");
 }
 
+// ------ Dead-letter queue: failed deliveries can be listed and redelivered ------
+
+/// NOTE: waits ~7 s for the retries to be exhausted, like the test above.
 #[tokio::test]
-async fn test_non_blocking_question_does_not_set_flag() {
+async fn test_failed_webhooks_listed_and_redelivered() {
     let s = TestServer::start().await;
-    let project = s.create_project("Q - NonBlocking").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task non-blocking Q").await;
-    let task_id = task["id"].as_str().unwrap();
+    set_agent_webhook(&s, "http://127.0.0.1:39877", None).await;
+    assign_task_to_self(&s, "dlq").await;
+    tokio::time::sleep(tokio::time::Duration::from_secs(7)).await;
 
-    // Create a non-blocking question
-    let resp = s
+    let failed_url = format!("{}/api/agents/{}/webhooks/failed", s.base_url, s.agent_id());
+    let failed: Value = s
         .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .get(&failed_url)
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "FYI question?", "blocking": false }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
+    let failed = failed.as_array().unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0]["event_type"], "task.assigned");
+    let notification_id = failed[0]["id"].as_i64().unwrap();
 
-    // has_open_questions should still be false (only blocking questions count)
+    // Other agents can't see or replay someone else's dead letters
+    let other: Value = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({"name": "nosy", "setup_token": "test-setup-token"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
     let resp = s
         .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .get(&failed_url)
+        .header(
+            "Authorization",
+            format!("Bearer {}", other["api_key"].as_str().unwrap()),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // Receiver fixed — redeliver
+    let (hook_url, received) = start_mock_webhook(200).await;
+    set_agent_webhook(&s, &hook_url, None).await;
+    let resp = s
+        .client()
+        .post(format!("{}/redeliver", failed_url))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    let t: Value = resp.json().await.unwrap();
-    assert_eq!(t["has_open_questions"], false);
+    assert_eq!(resp.status(), 200);
+    let redelivery: Value = resp.json().await.unwrap();
+    assert_eq!(redelivery["notification_ids"], json!([notification_id]));
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(800)).await;
+    assert!(!received.lock().await.is_empty());
+    assert!(unread_notifications(&s).await.is_empty());
+    let failed: Value = s
+        .client()
+        .get(&failed_url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(failed.as_array().unwrap().is_empty());
 }
 
+// ------ Event replay: missed wake-ups are redelivered without duplicating notifications ------
+
 #[tokio::test]
-async fn test_stale_release_skips_tasks_with_open_questions() {
-    // Direct DB test — create task with open question, simulate stale agent, verify not released
-    let tmp = TempDir::new().expect("failed to create temp dir");
-    let db_path = tmp.path().join("stale_q.db");
-    let db_path_str = db_path.to_str().unwrap();
-
-    let conn = db::init_db(db_path_str);
-    let (agent, _api_key) = db_ops::create_agent(
-        &conn,
-        &CreateAgent::new("stale-q-agent").with_skills(vec!["rust".to_string()]),
-    );
-
-    // Create project + task
-    let project = db_ops::create_project(
-        &conn,
-        None,
-        &opengate_models::CreateProject {
-            name: "Stale Q Project".to_string(),
-            description: None,
-            repo_url: None,
-            default_branch: None,
-            join_mode: None,
-            cta_enabled: None,
-            is_public: None,
-        },
-        &agent.id,
-    );
-
-    let task = db_ops::create_task(
-        &conn,
-        None,
-        &project.id,
-        &opengate_models::CreateTask {
-            title: "Stale Q Task".to_string(),
-            description: None,
-            priority: None,
-            tags: None,
-            context: None,
-            output: None,
-            due_date: None,
-            assignee_type: None,
-            assignee_id: None,
-            scheduled_at: None,
-            recurrence_rule: None,
-        },
-        &agent.id,
-    );
+async fn test_event_replay_redelivers_missed_wakeups() {
+    let s = TestServer::start().await;
+    // The agent was "down": no webhook configured when the task was assigned
+    let task_id = assign_task_to_self(&s, "replay").await;
+    assert_eq!(unread_notifications(&s).await.len(), 1);
 
-    // Move to todo first, then claim
-    db_ops::update_task(
-        &conn,
-        None,
-        &task.id,
-        &opengate_models::UpdateTask {
-            status: Some("todo".to_string()),
-            ..Default::default()
-        },
-    )
-    .unwrap();
-    db_ops::claim_task(&conn, None, &task.id, &agent.id, &agent.name).unwrap();
+    let replay = |body: Value| {
+        s.client()
+            .post(format!("{}/api/admin/events/replay", s.base_url))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
 
-    // Create a blocking open question on this task
-    db_ops::create_question(
-        &conn,
-        &task.id,
-        &opengate_models::CreateQuestion {
-            question: "Need clarification".to_string(),
-            question_type: None,
-            context: None,
-            target_type: None,
-            target_id: None,
-            required_capability: None,
-            blocking: Some(true),
-        },
-        "agent",
-        &agent.id,
-    );
+    let resp = replay(json!({ "agent_id": s.agent_id() })).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    // Non-orchestrators may only replay their own notifications
+    let resp = replay(json!({ "task_id": task_id })).await.unwrap();
+    assert_eq!(resp.status(), 403);
 
-    // Make the agent look stale (last_seen 2 hours ago)
-    let stale_time = (chrono::Utc::now() - chrono::Duration::hours(2)).to_rfc3339();
-    conn.execute(
-        "UPDATE agents SET last_seen_at = ?1 WHERE id = ?2",
-        rusqlite::params![stale_time, agent.id],
-    )
+    let (hook_url, received) = start_mock_webhook(200).await;
+    set_agent_webhook(&s, &hook_url, None).await;
+    let resp = replay(json!({
+        "task_id": task_id,
+        "agent_id": s.agent_id(),
+        "deliver_webhooks": true
+    }))
+    .await
     .unwrap();
+    assert_eq!(resp.status(), 200);
+    let result: Value = resp.json().await.unwrap();
+    assert!(result["events_replayed"].as_i64().unwrap() >= 1);
+    assert_eq!(result["notifications_created"], 0);
+    assert_eq!(result["notifications_redelivered"], 1);
+    assert_eq!(result["webhooks_fired"], true);
 
-    // Run stale release — task should NOT be released (has open questions)
-    let released = db_ops::release_stale_tasks(&conn, 30);
-    assert!(
-        released.is_empty(),
-        "Task with open questions should not be released"
-    );
+    tokio::time::sleep(tokio::time::Duration::from_millis(800)).await;
+    assert_eq!(all_notifications(&s).await.len(), 1);
+    assert!(unread_notifications(&s).await.is_empty());
+    assert!(received
+        .lock()
+        .await
+        .iter()
+        .any(|r| r["event_type"] == "task.assigned"));
 
-    // Verify task is still in_progress
-    let task_after = db_ops::get_task(&conn, None, &task.id).unwrap();
-    assert_eq!(task_after.status, "in_progress");
-    assert_eq!(task_after.assignee_id.as_deref(), Some(&*agent.id));
+    // Delivered now, so a second replay has nothing to wake
+    let result: Value = replay(json!({ "task_id": task_id, "agent_id": s.agent_id() }))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(result["notifications_redelivered"], 0);
+    assert_eq!(result["webhooks_fired"], false);
 }
 
+// ═══════════════════════════════════════════════════════
+// v4: Task Dependencies — schema + API + cycle detection
+// ═══════════════════════════════════════════════════════
+
 #[tokio::test]
-async fn test_agent_targeted_questions() {
+async fn test_task_dependencies_crud_and_auto_unblock() {
     let s = TestServer::start().await;
-    let project = s.create_project("Q - Agent Target").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task for agent Q").await;
-    let task_id = task["id"].as_str().unwrap();
+    let proj = s.create_project("deps-test").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Create a question targeted at the test agent
+    // Create two tasks: A depends on B
+    let a = s.create_task(pid, "Task A").await;
+    let b = s.create_task(pid, "Task B").await;
+    let a_id = a["id"].as_str().unwrap();
+    let b_id = b["id"].as_str().unwrap();
+
+    // POST /api/tasks/A/dependencies { depends_on: [B] }
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({
-            "question": "Can you handle this?",
-            "target_type": "agent",
-            "target_id": s.agent_id()
-        }))
+        .json(&json!({ "depends_on": [b_id] }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.status(), 200, "add dependency should succeed");
+    let task_a: Value = resp.json().await.unwrap();
+    assert!(
+        task_a["dependencies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|d| d.as_str() == Some(b_id)),
+        "Task A should list B in its dependencies"
+    );
 
-    // GET /api/agents/me/questions should return it
+    // GET /api/tasks/A/dependencies
     let resp = s
         .client()
-        .get(format!("{}/api/agents/me/questions", s.base_url))
+        .get(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let questions: Vec<Value> = resp.json().await.unwrap();
-    assert_eq!(questions.len(), 1);
-    assert_eq!(questions[0]["question"], "Can you handle this?");
-    assert_eq!(questions[0]["target_id"].as_str().unwrap(), s.agent_id());
-}
-
-#[tokio::test]
-async fn test_project_questions_and_unrouted() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Q - Project").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task proj Q").await;
-    let task_id = task["id"].as_str().unwrap();
+    let deps: Value = resp.json().await.unwrap();
+    assert_eq!(deps.as_array().unwrap().len(), 1);
+    assert_eq!(deps[0]["id"].as_str(), Some(b_id));
 
-    // Create a routed question (has target_id)
+    // GET /api/tasks/B/dependents
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .get(format!("{}/api/tasks/{}/dependents", s.base_url, b_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({
-            "question": "Routed Q?",
-            "target_type": "agent",
-            "target_id": s.agent_id()
-        }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.status(), 200);
+    let dependents: Value = resp.json().await.unwrap();
+    assert_eq!(dependents.as_array().unwrap().len(), 1);
+    assert_eq!(dependents[0]["id"].as_str(), Some(a_id));
 
-    // Create an unrouted question (no target_id)
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+    // Attempt to move A to in_progress — should fail (B is not done)
+    // First move A to todo (valid from backlog)
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "Unrouted Q?" }))
+        .json(&json!({ "status": "todo" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-
-    // GET /api/projects/:id/questions — should return both
+    // Now try in_progress — should fail with 409 Conflict due to unmet dep
     let resp = s
         .client()
-        .get(format!("{}/api/projects/{}/questions", s.base_url, pid))
+        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "in_progress" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let all: Vec<Value> = resp.json().await.unwrap();
-    assert_eq!(all.len(), 2);
+    assert_eq!(
+        resp.status(),
+        409,
+        "should block in_progress with 409 when dep not met"
+    );
 
-    // GET /api/projects/:id/questions?unrouted=true — should return 1
-    let resp = s
-        .client()
-        .get(format!(
-            "{}/api/projects/{}/questions?unrouted=true",
-            s.base_url, pid
-        ))
+    // Move B to todo, then claim → in_progress, then complete it → A should auto-transition to todo
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, b_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let unrouted: Vec<Value> = resp.json().await.unwrap();
-    assert_eq!(unrouted.len(), 1);
-    assert_eq!(unrouted[0]["question"], "Unrouted Q?");
-}
-
-#[tokio::test]
-async fn test_get_single_question() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Q - Get Single").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task single Q").await;
-    let task_id = task["id"].as_str().unwrap();
-
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, b_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "Single Q?", "context": "some context" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
+    s.client()
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, b_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "summary": "B done" }))
+        .send()
+        .await
+        .unwrap();
 
-    // GET single question
+    // Check A is now todo
     let resp = s
         .client()
-        .get(format!(
-            "{}/api/tasks/{}/questions/{}",
-            s.base_url, task_id, q_id
-        ))
+        .get(format!("{}/api/tasks/{}", s.base_url, a_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let fetched: Value = resp.json().await.unwrap();
-    assert_eq!(fetched["id"].as_str().unwrap(), q_id);
-    assert_eq!(fetched["question"], "Single Q?");
-    assert_eq!(fetched["context"], "some context");
-}
-
-// ===== Agent Inbox Tests =====
+    let task_a_after: Value = resp.json().await.unwrap();
+    assert_eq!(
+        task_a_after["status"].as_str(),
+        Some("todo"),
+        "A should be auto-unblocked to todo after B completes"
+    );
 
-#[tokio::test]
-async fn test_inbox_empty() {
-    let s = TestServer::start().await;
+    // DELETE dependency
     let resp = s
         .client()
-        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .delete(format!(
+            "{}/api/tasks/{}/dependencies/{}",
+            s.base_url, a_id, b_id
+        ))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let inbox: Value = resp.json().await.unwrap();
-    assert!(inbox["summary"]
-        .as_str()
-        .unwrap()
-        .contains("No actionable work"));
-    assert_eq!(inbox["todo_tasks"].as_array().unwrap().len(), 0);
-    assert_eq!(inbox["in_progress_tasks"].as_array().unwrap().len(), 0);
-    assert!(inbox["capacity"]["has_capacity"].as_bool().unwrap());
+    assert_eq!(resp.status(), 204, "delete dependency should return 204");
 }
 
 #[tokio::test]
-async fn test_inbox_assigned_todo_task() {
+async fn test_task_dependency_cycle_detection() {
     let s = TestServer::start().await;
-    let project = s.create_project("Inbox - Todo").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Inbox todo task").await;
-    let task_id = task["id"].as_str().unwrap();
+    let proj = s.create_project("cycle-test").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Move to todo
+    let a = s.create_task(pid, "Cycle A").await;
+    let b = s.create_task(pid, "Cycle B").await;
+    let a_id = a["id"].as_str().unwrap();
+    let b_id = b["id"].as_str().unwrap();
+
+    // A depends on B
     s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
+        .json(&json!({ "depends_on": [b_id] }))
         .send()
         .await
         .unwrap();
 
-    // Assign to our agent
-    s.client()
-        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
+    // B depends on A → cycle! Should be rejected.
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, b_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "agent_id": s.agent_id() }))
+        .json(&json!({ "depends_on": [a_id] }))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 400, "cycle should be rejected");
+    let body: Value = resp.json().await.unwrap();
+    assert!(
+        body["error"].as_str().unwrap().contains("cycle"),
+        "error message should mention cycle"
+    );
 
+    // Self-dependency should also be rejected
     let resp = s
         .client()
-        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "depends_on": [a_id] }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let inbox: Value = resp.json().await.unwrap();
-    let todos = inbox["todo_tasks"].as_array().unwrap();
-    assert_eq!(todos.len(), 1);
-    assert_eq!(todos[0]["id"].as_str().unwrap(), task_id);
-    assert_eq!(todos[0]["action"], "start_work");
-    assert_eq!(todos[0]["item_type"], "task");
+    assert_eq!(resp.status(), 400, "self-dependency should be rejected");
 }
 
+// ═══════════════════════════════════════════════════════
+// v4: Task Scheduling — scheduled_at + auto-transition
+// ═══════════════════════════════════════════════════════
+
 #[tokio::test]
-async fn test_inbox_in_progress_task() {
+async fn test_task_scheduling_create_and_enforce() {
     let s = TestServer::start().await;
-    let project = s.create_project("Inbox - InProgress").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Inbox ip task").await;
-    let task_id = task["id"].as_str().unwrap();
+    let proj = s.create_project("sched-test").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Move to todo then claim (→ in_progress)
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+    // Create a task scheduled in the future
+    let future_time = "2099-01-01T00:00:00Z";
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
+        .json(&json!({
+            "title": "Future Task",
+            "priority": "high",
+            "scheduled_at": future_time
+        }))
         .send()
         .await
         .unwrap();
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+    assert_eq!(resp.status(), 201);
+    let task: Value = resp.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap();
+    assert_eq!(
+        task["scheduled_at"].as_str(),
+        Some(future_time),
+        "scheduled_at should be persisted"
+    );
+
+    // Cannot manually advance a future-scheduled task (to todo or in_progress)
+    let resp = s
+        .client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
         .send()
         .await
         .unwrap();
+    assert_eq!(
+        resp.status(),
+        400,
+        "future-scheduled task should block manual advance to todo"
+    );
+    let body: Value = resp.json().await.unwrap();
+    assert!(
+        body["error"].as_str().unwrap_or("").contains("scheduled"),
+        "error should mention scheduling"
+    );
 
+    // Schedule listing: GET /api/projects/:id/schedule
     let resp = s
         .client()
-        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .get(format!("{}/api/projects/{}/schedule", s.base_url, pid))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let inbox: Value = resp.json().await.unwrap();
-    let ip = inbox["in_progress_tasks"].as_array().unwrap();
-    assert_eq!(ip.len(), 1);
-    assert_eq!(ip[0]["id"].as_str().unwrap(), task_id);
-    assert_eq!(ip[0]["action"], "continue_work");
+    let schedule: Value = resp.json().await.unwrap();
+    let entries = schedule.as_array().unwrap();
+    assert!(
+        !entries.is_empty(),
+        "schedule should include the future task"
+    );
+    let entry = entries
+        .iter()
+        .find(|e| e["id"].as_str() == Some(task_id))
+        .expect("task should be in schedule");
+    assert_eq!(entry["scheduled_at"].as_str(), Some(future_time));
 }
 
 #[tokio::test]
-async fn test_inbox_open_question() {
+async fn test_scheduled_task_auto_transition() {
     let s = TestServer::start().await;
-    let project = s.create_project("Inbox - Question").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task with question").await;
-    let task_id = task["id"].as_str().unwrap();
+    let proj = s.create_project("auto-sched-test").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Create a question targeted at our agent
+    // Create a task scheduled in the past (should be ready to transition)
+    let past_time = "2020-01-01T00:00:00Z";
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
         .json(&json!({
-            "question": "What framework to use?",
-            "target_type": "agent",
-            "target_id": s.agent_id()
+            "title": "Past Scheduled Task",
+            "priority": "medium",
+            "scheduled_at": past_time
         }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 201);
+    let task: Value = resp.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap();
 
+    // Trigger auto-transition
     let resp = s
         .client()
-        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .post(format!("{}/api/tasks/scheduled/transition", s.base_url))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let inbox: Value = resp.json().await.unwrap();
-    let questions = inbox["open_questions"].as_array().unwrap();
-    assert_eq!(questions.len(), 1);
-    assert_eq!(questions[0]["action"], "resolve_question");
-    assert_eq!(questions[0]["item_type"], "question");
-    assert_eq!(questions[0]["title"], "What framework to use?");
+    let result: Value = resp.json().await.unwrap();
+    assert!(
+        result["transitioned"].as_i64().unwrap_or(0) >= 1,
+        "should have transitioned at least one task"
+    );
+
+    // Task should now be todo
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let task_after: Value = resp.json().await.unwrap();
+    assert_eq!(
+        task_after["status"].as_str(),
+        Some("todo"),
+        "past-scheduled task should auto-transition to todo"
+    );
 }
 
+// ═══════════════════════════════════════════════════════
+// v4: Task Recurrence — rules + auto-creation on complete
+// ═══════════════════════════════════════════════════════
+
 #[tokio::test]
-async fn test_inbox_capacity_calculation() {
+async fn test_task_recurrence_creates_next_on_complete() {
     let s = TestServer::start().await;
-    let project = s.create_project("Inbox - Capacity").await;
-    let pid = project["id"].as_str().unwrap();
+    let proj = s.create_project("recurrence-test").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Create and claim a task
-    let task = s.create_task(pid, "Capacity test task").await;
+    // Create a daily recurring task
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "title": "Daily Standup",
+            "priority": "medium",
+            "scheduled_at": "2026-02-23T09:00:00Z",
+            "recurrence_rule": {
+                "frequency": "daily",
+                "interval": 1
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let task: Value = resp.json().await.unwrap();
     let task_id = task["id"].as_str().unwrap();
+    assert!(
+        task["recurrence_rule"].is_object(),
+        "recurrence_rule should be persisted"
+    );
+
+    // Move to todo first (scheduled_at in the past auto-transitions, but be explicit)
     s.client()
         .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
         .header("Authorization", s.auth_header())
@@ -2862,1453 +3433,9345 @@ async fn test_inbox_capacity_calculation() {
         .send()
         .await
         .unwrap();
+
+    // Claim task → in_progress, then complete it
     s.client()
         .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "summary": "Standup done" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200, "complete should succeed");
 
+    // Check that a new recurrence was created in the project
     let resp = s
         .client()
-        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let inbox: Value = resp.json().await.unwrap();
-    let capacity = &inbox["capacity"];
-    assert_eq!(capacity["current_active_tasks"].as_i64().unwrap(), 1);
-    assert!(capacity["max_concurrent_tasks"].as_i64().unwrap() >= 1);
-}
+    let tasks: Value = resp.json().await.unwrap();
+    let task_list = tasks.as_array().unwrap();
 
-// ===== Question Replies, Dismiss, Assign =====
+    let next_recurrence = task_list.iter().find(|t| {
+        t["recurrence_parent_id"].as_str() == Some(task_id)
+            || (t["id"].as_str() != Some(task_id)
+                && t["title"].as_str() == Some("Daily Standup")
+                && t["status"].as_str() != Some("done"))
+    });
+    assert!(
+        next_recurrence.is_some(),
+        "completing a recurring task should create the next occurrence"
+    );
+    let next = next_recurrence.unwrap();
+    assert_eq!(
+        next["status"].as_str(),
+        Some("backlog"),
+        "next recurrence should start in backlog"
+    );
+    assert!(
+        next["scheduled_at"].is_string(),
+        "next recurrence should have a scheduled_at"
+    );
+    // scheduled_at should be ~1 day after the original
+    assert_ne!(
+        next["scheduled_at"].as_str(),
+        Some("2026-02-23T09:00:00Z"),
+        "next occurrence should have a different scheduled_at"
+    );
+}
 
+// Occurrences belong to the tenant whose series they continue
 #[tokio::test]
-async fn test_question_replies() {
+async fn test_task_recurrence_keeps_the_tenant() {
     let s = TestServer::start().await;
-    let project = s.create_project("Reply Test Project").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task for replies").await;
-    let task_id = task["id"].as_str().unwrap();
+    let client = s.client();
+    let mut keys = Vec::new();
+    for org in ["acme", "globex"] {
+        let created: Value = client
+            .post(format!("{}/api/agents/register", s.base_url))
+            .json(&json!({
+                "name": format!("{org}-lead"),
+                "setup_token": "test-setup-token",
+                "owner_id": org,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        keys.push(format!("Bearer {}", created["api_key"].as_str().unwrap()));
+    }
+    let (acme, globex) = (&keys[0], &keys[1]);
 
-    // Create a question
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "What format?" }))
+    let project: Value = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", acme)
+        .json(&json!({"name": "Acme ops"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let pid = project["id"].as_str().unwrap();
+    let task: Value = client
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", acme)
+        .json(&json!({
+            "title": "Rotate certificates",
+            "scheduled_at": "2026-02-23T09:00:00Z",
+            "recurrence_rule": {"frequency": "daily", "interval": 1},
+        }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
+    let tid = task["id"].as_str().unwrap();
+    client
+        .patch(format!("{}/api/tasks/{}", s.base_url, tid))
+        .header("Authorization", acme)
+        .json(&json!({"status": "todo"}))
+        .send()
+        .await
+        .unwrap();
+    for action in ["claim", "complete"] {
+        let resp = client
+            .post(format!("{}/api/tasks/{}/{}", s.base_url, tid, action))
+            .header("Authorization", acme)
+            .json(&json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200, "{action}");
+    }
 
-    // Post a reply
-    let resp = s
-        .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/replies",
-            s.base_url, task_id, q_id
-        ))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "body": "Use JSON format", "is_resolution": false }))
+    let tasks: Value = client
+        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", acme)
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let reply: Value = resp.json().await.unwrap();
-    assert_eq!(reply["body"], "Use JSON format");
-    assert_eq!(reply["is_resolution"], false);
+    let next = tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["recurrence_parent_id"] == tid)
+        .expect("the next occurrence");
+    let next_id = next["id"].as_str().unwrap();
 
-    // List replies
-    let resp = s
-        .client()
-        .get(format!(
-            "{}/api/tasks/{}/questions/{}/replies",
-            s.base_url, task_id, q_id
-        ))
-        .header("Authorization", s.auth_header())
+    let resp = client
+        .get(format!("{}/api/tasks/{}", s.base_url, next_id))
+        .header("Authorization", globex)
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let replies: Value = resp.json().await.unwrap();
-    assert_eq!(replies.as_array().unwrap().len(), 1);
-    assert_eq!(replies[0]["body"], "Use JSON format");
+    assert_eq!(resp.status(), 404);
+    let tasks: Value = client
+        .get(format!("{}/api/tasks", s.base_url))
+        .header("Authorization", globex)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(tasks.as_array().unwrap().iter().all(|t| t["id"] != next_id));
+    {
+        let conn = s.storage.conn.lock().unwrap();
+        let owner: Option<String> = conn
+            .query_row(
+                "SELECT owner_id FROM tasks WHERE id = ?1",
+                [next_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(owner.as_deref(), Some("acme"));
+    }
 }
 
 #[tokio::test]
-async fn test_reply_with_resolution_auto_resolves_question() {
+async fn test_task_recurrence_respects_end_after() {
     let s = TestServer::start().await;
-    let project = s.create_project("Auto-resolve Project").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task for auto-resolve").await;
-    let task_id = task["id"].as_str().unwrap();
+    let proj = s.create_project("recurrence-end-test").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Create question
+    // Create a task that recurs only 1 more time after first completion
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "Is this correct?", "blocking": true }))
+        .json(&json!({
+            "title": "Limited Recurrence",
+            "priority": "low",
+            "scheduled_at": "2026-02-23T09:00:00Z",
+            "recurrence_rule": {
+                "frequency": "daily",
+                "interval": 1,
+                "end_after": 1
+            }
+        }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
+    let task: Value = resp.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap();
 
-    // Post reply with is_resolution=true
-    let resp = s
-        .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/replies",
-            s.base_url, task_id, q_id
-        ))
+    // Move to todo then claim original → complete → should create 1 recurrence
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "body": "Yes, looks correct!", "is_resolution": true }))
+        .json(&json!({ "status": "todo" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-
-    // Question should now be answered
-    let resp = s
-        .client()
-        .get(format!(
-            "{}/api/tasks/{}/questions/{}",
-            s.base_url, task_id, q_id
-        ))
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let q: Value = resp.json().await.unwrap();
-    assert_eq!(q["status"], "answered");
-    assert_eq!(q["resolution"], "Yes, looks correct!");
-}
-
-#[tokio::test]
-async fn test_dismiss_question() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Dismiss Project").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task for dismiss").await;
-    let task_id = task["id"].as_str().unwrap();
-
-    // Create question
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+    s.client()
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "Obsolete question?", "blocking": true }))
+        .json(&json!({ "summary": "done" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
 
-    // Dismiss it
     let resp = s
         .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/dismiss",
-            s.base_url, task_id, q_id
-        ))
+        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "reason": "No longer relevant" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let q: Value = resp.json().await.unwrap();
-    assert_eq!(q["status"], "dismissed");
+    let tasks: Value = resp.json().await.unwrap();
+    let children: Vec<_> = tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|t| t["recurrence_parent_id"].as_str() == Some(task_id))
+        .collect();
+    assert_eq!(
+        children.len(),
+        1,
+        "should have exactly 1 recurrence after first completion"
+    );
+    let child_id = children[0]["id"].as_str().unwrap().to_string();
 
-    // has_open_questions should be false
-    let resp = s
-        .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+    // Move recurrence to todo, then claim + complete → end_after=1 means no more
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, child_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let task: Value = resp.json().await.unwrap();
-    assert_eq!(task["has_open_questions"], false);
-}
-
-#[tokio::test]
-async fn test_assign_question() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Assign Question Project").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Task for assign").await;
-    let task_id = task["id"].as_str().unwrap();
-
-    // Create question
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, child_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "Who should handle this?" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
+    s.client()
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, child_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "summary": "done again" }))
+        .send()
+        .await
+        .unwrap();
 
-    // Assign to a user
     let resp = s
         .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/assign",
-            s.base_url, task_id, q_id
-        ))
+        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "target_type": "user", "target_id": "user-123" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let q: Value = resp.json().await.unwrap();
-    assert_eq!(q["target_type"], "user");
-    assert_eq!(q["target_id"], "user-123");
+    let tasks_after: Value = resp.json().await.unwrap();
+    let children_after: Vec<_> = tasks_after
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|t| t["recurrence_parent_id"].as_str() == Some(task_id))
+        .collect();
+    assert_eq!(
+        children_after.len(),
+        1,
+        "end_after=1 means no additional recurrences after the first child completes"
+    );
 }
 
-// ===== Auto-targeting capability matching tests =====
-
 #[tokio::test]
-async fn test_auto_target_zero_matches() {
-    // No agent/user has the required capability → question stays unrouted
-    let tmp = TempDir::new().unwrap();
-    let conn = db::init_db(tmp.path().join("at0.db").to_str().unwrap());
+async fn test_task_recurrence_rrule_byday() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("rrule-byday").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    let (agent, _) = db_ops::create_agent(
-        &conn,
-        &CreateAgent::new("agent-no-cap")
-            .with_skills(vec!["rust".to_string()])
-            .with_capabilities(vec!["coding:rust".to_string()]),
-    );
+    // 2026-02-23 is a Monday; series runs Mondays and Thursdays
+    let task = s
+        .create_recurring_task(
+            pid,
+            "Mon/Thu sync",
+            "2026-02-23T09:00:00Z",
+            json!({ "rrule": "RRULE:FREQ=WEEKLY;BYDAY=MO,TH" }),
+        )
+        .await;
+    let task_id = task["id"].as_str().unwrap();
 
-    let project = db_ops::create_project(
-        &conn,
-        None,
-        &opengate_models::CreateProject {
-            name: "AT0 Project".to_string(),
-            description: None,
-            repo_url: None,
-            default_branch: None,
-            join_mode: None,
-            cta_enabled: None,
-            is_public: None,
-        },
-        &agent.id,
-    );
-    let task = db_ops::create_task(
-        &conn,
-        None,
-        &project.id,
-        &opengate_models::CreateTask {
-            title: "AT0 Task".to_string(),
-            description: None,
-            priority: None,
-            tags: None,
-            context: None,
-            output: None,
-            due_date: None,
-            assignee_type: None,
-            assignee_id: None,
-            scheduled_at: None,
-            recurrence_rule: None,
-        },
-        &agent.id,
+    s.finish_task(task_id).await;
+    let children = s.recurrence_children(pid, task_id).await;
+    assert_eq!(children.len(), 1);
+    assert_eq!(
+        children[0]["scheduled_at"].as_str(),
+        Some("2026-02-26T09:00:00+00:00"),
+        "next occurrence should be the following Thursday"
     );
 
-    // Search for a capability nobody has
-    let targets = db_ops::find_capability_targets(&conn, "devops:terraform");
-    assert_eq!(targets.len(), 0, "Expected 0 matches for devops:terraform");
+    s.finish_task(children[0]["id"].as_str().unwrap()).await;
+    let children = s.recurrence_children(pid, task_id).await;
+    assert_eq!(children.len(), 2);
+    assert!(children
+        .iter()
+        .any(|t| t["scheduled_at"].as_str() == Some("2026-03-02T09:00:00+00:00")));
+}
 
-    // Create question with required_capability but no target
-    let question = db_ops::create_question(
-        &conn,
-        &task.id,
-        &opengate_models::CreateQuestion {
-            question: "Who handles terraform?".to_string(),
-            question_type: None,
-            context: None,
-            target_type: None,
-            target_id: None,
-            required_capability: Some("devops:terraform".to_string()),
-            blocking: Some(true),
-        },
-        "agent",
-        &agent.id,
-    );
+#[tokio::test]
+async fn test_task_recurrence_rrule_bymonthday_and_count() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("rrule-count").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Auto-target returns empty
-    let result = db_ops::auto_target_question(&conn, &question.id, "devops:terraform");
-    assert_eq!(result.len(), 0);
+    // COUNT=2 includes the first occurrence, so exactly one follow-up is created
+    let task = s
+        .create_recurring_task(
+            pid,
+            "Month-end close",
+            "2026-01-15T12:00:00Z",
+            json!({ "rrule": "FREQ=MONTHLY;BYMONTHDAY=15;COUNT=2" }),
+        )
+        .await;
+    let task_id = task["id"].as_str().unwrap();
 
-    // Question should remain unrouted
-    let q = db_ops::get_question(&conn, &question.id).unwrap();
-    assert!(
-        q.target_id.is_none(),
-        "Question should remain unrouted with 0 matches"
+    s.finish_task(task_id).await;
+    let children = s.recurrence_children(pid, task_id).await;
+    assert_eq!(children.len(), 1);
+    assert_eq!(
+        children[0]["scheduled_at"].as_str(),
+        Some("2026-02-15T12:00:00+00:00")
+    );
+
+    s.finish_task(children[0]["id"].as_str().unwrap()).await;
+    assert_eq!(
+        s.recurrence_children(pid, task_id).await.len(),
+        1,
+        "COUNT exhausted — no further occurrences"
     );
-    assert!(q.target_type.is_none());
 }
 
 #[tokio::test]
-async fn test_auto_target_single_exact_match() {
-    // Exactly one agent has the required capability → auto-assigns
-    let tmp = TempDir::new().unwrap();
-    let conn = db::init_db(tmp.path().join("at1.db").to_str().unwrap());
+async fn test_task_recurrence_rrule_until() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("rrule-until").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    let (creator, _) = db_ops::create_agent(
-        &conn,
-        &CreateAgent::new("creator-agent").with_capabilities(vec!["coding:rust".to_string()]),
-    );
-    let (devops_agent, _) = db_ops::create_agent(
-        &conn,
-        &CreateAgent::new("devops-agent")
-            .with_capabilities(vec!["devops:docker".to_string(), "devops:k8s".to_string()]),
-    );
+    let task = s
+        .create_recurring_task(
+            pid,
+            "Short series",
+            "2026-03-01T08:00:00Z",
+            json!({ "rrule": "FREQ=DAILY;INTERVAL=2;UNTIL=20260303T080000Z" }),
+        )
+        .await;
+    let task_id = task["id"].as_str().unwrap();
 
-    let project = db_ops::create_project(
-        &conn,
-        None,
-        &opengate_models::CreateProject {
-            name: "AT1 Project".to_string(),
-            description: None,
-            repo_url: None,
-            default_branch: None,
-            join_mode: None,
-            cta_enabled: None,
-            is_public: None,
-        },
-        &creator.id,
-    );
-    let task = db_ops::create_task(
-        &conn,
-        None,
-        &project.id,
-        &opengate_models::CreateTask {
-            title: "AT1 Task".to_string(),
-            description: None,
-            priority: None,
-            tags: None,
-            context: None,
-            output: None,
-            due_date: None,
-            assignee_type: None,
-            assignee_id: None,
-            scheduled_at: None,
-            recurrence_rule: None,
-        },
-        &creator.id,
+    s.finish_task(task_id).await;
+    let children = s.recurrence_children(pid, task_id).await;
+    assert_eq!(children.len(), 1);
+    assert_eq!(
+        children[0]["scheduled_at"].as_str(),
+        Some("2026-03-03T08:00:00+00:00")
     );
 
-    let question = db_ops::create_question(
-        &conn,
-        &task.id,
-        &opengate_models::CreateQuestion {
-            question: "How to set up the Docker build?".to_string(),
-            question_type: None,
-            context: None,
-            target_type: None,
-            target_id: None,
-            required_capability: Some("devops:docker".to_string()),
-            blocking: Some(true),
-        },
-        "agent",
-        &creator.id,
-    );
+    s.finish_task(children[0]["id"].as_str().unwrap()).await;
+    assert_eq!(s.recurrence_children(pid, task_id).await.len(), 1);
+}
 
-    let result = db_ops::auto_target_question(&conn, &question.id, "devops:docker");
-    assert_eq!(
-        result.len(),
-        1,
-        "Expected exactly 1 match for devops:docker"
-    );
-    assert_eq!(result[0].target_type, "agent");
-    assert_eq!(result[0].target_id, devops_agent.id);
+#[tokio::test]
+async fn test_task_recurrence_rejects_invalid_rrule() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("rrule-invalid").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    // Question should be auto-assigned
-    let q = db_ops::get_question(&conn, &question.id).unwrap();
-    assert_eq!(q.target_type.as_deref(), Some("agent"));
-    assert_eq!(q.target_id.as_deref(), Some(devops_agent.id.as_str()));
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "title": "Broken",
+            "recurrence_rule": { "rrule": "FREQ=SOMETIMES" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let task = s.create_task(pid, "Plain").await;
+    let resp = s
+        .client()
+        .patch(format!(
+            "{}/api/tasks/{}",
+            s.base_url,
+            task["id"].as_str().unwrap()
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "recurrence_rule": { "frequency": "hourly" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // A typo'd end_date must be refused rather than silently ignored
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "title": "Unbounded by accident",
+            "recurrence_rule": { "frequency": "daily", "end_date": "2026-02-30" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
 }
 
 #[tokio::test]
-async fn test_auto_target_multiple_matches() {
-    // Multiple agents match → question stays unrouted, all are returned
-    let tmp = TempDir::new().unwrap();
-    let conn = db::init_db(tmp.path().join("atm.db").to_str().unwrap());
+async fn test_task_recurrence_pause_and_resume() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("recurrence-pause").await;
+    let pid = proj["id"].as_str().unwrap();
 
-    let (agent1, _) = db_ops::create_agent(
-        &conn,
-        &CreateAgent::new("devops-agent-1").with_capabilities(vec!["devops:docker".to_string()]),
-    );
-    let (_agent2, _) = db_ops::create_agent(
-        &conn,
-        &CreateAgent::new("devops-agent-2")
-            .with_capabilities(vec!["devops:docker".to_string(), "devops:k8s".to_string()]),
-    );
+    let task = s
+        .create_recurring_task(
+            pid,
+            "Pausable",
+            "2026-02-23T09:00:00Z",
+            json!({ "frequency": "daily" }),
+        )
+        .await;
+    let task_id = task["id"].as_str().unwrap();
+    assert_eq!(task["recurrence_paused"], false);
+
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/recurrence/pause",
+            s.base_url, task_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let paused: Value = resp.json().await.unwrap();
+    assert_eq!(paused["recurrence_paused"], true);
+
+    let schedule: Value = s
+        .client()
+        .get(format!("{}/api/projects/{}/schedule", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(schedule[0]["recurrence_paused"], true);
+
+    // Completing while paused does not spawn the next occurrence
+    s.finish_task(task_id).await;
+    assert!(s.recurrence_children(pid, task_id).await.is_empty());
+
+    // Resuming picks the series back up
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/recurrence/resume",
+            s.base_url, task_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resumed: Value = resp.json().await.unwrap();
+    assert_eq!(resumed["recurrence_paused"], false);
+
+    let children = s.recurrence_children(pid, task_id).await;
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0]["recurrence_paused"], false);
+}
+
+#[tokio::test]
+async fn test_task_recurrence_skip_next_occurrence() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("recurrence-skip").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let task = s
+        .create_recurring_task(
+            pid,
+            "Skippable",
+            "2026-02-23T09:00:00Z",
+            json!({ "frequency": "daily" }),
+        )
+        .await;
+    let task_id = task["id"].as_str().unwrap();
+
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/recurrence/skip",
+            s.base_url, task_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["skipped"]["id"].as_str(), Some(task_id));
+    assert_eq!(body["skipped"]["status"], "cancelled");
+    let history = body["skipped"]["status_history"].as_array().unwrap();
+    assert_eq!(history.last().unwrap()["status"], "cancelled");
+    assert_eq!(
+        body["next"]["scheduled_at"].as_str(),
+        Some("2026-02-24T09:00:00+00:00")
+    );
+    let next_id = body["next"]["id"].as_str().unwrap().to_string();
+
+    // Skipping again (via any task in the series) moves past the new occurrence too
+    let body: Value = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/recurrence/skip",
+            s.base_url, task_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["skipped"]["id"].as_str(), Some(next_id.as_str()));
+    assert_eq!(
+        body["next"]["scheduled_at"].as_str(),
+        Some("2026-02-25T09:00:00+00:00")
+    );
+
+    // The series itself is intact: one open occurrence remains
+    let open: Vec<_> = s
+        .recurrence_children(pid, task_id)
+        .await
+        .into_iter()
+        .filter(|t| t["status"] == "backlog")
+        .collect();
+    assert_eq!(open.len(), 1);
+}
+
+#[tokio::test]
+async fn test_task_recurrence_pause_requires_recurring_task() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("recurrence-pause-plain").await;
+    let pid = proj["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Not recurring").await;
+
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/recurrence/pause",
+            s.base_url,
+            task["id"].as_str().unwrap()
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/does-not-exist/recurrence/pause",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_schedule_ics_export() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("ics-export").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let series = s
+        .create_recurring_task(
+            pid,
+            "Weekly review, all hands",
+            "2026-02-23T09:00:00Z",
+            json!({ "rrule": "FREQ=WEEKLY;BYDAY=MO" }),
+        )
+        .await;
+    let series_id = series["id"].as_str().unwrap();
+    // Skip the first occurrence → becomes an EXDATE on the series
+    s.client()
+        .post(format!(
+            "{}/api/tasks/{}/recurrence/skip",
+            s.base_url, series_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "title": "Ship release", "due_date": "2026-03-10" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let resp = s
+        .client()
+        .get(format!("{}/api/projects/{}/schedule.ics", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/calendar"));
+    let body = resp.text().await.unwrap();
+    assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(body.trim_end().ends_with("END:VCALENDAR"));
+    assert!(body.contains("SUMMARY:Weekly review\\, all hands"));
+    assert!(body.contains("DTSTART:20260223T090000Z"));
+    assert!(body.contains("RRULE:FREQ=WEEKLY;BYDAY=MO"));
+    assert!(body.contains("EXDATE:20260223T090000Z"));
+    assert!(body.contains("SUMMARY:Due: Ship release"));
+    assert!(body.contains("DTSTART;VALUE=DATE:20260310"));
+    // The spawned occurrence is folded into the series, not emitted separately
+    assert_eq!(body.matches("-scheduled@opengate").count(), 1);
+
+    // Calendar clients can authenticate with a query token instead of a header
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/schedule.ics?token={}",
+            s.base_url, pid, s.api_key
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/schedule.ics?token=bogus",
+            s.base_url, pid
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
+
+// ===== Inbound Webhook Triggers =====
+
+#[tokio::test]
+async fn test_webhook_trigger_create_and_list() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-test").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    // Create a trigger
+    let resp: Value = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "CI Failure",
+            "action_type": "create_task",
+            "action_config": {
+                "title": "CI failed on {{payload.repo}}",
+                "description": "Branch: {{payload.branch}}",
+                "priority": "high",
+                "tags": ["ci", "{{payload.repo}}"]
+            }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(resp["trigger"]["name"].as_str(), Some("CI Failure"));
+    assert_eq!(resp["trigger"]["action_type"].as_str(), Some("create_task"));
+    let secret = resp["secret"].as_str().unwrap();
+    assert!(!secret.is_empty(), "should return raw secret on creation");
+
+    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+
+    // List triggers
+    let list: Value = s
+        .client()
+        .get(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let arr = list.as_array().unwrap();
+    assert_eq!(arr.len(), 1);
+    assert_eq!(arr[0]["id"].as_str(), Some(trigger_id.as_str()));
+
+    // Secret is not exposed in list
+    assert!(arr[0].get("secret").is_none() || arr[0]["secret"].is_null());
+}
+
+#[tokio::test]
+async fn test_webhook_trigger_receive_creates_task() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-recv").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let resp: Value = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "Create Task Trigger",
+            "action_type": "create_task",
+            "action_config": {
+                "title": "Alert: {{payload.service}} is down",
+                "description": "Region: {{payload.region}}",
+                "priority": "high"
+            }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let secret = resp["secret"].as_str().unwrap().to_string();
+    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+
+    // Fire the webhook
+    let fire_resp = s
+        .client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url, trigger_id
+        ))
+        .header("X-Webhook-Secret", &secret)
+        .json(&json!({ "service": "payments-api", "region": "eu-west-1" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(fire_resp.status(), 200);
+    let result: Value = fire_resp.json().await.unwrap();
+    assert!(result["task_id"].is_string(), "should return task_id");
+
+    // Verify task was created with interpolated title
+    let task_id = result["task_id"].as_str().unwrap();
+    let task: Value = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(task["title"].as_str(), Some("Alert: payments-api is down"));
+}
+
+/// Create a trigger and return its id and secret.
+async fn create_trigger(
+    s: &TestServer,
+    pid: &str,
+    action_type: &str,
+    config: Value,
+) -> (String, String) {
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"name": action_type, "action_type": action_type, "action_config": config}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: Value = resp.json().await.unwrap();
+    (
+        body["trigger"]["id"].as_str().unwrap().to_string(),
+        body["secret"].as_str().unwrap().to_string(),
+    )
+}
+
+async fn fire_trigger(s: &TestServer, trigger: &(String, String), payload: Value) -> (u16, Value) {
+    let resp = s
+        .client()
+        .post(format!("{}/api/webhooks/trigger/{}", s.base_url, trigger.0))
+        .header("X-Webhook-Secret", &trigger.1)
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}
+
+// Triggers can drive a task's whole lifecycle: open it, move it along by external ref,
+// comment on it and resolve its questions
+#[tokio::test]
+async fn test_webhook_trigger_lifecycle_actions() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-lifecycle").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    // Actions that target a task need to say which one
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"name": "bad", "action_type": "update_task", "action_config": {"status": "todo"}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
+
+    let opened = create_trigger(
+        &s,
+        pid,
+        "create_task",
+        json!({"title": "Review PR #{{payload.number}}", "external_ref": "pr-{{payload.number}}"}),
+    )
+    .await;
+    let updated = create_trigger(
+        &s,
+        pid,
+        "update_task",
+        json!({
+            "external_ref": "pr-{{payload.number}}",
+            "status": "todo",
+            "priority": "high",
+            "context": {"ci": {"state": "{{payload.state}}"}}
+        }),
+    )
+    .await;
+    let commented = create_trigger(
+        &s,
+        pid,
+        "add_activity",
+        json!({"task_id": "{{payload.task}}", "content": "CI {{payload.state}} on {{payload.sha}}"}),
+    )
+    .await;
+    let resolved = create_trigger(
+        &s,
+        pid,
+        "resolve_question",
+        json!({"question_id": "{{payload.question}}", "resolution": "Approved by {{payload.user}}"}),
+    )
+    .await;
+
+    let (status, result) = fire_trigger(&s, &opened, json!({"number": 42})).await;
+    assert_eq!(status, 200);
+    let task_id = result["task_id"].as_str().unwrap().to_string();
+
+    let (status, result) =
+        fire_trigger(&s, &updated, json!({"number": 42, "state": "green"})).await;
+    assert_eq!(status, 200, "{result}");
+    assert_eq!(result["task_id"], task_id.as_str());
+    let task = s.get_task(&task_id).await;
+    assert_eq!(task["status"], "todo");
+    assert_eq!(task["priority"], "high");
+    assert_eq!(task["context"]["ci"]["state"], "green");
+    assert_eq!(task["context"]["external_ref"], "pr-42");
+
+    // No task with that ref
+    let (status, result) = fire_trigger(&s, &updated, json!({"number": 7})).await;
+    assert_eq!(status, 500);
+    assert!(result["error"].as_str().unwrap().contains("pr-7"));
+
+    let (status, result) = fire_trigger(
+        &s,
+        &commented,
+        json!({"task": task_id, "state": "passed", "sha": "abc123"}),
+    )
+    .await;
+    assert_eq!(status, 200, "{result}");
+    let activity: Value = s
+        .client()
+        .get(format!("{}/api/tasks/{}/activity", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(activity
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|a| a["content"] == "CI passed on abc123"));
+
+    let question: Value = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"question": "Ship it?"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let qid = question["id"].as_str().unwrap();
+    let (status, result) =
+        fire_trigger(&s, &resolved, json!({"question": qid, "user": "octocat"})).await;
+    assert_eq!(status, 200, "{result}");
+    assert_eq!(result["status"], "resolved");
+    let task = s.get_task(&task_id).await;
+    assert_eq!(task["has_open_questions"], false);
+
+    // Already resolved
+    let (status, _) =
+        fire_trigger(&s, &resolved, json!({"question": qid, "user": "octocat"})).await;
+    assert_eq!(status, 500);
+}
+
+// for_each fans one webhook out into a task per array element, with nested fields
+// mapped into context; the validate endpoint previews a mapping against a sample
+#[tokio::test]
+async fn test_webhook_trigger_payload_mapping() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-mapping").await;
+    let pid = proj["id"].as_str().unwrap();
+    let config = json!({
+        "for_each": "payload.commits",
+        "title": "Review {{item.id}}: {{item.message}}",
+        "description": "Pushed to {{$.ref}} ({{index}})",
+        "context": {"author": "{{item.author}}", "files": "{{item.files[*]}}"}
+    });
+    let push = json!({
+        "ref": "main",
+        "commits": [
+            {"id": "a1", "message": "Fix login", "author": {"name": "ada"}, "files": ["auth.rs"]},
+            {"id": "b2", "message": "Add tests", "author": {"name": "bob"}, "files": ["a.rs", "b.rs"]}
+        ]
+    });
+
+    let check = |config: Value, sample: Value| {
+        s.client()
+            .post(format!("{}/api/projects/{}/triggers/validate", s.base_url, pid))
+            .header("Authorization", s.auth_header())
+            .json(&json!({"action_type": "create_task", "action_config": config, "sample_payload": sample}))
+            .send()
+    };
+    let report: Value = check(config.clone(), push.clone())
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["valid"], true, "{report}");
+    assert_eq!(report["preview"].as_array().unwrap().len(), 2);
+    assert_eq!(report["preview"][1]["title"], "Review b2: Add tests");
+    assert_eq!(
+        report["preview"][1]["context"]["files"],
+        json!(["a.rs", "b.rs"])
+    );
+
+    let report: Value = check(
+        config.clone(),
+        json!({"ref": "main", "commits": [{"id": "c3"}]}),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(report["valid"], false);
+    assert!(report["errors"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("{{item.author}} is not in the sample payload")));
+    let report: Value = check(config.clone(), json!({"commits": "nope"}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        report["errors"][0],
+        "for_each: payload.commits is not an array"
+    );
+
+    let trigger = create_trigger(&s, pid, "create_task", config).await;
+    let (status, result) = fire_trigger(&s, &trigger, push).await;
+    assert_eq!(status, 200, "{result}");
+    assert_eq!(result["count"], 2);
+    let task = s
+        .get_task(result["results"][0]["task_id"].as_str().unwrap())
+        .await;
+    assert_eq!(task["title"], "Review a1: Fix login");
+    assert_eq!(task["description"], "Pushed to main (0)");
+    assert_eq!(task["context"]["author"]["name"], "ada");
+    assert_eq!(task["context"]["files"], json!(["auth.rs"]));
+}
+
+// Retried deliveries run once, and a retry storm hits the rate limit instead of
+// creating a task per request
+#[tokio::test]
+async fn test_webhook_trigger_dedup_and_rate_limit() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-dedup").await;
+    let pid = proj["id"].as_str().unwrap();
+    let created: Value = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "CI",
+            "action_type": "create_task",
+            "action_config": {"title": "Build {{payload.build}} failed"},
+            "dedup_key": "payload.delivery.id",
+            "rate_limit_per_minute": 2
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(created["trigger"]["dedup_key"], "payload.delivery.id");
+    let trigger = (
+        created["trigger"]["id"].as_str().unwrap().to_string(),
+        created["secret"].as_str().unwrap().to_string(),
+    );
+
+    let (status, first) =
+        fire_trigger(&s, &trigger, json!({"build": 1, "delivery": {"id": "d-1"}})).await;
+    assert_eq!(status, 200);
+    assert!(first["task_id"].is_string());
+    let (status, repeat) =
+        fire_trigger(&s, &trigger, json!({"build": 1, "delivery": {"id": "d-1"}})).await;
+    assert_eq!(status, 200);
+    assert_eq!(repeat["deduplicated"], true);
+    assert!(repeat.get("task_id").is_none());
+
+    let (status, _) =
+        fire_trigger(&s, &trigger, json!({"build": 2, "delivery": {"id": "d-2"}})).await;
+    assert_eq!(status, 200);
+    // Two runs this minute; the third is turned away
+    let (status, limited) =
+        fire_trigger(&s, &trigger, json!({"build": 3, "delivery": {"id": "d-3"}})).await;
+    assert_eq!(status, 429);
+    assert!(limited["error"]
+        .as_str()
+        .unwrap()
+        .contains("2 runs per minute"));
+
+    let logs: Value = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/triggers/{}/logs",
+            s.base_url, pid, trigger.0
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut statuses: Vec<&str> = logs
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| l["status"].as_str().unwrap())
+        .collect();
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        vec!["deduplicated", "rate_limited", "success", "success"]
+    );
+    let tasks: Value = s
+        .client()
+        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(tasks.as_array().unwrap().len(), 2);
+}
+
+// Stock GitHub / GitLab / Stripe webhooks are verified the provider's way
+#[tokio::test]
+async fn test_webhook_trigger_provider_signatures() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-signatures").await;
+    let pid = proj["id"].as_str().unwrap();
+    let create = |verification: &str, secret: Option<&str>| {
+        s.client()
+            .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+            .header("Authorization", s.auth_header())
+            .json(&json!({
+                "name": verification,
+                "action_type": "create_task",
+                "action_config": {"title": "Push to {{payload.ref}}"},
+                "verification": verification,
+                "signing_secret": secret,
+            }))
+            .send()
+    };
+
+    // Provider modes need the provider's secret
+    assert_eq!(create("github", None).await.unwrap().status(), 422);
+    assert_eq!(create("bitbucket", Some("x")).await.unwrap().status(), 422);
+
+    let github: Value = create("github", Some("gh-secret"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(github["trigger"]["verification"], "github");
+    assert!(github["trigger"].get("signing_secret").is_none());
+    let url = format!(
+        "{}/api/webhooks/trigger/{}",
+        s.base_url,
+        github["trigger"]["id"].as_str().unwrap()
+    );
+    let body = br#"{"ref":"refs/heads/main"}"#.to_vec();
+    let signature = format!(
+        "sha256={}",
+        hex::encode(opengate::signatures::hmac_sha256(b"gh-secret", &body))
+    );
+    let resp = s
+        .client()
+        .post(&url)
+        .header("X-Hub-Signature-256", &signature)
+        .header("Content-Type", "application/json")
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let result: Value = resp.json().await.unwrap();
+    let task = s.get_task(result["task_id"].as_str().unwrap()).await;
+    assert_eq!(task["title"], "Push to refs/heads/main");
+
+    // Our own secret header doesn't stand in for the provider's signature
+    let resp = s
+        .client()
+        .post(&url)
+        .header("X-Webhook-Secret", github["secret"].as_str().unwrap())
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = s
+        .client()
+        .post(&url)
+        .header("X-Hub-Signature-256", "sha256=00")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    let gitlab: Value = create("gitlab", Some("gl-token"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url,
+            gitlab["trigger"]["id"].as_str().unwrap()
+        ))
+        .header("X-Gitlab-Token", "gl-token")
+        .json(&json!({"ref": "main"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let stripe: Value = create("stripe", Some("whsec_1"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let body = br#"{"ref":"invoice"}"#.to_vec();
+    let ts = chrono::Utc::now().timestamp().to_string();
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url,
+            stripe["trigger"]["id"].as_str().unwrap()
+        ))
+        .header(
+            "Stripe-Signature",
+            format!(
+                "t={},v1={}",
+                ts,
+                opengate::signatures::stripe_signature("whsec_1", &ts, &body)
+            ),
+        )
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_webhook_trigger_invalid_secret_rejected() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-auth").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let resp: Value = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "Auth Test",
+            "action_type": "create_task",
+            "action_config": { "title": "Test", "priority": "low" }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+
+    // Wrong secret → 401
+    let fire_resp = s
+        .client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url, trigger_id
+        ))
+        .header("X-Webhook-Secret", "wrong-secret")
+        .json(&json!({ "foo": "bar" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(fire_resp.status(), 401);
+
+    // Missing secret → 401
+    let fire_resp2 = s
+        .client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url, trigger_id
+        ))
+        .json(&json!({ "foo": "bar" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(fire_resp2.status(), 401);
+}
+
+#[tokio::test]
+async fn test_webhook_trigger_delete() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-del").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let resp: Value = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "Delete Me",
+            "action_type": "create_task",
+            "action_config": { "title": "Deleted", "priority": "low" }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+
+    // Delete
+    let del_resp = s
+        .client()
+        .delete(format!(
+            "{}/api/projects/{}/triggers/{}",
+            s.base_url, pid, trigger_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(del_resp.status(), 204);
+
+    // List should be empty
+    let list: Value = s
+        .client()
+        .get(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(list.as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_webhook_trigger_logs_recorded() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-logs").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let resp: Value = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "Log Test",
+            "action_type": "create_task",
+            "action_config": { "title": "Logged Task", "priority": "medium" }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let secret = resp["secret"].as_str().unwrap().to_string();
+    let trigger_id = resp["trigger"]["id"].as_str().unwrap().to_string();
+
+    // Fire with correct secret
+    s.client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url, trigger_id
+        ))
+        .header("X-Webhook-Secret", &secret)
+        .json(&json!({ "x": 1 }))
+        .send()
+        .await
+        .unwrap();
+
+    // Fire with wrong secret
+    s.client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url, trigger_id
+        ))
+        .header("X-Webhook-Secret", "bad")
+        .json(&json!({ "x": 2 }))
+        .send()
+        .await
+        .unwrap();
+
+    // Check logs
+    let logs: Value = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/triggers/{}/logs",
+            s.base_url, pid, trigger_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let arr = logs.as_array().unwrap();
+    assert_eq!(arr.len(), 2, "should have 2 log entries");
+    // Most recent first
+    assert_eq!(
+        arr[0]["status"].as_str(),
+        Some("rejected"),
+        "last entry is rejected"
+    );
+    assert_eq!(
+        arr[1]["status"].as_str(),
+        Some("success"),
+        "first entry is success"
+    );
+}
+
+// ===== Slack integration =====
+
+/// Stand-in for Slack's `chat.postMessage`: records each call and answers with a new ts.
+async fn start_mock_slack() -> (
+    String,
+    Arc<tokio::sync::Mutex<Vec<(Option<String>, Value)>>>,
+) {
+    use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+
+    type Calls = Arc<tokio::sync::Mutex<Vec<(Option<String>, Value)>>>;
+    let calls: Calls = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+    let app = Router::new()
+        .route(
+            "/chat.postMessage",
+            post(
+                |State(calls): State<Calls>, headers: HeaderMap, Json(body): Json<Value>| async move {
+                    let auth = headers
+                        .get("authorization")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    let mut calls = calls.lock().await;
+                    calls.push((auth, body));
+                    Json(json!({
+                        "ok": true,
+                        "channel": "C0DEV",
+                        "ts": format!("1700000000.00010{}", calls.len()),
+                    }))
+                },
+            ),
+        )
+        .with_state(calls.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}", addr), calls)
+}
+
+#[tokio::test]
+async fn test_slack_integration_posts_threads_and_ingests_replies() {
+    let s = TestServer::start().await;
+    let (api_url, calls) = start_mock_slack().await;
+    opengate::slack::set_api_url(&api_url);
+
+    let project = s.create_project("Slack Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let slack_url = format!("{}/api/projects/{}/integrations/slack", s.base_url, pid);
+
+    let resp = s
+        .client()
+        .put(&slack_url)
+        .header("Authorization", s.auth_header())
+        .json(&json!({"channel": "#dev"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422, "needs a webhook_url or bot_token");
+
+    let resp = s
+        .client()
+        .put(&slack_url)
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "bot_token": "xoxb-test",
+            "channel": "#dev",
+            "signing_secret": "shh",
+            "events": ["task.assigned", "task.claimed"],
+            "dashboard_url": "https://gate.example.com/",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let config: Value = resp.json().await.unwrap();
+    assert_eq!(config["threaded"], true);
+    assert!(config.get("bot_token").is_none());
+    assert!(config.get("signing_secret").is_none());
+
+    let task = s.create_task(pid, "Ship the <beta>").await;
+    let task_id = task["id"].as_str().unwrap();
+    s.client()
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    let mut posted = Vec::new();
+    for _ in 0..50 {
+        posted = calls.lock().await.clone();
+        if posted.len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    assert!(posted.len() >= 2, "{:?}", posted);
+    let (auth, first) = &posted[0];
+    assert_eq!(auth.as_deref(), Some("Bearer xoxb-test"));
+    assert_eq!(first["channel"], "#dev");
+    assert!(first.get("thread_ts").is_none());
+    assert_eq!(
+        first["blocks"][0]["text"]["text"],
+        format!(
+            "*Assigned*  <https://gate.example.com/tasks/{}|Ship the &lt;beta&gt;>",
+            task_id
+        )
+    );
+    assert!(first["blocks"][1]["elements"][0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("By: test-agent"));
+    // Later events about the task reply in its thread
+    let (_, second) = &posted[1];
+    assert_eq!(second["channel"], "C0DEV");
+    assert_eq!(second["thread_ts"], "1700000000.000101");
+
+    // Inbound: a slack_thread_reply trigger turns thread replies into task comments
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"name": "Slack replies", "action_type": "slack_thread_reply", "action_config": {}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    let trigger_url = format!(
+        "{}/api/webhooks/trigger/{}",
+        s.base_url,
+        created["trigger"]["id"].as_str().unwrap()
+    );
+    let send_signed = |body: Value, secret: &'static str| {
+        let body = body.to_string();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature = opengate::slack::sign(secret, &timestamp, body.as_bytes());
+        s.client()
+            .post(&trigger_url)
+            .header("content-type", "application/json")
+            .header("x-slack-request-timestamp", timestamp)
+            .header("x-slack-signature", signature)
+            .body(body)
+            .send()
+    };
+
+    let resp = send_signed(
+        json!({"type": "url_verification", "challenge": "abc123"}),
+        "shh",
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.json::<Value>().await.unwrap()["challenge"], "abc123");
+
+    let reply = json!({
+        "type": "event_callback",
+        "event": {
+            "type": "message",
+            "channel": "C0DEV",
+            "user": "U42",
+            "text": "Ops says ship it",
+            "ts": "1700000100.000200",
+            "thread_ts": "1700000000.000101",
+        },
+    });
+    let resp = send_signed(reply.clone(), "wrong").await.unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = send_signed(reply, "shh").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.json::<Value>().await.unwrap()["task_id"], task_id);
+
+    let activity: Vec<Value> = s
+        .client()
+        .get(format!("{}/api/tasks/{}/activity", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let comment = activity
+        .iter()
+        .find(|a| a["content"] == "Ops says ship it")
+        .expect("reply ingested as a comment");
+    assert_eq!(comment["author_id"], "slack:U42");
+    assert_eq!(comment["metadata"]["source"], "slack");
+
+    let resp = s
+        .client()
+        .delete(&slack_url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+}
+
+// ===== User Enrichment Tests =====
+
+/// Helper: register a human user and return (user_id, jwt_token)
+#[allow(dead_code)]
+async fn register_user(s: &TestServer, username: &str, password: &str) -> (String, String) {
+    let resp = s
+        .client()
+        .post(format!("{}/api/auth/register", s.base_url))
+        .json(&json!({ "username": username, "password": password }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let id = body["user"]["id"].as_str().unwrap().to_string();
+    let token = body["token"].as_str().unwrap().to_string();
+    (id, token)
+}
+
+#[allow(dead_code)]
+async fn test_list_questions_with_status_filter() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Q - Filter").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task question filter").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create two questions
+    for q in &["Question 1?", "Question 2?"] {
+        let resp = s
+            .client()
+            .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+            .header("Authorization", s.auth_header())
+            .json(&json!({ "question": q }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+
+    // List all — should be 2
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let all: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(all.len(), 2);
+
+    // Resolve first question
+    let q_id = all[0]["id"].as_str().unwrap();
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/resolve",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "resolution": "Use JSON" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resolved: Value = resp.json().await.unwrap();
+    assert_eq!(resolved["status"], "resolved");
+    assert_eq!(resolved["resolution"], "Use JSON");
+
+    // Filter open — should be 1
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}/questions?status=open",
+            s.base_url, task_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let open: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(open.len(), 1);
+
+    // Filter resolved — should be 1
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}/questions?status=resolved",
+            s.base_url, task_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resolved_list: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(resolved_list.len(), 1);
+}
+
+#[tokio::test]
+async fn test_has_open_questions_flag() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Q - Flag").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task flag test").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Initially, has_open_questions should be false
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let t: Value = resp.json().await.unwrap();
+    assert_eq!(t["has_open_questions"], false);
+
+    // Create a blocking question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Blocking Q?", "blocking": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // Now has_open_questions should be true
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let t: Value = resp.json().await.unwrap();
+    assert_eq!(t["has_open_questions"], true);
+
+    // Resolve the question
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/resolve",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "resolution": "Answered" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // Flag should be false again
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let t: Value = resp.json().await.unwrap();
+    assert_eq!(t["has_open_questions"], false);
+}
+
+#[tokio::test]
+async fn test_non_blocking_question_does_not_set_flag() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Q - NonBlocking").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task non-blocking Q").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create a non-blocking question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "FYI question?", "blocking": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // has_open_questions should still be false (only blocking questions count)
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let t: Value = resp.json().await.unwrap();
+    assert_eq!(t["has_open_questions"], false);
+}
+
+#[tokio::test]
+async fn test_stale_release_skips_tasks_with_open_questions() {
+    // Direct DB test — create task with open question, simulate stale agent, verify not released
+    let tmp = TempDir::new().expect("failed to create temp dir");
+    let db_path = tmp.path().join("stale_q.db");
+    let db_path_str = db_path.to_str().unwrap();
+
+    let conn = db::init_db(db_path_str);
+    let (agent, _api_key) = db_ops::create_agent(
+        &conn,
+        &CreateAgent::new("stale-q-agent").with_skills(vec!["rust".to_string()]),
+    );
+
+    // Create project + task
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "Stale Q Project".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &agent.id,
+    );
+
+    let task = db_ops::create_task(
+        &conn,
+        None,
+        &project.id,
+        &opengate_models::CreateTask {
+            title: "Stale Q Task".to_string(),
+            description: None,
+            priority: None,
+            tags: None,
+            context: None,
+            output: None,
+            due_date: None,
+            assignee_type: None,
+            assignee_id: None,
+            scheduled_at: None,
+            recurrence_rule: None,
+        },
+        &agent.id,
+    );
+
+    // Move to todo first, then claim
+    db_ops::update_task(
+        &conn,
+        None,
+        &task.id,
+        &opengate_models::UpdateTask {
+            status: Some("todo".to_string()),
+            ..Default::default()
+        },
+        &Default::default(),
+    )
+    .unwrap();
+    db_ops::claim_task(
+        &conn,
+        None,
+        &task.id,
+        &agent.id,
+        &agent.name,
+        &Default::default(),
+    )
+    .unwrap();
+
+    // Create a blocking open question on this task
+    db_ops::create_question(
+        &conn,
+        &task.project_id,
+        Some(&task.id),
+        &opengate_models::CreateQuestion {
+            question: "Need clarification".to_string(),
+            question_type: None,
+            context: None,
+            target_type: None,
+            target_id: None,
+            required_capability: None,
+            blocking: Some(true),
+            priority: None,
+        },
+        "agent",
+        &agent.id,
+    );
+
+    // Make the agent look stale (last_seen 2 hours ago)
+    let stale_time = (chrono::Utc::now() - chrono::Duration::hours(2)).to_rfc3339();
+    conn.execute(
+        "UPDATE agents SET last_seen_at = ?1 WHERE id = ?2",
+        rusqlite::params![stale_time, agent.id],
+    )
+    .unwrap();
+
+    // Run stale release — task should NOT be released (has open questions)
+    let released = db_ops::release_stale_tasks(&conn, 30);
+    assert!(
+        released.is_empty(),
+        "Task with open questions should not be released"
+    );
+
+    // Verify task is still in_progress
+    let task_after = db_ops::get_task(&conn, None, &task.id).unwrap();
+    assert_eq!(task_after.status, "in_progress");
+    assert_eq!(task_after.assignee_id.as_deref(), Some(&*agent.id));
+}
+
+#[tokio::test]
+async fn test_agent_targeted_questions() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Q - Agent Target").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task for agent Q").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create a question targeted at the test agent
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "question": "Can you handle this?",
+            "target_type": "agent",
+            "target_id": s.agent_id()
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // GET /api/agents/me/questions should return it
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/me/questions", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let questions: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(questions.len(), 1);
+    assert_eq!(questions[0]["question"], "Can you handle this?");
+    assert_eq!(questions[0]["target_id"].as_str().unwrap(), s.agent_id());
+}
+
+#[tokio::test]
+async fn test_project_questions_and_unrouted() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Q - Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task proj Q").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create a routed question (has target_id)
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "question": "Routed Q?",
+            "target_type": "agent",
+            "target_id": s.agent_id()
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Create an unrouted question (no target_id)
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Unrouted Q?" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // GET /api/projects/:id/questions — should return both
+    let resp = s
+        .client()
+        .get(format!("{}/api/projects/{}/questions", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let all: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(all.len(), 2);
+
+    // GET /api/projects/:id/questions?unrouted=true — should return 1
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/questions?unrouted=true",
+            s.base_url, pid
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let unrouted: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(unrouted.len(), 1);
+    assert_eq!(unrouted[0]["question"], "Unrouted Q?");
+}
+
+#[tokio::test]
+async fn test_get_single_question() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Q - Get Single").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task single Q").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Single Q?", "context": "some context" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // GET single question
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}/questions/{}",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let fetched: Value = resp.json().await.unwrap();
+    assert_eq!(fetched["id"].as_str().unwrap(), q_id);
+    assert_eq!(fetched["question"], "Single Q?");
+    assert_eq!(fetched["context"], "some context");
+}
+
+// ===== Agent Inbox Tests =====
+
+#[tokio::test]
+async fn test_inbox_empty() {
+    let s = TestServer::start().await;
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let inbox: Value = resp.json().await.unwrap();
+    assert!(inbox["summary"]
+        .as_str()
+        .unwrap()
+        .contains("No actionable work"));
+    assert_eq!(inbox["todo_tasks"].as_array().unwrap().len(), 0);
+    assert_eq!(inbox["in_progress_tasks"].as_array().unwrap().len(), 0);
+    assert!(inbox["capacity"]["has_capacity"].as_bool().unwrap());
+}
+
+#[tokio::test]
+async fn test_inbox_assigned_todo_task() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Inbox - Todo").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Inbox todo task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Move to todo
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assign to our agent
+    s.client()
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let inbox: Value = resp.json().await.unwrap();
+    let todos = inbox["todo_tasks"].as_array().unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0]["id"].as_str().unwrap(), task_id);
+    assert_eq!(todos[0]["action"], "start_work");
+    assert_eq!(todos[0]["item_type"], "task");
+}
+
+#[tokio::test]
+async fn test_inbox_in_progress_task() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Inbox - InProgress").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Inbox ip task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Move to todo then claim (→ in_progress)
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
+        .send()
+        .await
+        .unwrap();
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let inbox: Value = resp.json().await.unwrap();
+    let ip = inbox["in_progress_tasks"].as_array().unwrap();
+    assert_eq!(ip.len(), 1);
+    assert_eq!(ip[0]["id"].as_str().unwrap(), task_id);
+    assert_eq!(ip[0]["action"], "continue_work");
+}
+
+#[tokio::test]
+async fn test_inbox_open_question() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Inbox - Question").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task with question").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create a question targeted at our agent
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "question": "What framework to use?",
+            "target_type": "agent",
+            "target_id": s.agent_id()
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let inbox: Value = resp.json().await.unwrap();
+    let questions = inbox["open_questions"].as_array().unwrap();
+    assert_eq!(questions.len(), 1);
+    assert_eq!(questions[0]["action"], "resolve_question");
+    assert_eq!(questions[0]["item_type"], "question");
+    assert_eq!(questions[0]["title"], "What framework to use?");
+}
+
+#[tokio::test]
+async fn test_inbox_capacity_calculation() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Inbox - Capacity").await;
+    let pid = project["id"].as_str().unwrap();
+
+    // Create and claim a task
+    let task = s.create_task(pid, "Capacity test task").await;
+    let task_id = task["id"].as_str().unwrap();
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
+        .send()
+        .await
+        .unwrap();
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let inbox: Value = resp.json().await.unwrap();
+    let capacity = &inbox["capacity"];
+    assert_eq!(capacity["current_active_tasks"].as_i64().unwrap(), 1);
+    assert!(capacity["max_concurrent_tasks"].as_i64().unwrap() >= 1);
+}
+
+// ===== Question Replies, Dismiss, Assign =====
+
+#[tokio::test]
+async fn test_question_replies() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Reply Test Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task for replies").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create a question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "What format?" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // Post a reply
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/replies",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "body": "Use JSON format", "is_resolution": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let reply: Value = resp.json().await.unwrap();
+    assert_eq!(reply["body"], "Use JSON format");
+    assert_eq!(reply["is_resolution"], false);
+
+    // List replies
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}/questions/{}/replies",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let replies: Value = resp.json().await.unwrap();
+    assert_eq!(replies.as_array().unwrap().len(), 1);
+    assert_eq!(replies[0]["body"], "Use JSON format");
+}
+
+#[tokio::test]
+async fn test_threaded_question_replies() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Thread Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Threaded task").await;
+    let task_id = task["id"].as_str().unwrap();
+    let q: Value = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Which queue?" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let replies_url = format!(
+        "{}/api/tasks/{}/questions/{}/replies",
+        s.base_url,
+        task_id,
+        q["id"].as_str().unwrap()
+    );
+    let reply = |body: &str, parent: Option<&str>| {
+        s.client()
+            .post(&replies_url)
+            .header("Authorization", s.auth_header())
+            .json(&json!({ "body": body, "parent_reply_id": parent }))
+            .send()
+    };
+
+    let first: Value = reply("SQS?", None).await.unwrap().json().await.unwrap();
+    let first_id = first["id"].as_str().unwrap();
+    assert!(first["parent_reply_id"].is_null());
+    let second: Value = reply("Or Kafka?", None)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let nested: Value = reply("SQS lacks ordering", Some(first_id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(nested["parent_reply_id"], first_id);
+    reply("FIFO queues have it", Some(nested["id"].as_str().unwrap()))
+        .await
+        .unwrap();
+
+    // A parent from nowhere is rejected
+    let resp = reply("orphan", Some("no-such-reply")).await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Flat listing keeps chronological order
+    let flat: Value = s
+        .client()
+        .get(&replies_url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(flat.as_array().unwrap().len(), 4);
+
+    let threads: Value = s
+        .client()
+        .get(format!("{}?threaded=true", replies_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let roots = threads.as_array().unwrap();
+    assert_eq!(roots.len(), 2);
+    assert_eq!(roots[0]["id"], first_id);
+    assert_eq!(roots[0]["replies"][0]["body"], "SQS lacks ordering");
+    assert_eq!(
+        roots[0]["replies"][0]["replies"][0]["body"],
+        "FIFO queues have it"
+    );
+    assert_eq!(roots[1]["id"], second["id"]);
+    assert_eq!(roots[1]["replies"].as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_question_and_reply_artifacts() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Question Artifacts").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Fix the build").await;
+    let task_id = task["id"].as_str().unwrap();
+    let q: Value = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Why does the build fail?" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let q_url = format!(
+        "{}/api/tasks/{}/questions/{}",
+        s.base_url,
+        task_id,
+        q["id"].as_str().unwrap()
+    );
+    let post = |url: String, body: Value| {
+        s.client()
+            .post(url)
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
+
+    let resp = post(
+        format!("{}/artifacts", q_url),
+        json!({"name": "build.log", "artifact_type": "text", "value": "error[E0308]: mismatched types"}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 201);
+    let log: Value = resp.json().await.unwrap();
+    assert!(log["reply_id"].is_null());
+
+    let resp = post(
+        format!("{}/artifacts", q_url),
+        json!({"name": "bad", "artifact_type": "binary", "value": "x"}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let reply: Value = post(
+        format!("{}/replies", q_url),
+        json!({"body": "Your config pins an old toolchain"}),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let reply_id = reply["id"].as_str().unwrap();
+    let resp = post(
+        format!("{}/replies/{}/artifacts", q_url, reply_id),
+        json!({"name": "rust-toolchain.toml", "artifact_type": "text", "value": "channel = \"1.70\""}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = post(
+        format!("{}/replies/no-such-reply/artifacts", q_url),
+        json!({"name": "x", "artifact_type": "text", "value": "x"}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let get = |url: String| {
+        s.client()
+            .get(url)
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let question: Value = get(q_url.clone()).await.unwrap().json().await.unwrap();
+    assert_eq!(question["attachments"].as_array().unwrap().len(), 1);
+    assert_eq!(question["attachments"][0]["name"], "build.log");
+    let replies: Value = get(format!("{}/replies", q_url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(replies[0]["attachments"][0]["name"], "rust-toolchain.toml");
+    let all: Value = get(format!("{}/artifacts", q_url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(all.as_array().unwrap().len(), 2);
+    assert_eq!(all[1]["reply_id"], reply_id);
+}
+
+#[tokio::test]
+async fn test_project_level_questions() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Project Questions").await;
+    let pid = project["id"].as_str().unwrap();
+    let registered: Value = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({
+            "name": "deployer",
+            "setup_token": "test-setup-token",
+            "capabilities": ["cloud:aws"]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let agent_id = registered["agent"]["id"].as_str().unwrap();
+    let agent_key = registered["api_key"].as_str().unwrap();
+
+    // Capability targeting works the same as for task questions
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/questions", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "question": "Which cloud region do we deploy to?",
+            "required_capability": "cloud:aws",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+    assert!(q["task_id"].is_null());
+    assert_eq!(q["project_id"], pid);
+    assert_eq!(q["target_id"], agent_id);
+
+    // Listed with the project's questions and in the target's inbox
+    let listed: Value = s
+        .client()
+        .get(format!("{}/api/projects/{}/questions", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|item| item["id"] == q_id));
+    let inbox: Value = s
+        .client()
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", format!("Bearer {}", agent_key))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(inbox["open_questions"][0]["id"], q_id);
+    assert_eq!(inbox["open_questions"][0]["project_id"], pid);
+
+    // Not reachable through a task path
+    let task = s.create_task(pid, "Unrelated task").await;
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}/questions/{}",
+            s.base_url,
+            task["id"].as_str().unwrap(),
+            q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/projects/{}/questions/{}/replies",
+            s.base_url, pid, q_id
+        ))
+        .header("Authorization", format!("Bearer {}", agent_key))
+        .json(&json!({"body": "eu-west-1", "is_resolution": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resolved: Value = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/questions/{}",
+            s.base_url, pid, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resolved["status"], "answered");
+    assert_eq!(resolved["resolution"], "eu-west-1");
+}
+
+#[tokio::test]
+async fn test_question_priority_orders_inbox() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Question Priority").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "High priority task").await;
+    let task_id = task["id"].as_str().unwrap();
+    let registered: Value = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({"name": "answerer", "setup_token": "test-setup-token"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let agent_id = registered["agent"]["id"].as_str().unwrap();
+    let agent_key = registered["api_key"].as_str().unwrap();
+
+    let ask = |question: &'static str, priority: Option<&'static str>| {
+        let mut body = json!({
+            "question": question,
+            "target_type": "agent",
+            "target_id": agent_id,
+        });
+        if let Some(p) = priority {
+            body["priority"] = json!(p);
+        }
+        s.client()
+            .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
+
+    let resp = ask("Nice to know", Some("low")).await.unwrap();
+    assert_eq!(resp.status(), 201);
+    // Without an explicit priority the question inherits its task's
+    let resp = ask("Inherited", None).await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let inherited: Value = resp.json().await.unwrap();
+    assert_eq!(inherited["priority"], "high");
+    let resp = ask("Blocks the release", Some("critical")).await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let resp = ask("Nonsense", Some("urgent")).await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let inbox: Value = s
+        .client()
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", format!("Bearer {}", agent_key))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let order: Vec<&str> = inbox["open_questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|q| q["priority"].as_str().unwrap())
+        .collect();
+    assert_eq!(order, vec!["critical", "high", "low"]);
+}
+
+#[tokio::test]
+async fn test_reply_with_resolution_auto_resolves_question() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Auto-resolve Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task for auto-resolve").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Is this correct?", "blocking": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // Post reply with is_resolution=true
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/replies",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "body": "Yes, looks correct!", "is_resolution": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Question should now be answered
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}/questions/{}",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let q: Value = resp.json().await.unwrap();
+    assert_eq!(q["status"], "answered");
+    assert_eq!(q["resolution"], "Yes, looks correct!");
+}
+
+#[tokio::test]
+async fn test_dismiss_question() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Dismiss Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task for dismiss").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Obsolete question?", "blocking": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // Dismiss it
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/dismiss",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "reason": "No longer relevant" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let q: Value = resp.json().await.unwrap();
+    assert_eq!(q["status"], "dismissed");
+
+    // has_open_questions should be false
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let task: Value = resp.json().await.unwrap();
+    assert_eq!(task["has_open_questions"], false);
+}
+
+#[tokio::test]
+async fn test_assign_question() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Assign Question Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Task for assign").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "Who should handle this?" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // Assign to a user
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/assign",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "target_type": "user", "target_id": "user-123" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let q: Value = resp.json().await.unwrap();
+    assert_eq!(q["target_type"], "user");
+    assert_eq!(q["target_id"], "user-123");
+}
+
+// ===== Auto-targeting capability matching tests =====
+
+#[tokio::test]
+async fn test_auto_target_zero_matches() {
+    // No agent/user has the required capability → question stays unrouted
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("at0.db").to_str().unwrap());
+
+    let (agent, _) = db_ops::create_agent(
+        &conn,
+        &CreateAgent::new("agent-no-cap")
+            .with_skills(vec!["rust".to_string()])
+            .with_capabilities(vec!["coding:rust".to_string()]),
+    );
+
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "AT0 Project".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &agent.id,
+    );
+    let task = db_ops::create_task(
+        &conn,
+        None,
+        &project.id,
+        &opengate_models::CreateTask {
+            title: "AT0 Task".to_string(),
+            description: None,
+            priority: None,
+            tags: None,
+            context: None,
+            output: None,
+            due_date: None,
+            assignee_type: None,
+            assignee_id: None,
+            scheduled_at: None,
+            recurrence_rule: None,
+        },
+        &agent.id,
+    );
+
+    // Search for a capability nobody has
+    let targets = db_ops::find_capability_targets(&conn, None, "devops:terraform");
+    assert_eq!(targets.len(), 0, "Expected 0 matches for devops:terraform");
+
+    // Create question with required_capability but no target
+    let question = db_ops::create_question(
+        &conn,
+        &task.project_id,
+        Some(&task.id),
+        &opengate_models::CreateQuestion {
+            question: "Who handles terraform?".to_string(),
+            question_type: None,
+            context: None,
+            target_type: None,
+            target_id: None,
+            required_capability: Some("devops:terraform".to_string()),
+            blocking: Some(true),
+            priority: None,
+        },
+        "agent",
+        &agent.id,
+    );
+
+    // Auto-target returns empty
+    let result = db_ops::auto_target_question(&conn, &question.id, "devops:terraform");
+    assert_eq!(result.len(), 0);
+
+    // Question should remain unrouted
+    let q = db_ops::get_question(&conn, &question.id).unwrap();
+    assert!(
+        q.target_id.is_none(),
+        "Question should remain unrouted with 0 matches"
+    );
+    assert!(q.target_type.is_none());
+}
+
+#[tokio::test]
+async fn test_auto_target_single_exact_match() {
+    // Exactly one agent has the required capability → auto-assigns
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("at1.db").to_str().unwrap());
+
+    let (creator, _) = db_ops::create_agent(
+        &conn,
+        &CreateAgent::new("creator-agent").with_capabilities(vec!["coding:rust".to_string()]),
+    );
+    let (devops_agent, _) = db_ops::create_agent(
+        &conn,
+        &CreateAgent::new("devops-agent")
+            .with_capabilities(vec!["devops:docker".to_string(), "devops:k8s".to_string()]),
+    );
+
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "AT1 Project".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &creator.id,
+    );
+    let task = db_ops::create_task(
+        &conn,
+        None,
+        &project.id,
+        &opengate_models::CreateTask {
+            title: "AT1 Task".to_string(),
+            description: None,
+            priority: None,
+            tags: None,
+            context: None,
+            output: None,
+            due_date: None,
+            assignee_type: None,
+            assignee_id: None,
+            scheduled_at: None,
+            recurrence_rule: None,
+        },
+        &creator.id,
+    );
+
+    let question = db_ops::create_question(
+        &conn,
+        &task.project_id,
+        Some(&task.id),
+        &opengate_models::CreateQuestion {
+            question: "How to set up the Docker build?".to_string(),
+            question_type: None,
+            context: None,
+            target_type: None,
+            target_id: None,
+            required_capability: Some("devops:docker".to_string()),
+            blocking: Some(true),
+            priority: None,
+        },
+        "agent",
+        &creator.id,
+    );
+
+    let result = db_ops::auto_target_question(&conn, &question.id, "devops:docker");
+    assert_eq!(
+        result.len(),
+        1,
+        "Expected exactly 1 match for devops:docker"
+    );
+    assert_eq!(result[0].target_type, "agent");
+    assert_eq!(result[0].target_id, devops_agent.id);
+
+    // Question should be auto-assigned
+    let q = db_ops::get_question(&conn, &question.id).unwrap();
+    assert_eq!(q.target_type.as_deref(), Some("agent"));
+    assert_eq!(q.target_id.as_deref(), Some(devops_agent.id.as_str()));
+}
+
+#[tokio::test]
+async fn test_auto_target_multiple_matches() {
+    // Multiple agents match → question stays unrouted, all are returned
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("atm.db").to_str().unwrap());
+
+    let (agent1, _) = db_ops::create_agent(
+        &conn,
+        &CreateAgent::new("devops-agent-1").with_capabilities(vec!["devops:docker".to_string()]),
+    );
+    let (_agent2, _) = db_ops::create_agent(
+        &conn,
+        &CreateAgent::new("devops-agent-2")
+            .with_capabilities(vec!["devops:docker".to_string(), "devops:k8s".to_string()]),
+    );
+
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "ATM Project".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &agent1.id,
+    );
+    let task = db_ops::create_task(
+        &conn,
+        None,
+        &project.id,
+        &opengate_models::CreateTask {
+            title: "ATM Task".to_string(),
+            description: None,
+            priority: None,
+            tags: None,
+            context: None,
+            output: None,
+            due_date: None,
+            assignee_type: None,
+            assignee_id: None,
+            scheduled_at: None,
+            recurrence_rule: None,
+        },
+        &agent1.id,
+    );
+
+    let question = db_ops::create_question(
+        &conn,
+        &task.project_id,
+        Some(&task.id),
+        &opengate_models::CreateQuestion {
+            question: "Docker build failing".to_string(),
+            question_type: None,
+            context: None,
+            target_type: None,
+            target_id: None,
+            required_capability: Some("devops:docker".to_string()),
+            blocking: Some(true),
+            priority: None,
+        },
+        "agent",
+        &agent1.id,
+    );
+
+    let result = db_ops::auto_target_question(&conn, &question.id, "devops:docker");
+    assert!(
+        result.len() >= 2,
+        "Expected 2+ matches for devops:docker, got {}",
+        result.len()
+    );
+
+    // Question should remain unrouted (N > 1)
+    let q = db_ops::get_question(&conn, &question.id).unwrap();
+    assert!(
+        q.target_id.is_none(),
+        "Question should remain unrouted with N matches"
+    );
+}
+
+#[tokio::test]
+async fn test_auto_target_finds_agents_by_capability() {
+    // Agents with matching capability should be returned
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("atu.db").to_str().unwrap());
+
+    let (_agent, _) = db_ops::create_agent(
+        &conn,
+        &CreateAgent::new("review-agent").with_capabilities(vec!["review".to_string()]),
+    );
+
+    let targets = db_ops::find_capability_targets(&conn, None, "review");
+    assert!(!targets.is_empty(), "Expected agent match");
+
+    let agent_idx = targets.iter().position(|t| t.target_type == "agent");
+    assert!(agent_idx.is_some(), "Agent should be in results");
+}
+
+#[tokio::test]
+async fn test_auto_target_via_api_single_match() {
+    // End-to-end: POST /api/tasks/:id/questions with required_capability
+    // and one matching agent → question auto-targeted
+    let s = TestServer::start().await;
+
+    // Register a devops agent with capabilities via the register endpoint
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({
+            "name": "devops-agent",
+            "setup_token": "test-setup-token",
+            "skills": ["devops"],
+            "capabilities": ["devops:docker", "devops:k8s"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let agent_resp: Value = resp.json().await.unwrap();
+    let devops_agent_id = agent_resp["agent"]["id"].as_str().unwrap().to_string();
+
+    let project = s.create_project("Auto-target API Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Auto-target API Task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Create question with required_capability but no target
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "question": "How to configure Docker?",
+            "required_capability": "devops:docker"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+
+    // Should be auto-targeted to the devops agent
+    assert_eq!(
+        q["target_type"], "agent",
+        "Should be auto-targeted to agent"
+    );
+    assert_eq!(
+        q["target_id"], devops_agent_id,
+        "Should be targeted to devops-agent"
+    );
+}
+
+#[tokio::test]
+async fn test_question_reply_notifications() {
+    // When a reply is posted, the question asker (if agent) gets notified
+    let s = TestServer::start().await;
+
+    // Register a second agent
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({
+            "name": "answerer-agent",
+            "setup_token": "test-setup-token",
+            "skills": ["devops"],
+            "capabilities": ["devops:docker"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let agent_resp: Value = resp.json().await.unwrap();
+    let answerer_key = agent_resp["api_key"].as_str().unwrap().to_string();
+
+    let project = s.create_project("Reply Notification Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Reply Notification Task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // First agent asks a question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "How to deploy?" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // Second agent replies
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/replies",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", format!("Bearer {}", answerer_key))
+        .json(&json!({ "body": "Use docker compose up" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // First agent should have a notification about the reply
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/agents/me/notifications?unread=true",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let notifications: Vec<Value> = resp.json().await.unwrap();
+
+    let reply_notif = notifications.iter().find(|n| {
+        let event_type = n["event_type"].as_str().unwrap_or("");
+        event_type == "question_replied"
+    });
+    assert!(
+        reply_notif.is_some(),
+        "Asker should receive question_replied notification"
+    );
+}
+
+#[tokio::test]
+async fn test_question_resolved_notification() {
+    // When a question is resolved, the original asker gets notified
+    let s = TestServer::start().await;
+
+    // Register a second agent to resolve the question
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({
+            "name": "resolver-agent",
+            "setup_token": "test-setup-token",
+            "skills": ["devops"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let agent_resp: Value = resp.json().await.unwrap();
+    let resolver_key = agent_resp["api_key"].as_str().unwrap().to_string();
+
+    let project = s.create_project("Resolved Notification Project").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Resolved Notification Task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // First agent asks a question
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "question": "What DB should we use?" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let q: Value = resp.json().await.unwrap();
+    let q_id = q["id"].as_str().unwrap();
+
+    // Second agent resolves the question
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/tasks/{}/questions/{}/resolve",
+            s.base_url, task_id, q_id
+        ))
+        .header("Authorization", format!("Bearer {}", resolver_key))
+        .json(&json!({ "resolution": "Use PostgreSQL" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // First agent should have a question_resolved notification
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/agents/me/notifications?unread=true",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let notifications: Vec<Value> = resp.json().await.unwrap();
+
+    let resolved_notif = notifications.iter().find(|n| {
+        let event_type = n["event_type"].as_str().unwrap_or("");
+        event_type == "question_resolved"
+    });
+    assert!(
+        resolved_notif.is_some(),
+        "Asker should receive question_resolved notification"
+    );
+}
+
+// ===== Assignment strategies =====
+
+/// Create heartbeating agents with the given (name, seniority) pairs, sorted by id.
+fn strategy_pool(conn: &rusqlite::Connection, specs: &[(&str, &str)]) -> Vec<Agent> {
+    let mut agents: Vec<Agent> = specs
+        .iter()
+        .map(|(name, seniority)| {
+            let (agent, _) = db_ops::create_agent(
+                conn,
+                &CreateAgent::new(*name)
+                    .with_skills(vec!["rust".to_string()])
+                    .with_seniority(*seniority),
+            );
+            db_ops::update_heartbeat(conn, &agent.id);
+            agent
+        })
+        .collect();
+    agents.sort_by(|a, b| a.id.cmp(&b.id));
+    agents
+}
+
+fn assign_strategy(name: &str) -> AssignStrategy {
+    AssignStrategy {
+        strategy: name.to_string(),
+        capabilities: None,
+        seniority: None,
+        role: None,
+        agent_id: None,
+    }
+}
+
+/// Create a todo task in `project_id` and claim it as `agent`.
+fn claim_new_task(conn: &rusqlite::Connection, project_id: &str, agent: &Agent) {
+    let task = db_ops::create_task(
+        conn,
+        None,
+        project_id,
+        &opengate_models::CreateTask {
+            title: "Load".to_string(),
+            description: None,
+            priority: None,
+            tags: None,
+            context: None,
+            output: None,
+            due_date: None,
+            assignee_type: None,
+            assignee_id: None,
+            scheduled_at: None,
+            recurrence_rule: None,
+        },
+        &agent.id,
+    );
+    db_ops::update_task(
+        conn,
+        None,
+        &task.id,
+        &opengate_models::UpdateTask {
+            status: Some("todo".to_string()),
+            ..Default::default()
+        },
+        &Default::default(),
+    )
+    .unwrap();
+    db_ops::claim_task(
+        conn,
+        None,
+        &task.id,
+        &agent.id,
+        &agent.name,
+        &Default::default(),
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_assign_strategy_round_robin_is_fair() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("rr.db").to_str().unwrap());
+    let agents = strategy_pool(&conn, &[("rr-a", "mid"), ("rr-b", "mid"), ("rr-c", "mid")]);
+
+    let picks: Vec<String> = (0..30)
+        .map(|_| db_ops::find_best_agent(&conn, None, &assign_strategy("round_robin")).unwrap())
+        .collect();
+
+    // Cycles through the pool in id order
+    for (i, pick) in picks.iter().enumerate() {
+        assert_eq!(pick, &agents[i % agents.len()].id, "pick {i}");
+    }
+
+    // A newly joined agent is served next, then rotation continues
+    let late = strategy_pool(&conn, &[("rr-late", "mid")]).remove(0);
+    assert_eq!(
+        db_ops::find_best_agent(&conn, None, &assign_strategy("round_robin")).unwrap(),
+        late.id
+    );
+    assert_eq!(
+        db_ops::find_best_agent(&conn, None, &assign_strategy("round_robin")).unwrap(),
+        agents[0].id
+    );
+}
+
+#[tokio::test]
+async fn test_assign_strategy_least_loaded_spreads_work() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("ll.db").to_str().unwrap());
+    let agents = strategy_pool(&conn, &[("ll-a", "mid"), ("ll-b", "mid"), ("ll-c", "mid")]);
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "LL Project".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &agents[0].id,
+    );
+
+    // Pre-load the lowest-id agent so it is no longer the tie-break winner
+    claim_new_task(&conn, &project.id, &agents[0]);
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for i in 0..5 {
+        let picked =
+            db_ops::find_best_agent(&conn, None, &assign_strategy("least_loaded")).unwrap();
+        if i == 0 {
+            assert_eq!(picked, agents[1].id, "ties break by agent id");
+        }
+        let agent = agents.iter().find(|a| a.id == picked).unwrap();
+        claim_new_task(&conn, &project.id, agent);
+        *counts.entry(picked).or_default() += 1;
+    }
+
+    // Every agent ends up with exactly two in-progress tasks
+    for agent in &agents {
+        let expected = if agent.id == agents[0].id { 1 } else { 2 };
+        assert_eq!(counts.get(&agent.id).copied().unwrap_or(0), expected);
+        let loaded = db_ops::get_agent(&conn, &agent.id).unwrap();
+        assert_eq!(loaded.current_task_count, 2);
+    }
+}
+
+#[tokio::test]
+async fn test_assign_strategy_highest_seniority() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("hs.db").to_str().unwrap());
+    let agents = strategy_pool(
+        &conn,
+        &[
+            ("hs-junior", "junior"),
+            ("hs-mid", "mid"),
+            ("hs-senior", "senior"),
+        ],
+    );
+    let senior = agents.iter().find(|a| a.seniority == "senior").unwrap();
+
+    for _ in 0..10 {
+        assert_eq!(
+            db_ops::find_best_agent(&conn, None, &assign_strategy("highest_seniority")).unwrap(),
+            senior.id
+        );
+    }
+
+    // Seniority filter still narrows the pool
+    let mut mid_only = assign_strategy("highest_seniority");
+    mid_only.seniority = Some("mid".to_string());
+    let mid = agents.iter().find(|a| a.seniority == "mid").unwrap();
+    assert_eq!(
+        db_ops::find_best_agent(&conn, None, &mid_only).unwrap(),
+        mid.id
+    );
+}
+
+#[tokio::test]
+async fn test_assign_strategy_random_covers_pool() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("rand.db").to_str().unwrap());
+    let agents = strategy_pool(
+        &conn,
+        &[("rand-a", "mid"), ("rand-b", "mid"), ("rand-c", "mid")],
+    );
+    // An offline agent is never eligible
+    let (offline, _) = db_ops::create_agent(&conn, &CreateAgent::new("rand-offline"));
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for _ in 0..300 {
+        let picked = db_ops::find_best_agent(&conn, None, &assign_strategy("random")).unwrap();
+        *counts.entry(picked).or_default() += 1;
+    }
+
+    assert!(!counts.contains_key(&offline.id));
+    for agent in &agents {
+        let n = counts.get(&agent.id).copied().unwrap_or(0);
+        assert!(n >= 50, "agent {} picked only {n}/300 times", agent.name);
+    }
+}
+
+#[tokio::test]
+async fn test_assign_strategy_legacy_names() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("legacy.db").to_str().unwrap());
+    let agents = strategy_pool(&conn, &[("lg-junior", "junior"), ("lg-senior", "senior")]);
+    let senior = agents.iter().find(|a| a.seniority == "senior").unwrap();
+
+    let mut by_seniority = assign_strategy("seniority");
+    by_seniority.seniority = Some("senior".to_string());
+    assert!(by_seniority.validate().is_ok());
+    assert_eq!(
+        db_ops::find_best_agent(&conn, None, &by_seniority).unwrap(),
+        senior.id
+    );
+
+    let mut explicit = assign_strategy("explicit");
+    assert!(explicit.validate().is_err());
+    assert_eq!(db_ops::find_best_agent(&conn, None, &explicit), None);
+    explicit.agent_id = Some(agents[0].id.clone());
+    assert!(explicit.validate().is_ok());
+    assert_eq!(
+        db_ops::find_best_agent(&conn, None, &explicit).unwrap(),
+        agents[0].id
+    );
+
+    // Unknown names pick no one rather than falling back to capability
+    let unknown = assign_strategy("telepathy");
+    assert!(unknown.validate().is_err());
+    assert_eq!(db_ops::find_best_agent(&conn, None, &unknown), None);
+}
+
+#[tokio::test]
+async fn test_trigger_rejects_unknown_assign_strategy() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("trigger-strategy").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    for assign_to in [
+        json!({ "strategy": "telepathy" }),
+        json!({ "strategy": "explicit" }),
+    ] {
+        let resp = s
+            .client()
+            .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+            .header("Authorization", s.auth_header())
+            .json(&json!({
+                "name": "Bad strategy",
+                "action_type": "create_task",
+                "action_config": { "title": "Work", "assign_to": assign_to }
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 422, "{}", assign_to);
+    }
+
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "Round robin",
+            "action_type": "create_task",
+            "action_config": {
+                "title": "Work",
+                "assign_to": { "strategy": "round_robin" }
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    let trigger_id = created["trigger"]["id"].as_str().unwrap();
+    let secret = created["secret"].as_str().unwrap();
+
+    // Bring the only agent online so the trigger has someone to assign
+    s.client()
+        .post(format!("{}/api/agents/heartbeat", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let fired: Value = s
+        .client()
+        .post(format!(
+            "{}/api/webhooks/trigger/{}",
+            s.base_url, trigger_id
+        ))
+        .header("X-Webhook-Secret", secret)
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let task: Value = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}",
+            s.base_url,
+            fired["task_id"].as_str().unwrap()
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(task["assignee_id"].as_str(), Some(s.agent_id()));
+}
+
+// ===== Agent quotas =====
+
+#[tokio::test]
+async fn test_agent_daily_quota_enforced_at_claim_and_assign() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("quota").await;
+    let pid = proj["id"].as_str().unwrap();
+    let client = s.client();
+    let lead = orchestrator_auth(&s, "lead");
+
+    let resp = client
+        .patch(format!("{}/api/agents/{}", s.base_url, s.agent_id()))
+        .header("Authorization", &lead)
+        .json(&json!({ "daily_task_quota": 2, "max_concurrent_tasks": 10 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let agent: Value = resp.json().await.unwrap();
+    assert_eq!(agent["daily_task_quota"], 2);
+    assert!(agent["weekly_task_quota"].is_null());
+
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let t = s.create_ready_task(pid, &format!("quota task {i}")).await;
+        ids.push(t["id"].as_str().unwrap().to_string());
+    }
+
+    for id in &ids[..2] {
+        let resp = client
+            .post(format!("{}/api/tasks/{}/claim", s.base_url, id))
+            .header("Authorization", s.auth_header())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    // Third claim exceeds the daily quota
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, ids[2]))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("daily quota"));
+
+    // Assignment counts against the same quota
+    let resp = client
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, ids[2]))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Usage is visible in the inbox capacity block
+    let inbox: Value = client
+        .get(format!("{}/api/agents/me/inbox", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(inbox["capacity"]["daily_task_quota"], 2);
+    assert_eq!(inbox["capacity"]["tasks_today"], 2);
+    assert_eq!(inbox["capacity"]["tasks_this_week"], 2);
+    assert_eq!(inbox["capacity"]["has_capacity"], false);
+    assert!(inbox["summary"]
+        .as_str()
+        .unwrap()
+        .contains("Daily quota: 2/2"));
+
+    // Re-claiming a released task does not count twice
+    let resp = client
+        .post(format!("{}/api/tasks/{}/release", s.base_url, ids[0]))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, ids[0]))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // Clearing the quota lifts the limit
+    let resp = client
+        .patch(format!("{}/api/agents/{}", s.base_url, s.agent_id()))
+        .header("Authorization", &lead)
+        .json(&json!({ "daily_task_quota": 0 }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.json::<Value>().await.unwrap()["daily_task_quota"].is_null());
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, ids[2]))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_agent_cannot_change_own_quota() {
+    let s = TestServer::start().await;
+    let (peer_id, peer_auth) = create_api_agent(&s, "peer").await;
+    for (url, auth) in [
+        (format!("{}/api/agents/me", s.base_url), s.auth_header()),
+        (
+            format!("{}/api/agents/{}", s.base_url, s.agent_id()),
+            s.auth_header(),
+        ),
+        // Nor can a plain agent set them for another
+        (
+            format!("{}/api/agents/{}", s.base_url, s.agent_id()),
+            peer_auth,
+        ),
+        (
+            format!("{}/api/agents/{}", s.base_url, peer_id),
+            s.auth_header(),
+        ),
+    ] {
+        let resp = s
+            .client()
+            .patch(&url)
+            .header("Authorization", auth)
+            .json(&json!({ "weekly_task_quota": 1000 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 403, "PATCH {url}");
+    }
+    let agent: Value = s
+        .client()
+        .get(format!("{}/api/agents/{}", s.base_url, s.agent_id()))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(agent["weekly_task_quota"].is_null());
+}
+
+#[tokio::test]
+async fn test_quota_exhausted_agents_skipped_by_auto_assignment() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("quota_rr.db").to_str().unwrap());
+    let agents = strategy_pool(&conn, &[("q-a", "mid"), ("q-b", "mid")]);
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "Quota Project".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &agents[0].id,
+    );
+    let mut quota = opengate_models::UpdateAgent {
+        description: None,
+        skills: None,
+        max_concurrent_tasks: None,
+        webhook_url: None,
+        webhook_events: None,
+        webhook_template: None,
+        config: None,
+        model: None,
+        provider: None,
+        cost_tier: None,
+        capabilities: None,
+        seniority: None,
+        role: None,
+        stale_timeout: None,
+        tags: None,
+        daily_task_quota: None,
+        weekly_task_quota: Some(1),
+        notification_preferences: None,
+    };
+    db_ops::update_agent(&conn, &agents[0].id, &quota).unwrap();
+    claim_new_task(&conn, &project.id, &agents[0]);
+
+    for _ in 0..4 {
+        assert_eq!(
+            db_ops::find_best_agent(&conn, None, &assign_strategy("round_robin")).unwrap(),
+            agents[1].id
+        );
+    }
+
+    quota.weekly_task_quota = Some(0);
+    db_ops::update_agent(&conn, &agents[0].id, &quota).unwrap();
+    assert_eq!(
+        db_ops::find_best_agent(&conn, None, &assign_strategy("round_robin")).unwrap(),
+        agents[0].id
+    );
+}
+
+#[tokio::test]
+async fn test_agents_cannot_grant_roles() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let (peer_id, _) = create_api_agent(&s, "peer").await;
+    let lead = orchestrator_auth(&s, "lead");
+
+    for (url, auth) in [
+        (format!("{}/api/agents/me", s.base_url), s.auth_header()),
+        (
+            format!("{}/api/agents/{}", s.base_url, s.agent_id()),
+            s.auth_header(),
+        ),
+        (
+            format!("{}/api/agents/{}", s.base_url, peer_id),
+            s.auth_header(),
+        ),
+        (
+            format!("{}/api/agents/{}", s.base_url, peer_id),
+            lead.clone(),
+        ),
+    ] {
+        let resp = client
+            .patch(&url)
+            .header("Authorization", auth)
+            .json(&json!({ "role": "orchestrator" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 403, "PATCH {url}");
+    }
+    let resp = client
+        .post(format!("{}/api/agents", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "name": "sock-puppet", "role": "orchestrator" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = client
+        .post(format!("{}/api/agents", s.base_url))
+        .header("Authorization", &lead)
+        .json(&json!({ "name": "deputy", "role": "orchestrator" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let agents: Vec<Value> = client
+        .get(format!("{}/api/agents", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    for agent in &agents {
+        let expected = if ["lead", "deputy"].contains(&agent["name"].as_str().unwrap()) {
+            "orchestrator"
+        } else {
+            "executor"
+        };
+        assert_eq!(agent["role"], expected, "{}", agent["name"]);
+    }
+}
+
+// ===== Delegated actions =====
+
+#[tokio::test]
+async fn test_orchestrator_acts_on_behalf_of_worker() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let proj = s.create_project("delegation").await;
+    let pid = proj["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Headless work").await;
+    let tid = task["id"].as_str().unwrap();
+
+    let orch = s.create_orchestrator("orchestrator", None);
+    let orch_id = orch["agent"]["id"].as_str().unwrap();
+    let orch_auth = format!("Bearer {}", orch["api_key"].as_str().unwrap());
+    let worker: Value = client
+        .post(format!("{}/api/agents", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "name": "headless-worker" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let worker_id = worker["agent"]["id"].as_str().unwrap();
+
+    // Executors cannot delegate
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, tid))
+        .header("Authorization", s.auth_header())
+        .header("X-On-Behalf-Of", worker_id)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // Unknown target agent
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, tid))
+        .header("Authorization", &orch_auth)
+        .header("X-On-Behalf-Of", "no-such-agent")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // Claim as the worker
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, tid))
+        .header("Authorization", &orch_auth)
+        .header("X-On-Behalf-Of", worker_id)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let claimed: Value = resp.json().await.unwrap();
+    assert_eq!(claimed["assignee_id"].as_str(), Some(worker_id));
+    let audit = claimed["activities"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["activity_type"] == "delegation")
+        .expect("claim should record a delegation activity");
+    assert_eq!(audit["author_id"].as_str(), Some(worker_id));
+    assert_eq!(audit["metadata"]["action"], "claim");
+    assert_eq!(
+        audit["metadata"]["delegation"]["delegated_by"].as_str(),
+        Some(orch_id)
+    );
+
+    // Post activity as the worker
+    let resp = client
+        .post(format!("{}/api/tasks/{}/activity", s.base_url, tid))
+        .header("Authorization", &orch_auth)
+        .header("X-On-Behalf-Of", worker_id)
+        .json(&json!({ "content": "Progress from worker", "metadata": { "step": 1 } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let activity: Value = resp.json().await.unwrap();
+    assert_eq!(activity["author_id"].as_str(), Some(worker_id));
+    assert_eq!(activity["metadata"]["step"], 1);
+    assert_eq!(
+        activity["metadata"]["delegation"]["delegated_by"].as_str(),
+        Some(orch_id)
+    );
+    assert_eq!(
+        activity["metadata"]["delegation"]["on_behalf_of"].as_str(),
+        Some(worker_id)
+    );
+
+    // Complete as the worker
+    let resp = client
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, tid))
+        .header("Authorization", &orch_auth)
+        .header("X-On-Behalf-Of", worker_id)
+        .json(&json!({ "summary": "Done by worker" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let activities: Vec<Value> = client
+        .get(format!("{}/api/tasks/{}/activity", s.base_url, tid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let done = activities
+        .iter()
+        .find(|a| a["content"] == "Done by worker")
+        .unwrap();
+    assert_eq!(done["author_id"].as_str(), Some(worker_id));
+    assert_eq!(
+        done["metadata"]["delegation"]["delegated_by"].as_str(),
+        Some(orch_id)
+    );
+}
+
+#[tokio::test]
+async fn test_question_rerouted_when_target_goes_quiet() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("reroute.db").to_str().unwrap());
+    let new_agent = |agent: CreateAgent| {
+        let (agent, _) = db_ops::create_agent(&conn, &agent);
+        db_ops::update_heartbeat(&conn, &agent.id);
+        agent
+    };
+    let sleeper =
+        new_agent(CreateAgent::new("sleeper").with_capabilities(vec!["postgres".to_string()]));
+    let backup =
+        new_agent(CreateAgent::new("backup").with_capabilities(vec!["postgres".to_string()]));
+    let orchestrator = new_agent(CreateAgent::new("orchestrator").with_role("orchestrator"));
+    let project = db_ops::create_project(
+        &conn,
+        None,
+        &opengate_models::CreateProject {
+            name: "Reroute".to_string(),
+            description: None,
+            repo_url: None,
+            default_branch: None,
+            join_mode: None,
+            cta_enabled: None,
+            is_public: None,
+        },
+        &orchestrator.id,
+    );
+    let task = db_ops::create_task(
+        &conn,
+        None,
+        &project.id,
+        &opengate_models::CreateTask {
+            title: "Migrate schema".to_string(),
+            description: None,
+            priority: None,
+            tags: None,
+            context: None,
+            output: None,
+            due_date: None,
+            assignee_type: None,
+            assignee_id: None,
+            scheduled_at: None,
+            recurrence_rule: None,
+        },
+        &orchestrator.id,
+    );
+    let question = db_ops::create_question(
+        &conn,
+        &task.project_id,
+        Some(&task.id),
+        &opengate_models::CreateQuestion {
+            question: "Is the index concurrent-safe?".to_string(),
+            question_type: None,
+            context: None,
+            target_type: Some("agent".to_string()),
+            target_id: Some(sleeper.id.clone()),
+            required_capability: Some("postgres".to_string()),
+            blocking: None,
+            priority: None,
+        },
+        "agent",
+        &orchestrator.id,
+    );
+    assert_eq!(question.routing_history.len(), 1);
+    assert_eq!(question.routing_history[0].reason, "asked");
+
+    // A freshly targeted question stays put
+    assert!(db_ops::reroute_inactive_questions(&conn, 30).0.is_empty());
+    let go_quiet = |agent_id: &str| {
+        conn.execute(
+            "UPDATE agents SET last_seen_at = NULL WHERE id = ?1",
+            rusqlite::params![agent_id],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE task_questions SET targeted_at = '2020-01-01T00:00:00+00:00' WHERE id = ?1",
+            rusqlite::params![question.id],
+        )
+        .unwrap();
+    };
+    go_quiet(&sleeper.id);
+
+    let (rerouted, _) = db_ops::reroute_inactive_questions(&conn, 30);
+    assert_eq!(rerouted.len(), 1);
+    assert_eq!(rerouted[0].from_agent_id, sleeper.id);
+    assert_eq!(rerouted[0].to_agent_id.as_deref(), Some(backup.id.as_str()));
+    let q = db_ops::get_question(&conn, &question.id).unwrap();
+    assert_eq!(q.target_id.as_deref(), Some(backup.id.as_str()));
+    let reasons: Vec<&str> = q
+        .routing_history
+        .iter()
+        .map(|r| r.reason.as_str())
+        .collect();
+    assert_eq!(reasons, vec!["asked", "target_inactive"]);
+    assert!(db_ops::list_notifications(&conn, &backup.id, None)
+        .iter()
+        .any(|n| n.event_type == "task.question_rerouted"));
+    assert!(db_ops::reroute_inactive_questions(&conn, 30).0.is_empty());
+
+    // The backup goes quiet too and the only other match already had it: unrouted
+    go_quiet(&backup.id);
+    let (rerouted, _) = db_ops::reroute_inactive_questions(&conn, 30);
+    assert_eq!(rerouted.len(), 1);
+    assert!(rerouted[0].to_agent_id.is_none());
+    let q = db_ops::get_question(&conn, &question.id).unwrap();
+    assert!(q.target_id.is_none());
+    assert_eq!(q.routing_history.last().unwrap().reason, "unrouted");
+    assert!(db_ops::list_notifications(&conn, &orchestrator.id, None)
+        .iter()
+        .any(|n| n.event_type == "task.question_unrouted"));
+}
+
+// ===== Agent offboarding =====
+
+/// Create an agent over the API, returning (id, "Bearer <key>").
+async fn create_api_agent(s: &TestServer, name: &str) -> (String, String) {
+    let created: Value = s
+        .client()
+        .post(format!("{}/api/agents", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "name": name }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    (
+        created["agent"]["id"].as_str().unwrap().to_string(),
+        format!("Bearer {}", created["api_key"].as_str().unwrap()),
+    )
+}
+
+/// "Bearer <key>" of a new orchestrator.
+fn orchestrator_auth(s: &TestServer, name: &str) -> String {
+    let created = s.create_orchestrator(name, None);
+    format!("Bearer {}", created["api_key"].as_str().unwrap())
+}
+
+/// Give `worker` one in-progress task, one assigned todo task, one review to do and one
+/// question to answer. Returns (in_progress_id, todo_id, reviewed_id, question_id).
+async fn seed_offboarding_work(
+    s: &TestServer,
+    pid: &str,
+    worker_id: &str,
+    worker_auth: &str,
+) -> (String, String, String, String) {
+    let client = s.client();
+    let t1 = s.create_ready_task(pid, "in flight").await;
+    let t1 = t1["id"].as_str().unwrap().to_string();
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, t1))
+        .header("Authorization", worker_auth)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let t2 = s.create_ready_task(pid, "queued").await;
+    let t2 = t2["id"].as_str().unwrap().to_string();
+    let resp = client
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, t2))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": worker_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let t3 = s.create_task(pid, "needs review").await;
+    let t3 = t3["id"].as_str().unwrap().to_string();
+    let resp = client
+        .patch(format!("{}/api/tasks/{}", s.base_url, t3))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "reviewer_type": "agent", "reviewer_id": worker_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let q: Value = client
+        .post(format!("{}/api/tasks/{}/questions", s.base_url, t3))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "question": "Which schema?",
+            "target_type": "agent",
+            "target_id": worker_id,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    (t1, t2, t3, q["id"].as_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn test_offboard_agent_releases_work() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("offboard-release").await;
+    let pid = proj["id"].as_str().unwrap();
+    let (worker_id, worker_auth) = create_api_agent(&s, "leaving").await;
+    let lead = orchestrator_auth(&s, "lead");
+    let (t1, t2, t3, qid) = seed_offboarding_work(&s, pid, &worker_id, &worker_auth).await;
+
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, worker_id))
+        .header("Authorization", &lead)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let result: Value = resp.json().await.unwrap();
+    assert_eq!(result["released_tasks"], json!([t1.clone(), t2.clone()]));
+    assert_eq!(result["cleared_reviews"], json!([t3.clone()]));
+    assert_eq!(result["unrouted_questions"], json!([qid.clone()]));
+    assert!(result["reassigned_tasks"].as_array().unwrap().is_empty());
+
+    for id in [&t1, &t2] {
+        let task = s.get_task(id).await;
+        assert_eq!(task["status"], "todo");
+        assert!(task["assignee_id"].is_null());
+    }
+    assert!(s.get_task(&t3).await["reviewer_id"].is_null());
+
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/{}", s.base_url, worker_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_offboard_agent_reassigns_work() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("offboard-reassign").await;
+    let pid = proj["id"].as_str().unwrap();
+    let (worker_id, worker_auth) = create_api_agent(&s, "leaving").await;
+    let lead = orchestrator_auth(&s, "lead");
+    let (backup_id, _) = create_api_agent(&s, "backup").await;
+    let (t1, t2, t3, qid) = seed_offboarding_work(&s, pid, &worker_id, &worker_auth).await;
+    let client = s.client();
+
+    // Invalid requests leave the agent in place
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, worker_id))
+        .header("Authorization", &lead)
+        .json(&json!({ "reassign_to": { "strategy": "telepathy" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, worker_id))
+        .header("Authorization", &lead)
+        .json(&json!({ "reassign_to": { "strategy": "capability", "agent_id": worker_id } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = client
+        .post(format!(
+            "{}/api/agents/{}/offboard",
+            s.base_url, "no-such-agent"
+        ))
+        .header("Authorization", &lead)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, worker_id))
+        .header("Authorization", &lead)
+        .json(&json!({ "reassign_to": { "strategy": "capability", "agent_id": backup_id } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let result: Value = resp.json().await.unwrap();
+    assert_eq!(result["reassigned_tasks"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        result["reassigned_reviews"][0]["id"].as_str(),
+        Some(t3.as_str())
+    );
+    assert_eq!(
+        result["rerouted_questions"][0]["id"].as_str(),
+        Some(qid.as_str())
+    );
+    assert!(result["released_tasks"].as_array().unwrap().is_empty());
+
+    // In-flight work restarts with the new owner
+    let task = s.get_task(&t1).await;
+    assert_eq!(task["assignee_id"].as_str(), Some(backup_id.as_str()));
+    assert_eq!(task["status"], "todo");
+    assert_eq!(
+        s.get_task(&t2).await["assignee_id"].as_str(),
+        Some(backup_id.as_str())
+    );
+    assert_eq!(
+        s.get_task(&t3).await["reviewer_id"].as_str(),
+        Some(backup_id.as_str())
+    );
+
+    let questions: Vec<Value> = client
+        .get(format!("{}/api/tasks/{}/questions", s.base_url, t3))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(questions[0]["target_id"].as_str(), Some(backup_id.as_str()));
+}
+
+#[tokio::test]
+async fn test_offboard_agent_permissions_and_tenants() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let (worker_id, worker_auth) = create_api_agent(&s, "leaving").await;
+    let (peer_id, peer_auth) = create_api_agent(&s, "peer").await;
+
+    // A plain agent cannot offboard another
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, worker_id))
+        .header("Authorization", &peer_auth)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, worker_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    // Tenants can neither offboard nor hand work to each other's agents
+    let register = |name: &'static str, owner: &'static str| {
+        let client = client.clone();
+        let url = format!("{}/api/agents/register", s.base_url);
+        async move {
+            let created: Value = client
+                .post(url)
+                .json(&json!({"name": name, "setup_token": "test-setup-token", "owner_id": owner}))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            (
+                created["agent"]["id"].as_str().unwrap().to_string(),
+                format!("Bearer {}", created["api_key"].as_str().unwrap()),
+            )
+        }
+    };
+    let (acme_id, acme_auth) = register("acme-worker", "acme").await;
+    let (globex_id, _) = register("globex-worker", "globex").await;
+    let globex_lead = s.create_orchestrator("globex-lead", Some("globex"));
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, acme_id))
+        .header(
+            "Authorization",
+            format!("Bearer {}", globex_lead["api_key"].as_str().unwrap()),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, acme_id))
+        .header("Authorization", &acme_auth)
+        .json(&json!({ "reassign_to": { "strategy": "capability", "agent_id": globex_id } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let resp = client
+        .get(format!("{}/api/agents/{}", s.base_url, acme_id))
+        .header("Authorization", &acme_auth)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // An agent may offboard itself
+    let resp = client
+        .post(format!("{}/api/agents/{}/offboard", s.base_url, worker_id))
+        .header("Authorization", &worker_auth)
+        .json(&json!({ "reassign_to": { "strategy": "capability", "agent_id": peer_id } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+// ===== Skill inference =====
+
+#[tokio::test]
+async fn test_suggested_skills_from_completed_tasks() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let proj = s.create_project("skills").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let create = |title: String, tags: Value| {
+        let client = client.clone();
+        let url = format!("{}/api/projects/{}/tasks", s.base_url, pid);
+        let auth = s.auth_header();
+        async move {
+            let t: Value = client
+                .post(url)
+                .header("Authorization", auth)
+                .json(&json!({ "title": title, "tags": tags }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            t["id"].as_str().unwrap().to_string()
+        }
+    };
+
+    for i in 0..3 {
+        let id = create(
+            format!("infra {i}"),
+            json!(["rust", "terraform", "infra:aws"]),
+        )
+        .await;
+        s.finish_task(&id).await;
+    }
+    let id = create("script".to_string(), json!(["python"])).await;
+    s.finish_task(&id).await;
+    // Unfinished work does not count
+    create("pending".to_string(), json!(["terraform", "k8s"])).await;
+
+    let url = format!(
+        "{}/api/agents/{}/suggested-skills",
+        s.base_url,
+        s.agent_id()
+    );
+    let suggested: Value = client
+        .get(&url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(suggested["completed_tasks"], 4);
+    // "rust" is already a skill; "python" is below the default threshold
+    assert_eq!(
+        suggested["skills"],
+        json!([{ "name": "terraform", "completed_tasks": 3 }])
+    );
+    assert_eq!(
+        suggested["capabilities"],
+        json!([{ "name": "infra:aws", "completed_tasks": 3 }])
+    );
+
+    let low: Value = client
+        .get(format!("{url}?min_tasks=1"))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let names: Vec<&str> = low["skills"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["terraform", "python"]);
+
+    let resp = client
+        .post(format!("{url}/apply"))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let agent: Value = resp.json().await.unwrap();
+    assert_eq!(agent["skills"], json!(["rust", "testing", "terraform"]));
+    assert_eq!(agent["capabilities"], json!(["infra:aws"]));
+
+    // Nothing left to suggest once applied
+    let after: Value = client
+        .get(&url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(after["skills"].as_array().unwrap().is_empty());
+    assert!(after["capabilities"].as_array().unwrap().is_empty());
+
+    let resp = client
+        .get(format!(
+            "{}/api/agents/no-such-agent/suggested-skills",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+// ===== Agent presence =====
+
+#[tokio::test]
+async fn test_presence_sweep_reports_offline_and_back_online() {
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("presence.db").to_str().unwrap());
+    let agents = strategy_pool(
+        &conn,
+        &[("flaky", "mid"), ("watcher", "mid"), ("bystander", "mid")],
+    );
+    let (flaky, watcher, bystander) = (&agents[0], &agents[1], &agents[2]);
+    let set_last_seen = |id: &str, minutes_ago: i64| {
+        let ts = (chrono::Utc::now() - chrono::Duration::minutes(minutes_ago)).to_rfc3339();
+        conn.execute(
+            "UPDATE agents SET last_seen_at = ?1 WHERE id = ?2",
+            rusqlite::params![ts, id],
+        )
+        .unwrap();
+    };
+
+    // Only the watcher opts in to presence notifications
+    let mut subscribe = opengate_models::UpdateAgent {
+        description: None,
+        skills: None,
+        max_concurrent_tasks: None,
+        webhook_url: None,
+        webhook_events: Some(vec![
+            "agent.went_offline".to_string(),
+            "agent.back_online".to_string(),
+        ]),
+        webhook_template: None,
+        config: None,
+        model: None,
+        provider: None,
+        cost_tier: None,
+        capabilities: None,
+        seniority: None,
+        role: None,
+        stale_timeout: None,
+        tags: None,
+        daily_task_quota: None,
+        weekly_task_quota: None,
+        notification_preferences: None,
+    };
+    db_ops::update_agent(&conn, &watcher.id, &subscribe).unwrap();
+    subscribe.webhook_events = Some(vec!["task.assigned".to_string()]);
+    db_ops::update_agent(&conn, &bystander.id, &subscribe).unwrap();
+    subscribe.webhook_events = None;
+    subscribe.stale_timeout = Some(30);
+    db_ops::update_agent(&conn, &flaky.id, &subscribe).unwrap();
+
+    // First sweep only records the baseline
+    assert!(db_ops::sweep_agent_presence(&conn).is_empty());
+    assert_eq!(
+        db_ops::get_agent(&conn, &flaky.id).unwrap().presence,
+        "online"
+    );
+
+    // Heartbeat ages through idle and stale without crossing the offline boundary
+    set_last_seen(&flaky.id, 10);
+    assert_eq!(
+        db_ops::get_agent(&conn, &flaky.id).unwrap().presence,
+        "idle"
+    );
+    set_last_seen(&flaky.id, 20);
+    let changes = db_ops::sweep_agent_presence(&conn);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(
+        (changes[0].from.as_str(), changes[0].to.as_str()),
+        ("online", "stale")
+    );
+    let flaky_now = db_ops::get_agent(&conn, &flaky.id).unwrap();
+    assert_eq!(flaky_now.presence, "stale");
+    assert_eq!(flaky_now.status, "available");
+    assert!(db_ops::list_notifications(&conn, &watcher.id, Some(true)).is_empty());
+
+    // Past its 30 min stale_timeout → offline, watcher is told
+    set_last_seen(&flaky.id, 45);
+    let changes = db_ops::sweep_agent_presence(&conn);
+    assert_eq!(changes[0].to, "offline");
+    assert_eq!(
+        db_ops::get_agent(&conn, &flaky.id).unwrap().status,
+        "offline"
+    );
+    let notes = db_ops::list_notifications(&conn, &watcher.id, Some(true));
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].event_type, "agent.went_offline");
+    assert!(db_ops::list_notifications(&conn, &bystander.id, Some(true)).is_empty());
+    assert!(
+        db_ops::sweep_agent_presence(&conn).is_empty(),
+        "no change, no event"
+    );
+
+    // Heartbeat brings it back
+    db_ops::update_heartbeat(&conn, &flaky.id);
+    let changes = db_ops::sweep_agent_presence(&conn);
+    assert_eq!(
+        (changes[0].from.as_str(), changes[0].to.as_str()),
+        ("offline", "online")
+    );
+    let notes = db_ops::list_notifications(&conn, &watcher.id, Some(true));
+    assert!(notes.iter().any(|n| n.event_type == "agent.back_online"));
+}
+
+// ===== Notification preferences =====
+
+#[tokio::test]
+async fn test_notification_preferences_filter_and_quiet() {
+    let s = TestServer::start().await;
+    let patch_me = |body: Value| {
+        s.client()
+            .patch(format!("{}/api/agents/me", s.base_url))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
+
+    let resp = patch_me(json!({
+        "notification_preferences": {"task.assigned": {"min_priority": "urgent"}}
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 422);
+
+    // Assignments only when critical, and never wake for them; everything else is muted
+    let resp = patch_me(json!({
+        "notification_preferences": {
+            "task.assigned": {"min_priority": "critical", "wake": false},
+            "*": {"in_app": false}
+        }
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+    let agent: Value = resp.json().await.unwrap();
+    assert_eq!(
+        agent["notification_preferences"]["task.assigned"]["min_priority"],
+        "critical"
+    );
+
+    // "high" is below the floor
+    let high_task = assign_task_to_self(&s, "prefs-high").await;
+    assert!(all_notifications(&s).await.is_empty());
+
+    let project = s.create_project("Prefs Critical").await;
+    let resp = s
+        .client()
+        .post(format!(
+            "{}/api/projects/{}/tasks",
+            s.base_url,
+            project["id"].as_str().unwrap()
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"title": "Pager duty", "priority": "critical"}))
+        .send()
+        .await
+        .unwrap();
+    let critical_id = resp.json::<Value>().await.unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, critical_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let notes = unread_notifications(&s).await;
+    assert_eq!(notes.len(), 1, "only the critical assignment: {:?}", notes);
+    assert_eq!(notes[0]["task_id"], critical_id.as_str());
+    assert_eq!(notes[0]["wake"], false);
+
+    // Muted by the "*" fallback
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, high_task))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(unread_notifications(&s).await.len(), 1);
+
+    // Clearing the map restores the defaults
+    let resp = patch_me(json!({"notification_preferences": {}}))
+        .await
+        .unwrap();
+    let agent: Value = resp.json().await.unwrap();
+    assert_eq!(agent["notification_preferences"], json!({}));
+    assign_task_to_self(&s, "prefs-default").await;
+    let notes = unread_notifications(&s).await;
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0]["wake"], true);
+}
+
+#[tokio::test]
+async fn test_notification_channel_matrix() {
+    let s = TestServer::start().await;
+    let (hook_url, received) = start_mock_webhook(200).await;
+    set_agent_webhook(&s, &hook_url, None).await;
+
+    // Assignments: inbox + email, but no webhook push
+    let resp = s
+        .client()
+        .patch(format!("{}/api/agents/me", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "notification_preferences": {"task.assigned": {"webhook": false, "email": true}}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let agent: Value = resp.json().await.unwrap();
+    assert_eq!(
+        agent["notification_preferences"]["task.assigned"],
+        json!({"in_app": true, "webhook": false, "wake": true, "email": true, "push": false})
+    );
+
+    assign_task_to_self(&s, "matrix-muted").await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let notes = unread_notifications(&s).await;
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0]["email"], true);
+    assert!(notes[0]["webhook_status"].is_null());
+    assert!(received.lock().await.is_empty());
+
+    // Built-in defaults push assignments to the webhook and don't email them
+    s.client()
+        .patch(format!("{}/api/agents/me", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"notification_preferences": {}}))
+        .send()
+        .await
+        .unwrap();
+    assign_task_to_self(&s, "matrix-default").await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let reqs = received.lock().await;
+    // The task event and the notification
+    assert_eq!(reqs.len(), 2, "{:?}", reqs);
+    assert!(reqs.iter().any(|r| r["event_type"] == "task.assigned"));
+    drop(reqs);
+    let notes = all_notifications(&s).await;
+    assert!(notes.iter().any(|n| n["email"] == false));
+}
+
+/// Mock ntfy server: records (title, body) for every publish.
+async fn start_mock_ntfy() -> (String, Arc<tokio::sync::Mutex<Vec<(String, String)>>>) {
+    use axum::{
+        extract::{Query, State},
+        routing::post,
+        Router,
+    };
+
+    type Pushes = Arc<tokio::sync::Mutex<Vec<(String, String)>>>;
+    let pushes: Pushes = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+    let app = Router::new()
+        .route(
+            "/gate-alerts",
+            post(
+                |State(pushes): State<Pushes>,
+                 Query(query): Query<HashMap<String, String>>,
+                 body: String| async move {
+                    let title = query.get("title").cloned().unwrap_or_default();
+                    pushes.lock().await.push((title, body));
+                    "{}"
+                },
+            ),
+        )
+        .with_state(pushes.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/gate-alerts", addr), pushes)
+}
+
+#[tokio::test]
+async fn test_push_target_buzzes_for_blocked_tasks() {
+    let s = TestServer::start().await;
+    let (topic_url, pushes) = start_mock_ntfy().await;
+    let push_url = format!("{}/api/agents/{}/push", s.base_url, s.agent_id());
+
+    // Pushover needs its app token
+    let resp = s
+        .client()
+        .put(&push_url)
+        .header("Authorization", s.auth_header())
+        .json(&json!({"provider": "pushover", "target": "u123"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
+
+    let resp = s
+        .client()
+        .put(&push_url)
+        .header("Authorization", s.auth_header())
+        .json(&json!({"provider": "ntfy", "target": topic_url, "token": "tk_secret"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let target: Value = resp.json().await.unwrap();
+    assert_eq!(target["provider"], "ntfy");
+    assert!(target.get("token").is_none());
+
+    // Assignments don't push by default
+    let task_id = assign_task_to_self(&s, "push").await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+    assert!(pushes.lock().await.is_empty());
+
+    // Blocked tasks do
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/block", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"reason": "waiting on credentials"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let got = pushes.lock().await.clone();
+    assert_eq!(got.len(), 1, "{:?}", got);
+    assert!(got[0].0.contains("Blocked"), "{:?}", got);
+    assert_eq!(got[0].1, "Task is blocked and needs intervention.");
+
+    // Users have their own target; agents can't set it
+    let resp = s
+        .client()
+        .put(format!("{}/api/users/me/push", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"provider": "ntfy", "target": topic_url}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = s
+        .client()
+        .delete(&push_url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+}
+
+// ===== Scoped API keys =====
+
+/// Helper: mint an additional key for the test agent, returning (key_id, secret).
+async fn mint_key(s: &TestServer, scope: Value) -> (String, String) {
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/{}/keys", s.base_url, s.agent_id()))
+        .header("Authorization", s.auth_header())
+        .json(&scope)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: Value = resp.json().await.unwrap();
+    (
+        body["id"].as_str().unwrap().to_string(),
+        body["api_key"].as_str().unwrap().to_string(),
+    )
+}
+
+#[tokio::test]
+async fn test_scoped_api_keys() {
+    let s = TestServer::start().await;
+    let ci_project = s.create_project("CI Visible").await;
+    let ci_pid = ci_project["id"].as_str().unwrap();
+    let other = s.create_project("CI Hidden").await;
+    let other_pid = other["id"].as_str().unwrap();
+    let ci_task = s.create_task(ci_pid, "Build").await;
+    let ci_tid = ci_task["id"].as_str().unwrap();
+    let other_task = s.create_task(other_pid, "Secret").await;
+    let other_tid = other_task["id"].as_str().unwrap();
+
+    let (ci_key_id, ci_key) = mint_key(
+        &s,
+        json!({"name": "ci", "read_only": true, "project_id": ci_pid}),
+    )
+    .await;
+    let as_ci = |method: reqwest::Method, path: String| {
+        s.client()
+            .request(method, format!("{}{}", s.base_url, path))
+            .header("Authorization", format!("Bearer {}", ci_key))
+    };
+
+    // The key acts as the agent, within its scope
+    let me: Value = as_ci(reqwest::Method::GET, "/api/auth/me".into())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(me["id"], s.agent_id());
+    for path in [
+        format!("/api/projects/{}/tasks", ci_pid),
+        format!("/api/tasks/{}", ci_tid),
+    ] {
+        let resp = as_ci(reqwest::Method::GET, path.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200, "{}", path);
+    }
+    for path in [
+        format!("/api/projects/{}", other_pid),
+        format!("/api/tasks/{}", other_tid),
+        "/api/tasks".to_string(),
+    ] {
+        let resp = as_ci(reqwest::Method::GET, path.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 403, "{}", path);
+    }
+    let resp = as_ci(
+        reqwest::Method::POST,
+        format!("/api/tasks/{}/claim", ci_tid),
+    )
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 403, "read-only key cannot claim");
+
+    // no_admin: regular work is fine, agent management is not
+    let (_, worker_key) = mint_key(&s, json!({"no_admin": true})).await;
+    let resp = s
+        .client()
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, other_pid))
+        .header("Authorization", format!("Bearer {}", worker_key))
+        .json(&json!({"title": "Allowed"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/{}/keys", s.base_url, s.agent_id()))
+        .header("Authorization", format!("Bearer {}", worker_key))
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    for (method, path) in [
+        (
+            reqwest::Method::PUT,
+            format!("/api/projects/{}/integrations/slack", other_pid),
+        ),
+        (
+            reqwest::Method::DELETE,
+            format!("/api/projects/{}/integrations/github", other_pid),
+        ),
+        (
+            reqwest::Method::POST,
+            format!("/api/projects/{}/integrations/gitlab/import", other_pid),
+        ),
+        (
+            reqwest::Method::POST,
+            format!("/api/projects/{}/import/jira", other_pid),
+        ),
+        (
+            reqwest::Method::POST,
+            format!("/api/projects/{}/import/linear", other_pid),
+        ),
+        (reqwest::Method::POST, "/api/import/trello".to_string()),
+    ] {
+        let resp = s
+            .client()
+            .request(method, format!("{}{}", s.base_url, path))
+            .header("Authorization", format!("Bearer {}", worker_key))
+            .json(&json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 403, "{}", path);
+    }
+    let resp = s
+        .client()
+        .get(format!(
+            "{}/api/projects/{}/integrations/slack",
+            s.base_url, other_pid
+        ))
+        .header("Authorization", format!("Bearer {}", worker_key))
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(resp.status(), 403);
+
+    // Listing never exposes secrets; revoking turns the key into an anonymous caller
+    let keys: Value = s
+        .client()
+        .get(format!("{}/api/agents/{}/keys", s.base_url, s.agent_id()))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(keys.as_array().unwrap().len(), 2);
+    assert!(keys[0].get("api_key").is_none());
+    assert_eq!(keys[0]["project_id"], ci_pid);
+    assert!(keys[0]["last_used_at"].is_string());
+
+    let resp = s
+        .client()
+        .delete(format!(
+            "{}/api/agents/{}/keys/{}",
+            s.base_url,
+            s.agent_id(),
+            ci_key_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    let me: Value = as_ci(reqwest::Method::GET, "/api/auth/me".into())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(me["type"], "anonymous");
+}
+
+// ===== Dependency Enforcement =====
+
+#[tokio::test]
+async fn test_cannot_start_task_with_unmet_deps() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("dep-enforce-test").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let a = s.create_task(pid, "Task A (blocked)").await;
+    let b = s.create_task(pid, "Task B (blocker)").await;
+    let a_id = a["id"].as_str().unwrap();
+    let b_id = b["id"].as_str().unwrap();
+
+    // A depends on B
+    s.client()
+        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "depends_on": [b_id] }))
+        .send()
+        .await
+        .unwrap();
+
+    // Move A to todo first (backlog → todo is always allowed)
+    let r = s
+        .client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(r.status().is_success(), "backlog→todo should succeed");
+
+    // Try to move A to in_progress — should fail (B not done)
+    let resp = s
+        .client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "in_progress" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.status(),
+        409,
+        "Should reject with 409 Conflict when deps unmet"
+    );
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("dependencies not met"));
+}
+
+#[tokio::test]
+async fn test_can_start_task_once_deps_done() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("dep-enforce-start").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let a = s.create_task(pid, "Task A").await;
+    let b = s.create_task(pid, "Task B").await;
+    let a_id = a["id"].as_str().unwrap();
+    let b_id = b["id"].as_str().unwrap();
+
+    // A depends on B
+    s.client()
+        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "depends_on": [b_id] }))
+        .send()
+        .await
+        .unwrap();
+
+    // Complete B: backlog → todo → in_progress → done
+    for status in &["todo", "in_progress", "done"] {
+        let r = s
+            .client()
+            .patch(format!("{}/api/tasks/{}", s.base_url, b_id))
+            .header("Authorization", s.auth_header())
+            .json(&json!({ "status": status }))
+            .send()
+            .await
+            .unwrap();
+        assert!(
+            r.status().is_success(),
+            "B transition to {} failed: {}",
+            status,
+            r.status()
+        );
+    }
+
+    // Now A should be auto-unblocked to todo; can move to in_progress
+    let resp = s
+        .client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "in_progress" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.status(),
+        200,
+        "Should allow in_progress once dep is done"
+    );
+}
+
+#[tokio::test]
+async fn test_assign_with_pending_deps_adds_warning() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("assign-dep-warn").await;
+    let pid = proj["id"].as_str().unwrap();
+
+    let a = s.create_task(pid, "Task A").await;
+    let b = s.create_task(pid, "Task B (blocker)").await;
+    let a_id = a["id"].as_str().unwrap();
+    let b_id = b["id"].as_str().unwrap();
+
+    // A depends on B (not done)
+    s.client()
+        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "depends_on": [b_id] }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assign A to the test agent (which exists in the test server)
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.status(),
+        200,
+        "Assign should succeed even with unmet deps"
+    );
+
+    // Check activity log has warning
+    let activity_resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}/activity", s.base_url, a_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(activity_resp.status(), 200);
+    let activity: Value = activity_resp.json().await.unwrap();
+    let has_warning = activity
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|a| a["content"].as_str().unwrap_or("").contains("unmet dep"));
+    assert!(has_warning, "Activity should contain dep warning");
+}
+
+// --- Start Review tests ---
+
+// Test start-review: sets started_review_at, only reviewer allowed, must be in review status
+#[tokio::test]
+async fn test_start_review_sets_timestamp() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Test Project - Start Review").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Test Start Review").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Claim (todo -> in_progress)
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    // Move to review and set reviewer to our test agent
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "review", "reviewer_type": "agent", "reviewer_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+
+    // Verify started_review_at is null before start-review
+    let resp = s
+        .client()
+        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(
+        body["started_review_at"].is_null(),
+        "started_review_at should be null initially"
+    );
+
+    // Start review
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/start-review", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "review", "Status should remain review");
+    assert!(
+        body["started_review_at"].is_string(),
+        "started_review_at should be set"
+    );
+}
+
+// Test start-review: must be in review status
+#[tokio::test]
+async fn test_start_review_requires_review_status() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Test Project - Start Review Status").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Test Start Review Status").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Claim (todo -> in_progress)
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    // Try start-review on in_progress task (should fail)
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/start-review", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.status(),
+        400,
+        "Should reject start-review when not in review status"
+    );
+}
+
+// Test start-review: non-reviewer gets 403
+#[tokio::test]
+async fn test_start_review_non_reviewer_gets_403() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Test Project - Non Reviewer").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Test Non Reviewer").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Register a second agent to be the reviewer
+    let resp = s
+        .client()
+        .post(format!("{}/api/agents/register", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({
+            "name": "reviewer-agent",
+            "skills": ["rust"],
+            "setup_token": "test-setup-token"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let reviewer: Value = resp.json().await.unwrap();
+    let reviewer_id = reviewer["agent"]["id"].as_str().unwrap();
+
+    // Claim (backlog -> in_progress)
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    // Move to review with reviewer-agent as reviewer
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "review", "reviewer_type": "agent", "reviewer_id": reviewer_id }))
+        .send()
+        .await
+        .unwrap();
+
+    // Try start-review as test-agent (not the reviewer) — should get 403
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/start-review", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403, "Non-reviewer should get 403");
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("Only the assigned reviewer"));
+}
+
+// Test review_task_count appears in agent response
+#[tokio::test]
+async fn test_review_task_count_in_agent_response() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Test Project - Review Count").await;
+    let pid = project["id"].as_str().unwrap();
+
+    // Create a task, claim it, move to review with our agent as reviewer
+    let task = s.create_ready_task(pid, "Task for Review Count").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "review", "reviewer_type": "agent", "reviewer_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+
+    // Fetch the agent and check review_task_count
+    let resp = s
+        .client()
+        .get(format!("{}/api/agents/{}", s.base_url, s.agent_id()))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let agent: Value = resp.json().await.unwrap();
+    assert_eq!(
+        agent["review_task_count"], 1,
+        "Agent should have 1 review task"
+    );
+    // current_task_count should be 0 since task moved to review (not in_progress)
+    assert_eq!(
+        agent["current_task_count"], 0,
+        "current_task_count should be 0 (task is in review, not in_progress)"
+    );
+}
+
+// Pre-assigned task: assign then claim should transition to in_progress
+#[tokio::test]
+async fn test_claim_preassigned_task() {
+    let s = TestServer::start().await;
+
+    // Heartbeat so agent is online
+    s.client()
+        .post(format!("{}/api/agents/heartbeat", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    let project = s.create_project("Test Project - Pre-assigned Claim").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Test Pre-assigned Claim").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Step 1: Assign to agent (status stays "todo")
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "agent_id": s.agent_id() }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "todo", "assign should keep status as todo");
+    assert_eq!(body["assignee_id"].as_str().unwrap(), s.agent_id());
+
+    // Step 2: Agent claims the pre-assigned task → should transition to in_progress
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["status"], "in_progress",
+        "claim on pre-assigned task should transition to in_progress"
+    );
+
+    // Step 3: Claim again → idempotent, still in_progress
+    let resp = s
+        .client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["status"], "in_progress",
+        "second claim should be idempotent"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// WebSocket integration tests
+// ---------------------------------------------------------------------------
+
+// WS Auth: valid API key → auth_ok with identity
+#[tokio::test]
+async fn test_ws_auth_valid() {
+    let s = TestServer::start().await;
+    let (ws, _) = tokio_tungstenite::connect_async(&s.ws_url())
+        .await
+        .expect("WS connect failed");
+    let (mut sink, mut stream) = ws.split();
+
+    let auth_msg = json!({"type": "auth", "token": s.api_key}).to_string();
+    sink.send(WsMessage::Text(auth_msg.into())).await.unwrap();
+
+    let resp = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected auth_ok");
+    assert_eq!(resp["type"], "auth_ok");
+    assert_eq!(resp["identity"]["type"], "agent");
+    assert_eq!(resp["identity"]["id"], s.agent_id());
+    assert!(resp["identity"]["name"].is_string());
+}
+
+// WS Auth: invalid API key → auth_failed error
+#[tokio::test]
+async fn test_ws_auth_invalid() {
+    let s = TestServer::start().await;
+    let (ws, _) = tokio_tungstenite::connect_async(&s.ws_url())
+        .await
+        .expect("WS connect failed");
+    let (mut sink, mut stream) = ws.split();
+
+    let auth_msg = json!({"type": "auth", "token": "bad-key-12345"}).to_string();
+    sink.send(WsMessage::Text(auth_msg.into())).await.unwrap();
+
+    let resp = recv_json(&mut stream, 2000).await.expect("expected error");
+    assert_eq!(resp["type"], "error");
+    assert_eq!(resp["code"], "auth_failed");
+}
+
+// WS Auth: subscribe before auth → auth_required error
+#[tokio::test]
+async fn test_ws_auth_required() {
+    let s = TestServer::start().await;
+    let (ws, _) = tokio_tungstenite::connect_async(&s.ws_url())
+        .await
+        .expect("WS connect failed");
+    let (mut sink, mut stream) = ws.split();
+
+    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+
+    let resp = recv_json(&mut stream, 2000).await.expect("expected error");
+    assert_eq!(resp["type"], "error");
+    assert_eq!(resp["code"], "auth_required");
+}
+
+// WS Event roundtrip: subscribe task.*, create task via REST, receive event
+#[tokio::test]
+async fn test_ws_event_roundtrip() {
+    let s = TestServer::start().await;
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    // Subscribe to task.*
+    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let resp = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected subscribed");
+    assert_eq!(resp["type"], "subscribed");
+    assert_eq!(resp["id"], "sub-1");
+
+    // Create a task via REST → triggers task.created event
+    let project = s.create_project("WS Roundtrip").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "WS Test Task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Should receive the event
+    let event = recv_json(&mut stream, 2000).await.expect("expected event");
+    assert_eq!(event["type"], "event");
+    assert_eq!(event["sub"], "sub-1");
+    assert_eq!(event["event"], "task.created");
+    assert_eq!(event["data"]["id"].as_str().unwrap(), task_id);
+}
+
+// WS Multiple subscribers: two clients both receive the same event
+#[tokio::test]
+async fn test_ws_multiple_subscribers() {
+    let s = TestServer::start().await;
+
+    // Connect two WS clients
+    let (mut sink1, mut stream1) = ws_auth(&s.ws_url(), &s.api_key).await;
+    let (mut sink2, mut stream2) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    // Both subscribe to task.*
+    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
+    sink1
+        .send(WsMessage::Text(sub_msg.clone().into()))
+        .await
+        .unwrap();
+    let r1 = recv_json(&mut stream1, 2000).await.unwrap();
+    assert_eq!(r1["type"], "subscribed");
+
+    sink2.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let r2 = recv_json(&mut stream2, 2000).await.unwrap();
+    assert_eq!(r2["type"], "subscribed");
+
+    // Create task via REST
+    let project = s.create_project("WS Multi Sub").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Multi Sub Task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // Both clients should receive the event
+    let ev1 = recv_json(&mut stream1, 2000)
+        .await
+        .expect("client 1 should receive event");
+    assert_eq!(ev1["event"], "task.created");
+    assert_eq!(ev1["data"]["id"].as_str().unwrap(), task_id);
+
+    let ev2 = recv_json(&mut stream2, 2000)
+        .await
+        .expect("client 2 should receive event");
+    assert_eq!(ev2["event"], "task.created");
+    assert_eq!(ev2["data"]["id"].as_str().unwrap(), task_id);
+}
+
+// WS No subscribers: creating a task with no WS clients doesn't panic
+#[tokio::test]
+async fn test_ws_no_subscribers_no_panic() {
+    let s = TestServer::start().await;
+    // No WS clients — just create a task via REST
+    let project = s.create_project("No Subscribers").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "No Subscriber Task").await;
+    assert!(
+        task["id"].is_string(),
+        "REST should succeed with no WS listeners"
+    );
+}
+
+// WS Filter: agent_id "self" only receives events for own agent
+#[tokio::test]
+async fn test_ws_filter_agent_self() {
+    let s = TestServer::start().await;
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    // Subscribe with agent_id: "self" filter
+    let sub_msg = json!({
+        "type": "subscribe",
+        "events": ["task.*"],
+        "filter": {"agent_id": "self"}
+    })
+    .to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let resp = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(resp["type"], "subscribed");
+
+    // Create a task (task.created has no assignee → agent_id is None) — should NOT match
+    let project = s.create_project("Agent Filter").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Agent Filter Task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    // task.created has agent_id=None, won't match "self" filter
+    let no_event = recv_json(&mut stream, 500).await;
+    assert!(
+        no_event.is_none(),
+        "should not receive task.created with no assignee"
+    );
+
+    // Move to todo so it can be claimed
+    s.client()
+        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "todo" }))
+        .send()
+        .await
+        .unwrap();
+    // Drain any status-change events
+    recv_json(&mut stream, 300).await;
+
+    // Claim the task → assigns agent, emits task.claimed with agent_id = self
+    s.client()
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+
+    // Should receive the claimed event (agent_id matches self)
+    let event = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected claimed event");
+    assert_eq!(event["event"], "task.claimed");
+    assert_eq!(event["data"]["assignee_id"].as_str().unwrap(), s.agent_id());
+}
+
+// WS Filter: project_id only receives events for that project
+#[tokio::test]
+async fn test_ws_filter_project() {
+    let s = TestServer::start().await;
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    // Create two projects
+    let proj_a = s.create_project("Project A").await;
+    let pid_a = proj_a["id"].as_str().unwrap();
+    let proj_b = s.create_project("Project B").await;
+    let pid_b = proj_b["id"].as_str().unwrap();
+
+    // Subscribe with project_id filter for project A only
+    let sub_msg = json!({
+        "type": "subscribe",
+        "events": ["task.*"],
+        "filter": {"project_id": pid_a}
+    })
+    .to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let resp = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(resp["type"], "subscribed");
+
+    // Create task in project B → should NOT receive event
+    s.create_task(pid_b, "Task in B").await;
+    let no_event = recv_json(&mut stream, 500).await;
+    assert!(no_event.is_none(), "should not receive event for project B");
+
+    // Create task in project A → SHOULD receive event
+    let task_a = s.create_task(pid_a, "Task in A").await;
+    let event = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected event for project A");
+    assert_eq!(event["event"], "task.created");
+    assert_eq!(
+        event["data"]["id"].as_str().unwrap(),
+        task_a["id"].as_str().unwrap()
+    );
+}
+
+// WS Wildcard no match: subscribe task.*, trigger knowledge.updated → not received
+#[tokio::test]
+async fn test_ws_wildcard_no_match() {
+    let s = TestServer::start().await;
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    // Subscribe to task.* only
+    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let resp = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(resp["type"], "subscribed");
+
+    // Trigger a knowledge.updated event via REST
+    let project = s.create_project("Wildcard NoMatch").await;
+    let pid = project["id"].as_str().unwrap();
+    s.client()
+        .put(format!(
+            "{}/api/projects/{}/knowledge/test-key",
+            s.base_url, pid
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"title": "Test Knowledge", "body": "Some content"}))
+        .send()
+        .await
+        .unwrap();
+
+    // Should NOT receive the knowledge event on a task.* subscription
+    let no_event = recv_json(&mut stream, 500).await;
+    assert!(
+        no_event.is_none(),
+        "task.* should not match knowledge.updated"
+    );
+}
+
+// WS Exact match: subscribe task.created, receive only task.created
+#[tokio::test]
+async fn test_ws_exact_match() {
+    let s = TestServer::start().await;
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    // Subscribe to exact pattern task.created
+    let sub_msg = json!({"type": "subscribe", "events": ["task.created"]}).to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let resp = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(resp["type"], "subscribed");
+
+    // Create task → should receive task.created
+    let project = s.create_project("Exact Match").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Exact Match Task").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    let event = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected task.created");
+    assert_eq!(event["event"], "task.created");
+    assert_eq!(event["data"]["id"].as_str().unwrap(), task_id);
+}
+
+// WS Unsubscribe: receive event, unsubscribe, no longer receive
+#[tokio::test]
+async fn test_ws_unsubscribe() {
+    let s = TestServer::start().await;
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    // Subscribe
+    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let resp = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(resp["type"], "subscribed");
+    let sub_id = resp["id"].as_str().unwrap().to_string();
+
+    // Create task → should receive event
+    let project = s.create_project("Unsubscribe Test").await;
+    let pid = project["id"].as_str().unwrap();
+    s.create_task(pid, "Before Unsub").await;
+    let event = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected event before unsub");
+    assert_eq!(event["event"], "task.created");
+
+    // Unsubscribe
+    let unsub_msg = json!({"type": "unsubscribe", "id": sub_id}).to_string();
+    sink.send(WsMessage::Text(unsub_msg.into())).await.unwrap();
+    let resp = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(resp["type"], "unsubscribed");
+
+    // Create another task → should NOT receive event
+    s.create_task(pid, "After Unsub").await;
+    let no_event = recv_json(&mut stream, 500).await;
+    assert!(
+        no_event.is_none(),
+        "should not receive events after unsubscribe"
+    );
+}
+
+// WS resume: a subscribe with since_event_id replays the persisted events it missed,
+// then goes live
+#[tokio::test]
+async fn test_ws_resume_from_event_cursor() {
+    let s = TestServer::start().await;
+
+    // Note the cursor of the last event seen before disconnecting
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+    let sub_msg = json!({"type": "subscribe", "events": ["task.assigned"]}).to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    assert_eq!(
+        recv_json(&mut stream, 2000).await.unwrap()["type"],
+        "subscribed"
+    );
+    assign_task_to_self(&s, "resume-seen").await;
+    let seen = recv_json(&mut stream, 2000).await.expect("expected event");
+    let cursor = seen["event_id"]
+        .as_i64()
+        .expect("live events carry a cursor");
+    drop((sink, stream));
+
+    // Missed while offline
+    let missed_a = assign_task_to_self(&s, "resume-a").await;
+    let missed_b = assign_task_to_self(&s, "resume-b").await;
+
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+    let sub_msg = json!({
+        "type": "subscribe",
+        "events": ["task.assigned"],
+        "since_event_id": cursor,
+    })
+    .to_string();
+    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    assert_eq!(
+        recv_json(&mut stream, 2000).await.unwrap()["type"],
+        "subscribed"
+    );
+    for missed in [&missed_a, &missed_b] {
+        let event = recv_json(&mut stream, 2000).await.expect("expected replay");
+        assert_eq!(event["event"], "task.assigned");
+        assert_eq!(event["data"]["id"], missed.as_str());
+        assert!(event["event_id"].as_i64().unwrap() > cursor);
+    }
+    let done = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected replayed");
+    assert_eq!(done["type"], "replayed", "{done}");
+    assert_eq!(done["count"], 2);
+    assert_eq!(done["complete"], true);
+
+    // Then live delivery
+    let live = assign_task_to_self(&s, "resume-live").await;
+    let event = recv_json(&mut stream, 2000)
+        .await
+        .expect("expected live event");
+    assert_eq!(event["data"]["id"], live.as_str());
+    assert!(event["event_id"].as_i64().unwrap() > done["cursor"].as_i64().unwrap());
+}
+
+/// Helper: send a WS `call` and wait for its result.
+async fn ws_call(
+    sink: &mut WsSink,
+    stream: &mut WsStream,
+    id: i64,
+    method: &str,
+    params: Value,
+) -> Value {
+    let msg = json!({"type": "call", "id": id, "method": method, "params": params});
+    sink.send(WsMessage::Text(msg.to_string().into()))
+        .await
+        .unwrap();
+    let resp = recv_json(stream, 2000).await.expect("expected result");
+    assert_eq!(resp["type"], "result", "{resp}");
+    assert_eq!(resp["id"], id);
+    resp
+}
+
+// WS calls: REST handlers over the socket, answered by id
+#[tokio::test]
+async fn test_ws_command_calls() {
+    let s = TestServer::start().await;
+    let project = s.create_project("WS Calls").await;
+    let task = s
+        .create_task(project["id"].as_str().unwrap(), "Over the socket")
+        .await;
+    let task_id = task["id"].as_str().unwrap();
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+
+    let resp = ws_call(
+        &mut sink,
+        &mut stream,
+        1,
+        "update_task",
+        json!({"task_id": task_id, "status": "todo"}),
+    )
+    .await;
+    assert_eq!(resp["status"], 200, "{resp}");
+    assert_eq!(resp["result"]["status"], "todo");
+
+    let resp = ws_call(
+        &mut sink,
+        &mut stream,
+        2,
+        "claim_task",
+        json!({"task_id": task_id}),
+    )
+    .await;
+    assert_eq!(resp["result"]["assignee_id"], s.agent_id());
+    assert_eq!(resp["result"]["status"], "in_progress");
+
+    let resp = ws_call(
+        &mut sink,
+        &mut stream,
+        3,
+        "post_comment",
+        json!({"task_id": task_id, "content": "On it"}),
+    )
+    .await;
+    assert_eq!(resp["status"], 201);
+    assert_eq!(resp["result"]["content"], "On it");
+
+    // Handler errors come back with their status
+    let resp = ws_call(
+        &mut sink,
+        &mut stream,
+        4,
+        "get_task",
+        json!({"task_id": "nope"}),
+    )
+    .await;
+    assert_eq!(resp["status"], 404);
+    assert_eq!(resp["error"], "Task not found");
+    assert!(resp.get("result").is_none());
+
+    let resp = ws_call(&mut sink, &mut stream, 5, "claim_task", json!({})).await;
+    assert_eq!(resp["status"], 422);
+
+    let resp = ws_call(&mut sink, &mut stream, 6, "drop_tables", json!({})).await;
+    assert_eq!(resp["status"], 404);
+    assert!(resp["error"].as_str().unwrap().contains("claim_task"));
+
+    // Same state as over REST
+    let task = s.get_task(task_id).await;
+    assert_eq!(task["status"], "in_progress");
+}
+
+// WS: a `notifications` subscription delivers the unread backlog, then new notifications
+// live, and `ack` acknowledges them over the same socket
+#[tokio::test]
+async fn test_ws_notification_stream() {
+    let s = TestServer::start().await;
+    assign_task_to_self(&s, "ws-backlog").await;
+    let backlog = unread_notifications(&s).await;
+    assert_eq!(backlog.len(), 1);
+
+    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+    sink.send(WsMessage::Text(
+        json!({"type": "subscribe", "target": "notifications", "events": ["task.*"]})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let subscribed = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(subscribed["type"], "subscribed");
+    let sub = subscribed["id"].as_str().unwrap().to_string();
+
+    let first = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(first["type"], "notification");
+    assert_eq!(first["sub"], sub.as_str());
+    assert_eq!(first["notification"]["id"], backlog[0]["id"]);
+
+    // A new notification arrives without polling REST
+    assign_task_to_self(&s, "ws-live").await;
+    let live = recv_json(&mut stream, 3000).await.unwrap();
+    assert_eq!(live["type"], "notification", "{live}");
+    assert_eq!(live["notification"]["event_type"], "task.assigned");
+    assert_ne!(live["notification"]["id"], backlog[0]["id"]);
+
+    sink.send(WsMessage::Text(
+        json!({"type": "ack", "notification_id": backlog[0]["id"]})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let acked = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(acked["type"], "acked");
+    assert_eq!(acked["count"], 1);
+    assert_eq!(unread_notifications(&s).await.len(), 1);
+
+    sink.send(WsMessage::Text(
+        json!({"type": "ack", "notification_id": 999999})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let missing = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(missing["code"], "not_found");
+
+    sink.send(WsMessage::Text(
+        json!({"type": "ack", "all": true}).to_string().into(),
+    ))
+    .await
+    .unwrap();
+    let acked = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(acked["count"], 1);
+    assert!(unread_notifications(&s).await.is_empty());
+
+    sink.send(WsMessage::Text(
+        json!({"type": "subscribe", "target": "inbox"})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let err = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(err["code"], "invalid_target");
+}
+
+// Each WS connection gets its own bounded event queue, reported by the bus stats
+#[tokio::test]
+async fn test_event_bus_stats_list_ws_queues() {
+    let s = TestServer::start().await;
+    let client = s.client();
+
+    // Executors can't read them
+    let resp = client
+        .get(format!("{}/api/admin/event-bus", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let orch = s.create_orchestrator("bus-watcher", None);
+    let orch_auth = format!("Bearer {}", orch["api_key"].as_str().unwrap());
+
+    let (_sink, _stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+    let stats: Value = client
+        .get(format!("{}/api/admin/event-bus", s.base_url))
+        .header("Authorization", &orch_auth)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let subscribers = stats["subscribers"].as_array().unwrap();
+    assert_eq!(subscribers.len(), 1, "{stats}");
+    assert_eq!(
+        subscribers[0]["label"],
+        format!("ws:{}", s.agent_id()).as_str()
+    );
+    assert_eq!(subscribers[0]["policy"], "drop-oldest");
+    assert_eq!(subscribers[0]["capacity"], 256);
+    assert_eq!(stats["dropped_total"], 0);
+}
+
+// Query diagnostics: per-statement timings from the SQLite profile hook
+#[tokio::test]
+async fn test_query_stats() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Query Stats").await;
+    s.create_task(project["id"].as_str().unwrap(), "Timed")
+        .await;
+
+    let resp = client
+        .get(format!("{}/api/admin/queries", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let orch = s.create_orchestrator("query-watcher", None);
+    let orch_auth = format!("Bearer {}", orch["api_key"].as_str().unwrap());
+
+    let stats: Value = client
+        .get(format!("{}/api/admin/queries", s.base_url))
+        .header("Authorization", &orch_auth)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(stats["slow_query_threshold_ms"].is_u64());
+    let insert = stats["statements"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|st| {
+            st["statement"]
+                .as_str()
+                .unwrap()
+                .starts_with("INSERT INTO tasks")
+        })
+        .unwrap_or_else(|| panic!("no task insert in {stats}"));
+    assert!(insert["count"].as_u64().unwrap() >= 1);
+    assert!(insert["p95_ms"].as_f64().unwrap() >= insert["p50_ms"].as_f64().unwrap());
+    assert!(insert["max_ms"].as_f64().unwrap() >= insert["p95_ms"].as_f64().unwrap());
+    assert!(stats["slow_queries"].is_array());
+
+    let resp = client
+        .delete(format!("{}/api/admin/queries", s.base_url))
+        .header("Authorization", &orch_auth)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+}
+
+// Runtime settings: toggled through the admin API, applied without a restart
+#[tokio::test]
+async fn test_runtime_settings() {
+    let s = TestServer::start().await;
+    let client = s.client();
+
+    let settings: Value = client
+        .get(format!("{}/api/admin/settings", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(settings["enforce_dependencies"], true);
+    assert_eq!(settings["auto_assign_reviewers"], true);
+    assert_eq!(settings["stale_release_minutes"], 240);
+
+    // Executors can read but not change them
+    let resp = client
+        .patch(format!("{}/api/admin/settings", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "enforce_dependencies": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let orch = s.create_orchestrator("settings-admin", None);
+    let orch_auth = format!("Bearer {}", orch["api_key"].as_str().unwrap());
+
+    let resp = client
+        .patch(format!("{}/api/admin/settings", s.base_url))
+        .header("Authorization", &orch_auth)
+        .json(&json!({ "stale_release_minutes": 0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let settings: Value = client
+        .patch(format!("{}/api/admin/settings", s.base_url))
+        .header("Authorization", &orch_auth)
+        .json(&json!({ "enforce_dependencies": false, "auto_assign_reviewers": false }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(settings["enforce_dependencies"], false);
+    assert_eq!(settings["auto_assign_reviewers"], false);
+    assert_eq!(settings["stale_release_minutes"], 240);
+
+    // A task with an unfinished dependency can now be claimed
+    let project = s.create_project("Runtime Settings").await;
+    let pid = project["id"].as_str().unwrap();
+    let blocked = s.create_ready_task(pid, "Blocked").await;
+    let blocker = s.create_task(pid, "Blocker").await;
+    let blocked_id = blocked["id"].as_str().unwrap();
+    let resp = client
+        .post(format!(
+            "{}/api/tasks/{}/dependencies",
+            s.base_url, blocked_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "depends_on": [blocker["id"]] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, blocked_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200, "{}", resp.text().await.unwrap());
+
+    // And goes to review without a reviewer being picked
+    let task: Value = client
+        .post(format!(
+            "{}/api/tasks/{}/submit-review",
+            s.base_url, blocked_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(task["status"], "review");
+    assert!(task["reviewer_id"].is_null(), "{task}");
+}
+
+// Multi-tenant mode: agents registered with an owner_id act within that tenant
+#[tokio::test]
+async fn test_multi_tenant_isolation() {
+    let s = TestServer::start().await;
+    let client = s.client();
+
+    // Each org bootstraps its first agent with the setup token
+    let mut leads = Vec::new();
+    for org in ["acme", "globex"] {
+        let created: Value = client
+            .post(format!("{}/api/agents/register", s.base_url))
+            .json(&json!({
+                "name": format!("{org}-lead"),
+                "setup_token": "test-setup-token",
+                "owner_id": org,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(created["agent"]["owner_id"], org);
+        leads.push((
+            created["agent"]["id"].as_str().unwrap().to_string(),
+            format!("Bearer {}", created["api_key"].as_str().unwrap()),
+        ));
+    }
+    let (acme_id, acme) = &leads[0];
+    let (globex_id, globex) = &leads[1];
+
+    let project: Value = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", acme)
+        .json(&json!({"name": "Acme roadmap"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let pid = project["id"].as_str().unwrap();
+    let task: Value = client
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .header("Authorization", acme)
+        .json(&json!({"title": "Ship the launch"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let tid = task["id"].as_str().unwrap();
+    let resp = client
+        .put(format!(
+            "{}/api/projects/{}/knowledge/launch",
+            s.base_url, pid
+        ))
+        .header("Authorization", acme)
+        .json(&json!({"title": "Launch plan", "content": "Friday"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // Agents created by a tenant stay in it, whatever owner they ask for
+    let worker: Value = client
+        .post(format!("{}/api/agents", s.base_url))
+        .header("Authorization", acme)
+        .json(&json!({"name": "acme-worker", "owner_id": "globex"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(worker["agent"]["owner_id"], "acme");
+
+    // Globex sees none of it
+    let projects: Value = client
+        .get(format!("{}/api/projects", s.base_url))
+        .header("Authorization", globex)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(projects.as_array().unwrap().iter().all(|p| p["id"] != pid));
+    for path in [
+        format!("/api/projects/{}", pid),
+        format!("/api/projects/{}/knowledge", pid),
+        format!("/api/tasks/{}", tid),
+        format!("/api/agents/{}", acme_id),
+    ] {
+        let resp = client
+            .get(format!("{}{}", s.base_url, path))
+            .header("Authorization", globex)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 404, "{path}");
+    }
+    let agents: Value = client
+        .get(format!("{}/api/agents", s.base_url))
+        .header("Authorization", globex)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let names: Vec<&str> = agents
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["globex-lead"]);
+
+    // Nor can Acme hand its work to Globex's agents
+    let resp = client
+        .post(format!("{}/api/tasks/{}/assign", s.base_url, tid))
+        .header("Authorization", acme)
+        .json(&json!({"agent_id": globex_id}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "Agent not found");
+
+    // Instance-wide settings are out of a tenant's reach, even for its orchestrators
+    let orchestrator = s.create_orchestrator("acme-orchestrator", Some("acme"));
+    let resp = client
+        .patch(format!("{}/api/admin/settings", s.base_url))
+        .header(
+            "Authorization",
+            format!("Bearer {}", orchestrator["api_key"].as_str().unwrap()),
+        )
+        .json(&json!({"enforce_dependencies": false}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // The unscoped test agent sees every tenant
+    let resp = client
+        .get(format!("{}/api/tasks/{}", s.base_url, tid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+// Naming an owner for a new agent takes an operator outside every tenant
+#[tokio::test]
+async fn test_create_agent_owner_requires_operator() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let orchestrator = s.create_orchestrator("operator", None);
+    let operator = format!("Bearer {}", orchestrator["api_key"].as_str().unwrap());
+
+    // Before any tenant exists anonymous callers get through auth, but not into a tenant
+    for auth in [None, Some(s.auth_header())] {
+        let mut request = client
+            .post(format!("{}/api/agents", s.base_url))
+            .json(&json!({"name": "planted", "owner_id": "acme"}));
+        if let Some(auth) = &auth {
+            request = request.header("Authorization", auth);
+        }
+        let resp = request.send().await.unwrap();
+        assert_eq!(resp.status(), 403, "{auth:?}");
+    }
+    assert!(s
+        .storage
+        .list_agents(None)
+        .iter()
+        .all(|a| a.name != "planted"));
+
+    let resp = client
+        .post(format!("{}/api/agents", s.base_url))
+        .header("Authorization", &operator)
+        .json(&json!({"name": "acme-worker", "owner_id": "acme"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["agent"]["owner_id"], "acme");
+
+    // Without an owner, executors still add untenanted agents
+    let resp = client
+        .post(format!("{}/api/agents", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"name": "helper"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+}
+
+// Without a key, a multi-tenant server gives nothing away
+#[tokio::test]
+async fn test_multi_tenant_anonymous_access() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Shared").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Visible to all").await;
+    let tid = task["id"].as_str().unwrap();
+
+    // Single-org installs keep working without keys
+    let resp = client
+        .get(format!("{}/api/projects", s.base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let created: Value = client
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({"name": "acme-lead", "setup_token": "test-setup-token", "owner_id": "acme"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let acme_key = created["api_key"].as_str().unwrap().to_string();
+
+    for path in [
+        "/api/projects".to_string(),
+        "/api/agents".to_string(),
+        "/api/stats".to_string(),
+        format!("/api/projects/{}/pulse", pid),
+        format!("/api/projects/{}/schedule.ics", pid),
+        format!("/api/projects/{}/knowledge", pid),
+        format!("/api/tasks/{}", tid),
+    ] {
+        let resp = client
+            .get(format!("{}{}", s.base_url, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 401, "GET {path}");
+    }
+    let resp = client
+        .patch(format!("{}/api/tasks/{}", s.base_url, tid))
+        .json(&json!({"title": "Defaced"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = client
+        .put(format!("{}/api/projects/{}/knowledge/x", s.base_url, pid))
+        .json(&json!({"title": "x", "content": "x"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = client
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+        .json(&json!({"title": "Spam"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    // An unknown key is no better than none
+    let resp = client
+        .get(format!("{}/api/projects", s.base_url))
+        .header("Authorization", "Bearer not-a-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    // Keys still work, in the header or as a calendar feed token
+    let resp = client
+        .get(format!("{}/api/projects/{}/pulse", s.base_url, pid))
+        .header("Authorization", format!("Bearer {}", acme_key))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .get(format!(
+            "{}/api/projects/{}/schedule.ics?token={}",
+            s.base_url, pid, s.api_key
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    // Registration needs no key
+    let resp = client
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({"name": "beta-lead", "setup_token": "test-setup-token", "owner_id": "beta"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+}
+
+#[tokio::test]
+async fn test_tenant_quotas() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let mut limits = opengate::config::TenantLimitsConfig::default();
+    limits.tenants.insert(
+        "quota-co".to_string(),
+        opengate::config::TenantLimits {
+            projects: Some(1),
+            open_tasks: Some(1),
+            agents: Some(1),
+            events_per_day: Some(2),
+            ..Default::default()
+        },
+    );
+    opengate::quotas::set_limits(limits);
+
+    let register = || {
+        client
+            .post(format!("{}/api/agents/register", s.base_url))
+            .json(&json!({
+                "name": "quota-lead",
+                "setup_token": "test-setup-token",
+                "owner_id": "quota-co",
+            }))
+            .send()
+    };
+    let created: Value = register().await.unwrap().json().await.unwrap();
+    let lead = format!("Bearer {}", created["api_key"].as_str().unwrap());
+    let resp = register().await.unwrap();
+    assert_eq!(resp.status(), 402);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["quota"], "agents");
+    assert_eq!(body["limit"], 1);
+
+    let create_project = |name: &str| {
+        client
+            .post(format!("{}/api/projects", s.base_url))
+            .header("Authorization", &lead)
+            .json(&json!({"name": name}))
+            .send()
+    };
+    let project: Value = create_project("Quota").await.unwrap().json().await.unwrap();
+    let pid = project["id"].as_str().unwrap();
+    assert_eq!(create_project("Another").await.unwrap().status(), 402);
+    // Operators outside any tenant aren't limited
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"name": "Ops"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let create_task = |title: &str| {
+        client
+            .post(format!("{}/api/projects/{}/tasks", s.base_url, pid))
+            .header("Authorization", &lead)
+            .json(&json!({"title": title}))
+            .send()
+    };
+    let task: Value = create_task("First").await.unwrap().json().await.unwrap();
+    let tid = task["id"].as_str().unwrap();
+    let resp = create_task("Second").await.unwrap();
+    assert_eq!(resp.status(), 402);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "Tenant 'quota-co' has reached its limit of 1 open tasks"
+    );
+
+    let usage: Value = client
+        .get(format!("{}/api/tenant/usage", s.base_url))
+        .header("Authorization", &lead)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(usage["tenant_id"], "quota-co");
+    assert_eq!(usage["projects"], json!({"used": 1, "limit": 1}));
+    assert_eq!(usage["open_tasks"], json!({"used": 1, "limit": 1}));
+    assert_eq!(usage["agents"], json!({"used": 1, "limit": 1}));
+    assert_eq!(usage["webhook_deliveries_today"]["limit"], Value::Null);
+
+    // Blocking records an event; once two are in, the tenant's writes wait for tomorrow
+    let set_status = |status: &str| {
+        client
+            .patch(format!("{}/api/tasks/{}", s.base_url, tid))
+            .header("Authorization", &lead)
+            .json(&json!({"status": status}))
+            .send()
+    };
+    for status in ["todo", "blocked", "todo", "blocked"] {
+        assert_eq!(set_status(status).await.unwrap().status(), 200);
+    }
+    let resp = set_status("todo").await.unwrap();
+    assert_eq!(resp.status(), 429);
+    assert!(resp.headers().contains_key("retry-after"));
+    let usage: Value = client
+        .get(format!("{}/api/tenant/usage", s.base_url))
+        .header("Authorization", &lead)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(usage["events_today"], json!({"used": 2, "limit": 2}));
+
+    // Unscoped callers have to say which tenant
+    let resp = client
+        .get(format!("{}/api/tenant/usage", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = client
+        .get(format!(
+            "{}/api/tenant/usage?tenant_id=quota-co",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_tenant_admin() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let orch = s.create_orchestrator("tenant-admin", None);
+    let admin = format!("Bearer {}", orch["api_key"].as_str().unwrap());
+
+    let create = |body: Value| {
+        client
+            .post(format!("{}/api/admin/tenants", s.base_url))
+            .header("Authorization", &admin)
+            .json(&body)
+            .send()
+    };
+    let resp = create(json!({"id": "initech", "name": "Initech"}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let tenant: Value = resp.json().await.unwrap();
+    assert_eq!(tenant["status"], "active");
+    assert_eq!(
+        create(json!({"id": "initech"})).await.unwrap().status(),
+        409
+    );
+    assert_eq!(
+        create(json!({"id": "no spaces"})).await.unwrap().status(),
+        400
+    );
+
+    // A tenant's setup token registers into it, whatever owner_id the agent asks for
+    let minted: Value = client
+        .post(format!(
+            "{}/api/admin/tenants/initech/setup-tokens",
+            s.base_url
+        ))
+        .header("Authorization", &admin)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = minted["setup_token"].as_str().unwrap();
+    let created: Value = client
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({"name": "initech-lead", "setup_token": token, "owner_id": "acme"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(created["agent"]["owner_id"], "initech");
+    let lead = format!("Bearer {}", created["api_key"].as_str().unwrap());
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", &lead)
+        .json(&json!({"name": "TPS reports"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Tenant agents can't manage tenants
+    let resp = client
+        .get(format!("{}/api/admin/tenants", s.base_url))
+        .header("Authorization", &lead)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let tenants: Value = client
+        .get(format!("{}/api/admin/tenants", s.base_url))
+        .header("Authorization", &admin)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(tenants[0]["id"], "initech");
+    assert_eq!(tenants[0]["usage"]["projects"]["used"], 1);
+    assert_eq!(tenants[0]["usage"]["agents"]["used"], 1);
+
+    // Suspended: keys and the setup token stop working until it resumes
+    let tenant_action = |action: &str| {
+        client
+            .post(format!(
+                "{}/api/admin/tenants/initech/{}",
+                s.base_url, action
+            ))
+            .header("Authorization", &admin)
+            .send()
+    };
+    let suspended: Value = tenant_action("suspend")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(suspended["status"], "suspended");
+    let me = || {
+        client
+            .get(format!("{}/api/projects", s.base_url))
+            .header("Authorization", &lead)
+            .send()
+    };
+    assert_eq!(me().await.unwrap().status(), 403);
+    let resp = client
+        .post(format!("{}/api/agents/register", s.base_url))
+        .json(&json!({"name": "initech-2", "setup_token": token}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    tenant_action("resume").await.unwrap();
+    assert_eq!(me().await.unwrap().status(), 200);
+
+    // Deleting takes the tenant's data and keys with it
+    let resp = client
+        .delete(format!("{}/api/admin/tenants/initech", s.base_url))
+        .header("Authorization", &admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    let projects: Value = client
+        .get(format!("{}/api/projects", s.base_url))
+        .header("Authorization", &admin)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(projects
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|p| p["name"] != "TPS reports"));
+    let resp = client
+        .get(format!("{}/api/admin/tenants/initech", s.base_url))
+        .header("Authorization", &admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_unix_socket_listener() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let tmp = TempDir::new().unwrap();
+    let conn = db::init_db(tmp.path().join("test.db").to_str().unwrap());
+    let (_, api_key) = db_ops::create_agent(&conn, &CreateAgent::new("socket-agent"));
+    let state = AppState {
+        storage: Arc::new(SqliteBackend::new(Arc::new(Mutex::new(conn)))),
+        setup_token: String::new(),
+        event_bus: opengate::events::EventBus::default(),
+    };
+    let path = tmp.path().join("opengate.sock");
+    // A socket file left by an earlier run doesn't stop the bind
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    let listener = opengate::listen::bind_unix(&path).unwrap();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(opengate::listen::serve_unix(
+        listener,
+        build_router(state),
+        async {
+            let _ = stop_rx.await;
+        },
+    ));
+
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let request = format!(
+        "GET /api/auth/me HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\nConnection: close\r\n\r\n",
+        api_key
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("socket-agent"));
+
+    stop_tx.send(()).unwrap();
+    server.await.unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_health() {
+    let s = TestServer::start().await;
+    let body: Value = s
+        .client()
+        .get(format!("{}/health", s.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_ui_dir_serving() {
+    use axum::http::Method;
+    use opengate::ui::serve_from;
+
+    let tmp = TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("index.html"), "<h1>OpenGate</h1>").unwrap();
+    std::fs::create_dir(tmp.path().join("assets")).unwrap();
+    std::fs::write(tmp.path().join("assets/app.js"), "console.log(1)").unwrap();
+    let get = |path: &'static str| serve_from(tmp.path(), &Method::GET, path);
+    let body = |resp: axum::response::Response| async {
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    };
+
+    let resp = get("/").await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+    assert_eq!(body(resp).await, "<h1>OpenGate</h1>");
+    let resp = get("/assets/app.js").await;
+    assert_eq!(
+        resp.headers()["content-type"],
+        "text/javascript; charset=utf-8"
+    );
+    // Client-side routes get the app; missing assets and the API don't
+    assert_eq!(
+        body(get("/projects/p1/tasks").await).await,
+        "<h1>OpenGate</h1>"
+    );
+    assert_eq!(get("/assets/missing.js").await.status(), 404);
+    assert_eq!(get("/api/nope").await.status(), 404);
+    assert_eq!(get("/../secret").await.status(), 404);
+    let resp = serve_from(tmp.path(), &Method::POST, "/").await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_stats_timeseries() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Trends").await;
+    let pid = project["id"].as_str().unwrap();
+    let done = s.create_ready_task(pid, "Ship it").await;
+    let blocked = s.create_ready_task(pid, "Stuck").await;
+    s.create_task(pid, "Later").await;
+    let set_status = |tid: String, status: &'static str| {
+        client
+            .patch(format!("{}/api/tasks/{}", s.base_url, tid))
+            .header("Authorization", s.auth_header())
+            .json(&json!({ "status": status }))
+            .send()
+    };
+    let done_id = done["id"].as_str().unwrap().to_string();
+    for status in ["in_progress", "done"] {
+        assert_eq!(
+            set_status(done_id.clone(), status).await.unwrap().status(),
+            200
+        );
+    }
+    let blocked_id = blocked["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        set_status(blocked_id, "blocked").await.unwrap().status(),
+        200
+    );
+
+    let series = |query: String| {
+        client
+            .get(format!("{}/api/stats/timeseries?{}", s.base_url, query))
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let today = chrono::Utc::now().date_naive();
+    let body: Value = series("metric=tasks_created".to_string())
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["interval"], "day");
+    let points = body["points"].as_array().unwrap();
+    assert_eq!(points.len(), 30);
+    assert_eq!(points[29]["date"], today.to_string());
+    assert_eq!(points[29]["count"], 3);
+    assert_eq!(points[0]["count"], 0);
+
+    for (metric, expected) in [("tasks_completed", 1), ("tasks_blocked", 1)] {
+        let body: Value = series(format!("metric={}&project_id={}", metric, pid))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["points"][29]["count"], expected, "{}", metric);
+    }
+
+    // Weeks start on Monday; every bucket in range is present
+    let from = today - chrono::Days::new(20);
+    let body: Value = series(format!(
+        "metric=tasks_created&interval=week&from={}&to={}",
+        from, today
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let points = body["points"].as_array().unwrap();
+    let total: i64 = points.iter().map(|p| p["count"].as_i64().unwrap()).sum();
+    assert_eq!(total, 3);
+    let first =
+        chrono::NaiveDate::parse_from_str(points[0]["date"].as_str().unwrap(), "%Y-%m-%d").unwrap();
+    assert_eq!(chrono::Datelike::weekday(&first), chrono::Weekday::Mon);
+    assert!(first <= from && from - first < chrono::Duration::days(7));
+
+    for bad in [
+        "metric=tasks_exploded".to_string(),
+        "metric=tasks_created&interval=hour".to_string(),
+        "metric=tasks_created&from=yesterday".to_string(),
+        format!("metric=tasks_created&from={}&to={}", today, from),
+        "metric=tasks_created&from=2000-01-01".to_string(),
+    ] {
+        assert_eq!(series(bad.clone()).await.unwrap().status(), 400, "{}", bad);
+    }
+    let resp = client
+        .get(format!(
+            "{}/api/stats/timeseries?metric=tasks_created",
+            s.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
+
+#[tokio::test]
+async fn test_agent_workload_stats() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Workload").await;
+    let pid = project["id"].as_str().unwrap();
+    let finished = s.create_task(pid, "Finished").await;
+    s.finish_task(finished["id"].as_str().unwrap()).await;
+    let started = s.create_ready_task(pid, "Started").await;
+    let resp = client
+        .post(format!(
+            "{}/api/tasks/{}/claim",
+            s.base_url,
+            started["id"].as_str().unwrap()
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .post(format!("{}/api/agents/heartbeat", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let stats: Value = client
+        .get(format!("{}/api/stats/agents", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["days"], 7);
+    let me = stats["agents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["agent_id"] == s.agent_id())
+        .unwrap();
+    assert_eq!(me["in_progress"], 1);
+    assert_eq!(me["open"], 0);
+    assert_eq!(me["completed"], 1);
+    assert!((me["throughput_per_day"].as_f64().unwrap() - 1.0 / 7.0).abs() < 1e-9);
+    assert!(me["seconds_since_heartbeat"].as_i64().unwrap() < 60);
+
+    let resp = client
+        .get(format!("{}/api/stats/agents?days=0", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_project_burndown() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Burndown").await;
+    let pid = project["id"].as_str().unwrap();
+    let mut ids = Vec::new();
+    for (title, estimate) in [("Big", Some(5)), ("Medium", Some(3)), ("Unsized", None)] {
+        let task = s.create_task(pid, title).await;
+        let id = task["id"].as_str().unwrap().to_string();
+        if let Some(estimate) = estimate {
+            let resp = client
+                .patch(format!("{}/api/tasks/{}/context", s.base_url, id))
+                .header("Authorization", s.auth_header())
+                .json(&json!({ "estimate": estimate }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+        ids.push(id);
+    }
+    s.finish_task(&ids[0]).await;
+
+    let burndown = |query: &str| {
+        client
+            .get(format!(
+                "{}/api/projects/{}/burndown?{}",
+                s.base_url, pid, query
+            ))
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let body: Value = burndown("iteration_days=10")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let points = body["points"].as_array().unwrap();
+    assert_eq!(points.len(), 30);
+    assert_eq!(points[0]["remaining"], 0.0);
+    assert_eq!(points[29]["remaining"], 2.0);
+    let iterations = body["iterations"].as_array().unwrap();
+    assert_eq!(iterations.len(), 3);
+    assert_eq!(iterations[2]["completed"], 1.0);
+    assert!((body["average_velocity"].as_f64().unwrap() - 1.0 / 3.0).abs() < 1e-9);
+
+    let body: Value = burndown("weight=estimate")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["weight"], "estimate");
+    assert_eq!(body["points"][29]["remaining"], 4.0);
+    let completed: f64 = body["iterations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|it| it["completed"].as_f64().unwrap())
+        .sum();
+    assert_eq!(completed, 5.0);
+
+    // Cancelled work leaves the scope
+    let resp = client
+        .patch(format!("{}/api/tasks/{}", s.base_url, ids[1]))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "status": "cancelled" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = burndown("").await.unwrap().json().await.unwrap();
+    assert_eq!(body["points"][29]["remaining"], 1.0);
+
+    assert_eq!(burndown("weight=points").await.unwrap().status(), 400);
+    assert_eq!(burndown("iteration_days=0").await.unwrap().status(), 400);
+    let resp = client
+        .get(format!("{}/api/projects/nope/burndown", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_review_latency_stats() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Reviews").await;
+    let pid = project["id"].as_str().unwrap();
+    let mut ids = Vec::new();
+    for title in ["Approved", "Waiting"] {
+        let task = s.create_ready_task(pid, title).await;
+        let id = task["id"].as_str().unwrap().to_string();
+        client
+            .post(format!("{}/api/tasks/{}/claim", s.base_url, id))
+            .header("Authorization", s.auth_header())
+            .send()
+            .await
+            .unwrap();
+        let resp = client
+            .patch(format!("{}/api/tasks/{}", s.base_url, id))
+            .header("Authorization", s.auth_header())
+            .json(&json!({ "status": "review", "reviewer_type": "agent", "reviewer_id": s.agent_id() }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        ids.push(id);
+    }
+    for action in ["start-review", "approve"] {
+        let resp = client
+            .post(format!("{}/api/tasks/{}/{}", s.base_url, ids[0], action))
+            .header("Authorization", s.auth_header())
+            .json(&json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200, "{}", action);
+    }
+
+    let report: Value = client
+        .get(format!("{}/api/stats/reviews", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["days"], 30);
+    let reviewer = &report["reviewers"][0];
+    assert_eq!(reviewer["id"], s.agent_id());
+    assert_eq!(reviewer["name"], "test-agent");
+    assert_eq!(reviewer["completed"], 1);
+    assert_eq!(reviewer["pending"], 1);
+    assert!(reviewer["avg_time_to_first_review_secs"].is_i64());
+    assert!(reviewer["avg_time_in_review_secs"].is_i64());
+    assert!(reviewer["oldest_pending_secs"].is_i64());
+    assert_eq!(report["projects"][0]["id"], pid);
+    assert_eq!(report["projects"][0]["name"], "Reviews");
+
+    let pulse: Value = client
+        .get(format!("{}/api/projects/{}/pulse", s.base_url, pid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let queues = pulse["slowest_review_queues"].as_array().unwrap();
+    assert_eq!(queues.len(), 1);
+    assert_eq!(queues[0]["id"], s.agent_id());
+    assert_eq!(queues[0]["pending"], 1);
+    assert_eq!(queues[0]["completed"], 0);
+
+    let resp = client
+        .get(format!("{}/api/stats/reviews?days=400", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_csv_export() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Spreadsheet").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Fix \"quotes\", commas").await;
+
+    let get = |path: &str| {
+        client
+            .get(format!("{}{}", s.base_url, path))
+            .header("Authorization", s.auth_header())
+            .send()
+    };
+    let resp = get("/api/tasks?format=csv").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "text/csv; charset=utf-8");
+    assert!(resp.headers()["content-disposition"]
+        .to_str()
+        .unwrap()
+        .contains("tasks.csv"));
+    let body = resp.text().await.unwrap();
+    let mut lines = body.split("\r\n");
+    assert!(lines
+        .next()
+        .unwrap()
+        .starts_with("id,project_id,title,description,status,priority,"));
+    let row = lines.next().unwrap();
+    assert!(row.starts_with(&format!(
+        "{},{},\"Fix \"\"quotes\"\", commas\",Test task,backlog,high,",
+        task["id"].as_str().unwrap(),
+        pid
+    )));
+    assert!(row.contains(",rust;testing,"));
+
+    let body = get(&format!("/api/projects/{}/tasks?format=csv", pid))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body.matches("\r\n").count(), 2);
+
+    let body = get("/api/stats/timeseries?metric=tasks_created&format=csv")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.starts_with("date,count\r\n"));
+    assert!(body.ends_with(",1\r\n"));
+    let body = get("/api/stats?format=csv")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains("total_tasks,1\r\n"));
+    assert!(body.contains("tasks.backlog,1\r\n"));
+    for path in [
+        "/api/stats/agents?format=csv",
+        "/api/stats/reviews?format=csv",
+    ] {
+        let resp = get(path).await.unwrap();
+        assert_eq!(resp.status(), 200, "{}", path);
+        assert_eq!(resp.headers()["content-type"], "text/csv; charset=utf-8");
+    }
+
+    // JSON stays the default
+    let tasks: Value = get("/api/tasks").await.unwrap().json().await.unwrap();
+    assert_eq!(tasks.as_array().unwrap().len(), 1);
+    assert_eq!(get("/api/tasks?format=xml").await.unwrap().status(), 400);
+}
+
+#[tokio::test]
+async fn test_cost_stats() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Costs").await;
+    let pid = project["id"].as_str().unwrap();
+    let task = s.create_task(pid, "Spend").await;
+    let tid = task["id"].as_str().unwrap();
+
+    let report = |body: Value| {
+        client
+            .post(format!("{}/api/tasks/{}/usage", s.base_url, tid))
+            .header("Authorization", s.auth_header())
+            .json(&body)
+            .send()
+    };
+    let resp = report(json!({})).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = report(json!({ "cost_usd": -1.0 })).await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = client
+        .patch(format!("{}/api/agents/me", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "cost_tier": "premium", "model": "big-model" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = report(json!({ "input_tokens": 1000, "output_tokens": 200, "cost_usd": 1.5 }))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let usage: Value = resp.json().await.unwrap();
+    assert_eq!(usage["cost_tier"], "premium");
+    assert_eq!(usage["model"], "big-model");
+
+    // Rerouting the agent to a cheaper tier doesn't restate spend already reported
+    client
+        .patch(format!("{}/api/agents/me", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "cost_tier": "cheap", "model": "small-model" }))
+        .send()
+        .await
+        .unwrap();
+    let resp = report(json!({ "input_tokens": 500, "cost_usd": 0.25 }))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let usage: Value = client
+        .get(format!("{}/api/tasks/{}/usage", s.base_url, tid))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(usage.as_array().unwrap().len(), 2);
+
+    let costs: Value = client
+        .get(format!("{}/api/stats/costs", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(costs["group_by"], "cost_tier");
+    assert_eq!(costs["total_cost_usd"], 1.75);
+    let buckets = costs["buckets"].as_array().unwrap();
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0]["group"], "premium");
+    assert_eq!(buckets[0]["input_tokens"], 1000);
+    assert_eq!(buckets[0]["tasks"], 1);
+    assert_eq!(buckets[1]["group"], "cheap");
+    assert_eq!(buckets[1]["cost_usd"], 0.25);
+
+    let costs: Value = client
+        .get(format!(
+            "{}/api/stats/costs?group_by=project&interval=week",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(costs["buckets"][0]["group"], pid);
+    assert_eq!(costs["buckets"][0]["tasks"], 1);
+    assert_eq!(costs["buckets"][0]["output_tokens"], 200);
+
+    let csv = client
+        .get(format!(
+            "{}/api/stats/costs?group_by=model&format=csv",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(csv.starts_with("period,group,tasks,input_tokens,output_tokens,cost_usd\r\n"));
+    assert!(csv.contains(",big-model,1,1000,200,1.5\r\n"));
 
-    let project = db_ops::create_project(
-        &conn,
-        None,
-        &opengate_models::CreateProject {
-            name: "ATM Project".to_string(),
-            description: None,
-            repo_url: None,
-            default_branch: None,
-            join_mode: None,
-            cta_enabled: None,
-            is_public: None,
-        },
-        &agent1.id,
-    );
-    let task = db_ops::create_task(
-        &conn,
-        None,
-        &project.id,
-        &opengate_models::CreateTask {
-            title: "ATM Task".to_string(),
-            description: None,
-            priority: None,
-            tags: None,
-            context: None,
-            output: None,
-            due_date: None,
-            assignee_type: None,
-            assignee_id: None,
-            scheduled_at: None,
-            recurrence_rule: None,
-        },
-        &agent1.id,
-    );
+    let resp = client
+        .get(format!("{}/api/stats/costs?group_by=provider", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = client
+        .get(format!("{}/api/stats/costs", s.base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
 
-    let question = db_ops::create_question(
-        &conn,
-        &task.id,
-        &opengate_models::CreateQuestion {
-            question: "Docker build failing".to_string(),
-            question_type: None,
-            context: None,
-            target_type: None,
-            target_id: None,
-            required_capability: Some("devops:docker".to_string()),
-            blocking: Some(true),
-        },
-        "agent",
-        &agent1.id,
-    );
+#[tokio::test]
+async fn test_blocked_time_stats() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let project = s.create_project("Blocked").await;
+    let pid = project["id"].as_str().unwrap();
+    let upstream = s.create_ready_task(pid, "Upstream").await;
+    let upstream_id = upstream["id"].as_str().unwrap();
+    let waiting = s.create_task(pid, "Waiting").await;
+    let waiting_id = waiting["id"].as_str().unwrap();
+    let resp = client
+        .post(format!(
+            "{}/api/tasks/{}/dependencies",
+            s.base_url, waiting_id
+        ))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "depends_on": [upstream_id] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let stuck = s.create_ready_task(pid, "Stuck").await;
+    let stuck_id = stuck["id"].as_str().unwrap();
+    client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, stuck_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{}/api/tasks/{}/block", s.base_url, stuck_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "reason": "waiting on credentials" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    tokio::time::sleep(tokio::time::Duration::from_millis(1100)).await;
 
-    let result = db_ops::auto_target_question(&conn, &question.id, "devops:docker");
+    let report: Value = client
+        .get(format!(
+            "{}/api/stats/blocked?project_id={}",
+            s.base_url, pid
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let most_blocked = report["most_blocked"].as_array().unwrap();
+    assert_eq!(most_blocked.len(), 2);
+    let accounted = |id: &str| {
+        most_blocked
+            .iter()
+            .find(|t| t["task_id"] == id)
+            .unwrap()
+            .clone()
+    };
+    assert!(accounted(stuck_id)["blocked_secs"].as_i64().unwrap() >= 1);
+    assert_eq!(accounted(stuck_id)["blocked_by_deps_secs"], 0);
+    assert_eq!(accounted(waiting_id)["blocked_secs"], 0);
     assert!(
-        result.len() >= 2,
-        "Expected 2+ matches for devops:docker, got {}",
-        result.len()
+        accounted(waiting_id)["blocked_by_deps_secs"]
+            .as_i64()
+            .unwrap()
+            >= 1
     );
 
-    // Question should remain unrouted (N > 1)
-    let q = db_ops::get_question(&conn, &question.id).unwrap();
-    assert!(
-        q.target_id.is_none(),
-        "Question should remain unrouted with N matches"
-    );
+    let blocker = &report["top_blockers"][0];
+    assert_eq!(blocker["task_id"], upstream_id);
+    assert_eq!(blocker["title"], "Upstream");
+    assert_eq!(blocker["dependents"], 1);
+    assert!(blocker["dependent_wait_secs"].as_i64().unwrap() >= 1);
+
+    let totals = &report["projects"][0];
+    assert_eq!(totals["project_id"], pid);
+    assert_eq!(totals["name"], "Blocked");
+    assert_eq!(totals["tasks"], 2);
+
+    let resp = client
+        .get(format!("{}/api/stats/blocked?limit=0", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
 }
 
 #[tokio::test]
-async fn test_auto_target_finds_agents_by_capability() {
-    // Agents with matching capability should be returned
-    let tmp = TempDir::new().unwrap();
-    let conn = db::init_db(tmp.path().join("atu.db").to_str().unwrap());
+async fn test_stats_filters() {
+    let s = TestServer::start().await;
+    let client = s.client();
+    let alpha = s.create_project("Alpha").await;
+    let alpha_id = alpha["id"].as_str().unwrap();
+    let beta = s.create_project("Beta").await;
+    let beta_id = beta["id"].as_str().unwrap();
+    s.create_task(alpha_id, "Alpha one").await;
+    s.create_ready_task(alpha_id, "Alpha two").await;
+    let resp = client
+        .post(format!("{}/api/projects/{}/tasks", s.base_url, beta_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "title": "Beta ops", "tags": ["ops"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
 
-    let (_agent, _) = db_ops::create_agent(
-        &conn,
-        &CreateAgent::new("review-agent").with_capabilities(vec!["review".to_string()]),
-    );
+    let stats = |query: String| {
+        let client = client.clone();
+        let url = format!("{}/api/stats{}", s.base_url, query);
+        let auth = s.auth_header();
+        async move {
+            let resp = client
+                .get(url)
+                .header("Authorization", auth)
+                .send()
+                .await
+                .unwrap();
+            (resp.status(), resp.json::<Value>().await.unwrap())
+        }
+    };
+    let (_, all) = stats(String::new()).await;
+    assert_eq!(all["total_tasks"], 3);
+    assert_eq!(all["total_projects"], 2);
+
+    let (_, alpha_stats) = stats(format!("?project_id={}", alpha_id)).await;
+    assert_eq!(alpha_stats["total_tasks"], 2);
+    assert_eq!(alpha_stats["total_projects"], 1);
+    assert_eq!(alpha_stats["tasks_by_status"]["todo"], 1);
+    assert_eq!(alpha_stats["tasks_by_status"]["backlog"], 1);
+
+    let (_, ops) = stats("?tag=ops".to_string()).await;
+    assert_eq!(ops["total_tasks"], 1);
+    assert_eq!(ops["tasks_by_status"]["backlog"], 1);
+
+    let (_, past) = stats("?to=2000-01-01".to_string()).await;
+    assert_eq!(past["total_tasks"], 0);
+    assert_eq!(past["recent_activity"].as_array().unwrap().len(), 0);
+    let today = chrono::Utc::now().date_naive();
+    let (_, current) = stats(format!("?from={}&tag=rust", today)).await;
+    assert_eq!(current["total_tasks"], 2);
+
+    let (status, _) = stats("?from=yesterday".to_string()).await;
+    assert_eq!(status, 400);
+}
 
-    let targets = db_ops::find_capability_targets(&conn, "review");
-    assert!(!targets.is_empty(), "Expected agent match");
+/// Stand-in for GitHub's issue comments API: records each call's path, auth and body.
+async fn start_mock_github() -> (
+    String,
+    Arc<tokio::sync::Mutex<Vec<(String, Option<String>, Value)>>>,
+) {
+    use axum::{extract::State, http::HeaderMap, http::Uri, routing::post, Json, Router};
 
-    let agent_idx = targets.iter().position(|t| t.target_type == "agent");
-    assert!(agent_idx.is_some(), "Agent should be in results");
+    type Calls = Arc<tokio::sync::Mutex<Vec<(String, Option<String>, Value)>>>;
+    let calls: Calls = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+    let app = Router::new()
+        .route(
+            "/repos/:owner/:repo/issues/:number/comments",
+            post(
+                |State(calls): State<Calls>,
+                 uri: Uri,
+                 headers: HeaderMap,
+                 Json(body): Json<Value>| async move {
+                    let auth = headers
+                        .get("authorization")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    calls
+                        .lock()
+                        .await
+                        .push((uri.path().to_string(), auth, body));
+                    (axum::http::StatusCode::CREATED, Json(json!({"id": 1})))
+                },
+            ),
+        )
+        .with_state(calls.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}", addr), calls)
 }
 
 #[tokio::test]
-async fn test_auto_target_via_api_single_match() {
-    // End-to-end: POST /api/tasks/:id/questions with required_capability
-    // and one matching agent → question auto-targeted
+async fn test_github_pull_request_linking() {
     let s = TestServer::start().await;
+    let client = s.client();
+    let (api_url, calls) = start_mock_github().await;
+    opengate::github::set_api_url(&api_url);
 
-    // Register a devops agent with capabilities via the register endpoint
-    let resp = s
-        .client()
-        .post(format!("{}/api/agents/register", s.base_url))
-        .json(&json!({
-            "name": "devops-agent",
-            "setup_token": "test-setup-token",
-            "skills": ["devops"],
-            "capabilities": ["devops:docker", "devops:k8s"]
-        }))
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "name": "Widgets", "repo_url": "https://github.com/acme/widgets" }))
         .send()
         .await
         .unwrap();
-    assert!(resp.status().is_success());
-    let agent_resp: Value = resp.json().await.unwrap();
-    let devops_agent_id = agent_resp["agent"]["id"].as_str().unwrap().to_string();
-
-    let project = s.create_project("Auto-target API Project").await;
+    let project: Value = resp.json().await.unwrap();
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Auto-target API Task").await;
+    let github_url = format!("{}/api/projects/{}/integrations/github", s.base_url, pid);
+    let resp = client
+        .put(&github_url)
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "token": "ghp_test" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let config: Value = resp.json().await.unwrap();
+    assert_eq!(config["has_token"], true);
+    assert_eq!(config["comment_on_complete"], true);
+    assert!(config.get("token").is_none());
+
+    // Completing with a pr_url attaches it and comments on the pull request
+    let task = s.create_ready_task(pid, "Fix login").await;
     let task_id = task["id"].as_str().unwrap();
+    client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let pr_url = "https://github.com/acme/widgets/pull/42";
+    let resp = client
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "summary": "Added a retry", "output": { "pr_url": pr_url } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let artifacts: Value = client
+        .get(format!("{}/api/tasks/{}/artifacts", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(artifacts[0]["artifact_type"], "url");
+    assert_eq!(artifacts[0]["value"], pr_url);
+    assert_eq!(artifacts[0]["name"], "Pull request");
 
-    // Create question with required_capability but no target
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+    let mut posted = Vec::new();
+    for _ in 0..50 {
+        posted = calls.lock().await.clone();
+        if !posted.is_empty() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    let (path, auth, body) = &posted[0];
+    assert_eq!(path, "/repos/acme/widgets/issues/42/comments");
+    assert_eq!(auth.as_deref(), Some("Bearer ghp_test"));
+    assert_eq!(
+        body["body"],
+        "**Fix login** was completed by test-agent in OpenGate.\n\nAdded a retry"
+    );
+
+    // An opened pull request naming a task in its branch moves the task to review
+    let resp = client
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
         .header("Authorization", s.auth_header())
         .json(&json!({
-            "question": "How to configure Docker?",
-            "required_capability": "devops:docker"
+            "name": "Pull requests",
+            "action_type": "github_pull_request",
+            "action_config": {},
+            "verification": "github",
+            "signing_secret": "gh-secret",
         }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-
-    // Should be auto-targeted to the devops agent
-    assert_eq!(
-        q["target_type"], "agent",
-        "Should be auto-targeted to agent"
+    let created: Value = resp.json().await.unwrap();
+    let trigger_url = format!(
+        "{}/api/webhooks/trigger/{}",
+        s.base_url,
+        created["trigger"]["id"].as_str().unwrap()
     );
+    let task = s.create_ready_task(pid, "Add search").await;
+    let task_id = task["id"].as_str().unwrap();
+    client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    let deliver = |payload: Value| {
+        let body = payload.to_string().into_bytes();
+        let signature = format!(
+            "sha256={}",
+            hex::encode(opengate::signatures::hmac_sha256(b"gh-secret", &body))
+        );
+        client
+            .post(&trigger_url)
+            .header("X-Hub-Signature-256", signature)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+    };
+    let pull_request = |action: &str| {
+        json!({
+            "action": action,
+            "number": 43,
+            "pull_request": {
+                "number": 43,
+                "html_url": "https://github.com/acme/widgets/pull/43",
+                "title": "Add search",
+                "body": null,
+                "draft": false,
+                "head": { "ref": format!("agent/{}", task_id) },
+            },
+        })
+    };
+    let result: Value = deliver(pull_request("synchronize"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(result["ignored"], true);
+    let result: Value = deliver(pull_request("opened"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(result["task_id"], task_id);
+    assert_eq!(result["status"], "review");
+    let task = s.get_task(task_id).await;
+    assert_eq!(task["status"], "review");
+    let artifacts: Value = client
+        .get(format!("{}/api/tasks/{}/artifacts", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
     assert_eq!(
-        q["target_id"], devops_agent_id,
-        "Should be targeted to devops-agent"
+        artifacts[0]["value"],
+        "https://github.com/acme/widgets/pull/43"
     );
+
+    let resp = client
+        .delete(&github_url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+}
+
+async fn start_mock_gitlab() -> (
+    String,
+    Arc<tokio::sync::Mutex<Vec<(String, Option<String>, Value)>>>,
+) {
+    use axum::{
+        extract::State,
+        http::HeaderMap,
+        http::Uri,
+        routing::{get, post},
+        Json, Router,
+    };
+
+    type Calls = Arc<tokio::sync::Mutex<Vec<(String, Option<String>, Value)>>>;
+    let calls: Calls = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+    let app = Router::new()
+        .route(
+            "/projects/:project/merge_requests/:iid/notes",
+            post(
+                |State(calls): State<Calls>,
+                 uri: Uri,
+                 headers: HeaderMap,
+                 Json(body): Json<Value>| async move {
+                    let token = headers
+                        .get("private-token")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    calls
+                        .lock()
+                        .await
+                        .push((uri.path().to_string(), token, body));
+                    (axum::http::StatusCode::CREATED, Json(json!({"id": 1})))
+                },
+            ),
+        )
+        .route(
+            "/projects/:project/issues",
+            get(|| async {
+                Json(json!([
+                    {
+                        "iid": 1,
+                        "title": "Crash on empty cart",
+                        "description": "Steps in the log",
+                        "labels": ["bug"],
+                        "web_url": "https://gitlab.com/acme/widgets/-/issues/1",
+                    },
+                    {
+                        "iid": 2,
+                        "title": "Dark mode",
+                        "description": null,
+                        "labels": [],
+                        "web_url": "https://gitlab.com/acme/widgets/-/issues/2",
+                    },
+                ]))
+            }),
+        )
+        .with_state(calls.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}", addr), calls)
 }
 
 #[tokio::test]
-async fn test_question_reply_notifications() {
-    // When a reply is posted, the question asker (if agent) gets notified
+async fn test_gitlab_integration() {
     let s = TestServer::start().await;
+    let client = s.client();
+    let (api_url, calls) = start_mock_gitlab().await;
+    opengate::gitlab::set_api_url(&api_url);
 
-    // Register a second agent
-    let resp = s
-        .client()
-        .post(format!("{}/api/agents/register", s.base_url))
-        .json(&json!({
-            "name": "answerer-agent",
-            "setup_token": "test-setup-token",
-            "skills": ["devops"],
-            "capabilities": ["devops:docker"]
-        }))
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "name": "Widgets", "repo_url": "https://gitlab.com/acme/widgets" }))
         .send()
         .await
         .unwrap();
-    assert!(resp.status().is_success());
-    let agent_resp: Value = resp.json().await.unwrap();
-    let answerer_key = agent_resp["api_key"].as_str().unwrap().to_string();
-
-    let project = s.create_project("Reply Notification Project").await;
+    let project: Value = resp.json().await.unwrap();
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Reply Notification Task").await;
-    let task_id = task["id"].as_str().unwrap();
+    let gitlab_url = format!("{}/api/projects/{}/integrations/gitlab", s.base_url, pid);
+    let github_url = format!("{}/api/projects/{}/integrations/github", s.base_url, pid);
 
-    // First agent asks a question
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+    // Import needs a configured integration
+    let resp = client
+        .post(format!("{}/import", gitlab_url))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "How to deploy?" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
-
-    // Second agent replies
-    let resp = s
-        .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/replies",
-            s.base_url, task_id, q_id
-        ))
-        .header("Authorization", format!("Bearer {}", answerer_key))
-        .json(&json!({ "body": "Use docker compose up" }))
+    assert_eq!(resp.status(), 404);
+    let resp = client
+        .put(&gitlab_url)
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "token": "glpat-test", "gitlab_project": "acme/widgets" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.status(), 200);
+    let config: Value = resp.json().await.unwrap();
+    assert_eq!(config["has_token"], true);
+    assert_eq!(config["gitlab_project"], "acme/widgets");
+    assert!(config.get("token").is_none());
 
-    // First agent should have a notification about the reply
-    let resp = s
-        .client()
-        .get(format!(
-            "{}/api/agents/me/notifications?unread=true",
-            s.base_url
-        ))
+    // One forge per project
+    let resp = client
+        .put(&github_url)
         .header("Authorization", s.auth_header())
+        .json(&json!({ "token": "ghp_test" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let notifications: Vec<Value> = resp.json().await.unwrap();
-
-    let reply_notif = notifications.iter().find(|n| {
-        let event_type = n["event_type"].as_str().unwrap_or("");
-        event_type == "question_replied"
-    });
-    assert!(
-        reply_notif.is_some(),
-        "Asker should receive question_replied notification"
-    );
-}
-
-#[tokio::test]
-async fn test_question_resolved_notification() {
-    // When a question is resolved, the original asker gets notified
-    let s = TestServer::start().await;
+    assert_eq!(resp.status(), 409);
 
-    // Register a second agent to resolve the question
-    let resp = s
-        .client()
-        .post(format!("{}/api/agents/register", s.base_url))
-        .json(&json!({
-            "name": "resolver-agent",
-            "setup_token": "test-setup-token",
-            "skills": ["devops"]
-        }))
+    // Open issues become tasks once
+    let resp = client
+        .post(format!("{}/import", gitlab_url))
+        .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert!(resp.status().is_success());
-    let agent_resp: Value = resp.json().await.unwrap();
-    let resolver_key = agent_resp["api_key"].as_str().unwrap().to_string();
+    assert_eq!(resp.status(), 201);
+    let import: Value = resp.json().await.unwrap();
+    assert_eq!(import["imported"].as_array().unwrap().len(), 2);
+    assert_eq!(import["skipped"], 0);
+    let first = &import["imported"][0];
+    assert_eq!(first["title"], "Crash on empty cart");
+    assert_eq!(first["tags"], json!(["bug"]));
+    assert_eq!(first["context"]["external_ref"], "gitlab:acme/widgets#1");
+    let resp = client
+        .post(format!("{}/import", gitlab_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let import: Value = resp.json().await.unwrap();
+    assert_eq!(import["imported"], json!([]));
+    assert_eq!(import["skipped"], 2);
 
-    let project = s.create_project("Resolved Notification Project").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Resolved Notification Task").await;
+    // Completing with an mr_url attaches it and notes on the merge request
+    let task = s.create_ready_task(pid, "Fix login").await;
     let task_id = task["id"].as_str().unwrap();
-
-    // First agent asks a question
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/questions", s.base_url, task_id))
+    client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "question": "What DB should we use?" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let q: Value = resp.json().await.unwrap();
-    let q_id = q["id"].as_str().unwrap();
-
-    // Second agent resolves the question
-    let resp = s
-        .client()
-        .post(format!(
-            "{}/api/tasks/{}/questions/{}/resolve",
-            s.base_url, task_id, q_id
-        ))
-        .header("Authorization", format!("Bearer {}", resolver_key))
-        .json(&json!({ "resolution": "Use PostgreSQL" }))
+    let mr_url = "https://gitlab.com/acme/widgets/-/merge_requests/42";
+    let resp = client
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, task_id))
+        .header("Authorization", s.auth_header())
+        .json(&json!({ "summary": "Added a retry", "output": { "mr_url": mr_url } }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-
-    // First agent should have a question_resolved notification
-    let resp = s
-        .client()
-        .get(format!(
-            "{}/api/agents/me/notifications?unread=true",
-            s.base_url
-        ))
+    let artifacts: Value = client
+        .get(format!("{}/api/tasks/{}/artifacts", s.base_url, task_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let notifications: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(artifacts[0]["value"], mr_url);
+    assert_eq!(artifacts[0]["name"], "Merge request");
 
-    let resolved_notif = notifications.iter().find(|n| {
-        let event_type = n["event_type"].as_str().unwrap_or("");
-        event_type == "question_resolved"
-    });
-    assert!(
-        resolved_notif.is_some(),
-        "Asker should receive question_resolved notification"
+    let mut posted = Vec::new();
+    for _ in 0..50 {
+        posted = calls.lock().await.clone();
+        if !posted.is_empty() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    let (path, token, body) = &posted[0];
+    assert_eq!(path, "/projects/acme%2Fwidgets/merge_requests/42/notes");
+    assert_eq!(token.as_deref(), Some("glpat-test"));
+    assert_eq!(
+        body["body"],
+        "**Fix login** was completed by test-agent in OpenGate.\n\nAdded a retry"
     );
-}
-
-// ===== Dependency Enforcement =====
-
-#[tokio::test]
-async fn test_cannot_start_task_with_unmet_deps() {
-    let s = TestServer::start().await;
-    let proj = s.create_project("dep-enforce-test").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    let a = s.create_task(pid, "Task A (blocked)").await;
-    let b = s.create_task(pid, "Task B (blocker)").await;
-    let a_id = a["id"].as_str().unwrap();
-    let b_id = b["id"].as_str().unwrap();
 
-    // A depends on B
-    s.client()
-        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+    // An opened merge request naming a task in its branch moves the task to review
+    let resp = client
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "depends_on": [b_id] }))
+        .json(&json!({
+            "name": "Merge requests",
+            "action_type": "gitlab_merge_request",
+            "action_config": {},
+            "verification": "gitlab",
+            "signing_secret": "gl-secret",
+        }))
         .send()
         .await
         .unwrap();
-
-    // Move A to todo first (backlog → todo is always allowed)
-    let r = s
-        .client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    let trigger_url = format!(
+        "{}/api/webhooks/trigger/{}",
+        s.base_url,
+        created["trigger"]["id"].as_str().unwrap()
+    );
+    let task = s.create_ready_task(pid, "Add search").await;
+    let task_id = task["id"].as_str().unwrap();
+    client
+        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
         .send()
         .await
         .unwrap();
-    assert!(r.status().is_success(), "backlog→todo should succeed");
-
-    // Try to move A to in_progress — should fail (B not done)
-    let resp = s
-        .client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
-        .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "in_progress" }))
+    let merge_request = |action: &str, draft: bool| {
+        json!({
+            "object_kind": "merge_request",
+            "object_attributes": {
+                "iid": 43,
+                "action": action,
+                "url": "https://gitlab.com/acme/widgets/-/merge_requests/43",
+                "title": "Add search",
+                "description": "",
+                "draft": draft,
+                "source_branch": format!("agent/{}", task_id),
+            },
+        })
+    };
+    let resp = client
+        .post(&trigger_url)
+        .header("X-Gitlab-Token", "wrong")
+        .json(&merge_request("open", false))
         .send()
         .await
         .unwrap();
-    assert_eq!(
-        resp.status(),
-        409,
-        "Should reject with 409 Conflict when deps unmet"
-    );
-    let body: Value = resp.json().await.unwrap();
-    assert!(body["error"]
-        .as_str()
+    assert_eq!(resp.status(), 401);
+    let result: Value = client
+        .post(&trigger_url)
+        .header("X-Gitlab-Token", "gl-secret")
+        .json(&merge_request("open", true))
+        .send()
+        .await
         .unwrap()
-        .contains("dependencies not met"));
-}
-
-#[tokio::test]
-async fn test_can_start_task_once_deps_done() {
-    let s = TestServer::start().await;
-    let proj = s.create_project("dep-enforce-start").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    let a = s.create_task(pid, "Task A").await;
-    let b = s.create_task(pid, "Task B").await;
-    let a_id = a["id"].as_str().unwrap();
-    let b_id = b["id"].as_str().unwrap();
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(result["ignored"], true);
+    let result: Value = client
+        .post(&trigger_url)
+        .header("X-Gitlab-Token", "gl-secret")
+        .json(&merge_request("open", false))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(result["task_id"], task_id);
+    assert_eq!(result["status"], "review");
+    assert_eq!(s.get_task(task_id).await["status"], "review");
 
-    // A depends on B
-    s.client()
-        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+    let resp = client
+        .delete(&gitlab_url)
         .header("Authorization", s.auth_header())
-        .json(&json!({ "depends_on": [b_id] }))
         .send()
         .await
         .unwrap();
-
-    // Complete B: backlog → todo → in_progress → done
-    for status in &["todo", "in_progress", "done"] {
-        let r = s
-            .client()
-            .patch(format!("{}/api/tasks/{}", s.base_url, b_id))
-            .header("Authorization", s.auth_header())
-            .json(&json!({ "status": status }))
-            .send()
-            .await
-            .unwrap();
-        assert!(
-            r.status().is_success(),
-            "B transition to {} failed: {}",
-            status,
-            r.status()
-        );
-    }
-
-    // Now A should be auto-unblocked to todo; can move to in_progress
-    let resp = s
-        .client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, a_id))
+    assert_eq!(resp.status(), 204);
+    let resp = client
+        .put(&github_url)
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "in_progress" }))
+        .json(&json!({ "token": "ghp_test" }))
         .send()
         .await
         .unwrap();
-    assert_eq!(
-        resp.status(),
-        200,
-        "Should allow in_progress once dep is done"
-    );
+    assert_eq!(resp.status(), 200);
 }
 
 #[tokio::test]
-async fn test_assign_with_pending_deps_adds_warning() {
+async fn test_jira_import() {
     let s = TestServer::start().await;
-    let proj = s.create_project("assign-dep-warn").await;
-    let pid = proj["id"].as_str().unwrap();
-
-    let a = s.create_task(pid, "Task A").await;
-    let b = s.create_task(pid, "Task B (blocker)").await;
-    let a_id = a["id"].as_str().unwrap();
-    let b_id = b["id"].as_str().unwrap();
+    let client = s.client();
+    let project = s.create_project("Jira").await;
+    let pid = project["id"].as_str().unwrap();
+    let url = format!("{}/api/projects/{}/import/jira", s.base_url, pid);
+    let export = "Summary,Issue key,Status,Priority,Labels,Labels,Outward issue link (Blocks),Inward issue link (Relates)\n\
+                  Design login,WEB-1,Done,Highest,auth,ui,WEB-2,\n\
+                  Build login,WEB-2,In Review,Low,auth,,,WEB-1\n\
+                  Polish,WEB-3,Triage,P7,,,WEB-9,\n";
 
-    // A depends on B (not done)
-    s.client()
-        .post(format!("{}/api/tasks/{}/dependencies", s.base_url, a_id))
+    // Dry run: the mapping, nothing created
+    let resp = client
+        .post(format!("{}?dry_run=true", url))
+        .header("Authorization", s.auth_header())
+        .header("Content-Type", "text/csv")
+        .body(export)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let plan: Value = resp.json().await.unwrap();
+    assert_eq!(plan["dry_run"], true);
+    assert_eq!(plan["issues"][0]["status"], "done");
+    assert_eq!(plan["issues"][0]["priority"], "critical");
+    assert_eq!(plan["issues"][0]["tags"], json!(["auth", "ui"]));
+    assert_eq!(plan["issues"][1]["status"], "review");
+    assert_eq!(plan["issues"][2]["status"], "backlog");
+    assert!(plan["issues"][0]["task_id"].is_null());
+    assert_eq!(
+        plan["dependencies"],
+        json!([{"key": "WEB-2", "depends_on": "WEB-1"}])
+    );
+    // Unknown status, unknown priority, link outside the export
+    assert_eq!(plan["warnings"].as_array().unwrap().len(), 3);
+    let tasks: Value = client
+        .get(format!("{}/api/projects/{}/tasks", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "depends_on": [b_id] }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
+    assert_eq!(tasks.as_array().unwrap().len(), 0);
 
-    // Assign A to the test agent (which exists in the test server)
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/assign", s.base_url, a_id))
+    let resp = client
+        .post(&url)
         .header("Authorization", s.auth_header())
-        .json(&json!({ "agent_id": s.agent_id() }))
+        .header("Content-Type", "text/csv")
+        .body(export)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let result: Value = resp.json().await.unwrap();
+    let design_id = result["issues"][0]["task_id"].as_str().unwrap();
+    let build_id = result["issues"][1]["task_id"].as_str().unwrap();
+    let design = s.get_task(design_id).await;
+    assert_eq!(design["status"], "done");
+    assert_eq!(design["context"]["external_ref"], "jira:WEB-1");
+    let build = s.get_task(build_id).await;
+    assert_eq!(build["status"], "review");
+    assert_eq!(build["priority"], "low");
+    let deps: Value = client
+        .get(format!(
+            "{}/api/tasks/{}/dependencies",
+            s.base_url, build_id
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(deps[0]["id"], design_id);
+
+    // A JSON export that grew: old issues are skipped, links to them still resolve
+    let export = json!({
+        "issues": [
+            {"key": "WEB-1", "fields": {"summary": "Design login", "status": {"name": "Done"}}},
+            {
+                "key": "WEB-4",
+                "fields": {
+                    "summary": "Login docs",
+                    "description": {
+                        "type": "doc",
+                        "content": [{"type": "paragraph", "content": [{"type": "text", "text": "Write them"}]}]
+                    },
+                    "status": {"name": "Ready", "statusCategory": {"key": "new"}},
+                    "priority": {"name": "Medium"},
+                    "labels": ["docs"],
+                    "issuelinks": [
+                        {"type": {"name": "Blocks"}, "inwardIssue": {"key": "WEB-2"}}
+                    ]
+                }
+            }
+        ]
+    });
+    let resp = client
+        .post(&url)
+        .header("Authorization", s.auth_header())
+        .json(&export)
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 201);
+    let result: Value = resp.json().await.unwrap();
+    assert_eq!(result["skipped"], json!(["WEB-1"]));
+    assert_eq!(result["issues"].as_array().unwrap().len(), 1);
+    let docs = s
+        .get_task(result["issues"][0]["task_id"].as_str().unwrap())
+        .await;
+    assert_eq!(docs["status"], "todo");
+    assert_eq!(docs["description"], "Write them");
     assert_eq!(
-        resp.status(),
-        200,
-        "Assign should succeed even with unmet deps"
+        result["dependencies"],
+        json!([{"key": "WEB-4", "depends_on": "WEB-2"}])
     );
 
-    // Check activity log has warning
-    let activity_resp = s
-        .client()
-        .get(format!("{}/api/tasks/{}/activity", s.base_url, a_id))
+    let resp = client
+        .post(&url)
         .header("Authorization", s.auth_header())
+        .header("Content-Type", "text/csv")
+        .body("Summary\nNo key\n")
         .send()
         .await
         .unwrap();
-    assert_eq!(activity_resp.status(), 200);
-    let activity: Value = activity_resp.json().await.unwrap();
-    let has_warning = activity
-        .as_array()
-        .unwrap()
-        .iter()
-        .any(|a| a["content"].as_str().unwrap_or("").contains("unmet dep"));
-    assert!(has_warning, "Activity should contain dep warning");
+    assert_eq!(resp.status(), 422);
 }
 
-// --- Start Review tests ---
+async fn start_mock_linear() -> (
+    String,
+    Arc<tokio::sync::Mutex<Vec<(Option<String>, Value)>>>,
+) {
+    use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+
+    type Calls = Arc<tokio::sync::Mutex<Vec<(Option<String>, Value)>>>;
+    let calls: Calls = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+    let app = Router::new()
+        .route(
+            "/graphql",
+            post(
+                |State(calls): State<Calls>, headers: HeaderMap, Json(body): Json<Value>| async move {
+                    let auth = headers
+                        .get("authorization")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    let query = body["query"].as_str().unwrap_or_default().to_string();
+                    calls.lock().await.push((auth, body));
+                    let data = if query.contains("query Issues") {
+                        json!({"issues": {
+                            "nodes": [
+                                {
+                                    "identifier": "ENG-1",
+                                    "title": "Fix login",
+                                    "state": {"name": "In Progress", "type": "started"},
+                                    "labels": {"nodes": []},
+                                },
+                                {
+                                    "identifier": "ENG-3",
+                                    "title": "Rate limits",
+                                    "description": "Per key",
+                                    "url": "https://linear.app/acme/issue/ENG-3",
+                                    "priorityLabel": "Urgent",
+                                    "state": {"name": "Ready", "type": "unstarted"},
+                                    "labels": {"nodes": [{"name": "api"}]},
+                                },
+                            ],
+                            "pageInfo": {"hasNextPage": false, "endCursor": null},
+                        }})
+                    } else if query.contains("query CompletedState") {
+                        json!({"issue": {
+                            "id": "issue-uuid-3",
+                            "team": {"states": {"nodes": [{"id": "state-done"}]}},
+                        }})
+                    } else {
+                        json!({
+                            "issueUpdate": {"success": true},
+                            "commentCreate": {"success": true},
+                        })
+                    };
+                    Json(json!({ "data": data }))
+                },
+            ),
+        )
+        .with_state(calls.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/graphql", addr), calls)
+}
 
-// Test start-review: sets started_review_at, only reviewer allowed, must be in review status
 #[tokio::test]
-async fn test_start_review_sets_timestamp() {
+async fn test_linear_import_and_export() {
     let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Start Review").await;
+    let client = s.client();
+    let (api_url, calls) = start_mock_linear().await;
+    opengate::linear::set_api_url(&api_url);
+    let project = s.create_project("Linear").await;
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_ready_task(pid, "Test Start Review").await;
-    let task_id = task["id"].as_str().unwrap();
+    let url = format!("{}/api/projects/{}/import/linear", s.base_url, pid);
 
-    // Claim (todo -> in_progress)
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+    // Without a CSV body, import needs the API integration
+    let resp = client
+        .post(&url)
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 422);
 
-    // Move to review and set reviewer to our test agent
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+    let export = "ID,Team,Title,Description,Status,Priority,Labels\n\
+                  ENG-1,Engineering,Fix login,Users get logged out,In Progress,High,\"Bug, Auth\"\n\
+                  ENG-2,Engineering,Old idea,,Canceled,Someday,\n";
+    let resp = client
+        .post(format!("{}?dry_run=true", url))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "review", "reviewer_type": "agent", "reviewer_id": s.agent_id() }))
+        .header("Content-Type", "text/csv")
+        .body(export)
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 200);
+    let plan: Value = resp.json().await.unwrap();
+    assert_eq!(plan["issues"][0]["status"], "in_progress");
+    assert_eq!(plan["issues"][0]["priority"], "high");
+    assert_eq!(plan["issues"][0]["tags"], json!(["Bug", "Auth"]));
+    assert_eq!(plan["issues"][1]["status"], "cancelled");
+    assert!(plan["warnings"][0]
+        .as_str()
+        .unwrap()
+        .contains("priority 'Someday'"));
 
-    // Verify started_review_at is null before start-review
-    let resp = s
-        .client()
-        .get(format!("{}/api/tasks/{}", s.base_url, task_id))
+    let resp = client
+        .post(&url)
         .header("Authorization", s.auth_header())
+        .header("Content-Type", "text/csv")
+        .body(export)
         .send()
         .await
         .unwrap();
-    let body: Value = resp.json().await.unwrap();
-    assert!(
-        body["started_review_at"].is_null(),
-        "started_review_at should be null initially"
-    );
+    assert_eq!(resp.status(), 201);
+    let result: Value = resp.json().await.unwrap();
+    let login = s
+        .get_task(result["issues"][0]["task_id"].as_str().unwrap())
+        .await;
+    assert_eq!(login["status"], "in_progress");
+    assert_eq!(login["context"]["external_ref"], "linear:ENG-1");
 
-    // Start review
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/start-review", s.base_url, task_id))
+    // From the API: ENG-1 is already here
+    let resp = client
+        .put(format!(
+            "{}/api/projects/{}/integrations/linear",
+            s.base_url, pid
+        ))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "token": "lin_api_test", "team": "ENG" }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "review", "Status should remain review");
-    assert!(
-        body["started_review_at"].is_string(),
-        "started_review_at should be set"
+    let config: Value = resp.json().await.unwrap();
+    assert_eq!(config["has_token"], true);
+    assert_eq!(config["sync_on_complete"], true);
+    let resp = client
+        .post(&url)
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let result: Value = resp.json().await.unwrap();
+    assert_eq!(result["skipped"], json!(["ENG-1"]));
+    assert_eq!(result["issues"][0]["identifier"], "ENG-3");
+    assert_eq!(result["issues"][0]["priority"], "critical");
+    let task_id = result["issues"][0]["task_id"].as_str().unwrap();
+    let task = s.get_task(task_id).await;
+    assert_eq!(task["status"], "todo");
+    assert_eq!(
+        task["context"]["issue_url"],
+        "https://linear.app/acme/issue/ENG-3"
     );
-}
-
-// Test start-review: must be in review status
-#[tokio::test]
-async fn test_start_review_requires_review_status() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Start Review Status").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_ready_task(pid, "Test Start Review Status").await;
-    let task_id = task["id"].as_str().unwrap();
+    {
+        let calls = calls.lock().await;
+        assert_eq!(calls[0].0.as_deref(), Some("lin_api_test"));
+        assert_eq!(calls[0].1["variables"]["team"], "ENG");
+    }
 
-    // Claim (todo -> in_progress)
-    s.client()
+    // Completing it completes the Linear issue
+    client
         .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-
-    // Try start-review on in_progress task (should fail)
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/start-review", s.base_url, task_id))
+    let resp = client
+        .post(format!("{}/api/tasks/{}/complete", s.base_url, task_id))
         .header("Authorization", s.auth_header())
+        .json(&json!({ "summary": "Token bucket per key" }))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 200);
+    let mut mutation = None;
+    for _ in 0..50 {
+        mutation = calls
+            .lock()
+            .await
+            .iter()
+            .find(|(_, body)| {
+                body["query"]
+                    .as_str()
+                    .unwrap()
+                    .contains("mutation Complete")
+            })
+            .cloned();
+        if mutation.is_some() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    let (_, body) = mutation.expect("issue completed in Linear");
+    assert_eq!(body["variables"]["id"], "issue-uuid-3");
+    assert_eq!(body["variables"]["stateId"], "state-done");
     assert_eq!(
-        resp.status(),
-        400,
-        "Should reject start-review when not in review status"
+        body["variables"]["body"],
+        "Completed in OpenGate by test-agent.\n\nToken bucket per key"
     );
 }
 
-// Test start-review: non-reviewer gets 403
 #[tokio::test]
-async fn test_start_review_non_reviewer_gets_403() {
+async fn test_trello_import() {
     let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Non Reviewer").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_ready_task(pid, "Test Non Reviewer").await;
-    let task_id = task["id"].as_str().unwrap();
+    let client = s.client();
+    let board = json!({
+        "id": "b1",
+        "name": "Roadmap",
+        "desc": "Q3 plans",
+        "lists": [
+            {"id": "l1", "name": "Icebox", "pos": 1},
+            {"id": "l2", "name": "Doing", "pos": 2},
+            {"id": "l3", "name": "Shipped", "pos": 3},
+        ],
+        "cards": [
+            {
+                "id": "c1",
+                "name": "Search",
+                "desc": "Full text",
+                "idList": "l2",
+                "pos": 1,
+                "due": "2026-12-01T00:00:00.000Z",
+                "labels": [{"name": "api", "color": "green"}],
+                "attachments": [{"name": "Spec", "url": "https://docs.example.com/search"}],
+                "url": "https://trello.com/c/abc/1-search",
+            },
+            {"id": "c2", "name": "Themes", "idList": "l1", "pos": 1},
+            {"id": "c3", "name": "Login", "idList": "l3", "pos": 1},
+            {"id": "c4", "name": "Archived", "idList": "l1", "closed": true},
+        ],
+        "checklists": [
+            {"id": "k1", "name": "Steps", "idCard": "c1", "checkItems": [
+                {"name": "Index", "state": "complete", "pos": 1},
+                {"name": "Query", "state": "incomplete", "pos": 2},
+            ]},
+        ],
+    });
+    let url = format!("{}/api/import/trello", s.base_url);
 
-    // Register a second agent to be the reviewer
-    let resp = s
-        .client()
-        .post(format!("{}/api/agents/register", s.base_url))
+    let resp = client
+        .post(format!("{}?dry_run=true", url))
         .header("Authorization", s.auth_header())
-        .json(&json!({
-            "name": "reviewer-agent",
-            "skills": ["rust"],
-            "setup_token": "test-setup-token"
-        }))
+        .json(&board)
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 201);
-    let reviewer: Value = resp.json().await.unwrap();
-    let reviewer_id = reviewer["agent"]["id"].as_str().unwrap();
+    assert_eq!(resp.status(), 200);
+    let plan: Value = resp.json().await.unwrap();
+    assert!(plan["project"].is_null());
+    assert_eq!(plan["checklists"], "artifacts");
+    let statuses: Vec<&str> = plan["lists"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| l["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, vec!["backlog", "in_progress", "done"]);
+    assert_eq!(plan["cards"].as_array().unwrap().len(), 3);
 
-    // Claim (backlog -> in_progress)
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+    // Checklists as artifacts
+    let resp = client
+        .post(&url)
         .header("Authorization", s.auth_header())
+        .json(&board)
         .send()
         .await
         .unwrap();
-
-    // Move to review with reviewer-agent as reviewer
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+    assert_eq!(resp.status(), 201);
+    let result: Value = resp.json().await.unwrap();
+    assert_eq!(result["project"]["name"], "Roadmap");
+    let search_id = result["cards"][1]["task_id"].as_str().unwrap();
+    let search = s.get_task(search_id).await;
+    assert_eq!(search["status"], "in_progress");
+    assert_eq!(search["tags"], json!(["api"]));
+    assert_eq!(
+        search["context"]["card_url"],
+        "https://trello.com/c/abc/1-search"
+    );
+    let artifacts: Value = client
+        .get(format!("{}/api/tasks/{}/artifacts", s.base_url, search_id))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "review", "reviewer_type": "agent", "reviewer_id": reviewer_id }))
         .send()
         .await
-        .unwrap();
-
-    // Try start-review as test-agent (not the reviewer) — should get 403
-    let resp = s
-        .client()
-        .post(format!("{}/api/tasks/{}/start-review", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
-        .send()
+        .unwrap()
+        .json()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 403, "Non-reviewer should get 403");
-    let body: Value = resp.json().await.unwrap();
-    assert!(body["error"]
-        .as_str()
+    let artifacts: Vec<(&str, &str)> = artifacts
+        .as_array()
         .unwrap()
-        .contains("Only the assigned reviewer"));
-}
-
-// Test review_task_count appears in agent response
-#[tokio::test]
-async fn test_review_task_count_in_agent_response() {
-    let s = TestServer::start().await;
-    let project = s.create_project("Test Project - Review Count").await;
-    let pid = project["id"].as_str().unwrap();
-
-    // Create a task, claim it, move to review with our agent as reviewer
-    let task = s.create_ready_task(pid, "Task for Review Count").await;
-    let task_id = task["id"].as_str().unwrap();
+        .iter()
+        .map(|a| {
+            (
+                a["artifact_type"].as_str().unwrap(),
+                a["value"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert!(artifacts.contains(&("url", "https://docs.example.com/search")));
+    assert!(artifacts.contains(&("text", "- [x] Index\n- [ ] Query")));
+    let login = s
+        .get_task(result["cards"][2]["task_id"].as_str().unwrap())
+        .await;
+    assert_eq!(login["status"], "done");
 
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+    // Checklists as subtasks, with a status override
+    let resp = client
+        .post(&url)
         .header("Authorization", s.auth_header())
+        .json(&json!({
+            "board": board,
+            "name": "Roadmap v2",
+            "status_map": {"Icebox": "todo"},
+            "checklists": "subtasks",
+        }))
         .send()
         .await
         .unwrap();
-
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+    assert_eq!(resp.status(), 201);
+    let result: Value = resp.json().await.unwrap();
+    assert_eq!(result["project"]["name"], "Roadmap v2");
+    assert_eq!(result["cards"][0]["status"], "todo");
+    let search_id = result["cards"][1]["task_id"].as_str().unwrap();
+    let subtasks = result["cards"][1]["subtask_ids"].as_array().unwrap();
+    assert_eq!(subtasks.len(), 2);
+    let index = s.get_task(subtasks[0].as_str().unwrap()).await;
+    assert_eq!(index["title"], "Index");
+    assert_eq!(index["status"], "done");
+    assert_eq!(index["context"]["parent_task_id"], search_id);
+    assert_eq!(
+        s.get_task(subtasks[1].as_str().unwrap()).await["status"],
+        "todo"
+    );
+    let deps: Value = client
+        .get(format!(
+            "{}/api/tasks/{}/dependencies",
+            s.base_url, search_id
+        ))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "review", "reviewer_type": "agent", "reviewer_id": s.agent_id() }))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
+    assert_eq!(deps.as_array().unwrap().len(), 2);
 
-    // Fetch the agent and check review_task_count
-    let resp = s
-        .client()
-        .get(format!("{}/api/agents/{}", s.base_url, s.agent_id()))
+    let resp = client
+        .post(&url)
         .header("Authorization", s.auth_header())
+        .json(&json!({"board": board, "status_map": {"Nope": "todo"}}))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let agent: Value = resp.json().await.unwrap();
-    assert_eq!(
-        agent["review_task_count"], 1,
-        "Agent should have 1 review task"
-    );
-    // current_task_count should be 0 since task moved to review (not in_progress)
-    assert_eq!(
-        agent["current_task_count"], 0,
-        "current_task_count should be 0 (task is in review, not in_progress)"
-    );
+    assert_eq!(resp.status(), 422);
 }
 
-// Pre-assigned task: assign then claim should transition to in_progress
 #[tokio::test]
-async fn test_claim_preassigned_task() {
+async fn test_email_task_trigger() {
     let s = TestServer::start().await;
-
-    // Heartbeat so agent is online
-    s.client()
-        .post(format!("{}/api/agents/heartbeat", s.base_url))
+    let proj = s.create_project("email-inbox").await;
+    let pid = proj["id"].as_str().unwrap();
+    let trigger = create_trigger(
+        &s,
+        pid,
+        "email_task",
+        json!({
+            "address": "Support@gate.example.com",
+            "allowed_senders": ["@acme.com"],
+            "tags": ["support"],
+            "initial_status": "todo",
+        }),
+    )
+    .await;
+
+    let (status, result) = fire_trigger(
+        &s,
+        &trigger,
+        json!({
+            "FromFull": {"Email": "ada@acme.com", "Name": "Ada"},
+            "ToFull": [{"Email": "support@gate.example.com"}],
+            "Subject": "Checkout is slow",
+            "TextBody": "Since this morning.",
+            "MessageID": "m-1",
+            "Attachments": [
+                {"Name": "trace.txt", "ContentType": "text/plain", "Content": "c2xvdw=="},
+            ],
+        }),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(result["status"], "todo");
+    assert_eq!(result["attachments"], 1);
+    let task = s.get_task(result["task_id"].as_str().unwrap()).await;
+    assert_eq!(task["title"], "Checkout is slow");
+    assert_eq!(task["description"], "Since this morning.");
+    assert_eq!(task["tags"], json!(["support"]));
+    assert_eq!(task["context"]["email"]["from"], "ada@acme.com");
+    assert_eq!(task["context"]["email"]["from_name"], "Ada");
+    assert_eq!(task["context"]["external_ref"], "email:m-1");
+    let artifacts: Value = s
+        .client()
+        .get(format!(
+            "{}/api/tasks/{}/artifacts",
+            s.base_url,
+            task["id"].as_str().unwrap()
+        ))
         .header("Authorization", s.auth_header())
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
+    assert_eq!(artifacts[0]["name"], "trace.txt");
+    assert_eq!(artifacts[0]["artifact_type"], "file");
+    assert_eq!(artifacts[0]["value"], "data:text/plain;base64,c2xvdw==");
 
-    let project = s.create_project("Test Project - Pre-assigned Claim").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Test Pre-assigned Claim").await;
-    let task_id = task["id"].as_str().unwrap();
+    // A stranger, or mail for another address, is ignored
+    for payload in [
+        json!({"from": "eve@evil.com", "to": "support@gate.example.com", "subject": "Hi"}),
+        json!({"from": "ada@acme.com", "to": "sales@gate.example.com", "subject": "Hi"}),
+        json!({"event": "not an email"}),
+    ] {
+        let (status, result) = fire_trigger(&s, &trigger, payload).await;
+        assert_eq!(status, 200);
+        assert_eq!(result["ignored"], true);
+    }
 
-    // Step 1: Assign to agent (status stays "todo")
     let resp = s
         .client()
-        .post(format!("{}/api/tasks/{}/assign", s.base_url, task_id))
+        .post(format!("{}/api/projects/{}/triggers", s.base_url, pid))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "agent_id": s.agent_id() }))
+        .json(&json!({
+            "name": "bad",
+            "action_type": "email_task",
+            "action_config": {"allowed_senders": "@acme.com"},
+        }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(body["status"], "todo", "assign should keep status as todo");
-    assert_eq!(body["assignee_id"].as_str().unwrap(), s.agent_id());
+    assert_eq!(resp.status(), 422);
+}
 
-    // Step 2: Agent claims the pre-assigned task → should transition to in_progress
-    let resp = s
+#[tokio::test]
+async fn test_commit_references_trigger() {
+    let s = TestServer::start().await;
+    let proj = s.create_project("commit-refs").await;
+    let pid = proj["id"].as_str().unwrap();
+    let task = s.create_ready_task(pid, "Fix login").await;
+    let task_id = task["id"].as_str().unwrap();
+    let trigger = create_trigger(
+        &s,
+        pid,
+        "commit_references",
+        json!({"review_on_merge": true}),
+    )
+    .await;
+
+    let (status, result) = fire_trigger(
+        &s,
+        &trigger,
+        json!({
+            "ref": "refs/heads/main",
+            "commits": [
+                {
+                    "id": "a1b2c3d4e5f6",
+                    "message": format!("Fix login (OG-{})\n\nDetails", &task_id[..8]),
+                    "url": "https://github.com/acme/app/commit/a1b2c3d4e5f6",
+                },
+                {"id": "ffff", "message": "Tweak OG-deadbeef", "url": "https://github.com/acme/app/commit/ffff"},
+                {"id": "eeee", "message": "No refs", "url": "https://github.com/acme/app/commit/eeee"},
+            ],
+        }),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(result["linked"].as_array().unwrap().len(), 1);
+    assert_eq!(result["unmatched"], json!(["OG-deadbeef"]));
+
+    let artifacts: Value = s
         .client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
+        .get(format!("{}/api/tasks/{}/artifacts", s.base_url, task_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
+    assert_eq!(artifacts[0]["name"], "Commit a1b2c3d");
     assert_eq!(
-        body["status"], "in_progress",
-        "claim on pre-assigned task should transition to in_progress"
+        artifacts[0]["value"],
+        "https://github.com/acme/app/commit/a1b2c3d4e5f6"
     );
 
-    // Step 3: Claim again → idempotent, still in_progress
-    let resp = s
-        .client()
+    s.client()
         .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
         .header("Authorization", s.auth_header())
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-    let body: Value = resp.json().await.unwrap();
-    assert_eq!(
-        body["status"], "in_progress",
-        "second claim should be idempotent"
-    );
-}
-
-// ---------------------------------------------------------------------------
-// WebSocket integration tests
-// ---------------------------------------------------------------------------
-
-// WS Auth: valid API key → auth_ok with identity
-#[tokio::test]
-async fn test_ws_auth_valid() {
-    let s = TestServer::start().await;
-    let (ws, _) = tokio_tungstenite::connect_async(&s.ws_url())
-        .await
-        .expect("WS connect failed");
-    let (mut sink, mut stream) = ws.split();
-
-    let auth_msg = json!({"type": "auth", "token": s.api_key}).to_string();
-    sink.send(WsMessage::Text(auth_msg.into())).await.unwrap();
-
-    let resp = recv_json(&mut stream, 2000)
-        .await
-        .expect("expected auth_ok");
-    assert_eq!(resp["type"], "auth_ok");
-    assert_eq!(resp["identity"]["type"], "agent");
-    assert_eq!(resp["identity"]["id"], s.agent_id());
-    assert!(resp["identity"]["name"].is_string());
-}
-
-// WS Auth: invalid API key → auth_failed error
-#[tokio::test]
-async fn test_ws_auth_invalid() {
-    let s = TestServer::start().await;
-    let (ws, _) = tokio_tungstenite::connect_async(&s.ws_url())
-        .await
-        .expect("WS connect failed");
-    let (mut sink, mut stream) = ws.split();
+    assert_eq!(s.get_task(task_id).await["status"], "in_progress");
 
-    let auth_msg = json!({"type": "auth", "token": "bad-key-12345"}).to_string();
-    sink.send(WsMessage::Text(auth_msg.into())).await.unwrap();
+    let pr = json!({
+        "html_url": "https://github.com/acme/app/pull/7",
+        "number": 7,
+        "title": "Login fix",
+        "body": format!("Closes {}", task_id),
+        "head": {"ref": "fix-login"},
+        "merged": true,
+    });
+    let (status, result) = fire_trigger(
+        &s,
+        &trigger,
+        json!({"action": "closed", "pull_request": pr}),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(result["moved_to_review"], json!([task_id]));
+    assert_eq!(s.get_task(task_id).await["status"], "review");
 
-    let resp = recv_json(&mut stream, 2000).await.expect("expected error");
-    assert_eq!(resp["type"], "error");
-    assert_eq!(resp["code"], "auth_failed");
+    let (_, result) = fire_trigger(&s, &trigger, json!({"zen": "ping"})).await;
+    assert_eq!(result["ignored"], true);
 }
 
-// WS Auth: subscribe before auth → auth_required error
 #[tokio::test]
-async fn test_ws_auth_required() {
-    let s = TestServer::start().await;
-    let (ws, _) = tokio_tungstenite::connect_async(&s.ws_url())
-        .await
-        .expect("WS connect failed");
-    let (mut sink, mut stream) = ws.split();
-
-    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
-    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
-
-    let resp = recv_json(&mut stream, 2000).await.expect("expected error");
-    assert_eq!(resp["type"], "error");
-    assert_eq!(resp["code"], "auth_required");
-}
+async fn test_cli_client() {
+    use opengate::client::{self, Client};
 
-// WS Event roundtrip: subscribe task.*, create task via REST, receive event
-#[tokio::test]
-async fn test_ws_event_roundtrip() {
     let s = TestServer::start().await;
-    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
-
-    // Subscribe to task.*
-    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
-    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
-    let resp = recv_json(&mut stream, 2000)
-        .await
-        .expect("expected subscribed");
-    assert_eq!(resp["type"], "subscribed");
-    assert_eq!(resp["id"], "sub-1");
-
-    // Create a task via REST → triggers task.created event
-    let project = s.create_project("WS Roundtrip").await;
+    let project = s.create_project("CLI").await;
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "WS Test Task").await;
+    let task = s.create_ready_task(pid, "Drive me from a terminal").await;
     let task_id = task["id"].as_str().unwrap();
+    s.create_task(pid, "Still in backlog").await;
 
-    // Should receive the event
-    let event = recv_json(&mut stream, 2000).await.expect("expected event");
-    assert_eq!(event["type"], "event");
-    assert_eq!(event["sub"], "sub-1");
-    assert_eq!(event["event"], "task.created");
-    assert_eq!(event["data"]["id"].as_str().unwrap(), task_id);
-}
-
-// WS Multiple subscribers: two clients both receive the same event
-#[tokio::test]
-async fn test_ws_multiple_subscribers() {
-    let s = TestServer::start().await;
-
-    // Connect two WS clients
-    let (mut sink1, mut stream1) = ws_auth(&s.ws_url(), &s.api_key).await;
-    let (mut sink2, mut stream2) = ws_auth(&s.ws_url(), &s.api_key).await;
-
-    // Both subscribe to task.*
-    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
-    sink1
-        .send(WsMessage::Text(sub_msg.clone().into()))
+    let cli = Client::new(&s.base_url, Some(s.api_key.clone()));
+    let todo = cli
+        .list_tasks(&opengate_models::TaskFilters {
+            status: Some("todo".to_string()),
+            ..Default::default()
+        })
         .await
         .unwrap();
-    let r1 = recv_json(&mut stream1, 2000).await.unwrap();
-    assert_eq!(r1["type"], "subscribed");
-
-    sink2.send(WsMessage::Text(sub_msg.into())).await.unwrap();
-    let r2 = recv_json(&mut stream2, 2000).await.unwrap();
-    assert_eq!(r2["type"], "subscribed");
-
-    // Create task via REST
-    let project = s.create_project("WS Multi Sub").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Multi Sub Task").await;
-    let task_id = task["id"].as_str().unwrap();
+    assert_eq!(todo.len(), 1);
+    let table = client::task_table(&todo);
+    assert!(table.starts_with("ID"));
+    assert!(table.contains(&task_id[..8]));
 
-    // Both clients should receive the event
-    let ev1 = recv_json(&mut stream1, 2000)
+    // Abbreviated ids, as the table prints them
+    let claimed = cli.claim_task(&task_id[..8]).await.unwrap();
+    assert_eq!(claimed.id, task_id);
+    assert_eq!(claimed.assignee_id.as_deref(), Some(s.agent_id()));
+    let done = cli
+        .complete_task(task_id, Some("Done from the CLI"))
         .await
-        .expect("client 1 should receive event");
-    assert_eq!(ev1["event"], "task.created");
-    assert_eq!(ev1["data"]["id"].as_str().unwrap(), task_id);
+        .unwrap();
+    assert_eq!(done.status, "done");
+    assert!(cli
+        .get_task("ffffffff")
+        .await
+        .unwrap_err()
+        .contains("No task"));
 
-    let ev2 = recv_json(&mut stream2, 2000)
+    let anonymous = Client::new(&s.base_url, None);
+    assert!(anonymous
+        .list_tasks(&Default::default())
         .await
-        .expect("client 2 should receive event");
-    assert_eq!(ev2["event"], "task.created");
-    assert_eq!(ev2["data"]["id"].as_str().unwrap(), task_id);
+        .unwrap_err()
+        .contains("No API key"));
+    let (agent_id, api_key) = anonymous
+        .register_agent("cli-agent", &["ops".to_string()], "test-setup-token")
+        .await
+        .unwrap();
+    assert!(!agent_id.is_empty());
+    let registered = Client::new(&s.base_url, Some(api_key));
+    assert_eq!(
+        registered
+            .list_tasks(&Default::default())
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+    let err = anonymous
+        .register_agent("nope", &[], "wrong")
+        .await
+        .unwrap_err();
+    assert!(
+        err.contains("Invalid setup token") && err.contains("403"),
+        "{err}"
+    );
 }
 
-// WS No subscribers: creating a task with no WS clients doesn't panic
 #[tokio::test]
-async fn test_ws_no_subscribers_no_panic() {
+async fn test_tui_dashboard_data() {
+    use opengate::client::Client;
+    use opengate::tui::{Dashboard, EventStream};
+
     let s = TestServer::start().await;
-    // No WS clients — just create a task via REST
-    let project = s.create_project("No Subscribers").await;
+    let project = s.create_project("Ops board").await;
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "No Subscriber Task").await;
+    let cli = Client::new(&s.base_url, Some(s.api_key.clone()));
+
+    let mut events = EventStream::connect(&cli, pid).await.unwrap();
+    s.create_ready_task(pid, "Page the on-call").await;
+    let line = tokio::time::timeout(std::time::Duration::from_secs(5), events.next_line())
+        .await
+        .unwrap()
+        .unwrap();
     assert!(
-        task["id"].is_string(),
-        "REST should succeed with no WS listeners"
+        line.contains("task.created") && line.contains("Page the on-call"),
+        "{line}"
+    );
+
+    let mut dashboard = Dashboard::new(pid, "Ops board");
+    dashboard.refresh(&cli).await;
+    assert!(
+        dashboard.status.starts_with("updated"),
+        "{}",
+        dashboard.status
     );
+    assert_eq!(dashboard.tasks.len(), 1);
+    assert!(dashboard.pulse["agents"].is_array());
+    let screen = dashboard.render(120, 30).join("\n");
+    assert!(screen.contains("TODO (1)"));
+
+    let bad_key = Client::new(&s.base_url, Some("nope".to_string()));
+    assert!(EventStream::connect(&bad_key, pid).await.is_err());
 }
 
-// WS Filter: agent_id "self" only receives events for own agent
 #[tokio::test]
-async fn test_ws_filter_agent_self() {
+async fn test_compression() {
+    use opengate::compression::{brotli, gunzip, gzip};
+
     let s = TestServer::start().await;
-    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+    let client = s.client();
+    // A gzipped request body is inflated before the handler sees it
+    let body = serde_json::to_vec(&json!({"name": "Compressed", "description": "x".repeat(2000)}))
+        .unwrap();
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "gzip")
+        .body(gzip(&body))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
 
-    // Subscribe with agent_id: "self" filter
-    let sub_msg = json!({
-        "type": "subscribe",
-        "events": ["task.*"],
-        "filter": {"agent_id": "self"}
-    })
-    .to_string();
-    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
-    let resp = recv_json(&mut stream, 2000).await.unwrap();
-    assert_eq!(resp["type"], "subscribed");
+    let resp = client
+        .get(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .header("Accept-Encoding", "br;q=0.5, gzip")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-encoding"], "gzip");
+    assert_eq!(resp.headers()["vary"], "accept-encoding");
+    let packed = resp.bytes().await.unwrap();
+    let projects: Value = serde_json::from_slice(&gunzip(&packed).unwrap()).unwrap();
+    assert!(packed.len() < serde_json::to_vec(&projects).unwrap().len());
+    assert_eq!(projects[0]["name"], "Compressed");
 
-    // Create a task (task.created has no assignee → agent_id is None) — should NOT match
-    let project = s.create_project("Agent Filter").await;
-    let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Agent Filter Task").await;
-    let task_id = task["id"].as_str().unwrap();
+    // brotli when preferred or tied; a body the size of this one is encoded off the worker
+    let description: String = (0..30_000).map(|i| format!("{} ", i % 97)).collect();
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .json(&json!({"name": "Large", "description": description}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let plain = client
+        .get(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert!(plain.len() > 64 * 1024);
+    for accept in ["br, gzip", "gzip;q=0, *"] {
+        let resp = client
+            .get(format!("{}/api/projects", s.base_url))
+            .header("Authorization", s.auth_header())
+            .header("Accept-Encoding", accept)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()["content-encoding"], "br");
+        let packed = resp.bytes().await.unwrap();
+        assert_eq!(packed, brotli(&plain));
+        assert!(packed.len() < gzip(&plain).len());
+    }
+    let resp = client
+        .get(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .header("Accept-Encoding", "gzip;q=0, br;q=0, *")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get("content-encoding").is_none());
 
-    // task.created has agent_id=None, won't match "self" filter
-    let no_event = recv_json(&mut stream, 500).await;
-    assert!(
-        no_event.is_none(),
-        "should not receive task.created with no assignee"
-    );
+    // Not asked for, or too small to bother
+    let resp = client
+        .get(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get("content-encoding").is_none());
+    let resp = client
+        .get(format!("{}/health", s.base_url))
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get("content-encoding").is_none());
 
-    // Move to todo so it can be claimed
-    s.client()
-        .patch(format!("{}/api/tasks/{}", s.base_url, task_id))
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
+        .header("Authorization", s.auth_header())
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "br")
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 415);
+    let resp = client
+        .post(format!("{}/api/projects", s.base_url))
         .header("Authorization", s.auth_header())
-        .json(&json!({ "status": "todo" }))
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "gzip")
+        .body(body)
         .send()
         .await
         .unwrap();
-    // Drain any status-change events
-    recv_json(&mut stream, 300).await;
+    assert_eq!(resp.status(), 400);
+}
 
-    // Claim the task → assigns agent, emits task.claimed with agent_id = self
-    s.client()
-        .post(format!("{}/api/tasks/{}/claim", s.base_url, task_id))
-        .header("Authorization", s.auth_header())
+// SSE: same subscription semantics as WS, delivered over a long-lived GET
+#[tokio::test]
+async fn test_sse_event_stream() {
+    let s = TestServer::start().await;
+    let project = s.create_project("SSE Stream").await;
+    let pid = project["id"].as_str().unwrap();
+    let other = s.create_project("SSE Other").await;
+
+    let resp = s
+        .client()
+        .get(format!("{}/api/events/stream?events=task.*", s.base_url))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 401);
 
-    // Should receive the claimed event (agent_id matches self)
-    let event = recv_json(&mut stream, 2000)
+    let mut resp = s
+        .client()
+        .get(format!(
+            "{}/api/events/stream?events=task.created&project_id={}",
+            s.base_url, pid
+        ))
+        .header("Authorization", s.auth_header())
+        .send()
         .await
-        .expect("expected claimed event");
-    assert_eq!(event["event"], "task.claimed");
-    assert_eq!(event["data"]["assignee_id"].as_str().unwrap(), s.agent_id());
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/event-stream"));
+
+    // Filtered out by project, then a match
+    s.create_task(other["id"].as_str().unwrap(), "Elsewhere")
+        .await;
+    let task = s.create_task(pid, "Streamed").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    let mut body = String::new();
+    while !body.contains("\n\n") {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(2), resp.chunk())
+            .await
+            .expect("expected an SSE event")
+            .unwrap()
+            .expect("stream ended");
+        body.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    let frame = body.split("\n\n").next().unwrap();
+    assert!(frame.contains("event: task.created"));
+    let data = frame
+        .lines()
+        .find_map(|l| l.strip_prefix("data: "))
+        .unwrap();
+    let data: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(data["id"], task_id);
 }
 
-// WS Filter: project_id only receives events for that project
+// CloudEvents: opt in per WS subscription or via Accept on the SSE stream
 #[tokio::test]
-async fn test_ws_filter_project() {
+async fn test_cloudevents_format() {
     let s = TestServer::start().await;
+    let project = s.create_project("CloudEvents").await;
+    let pid = project["id"].as_str().unwrap();
     let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
 
-    // Create two projects
-    let proj_a = s.create_project("Project A").await;
-    let pid_a = proj_a["id"].as_str().unwrap();
-    let proj_b = s.create_project("Project B").await;
-    let pid_b = proj_b["id"].as_str().unwrap();
+    let sub_msg = json!({"type": "subscribe", "events": ["task.created"], "format": "xml"});
+    sink.send(WsMessage::Text(sub_msg.to_string().into()))
+        .await
+        .unwrap();
+    let resp = recv_json(&mut stream, 2000).await.unwrap();
+    assert_eq!(resp["code"], "invalid_format");
 
-    // Subscribe with project_id filter for project A only
-    let sub_msg = json!({
-        "type": "subscribe",
-        "events": ["task.*"],
-        "filter": {"project_id": pid_a}
-    })
-    .to_string();
-    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
+    let sub_msg = json!({"type": "subscribe", "events": ["task.created"], "format": "cloudevents"});
+    sink.send(WsMessage::Text(sub_msg.to_string().into()))
+        .await
+        .unwrap();
     let resp = recv_json(&mut stream, 2000).await.unwrap();
     assert_eq!(resp["type"], "subscribed");
 
-    // Create task in project B → should NOT receive event
-    s.create_task(pid_b, "Task in B").await;
-    let no_event = recv_json(&mut stream, 500).await;
-    assert!(no_event.is_none(), "should not receive event for project B");
+    let mut sse = s
+        .client()
+        .get(format!(
+            "{}/api/events/stream?events=task.created",
+            s.base_url
+        ))
+        .header("Authorization", s.auth_header())
+        .header("Accept", "application/cloudevents+json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(sse.status(), 200);
 
-    // Create task in project A → SHOULD receive event
-    let task_a = s.create_task(pid_a, "Task in A").await;
-    let event = recv_json(&mut stream, 2000)
+    let task = s.create_task(pid, "Enveloped").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    let ce = recv_json(&mut stream, 2000)
         .await
-        .expect("expected event for project A");
-    assert_eq!(event["event"], "task.created");
+        .expect("expected a CloudEvent");
+    assert_eq!(ce["specversion"], "1.0");
+    assert_eq!(ce["type"], "task.created");
+    assert_eq!(ce["source"], format!("/opengate/projects/{}", pid));
+    assert_eq!(ce["subject"], task_id);
+    assert_eq!(ce["subscription"], resp["id"]);
+    assert_eq!(ce["data"]["id"], task_id);
+
+    let mut body = String::new();
+    while !body.contains("\n\n") {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(2), sse.chunk())
+            .await
+            .expect("expected an SSE event")
+            .unwrap()
+            .expect("stream ended");
+        body.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    let data = body.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+    let sse_ce: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(sse_ce["specversion"], "1.0");
+    // Same event, same id on every channel
+    assert_eq!(sse_ce["id"], ce["id"]);
+}
+
+#[test]
+fn test_event_outbox_keeps_events_until_acked() {
+    let conn = db::init_db(":memory:");
+    let first = db_ops::enqueue_outbox_event(&conn, "opengate.tasks", Some("p1"), "{\"a\":1}");
+    let second = db_ops::enqueue_outbox_event(&conn, "opengate.tasks", None, "{\"a\":2}");
+
+    db_ops::fail_outbox_events(&conn, &[first, second], "broker unreachable");
+    let pending = db_ops::list_outbox_events(&conn, 10);
     assert_eq!(
-        event["data"]["id"].as_str().unwrap(),
-        task_a["id"].as_str().unwrap()
+        pending.iter().map(|e| e.id).collect::<Vec<_>>(),
+        vec![first, second]
     );
+    assert_eq!(pending[0].attempts, 1);
+    assert_eq!(pending[0].event_key.as_deref(), Some("p1"));
+    assert_eq!(pending[0].last_error.as_deref(), Some("broker unreachable"));
+
+    db_ops::ack_outbox_events(&conn, &[first]);
+    let pending = db_ops::list_outbox_events(&conn, 10);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, second);
 }
 
-// WS Wildcard no match: subscribe task.*, trigger knowledge.updated → not received
-#[tokio::test]
-async fn test_ws_wildcard_no_match() {
-    let s = TestServer::start().await;
-    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+#[test]
+fn test_stored_events_enter_the_outbox_with_their_row() {
+    // Process-wide; only these routes are ever set in this binary
+    opengate::kafka::set_kafka(
+        opengate::kafka::parse_config("localhost", "task.*=opengate.tasks").unwrap(),
+    );
+    let conn = db::init_db(":memory:");
+    let payload = json!({ "task_title": "Ship it" });
+    let (event_id, _) =
+        db_ops::record_event(&conn, "task.updated", None, "p1", "agent", "a1", &payload);
+    db_ops::record_event(
+        &conn,
+        "knowledge.updated",
+        None,
+        "p1",
+        "agent",
+        "a1",
+        &json!({}),
+    );
 
-    // Subscribe to task.* only
-    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
-    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
-    let resp = recv_json(&mut stream, 2000).await.unwrap();
-    assert_eq!(resp["type"], "subscribed");
+    let pending = db_ops::list_outbox_events(&conn, 10);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].topic, "opengate.tasks");
+    assert_eq!(pending[0].event_key.as_deref(), Some("p1"));
+    let event: Value = serde_json::from_str(&pending[0].payload).unwrap();
+    assert_eq!(event["event_type"], "task.updated");
+    assert_eq!(event["event_id"], event_id);
+    assert_eq!(event["data"], payload);
+}
 
-    // Trigger a knowledge.updated event via REST
-    let project = s.create_project("Wildcard NoMatch").await;
-    let pid = project["id"].as_str().unwrap();
+// ===== MCP over HTTP =====
+
+async fn mcp_post(s: &TestServer, body: Value) -> reqwest::Response {
     s.client()
-        .put(format!(
-            "{}/api/projects/{}/knowledge/test-key",
-            s.base_url, pid
-        ))
+        .post(format!("{}/api/mcp", s.base_url))
         .header("Authorization", s.auth_header())
-        .json(&json!({"title": "Test Knowledge", "body": "Some content"}))
+        .header("Accept", "application/json, text/event-stream")
+        .json(&body)
         .send()
         .await
-        .unwrap();
-
-    // Should NOT receive the knowledge event on a task.* subscription
-    let no_event = recv_json(&mut stream, 500).await;
-    assert!(
-        no_event.is_none(),
-        "task.* should not match knowledge.updated"
-    );
+        .unwrap()
 }
 
-// WS Exact match: subscribe task.created, receive only task.created
 #[tokio::test]
-async fn test_ws_exact_match() {
+async fn test_mcp_http_transport() {
     let s = TestServer::start().await;
-    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
-
-    // Subscribe to exact pattern task.created
-    let sub_msg = json!({"type": "subscribe", "events": ["task.created"]}).to_string();
-    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
-    let resp = recv_json(&mut stream, 2000).await.unwrap();
-    assert_eq!(resp["type"], "subscribed");
-
-    // Create task → should receive task.created
-    let project = s.create_project("Exact Match").await;
+    let project = s.create_project("MCP").await;
     let pid = project["id"].as_str().unwrap();
-    let task = s.create_task(pid, "Exact Match Task").await;
+    let task = s.create_task(pid, "Reachable over HTTP").await;
     let task_id = task["id"].as_str().unwrap();
 
-    let event = recv_json(&mut stream, 2000)
+    let resp = mcp_post(
+        &s,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+               "params": {"protocolVersion": "2025-03-26", "capabilities": {}}}),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let init: Value = resp.json().await.unwrap();
+    assert_eq!(init["id"], 1);
+    assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+    assert!(init["result"]["capabilities"]["resources"].is_object());
+
+    // Notifications are accepted without a body
+    let resp = mcp_post(
+        &s,
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+    )
+    .await;
+    assert_eq!(resp.status(), 202);
+
+    // Batches answer every request, as the authenticated agent
+    let resp = mcp_post(
+        &s,
+        json!([
+            {"jsonrpc": "2.0", "id": 2, "method": "tools/call",
+             "params": {"name": "get_task", "arguments": {"id": task_id}}},
+            {"jsonrpc": "2.0", "id": 3, "method": "resources/read",
+             "params": {"uri": format!("opengate://pulse/{}", pid)}},
+            {"jsonrpc": "2.0", "id": 4, "method": "resources/read",
+             "params": {"uri": "opengate://task/missing"}}
+        ]),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let replies: Vec<Value> = resp.json().await.unwrap();
+    assert_eq!(replies.len(), 3);
+    let text = replies[0]["result"]["content"][0]["text"].as_str().unwrap();
+    let fetched: Value = serde_json::from_str(text).unwrap();
+    assert_eq!(fetched["title"], "Reachable over HTTP");
+    let pulse_text = replies[1]["result"]["contents"][0]["text"]
+        .as_str()
+        .unwrap();
+    let pulse: Value = serde_json::from_str(pulse_text).unwrap();
+    assert!(pulse["active_tasks"].is_array());
+    assert_eq!(replies[2]["error"]["code"], -32002);
+
+    // SSE-only clients get the reply as one event
+    let resp = s
+        .client()
+        .post(format!("{}/api/mcp", s.base_url))
+        .header("Authorization", s.auth_header())
+        .header("Accept", "text/event-stream")
+        .json(&json!({"jsonrpc": "2.0", "id": 5, "method": "ping"}))
+        .send()
         .await
-        .expect("expected task.created");
-    assert_eq!(event["event"], "task.created");
-    assert_eq!(event["data"]["id"].as_str().unwrap(), task_id);
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/event-stream"));
+    let body = resp.text().await.unwrap();
+    let data = body.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(data).unwrap()["id"], 5);
+
+    let resp = s
+        .client()
+        .post(format!("{}/api/mcp", s.base_url))
+        .json(&json!({"jsonrpc": "2.0", "id": 6, "method": "tools/list"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(resp.headers()["www-authenticate"], "Bearer");
 }
 
-// WS Unsubscribe: receive event, unsubscribe, no longer receive
 #[tokio::test]
-async fn test_ws_unsubscribe() {
+async fn test_mcp_http_pushes_notifications() {
     let s = TestServer::start().await;
-    let (mut sink, mut stream) = ws_auth(&s.ws_url(), &s.api_key).await;
+    let mut stream = s
+        .client()
+        .get(format!("{}/api/mcp", s.base_url))
+        .header("Authorization", s.auth_header())
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stream.status(), 200);
 
-    // Subscribe
-    let sub_msg = json!({"type": "subscribe", "events": ["task.*"]}).to_string();
-    sink.send(WsMessage::Text(sub_msg.into())).await.unwrap();
-    let resp = recv_json(&mut stream, 2000).await.unwrap();
-    assert_eq!(resp["type"], "subscribed");
-    let sub_id = resp["id"].as_str().unwrap().to_string();
+    let task_id = assign_task_to_self(&s, "mcp-push").await;
 
-    // Create task → should receive event
-    let project = s.create_project("Unsubscribe Test").await;
-    let pid = project["id"].as_str().unwrap();
-    s.create_task(pid, "Before Unsub").await;
-    let event = recv_json(&mut stream, 2000)
-        .await
-        .expect("expected event before unsub");
-    assert_eq!(event["event"], "task.created");
+    let mut body = String::new();
+    while !body.contains("\n\n") {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), stream.chunk())
+            .await
+            .expect("expected a pushed notification")
+            .unwrap()
+            .expect("stream ended");
+        body.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(body.starts_with("event: message"));
+    let data = body.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+    let message: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(message["method"], "notifications/message");
+    assert!(message.get("id").is_none());
+    let notification = &message["params"]["data"]["notification"];
+    assert_eq!(notification["task_id"], task_id.as_str());
+    assert_eq!(notification["agent_id"], s.agent_id());
+}
 
-    // Unsubscribe
-    let unsub_msg = json!({"type": "unsubscribe", "id": sub_id}).to_string();
-    sink.send(WsMessage::Text(unsub_msg.into())).await.unwrap();
-    let resp = recv_json(&mut stream, 2000).await.unwrap();
-    assert_eq!(resp["type"], "unsubscribed");
+#[tokio::test]
+async fn test_prometheus_metrics() {
+    let s = TestServer::start().await;
+    let project = s.create_project("Metrics").await;
+    let project_id = project["id"].as_str().unwrap();
+    s.create_task(project_id, "Counted").await;
+    let resp = s
+        .client()
+        .get(format!("{}/api/projects/{}", s.base_url, project_id))
+        .header("Authorization", s.auth_header())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
 
-    // Create another task → should NOT receive event
-    s.create_task(pid, "After Unsub").await;
-    let no_event = recv_json(&mut stream, 500).await;
-    assert!(
-        no_event.is_none(),
-        "should not receive events after unsubscribe"
-    );
+    let resp = s
+        .client()
+        .get(format!("{}/metrics", s.base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = resp.text().await.unwrap();
+    // Routes are labelled by pattern, not by id
+    assert!(body.contains(
+        "opengate_http_requests_total{method=\"GET\",route=\"/api/projects/:id\",status=\"200\"}"
+    ));
+    assert!(!body.contains(project_id));
+    assert!(body.contains(
+        "opengate_http_request_duration_seconds_bucket{method=\"GET\",route=\"/api/projects/:id\",le=\"+Inf\"}"
+    ));
+    assert!(body
+        .lines()
+        .any(|l| l.starts_with("opengate_tasks{status=") && !l.ends_with(" 0")));
+    assert!(body.contains("opengate_webhook_deliveries_total{kind=\"task\",result=\"delivered\"}"));
+    assert!(body.contains("opengate_event_subscribers{transport=\"ws\"}"));
 }
 
 // ===== Tenant isolation tests =====
@@ -4495,3 +12958,81 @@ fn test_tenant_isolation_tasks() {
     let all = db_ops::list_tasks(&conn, None, &filters);
     assert_eq!(all.len(), 2, "OSS mode should see all tasks");
 }
+
+#[test]
+fn test_tenant_isolation_events_and_agents() {
+    use opengate::db;
+    use opengate::db_ops;
+    use opengate_models::{CreateAgent, CreateProject};
+
+    let conn = db::init_db(":memory:");
+    let project = |name: &str| CreateProject {
+        name: name.to_string(),
+        description: None,
+        repo_url: None,
+        default_branch: None,
+        join_mode: None,
+        cta_enabled: None,
+        is_public: None,
+    };
+    let proj_a = db_ops::create_project(&conn, Some("tenant_a"), &project("A"), "sys");
+    let proj_b = db_ops::create_project(&conn, Some("tenant_b"), &project("B"), "sys");
+    let payload = serde_json::json!({});
+    db_ops::emit_event(
+        &conn,
+        "task.created",
+        None,
+        &proj_a.id,
+        "system",
+        "system",
+        &payload,
+    );
+    db_ops::emit_event(
+        &conn,
+        "task.created",
+        None,
+        &proj_b.id,
+        "system",
+        "system",
+        &payload,
+    );
+
+    let events_a = db_ops::list_events_after(&conn, Some("tenant_a"), 0, 100);
+    assert_eq!(events_a.len(), 1);
+    assert_eq!(events_a[0].project_id, proj_a.id);
+    assert_eq!(db_ops::list_events_after(&conn, None, 0, 100).len(), 2);
+
+    let (agent_a, _) = db_ops::create_agent(
+        &conn,
+        &CreateAgent {
+            owner_id: Some("tenant_a".to_string()),
+            ..CreateAgent::new("agent-a")
+        },
+    );
+    let (unowned, _) = db_ops::create_agent(&conn, &CreateAgent::new("unowned"));
+    assert!(db_ops::agent_in_tenant(
+        &conn,
+        Some("tenant_a"),
+        &agent_a.id
+    ));
+    assert!(!db_ops::agent_in_tenant(
+        &conn,
+        Some("tenant_b"),
+        &agent_a.id
+    ));
+    assert!(
+        !db_ops::agent_in_tenant(&conn, Some("tenant_a"), &unowned.id),
+        "unowned agents belong to no tenant"
+    );
+    assert!(db_ops::agent_in_tenant(&conn, None, &agent_a.id));
+    assert!(db_ops::project_in_tenant(
+        &conn,
+        Some("tenant_b"),
+        &proj_b.id
+    ));
+    assert!(!db_ops::project_in_tenant(
+        &conn,
+        Some("tenant_b"),
+        &proj_a.id
+    ));
+}